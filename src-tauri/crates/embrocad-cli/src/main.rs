@@ -0,0 +1,318 @@
+// main.rs - Command line access to the design readers, writers and renderers
+
+use clap::{Parser, Subcommand, ValueEnum};
+use embrocad_core::dst::{
+    Bounds, ColorBlock, ParseOptions, ParseWarning, PatternMetadata, PatternStatistics,
+};
+use embrocad_core::export::{
+    encode_design, fit_size, render_png, write_svg, ExportFormat, ExportOptions, PngOptions,
+    RenderMode, SvgOptions,
+};
+use embrocad_core::format::{read_design, DesignFormat, LoadedDesign};
+use embrocad_core::machines::{find_machine, validate_for_machine, MachineViolation, MACHINES};
+use embrocad_core::units::{DesignStats, Unit};
+use serde::Serialize;
+use std::path::Path;
+use std::process::ExitCode;
+
+/// Exit status when a design breaks a machine's limits
+const EXIT_VIOLATIONS: u8 = 1;
+
+/// Exit status when a file cannot be read or written; clap uses it for
+/// usage errors too
+const EXIT_ERROR: u8 = 2;
+
+#[derive(Parser)]
+#[command(
+    name = "embrocad-cli",
+    version,
+    about = "Inspect, convert, render and check embroidery designs"
+)]
+struct Cli {
+    /// Refuse damaged files rather than read what can be read
+    #[arg(long, global = true)]
+    strict: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print a design's metadata and statistics
+    Info {
+        file: String,
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+    /// Save a design in another format, chosen by the output extension
+    Convert { input: String, output: String },
+    /// Draw a design to a .png or .svg file
+    Render {
+        input: String,
+        output: String,
+        /// Longest side of a PNG, in pixels
+        #[arg(long, default_value_t = 1024)]
+        size: u32,
+        #[arg(long, value_enum, default_value_t = Mode::Flat)]
+        mode: Mode,
+        /// Fill behind the design as #rrggbb; transparent when unset
+        #[arg(long, value_parser = parse_rgb)]
+        background: Option<[u8; 3]>,
+        /// Draw jumps and trims as dashed lines (SVG only)
+        #[arg(long)]
+        jumps: bool,
+    },
+    /// Check a design against a machine's limits; exits 1 if it breaks any
+    Validate {
+        input: String,
+        /// Machine id, e.g. brother_pe800
+        #[arg(long)]
+        machine: String,
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Mode {
+    Flat,
+    Realistic,
+}
+
+impl From<Mode> for RenderMode {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Flat => RenderMode::Flat,
+            Mode::Realistic => RenderMode::Realistic,
+        }
+    }
+}
+
+/// `info --json` output: everything about a design except its stitches
+#[derive(Serialize)]
+struct DesignInfo<'a> {
+    file: &'a str,
+    format: DesignFormat,
+    metadata: &'a PatternMetadata,
+    bounds: Option<&'a Bounds>,
+    statistics: &'a PatternStatistics,
+    color_blocks: &'a [ColorBlock],
+    warnings: &'a [ParseWarning],
+}
+
+/// `validate --json` output
+#[derive(Serialize)]
+struct ValidationReport<'a> {
+    machine: &'a str,
+    violations: &'a [MachineViolation],
+}
+
+/// Parse a `#rrggbb` or `rrggbb` color
+fn parse_rgb(value: &str) -> Result<[u8; 3], String> {
+    let hex = value.trim_start_matches('#');
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+    };
+    match (hex.len(), channel(0), channel(2), channel(4)) {
+        (6, Some(r), Some(g), Some(b)) => Ok([r, g, b]),
+        _ => Err(format!("expected a color like #ff8800, not \"{}\"", value)),
+    }
+}
+
+/// Lowercase extension of `path`, or an empty string
+fn extension(path: &str) -> String {
+    Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase()
+}
+
+fn write_file(path: &str, data: &[u8]) -> Result<(), String> {
+    std::fs::write(path, data).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+fn print_info(file: &str, design: &LoadedDesign) {
+    let pattern = &design.pattern;
+    let stats = &pattern.statistics;
+    let label = pattern.metadata.label.as_deref().unwrap_or("-");
+    println!("File:         {}", file);
+    println!("Format:       {}", design.format.name());
+    println!("Label:        {}", label);
+    println!(
+        "Size:         {}",
+        DesignStats::new(pattern, Unit::Mm).size_label
+    );
+    println!("Stitches:     {}", stats.real_stitch_count);
+    println!("Jumps:        {}", stats.jump_count);
+    println!("Trims:        {}", stats.trim_count);
+    println!("Colors:       {}", pattern.color_blocks.len());
+    if stats.sequin_count > 0 {
+        println!("Sequins:      {}", stats.sequin_count);
+    }
+    println!(
+        "Thread:       {:.2} m top, {:.2} m bobbin",
+        stats.top_thread_m, stats.bobbin_thread_m
+    );
+    println!("Run time:     {:.1} min", stats.estimated_time_minutes);
+    for warning in &pattern.warnings {
+        println!("Warning:      {}", warning);
+    }
+}
+
+/// A violation as one line of text
+fn describe(violation: &MachineViolation) -> String {
+    match violation {
+        MachineViolation::JumpTooLong {
+            index,
+            length_mm,
+            max_mm,
+        } => format!(
+            "jump at record {} is {:.1} mm, longer than {:.1} mm",
+            index, length_mm, max_mm
+        ),
+        MachineViolation::StitchTooLong {
+            index,
+            length_mm,
+            max_mm,
+        } => format!(
+            "stitch at record {} is {:.1} mm, longer than {:.1} mm",
+            index, length_mm, max_mm
+        ),
+        MachineViolation::TooManyColorChanges { count, max } => {
+            format!("{} color changes, more than {}", count, max)
+        }
+        MachineViolation::TooManyStitches { count, max } => {
+            format!("{} stitches, more than {}", count, max)
+        }
+        MachineViolation::ExceedsField {
+            width_mm,
+            height_mm,
+            field_width_mm,
+            field_height_mm,
+        } => format!(
+            "design is {:.1} x {:.1} mm, larger than the {:.1} x {:.1} mm field",
+            width_mm, height_mm, field_width_mm, field_height_mm
+        ),
+        MachineViolation::MoreColorsThanNeedles { colors, needles } => {
+            format!("{} thread colors on a {}-needle machine", colors, needles)
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<ExitCode, String> {
+    let options = ParseOptions {
+        strict: cli.strict,
+        ..ParseOptions::default()
+    };
+    match cli.command {
+        Command::Info { file, json } => {
+            let design = read_design(&file, &options)?;
+            if json {
+                let pattern = &design.pattern;
+                let info = DesignInfo {
+                    file: &file,
+                    format: design.format,
+                    metadata: &pattern.metadata,
+                    bounds: pattern.bounds.as_ref(),
+                    statistics: &pattern.statistics,
+                    color_blocks: &pattern.color_blocks,
+                    warnings: &pattern.warnings,
+                };
+                let text = serde_json::to_string_pretty(&info).map_err(|e| e.to_string())?;
+                println!("{}", text);
+            } else {
+                print_info(&file, &design);
+            }
+        }
+        Command::Convert { input, output } => {
+            let format = ExportFormat::from_extension(&extension(&output))
+                .ok_or_else(|| format!("Unknown output format: {}", output))?;
+            let design = read_design(&input, &options)?;
+            let data = encode_design(&design.pattern, format, &ExportOptions::default())?;
+            write_file(&output, &data)?;
+        }
+        Command::Render {
+            input,
+            output,
+            size,
+            mode,
+            background,
+            jumps,
+        } => {
+            let format = extension(&output);
+            if format != "png" && format != "svg" {
+                return Err(format!("Render to a .png or .svg file, not {}", output));
+            }
+            if size == 0 {
+                return Err("Size must be at least 1 pixel".to_string());
+            }
+            let design = read_design(&input, &options)?;
+            let data = if format == "png" {
+                let (width, height) = fit_size(&design.pattern, size);
+                let png = PngOptions {
+                    mode: mode.into(),
+                    background,
+                    ..PngOptions::default()
+                };
+                render_png(&design.pattern, width, height, &png).map_err(|e| e.to_string())?
+            } else {
+                let svg = SvgOptions {
+                    include_jumps: jumps,
+                    background,
+                    ..SvgOptions::default()
+                };
+                write_svg(&design.pattern, &svg).into_bytes()
+            };
+            write_file(&output, &data)?;
+        }
+        Command::Validate {
+            input,
+            machine,
+            json,
+        } => {
+            let profile = find_machine(&machine).ok_or_else(|| {
+                let known: Vec<&str> = MACHINES.iter().map(|m| m.id.as_ref()).collect();
+                format!(
+                    "Unknown machine {}; known machines: {}",
+                    machine,
+                    known.join(", ")
+                )
+            })?;
+            let design = read_design(&input, &options)?;
+            let violations = validate_for_machine(&design.pattern, profile);
+            if json {
+                let report = ValidationReport {
+                    machine: &profile.id,
+                    violations: &violations,
+                };
+                let text = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+                println!("{}", text);
+            } else if violations.is_empty() {
+                println!("{} fits the {}", input, profile.name);
+            } else {
+                for violation in &violations {
+                    println!("{}", describe(violation));
+                }
+            }
+            if !violations.is_empty() {
+                return Ok(ExitCode::from(EXIT_VIOLATIONS));
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("embrocad-cli: {}", e);
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
@@ -0,0 +1,161 @@
+// cli.rs - The embrocad-cli binary run over the core fixture files
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use std::path::PathBuf;
+
+fn fixture(name: &str) -> String {
+    format!(
+        "{}/../embrocad-core/tests/fixtures/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    )
+}
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("embrocad-cli-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn cli() -> Command {
+    Command::cargo_bin("embrocad-cli").unwrap()
+}
+
+#[test]
+fn test_info_text_and_json() {
+    cli()
+        .args(["info", &fixture("dst/sequins.dst")])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Format:       DST"))
+        .stdout(predicate::str::contains("Stitches:     7"))
+        .stdout(predicate::str::contains("Sequins:      9"));
+
+    let output = cli()
+        .args(["info", "--json", &fixture("legacy/design.exp")])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let info: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(info["format"], "exp");
+    assert!(info["statistics"]["real_stitch_count"].as_u64().unwrap() > 0);
+    assert!(info["bounds"].is_object());
+    assert!(info.get("stitches").is_none());
+}
+
+#[test]
+fn test_convert_by_extension() {
+    let dir = scratch("convert");
+    let output = dir.join("design.dst");
+    cli()
+        .args(["convert", &fixture("legacy/design.t01")])
+        .arg(&output)
+        .assert()
+        .success();
+    let data = fs::read(&output).unwrap();
+    assert_eq!(&data[..3], b"LA:");
+
+    // The converted file reads back as the same design
+    let info = |path: &str| {
+        let output = cli().args(["info", "--json", path]).output().unwrap();
+        serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap()
+    };
+    let original = info(&fixture("legacy/design.t01"));
+    let converted = info(output.to_str().unwrap());
+    assert_eq!(converted["format"], "dst");
+    assert_eq!(converted["bounds"], original["bounds"]);
+    assert_eq!(
+        converted["statistics"]["real_stitch_count"],
+        original["statistics"]["real_stitch_count"]
+    );
+
+    cli()
+        .args(["convert", &fixture("dst/sequins.dst")])
+        .arg(dir.join("design.vp3"))
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("Unknown output format"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_render_png_and_svg() {
+    let dir = scratch("render");
+    let png = dir.join("design.png");
+    cli()
+        .args(["render", &fixture("dst/sequins.dst")])
+        .arg(&png)
+        .args([
+            "--size",
+            "200",
+            "--mode",
+            "realistic",
+            "--background",
+            "#ffffff",
+        ])
+        .assert()
+        .success();
+    let data = fs::read(&png).unwrap();
+    assert_eq!(&data[..8], b"\x89PNG\r\n\x1a\n");
+    // Width and height from the IHDR chunk; the longest side is the size
+    let width = u32::from_be_bytes(data[16..20].try_into().unwrap());
+    let height = u32::from_be_bytes(data[20..24].try_into().unwrap());
+    assert_eq!(width.max(height), 200);
+
+    let svg = dir.join("design.svg");
+    cli()
+        .args(["render", "--jumps", &fixture("dst/sequins.dst")])
+        .arg(&svg)
+        .assert()
+        .success();
+    assert!(fs::read_to_string(&svg).unwrap().contains("<svg"));
+
+    cli()
+        .args(["render", &fixture("dst/sequins.dst")])
+        .arg(dir.join("design.dst"))
+        .assert()
+        .code(2);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_validate_against_machine() {
+    cli()
+        .args(["validate", &fixture("dst/sequins.dst")])
+        .args(["--machine", "brother_pe800"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("fits the Brother PE800"));
+
+    // One 20mm stitch, over the PE800's 12.7mm limit
+    let dir = scratch("validate");
+    let long = dir.join("long.csv");
+    fs::write(&long, "0,0,STITCH\n200,0,STITCH\n200,0,END\n").unwrap();
+    cli()
+        .arg("validate")
+        .arg(&long)
+        .args(["--machine", "brother_pe800"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("stitch at record 1 is 20.0 mm"));
+    let output = cli()
+        .arg("validate")
+        .arg(&long)
+        .args(["--machine", "brother_pe800", "--json"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["violations"][0]["kind"], "stitch_too_long");
+
+    cli()
+        .args(["validate", &fixture("dst/sequins.dst")])
+        .args(["--machine", "sewing_robot"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("brother_pe800"));
+    let _ = fs::remove_dir_all(&dir);
+}
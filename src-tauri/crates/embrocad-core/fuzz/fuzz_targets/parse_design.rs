@@ -0,0 +1,27 @@
+// parse_design.rs - Fuzz every design parser through the format dispatcher
+
+#![no_main]
+
+use embrocad_core::dst::ParseOptions;
+use embrocad_core::format::parse_design;
+use embrocad_core::progress::LoadMonitor;
+use libfuzzer_sys::fuzz_target;
+
+/// Extensions the first input byte chooses between, so headerless formats
+/// are reached too; signatures in the data still take precedence
+const EXTENSIONS: [&str; 20] = [
+    "dst", "dsb", "dsz", "pes", "pec", "exp", "jef", "vp3", "xxx", "hus", "vip", "sew", "pcs",
+    "t01", "t03", "t09", "10o", "ksm", "csv", "svg",
+];
+
+fuzz_target!(|input: &[u8]| {
+    let Some((&choice, data)) = input.split_first() else {
+        return;
+    };
+    let path = format!("fuzz.{}", EXTENSIONS[choice as usize % EXTENSIONS.len()]);
+    let options = ParseOptions {
+        max_stitches: Some(100_000),
+        ..ParseOptions::default()
+    };
+    let _ = parse_design(&path, data, &options, &mut LoadMonitor::none());
+});
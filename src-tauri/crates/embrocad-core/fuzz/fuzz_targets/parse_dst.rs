@@ -0,0 +1,40 @@
+// parse_dst.rs - Fuzz the DST-family decoder in every stitch encoding
+
+#![no_main]
+
+use embrocad_core::dst::{
+    parse_dst, parse_dst_multi, parse_dst_variant, read_dst_info, DstVariant, ParseOptions,
+    MAX_COORDINATE,
+};
+use embrocad_core::progress::LoadMonitor;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let options = ParseOptions {
+        max_stitches: Some(100_000),
+        ..ParseOptions::default()
+    };
+    // Reading further designs never changes the first
+    if let Ok(pattern) = parse_dst(data, &options) {
+        let patterns = parse_dst_multi(data, &options).expect("the single parse succeeded");
+        assert_eq!(patterns[0].stitches, pattern.stitches);
+    }
+
+    for variant in [DstVariant::Tajima, DstVariant::Barudan, DstVariant::Zsk] {
+        let Ok(pattern) = parse_dst_variant(data, variant, &options) else {
+            continue;
+        };
+        // The limit counts records; a missing End is made up after them
+        assert!(pattern.stitches.len() <= 100_001);
+        if let Some(bounds) = &pattern.bounds {
+            assert!(bounds.min_x.abs().max(bounds.max_x.abs()) <= MAX_COORDINATE);
+            assert!(bounds.min_y.abs().max(bounds.max_y.abs()) <= MAX_COORDINATE);
+        }
+
+        // Streaming the same bytes must agree with the full parse
+        let info = read_dst_info(data, variant, &options, &mut LoadMonitor::none())
+            .expect("the full parse succeeded");
+        assert_eq!(info.stitch_count, pattern.stitches.len());
+        assert_eq!(info.warnings, pattern.warnings);
+    }
+});
@@ -0,0 +1,236 @@
+// compare.rs - Differences between two versions of a design
+
+use crate::dst::{Bounds, Pattern, Stitch, StitchCommand, UNITS_PER_MM};
+use crate::export::block_rgb;
+use crate::view::StitchIndex;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// Penetrations closer than this to one in the other design count as unchanged
+pub const DEFAULT_MATCH_TOLERANCE_MM: f64 = 0.5;
+
+/// Stitch counts of one color block position in both designs
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct BlockCountDiff {
+    pub block: usize,
+    /// None when the design has no block at this position
+    pub stitches_a: Option<u32>,
+    pub stitches_b: Option<u32>,
+    pub delta: i64,
+}
+
+/// A color block position whose thread differs between the designs
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ColorDiff {
+    pub block: usize,
+    pub rgb_a: Option<[u8; 3]>,
+    pub rgb_b: Option<[u8; 3]>,
+}
+
+/// What changed from design A to design B
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct DesignDiff {
+    pub stitches_a: u32,
+    pub stitches_b: u32,
+    pub stitch_delta: i64,
+    pub blocks: Vec<BlockCountDiff>,
+    pub bounds_a: Option<Bounds>,
+    pub bounds_b: Option<Bounds>,
+    pub width_delta_mm: f64,
+    pub height_delta_mm: f64,
+    /// Positions in the color sequence whose thread changed, was added or removed
+    pub colors: Vec<ColorDiff>,
+    pub tolerance_mm: f64,
+    /// Percentage of A's penetrations with none in B within the tolerance
+    pub removed_percent: f64,
+    /// Percentage of B's penetrations with none in A within the tolerance
+    pub added_percent: f64,
+}
+
+/// Index of the Stitch records alone, so jumps and trims never match
+fn penetration_index(pattern: &Pattern) -> StitchIndex {
+    let mut penetrations = Pattern::new();
+    penetrations.stitches = penetrations_of(pattern).cloned().collect();
+    StitchIndex::new(&penetrations)
+}
+
+fn penetrations_of(pattern: &Pattern) -> impl Iterator<Item = &Stitch> {
+    pattern
+        .stitches
+        .iter()
+        .filter(|s| s.command == StitchCommand::Stitch)
+}
+
+/// Percentage of the penetrations of `from` with no penetration of `to`
+/// within `tolerance` units
+fn unmatched_percent(from: &Pattern, to: &StitchIndex, tolerance: f64) -> f64 {
+    let (mut total, mut unmatched) = (0usize, 0usize);
+    for stitch in penetrations_of(from) {
+        total += 1;
+        if to.nearest(stitch.x, stitch.y, tolerance).is_none() {
+            unmatched += 1;
+        }
+    }
+    if total == 0 {
+        0.0
+    } else {
+        unmatched as f64 * 100.0 / total as f64
+    }
+}
+
+fn size_mm(bounds: Option<&Bounds>) -> (f64, f64) {
+    bounds.map_or((0.0, 0.0), |b| {
+        (b.width() / UNITS_PER_MM, b.height() / UNITS_PER_MM)
+    })
+}
+
+/// Compare design `b` against design `a`
+///
+/// Blocks and colors are compared by position in the color sequence. The
+/// geometric difference looks up every penetration of each design in a
+/// spatial grid of the other's.
+pub fn compare_patterns(a: &Pattern, b: &Pattern, tolerance_mm: f64) -> DesignDiff {
+    let block_count = a.color_blocks.len().max(b.color_blocks.len());
+    let blocks = (0..block_count)
+        .map(|block| {
+            let stitches_a = a.color_blocks.get(block).map(|b| b.stitch_count);
+            let stitches_b = b.color_blocks.get(block).map(|b| b.stitch_count);
+            BlockCountDiff {
+                block,
+                stitches_a,
+                stitches_b,
+                delta: stitches_b.unwrap_or(0) as i64 - stitches_a.unwrap_or(0) as i64,
+            }
+        })
+        .collect();
+
+    let colors = (0..block_count)
+        .filter_map(|block| {
+            let rgb_a = (block < a.color_blocks.len()).then(|| block_rgb(a, block));
+            let rgb_b = (block < b.color_blocks.len()).then(|| block_rgb(b, block));
+            (rgb_a != rgb_b).then_some(ColorDiff {
+                block,
+                rgb_a,
+                rgb_b,
+            })
+        })
+        .collect();
+
+    let (width_a, height_a) = size_mm(a.bounds.as_ref());
+    let (width_b, height_b) = size_mm(b.bounds.as_ref());
+    let tolerance = tolerance_mm.max(0.0) * UNITS_PER_MM;
+    let (stitches_a, stitches_b) = (
+        a.statistics.real_stitch_count,
+        b.statistics.real_stitch_count,
+    );
+
+    DesignDiff {
+        stitches_a,
+        stitches_b,
+        stitch_delta: stitches_b as i64 - stitches_a as i64,
+        blocks,
+        bounds_a: a.bounds.clone(),
+        bounds_b: b.bounds.clone(),
+        width_delta_mm: width_b - width_a,
+        height_delta_mm: height_b - height_a,
+        colors,
+        tolerance_mm,
+        removed_percent: unmatched_percent(a, &penetration_index(b), tolerance),
+        added_percent: unmatched_percent(b, &penetration_index(a), tolerance),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::ThreadColor;
+
+    /// Three blocks of ten stitches on a 2mm grid, red, green and blue
+    fn design() -> Pattern {
+        let mut pattern = Pattern::new();
+        for block in 0..3 {
+            if block > 0 {
+                let last = pattern.stitches.last().unwrap().clone();
+                pattern.add_stitch(last.x, last.y, StitchCommand::ColorChange);
+            }
+            for i in 0..10 {
+                let x = (block * 10 + i) as f64 * 20.0;
+                pattern.add_stitch(x, (i % 2) as f64 * 20.0, StitchCommand::Stitch);
+            }
+        }
+        pattern.add_stitch(580.0, 20.0, StitchCommand::End);
+        pattern.metadata.thread_colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255]]
+            .into_iter()
+            .map(ThreadColor::new)
+            .collect();
+        pattern.calculate_bounds();
+        pattern.calculate_statistics();
+        pattern.calculate_color_blocks();
+        pattern
+    }
+
+    #[test]
+    fn test_identical_designs() {
+        let diff = compare_patterns(&design(), &design(), DEFAULT_MATCH_TOLERANCE_MM);
+        assert_eq!(diff.stitch_delta, 0);
+        assert!(diff.blocks.iter().all(|b| b.delta == 0));
+        assert!(diff.colors.is_empty());
+        assert_eq!((diff.removed_percent, diff.added_percent), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_translated_copy() {
+        let original = design();
+        let mut moved = design();
+        moved.translate(30.0, 0.0);
+
+        let diff = compare_patterns(&original, &moved, DEFAULT_MATCH_TOLERANCE_MM);
+        assert_eq!(diff.stitch_delta, 0);
+        assert!(diff.colors.is_empty());
+        assert_eq!(diff.width_delta_mm, 0.0);
+        assert_eq!(diff.bounds_b.unwrap().min_x, 30.0);
+        // Shifted 3mm along a 2mm grid, every penetration is 1mm or more
+        // from the nearest old one
+        assert_eq!((diff.removed_percent, diff.added_percent), (100.0, 100.0));
+
+        let loose = compare_patterns(&original, &moved, 3.5);
+        assert_eq!((loose.removed_percent, loose.added_percent), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_block_deleted_copy() {
+        let original = design();
+        let mut trimmed = design();
+        trimmed.delete_block(1).unwrap();
+
+        let diff = compare_patterns(&original, &trimmed, DEFAULT_MATCH_TOLERANCE_MM);
+        assert_eq!(diff.stitch_delta, -10);
+        let deltas: Vec<_> = diff
+            .blocks
+            .iter()
+            .map(|b| (b.stitches_b, b.delta))
+            .collect();
+        assert_eq!(deltas, vec![(Some(10), 0), (Some(10), 0), (None, -10)]);
+        assert_eq!(
+            diff.colors,
+            vec![
+                ColorDiff {
+                    block: 1,
+                    rgb_a: Some([0, 255, 0]),
+                    rgb_b: Some([0, 0, 255]),
+                },
+                ColorDiff {
+                    block: 2,
+                    rgb_a: Some([0, 0, 255]),
+                    rgb_b: None,
+                },
+            ]
+        );
+        assert!((diff.removed_percent - 100.0 / 3.0).abs() < 1e-9);
+        assert_eq!(diff.added_percent, 0.0);
+        assert_eq!(diff.width_delta_mm, 0.0);
+    }
+}
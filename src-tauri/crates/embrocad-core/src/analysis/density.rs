@@ -0,0 +1,330 @@
+// density.rs - Needle penetration density grid for thread-buildup warnings
+
+use crate::dst::{feed_all, Bounds, Pattern, Stitch, StitchCommand, StitchSink, UNITS_PER_MM};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Penetrations per mm² above which a cell is flagged as a warning
+pub const DEFAULT_DENSITY_WARNING: f64 = 1.5;
+
+/// Penetrations per mm² above which a cell is flagged as critical
+pub const DEFAULT_DENSITY_CRITICAL: f64 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum DensityLevel {
+    Warning,
+    Critical,
+}
+
+/// One over-dense grid cell; `x`/`y` are the cell's top-left corner in 0.1mm units
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DensityCell {
+    pub x: f64,
+    pub y: f64,
+    /// Penetrations per mm²
+    pub density: f64,
+    pub level: DensityLevel,
+}
+
+/// Cells over the warning threshold, for the frontend heatmap overlay
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DensityMap {
+    pub cell_size_mm: f64,
+    pub columns: usize,
+    pub rows: usize,
+    pub max_density: f64,
+    pub cells: Vec<DensityCell>,
+}
+
+/// Penetration counts binned while stitches arrive, over bounds known
+/// beforehand (a stitch outside them lands in the nearest edge cell)
+#[derive(Debug)]
+pub struct DensityGrid {
+    bounds: Option<Bounds>,
+    cell_size_mm: f64,
+    columns: usize,
+    rows: usize,
+    counts: Vec<u32>,
+    /// With `indexed`, the records penetrating each cell, in order
+    penetrations: Option<Vec<Vec<usize>>>,
+    /// With `indexed`, the stitches whose box covers each cell, by the
+    /// record they end at, in order
+    passes: Option<Vec<Vec<usize>>>,
+}
+
+impl DensityGrid {
+    /// A grid of `cell_size_mm` square cells over `bounds`; with no bounds
+    /// or a cell size that is not positive the grid stays empty
+    pub fn new(bounds: Option<&Bounds>, cell_size_mm: f64) -> Self {
+        let bounds = bounds.filter(|_| cell_size_mm > 0.0).cloned();
+        let (columns, rows) = bounds.as_ref().map_or((0, 0), |bounds| {
+            let cell = cell_size_mm * UNITS_PER_MM;
+            (
+                (bounds.width() / cell) as usize + 1,
+                (bounds.height() / cell) as usize + 1,
+            )
+        });
+        Self {
+            bounds,
+            cell_size_mm,
+            columns,
+            rows,
+            counts: vec![0; columns * rows],
+            penetrations: None,
+            passes: None,
+        }
+    }
+
+    /// A grid over records `range` of `pattern` and their bounds, that also
+    /// remembers which records penetrate each cell and which stitches pass
+    /// over it, for checks that look stitches up by where they are
+    pub(crate) fn indexed(pattern: &Pattern, range: Range<usize>, cell_size_mm: f64) -> Self {
+        let records = &pattern.stitches[range.clone()];
+        let bounds = (!records.is_empty()).then(|| {
+            let mut bounds = Bounds::new();
+            for stitch in records {
+                bounds.update(stitch.x, stitch.y);
+            }
+            bounds
+        });
+        let mut grid = Self::new(bounds.as_ref(), cell_size_mm);
+        let mut penetrations = vec![Vec::new(); grid.counts.len()];
+        let mut passes = vec![Vec::new(); grid.counts.len()];
+        for index in range {
+            let stitch = &pattern.stitches[index];
+            if stitch.command != StitchCommand::Stitch {
+                continue;
+            }
+            if let Some(cell) = grid.cell(stitch.x, stitch.y) {
+                grid.counts[cell] += 1;
+                penetrations[cell].push(index);
+            }
+            if let Some(from) = index.checked_sub(1).map(|before| &pattern.stitches[before]) {
+                for cell in grid.cells_between((from.x, from.y), (stitch.x, stitch.y)) {
+                    passes[cell].push(index);
+                }
+            }
+        }
+        grid.penetrations = Some(penetrations);
+        grid.passes = Some(passes);
+        grid
+    }
+
+    /// Number of cells, which are numbered row by row from the top left
+    pub(crate) fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// The cell (`x`, `y`) falls in, the nearest edge cell when outside the
+    /// bounds; None when the grid is empty
+    pub(crate) fn cell(&self, x: f64, y: f64) -> Option<usize> {
+        let bounds = self.bounds.as_ref()?;
+        let cell = self.cell_size_mm * UNITS_PER_MM;
+        let column = ((x - bounds.min_x) / cell).max(0.0) as usize;
+        let row = ((y - bounds.min_y) / cell).max(0.0) as usize;
+        Some(row.min(self.rows - 1) * self.columns + column.min(self.columns - 1))
+    }
+
+    /// Every cell in the box spanned by `p` and `q`
+    pub(crate) fn cells_between(&self, p: (f64, f64), q: (f64, f64)) -> Vec<usize> {
+        let (Some(a), Some(b)) = (self.cell(p.0, p.1), self.cell(q.0, q.1)) else {
+            return Vec::new();
+        };
+        let (columns, rows) = (
+            (a % self.columns).min(b % self.columns)..=(a % self.columns).max(b % self.columns),
+            (a / self.columns).min(b / self.columns)..=(a / self.columns).max(b / self.columns),
+        );
+        rows.flat_map(|row| {
+            columns
+                .clone()
+                .map(move |column| row * self.columns + column)
+        })
+        .collect()
+    }
+
+    /// Centre of `cell` in 0.1mm units
+    pub(crate) fn center(&self, cell: usize) -> (f64, f64) {
+        let (min_x, min_y) = self
+            .bounds
+            .as_ref()
+            .map_or((0.0, 0.0), |b| (b.min_x, b.min_y));
+        let size = self.cell_size_mm * UNITS_PER_MM;
+        (
+            min_x + ((cell % self.columns) as f64 + 0.5) * size,
+            min_y + ((cell / self.columns) as f64 + 0.5) * size,
+        )
+    }
+
+    /// Penetrations per mm² in `cell`
+    pub(crate) fn density(&self, cell: usize) -> f64 {
+        self.counts[cell] as f64 / (self.cell_size_mm * self.cell_size_mm)
+    }
+
+    /// Records penetrating `cell`, in order; none unless the grid is indexed
+    pub(crate) fn penetrations(&self, cell: usize) -> &[usize] {
+        self.penetrations.as_ref().map_or(&[], |p| &p[cell])
+    }
+
+    /// Stitches passing over `cell`, by the record each ends at, in order;
+    /// none unless the grid is indexed
+    pub(crate) fn passes(&self, cell: usize) -> &[usize] {
+        self.passes.as_ref().map_or(&[], |p| &p[cell])
+    }
+
+    /// Keep cells denser than `warning` stitches per mm²
+    pub fn finish(self, warning: f64, critical: f64) -> DensityMap {
+        let mut map = DensityMap {
+            cell_size_mm: self.cell_size_mm,
+            columns: self.columns,
+            rows: self.rows,
+            ..DensityMap::default()
+        };
+        let Some(bounds) = &self.bounds else {
+            return map;
+        };
+
+        let cell = self.cell_size_mm * UNITS_PER_MM;
+        let area = self.cell_size_mm * self.cell_size_mm;
+        for (index, &count) in self.counts.iter().enumerate() {
+            let density = count as f64 / area;
+            map.max_density = map.max_density.max(density);
+            if density <= warning {
+                continue;
+            }
+            map.cells.push(DensityCell {
+                x: bounds.min_x + (index % map.columns) as f64 * cell,
+                y: bounds.min_y + (index / map.columns) as f64 * cell,
+                density,
+                level: if density > critical {
+                    DensityLevel::Critical
+                } else {
+                    DensityLevel::Warning
+                },
+            });
+        }
+        map
+    }
+}
+
+impl StitchSink for DensityGrid {
+    fn push(&mut self, stitch: &Stitch) {
+        if stitch.command != StitchCommand::Stitch {
+            return;
+        }
+        if let Some(cell) = self.cell(stitch.x, stitch.y) {
+            self.counts[cell] += 1;
+        }
+    }
+}
+
+impl Pattern {
+    /// Bin Stitch penetrations into square cells over the pattern bounds in a
+    /// single pass, keeping cells denser than `warning` stitches per mm²
+    pub fn density_map(&self, cell_size_mm: f64, warning: f64, critical: f64) -> DensityMap {
+        feed_all(
+            &self.stitches,
+            DensityGrid::new(self.bounds.as_ref(), cell_size_mm),
+        )
+        .finish(warning, critical)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stitches every `spacing` units over a `size` x `size` square
+    fn grid_pattern(size: usize, spacing: usize) -> Pattern {
+        let mut pattern = Pattern::new();
+        for y in (0..size).step_by(spacing) {
+            for x in (0..size).step_by(spacing) {
+                pattern.add_stitch(x as f64, y as f64, StitchCommand::Stitch);
+            }
+        }
+        pattern.calculate_bounds();
+        pattern
+    }
+
+    #[test]
+    fn test_uniform_grid_has_uniform_density() {
+        // One stitch every 0.5mm over 10mm: 4 per mm² in every 1mm cell
+        let pattern = grid_pattern(100, 5);
+        let map = pattern.density_map(1.0, 1.0, 10.0);
+
+        assert_eq!((map.columns, map.rows), (10, 10));
+        assert_eq!(map.cells.len(), 100);
+        assert!(map.cells.iter().all(|c| c.density == 4.0));
+        assert!(map.cells.iter().all(|c| c.level == DensityLevel::Warning));
+        assert_eq!(map.max_density, 4.0);
+    }
+
+    #[test]
+    fn test_stacked_stitches_are_critical() {
+        let mut pattern = grid_pattern(100, 10);
+        for _ in 0..20 {
+            pattern.add_stitch(55.0, 55.0, StitchCommand::Stitch);
+        }
+        // Jumps never penetrate the fabric
+        for _ in 0..20 {
+            pattern.add_stitch(15.0, 15.0, StitchCommand::Move);
+        }
+        pattern.calculate_bounds();
+        let map = pattern.density_map(1.0, DEFAULT_DENSITY_WARNING, DEFAULT_DENSITY_CRITICAL);
+
+        assert_eq!(map.cells.len(), 1);
+        let cell = &map.cells[0];
+        assert_eq!((cell.x, cell.y), (50.0, 50.0));
+        assert_eq!(cell.density, 21.0);
+        assert_eq!(cell.level, DensityLevel::Critical);
+    }
+
+    #[test]
+    fn test_indexed_grid_over_records() {
+        let mut pattern = grid_pattern(100, 10);
+        pattern.add_stitch(55.0, 55.0, StitchCommand::Move);
+        pattern.add_stitch(55.0, 55.0, StitchCommand::Stitch);
+
+        // The last row and the added records span 9 by 3.5mm from (0, 5.5mm)
+        let grid = DensityGrid::indexed(&pattern, 90..102, 1.0);
+        assert_eq!(grid.len(), 10 * 4);
+        let cell = grid.cell(55.0, 55.0).unwrap();
+        assert_eq!(cell, 5);
+        assert_eq!(grid.penetrations(cell), &[101]);
+        assert_eq!(grid.density(cell), 1.0);
+        assert_eq!(grid.center(cell), (55.0, 60.0));
+        // Points outside land in the nearest edge cell
+        assert_eq!(grid.cell(500.0, 500.0), Some(39));
+        assert_eq!(grid.penetrations(39), &[99]);
+        assert_eq!(
+            grid.cells_between((25.0, 70.0), (0.0, 55.0)),
+            vec![0, 1, 2, 10, 11, 12]
+        );
+        // The stitch from (8mm, 9mm) to (9mm, 9mm) passes over its end's cell
+        assert!(grid.passes(39).contains(&99));
+
+        // A plain grid keeps no records
+        pattern.calculate_bounds();
+        let plain = feed_all(
+            &pattern.stitches,
+            DensityGrid::new(pattern.bounds.as_ref(), 1.0),
+        );
+        assert!(plain
+            .penetrations(plain.cell(55.0, 55.0).unwrap())
+            .is_empty());
+        assert!(DensityGrid::new(None, 1.0).cell(0.0, 0.0).is_none());
+    }
+
+    #[test]
+    fn test_empty_pattern_and_bad_cell_size() {
+        assert!(Pattern::new().density_map(1.0, 1.0, 2.0).cells.is_empty());
+        assert!(grid_pattern(10, 1)
+            .density_map(0.0, 1.0, 2.0)
+            .cells
+            .is_empty());
+    }
+}
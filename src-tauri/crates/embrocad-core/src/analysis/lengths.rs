@@ -0,0 +1,116 @@
+// lengths.rs - Stitch length histogram and too-long/too-short outlier detection
+
+use crate::dst::{Pattern, StitchCommand};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Longest stitch a single DST record can encode
+pub const DEFAULT_LONG_STITCH_MM: f64 = 12.1;
+
+/// Stitches shorter than this shred the thread
+pub const DEFAULT_SHORT_STITCH_MM: f64 = 0.3;
+
+/// A stitch whose length falls outside the safe range
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LengthOutlier {
+    /// Index into `Pattern::stitches`
+    pub index: usize,
+    pub length_mm: f64,
+}
+
+/// Stitches longer or shorter than the thresholds passed to `length_outliers`
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LengthOutliers {
+    pub long: Vec<LengthOutlier>,
+    pub short: Vec<LengthOutlier>,
+}
+
+impl Pattern {
+    /// Lengths of every Stitch record that has a predecessor, with its index
+    fn stitch_lengths(&self) -> impl Iterator<Item = (usize, f64)> + '_ {
+        self.stitches
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.command == StitchCommand::Stitch)
+            .filter_map(|(index, _)| Some((index, self.record_length_mm(index)?)))
+    }
+
+    /// Count stitches per length bucket; bucket `i` covers `[i, i + 1) * bucket_mm`
+    pub fn stitch_length_histogram(&self, bucket_mm: f64) -> Vec<u32> {
+        if bucket_mm.is_nan() || bucket_mm <= 0.0 {
+            return Vec::new();
+        }
+
+        let mut counts = Vec::new();
+        for (_, length) in self.stitch_lengths() {
+            let bucket = (length / bucket_mm) as usize;
+            if bucket >= counts.len() {
+                counts.resize(bucket + 1, 0);
+            }
+            counts[bucket] += 1;
+        }
+        counts
+    }
+
+    /// Stitches strictly longer than `long_mm` or strictly shorter than `short_mm`
+    pub fn length_outliers(&self, long_mm: f64, short_mm: f64) -> LengthOutliers {
+        let mut outliers = LengthOutliers::default();
+        for (index, length_mm) in self.stitch_lengths() {
+            if length_mm > long_mm {
+                outliers.long.push(LengthOutlier { index, length_mm });
+            } else if length_mm < short_mm {
+                outliers.short.push(LengthOutlier { index, length_mm });
+            }
+        }
+        outliers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stitches of the given lengths in mm, laid end to end along X
+    fn pattern_of_lengths(lengths: &[f64]) -> Pattern {
+        let mut pattern = Pattern::new();
+        let mut x = 0.0;
+        pattern.add_stitch(x, 0.0, StitchCommand::Stitch);
+        for length in lengths {
+            x += length * 10.0;
+            pattern.add_stitch(x, 0.0, StitchCommand::Stitch);
+        }
+        pattern
+    }
+
+    #[test]
+    fn test_histogram_buckets() {
+        let pattern = pattern_of_lengths(&[0.5, 1.2, 1.9, 3.0, 0.0]);
+
+        assert_eq!(pattern.stitch_length_histogram(1.0), vec![2, 2, 0, 1]);
+        assert_eq!(pattern.stitch_length_histogram(10.0), vec![5]);
+        assert!(pattern.stitch_length_histogram(0.0).is_empty());
+    }
+
+    #[test]
+    fn test_histogram_ignores_jumps() {
+        let mut pattern = pattern_of_lengths(&[1.0]);
+        pattern.add_stitch(500.0, 0.0, StitchCommand::Move);
+        pattern.add_stitch(500.0, 5.0, StitchCommand::Stitch);
+
+        assert_eq!(pattern.stitch_length_histogram(1.0), vec![1, 1]);
+    }
+
+    #[test]
+    fn test_outliers_on_both_sides_of_thresholds() {
+        let pattern = pattern_of_lengths(&[12.0, 12.5, 0.3, 0.2, 5.0, 20.0]);
+        let outliers = pattern.length_outliers(DEFAULT_LONG_STITCH_MM, DEFAULT_SHORT_STITCH_MM);
+
+        let long: Vec<_> = outliers.long.iter().map(|o| o.index).collect();
+        let short: Vec<_> = outliers.short.iter().map(|o| o.index).collect();
+        assert_eq!(long, vec![2, 6]);
+        assert_eq!(short, vec![4]);
+        assert!((outliers.long[0].length_mm - 12.5).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,207 @@
+// measure.rs - Distances, angles and sewn lengths for the measure tool
+
+use crate::dst::{Bounds, Pattern, StitchCommand};
+use crate::units::Unit;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::ops::Range;
+
+/// What the measure tool shows between two records, in one unit
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Measurement {
+    pub unit: Unit,
+    pub from_index: usize,
+    pub to_index: usize,
+    /// Straight line from the first record to the second
+    pub distance: f64,
+    /// Direction of that line in degrees, counter-clockwise on screen from
+    /// the x axis, in (-180, 180]
+    pub angle_degrees: f64,
+    /// Thread sewn between the two records, jumps excluded
+    pub path_length: f64,
+    /// Needle drops after the earlier record, up to and including the later
+    pub penetrations: usize,
+}
+
+/// Size and sewn length of one color block, in one unit
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct BlockMeasurement {
+    pub unit: Unit,
+    pub block: usize,
+    /// Thread sewn in the block, jumps excluded
+    pub path_length: f64,
+    /// Distance jumped within the block
+    pub travel_length: f64,
+    pub penetrations: usize,
+    /// Box around the block's needle drops; None when it has none
+    pub bounds: Option<Bounds>,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Sewn length, jumped length and needle drops over a run of records, in
+/// native units
+#[derive(Debug, Default)]
+struct Walk {
+    sewn: f64,
+    jumped: f64,
+    penetrations: usize,
+    bounds: Option<Bounds>,
+}
+
+impl Pattern {
+    /// Walk `range`, measuring each record from the one before it
+    fn walk(&self, range: Range<usize>) -> Walk {
+        let mut walk = Walk::default();
+        for index in range {
+            let stitch = &self.stitches[index];
+            let length = index.checked_sub(1).map_or(0.0, |before| {
+                let before = &self.stitches[before];
+                (stitch.x - before.x).hypot(stitch.y - before.y)
+            });
+            match stitch.command {
+                StitchCommand::Stitch | StitchCommand::SequinEject => {
+                    if stitch.command == StitchCommand::Stitch {
+                        walk.sewn += length;
+                    }
+                    walk.penetrations += 1;
+                    walk.bounds
+                        .get_or_insert_with(Bounds::new)
+                        .update(stitch.x, stitch.y);
+                }
+                StitchCommand::Move => walk.jumped += length,
+                _ => {}
+            }
+        }
+        walk
+    }
+
+    /// Measure from record `from` to record `to` in `unit`; None when
+    /// either does not exist
+    ///
+    /// The straight line runs from `from` to `to` in the order given; the
+    /// sewn path is the same either way round.
+    pub fn measure(&self, from: usize, to: usize, unit: Unit) -> Option<Measurement> {
+        let (a, b) = (self.stitches.get(from)?, self.stitches.get(to)?);
+        let (dx, dy) = (b.x - a.x, b.y - a.y);
+        // Y points down, so the screen angle flips its sign
+        let angle = if dx == 0.0 && dy == 0.0 {
+            0.0
+        } else {
+            (-dy).atan2(dx).to_degrees()
+        };
+        let walk = self.walk(from.min(to) + 1..from.max(to) + 1);
+        Some(Measurement {
+            unit,
+            from_index: from,
+            to_index: to,
+            distance: unit.from_native(dx.hypot(dy)),
+            angle_degrees: if angle == -180.0 { 180.0 } else { angle },
+            path_length: unit.from_native(walk.sewn),
+            penetrations: walk.penetrations,
+        })
+    }
+
+    /// Measure color block `block` in `unit`; None when it does not exist
+    pub fn measure_block(&self, block: usize, unit: Unit) -> Option<BlockMeasurement> {
+        let range = self.color_blocks.get(block)?;
+        let walk = self.walk(range.start..range.end);
+        let bounds = walk.bounds.map(|b| unit.bounds_from_native(&b));
+        let (width, height) = bounds
+            .as_ref()
+            .map_or((0.0, 0.0), |b| (b.width(), b.height()));
+        Some(BlockMeasurement {
+            unit,
+            block,
+            path_length: unit.from_native(walk.sewn),
+            travel_length: unit.from_native(walk.jumped),
+            penetrations: walk.penetrations,
+            bounds,
+            width,
+            height,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StitchCommand::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    /// A zigzag of four 5mm stitches, then a jump to a 10mm upright
+    fn zigzag() -> Pattern {
+        let mut pattern = Pattern::new();
+        for (i, y) in [0.0, 40.0, 0.0, 40.0, 0.0].into_iter().enumerate() {
+            pattern.add_stitch(i as f64 * 30.0, y, Stitch);
+        }
+        pattern.add_stitch(120.0, 0.0, Trim);
+        pattern.add_stitch(120.0, 0.0, ColorChange);
+        pattern.add_stitch(200.0, 0.0, Move);
+        pattern.add_stitch(200.0, 0.0, Stitch);
+        pattern.add_stitch(200.0, 100.0, Stitch);
+        pattern.add_stitch(200.0, 100.0, End);
+        pattern.calculate_color_blocks();
+        pattern
+    }
+
+    #[test]
+    fn test_measure_between_records() {
+        let pattern = zigzag();
+        let across = pattern.measure(0, 4, Unit::Mm).unwrap();
+        assert!(close(across.distance, 12.0));
+        assert!(close(across.angle_degrees, 0.0));
+        assert!(close(across.path_length, 20.0));
+        assert_eq!(across.penetrations, 4);
+
+        // Down and to the right on screen, since Y points down
+        let first = pattern.measure(0, 1, Unit::Mm).unwrap();
+        assert!(close(first.distance, 5.0));
+        assert!(close(
+            first.angle_degrees,
+            (-40.0f64).atan2(30.0).to_degrees()
+        ));
+
+        let back = pattern.measure(4, 0, Unit::Mm).unwrap();
+        assert!(close(back.angle_degrees, 180.0));
+        assert!(close(back.path_length, across.path_length));
+        assert_eq!(back.penetrations, 4);
+
+        // The jump between the blocks is not sewn thread
+        let jumped = pattern.measure(4, 9, Unit::Inch).unwrap();
+        assert!(close(jumped.path_length, 10.0 / 25.4));
+        assert_eq!(jumped.penetrations, 2);
+        assert!(close(
+            pattern.measure(0, 0, Unit::Native).unwrap().distance,
+            0.0
+        ));
+
+        assert!(pattern.measure(0, 11, Unit::Mm).is_none());
+    }
+
+    #[test]
+    fn test_measure_block() {
+        let pattern = zigzag();
+        let first = pattern.measure_block(0, Unit::Mm).unwrap();
+        assert!(close(first.path_length, 20.0));
+        assert!(close(first.travel_length, 0.0));
+        assert_eq!(first.penetrations, 5);
+        assert!(close(first.width, 12.0));
+        assert!(close(first.height, 4.0));
+
+        let second = pattern.measure_block(1, Unit::Native).unwrap();
+        assert!(close(second.path_length, 100.0));
+        assert!(close(second.travel_length, 80.0));
+        assert_eq!(second.penetrations, 2);
+        let bounds = second.bounds.unwrap();
+        assert_eq!((bounds.min_x, bounds.min_y), (200.0, 0.0));
+        assert_eq!((second.width, second.height), (0.0, 100.0));
+
+        assert!(pattern.measure_block(2, Unit::Mm).is_none());
+    }
+}
@@ -0,0 +1,71 @@
+// mod.rs - Analysis module exports for pattern measurements and estimates
+
+mod compare;
+mod density;
+mod lengths;
+mod measure;
+mod risks;
+mod thread;
+mod underlay;
+
+pub use compare::{compare_patterns, DesignDiff, DEFAULT_MATCH_TOLERANCE_MM};
+pub use density::{DensityGrid, DensityMap, DEFAULT_DENSITY_CRITICAL, DEFAULT_DENSITY_WARNING};
+pub use lengths::{LengthOutliers, DEFAULT_LONG_STITCH_MM, DEFAULT_SHORT_STITCH_MM};
+pub use measure::{BlockMeasurement, Measurement};
+pub use risks::{Risk, RiskKind, RiskSeverity, RiskThresholds};
+pub use thread::{estimate_thread_usage, ThreadUsage, ThreadUsageOptions};
+pub use underlay::{UnderlayOptions, UnderlayRun};
+
+use crate::dst::Pattern;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Tunable thresholds for `analyze`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AnalysisOptions {
+    pub bucket_mm: f64,
+    pub long_stitch_mm: f64,
+    pub short_stitch_mm: f64,
+    pub density_cell_mm: f64,
+    pub density_warning: f64,
+    pub density_critical: f64,
+}
+
+impl Default for AnalysisOptions {
+    fn default() -> Self {
+        Self {
+            bucket_mm: 0.5,
+            long_stitch_mm: DEFAULT_LONG_STITCH_MM,
+            short_stitch_mm: DEFAULT_SHORT_STITCH_MM,
+            density_cell_mm: 2.0,
+            density_warning: DEFAULT_DENSITY_WARNING,
+            density_critical: DEFAULT_DENSITY_CRITICAL,
+        }
+    }
+}
+
+/// Quality report for a design, for highlighting problem stitches in the UI
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct DesignAnalysis {
+    pub bucket_mm: f64,
+    pub length_histogram: Vec<u32>,
+    pub outliers: LengthOutliers,
+    pub density: DensityMap,
+}
+
+/// Run every analysis on a pattern
+pub fn analyze(pattern: &Pattern, options: &AnalysisOptions) -> DesignAnalysis {
+    DesignAnalysis {
+        bucket_mm: options.bucket_mm,
+        length_histogram: pattern.stitch_length_histogram(options.bucket_mm),
+        outliers: pattern.length_outliers(options.long_stitch_mm, options.short_stitch_mm),
+        density: pattern.density_map(
+            options.density_cell_mm,
+            options.density_warning,
+            options.density_critical,
+        ),
+    }
+}
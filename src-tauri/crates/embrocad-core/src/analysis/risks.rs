@@ -0,0 +1,370 @@
+// risks.rs - Places a design is likely to break thread, from several stitch checks
+
+use super::density::{DensityGrid, DEFAULT_DENSITY_CRITICAL, DEFAULT_DENSITY_WARNING};
+use super::lengths::DEFAULT_SHORT_STITCH_MM;
+use crate::dst::{Pattern, StitchCommand, UNITS_PER_MM};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// When each thread-break check trips
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct RiskThresholds {
+    /// Side of the square cells penetrations are counted in
+    pub density_cell_mm: f64,
+    /// Penetrations per mm² of a dense cell
+    pub density_warning: f64,
+    /// Penetrations per mm² of a critically dense cell
+    pub density_critical: f64,
+    /// Stitches shorter than this are short
+    pub short_stitch_mm: f64,
+    /// Consecutive short stitches that make a risky run; twice as many is
+    /// critical
+    pub short_run: usize,
+    /// Turns at least this sharp between two stitches count as reversals
+    pub reversal_degrees: f64,
+    /// Reversals only count when both stitches are shorter than this
+    pub reversal_max_mm: f64,
+    /// Stitches at least this long are loose enough to snag where they lie
+    /// over dense stitching
+    pub long_stitch_mm: f64,
+}
+
+impl Default for RiskThresholds {
+    fn default() -> Self {
+        Self {
+            density_cell_mm: 2.0,
+            density_warning: DEFAULT_DENSITY_WARNING,
+            density_critical: DEFAULT_DENSITY_CRITICAL,
+            short_stitch_mm: DEFAULT_SHORT_STITCH_MM,
+            short_run: 3,
+            reversal_degrees: 170.0,
+            reversal_max_mm: 1.0,
+            long_stitch_mm: 7.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum RiskKind {
+    /// Penetrations packed into one cell
+    Density,
+    /// A run of very short stitches
+    ShortStitches,
+    /// Short stitches doubling back on each other
+    Reversal,
+    /// A long stitch lying over dense stitching
+    LongOverDense,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum RiskSeverity {
+    Warning,
+    Critical,
+}
+
+/// One place a check tripped, for a marker in the UI
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Risk {
+    pub kind: RiskKind,
+    pub severity: RiskSeverity,
+    /// Records involved, in order
+    pub indices: Vec<usize>,
+    /// Where the marker goes, in 0.1mm units
+    pub x: f64,
+    pub y: f64,
+    /// What tripped the check: penetrations per mm², stitches in the run,
+    /// the sharpest turn in degrees, or the stitch length in mm
+    pub value: f64,
+}
+
+impl Pattern {
+    /// Run every thread-break check, returning what tripped ordered by the
+    /// first record involved
+    ///
+    /// Thresholds that are not positive turn their check off.
+    pub fn thread_break_risks(&self, thresholds: &RiskThresholds) -> Vec<Risk> {
+        let mut risks = Vec::new();
+        if thresholds.density_cell_mm > 0.0 {
+            let grid =
+                DensityGrid::indexed(self, 0..self.stitches.len(), thresholds.density_cell_mm);
+            self.dense_cells(&grid, thresholds, &mut risks);
+            self.long_over_dense(&grid, thresholds, &mut risks);
+        }
+        self.short_runs(thresholds, &mut risks);
+        self.reversals(thresholds, &mut risks);
+        risks.sort_by_key(|r| (r.indices.first().copied(), r.kind as u8));
+        risks
+    }
+
+    fn dense_cells(&self, grid: &DensityGrid, thresholds: &RiskThresholds, risks: &mut Vec<Risk>) {
+        for cell in 0..grid.len() {
+            let density = grid.density(cell);
+            if density <= thresholds.density_warning {
+                continue;
+            }
+            let (x, y) = grid.center(cell);
+            risks.push(Risk {
+                kind: RiskKind::Density,
+                severity: if density > thresholds.density_critical {
+                    RiskSeverity::Critical
+                } else {
+                    RiskSeverity::Warning
+                },
+                indices: grid.penetrations(cell).to_vec(),
+                x,
+                y,
+                value: density,
+            });
+        }
+    }
+
+    /// Long stitches with a point along them, ends aside, in a dense cell
+    fn long_over_dense(
+        &self,
+        grid: &DensityGrid,
+        thresholds: &RiskThresholds,
+        risks: &mut Vec<Risk>,
+    ) {
+        if thresholds.long_stitch_mm <= 0.0 {
+            return;
+        }
+        let cell_size = thresholds.density_cell_mm * UNITS_PER_MM;
+        for (index, stitch) in self.stitches.iter().enumerate().skip(1) {
+            let Some(length_mm) = self.record_length_mm(index) else {
+                continue;
+            };
+            if stitch.command != StitchCommand::Stitch || length_mm < thresholds.long_stitch_mm {
+                continue;
+            }
+            let from = &self.stitches[index - 1];
+            // Every half cell, so no cell it crosses is skipped
+            let steps = (length_mm * UNITS_PER_MM / (cell_size / 2.0)).ceil() as usize;
+            let densest = (1..steps)
+                .map(|step| {
+                    let t = step as f64 / steps as f64;
+                    let x = from.x + (stitch.x - from.x) * t;
+                    let y = from.y + (stitch.y - from.y) * t;
+                    grid.cell(x, y).map_or(0.0, |cell| grid.density(cell))
+                })
+                .fold(0.0, f64::max);
+            if densest <= thresholds.density_warning {
+                continue;
+            }
+            risks.push(Risk {
+                kind: RiskKind::LongOverDense,
+                severity: if densest > thresholds.density_critical {
+                    RiskSeverity::Critical
+                } else {
+                    RiskSeverity::Warning
+                },
+                indices: vec![index],
+                x: (from.x + stitch.x) / 2.0,
+                y: (from.y + stitch.y) / 2.0,
+                value: length_mm,
+            });
+        }
+    }
+
+    fn short_runs(&self, thresholds: &RiskThresholds, risks: &mut Vec<Risk>) {
+        if thresholds.short_stitch_mm <= 0.0 || thresholds.short_run == 0 {
+            return;
+        }
+        let short = |index: usize| {
+            self.stitches[index].command == StitchCommand::Stitch
+                && self
+                    .record_length_mm(index)
+                    .is_some_and(|length| length < thresholds.short_stitch_mm)
+        };
+        let mut index = 0;
+        while index < self.stitches.len() {
+            if !short(index) {
+                index += 1;
+                continue;
+            }
+            let start = index;
+            while index < self.stitches.len() && short(index) {
+                index += 1;
+            }
+            let count = index - start;
+            if count < thresholds.short_run {
+                continue;
+            }
+            let first = &self.stitches[start];
+            risks.push(Risk {
+                kind: RiskKind::ShortStitches,
+                severity: if count >= 2 * thresholds.short_run {
+                    RiskSeverity::Critical
+                } else {
+                    RiskSeverity::Warning
+                },
+                indices: (start..index).collect(),
+                x: first.x,
+                y: first.y,
+                value: count as f64,
+            });
+        }
+    }
+
+    /// Pairs of short stitches turning back on themselves; neighbouring
+    /// reversals make one risk, critical when there is more than one
+    fn reversals(&self, thresholds: &RiskThresholds, risks: &mut Vec<Risk>) {
+        if thresholds.reversal_max_mm <= 0.0 {
+            return;
+        }
+        let max = thresholds.reversal_max_mm * UNITS_PER_MM;
+        // The turn at record `index - 1` on the way to `index`, in degrees
+        let turn = |index: usize| -> Option<f64> {
+            let [a, b, c] = &self.stitches[index - 2..=index] else {
+                return None;
+            };
+            if b.command != StitchCommand::Stitch || c.command != StitchCommand::Stitch {
+                return None;
+            }
+            let (ux, uy) = (b.x - a.x, b.y - a.y);
+            let (vx, vy) = (c.x - b.x, c.y - b.y);
+            let (u, v) = (ux.hypot(uy), vx.hypot(vy));
+            if u == 0.0 || v == 0.0 || u >= max || v >= max {
+                return None;
+            }
+            let cos = ((ux * vx + uy * vy) / (u * v)).clamp(-1.0, 1.0);
+            Some(cos.acos().to_degrees()).filter(|&t| t >= thresholds.reversal_degrees)
+        };
+
+        let mut group: Vec<(usize, f64)> = Vec::new();
+        let mut flush = |group: &mut Vec<(usize, f64)>| {
+            let (Some(&(first, _)), Some(&(last, _))) = (group.first(), group.last()) else {
+                return;
+            };
+            let sharpest = group.iter().map(|&(_, t)| t).fold(0.0, f64::max);
+            let corner = &self.stitches[first - 1];
+            risks.push(Risk {
+                kind: RiskKind::Reversal,
+                severity: if group.len() > 1 {
+                    RiskSeverity::Critical
+                } else {
+                    RiskSeverity::Warning
+                },
+                indices: (first - 2..=last).collect(),
+                x: corner.x,
+                y: corner.y,
+                value: sharpest,
+            });
+            group.clear();
+        };
+        for index in 2..self.stitches.len() {
+            match turn(index) {
+                Some(degrees) => group.push((index, degrees)),
+                None => flush(&mut group),
+            }
+        }
+        flush(&mut group);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StitchCommand::*;
+
+    /// One of each risk: a long stitch into a 1.5mm square sewn round four
+    /// times, a run of 0.2mm stitches, and a 0.8mm stitch sewn back on itself
+    fn risky() -> Pattern {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(10.0, 80.0, Stitch);
+        pattern.add_stitch(10.0, 5.0, Stitch);
+        for _ in 0..4 {
+            for (x, y) in [(0.0, 0.0), (15.0, 0.0), (15.0, 15.0), (0.0, 15.0)] {
+                pattern.add_stitch(x, y, Stitch);
+            }
+        }
+        pattern.add_stitch(0.0, 0.0, Stitch);
+
+        pattern.add_stitch(100.0, 0.0, Move);
+        for x in [100.0, 102.0, 104.0, 106.0, 108.0] {
+            pattern.add_stitch(x, 0.0, Stitch);
+        }
+
+        pattern.add_stitch(200.0, 0.0, Move);
+        for (x, y) in [
+            (200.0, 0.0),
+            (208.0, 0.0),
+            (200.0, 0.0),
+            (200.0, 20.0),
+            (200.0, 40.0),
+        ] {
+            pattern.add_stitch(x, y, Stitch);
+        }
+        pattern.add_stitch(200.0, 40.0, End);
+        pattern.calculate_bounds();
+        pattern
+    }
+
+    #[test]
+    fn test_each_rule_trips_once() {
+        let risks = risky().thread_break_risks(&RiskThresholds::default());
+        let kinds: Vec<RiskKind> = risks.iter().map(|r| r.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                RiskKind::Density,
+                RiskKind::LongOverDense,
+                RiskKind::ShortStitches,
+                RiskKind::Reversal,
+            ]
+        );
+
+        // Eighteen penetrations in a 4mm² cell
+        let density = &risks[0];
+        assert_eq!(density.severity, RiskSeverity::Critical);
+        assert_eq!(density.indices.len(), 18);
+        assert!((density.value - 4.5).abs() < 1e-12);
+        assert_eq!((density.x, density.y), (10.0, 10.0));
+
+        let long = &risks[1];
+        assert_eq!(long.indices, vec![1]);
+        assert!((long.value - 7.5).abs() < 1e-12);
+        assert_eq!(long.severity, RiskSeverity::Critical);
+
+        // The stitch in place after the jump is short too
+        let short = &risks[2];
+        assert_eq!(short.indices, vec![20, 21, 22, 23, 24]);
+        assert_eq!(short.severity, RiskSeverity::Warning);
+        assert_eq!((short.x, short.y), (100.0, 0.0));
+
+        let reversal = &risks[3];
+        assert_eq!(reversal.indices, vec![26, 27, 28]);
+        assert_eq!(reversal.severity, RiskSeverity::Warning);
+        assert!((reversal.value - 180.0).abs() < 1e-9);
+        assert_eq!((reversal.x, reversal.y), (208.0, 0.0));
+    }
+
+    #[test]
+    fn test_thresholds_turn_checks_off() {
+        let thresholds = RiskThresholds {
+            density_cell_mm: 0.0,
+            short_stitch_mm: 0.0,
+            reversal_max_mm: 0.0,
+            ..RiskThresholds::default()
+        };
+        assert!(risky().thread_break_risks(&thresholds).is_empty());
+
+        // A longer run is critical
+        let thresholds = RiskThresholds {
+            short_run: 2,
+            ..RiskThresholds::default()
+        };
+        let risks = risky().thread_break_risks(&thresholds);
+        let short = risks
+            .iter()
+            .find(|r| r.kind == RiskKind::ShortStitches)
+            .unwrap();
+        assert_eq!(short.severity, RiskSeverity::Critical);
+    }
+}
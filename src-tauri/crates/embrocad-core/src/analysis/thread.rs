@@ -0,0 +1,145 @@
+// thread.rs - Top and bobbin thread consumption estimates per color
+
+use crate::dst::{
+    Pattern, ThreadColor, DEFAULT_BOBBIN_THREAD_MULTIPLIER, DEFAULT_TOP_THREAD_MULTIPLIER,
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Calibration for the consumption model; shops tune these against weighed cones
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ThreadUsageOptions {
+    pub top_multiplier: f64,
+    pub bobbin_multiplier: f64,
+}
+
+impl Default for ThreadUsageOptions {
+    fn default() -> Self {
+        Self {
+            top_multiplier: DEFAULT_TOP_THREAD_MULTIPLIER,
+            bobbin_multiplier: DEFAULT_BOBBIN_THREAD_MULTIPLIER,
+        }
+    }
+}
+
+/// Consumption for one thread color, in meters
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ColorThreadUsage {
+    /// Color blocks sewn with this thread
+    pub blocks: Vec<usize>,
+    pub color: Option<ThreadColor>,
+    /// Flat 2D length of the stitches
+    pub path_m: f64,
+    pub top_thread_m: f64,
+    pub bobbin_thread_m: f64,
+}
+
+/// Consumption for a whole design, in meters
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ThreadUsage {
+    pub colors: Vec<ColorThreadUsage>,
+    pub top_thread_m: f64,
+    pub bobbin_thread_m: f64,
+}
+
+/// Estimate thread consumption as stitch path length times a multiplier
+///
+/// Blocks that declare the same thread color are combined so the figures can
+/// be matched to a cone; blocks without a declared color are listed separately.
+pub fn estimate_thread_usage(pattern: &Pattern, options: &ThreadUsageOptions) -> ThreadUsage {
+    let mut colors: Vec<ColorThreadUsage> = Vec::new();
+
+    for block in pattern.color_blocks() {
+        let path_m = block.statistics.thread_length_mm / 1000.0;
+        let existing = block
+            .color
+            .as_ref()
+            .and_then(|color| colors.iter_mut().find(|c| c.color.as_ref() == Some(color)));
+
+        match existing {
+            Some(usage) => {
+                usage.blocks.push(block.index);
+                usage.path_m += path_m;
+            }
+            None => colors.push(ColorThreadUsage {
+                blocks: vec![block.index],
+                color: block.color,
+                path_m,
+                top_thread_m: 0.0,
+                bobbin_thread_m: 0.0,
+            }),
+        }
+    }
+
+    for usage in &mut colors {
+        usage.top_thread_m = usage.path_m * options.top_multiplier;
+        usage.bobbin_thread_m = usage.path_m * options.bobbin_multiplier;
+    }
+
+    ThreadUsage {
+        top_thread_m: colors.iter().map(|c| c.top_thread_m).sum(),
+        bobbin_thread_m: colors.iter().map(|c| c.bobbin_thread_m).sum(),
+        colors,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::StitchCommand;
+
+    /// A 10mm square sewn in red, another in blue, then the first again in red
+    fn squares() -> Pattern {
+        let mut pattern = Pattern::new();
+        for (i, offset) in [0.0, 200.0, 400.0].into_iter().enumerate() {
+            if i > 0 {
+                pattern.add_stitch(offset, 0.0, StitchCommand::ColorChange);
+            }
+            pattern.add_stitch(offset, 0.0, StitchCommand::Move);
+            for (x, y) in [(100.0, 0.0), (100.0, 100.0), (0.0, 100.0), (0.0, 0.0)] {
+                pattern.add_stitch(offset + x, y, StitchCommand::Stitch);
+            }
+        }
+        pattern.add_stitch(400.0, 0.0, StitchCommand::End);
+
+        let red = ThreadColor::new([255, 0, 0]);
+        let blue = ThreadColor::new([0, 0, 255]);
+        pattern.metadata.thread_colors = vec![red.clone(), blue, red];
+        pattern.calculate_statistics();
+        pattern
+    }
+
+    #[test]
+    fn test_square_consumption_defaults() {
+        let pattern = squares();
+        let usage = estimate_thread_usage(&pattern, &ThreadUsageOptions::default());
+
+        // Each square is 40mm of path; red sews two of them
+        assert_eq!(usage.colors.len(), 2);
+        assert_eq!(usage.colors[0].blocks, vec![0, 2]);
+        assert!((usage.colors[0].path_m - 0.08).abs() < 1e-12);
+        assert!((usage.colors[0].top_thread_m - 0.1).abs() < 1e-12);
+        assert!((usage.colors[0].bobbin_thread_m - 0.0264).abs() < 1e-12);
+        assert!((usage.colors[1].path_m - 0.04).abs() < 1e-12);
+
+        assert!((usage.top_thread_m - 0.15).abs() < 1e-12);
+        assert!((usage.top_thread_m - pattern.statistics.top_thread_m).abs() < 1e-12);
+        assert!((usage.bobbin_thread_m - pattern.statistics.bobbin_thread_m).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_custom_multipliers() {
+        let options = ThreadUsageOptions {
+            top_multiplier: 2.0,
+            bobbin_multiplier: 0.5,
+        };
+        let usage = estimate_thread_usage(&squares(), &options);
+
+        assert!((usage.top_thread_m - 0.24).abs() < 1e-12);
+        assert!((usage.bobbin_thread_m - 0.06).abs() < 1e-12);
+    }
+}
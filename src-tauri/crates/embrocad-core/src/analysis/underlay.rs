@@ -0,0 +1,253 @@
+// underlay.rs - Guessing which stitches are underlay covered by later stitching
+
+use super::density::DensityGrid;
+use crate::dst::{Pattern, StitchCommand, UNITS_PER_MM};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// How cautious the underlay classifier is
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct UnderlayOptions {
+    /// Fewest stitches in a straight run that can be underlay
+    pub min_run: usize,
+    /// Stitches shorter than this break a run
+    pub min_stitch_mm: f64,
+    /// Sharpest turn allowed between two stitches of a run
+    pub max_turn_degrees: f64,
+    /// Later stitches of the block that must cross a run's stitch for it to
+    /// count as covered
+    pub min_crossings: usize,
+    /// Penetrations per mm² around a run, counting those sewn up to its
+    /// end, above which it is not sparse and loses half its confidence
+    pub sparse_density: f64,
+    /// Side of the square cells crossings and density are looked up in
+    pub cell_mm: f64,
+    /// Runs less certain than this are left alone
+    pub min_confidence: f64,
+}
+
+impl Default for UnderlayOptions {
+    fn default() -> Self {
+        Self {
+            min_run: 3,
+            min_stitch_mm: 1.0,
+            max_turn_degrees: 20.0,
+            min_crossings: 2,
+            sparse_density: 1.0,
+            cell_mm: 2.0,
+            min_confidence: 0.75,
+        }
+    }
+}
+
+/// Records `start..end` of color block `block` are likely underlay
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UnderlayRun {
+    pub block: usize,
+    pub start: usize,
+    pub end: usize,
+    /// From 0 to 1: the share of the run's stitches crossed by later
+    /// stitching, halved when the run is sewn over stitching already dense
+    pub confidence: f64,
+}
+
+/// Whether segments `p`-`q` and `r`-`s` cross at a point inside both
+fn crosses(p: (f64, f64), q: (f64, f64), r: (f64, f64), s: (f64, f64)) -> bool {
+    let side = |a: (f64, f64), b: (f64, f64), c: (f64, f64)| {
+        (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+    };
+    let (d1, d2) = (side(r, s, p), side(r, s, q));
+    let (d3, d4) = (side(p, q, r), side(p, q, s));
+    d1 * d2 < 0.0 && d3 * d4 < 0.0
+}
+
+impl Pattern {
+    /// Guess which runs of stitches are underlay: long, nearly straight
+    /// runs sewn where the block has little stitching yet, and crossed
+    /// afterwards by the block's own later stitches
+    ///
+    /// The guess errs towards leaving stitches out; fill rows and satin
+    /// columns are never crossed by the rows after them and are not tagged.
+    pub fn detect_underlay(&self, options: &UnderlayOptions) -> Vec<UnderlayRun> {
+        let mut found = Vec::new();
+        if !(options.cell_mm.is_finite() && options.cell_mm > 0.0) {
+            return found;
+        }
+        let position = |index: usize| (self.stitches[index].x, self.stitches[index].y);
+        for (block, color) in self.color_blocks.iter().enumerate() {
+            let runs = self.straight_runs(color.start..color.end, options);
+            if runs.is_empty() {
+                continue;
+            }
+            let grid = DensityGrid::indexed(self, color.start..color.end, options.cell_mm);
+            let area = options.cell_mm * options.cell_mm;
+
+            for run in runs {
+                let crossed = run
+                    .clone()
+                    .filter(|&index| {
+                        let (p, q) = (position(index - 1), position(index));
+                        let mut later: Vec<usize> = grid
+                            .cells_between(p, q)
+                            .into_iter()
+                            .flat_map(|cell| grid.passes(cell))
+                            .copied()
+                            .filter(|&other| other >= run.end)
+                            .collect();
+                        later.sort_unstable();
+                        later.dedup();
+                        let crossings = later
+                            .into_iter()
+                            .filter(|&other| crosses(p, q, position(other - 1), position(other)))
+                            .count();
+                        crossings >= options.min_crossings
+                    })
+                    .count();
+
+                // Penetrations sewn up to the run's end around its own
+                let mut run_cells: Vec<usize> = (run.start - 1..run.end)
+                    .filter_map(|index| {
+                        let (x, y) = position(index);
+                        grid.cell(x, y)
+                    })
+                    .collect();
+                run_cells.sort_unstable();
+                run_cells.dedup();
+                let sewn: usize = run_cells
+                    .iter()
+                    .map(|&cell| {
+                        grid.penetrations(cell)
+                            .partition_point(|&index| index < run.end)
+                    })
+                    .sum();
+                let density = sewn as f64 / (run_cells.len() as f64 * area);
+
+                let covered = crossed as f64 / run.len() as f64;
+                let confidence = if density <= options.sparse_density {
+                    covered
+                } else {
+                    covered / 2.0
+                };
+                if confidence >= options.min_confidence {
+                    found.push(UnderlayRun {
+                        block,
+                        start: run.start,
+                        end: run.end,
+                        confidence,
+                    });
+                }
+            }
+        }
+        found
+    }
+
+    /// Runs of at least `min_run` stitches of `block`, each long enough and
+    /// turning no more than allowed from the one before
+    fn straight_runs(&self, block: Range<usize>, options: &UnderlayOptions) -> Vec<Range<usize>> {
+        let min_length = options.min_stitch_mm * UNITS_PER_MM;
+        let max_turn = options.max_turn_degrees.to_radians().cos();
+        let vector = |index: usize| {
+            let (from, to) = (&self.stitches[index - 1], &self.stitches[index]);
+            (to.x - from.x, to.y - from.y)
+        };
+        let long = |index: usize| {
+            index > 0 && self.stitches[index].command == StitchCommand::Stitch && {
+                let (dx, dy) = vector(index);
+                dx.hypot(dy) >= min_length
+            }
+        };
+        let straight = |index: usize| {
+            let ((ux, uy), (vx, vy)) = (vector(index - 1), vector(index));
+            (ux * vx + uy * vy) / (ux.hypot(uy) * vx.hypot(vy)) >= max_turn
+        };
+
+        let mut runs = Vec::new();
+        let mut start = None;
+        for index in block.clone() {
+            if start.is_some() && long(index) && straight(index) {
+                continue;
+            }
+            if let Some(begin) = start.take() {
+                if index - begin >= options.min_run.max(1) {
+                    runs.push(begin..index);
+                }
+            }
+            start = long(index).then_some(index);
+        }
+        if let Some(begin) = start {
+            if block.end - begin >= options.min_run.max(1) {
+                runs.push(begin..block.end);
+            }
+        }
+        runs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StitchCommand::*;
+
+    /// A 20 x 10mm block: a centre walk down x = 11mm, then fill rows 0.4mm
+    /// apart sewn across it in 2.5mm stitches
+    fn fill_over_underlay(with_underlay: bool) -> Pattern {
+        let mut pattern = Pattern::new();
+        if with_underlay {
+            for y in [2.0, 26.0, 50.0, 74.0, 98.0] {
+                pattern.add_stitch(110.0, y, Stitch);
+            }
+            pattern.add_stitch(0.0, 0.0, Move);
+        }
+        for row in 0..26 {
+            let y = row as f64 * 4.0;
+            let xs: Vec<f64> = (0..=8).map(|i| i as f64 * 25.0).collect();
+            let xs: Vec<f64> = if row % 2 == 0 {
+                xs
+            } else {
+                xs.into_iter().rev().collect()
+            };
+            for x in xs {
+                pattern.add_stitch(x, y, Stitch);
+            }
+        }
+        pattern.add_stitch(0.0, 100.0, End);
+        pattern.calculate_color_blocks();
+        pattern
+    }
+
+    #[test]
+    fn test_finds_walk_under_fill() {
+        let pattern = fill_over_underlay(true);
+        let runs = pattern.detect_underlay(&UnderlayOptions::default());
+        assert_eq!(
+            runs,
+            vec![UnderlayRun {
+                block: 0,
+                start: 1,
+                end: 5,
+                confidence: 1.0,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_leaves_fill_alone() {
+        // The rows are straight runs too, but nothing crosses them
+        let pattern = fill_over_underlay(false);
+        assert!(pattern
+            .detect_underlay(&UnderlayOptions::default())
+            .is_empty());
+
+        // With no later stitching a walk is just a walk
+        let mut walk = Pattern::new();
+        for y in [2.0, 26.0, 50.0, 74.0, 98.0] {
+            walk.add_stitch(110.0, y, Stitch);
+        }
+        walk.calculate_color_blocks();
+        assert!(walk.detect_underlay(&UnderlayOptions::default()).is_empty());
+    }
+}
@@ -0,0 +1,222 @@
+// batch.rs - Folder-wide conversion of design files with a per-file report
+
+use crate::dst::ParseWarning;
+use crate::export::{ExportFormat, ExportOptions};
+use crate::format::DesignFormat;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Settings for `convert_batch`
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct BatchOptions {
+    /// Also convert files in subfolders, mirroring them under the output folder
+    pub recursive: bool,
+    /// List what would be converted without reading or writing any design
+    pub dry_run: bool,
+    pub export: ExportOptions,
+}
+
+/// Outcome of one file in a batch
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BatchFileReport {
+    pub input: String,
+    pub output: String,
+    /// True once written, or for every file of a dry run
+    pub success: bool,
+    pub warnings: Vec<ParseWarning>,
+    pub error: Option<String>,
+}
+
+/// Whether `path` has the extension of a supported design format
+pub fn is_design_file(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    DesignFormat::from_extension(&extension).is_some()
+}
+
+/// Every file under `dir` with a supported design extension, sorted by path
+pub fn find_design_files(dir: &Path, recursive: bool) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(current) = pending.pop() {
+        for entry in fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                if recursive {
+                    pending.push(path);
+                }
+                continue;
+            }
+            if is_design_file(&path) {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Where `file` from `input_dir` lands in `output_dir` once converted
+pub fn output_path(
+    input_dir: &Path,
+    output_dir: &Path,
+    file: &Path,
+    format: ExportFormat,
+) -> PathBuf {
+    let relative = file.strip_prefix(input_dir).unwrap_or(file);
+    output_dir.join(relative).with_extension(format.extension())
+}
+
+/// Convert every design file in `input_dir` to `format` under `output_dir`
+///
+/// `convert` reads one file and returns the encoded output with the parse
+/// warnings; a failing file is recorded in the report and the batch moves on.
+/// `progress` is called with the file about to be converted, its position and
+/// the total. Only an unreadable input folder fails the whole batch.
+pub fn convert_batch(
+    input_dir: &Path,
+    output_dir: &Path,
+    format: ExportFormat,
+    options: &BatchOptions,
+    mut convert: impl FnMut(&Path) -> Result<(Vec<u8>, Vec<ParseWarning>), String>,
+    mut progress: impl FnMut(&Path, usize, usize),
+) -> io::Result<Vec<BatchFileReport>> {
+    let files = find_design_files(input_dir, options.recursive)?;
+    let total = files.len();
+
+    let reports = files
+        .iter()
+        .enumerate()
+        .map(|(index, file)| {
+            progress(file, index, total);
+            let output = output_path(input_dir, output_dir, file, format);
+            let mut report = BatchFileReport {
+                input: file.to_string_lossy().into_owned(),
+                output: output.to_string_lossy().into_owned(),
+                success: true,
+                warnings: Vec::new(),
+                error: None,
+            };
+            if options.dry_run {
+                return report;
+            }
+
+            let written = convert(file).and_then(|(data, warnings)| {
+                report.warnings = warnings;
+                output
+                    .parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|_| fs::write(&output, data))
+                    .map_err(|e| format!("Failed to write file: {}", e))
+            });
+            if let Err(error) = written {
+                report.success = false;
+                report.error = Some(error);
+            }
+            report
+        })
+        .collect();
+
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::{parse_dst, write_dst, ParseOptions, Pattern, StitchCommand};
+
+    /// A fresh scratch folder under the system temp directory
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("embrocad-batch-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        dir
+    }
+
+    fn dst_bytes() -> Vec<u8> {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(0.0, 0.0, StitchCommand::Stitch);
+        pattern.add_stitch(20.0, 10.0, StitchCommand::Stitch);
+        pattern.add_stitch(20.0, 10.0, StitchCommand::End);
+        write_dst(&pattern)
+    }
+
+    fn dst_to_dst(path: &Path) -> Result<(Vec<u8>, Vec<ParseWarning>), String> {
+        let data = fs::read(path).map_err(|e| e.to_string())?;
+        let pattern = parse_dst(&data, &ParseOptions::default()).map_err(|e| e.to_string())?;
+        Ok((write_dst(&pattern), pattern.warnings))
+    }
+
+    #[test]
+    fn test_keeps_going_after_a_failure() {
+        let input = scratch("convert");
+        let output = input.join("out");
+        fs::write(input.join("a.dst"), dst_bytes()).unwrap();
+        fs::write(input.join("b.DST"), b"not a design").unwrap();
+        fs::write(input.join("notes.txt"), b"ignored").unwrap();
+        fs::write(input.join("nested").join("c.dst"), dst_bytes()).unwrap();
+
+        let mut seen = Vec::new();
+        let reports = convert_batch(
+            &input,
+            &output,
+            ExportFormat::Dst,
+            &BatchOptions {
+                recursive: true,
+                ..BatchOptions::default()
+            },
+            dst_to_dst,
+            |file, _, total| seen.push((file.file_name().unwrap().to_owned(), total)),
+        )
+        .unwrap();
+
+        assert_eq!(seen.len(), 3);
+        assert!(seen.iter().all(|&(_, total)| total == 3));
+        let outcomes: Vec<bool> = reports.iter().map(|r| r.success).collect();
+        assert_eq!(outcomes, vec![true, false, true]);
+        assert!(reports[1].error.is_some());
+        assert!(output.join("a.dst").exists());
+        assert!(output.join("nested").join("c.dst").exists());
+
+        fs::remove_dir_all(&input).unwrap();
+    }
+
+    #[test]
+    fn test_dry_run_writes_nothing() {
+        let input = scratch("dry-run");
+        let output = input.join("out");
+        fs::write(input.join("a.dst"), dst_bytes()).unwrap();
+        fs::write(input.join("nested").join("c.dst"), dst_bytes()).unwrap();
+
+        let reports = convert_batch(
+            &input,
+            &output,
+            ExportFormat::Pes,
+            &BatchOptions {
+                dry_run: true,
+                ..BatchOptions::default()
+            },
+            |_| panic!("a dry run converts nothing"),
+            |_, _, _| {},
+        )
+        .unwrap();
+
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].output.ends_with("a.pes"));
+        assert!(reports[0].success);
+        assert!(!output.exists());
+
+        fs::remove_dir_all(&input).unwrap();
+    }
+}
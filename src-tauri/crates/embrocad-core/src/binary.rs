@@ -0,0 +1,130 @@
+// binary.rs - Bounds-checked byte cursor shared by the binary format parsers
+
+/// Sequential reader over a byte slice where every read returns None past the end
+pub struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+#[allow(dead_code)]
+impl<'a> ByteReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// Current offset from the start of the data
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    /// Move to an absolute offset; fails if it lies beyond the end
+    pub fn seek(&mut self, pos: usize) -> Option<()> {
+        (pos <= self.data.len()).then(|| self.pos = pos)
+    }
+
+    pub fn skip(&mut self, count: usize) -> Option<()> {
+        self.seek(self.pos.checked_add(count)?)
+    }
+
+    pub fn bytes(&mut self, count: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(count)?;
+        let bytes = self.data.get(self.pos..end)?;
+        self.pos = end;
+        Some(bytes)
+    }
+
+    /// Everything from the current position to the end
+    pub fn rest(&mut self) -> &'a [u8] {
+        let rest = self.data.get(self.pos..).unwrap_or(&[]);
+        self.pos = self.data.len();
+        rest
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+
+    pub fn i8(&mut self) -> Option<i8> {
+        self.u8().map(|b| b as i8)
+    }
+
+    pub fn u16_le(&mut self) -> Option<u16> {
+        self.bytes(2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    pub fn u16_be(&mut self) -> Option<u16> {
+        self.bytes(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    pub fn i16_le(&mut self) -> Option<i16> {
+        self.u16_le().map(|v| v as i16)
+    }
+
+    pub fn i16_be(&mut self) -> Option<i16> {
+        self.u16_be().map(|v| v as i16)
+    }
+
+    pub fn u24_be(&mut self) -> Option<u32> {
+        self.bytes(3)
+            .map(|b| u32::from_be_bytes([0, b[0], b[1], b[2]]))
+    }
+
+    pub fn i24_le(&mut self) -> Option<i32> {
+        // Shift the sign bit into place, then back down with sign extension
+        self.bytes(3)
+            .map(|b| i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8)
+    }
+
+    pub fn u32_le(&mut self) -> Option<u32> {
+        self.bytes(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub fn u32_be(&mut self) -> Option<u32> {
+        self.bytes(4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub fn i32_le(&mut self) -> Option<i32> {
+        self.u32_le().map(|v| v as i32)
+    }
+
+    pub fn i32_be(&mut self) -> Option<i32> {
+        self.u32_be().map(|v| v as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_past_end_return_none() {
+        let mut reader = ByteReader::new(&[0x12, 0x34, 0x56]);
+
+        assert_eq!(reader.u16_be(), Some(0x1234));
+        assert_eq!(reader.u16_le(), None);
+        assert_eq!(reader.position(), 2);
+        assert_eq!(reader.u8(), Some(0x56));
+        assert_eq!(reader.remaining(), 0);
+        assert!(reader.seek(4).is_none());
+        assert!(reader.skip(usize::MAX).is_none());
+    }
+
+    #[test]
+    fn test_signed_reads() {
+        let mut reader = ByteReader::new(&[0xFF, 0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0x80]);
+
+        assert_eq!(reader.i16_be(), Some(-2));
+        assert_eq!(reader.i32_le(), Some(-1));
+        assert_eq!(reader.i8(), Some(-128));
+
+        let mut reader = ByteReader::new(&[0xFE, 0xFF, 0xFF, 0x10, 0x00, 0x00]);
+        assert_eq!(reader.i24_le(), Some(-2));
+        assert_eq!(reader.i24_le(), Some(16));
+    }
+}
@@ -0,0 +1,89 @@
+// duplicates.rs - Collapse repeated penetrations at the same coordinate
+
+use crate::dst::{Pattern, StitchCommand};
+
+/// Keep two penetrations per point by default so lock stitches survive
+pub const DEFAULT_DUPLICATE_KEEP: usize = 2;
+
+impl Pattern {
+    /// Shorten runs of identical-coordinate Stitch records to `keep` records,
+    /// returning how many were dropped
+    pub fn collapse_duplicate_stitches(&mut self, keep: usize) -> usize {
+        let keep = keep.max(1);
+        let before = self.stitches.len();
+
+        let mut run = 0;
+        let mut previous: Option<(f64, f64)> = None;
+        self.stitches.retain(|stitch| {
+            if stitch.command != StitchCommand::Stitch {
+                previous = None;
+                return true;
+            }
+            if previous == Some((stitch.x, stitch.y)) {
+                run += 1;
+            } else {
+                run = 1;
+                previous = Some((stitch.x, stitch.y));
+            }
+            run <= keep
+        });
+
+        let removed = before - self.stitches.len();
+        if removed > 0 {
+            self.calculate_bounds();
+            self.calculate_statistics();
+            self.calculate_color_blocks();
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_run(repeats: usize) -> Pattern {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(0.0, 0.0, StitchCommand::Stitch);
+        for _ in 0..repeats {
+            pattern.add_stitch(10.0, 10.0, StitchCommand::Stitch);
+        }
+        pattern.add_stitch(20.0, 10.0, StitchCommand::Stitch);
+        pattern.add_stitch(20.0, 10.0, StitchCommand::End);
+        pattern
+    }
+
+    #[test]
+    fn test_run_of_ten_collapses_to_two() {
+        let mut pattern = with_run(10);
+
+        assert_eq!(
+            pattern.collapse_duplicate_stitches(DEFAULT_DUPLICATE_KEEP),
+            8
+        );
+        assert_eq!(pattern.stitches.len(), 5);
+        assert_eq!(pattern.stitches[2].x, 10.0);
+        assert_eq!(pattern.stitches[3].x, 20.0);
+    }
+
+    #[test]
+    fn test_tie_off_survives_with_keep_three() {
+        let mut pattern = with_run(3);
+        assert_eq!(pattern.collapse_duplicate_stitches(3), 0);
+        assert_eq!(pattern.stitches.len(), 6);
+    }
+
+    #[test]
+    fn test_commands_break_runs() {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(5.0, 5.0, StitchCommand::Stitch);
+        pattern.add_stitch(5.0, 5.0, StitchCommand::Stitch);
+        pattern.add_stitch(5.0, 5.0, StitchCommand::ColorChange);
+        pattern.add_stitch(5.0, 5.0, StitchCommand::Stitch);
+        pattern.add_stitch(5.0, 5.0, StitchCommand::Stitch);
+
+        assert_eq!(pattern.collapse_duplicate_stitches(2), 0);
+        assert_eq!(pattern.collapse_duplicate_stitches(1), 2);
+        assert_eq!(pattern.color_changes, 1);
+    }
+}
@@ -0,0 +1,101 @@
+// mod.rs - Cleanup filters for purchased designs with problem stitches
+
+mod duplicates;
+mod small;
+mod ties;
+mod trims;
+
+pub use duplicates::DEFAULT_DUPLICATE_KEEP;
+pub use ties::{tie_stitches, TieStyle, DEFAULT_TIE_LENGTH_MM};
+pub use trims::DEFAULT_TRIM_JUMP_MM;
+
+use crate::analysis::DEFAULT_SHORT_STITCH_MM;
+use crate::dst::Pattern;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Which filters `cleanup` runs, and their thresholds
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct CleanupOptions {
+    pub remove_small_stitches: bool,
+    pub min_stitch_mm: f64,
+    pub collapse_duplicates: bool,
+    /// Penetrations kept at one coordinate; 2 keeps lock stitches intact
+    pub duplicate_keep: usize,
+    /// Add lock stitches at block starts and before trims
+    pub insert_ties: bool,
+    pub tie_style: TieStyle,
+    pub tie_length_mm: f64,
+    /// Add trims before long jumps, for machines that don't cut on their own
+    pub insert_trims: bool,
+    pub trim_jump_mm: f64,
+    pub trim_at_color_changes: bool,
+}
+
+impl Default for CleanupOptions {
+    fn default() -> Self {
+        Self {
+            remove_small_stitches: true,
+            min_stitch_mm: DEFAULT_SHORT_STITCH_MM,
+            collapse_duplicates: true,
+            duplicate_keep: DEFAULT_DUPLICATE_KEEP,
+            insert_ties: false,
+            tie_style: TieStyle::default(),
+            tie_length_mm: DEFAULT_TIE_LENGTH_MM,
+            insert_trims: false,
+            trim_jump_mm: DEFAULT_TRIM_JUMP_MM,
+            trim_at_color_changes: false,
+        }
+    }
+}
+
+/// What the filters changed
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct CleanupSummary {
+    pub stitches_before: usize,
+    pub stitches_after: usize,
+    pub small_stitches_removed: usize,
+    pub duplicate_stitches_removed: usize,
+    pub ties_inserted: usize,
+    pub trims_inserted: usize,
+}
+
+/// A cleaned pattern plus a summary for the UI
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct CleanupResult {
+    pub pattern: Pattern,
+    pub summary: CleanupSummary,
+}
+
+/// Run the enabled filters over `pattern`
+pub fn cleanup(mut pattern: Pattern, options: &CleanupOptions) -> CleanupResult {
+    let mut summary = CleanupSummary {
+        stitches_before: pattern.stitches.len(),
+        ..CleanupSummary::default()
+    };
+
+    if options.collapse_duplicates {
+        summary.duplicate_stitches_removed =
+            pattern.collapse_duplicate_stitches(options.duplicate_keep);
+    }
+    if options.remove_small_stitches {
+        summary.small_stitches_removed = pattern.remove_small_stitches(options.min_stitch_mm);
+    }
+
+    if options.insert_trims {
+        summary.trims_inserted =
+            pattern.insert_trims(options.trim_jump_mm, options.trim_at_color_changes);
+    }
+    // Ties go last so the small stitch filter can't take them apart, and
+    // after trims so the new trims get tie-offs
+    if options.insert_ties {
+        summary.ties_inserted = pattern.insert_ties(options.tie_style, options.tie_length_mm);
+    }
+
+    summary.stitches_after = pattern.stitches.len();
+    CleanupResult { pattern, summary }
+}
@@ -0,0 +1,153 @@
+// small.rs - Merge sub-threshold stitches into their neighbours
+
+use crate::dst::{Pattern, Stitch, StitchCommand, UNITS_PER_MM};
+
+fn distance(a: &Stitch, b: &Stitch) -> f64 {
+    (a.x - b.x).hypot(a.y - b.y)
+}
+
+impl Pattern {
+    /// Drop stitches shorter than `min_len_mm`, returning how many were removed
+    ///
+    /// Only the interior of a run of consecutive Stitch records is touched, so
+    /// jumps, trims and color changes stay put and each run keeps its first and
+    /// last penetration. A dropped stitch's length is folded into the next one.
+    /// Exact repeats of a kept point are left for `collapse_duplicate_stitches`.
+    pub fn remove_small_stitches(&mut self, min_len_mm: f64) -> usize {
+        let min_len = min_len_mm * UNITS_PER_MM;
+        let mut keep = vec![true; self.stitches.len()];
+
+        let mut start = 0;
+        while start < self.stitches.len() {
+            if self.stitches[start].command != StitchCommand::Stitch {
+                start += 1;
+                continue;
+            }
+            let mut end = start;
+            while end + 1 < self.stitches.len()
+                && self.stitches[end + 1].command == StitchCommand::Stitch
+            {
+                end += 1;
+            }
+
+            let mut anchor = start;
+            for (index, kept) in keep.iter_mut().enumerate().take(end).skip(start + 1) {
+                let length = distance(&self.stitches[anchor], &self.stitches[index]);
+                // Repeats of a kept point are lock stitches, left to the duplicate filter
+                let repeat = length == 0.0 && anchor == index - 1;
+                if length < min_len && !repeat {
+                    *kept = false;
+                } else {
+                    anchor = index;
+                }
+            }
+            // The run's last stitch is fixed, so merge a short tail into it instead
+            let tail = distance(&self.stitches[anchor], &self.stitches[end]);
+            if end > start + 1 && anchor != start && tail > 0.0 && tail < min_len {
+                keep[anchor] = false;
+            }
+
+            start = end + 1;
+        }
+
+        let removed = keep.iter().filter(|&&k| !k).count();
+        if removed > 0 {
+            let mut flags = keep.into_iter();
+            self.stitches.retain(|_| flags.next().unwrap_or(true));
+            self.calculate_bounds();
+            self.calculate_statistics();
+            self.calculate_color_blocks();
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StitchCommand::*;
+
+    fn build(records: &[(f64, f64, StitchCommand)]) -> Pattern {
+        let mut pattern = Pattern::new();
+        for &(x, y, command) in records {
+            pattern.add_stitch(x, y, command);
+        }
+        pattern
+    }
+
+    fn points(pattern: &Pattern) -> Vec<(f64, f64, StitchCommand)> {
+        pattern
+            .stitches
+            .iter()
+            .map(|s| (s.x, s.y, s.command))
+            .collect()
+    }
+
+    #[test]
+    fn test_merges_short_interior_stitches() {
+        let mut pattern = build(&[
+            (0.0, 0.0, Stitch),
+            (1.0, 0.0, Stitch),
+            (2.0, 0.0, Stitch),
+            (20.0, 0.0, Stitch),
+            (21.0, 0.0, Stitch),
+            (40.0, 0.0, Stitch),
+            (40.0, 0.0, End),
+        ]);
+
+        assert_eq!(pattern.remove_small_stitches(0.3), 3);
+        assert_eq!(
+            points(&pattern),
+            vec![
+                (0.0, 0.0, Stitch),
+                (20.0, 0.0, Stitch),
+                (40.0, 0.0, Stitch),
+                (40.0, 0.0, End)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_short_tail_merges_into_last_stitch() {
+        let mut pattern = build(&[(0.0, 0.0, Stitch), (20.0, 0.0, Stitch), (21.0, 0.0, Stitch)]);
+
+        assert_eq!(pattern.remove_small_stitches(0.3), 1);
+        assert_eq!(
+            points(&pattern),
+            vec![(0.0, 0.0, Stitch), (21.0, 0.0, Stitch)]
+        );
+    }
+
+    #[test]
+    fn test_leaves_lock_stitches() {
+        let records = [
+            (0.0, 0.0, Stitch),
+            (0.0, 0.0, Stitch),
+            (0.0, 0.0, Stitch),
+            (20.0, 0.0, Stitch),
+        ];
+        let mut pattern = build(&records);
+
+        assert_eq!(pattern.remove_small_stitches(0.3), 0);
+        assert_eq!(points(&pattern), records.to_vec());
+    }
+
+    #[test]
+    fn test_keeps_commands_and_block_endpoints() {
+        let records = [
+            (0.0, 0.0, Stitch),
+            (1.0, 0.0, Stitch),
+            (1.0, 0.0, Trim),
+            (50.0, 0.0, Move),
+            (51.0, 0.0, Stitch),
+            (51.0, 0.0, ColorChange),
+            (51.0, 1.0, Stitch),
+            (52.0, 1.0, Stitch),
+            (52.0, 1.0, End),
+        ];
+        let mut pattern = build(&records);
+
+        assert_eq!(pattern.remove_small_stitches(0.3), 0);
+        assert_eq!(points(&pattern), records.to_vec());
+    }
+}
@@ -0,0 +1,228 @@
+// ties.rs - Lock stitch insertion at block starts and before trims
+
+use crate::dst::{Pattern, Stitch, StitchCommand, UNITS_PER_MM};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Lock stitch length used when none is configured
+pub const DEFAULT_TIE_LENGTH_MM: f64 = 0.8;
+
+/// Stitches this close to a boundary count as an existing lock
+const EXISTING_LOCK_RADIUS_MM: f64 = 1.0;
+
+/// Penetrations within the radius that make up an existing lock
+const EXISTING_LOCK_STITCHES: usize = 3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum TieStyle {
+    /// Out, back and out again along the path direction
+    #[default]
+    Back3,
+    /// A small triangle closing on the anchor stitch
+    Triangle,
+}
+
+/// The stitches a tie adds after the anchor at `(x, y)`, heading along `(ux, uy)`
+pub fn tie_stitches(
+    style: TieStyle,
+    (x, y): (f64, f64),
+    (ux, uy): (f64, f64),
+    length: f64,
+) -> [(f64, f64); 3] {
+    let out = (x + ux * length, y + uy * length);
+    match style {
+        TieStyle::Back3 => [out, (x, y), out],
+        TieStyle::Triangle => {
+            // Second corner is the path direction turned by 60 degrees
+            let (sin, cos) = 60f64.to_radians().sin_cos();
+            let corner = (
+                x + (ux * cos - uy * sin) * length,
+                y + (ux * sin + uy * cos) * length,
+            );
+            [out, corner, (x, y)]
+        }
+    }
+}
+
+/// Unit vector from `from` towards `to`, or +X when they coincide
+fn direction(from: &Stitch, to: Option<&Stitch>) -> (f64, f64) {
+    let Some(to) = to else { return (1.0, 0.0) };
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    let length = dx.hypot(dy);
+    if length == 0.0 {
+        (1.0, 0.0)
+    } else {
+        (dx / length, dy / length)
+    }
+}
+
+/// Heading for a tie at `anchor`, or None if the boundary is already locked
+///
+/// Looks forward along the stitches for a tie-in and backward for a tie-off.
+fn tie_heading(stitches: &[Stitch], anchor: usize, forward: bool) -> Option<(f64, f64)> {
+    let radius = EXISTING_LOCK_RADIUS_MM * UNITS_PER_MM;
+    let origin = &stitches[anchor];
+    let is_stitch = |s: &&Stitch| s.command == StitchCommand::Stitch;
+    let run: Vec<&Stitch> = if forward {
+        let run = stitches[anchor..].iter().take_while(is_stitch);
+        run.take(EXISTING_LOCK_STITCHES).collect()
+    } else {
+        let run = stitches[..=anchor].iter().rev().take_while(is_stitch);
+        run.take(EXISTING_LOCK_STITCHES).collect()
+    };
+
+    let nearby = run
+        .iter()
+        .take_while(|s| (s.x - origin.x).hypot(s.y - origin.y) <= radius)
+        .count();
+    if nearby >= EXISTING_LOCK_STITCHES {
+        return None;
+    }
+    Some(direction(origin, run.get(1).copied()))
+}
+
+impl Pattern {
+    /// Add lock stitches at the first stitch of each color block and at the
+    /// last stitch before each Trim, returning how many ties were added
+    ///
+    /// Boundaries that already have three or more stitches within 1mm are
+    /// assumed to be locked by the digitizer and are left alone.
+    pub fn insert_ties(&mut self, style: TieStyle, length_mm: f64) -> usize {
+        let length = length_mm * UNITS_PER_MM;
+        let stitches = &self.stitches;
+        let mut output = Vec::with_capacity(stitches.len());
+        let mut ties = 0;
+        let mut block_started = false;
+
+        for (index, stitch) in stitches.iter().enumerate() {
+            output.push(stitch.clone());
+            match stitch.command {
+                StitchCommand::ColorChange => block_started = false,
+                StitchCommand::Stitch => {
+                    let tie_in = !block_started;
+                    block_started = true;
+                    let tie_off = stitches
+                        .get(index + 1)
+                        .is_some_and(|s| s.command == StitchCommand::Trim);
+
+                    for (wanted, forward) in [(tie_in, true), (tie_off, false)] {
+                        let Some(heading) = wanted
+                            .then(|| tie_heading(stitches, index, forward))
+                            .flatten()
+                        else {
+                            continue;
+                        };
+                        for (x, y) in tie_stitches(style, (stitch.x, stitch.y), heading, length) {
+                            output.push(Stitch::new(x, y, StitchCommand::Stitch));
+                        }
+                        ties += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if ties > 0 {
+            self.stitches = output;
+            self.calculate_bounds();
+            self.calculate_statistics();
+            self.calculate_color_blocks();
+        }
+        ties
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StitchCommand::*;
+
+    fn build(records: &[(f64, f64, StitchCommand)]) -> Pattern {
+        let mut pattern = Pattern::new();
+        for &(x, y, command) in records {
+            pattern.add_stitch(x, y, command);
+        }
+        pattern
+    }
+
+    fn points(pattern: &Pattern) -> Vec<(f64, f64, StitchCommand)> {
+        pattern
+            .stitches
+            .iter()
+            .map(|s| {
+                // Round away float noise from the triangle corner
+                let round = |v: f64| (v * 1e6).round() / 1e6;
+                (round(s.x), round(s.y), s.command)
+            })
+            .collect()
+    }
+
+    const PLAIN_BLOCK: [(f64, f64, StitchCommand); 5] = [
+        (0.0, 0.0, Move),
+        (0.0, 0.0, Stitch),
+        (30.0, 0.0, Stitch),
+        (30.0, 0.0, Trim),
+        (30.0, 0.0, End),
+    ];
+
+    #[test]
+    fn test_back3_ties_on_plain_block() {
+        let mut pattern = build(&PLAIN_BLOCK);
+        assert_eq!(pattern.insert_ties(TieStyle::Back3, 1.0), 2);
+
+        assert_eq!(
+            points(&pattern),
+            vec![
+                (0.0, 0.0, Move),
+                (0.0, 0.0, Stitch),
+                (10.0, 0.0, Stitch),
+                (0.0, 0.0, Stitch),
+                (10.0, 0.0, Stitch),
+                (30.0, 0.0, Stitch),
+                (20.0, 0.0, Stitch),
+                (30.0, 0.0, Stitch),
+                (20.0, 0.0, Stitch),
+                (30.0, 0.0, Trim),
+                (30.0, 0.0, End),
+            ]
+        );
+        // Running again finds the new locks
+        assert_eq!(pattern.insert_ties(TieStyle::Back3, 1.0), 0);
+    }
+
+    #[test]
+    fn test_triangle_ends_on_anchor() {
+        let mut pattern = build(&PLAIN_BLOCK);
+        assert_eq!(pattern.insert_ties(TieStyle::Triangle, 1.0), 2);
+
+        let points = points(&pattern);
+        assert_eq!(points[2], (10.0, 0.0, Stitch));
+        assert_eq!(points[3], (5.0, 8.660254, Stitch));
+        assert_eq!(points[4], (0.0, 0.0, Stitch));
+        assert_eq!(points[8], (30.0, 0.0, Stitch));
+    }
+
+    #[test]
+    fn test_existing_locks_are_kept() {
+        let records = [
+            (0.0, 0.0, Stitch),
+            (5.0, 0.0, Stitch),
+            (0.0, 0.0, Stitch),
+            (40.0, 0.0, Stitch),
+            (35.0, 0.0, Stitch),
+            (40.0, 0.0, Stitch),
+            (40.0, 0.0, Trim),
+            (40.0, 0.0, ColorChange),
+            (40.0, 10.0, Stitch),
+            (60.0, 10.0, Stitch),
+        ];
+        let mut pattern = build(&records);
+
+        // Only the second block's start needs a tie
+        assert_eq!(pattern.insert_ties(TieStyle::Back3, 1.0), 1);
+        assert_eq!(pattern.stitches.len(), records.len() + 3);
+        assert_eq!(points(&pattern)[..9], records[..9]);
+    }
+}
@@ -0,0 +1,135 @@
+// trims.rs - Explicit trims for machines that don't cut on long jumps
+
+use crate::dst::{Pattern, Stitch, StitchCommand, UNITS_PER_MM};
+
+/// Jumps longer than this leave a thread tail worth cutting
+pub const DEFAULT_TRIM_JUMP_MM: f64 = 10.0;
+
+impl Pattern {
+    /// Insert a Trim before every jump run longer than `min_jump_mm`, and
+    /// before every color change when `at_color_changes` is set
+    ///
+    /// Returns how many trims were added; a spot that already has a Trim
+    /// right before it is left alone.
+    pub fn insert_trims(&mut self, min_jump_mm: f64, at_color_changes: bool) -> usize {
+        let min_jump = min_jump_mm * UNITS_PER_MM;
+        let mut output: Vec<Stitch> = Vec::with_capacity(self.stitches.len());
+        let mut inserted = 0;
+
+        let mut index = 0;
+        while index < self.stitches.len() {
+            let stitch = &self.stitches[index];
+            let (x, y) = output.last().map_or((0.0, 0.0), |s| (s.x, s.y));
+            let trimmed = output
+                .last()
+                .is_some_and(|s| s.command == StitchCommand::Trim);
+
+            let wants_trim = match stitch.command {
+                StitchCommand::Move => {
+                    let run = self.stitches[index..]
+                        .iter()
+                        .take_while(|s| s.command == StitchCommand::Move)
+                        .count();
+                    let landing = &self.stitches[index + run - 1];
+                    let wants = (landing.x - x).hypot(landing.y - y) > min_jump;
+                    output.extend_from_slice(&self.stitches[index..index + run]);
+                    if wants && !trimmed {
+                        output.insert(output.len() - run, Stitch::new(x, y, StitchCommand::Trim));
+                        inserted += 1;
+                    }
+                    index += run;
+                    continue;
+                }
+                StitchCommand::ColorChange => at_color_changes,
+                _ => false,
+            };
+
+            // Nothing to cut before the first stitch
+            if wants_trim && !trimmed && !output.is_empty() {
+                output.push(Stitch::new(x, y, StitchCommand::Trim));
+                inserted += 1;
+            }
+            output.push(stitch.clone());
+            index += 1;
+        }
+
+        if inserted > 0 {
+            self.stitches = output;
+            self.calculate_statistics();
+            self.calculate_color_blocks();
+        }
+        inserted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StitchCommand::*;
+
+    fn build(records: &[(f64, f64, StitchCommand)]) -> Pattern {
+        let mut pattern = Pattern::new();
+        for &(x, y, command) in records {
+            pattern.add_stitch(x, y, command);
+        }
+        pattern
+    }
+
+    fn commands(pattern: &Pattern) -> Vec<StitchCommand> {
+        pattern.stitches.iter().map(|s| s.command).collect()
+    }
+
+    #[test]
+    fn test_trims_long_jumps_only() {
+        let mut pattern = build(&[
+            (0.0, 0.0, Stitch),
+            (50.0, 0.0, Move),
+            (50.0, 10.0, Stitch),
+            (100.0, 10.0, Move),
+            (160.0, 10.0, Move),
+            (160.0, 20.0, Stitch),
+        ]);
+
+        // The 5mm jump stays, the 11mm two-record run gets a trim
+        assert_eq!(pattern.insert_trims(DEFAULT_TRIM_JUMP_MM, false), 1);
+        assert_eq!(
+            commands(&pattern),
+            vec![Stitch, Move, Stitch, Trim, Move, Move, Stitch]
+        );
+        let trim = &pattern.stitches[3];
+        assert_eq!((trim.x, trim.y), (50.0, 10.0));
+    }
+
+    #[test]
+    fn test_trims_at_color_changes() {
+        let mut pattern = build(&[
+            (0.0, 0.0, Stitch),
+            (0.0, 0.0, ColorChange),
+            (5.0, 0.0, Stitch),
+            (5.0, 0.0, End),
+        ]);
+
+        assert_eq!(pattern.insert_trims(DEFAULT_TRIM_JUMP_MM, false), 0);
+        assert_eq!(pattern.insert_trims(DEFAULT_TRIM_JUMP_MM, true), 1);
+        assert_eq!(
+            commands(&pattern),
+            vec![Stitch, Trim, ColorChange, Stitch, End]
+        );
+    }
+
+    #[test]
+    fn test_existing_trims_are_not_doubled() {
+        let records = [
+            (0.0, 0.0, Stitch),
+            (0.0, 0.0, Trim),
+            (500.0, 0.0, Move),
+            (500.0, 0.0, Trim),
+            (500.0, 0.0, ColorChange),
+            (500.0, 5.0, Stitch),
+        ];
+        let mut pattern = build(&records);
+
+        assert_eq!(pattern.insert_trims(DEFAULT_TRIM_JUMP_MM, true), 0);
+        assert_eq!(pattern.stitches.len(), records.len());
+    }
+}
@@ -0,0 +1,73 @@
+// mod.rs - Companion color files that digitizing software writes next to designs
+
+mod parser;
+
+pub use parser::{parse_col, parse_edr, parse_inf, CompanionError};
+
+use crate::dst::ThreadColor;
+#[cfg(feature = "fs")]
+use std::fs;
+#[cfg(feature = "fs")]
+use std::path::{Path, PathBuf};
+
+/// Extensions looked for next to a design, best first: INF carries thread
+/// names, the others only RGB
+pub const COMPANION_EXTENSIONS: [&str; 4] = ["inf", "edr", "col", "rgb"];
+
+/// Parse a companion file by its lowercase extension
+pub fn parse_companion(extension: &str, data: &[u8]) -> Result<Vec<ThreadColor>, CompanionError> {
+    match extension {
+        "inf" => parse_inf(data),
+        "col" => parse_col(data),
+        _ => Ok(parse_edr(data)),
+    }
+}
+
+/// Thread colors from the first usable companion file beside `design`,
+/// with that file's path
+///
+/// Both lower- and uppercase extensions are tried. Files that fail to parse
+/// or hold no colors are skipped.
+#[cfg(feature = "fs")]
+pub fn find_companion_colors(design: &Path) -> Option<(PathBuf, Vec<ThreadColor>)> {
+    COMPANION_EXTENSIONS.iter().find_map(|&extension| {
+        [extension.to_string(), extension.to_uppercase()]
+            .iter()
+            .map(|candidate| design.with_extension(candidate))
+            .find_map(|path| {
+                let data = fs::read(&path).ok()?;
+                let colors = parse_companion(extension, &data).ok()?;
+                (!colors.is_empty()).then_some((path, colors))
+            })
+    })
+}
+
+#[cfg(all(test, feature = "fs"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_companion_colors() {
+        let dir = std::env::temp_dir().join(format!("embrocad-companion-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let design = dir.join("rose.dst");
+        assert_eq!(find_companion_colors(&design), None);
+
+        fs::write(dir.join("rose.RGB"), [1, 2, 3, 0]).unwrap();
+        fs::write(dir.join("rose.col"), b"1\n0,200,16,46\n").unwrap();
+        // A broken INF is passed over
+        fs::write(dir.join("rose.inf"), [0, 0, 0, 1]).unwrap();
+
+        let (path, colors) = find_companion_colors(&design).unwrap();
+        assert_eq!(path, dir.join("rose.col"));
+        assert_eq!(colors, vec![ThreadColor::new([200, 16, 46])]);
+
+        fs::remove_file(dir.join("rose.col")).unwrap();
+        let (path, colors) = find_companion_colors(&design).unwrap();
+        assert!(path.extension().unwrap().eq_ignore_ascii_case("rgb"));
+        assert_eq!(colors[0].rgb, [1, 2, 3]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
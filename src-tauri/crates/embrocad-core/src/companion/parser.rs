@@ -0,0 +1,180 @@
+// parser.rs - Parsers for the EDR, RGB, COL and INF companion color files
+
+use crate::binary::ByteReader;
+use crate::dst::ThreadColor;
+
+/// Error type for companion color file parsing
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum CompanionError {
+    #[error("Invalid COL file: not text")]
+    InvalidText,
+    #[error("Invalid color on line {line} of COL file")]
+    InvalidLine { line: usize },
+    #[error("INF file is truncated")]
+    Truncated,
+}
+
+/// Parse an Embird EDR color file: four bytes per thread, red, green, blue
+/// and a zero pad byte
+///
+/// RGB files share the layout. A trailing partial entry is ignored.
+pub fn parse_edr(data: &[u8]) -> Vec<ThreadColor> {
+    data.chunks_exact(4)
+        .map(|entry| ThreadColor::new([entry[0], entry[1], entry[2]]))
+        .collect()
+}
+
+/// Parse a COL color file: a line with the thread count, then one
+/// `index,red,green,blue` line per thread
+///
+/// The count is not trusted; every color line is read.
+pub fn parse_col(data: &[u8]) -> Result<Vec<ThreadColor>, CompanionError> {
+    let text = std::str::from_utf8(data).map_err(|_| CompanionError::InvalidText)?;
+    let mut colors = Vec::new();
+
+    for (index, line) in text.lines().enumerate().skip(1) {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let invalid = || CompanionError::InvalidLine { line: index + 1 };
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let [_, red, green, blue] = fields.as_slice() else {
+            return Err(invalid());
+        };
+        let channel = |value: &str| value.parse::<u8>().map_err(|_| invalid());
+        colors.push(ThreadColor::new([
+            channel(red)?,
+            channel(green)?,
+            channel(blue)?,
+        ]));
+    }
+    Ok(colors)
+}
+
+/// Parse an INF color file, the only companion format with thread names
+///
+/// Layout, big-endian: four u32 header fields (version, header size, file
+/// size, thread count), then per thread a u16 entry length that counts
+/// itself, u16 index, red, green, blue, u16 needle, and the NUL-terminated
+/// description and catalog number.
+pub fn parse_inf(data: &[u8]) -> Result<Vec<ThreadColor>, CompanionError> {
+    let mut reader = ByteReader::new(data);
+    reader.skip(12).ok_or(CompanionError::Truncated)?;
+    let count = reader.u32_be().ok_or(CompanionError::Truncated)?;
+
+    let mut colors = Vec::new();
+    for _ in 0..count {
+        let length = reader.u16_be().ok_or(CompanionError::Truncated)? as usize;
+        let entry = length
+            .checked_sub(2)
+            .and_then(|length| reader.bytes(length))
+            .filter(|entry| entry.len() >= 7)
+            .ok_or(CompanionError::Truncated)?;
+
+        // Needle 0 means none was assigned
+        let needle = u8::try_from(u16::from_be_bytes([entry[5], entry[6]]))
+            .ok()
+            .filter(|&needle| needle > 0);
+        let mut strings = entry[7..].split(|&b| b == 0).map(|field| {
+            let text = String::from_utf8_lossy(field).trim().to_string();
+            (!text.is_empty()).then_some(text)
+        });
+        colors.push(ThreadColor {
+            rgb: [entry[2], entry[3], entry[4]],
+            description: strings.next().flatten(),
+            catalog_number: strings.next().flatten(),
+            needle,
+            basting: false,
+        });
+    }
+    Ok(colors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An INF entry as written by digitizing software
+    fn inf_entry(index: u16, rgb: [u8; 3], description: &str, catalog: &str) -> Vec<u8> {
+        let mut entry = Vec::new();
+        entry.extend_from_slice(&index.to_be_bytes());
+        entry.extend_from_slice(&rgb);
+        entry.extend_from_slice(&(index + 1).to_be_bytes());
+        entry.extend_from_slice(description.as_bytes());
+        entry.push(0);
+        entry.extend_from_slice(catalog.as_bytes());
+        entry.push(0);
+
+        let mut data = ((entry.len() + 2) as u16).to_be_bytes().to_vec();
+        data.extend(entry);
+        data
+    }
+
+    fn inf_file(entries: &[Vec<u8>]) -> Vec<u8> {
+        let body: Vec<u8> = entries.concat();
+        let mut data = Vec::new();
+        for field in [1, 8, 16 + body.len() as u32, entries.len() as u32] {
+            data.extend_from_slice(&field.to_be_bytes());
+        }
+        data.extend(body);
+        data
+    }
+
+    #[test]
+    fn test_parse_edr() {
+        let data = [255, 0, 0, 0, 0, 128, 255, 0, 9, 9];
+        let rgbs: Vec<_> = parse_edr(&data).iter().map(|c| c.rgb).collect();
+        assert_eq!(rgbs, vec![[255, 0, 0], [0, 128, 255]]);
+        assert!(parse_edr(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_parse_col() {
+        let data = b"3\r\n0,255,0,0\r\n1, 0, 128, 255\r\n\r\n2,10,20,30\r\n";
+        let rgbs: Vec<_> = parse_col(data).unwrap().iter().map(|c| c.rgb).collect();
+        assert_eq!(rgbs, vec![[255, 0, 0], [0, 128, 255], [10, 20, 30]]);
+
+        assert_eq!(
+            parse_col(b"1\n0,255,0\n"),
+            Err(CompanionError::InvalidLine { line: 2 })
+        );
+        assert_eq!(
+            parse_col(b"1\n0,256,0,0\n"),
+            Err(CompanionError::InvalidLine { line: 2 })
+        );
+        assert_eq!(parse_col(&[0xFF, 0xFE]), Err(CompanionError::InvalidText));
+    }
+
+    #[test]
+    fn test_parse_inf() {
+        let data = inf_file(&[
+            inf_entry(0, [200, 16, 46], "Poinsettia", "1902"),
+            inf_entry(1, [0, 0, 0], "Black", ""),
+        ]);
+        let colors = parse_inf(&data).unwrap();
+
+        assert_eq!(
+            colors[0],
+            ThreadColor {
+                rgb: [200, 16, 46],
+                description: Some("Poinsettia".to_string()),
+                catalog_number: Some("1902".to_string()),
+                needle: Some(1),
+                basting: false,
+            }
+        );
+        assert_eq!(colors[1].description.as_deref(), Some("Black"));
+        assert_eq!(colors[1].catalog_number, None);
+    }
+
+    #[test]
+    fn test_truncated_inf() {
+        let data = inf_file(&[inf_entry(0, [1, 2, 3], "Red", "800")]);
+        assert_eq!(
+            parse_inf(&data[..data.len() - 4]),
+            Err(CompanionError::Truncated)
+        );
+        assert_eq!(parse_inf(&data[..10]), Err(CompanionError::Truncated));
+    }
+}
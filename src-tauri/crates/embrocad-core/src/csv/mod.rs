@@ -0,0 +1,5 @@
+// mod.rs - CSV module exports for the text stitch list parser
+
+mod parser;
+
+pub use parser::parse_csv;
@@ -0,0 +1,304 @@
+// parser.rs - CSV stitch list parser for pyembroidery dumps and plain x,y,command lists
+
+use crate::dst::{ParseWarning, Pattern, StitchCommand, ThreadColor, UNITS_PER_MM};
+
+/// Error type for CSV parsing
+#[derive(Debug, thiserror::Error)]
+pub enum CsvError {
+    #[error("Invalid CSV file: not UTF-8 text")]
+    InvalidText,
+    #[error("Invalid number \"{value}\" on line {line}")]
+    InvalidNumber { line: usize, value: String },
+    #[error("Line {line} has no {column} column")]
+    MissingColumn { line: usize, column: &'static str },
+}
+
+/// Split one CSV line into fields, honouring quotes and doubled quote escapes
+fn split_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields.into_iter().map(|f| f.trim().to_string()).collect()
+}
+
+/// Map a command name from any of the supported dialects
+fn command_from_name(name: &str) -> Option<StitchCommand> {
+    let command = match name.to_ascii_uppercase().as_str() {
+        "STITCH" => StitchCommand::Stitch,
+        "MOVE" | "JUMP" => StitchCommand::Move,
+        "TRIM" => StitchCommand::Trim,
+        "COLOR_CHANGE" | "STOP" => StitchCommand::ColorChange,
+        "SEQUIN_MODE" => StitchCommand::SequinMode,
+        "SEQUIN_EJECT" => StitchCommand::SequinEject,
+        "END" => StitchCommand::End,
+        _ => return None,
+    };
+    Some(command)
+}
+
+fn number(value: &str, line: usize) -> Result<f64, CsvError> {
+    value.parse().map_err(|_| CsvError::InvalidNumber {
+        line,
+        value: value.to_string(),
+    })
+}
+
+/// Column positions taken from a header row, with the coordinate scale
+struct Columns {
+    x: usize,
+    y: usize,
+    command: usize,
+    /// Multiplier from the file's unit to 0.1mm
+    scale: f64,
+}
+
+impl Columns {
+    /// Plain lists without a header are x, y, command in 0.1mm
+    const PLAIN: Columns = Columns {
+        x: 0,
+        y: 1,
+        command: 2,
+        scale: 1.0,
+    };
+
+    /// Read a header row; the `_mm` column names select millimeters
+    fn from_header(fields: &[String]) -> Option<Self> {
+        let find = |names: &[&str]| {
+            fields
+                .iter()
+                .position(|f| names.contains(&f.to_ascii_lowercase().as_str()))
+        };
+        let command = find(&["command", "type"])?;
+        if let (Some(x), Some(y)) = (find(&["x_mm"]), find(&["y_mm"])) {
+            return Some(Self {
+                x,
+                y,
+                command,
+                scale: UNITS_PER_MM,
+            });
+        }
+        Some(Self {
+            x: find(&["x"])?,
+            y: find(&["y"])?,
+            command,
+            scale: 1.0,
+        })
+    }
+}
+
+/// Parse a CSV stitch list from bytes
+///
+/// Three layouts are accepted:
+/// - pyembroidery dumps, whose stitch rows start with `*` followed by an
+///   optional index, the command, X and Y in 0.1mm, and whose `$` rows list
+///   thread colors;
+/// - lists with a header row naming `x`, `y` and `command` columns (or
+///   `x_mm` and `y_mm` for millimeters), as written by `export_csv`, with an
+///   optional `label` row before the header;
+/// - headerless `x,y,command` rows in 0.1mm.
+///
+/// Blank lines and lines starting with `#` are skipped. Rows with an unknown
+/// command are dropped with a warning.
+pub fn parse_csv(data: &[u8]) -> Result<Pattern, CsvError> {
+    let text = std::str::from_utf8(data).map_err(|_| CsvError::InvalidText)?;
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+
+    let mut pattern = Pattern::new();
+    let mut columns: Option<Columns> = None;
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = split_fields(line);
+
+        // pyembroidery rows are tagged by their first field
+        let (command, x, y, scale) = match fields[0].as_str() {
+            "#" | ">" | "@" => continue,
+            "$" => {
+                if let Some(rgb) = fields.get(2).and_then(|hex| parse_hex_color(hex)) {
+                    let mut color = ThreadColor::new(rgb);
+                    color.description = fields.get(3).filter(|d| !d.is_empty()).cloned();
+                    color.catalog_number = fields.get(4).filter(|c| !c.is_empty()).cloned();
+                    pattern.metadata.thread_colors.push(color);
+                }
+                continue;
+            }
+            "*" => {
+                // Newer dumps put the stitch index before the command
+                let start = if fields.len() > 4 { 2 } else { 1 };
+                let get = |offset: usize, column| {
+                    fields.get(start + offset).ok_or(CsvError::MissingColumn {
+                        line: line_number,
+                        column,
+                    })
+                };
+                (get(0, "command")?, get(1, "x")?, get(2, "y")?, 1.0)
+            }
+            first if first.eq_ignore_ascii_case("label") && columns.is_none() => {
+                pattern.metadata.label = fields.get(1).cloned();
+                continue;
+            }
+            _ => {
+                // The first data row is either a header or already a stitch
+                if columns.is_none() {
+                    if let Some(header) = Columns::from_header(&fields) {
+                        columns = Some(header);
+                        continue;
+                    }
+                }
+                let layout = columns.get_or_insert(Columns::PLAIN);
+                let get = |position: usize, column| {
+                    fields.get(position).ok_or(CsvError::MissingColumn {
+                        line: line_number,
+                        column,
+                    })
+                };
+                (
+                    get(layout.command, "command")?,
+                    get(layout.x, "x")?,
+                    get(layout.y, "y")?,
+                    layout.scale,
+                )
+            }
+        };
+
+        let Some(command) = command_from_name(command) else {
+            pattern.warnings.push(ParseWarning::UnknownCommand {
+                line: line_number,
+                command: command.clone(),
+            });
+            continue;
+        };
+        let x = number(x, line_number)? * scale;
+        let y = number(y, line_number)? * scale;
+        pattern.add_stitch(x, y, command);
+    }
+
+    pattern.metadata.stitch_count = Some(pattern.stitches.len() as u32);
+    pattern.metadata.color_count = Some(pattern.color_changes);
+
+    // Calculate bounds, statistics and color blocks
+    pattern.calculate_bounds();
+    pattern.calculate_statistics();
+    pattern.calculate_color_blocks();
+
+    Ok(pattern)
+}
+
+/// Parse a `#rrggbb` color
+fn parse_hex_color(value: &str) -> Option<[u8; 3]> {
+    let hex = value.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{export_csv, StitchListOptions};
+    use StitchCommand::*;
+
+    fn points(pattern: &Pattern) -> Vec<(f64, f64, StitchCommand)> {
+        pattern
+            .stitches
+            .iter()
+            .map(|s| (s.x, s.y, s.command))
+            .collect()
+    }
+
+    #[test]
+    fn test_export_csv_round_trips() {
+        let mut original = Pattern::new();
+        for &(x, y, command) in &[
+            (0.0, 0.0, Stitch),
+            (25.0, -13.0, Stitch),
+            (25.0, -13.0, Trim),
+            (140.0, 60.0, Move),
+            (140.0, 60.0, ColorChange),
+            (150.0, 61.0, Stitch),
+            (150.0, 61.0, End),
+        ] {
+            original.add_stitch(x, y, command);
+        }
+        original.metadata.label = Some("Rose, \"small\"".to_string());
+
+        let csv = export_csv(&original, &StitchListOptions::default());
+        let parsed = parse_csv(csv.as_bytes()).unwrap();
+
+        assert_eq!(parsed.metadata.label, original.metadata.label);
+        assert_eq!(points(&parsed), points(&original));
+        assert!(parsed.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_pyembroidery_dump() {
+        let data = b"\"#\",\"[VAR_NAME]\",\"[VAR_VALUE]\"\n\
+            \">\",\"STITCH_COUNT:\",\"3\"\n\
+            \"$\",\"0\",\"#ff0000\",\"Red\",\"1147\"\n\
+            \"*\",\"0\",\"JUMP\",\"10.0\",\"-5.0\"\n\
+            \"*\",\"1\",\"STITCH\",\"12.5\",\"-5.0\"\n\
+            \"*\",\"STITCH\",\"20\",\"0\"\n";
+
+        let pattern = parse_csv(data).unwrap();
+        assert_eq!(
+            points(&pattern),
+            vec![
+                (10.0, -5.0, Move),
+                (12.5, -5.0, Stitch),
+                (20.0, 0.0, Stitch)
+            ]
+        );
+        let thread = &pattern.metadata.thread_colors[0];
+        assert_eq!(thread.rgb, [255, 0, 0]);
+        assert_eq!(thread.description.as_deref(), Some("Red"));
+        assert_eq!(thread.catalog_number.as_deref(), Some("1147"));
+    }
+
+    #[test]
+    fn test_plain_rows_and_unknown_commands() {
+        let data = b"0,0,STITCH\n10,5,stitch\n10,5,FRAME_EJECT\n10,5,end\n";
+
+        let pattern = parse_csv(data).unwrap();
+        assert_eq!(
+            points(&pattern),
+            vec![(0.0, 0.0, Stitch), (10.0, 5.0, Stitch), (10.0, 5.0, End)]
+        );
+        assert_eq!(
+            pattern.warnings,
+            vec![ParseWarning::UnknownCommand {
+                line: 3,
+                command: "FRAME_EJECT".to_string()
+            }]
+        );
+        assert_eq!(pattern.statistics.real_stitch_count, 2);
+    }
+
+    #[test]
+    fn test_bad_number_reports_line() {
+        let data = b"x,y,command\n1,2,STITCH\n1,two,STITCH\n";
+
+        assert!(matches!(
+            parse_csv(data),
+            Err(CsvError::InvalidNumber { line: 3, .. })
+        ));
+    }
+}
@@ -0,0 +1,90 @@
+// image.rs - PNG and JPEG decoding to opaque RGB pixels
+
+use super::DigitizeError;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const JPEG_SIGNATURE: &[u8] = b"\xFF\xD8\xFF";
+
+/// Pixels at least this opaque are digitized; the rest are left bare
+const OPAQUE_ALPHA: u8 = 128;
+
+/// A decoded image, row by row from the top left
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    /// None where the image is transparent
+    pub pixels: Vec<Option<[u8; 3]>>,
+}
+
+/// Decode a PNG or JPEG, told apart by their signatures
+pub fn decode_image(data: &[u8]) -> Result<Image, DigitizeError> {
+    let image = if data.starts_with(PNG_SIGNATURE) {
+        decode_png(data)?
+    } else if data.starts_with(JPEG_SIGNATURE) {
+        decode_jpeg(data)?
+    } else {
+        return Err(DigitizeError::UnsupportedImage);
+    };
+    if image.width == 0 || image.height == 0 {
+        return Err(DigitizeError::EmptyImage);
+    }
+    Ok(image)
+}
+
+fn decode_png(data: &[u8]) -> Result<Image, DigitizeError> {
+    let mut decoder = png::Decoder::new(data);
+    // Palettes and low bit depths come out as 8-bit channels
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer)?;
+
+    let channels = info.color_type.samples();
+    let pixels = buffer[..info.buffer_size()]
+        .chunks_exact(info.line_size)
+        .flat_map(|line| line[..info.width as usize * channels].chunks_exact(channels))
+        .map(|pixel| match *pixel {
+            [l] => Some([l, l, l]),
+            [l, a] => (a >= OPAQUE_ALPHA).then_some([l, l, l]),
+            [r, g, b] => Some([r, g, b]),
+            [r, g, b, a] => (a >= OPAQUE_ALPHA).then_some([r, g, b]),
+            _ => None,
+        })
+        .collect();
+    Ok(Image {
+        width: info.width as usize,
+        height: info.height as usize,
+        pixels,
+    })
+}
+
+fn decode_jpeg(data: &[u8]) -> Result<Image, DigitizeError> {
+    let mut decoder = jpeg_decoder::Decoder::new(data);
+    let samples = decoder.decode()?;
+    let info = decoder.info().ok_or(DigitizeError::EmptyImage)?;
+
+    let pixels = match info.pixel_format {
+        jpeg_decoder::PixelFormat::L8 => samples.iter().map(|&l| Some([l, l, l])).collect(),
+        jpeg_decoder::PixelFormat::L16 => samples
+            .chunks_exact(2)
+            .map(|l| Some([l[0], l[0], l[0]]))
+            .collect(),
+        jpeg_decoder::PixelFormat::RGB24 => samples
+            .chunks_exact(3)
+            .map(|p| Some([p[0], p[1], p[2]]))
+            .collect(),
+        jpeg_decoder::PixelFormat::CMYK32 => samples
+            .chunks_exact(4)
+            .map(|p| {
+                let k = 255 - p[3] as u16;
+                let channel = |c: u8| ((255 - c as u16) * k / 255) as u8;
+                Some([channel(p[0]), channel(p[1]), channel(p[2])])
+            })
+            .collect(),
+    };
+    Ok(Image {
+        width: info.width as usize,
+        height: info.height as usize,
+        pixels,
+    })
+}
@@ -0,0 +1,284 @@
+// mod.rs - Raster artwork digitized into fill blocks, one per reduced color
+
+mod image;
+mod quantize;
+mod trace;
+
+use crate::dst::Pattern;
+use crate::generate::{fill_polygon, join_blocks, ColorRuns, FillOptions, GenerateError};
+use image::decode_image;
+use quantize::quantize;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use trace::{ring_area, simplify, trace_regions};
+
+/// Error type for image digitizing
+#[derive(Debug, thiserror::Error)]
+pub enum DigitizeError {
+    #[error("Only PNG and JPEG images can be digitized")]
+    UnsupportedImage,
+    #[error("Invalid PNG image: {0}")]
+    Png(#[from] png::DecodingError),
+    #[error("Invalid JPEG image: {0}")]
+    Jpeg(#[from] jpeg_decoder::Error),
+    #[error("The image has no pixels")]
+    EmptyImage,
+    #[error("At least one color is needed")]
+    InvalidColorCount,
+    #[error("Design width must be positive")]
+    InvalidWidth,
+    #[error("No area of the image is large enough to stitch")]
+    NoRegions,
+    #[error(transparent)]
+    Generate(#[from] GenerateError),
+}
+
+/// How an image is turned into fills
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct DigitizeOptions {
+    /// Width of the finished design; the height keeps the image's aspect
+    pub width_mm: f64,
+    /// Regions and holes smaller than this are left out as specks
+    pub min_area_mm2: f64,
+    /// How far simplified outlines may stray from the pixel edges
+    pub simplify_mm: f64,
+    /// Rows of every fill
+    pub fill: FillOptions,
+}
+
+impl Default for DigitizeOptions {
+    fn default() -> Self {
+        Self {
+            width_mm: 100.0,
+            min_area_mm2: 4.0,
+            simplify_mm: 0.3,
+            fill: FillOptions::default(),
+        }
+    }
+}
+
+/// Step of a digitizing run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum DigitizeStage {
+    Quantizing,
+    Tracing,
+    Filling,
+}
+
+/// How far digitizing has got: `done` of `total` colors traced or regions filled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct DigitizeProgress {
+    pub stage: DigitizeStage,
+    pub done: usize,
+    pub total: usize,
+}
+
+/// Digitize a PNG or JPEG into fill blocks of at most `max_colors` colors
+///
+/// The image is reduced to `max_colors` colors and each connected patch of
+/// a color is traced along its pixel edges, simplified, and filled with
+/// its holes left open. Each color is one block, and blocks are ordered by
+/// the area they cover, largest first, so backgrounds are sewn before the
+/// details on top of them. Transparent pixels are left bare. The image's
+/// top left corner lands on the origin. Meant for flat logos; photos give
+/// a patchwork.
+pub fn digitize_image(
+    data: &[u8],
+    max_colors: usize,
+    options: &DigitizeOptions,
+    mut report: impl FnMut(DigitizeProgress),
+) -> Result<Pattern, DigitizeError> {
+    if max_colors == 0 {
+        return Err(DigitizeError::InvalidColorCount);
+    }
+    if options.width_mm.is_nan() || options.width_mm <= 0.0 {
+        return Err(DigitizeError::InvalidWidth);
+    }
+    let image = decode_image(data)?;
+    report(DigitizeProgress {
+        stage: DigitizeStage::Quantizing,
+        done: 0,
+        total: 1,
+    });
+    let (palette, labels) = quantize(&image.pixels, max_colors);
+
+    // Work in pixels until the fills
+    let pixel_mm = options.width_mm / image.width as f64;
+    let min_area = options.min_area_mm2 / (pixel_mm * pixel_mm);
+    let tolerance = options.simplify_mm / pixel_mm;
+    let mut colors = Vec::new();
+    for (index, &rgb) in palette.iter().enumerate() {
+        report(DigitizeProgress {
+            stage: DigitizeStage::Tracing,
+            done: index,
+            total: palette.len(),
+        });
+        let mut regions = trace_regions(&labels, image.width, index);
+        regions.retain(|region| region.area >= min_area);
+        for region in &mut regions {
+            region.outline = simplify(&region.outline, tolerance);
+            region.holes = region
+                .holes
+                .iter()
+                .filter(|hole| ring_area(hole).abs() >= min_area)
+                .map(|hole| simplify(hole, tolerance))
+                .filter(|hole| hole.len() >= 3)
+                .collect();
+        }
+        regions.retain(|region| region.outline.len() >= 3);
+        regions.sort_by(|a, b| b.area.total_cmp(&a.area));
+        let area: f64 = regions.iter().map(|region| region.area).sum();
+        if !regions.is_empty() {
+            colors.push((rgb, regions, area));
+        }
+    }
+    colors.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+    let total = colors.iter().map(|(_, regions, _)| regions.len()).sum();
+    let mut done = 0;
+    let to_mm = |ring: &[(f64, f64)]| -> Vec<(f64, f64)> {
+        ring.iter()
+            .map(|&(x, y)| (x * pixel_mm, y * pixel_mm))
+            .collect()
+    };
+    let mut blocks: Vec<ColorRuns> = Vec::new();
+    for (rgb, regions, _) in colors {
+        let mut fills = Vec::new();
+        for region in regions {
+            report(DigitizeProgress {
+                stage: DigitizeStage::Filling,
+                done,
+                total,
+            });
+            let holes: Vec<_> = region.holes.iter().map(|hole| to_mm(hole)).collect();
+            match fill_polygon(&to_mm(&region.outline), &holes, &options.fill) {
+                Ok(fill) => fills.push(fill),
+                // Simplifying can leave a sliver with no area to fill
+                Err(GenerateError::TooFewPoints) => {}
+                Err(e) => return Err(e.into()),
+            }
+            done += 1;
+        }
+        blocks.push((rgb, fills));
+    }
+    report(DigitizeProgress {
+        stage: DigitizeStage::Filling,
+        done,
+        total,
+    });
+
+    let pattern = join_blocks(&blocks);
+    if pattern.metadata.thread_colors.is_empty() {
+        return Err(DigitizeError::NoRegions);
+    }
+    Ok(pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::StitchCommand;
+
+    /// A `width` by `height` RGB PNG colored by `color(x, y)`
+    fn png_image(width: u32, height: u32, color: impl Fn(u32, u32) -> [u8; 3]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut encoder = png::Encoder::new(&mut data, width, height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let pixels: Vec<u8> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .flat_map(|(x, y)| color(x, y))
+            .collect();
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&pixels).unwrap();
+        writer.finish().unwrap();
+        data
+    }
+
+    /// Extent of the penetrations in one block, as (min x, min y, max x, max y)
+    fn sewn_extent(pattern: &Pattern, block: usize) -> (f64, f64, f64, f64) {
+        let range = &pattern.color_blocks[block];
+        pattern.stitches[range.start..range.end]
+            .iter()
+            .filter(|s| s.command == StitchCommand::Stitch)
+            .fold(
+                (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+                |(x0, y0, x1, y1), s| (x0.min(s.x), y0.min(s.y), x1.max(s.x), y1.max(s.y)),
+            )
+    }
+
+    #[test]
+    fn test_two_color_logo() {
+        // A blue 20px square in a 60 x 40 white field, 1mm to the pixel
+        let data = png_image(60, 40, |x, y| {
+            if (20..40).contains(&x) && (10..30).contains(&y) {
+                [0, 0, 255]
+            } else {
+                [255, 255, 255]
+            }
+        });
+        let options = DigitizeOptions {
+            width_mm: 60.0,
+            ..DigitizeOptions::default()
+        };
+        let mut stages = Vec::new();
+        let pattern = digitize_image(&data, 2, &options, |p| stages.push(p.stage)).unwrap();
+
+        // The white field has the larger area, so it is sewn first
+        assert_eq!(pattern.color_blocks.len(), 2);
+        let colors: Vec<_> = pattern
+            .metadata
+            .thread_colors
+            .iter()
+            .map(|t| t.rgb)
+            .collect();
+        assert_eq!(colors, vec![[255, 255, 255], [0, 0, 255]]);
+
+        let (min_x, min_y, max_x, max_y) = sewn_extent(&pattern, 0);
+        assert!((0.0..5.0).contains(&min_x) && (595.0..=600.0).contains(&max_x));
+        assert!((0.0..5.0).contains(&min_y) && (395.0..=400.0).contains(&max_y));
+        let (min_x, min_y, max_x, max_y) = sewn_extent(&pattern, 1);
+        assert!((200.0..205.0).contains(&min_x) && (395.0..=400.0).contains(&max_x));
+        assert!((100.0..105.0).contains(&min_y) && (295.0..=300.0).contains(&max_y));
+
+        // The field leaves the square's hole unsewn
+        let white = &pattern.stitches[..pattern.color_blocks[1].start];
+        assert!(!white.iter().any(|s| {
+            s.command == StitchCommand::Stitch
+                && (200.5..399.5).contains(&s.x)
+                && (100.5..299.5).contains(&s.y)
+        }));
+
+        assert_eq!(stages.first(), Some(&DigitizeStage::Quantizing));
+        assert_eq!(stages.last(), Some(&DigitizeStage::Filling));
+    }
+
+    #[test]
+    fn test_rejects_bad_input() {
+        let options = DigitizeOptions::default();
+        let data = png_image(4, 4, |_, _| [0, 0, 0]);
+        assert!(matches!(
+            digitize_image(b"GIF89a", 2, &options, |_| {}),
+            Err(DigitizeError::UnsupportedImage)
+        ));
+        assert!(matches!(
+            digitize_image(&data, 0, &options, |_| {}),
+            Err(DigitizeError::InvalidColorCount)
+        ));
+        // A 4 pixel square is 100mm across by default, but a speck at 1mm
+        assert!(digitize_image(&data, 2, &options, |_| {}).is_ok());
+        let tiny = DigitizeOptions {
+            width_mm: 1.0,
+            ..DigitizeOptions::default()
+        };
+        assert!(matches!(
+            digitize_image(&data, 2, &tiny, |_| {}),
+            Err(DigitizeError::NoRegions)
+        ));
+    }
+}
@@ -0,0 +1,140 @@
+// quantize.rs - Palette reduction by k-means over a coarse color histogram
+
+/// Refinement passes; flat artwork settles in two or three
+const ITERATIONS: usize = 12;
+
+type Color = [f64; 3];
+
+/// Histogram bucket of a color, from the top five bits of each channel
+fn bucket([r, g, b]: [u8; 3]) -> usize {
+    ((r as usize >> 3) << 10) | ((g as usize >> 3) << 5) | (b as usize >> 3)
+}
+
+fn distance2(a: Color, b: Color) -> f64 {
+    (0..3).map(|i| (a[i] - b[i]) * (a[i] - b[i])).sum()
+}
+
+fn nearest(centers: &[Color], color: Color) -> usize {
+    (0..centers.len())
+        .min_by(|&a, &b| distance2(centers[a], color).total_cmp(&distance2(centers[b], color)))
+        .expect("at least one center")
+}
+
+/// Reduce `pixels` to at most `max_colors` colors
+///
+/// Returns the palette and each pixel's index into it; transparent pixels
+/// get none. Pixels are first gathered into 32 levels per channel, so
+/// artwork with few distinct colors keeps them exactly. Starting colors
+/// are picked far apart, weighted by how many pixels they cover, so the
+/// anti-aliased edges of a logo do not become colors of their own.
+pub fn quantize(
+    pixels: &[Option<[u8; 3]>],
+    max_colors: usize,
+) -> (Vec<[u8; 3]>, Vec<Option<usize>>) {
+    let mut sums = vec![[0.0; 3]; 1 << 15];
+    let mut counts = vec![0usize; 1 << 15];
+    for rgb in pixels.iter().flatten() {
+        let key = bucket(*rgb);
+        for (sum, value) in sums[key].iter_mut().zip(rgb) {
+            *sum += *value as f64;
+        }
+        counts[key] += 1;
+    }
+    let occupied: Vec<usize> = (0..counts.len()).filter(|&key| counts[key] > 0).collect();
+    if occupied.is_empty() || max_colors == 0 {
+        return (Vec::new(), vec![None; pixels.len()]);
+    }
+    let colors: Vec<(Color, f64)> = occupied
+        .iter()
+        .map(|&key| {
+            let n = counts[key] as f64;
+            let [r, g, b] = sums[key];
+            ([r / n, g / n, b / n], n)
+        })
+        .collect();
+
+    let heaviest = (0..colors.len())
+        .max_by(|&a, &b| colors[a].1.total_cmp(&colors[b].1))
+        .expect("colors are not empty");
+    let mut centers = vec![colors[heaviest].0];
+    while centers.len() < max_colors {
+        let (score, pick) = colors
+            .iter()
+            .map(|&(color, weight)| {
+                let gap = centers
+                    .iter()
+                    .map(|&c| distance2(c, color))
+                    .fold(f64::MAX, f64::min);
+                (gap * weight, color)
+            })
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .expect("colors are not empty");
+        if score <= 0.0 {
+            break;
+        }
+        centers.push(pick);
+    }
+
+    for _ in 0..ITERATIONS {
+        let mut totals = vec![([0.0; 3], 0.0); centers.len()];
+        for &(color, weight) in &colors {
+            let total = &mut totals[nearest(&centers, color)];
+            for (sum, value) in total.0.iter_mut().zip(color) {
+                *sum += value * weight;
+            }
+            total.1 += weight;
+        }
+        // A center that lost all its colors is dropped
+        centers = totals
+            .into_iter()
+            .filter(|&(_, weight)| weight > 0.0)
+            .map(|([r, g, b], weight)| [r / weight, g / weight, b / weight])
+            .collect();
+    }
+
+    let mut index = vec![0; 1 << 15];
+    for (&key, &(color, _)) in occupied.iter().zip(&colors) {
+        index[key] = nearest(&centers, color);
+    }
+    let palette = centers
+        .iter()
+        .map(|c| c.map(|channel| channel.round().clamp(0.0, 255.0) as u8))
+        .collect();
+    let labels = pixels
+        .iter()
+        .map(|rgb| rgb.map(|rgb| index[bucket(rgb)]))
+        .collect();
+    (palette, labels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_flat_colors_and_merges_edges() {
+        let red = Some([200, 30, 30]);
+        let white = Some([255, 255, 255]);
+        // Mostly red and white, a few anti-aliased pink pixels and a hole
+        let mut pixels = vec![red; 50];
+        pixels.extend(vec![white; 40]);
+        pixels.extend(vec![Some([240, 200, 200]); 3]);
+        pixels.push(None);
+
+        let (palette, labels) = quantize(&pixels, 2);
+        assert_eq!(palette.len(), 2);
+        assert_eq!(labels[93], None);
+        let (r, w) = (labels[0].unwrap(), labels[50].unwrap());
+        assert_ne!(r, w);
+        assert!(labels[..50].iter().all(|&l| l == Some(r)));
+        // The pink joins white, the nearer of the two, pulling it slightly
+        assert!(labels[90..93].iter().all(|&l| l == Some(w)));
+        assert_eq!(palette[r], [200, 30, 30]);
+        assert!(palette[w][1] < 255 && palette[w][1] > 240);
+
+        // With room for every color each is kept exactly
+        let (palette, _) = quantize(&pixels, 8);
+        assert_eq!(palette.len(), 3);
+        assert!(palette.contains(&[240, 200, 200]));
+    }
+}
@@ -0,0 +1,260 @@
+// trace.rs - Region outlines traced along pixel edges, then simplified
+
+use std::collections::HashMap;
+
+type Point = (f64, f64);
+
+/// One connected patch of a color, in pixel units with y down
+#[derive(Debug, Clone)]
+pub struct Region {
+    /// Clockwise on screen, without repeating its first point
+    pub outline: Vec<Point>,
+    /// Counter-clockwise on screen
+    pub holes: Vec<Vec<Point>>,
+    /// Pixels covered, holes excluded
+    pub area: f64,
+}
+
+/// Signed area of a ring, positive when it turns clockwise on screen
+pub fn ring_area(ring: &[Point]) -> f64 {
+    let twice: f64 = (0..ring.len())
+        .map(|i| {
+            let (a, b) = (ring[i], ring[(i + 1) % ring.len()]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum();
+    twice / 2.0
+}
+
+/// Even-odd test of `point` against a ring
+fn contains(ring: &[Point], (x, y): Point) -> bool {
+    let mut inside = false;
+    for i in 0..ring.len() {
+        let (a, b) = (ring[i], ring[(i + 1) % ring.len()]);
+        if (a.1 > y) != (b.1 > y) && x < a.0 + (y - a.1) * (b.0 - a.0) / (b.1 - a.1) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// The regions of pixels labelled `label`, in a `width` wide grid
+///
+/// Every pixel side between the label and anything else becomes an edge
+/// with the label on its right, and edges are chained into rings. Where
+/// two pixels touch only at a corner the chain turns right, keeping them
+/// apart, so regions are 4-connected. Clockwise rings are outlines and
+/// the rest holes, each given to the smallest outline around it.
+pub fn trace_regions(labels: &[Option<usize>], width: usize, label: usize) -> Vec<Region> {
+    let height = labels.len() / width;
+    let inside =
+        |x: usize, y: usize| x < width && y < height && labels[y * width + x] == Some(label);
+    let vertex = |x: usize, y: usize| y * (width + 1) + x;
+    let mut edges: Vec<(usize, usize)> = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            if !inside(x, y) {
+                continue;
+            }
+            if y == 0 || !inside(x, y - 1) {
+                edges.push((vertex(x, y), vertex(x + 1, y)));
+            }
+            if !inside(x + 1, y) {
+                edges.push((vertex(x + 1, y), vertex(x + 1, y + 1)));
+            }
+            if !inside(x, y + 1) {
+                edges.push((vertex(x + 1, y + 1), vertex(x, y + 1)));
+            }
+            if x == 0 || !inside(x - 1, y) {
+                edges.push((vertex(x, y + 1), vertex(x, y)));
+            }
+        }
+    }
+
+    let mut outgoing: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, &(from, _)) in edges.iter().enumerate() {
+        outgoing.entry(from).or_default().push(i);
+    }
+    let point = |v: usize| ((v % (width + 1)) as f64, (v / (width + 1)) as f64);
+    let direction = |e: usize| {
+        let (a, b) = (point(edges[e].0), point(edges[e].1));
+        (b.0 - a.0, b.1 - a.1)
+    };
+
+    let mut used = vec![false; edges.len()];
+    let mut rings = Vec::new();
+    for first in 0..edges.len() {
+        if used[first] {
+            continue;
+        }
+        used[first] = true;
+        let mut ring = vec![point(edges[first].0)];
+        let mut current = first;
+        loop {
+            let candidates: Vec<usize> = outgoing[&edges[current].1]
+                .iter()
+                .copied()
+                .filter(|&e| !used[e] || e == first)
+                .collect();
+            let (dx, dy) = direction(current);
+            let next = candidates
+                .iter()
+                .copied()
+                .find(|&e| candidates.len() == 1 || direction(e) == (-dy, dx))
+                .unwrap_or(candidates[0]);
+            if next == first {
+                break;
+            }
+            used[next] = true;
+            // Only corners are kept
+            if direction(next) != (dx, dy) {
+                ring.push(point(edges[next].0));
+            }
+            current = next;
+        }
+        rings.push(ring);
+    }
+
+    let (outlines, holes): (Vec<_>, Vec<_>) = rings.into_iter().partition(|r| ring_area(r) > 0.0);
+    let mut regions: Vec<Region> = outlines
+        .into_iter()
+        .map(|outline| Region {
+            area: ring_area(&outline),
+            outline,
+            holes: Vec::new(),
+        })
+        .collect();
+    for hole in holes {
+        // The middle of the hole's first pixel side lies on no other ring
+        let (a, b) = (hole[0], hole[1]);
+        let probe = (
+            a.0 + (b.0 - a.0).signum() * 0.5,
+            a.1 + (b.1 - a.1).signum() * 0.5,
+        );
+        let owner = regions
+            .iter_mut()
+            .filter(|region| contains(&region.outline, probe))
+            .min_by(|p, q| p.area.total_cmp(&q.area));
+        if let Some(region) = owner {
+            region.holes.push(hole);
+        }
+    }
+    for region in &mut regions {
+        region.area =
+            ring_area(&region.outline) + region.holes.iter().map(|h| ring_area(h)).sum::<f64>();
+    }
+    regions
+}
+
+/// Distance from `p` to the segment from `a` to `b`
+fn segment_distance(p: Point, a: Point, b: Point) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length2 = dx * dx + dy * dy;
+    let t = if length2 == 0.0 {
+        0.0
+    } else {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length2).clamp(0.0, 1.0)
+    };
+    (p.0 - a.0 - t * dx).hypot(p.1 - a.1 - t * dy)
+}
+
+/// Drop ring points that stray no more than `tolerance` from the line
+/// between their neighbours (Douglas-Peucker), turning pixel staircases
+/// into slopes
+pub fn simplify(ring: &[Point], tolerance: f64) -> Vec<Point> {
+    if ring.len() < 4 {
+        return ring.to_vec();
+    }
+    // Split the ring at the point furthest from the first, so both halves
+    // are open chains with fixed ends
+    let from_start = |i: usize| (ring[i].0 - ring[0].0).hypot(ring[i].1 - ring[0].1);
+    let far = (1..ring.len())
+        .max_by(|&a, &b| from_start(a).total_cmp(&from_start(b)))
+        .expect("ring has points");
+    let mut closed = ring.to_vec();
+    closed.push(ring[0]);
+    let last = closed.len() - 1;
+    let mut keep = vec![false; closed.len()];
+    keep[0] = true;
+    keep[far] = true;
+
+    let mut spans = vec![(0, far), (far, last)];
+    while let Some((a, b)) = spans.pop() {
+        if b <= a + 1 {
+            continue;
+        }
+        let (worst, distance) = (a + 1..b)
+            .map(|i| (i, segment_distance(closed[i], closed[a], closed[b])))
+            .max_by(|p, q| p.1.total_cmp(&q.1))
+            .expect("span has inner points");
+        if distance > tolerance {
+            keep[worst] = true;
+            spans.push((a, worst));
+            spans.push((worst, b));
+        }
+    }
+    closed[..last]
+        .iter()
+        .zip(&keep)
+        .filter(|(_, &kept)| kept)
+        .map(|(&p, _)| p)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Labels from rows of text: '#' is label 0, '.' label 1, ' ' nothing
+    fn grid(rows: &[&str]) -> (Vec<Option<usize>>, usize) {
+        let labels = rows
+            .iter()
+            .flat_map(|row| {
+                row.chars().map(|c| match c {
+                    '#' => Some(0),
+                    '.' => Some(1),
+                    _ => None,
+                })
+            })
+            .collect();
+        (labels, rows[0].len())
+    }
+
+    #[test]
+    fn test_rings_holes_and_diagonals() {
+        let (labels, width) = grid(&["#####..", "#...#..", "#...#..", "#####.#", ".....#."]);
+        let mut regions = trace_regions(&labels, width, 0);
+        regions.sort_by(|a, b| b.area.total_cmp(&a.area));
+
+        // A frame with a 3x2 hole, and two corner-touching pixels kept apart
+        assert_eq!(regions.len(), 3);
+        assert_eq!(regions[0].area, 20.0 - 6.0);
+        assert_eq!(regions[0].outline.len(), 4);
+        assert_eq!(regions[0].holes.len(), 1);
+        assert_eq!(ring_area(&regions[0].holes[0]), -6.0);
+        assert_eq!(regions[1].area, 1.0);
+        assert_eq!(regions[2].area, 1.0);
+
+        // The dots inside the frame are a region of their own, not a hole
+        let dots = trace_regions(&labels, width, 1);
+        let mut areas: Vec<f64> = dots.iter().map(|r| r.area).collect();
+        areas.sort_by(f64::total_cmp);
+        assert_eq!(areas, vec![1.0, 5.0, 6.0, 7.0]);
+        assert!(dots.iter().all(|r| r.holes.is_empty()));
+    }
+
+    #[test]
+    fn test_simplify_straightens_staircases() {
+        // A 10-step staircase triangle
+        let mut ring = vec![(0.0, 0.0)];
+        for i in 0..10 {
+            ring.push((i as f64, i as f64 + 1.0));
+            ring.push((i as f64 + 1.0, i as f64 + 1.0));
+        }
+        ring.push((10.0, 0.0));
+        let simple = simplify(&ring, 0.8);
+        assert_eq!(simple.len(), 3);
+        assert!((ring_area(&simple).abs() - 50.0).abs() < 5.0);
+        assert!(simplify(&ring, 0.1).len() > 10);
+    }
+}
@@ -0,0 +1,22 @@
+// mod.rs - DST module exports for parser, writer and pattern types
+
+mod parser;
+mod summary;
+mod tape;
+mod time;
+mod types;
+mod writer;
+
+pub use parser::{
+    detect_variant, parse_dst, parse_dst_monitored, parse_dst_multi, parse_dst_variant,
+    read_dst_info, DstStitchIter, DstVariant,
+};
+pub use summary::{feed_all, PatternInfo, RunningSummary, StitchSink};
+pub use tape::{parse_t01, parse_t03, parse_t09};
+pub use time::{RunTimeline, TimeEstimate, TimeEstimator, TimePoint, DEFAULT_SAMPLE_EVERY};
+pub use types::{
+    Bounds, ColorBlock, ParseOptions, ParseWarning, Pattern, PatternMetadata, PatternStatistics,
+    Stitch, StitchCommand, ThreadColor, DEFAULT_BOBBIN_THREAD_MULTIPLIER,
+    DEFAULT_TOP_THREAD_MULTIPLIER, MAX_COORDINATE, UNITS_PER_MM,
+};
+pub use writer::write_dst;
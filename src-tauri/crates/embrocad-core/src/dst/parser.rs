@@ -0,0 +1,2185 @@
+// parser.rs - DST embroidery file format parser with stitch decoding
+
+use crate::dst::summary::{JumpRunCollapser, PatternInfo, RunningSummary, StitchSink};
+use crate::dst::types::{
+    ParseOptions, ParseWarning, Pattern, PatternMetadata, Stitch, StitchCommand, ThreadColor,
+    MAX_COORDINATE,
+};
+use crate::progress::{LoadMonitor, LoadProgress};
+use std::borrow::Cow;
+
+/// DST header size in bytes
+const HEADER_SIZE: usize = 512;
+
+/// Error type for DST parsing
+#[derive(Debug, thiserror::Error)]
+#[allow(dead_code)]
+pub enum DstError {
+    #[error(
+        "Invalid DST file: data ends at offset {offset}, but at least {needed} bytes are needed"
+    )]
+    InsufficientData { offset: usize, needed: usize },
+    #[error("Invalid DST file format")]
+    InvalidFormat,
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("{0}")]
+    Strict(ParseWarning),
+    #[error("Load cancelled")]
+    Cancelled,
+}
+
+impl DstError {
+    /// Byte offset in the file the error was found at, when it has one
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            Self::InsufficientData { offset, .. } => Some(*offset),
+            Self::Strict(warning) => warning.offset(),
+            _ => None,
+        }
+    }
+
+    /// The error for a file too short to hold its header
+    fn short_header(data: &[u8]) -> Self {
+        Self::InsufficientData {
+            offset: data.len(),
+            needed: HEADER_SIZE,
+        }
+    }
+}
+
+/// Extract a single bit from a byte
+#[inline]
+fn get_bit(byte: u8, bit: u8) -> i32 {
+    ((byte >> bit) & 1) as i32
+}
+
+/// Decode X displacement from 3 bytes using DST bit encoding
+#[allow(clippy::identity_op, clippy::neg_multiply)]
+pub fn decode_dx(b0: u8, b1: u8, b2: u8) -> i32 {
+    let mut x = 0i32;
+    x += get_bit(b2, 2) * 81;
+    x += get_bit(b2, 3) * -81;
+    x += get_bit(b1, 2) * 27;
+    x += get_bit(b1, 3) * -27;
+    x += get_bit(b0, 2) * 9;
+    x += get_bit(b0, 3) * -9;
+    x += get_bit(b1, 0) * 3;
+    x += get_bit(b1, 1) * -3;
+    x += get_bit(b0, 0) * 1;
+    x += get_bit(b0, 1) * -1;
+    x
+}
+
+/// Decode Y displacement from 3 bytes using DST bit encoding
+#[allow(clippy::identity_op, clippy::neg_multiply)]
+pub fn decode_dy(b0: u8, b1: u8, b2: u8) -> i32 {
+    let mut y = 0i32;
+    y += get_bit(b2, 5) * 81;
+    y += get_bit(b2, 4) * -81;
+    y += get_bit(b1, 5) * 27;
+    y += get_bit(b1, 4) * -27;
+    y += get_bit(b0, 5) * 9;
+    y += get_bit(b0, 4) * -9;
+    y += get_bit(b1, 7) * 3;
+    y += get_bit(b1, 6) * -3;
+    y += get_bit(b0, 7) * 1;
+    y += get_bit(b0, 6) * -1;
+    -y // Invert Y axis
+}
+
+/// Header text ends at the first SUB (0x1A) byte
+const HEADER_TERMINATOR: u8 = 0x1A;
+
+/// Parse a numeric header value, ignoring padding between the sign and the digits
+fn parse_header_number<T: std::str::FromStr>(value: &str) -> Option<T> {
+    let compact: String = value
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != char::from(0))
+        .collect();
+    compact.parse().ok()
+}
+
+/// Parse a 6-digit hex color, with or without a leading '#'
+fn parse_hex_color(hex: &str) -> Option<[u8; 3]> {
+    let hex = hex.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// Parse a thread color record in either of the forms written by digitizers:
+/// `TC:#RRGGBB,Description,Catalog` or `#TCRRGGBB Description Catalog`
+fn parse_thread_color(record: &str) -> Option<ThreadColor> {
+    if let Some(value) = record.strip_prefix("TC:") {
+        let mut fields = value.split(',');
+        let mut color = ThreadColor::new(parse_hex_color(fields.next()?)?);
+        color.description = fields.next().and_then(non_empty);
+        color.catalog_number = fields.next().and_then(non_empty);
+        return Some(color);
+    }
+
+    let value = record.strip_prefix("#TC")?;
+    let mut color = ThreadColor::new(parse_hex_color(value.get(..6)?)?);
+    let rest = value[6..].trim();
+    // The catalog code is the last word; everything before it is the name
+    match rest.rsplit_once(char::is_whitespace) {
+        Some((description, catalog)) => {
+            color.description = non_empty(description);
+            color.catalog_number = non_empty(catalog);
+        }
+        None => color.description = non_empty(rest),
+    }
+    Some(color)
+}
+
+/// Parse a numeric header field, noting a warning when it holds anything else
+fn header_number<T: std::str::FromStr>(
+    value: &str,
+    key: &str,
+    offset: usize,
+    warnings: &mut Vec<ParseWarning>,
+) -> Option<T> {
+    let number = parse_header_number(value);
+    if number.is_none() {
+        warnings.push(ParseWarning::InvalidHeaderRecord {
+            offset,
+            key: key.to_string(),
+        });
+    }
+    number
+}
+
+/// Parse the DST header to extract metadata
+///
+/// The header is a sequence of `XX:value` records separated by carriage returns.
+/// Records are looked up by key rather than fixed offset so that files with
+/// missing or differently padded fields still yield whatever is present.
+/// Known records that are not UTF-8 text or hold an unreadable number are
+/// warned about with their offset; unknown records are skipped silently.
+fn parse_header(data: &[u8]) -> (PatternMetadata, Vec<ParseWarning>) {
+    let mut metadata = PatternMetadata::default();
+    let mut warnings = Vec::new();
+
+    if data.len() < HEADER_SIZE {
+        return (metadata, warnings);
+    }
+
+    let header = &data[..HEADER_SIZE];
+    let header = match header.iter().position(|&b| b == HEADER_TERMINATOR) {
+        Some(end) => &header[..end],
+        None => header,
+    };
+
+    let mut next = 0;
+    for record in header.split(|&b| b == b'\r' || b == b'\n') {
+        let offset = next;
+        next += record.len() + 1;
+        let text = String::from_utf8_lossy(record);
+        // Lossy decoding only allocates when it had to replace something
+        let garbled = matches!(text, Cow::Owned(_));
+        let invalid = |key: &str| ParseWarning::InvalidHeaderRecord {
+            offset,
+            key: key.to_string(),
+        };
+
+        let record = text.trim_start_matches([' ', char::from(0)]);
+        if record.starts_with("TC:") || record.starts_with("#TC") {
+            match parse_thread_color(record.trim_end_matches(char::from(0))) {
+                Some(color) if !garbled => metadata.thread_colors.push(color),
+                _ => warnings.push(invalid("TC")),
+            }
+            continue;
+        }
+
+        let Some((key, value)) = record.split_once(':') else {
+            continue;
+        };
+        let warnings = &mut warnings;
+
+        match key {
+            "ST" => metadata.stitch_count = header_number(value, key, offset, warnings),
+            "CO" => metadata.color_count = header_number(value, key, offset, warnings),
+            "+X" => metadata.extent_plus_x = header_number(value, key, offset, warnings),
+            "-X" => metadata.extent_minus_x = header_number(value, key, offset, warnings),
+            "+Y" => metadata.extent_plus_y = header_number(value, key, offset, warnings),
+            "-Y" => metadata.extent_minus_y = header_number(value, key, offset, warnings),
+            "AX" => metadata.end_offset_x = header_number(value, key, offset, warnings),
+            "AY" => metadata.end_offset_y = header_number(value, key, offset, warnings),
+            "MX" => metadata.multi_start_x = header_number(value, key, offset, warnings),
+            "MY" => metadata.multi_start_y = header_number(value, key, offset, warnings),
+            "LA" | "PD" => {
+                if garbled {
+                    warnings.push(invalid(key));
+                }
+                let text = value.trim_end_matches(char::from(0)).trim();
+                if text.is_empty() {
+                    continue;
+                }
+                match key {
+                    "LA" => metadata.label = Some(text.to_string()),
+                    _ => metadata.previous_design = Some(text.to_string()),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    (metadata, warnings)
+}
+
+/// Stitch encodings that share the 512-byte DST header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DstVariant {
+    /// Tajima DST: balanced-ternary displacement bits spread over all three bytes
+    Tajima,
+    /// Barudan DSB: control byte first, then Y and X magnitudes
+    Barudan,
+    /// ZSK DSZ: Y and X magnitudes first, then the control byte
+    Zsk,
+}
+
+/// Number of leading records examined when guessing the variant
+const DETECTION_RECORDS: usize = 256;
+
+/// Guess the stitch encoding from the records following the header
+///
+/// Every Tajima record has the two low bits of its third byte set, while
+/// Barudan and ZSK keep command codes in the low bits of their control byte.
+/// The variant whose invariant holds for the most records wins; ties go to
+/// Tajima so that ordinary DST files are never reinterpreted. Counting stops
+/// at the End record of the variant leading so far, so padding or another
+/// design after it cannot sway the guess.
+pub fn detect_variant(data: &[u8]) -> DstVariant {
+    let records = data
+        .get(HEADER_SIZE..)
+        .unwrap_or_default()
+        .chunks_exact(3)
+        .take(DETECTION_RECORDS);
+
+    let (mut tajima, mut barudan, mut zsk) = (0usize, 0usize, 0usize);
+    for record in records {
+        if record[2] & 0x03 == 0x03 {
+            tajima += 1;
+        }
+        if record[0] & 0x1F <= 0x01 || record[0] >= DSB_TRIM {
+            barudan += 1;
+        }
+        if record[2] & 0x1F <= 0x01 || record[2] & 0x80 != 0 {
+            zsk += 1;
+        }
+        let ends = match leading_variant(tajima, barudan, zsk) {
+            DstVariant::Tajima => record[2] & 0xF3 == 0xF3,
+            DstVariant::Barudan => record[0] == DSB_END,
+            DstVariant::Zsk => record[2] & !0x60 == DSZ_END,
+        };
+        if ends {
+            break;
+        }
+    }
+    leading_variant(tajima, barudan, zsk)
+}
+
+/// The variant whose invariant held for the most records, Tajima on a tie
+fn leading_variant(tajima: usize, barudan: usize, zsk: usize) -> DstVariant {
+    if barudan > tajima && barudan >= zsk {
+        DstVariant::Barudan
+    } else if zsk > tajima && zsk > barudan {
+        DstVariant::Zsk
+    } else {
+        DstVariant::Tajima
+    }
+}
+
+/// A decoded stitch record
+struct Record {
+    dx: i32,
+    dy: i32,
+    command: StitchCommand,
+    /// Control code the decoder did not recognise; `command` is its lenient fallback
+    unknown_control: Option<u8>,
+}
+
+impl Record {
+    fn new(dx: i32, dy: i32, command: StitchCommand) -> Self {
+        Self {
+            dx,
+            dy,
+            command,
+            unknown_control: None,
+        }
+    }
+}
+
+/// Decode a Tajima record, toggling sequin mode when the record asks for it
+fn decode_tajima_record(record: [u8; 3], sequin_mode: &mut bool) -> Record {
+    let [b0, b1, b2] = record;
+    let dx = decode_dx(b0, b1, b2);
+    let dy = decode_dy(b0, b1, b2);
+
+    // Check for end of pattern (0xF3 pattern)
+    let command = if b2 & 0b11110011 == 0b11110011 {
+        StitchCommand::End
+    }
+    // Color change (0xC3 pattern)
+    else if b2 & 0b11000011 == 0b11000011 {
+        StitchCommand::ColorChange
+    }
+    // Sequin mode toggle (0x43 pattern)
+    else if b2 & 0b01000011 == 0b01000011 {
+        *sequin_mode = !*sequin_mode;
+        StitchCommand::SequinMode
+    }
+    // Move/Jump or Sequin eject (0x83 pattern)
+    else if b2 & 0b10000011 == 0b10000011 {
+        if *sequin_mode {
+            StitchCommand::SequinEject
+        } else {
+            StitchCommand::Move
+        }
+    }
+    // Regular stitch
+    else {
+        StitchCommand::Stitch
+    };
+
+    Record::new(dx, dy, command)
+}
+
+/// Barudan command codes; codes 0xE9-0xF7 select a needle
+const DSB_END: u8 = 0xF8;
+const DSB_TRIM: u8 = 0xE7;
+const DSB_STOP: u8 = 0xE8;
+const DSB_NEEDLE_FIRST: u8 = 0xE9;
+const DSB_NEEDLE_LAST: u8 = 0xF7;
+
+/// Decode a Barudan record: control, Y magnitude, X magnitude
+///
+/// Control bit 0x40 negates X and 0x20 negates Y. Low bits 0 and 1 mean
+/// stitch and jump; end, trim, stop and needle codes use the whole byte, and
+/// any other code is unknown and ends the design.
+fn decode_dsb_record(record: [u8; 3]) -> Record {
+    let [control, y, x] = record;
+    let mut dx = x as i32;
+    let mut dy = -(y as i32); // Invert Y axis
+    if control & 0x40 != 0 {
+        dx = -dx;
+    }
+    if control & 0x20 != 0 {
+        dy = -dy;
+    }
+
+    let command = match control {
+        _ if control & 0x1F == 0 => StitchCommand::Stitch,
+        _ if control & 0x1F == 1 => StitchCommand::Move,
+        DSB_END => StitchCommand::End,
+        DSB_TRIM => StitchCommand::Trim,
+        DSB_STOP | DSB_NEEDLE_FIRST..=DSB_NEEDLE_LAST => StitchCommand::ColorChange,
+        _ => {
+            return Record {
+                unknown_control: Some(control),
+                ..Record::new(0, 0, StitchCommand::End)
+            }
+        }
+    };
+
+    // Commands other than jumps carry no displacement
+    match command {
+        StitchCommand::Stitch | StitchCommand::Move => Record::new(dx, dy, command),
+        _ => Record::new(0, 0, command),
+    }
+}
+
+/// ZSK command codes, after the two sign bits are masked off
+const DSZ_STOP: u8 = 0x82;
+const DSZ_NEEDLE_FIRST: u8 = 0x83;
+const DSZ_NEEDLE_LAST: u8 = 0x90;
+const DSZ_END: u8 = 0x91;
+const DSZ_TRIM: u8 = 0x9B;
+
+/// Decode a ZSK record: Y magnitude, X magnitude, control
+///
+/// Control bit 0x20 negates X and 0x40 negates Y. With the sign bits masked,
+/// 0x00 is a stitch, 0x01 a jump and the 0x80-range codes are stop, needle
+/// change, end and trim; unknown codes are treated as stitches.
+fn decode_dsz_record(record: [u8; 3]) -> Record {
+    let [y, x, control] = record;
+    let mut dx = x as i32;
+    let mut dy = -(y as i32); // Invert Y axis
+    if control & 0x20 != 0 {
+        dx = -dx;
+    }
+    if control & 0x40 != 0 {
+        dy = -dy;
+    }
+
+    let command = match control & !0x60 {
+        0x00 => StitchCommand::Stitch,
+        0x01 => StitchCommand::Move,
+        DSZ_STOP | DSZ_NEEDLE_FIRST..=DSZ_NEEDLE_LAST => StitchCommand::ColorChange,
+        DSZ_END => StitchCommand::End,
+        DSZ_TRIM => StitchCommand::Trim,
+        _ => {
+            return Record {
+                unknown_control: Some(control),
+                ..Record::new(dx, dy, StitchCommand::Stitch)
+            }
+        }
+    };
+
+    match command {
+        StitchCommand::Stitch | StitchCommand::Move => Record::new(dx, dy, command),
+        _ => Record::new(0, 0, command),
+    }
+}
+
+/// Whether every record has the framing bits its encoding always sets;
+/// only Tajima records have any
+fn well_framed(records: &[u8], variant: DstVariant) -> bool {
+    variant != DstVariant::Tajima || records.chunks_exact(3).all(|r| r[2] & 0x03 == 0x03)
+}
+
+/// Bytes that commonly pad a file after its End record
+fn is_padding(byte: u8) -> bool {
+    byte == 0x00 || byte == HEADER_TERMINATOR
+}
+
+/// Lazily decodes the stitch records that follow a DST header
+///
+/// Yields each record at its absolute position, in 0.1mm units, exactly as
+/// the file encodes it; Tajima jump runs are not yet collapsed into trims.
+/// Irregularities in the stream's framing are recorded as warnings: a partial
+/// final record, a missing End, anything other than padding after End, and
+/// unknown control codes. Corrupt data cannot run away: the iterator stops
+/// once the stitch limits are reached or coordinates leave the physical range.
+/// A stream that stops for any reason but an End record, after decoding at
+/// least one, is closed with an End made up at the needle's last position,
+/// which is not counted as a record.
+pub struct DstStitchIter<'a> {
+    data: &'a [u8],
+    /// Offset of `data` in the file
+    start: usize,
+    variant: DstVariant,
+    /// Bytes of `data` consumed, always whole records
+    position: usize,
+    limit: Option<usize>,
+    records: usize,
+    x: f64,
+    y: f64,
+    sequin_mode: bool,
+    warnings: Vec<ParseWarning>,
+    finished: bool,
+    /// Whether the stream finished with a real End record
+    ended: bool,
+}
+
+impl<'a> DstStitchIter<'a> {
+    /// Decode `data`, the bytes after the header, applying the stitch limits
+    /// of `options` and of the header's declared count
+    pub fn new(
+        data: &'a [u8],
+        variant: DstVariant,
+        options: &ParseOptions,
+        metadata: &PatternMetadata,
+    ) -> Self {
+        // A header count of zero is treated as missing rather than as a zero limit
+        let header_limit = options
+            .header_stitch_factor
+            .zip(metadata.stitch_count.filter(|&n| n > 0))
+            .map(|(factor, declared)| (declared as usize).saturating_mul(factor));
+        let limit = [options.max_stitches, header_limit]
+            .into_iter()
+            .flatten()
+            .min();
+        Self {
+            data,
+            start: HEADER_SIZE,
+            variant,
+            position: 0,
+            limit,
+            records: 0,
+            x: 0.0,
+            y: 0.0,
+            sequin_mode: false,
+            warnings: Vec::new(),
+            finished: false,
+            ended: false,
+        }
+    }
+
+    /// Place `data` at `start` in the file, for a design that follows
+    /// another instead of a single header
+    fn starting_at(mut self, start: usize) -> Self {
+        self.start = start;
+        self
+    }
+
+    /// Records decoded so far
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    pub fn into_warnings(self) -> Vec<ParseWarning> {
+        self.warnings
+    }
+
+    /// How far decoding has got, in bytes of the whole file
+    pub fn progress(&self) -> LoadProgress {
+        LoadProgress {
+            bytes_processed: self.start + self.position,
+            total_bytes: self.start + self.data.len(),
+            stitches_decoded: self.records,
+        }
+    }
+
+    /// Whether the next call yields nothing without reading another record
+    fn exhausted(&self) -> bool {
+        self.finished || self.limit.is_some_and(|limit| self.records >= limit)
+    }
+
+    /// Stop decoding early, closing the design where the needle is unless
+    /// no record was decoded at all
+    fn stop(&mut self, warning: ParseWarning) -> Option<Stitch> {
+        self.warnings.push(warning);
+        self.finished = true;
+        (self.records > 0).then(|| Stitch::new(self.x, self.y, StitchCommand::End))
+    }
+}
+
+impl Iterator for DstStitchIter<'_> {
+    type Item = Stitch;
+
+    fn next(&mut self) -> Option<Stitch> {
+        if self.finished {
+            return None;
+        }
+        if self.limit.is_some_and(|limit| self.records >= limit) {
+            let limit = self.records;
+            return self.stop(ParseWarning::StitchLimitReached { limit });
+        }
+
+        let offset = self.start + self.position;
+        let Some(&[b0, b1, b2]) = self.data.get(self.position..self.position + 3) else {
+            if self.position < self.data.len() {
+                self.warnings.push(ParseWarning::TruncatedRecord {
+                    offset,
+                    length: self.data.len() - self.position,
+                });
+            }
+            return self.stop(ParseWarning::MissingEnd {
+                offset: self.start + self.data.len(),
+            });
+        };
+        self.position += 3;
+
+        let record = match self.variant {
+            DstVariant::Tajima => decode_tajima_record([b0, b1, b2], &mut self.sequin_mode),
+            DstVariant::Barudan => decode_dsb_record([b0, b1, b2]),
+            DstVariant::Zsk => decode_dsz_record([b0, b1, b2]),
+        };
+        if let Some(code) = record.unknown_control {
+            self.warnings
+                .push(ParseWarning::UnknownControl { offset, code });
+        }
+
+        let (x, y) = (self.x + record.dx as f64, self.y + record.dy as f64);
+        if x.abs() > MAX_COORDINATE || y.abs() > MAX_COORDINATE {
+            return self.stop(ParseWarning::CoordinateOutOfRange { offset });
+        }
+        (self.x, self.y) = (x, y);
+        self.records += 1;
+
+        if record.command == StitchCommand::End {
+            self.finished = true;
+            self.ended = true;
+            let trailing = &self.data[self.position..];
+            if !trailing.iter().copied().all(is_padding) {
+                self.warnings.push(ParseWarning::DataAfterEnd {
+                    offset: self.start + self.position,
+                    length: trailing.len(),
+                });
+            }
+        }
+        Some(Stitch::new(self.x, self.y, record.command))
+    }
+}
+
+/// What decoding one design's stitch records found
+struct Decoded {
+    /// Records read, before jump runs are collapsed
+    records: usize,
+    /// Offset in the file just past the last record read
+    end: usize,
+    /// Whether the records finished with a real End
+    ended: bool,
+    warnings: Vec<ParseWarning>,
+}
+
+/// Decode the stitch records from offset `start` of `data` into `sink`
+///
+/// Tajima jump runs reach the sink already collapsed into trims. Stops with
+/// `Cancelled` as soon as the monitor's token is set.
+fn parse_stitches(
+    data: &[u8],
+    start: usize,
+    variant: DstVariant,
+    options: &ParseOptions,
+    metadata: &PatternMetadata,
+    sink: &mut impl StitchSink,
+    monitor: &mut LoadMonitor,
+) -> Result<Decoded, DstError> {
+    let mut records =
+        DstStitchIter::new(&data[start..], variant, options, metadata).starting_at(start);
+
+    // Barudan and ZSK have explicit trim codes; Tajima trims are jump runs
+    match (variant, options.trim_jump_threshold) {
+        (DstVariant::Tajima, Some(threshold)) => {
+            let mut collapser = JumpRunCollapser::new(sink, threshold);
+            feed(&mut records, &mut collapser, monitor)?;
+            collapser.finish();
+        }
+        _ => feed(&mut records, sink, monitor)?,
+    }
+    Ok(Decoded {
+        records: records.records(),
+        end: start + records.position,
+        ended: records.ended,
+        warnings: records.into_warnings(),
+    })
+}
+
+/// Pass every record to `sink`, checking the monitor before each one is read
+fn feed(
+    records: &mut DstStitchIter,
+    sink: &mut impl StitchSink,
+    monitor: &mut LoadMonitor,
+) -> Result<(), DstError> {
+    loop {
+        if !records.exhausted() && !monitor.tick(records.progress()) {
+            return Err(DstError::Cancelled);
+        }
+        match records.next() {
+            Some(stitch) => sink.push(&stitch),
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Compare the header's declared counts with what the stream produced
+fn check_header_counts(
+    metadata: &PatternMetadata,
+    records: usize,
+    color_changes: u32,
+    warnings: &mut Vec<ParseWarning>,
+) {
+    let decoded = records as u32;
+    if let Some(header) = metadata.stitch_count.filter(|&n| n != decoded) {
+        warnings.push(ParseWarning::StitchCountMismatch { header, decoded });
+    }
+
+    let decoded = color_changes;
+    if let Some(header) = metadata.color_count.filter(|&n| n != decoded) {
+        warnings.push(ParseWarning::ColorCountMismatch { header, decoded });
+    }
+}
+
+/// Parse a DST file from bytes, detecting Barudan or ZSK stitch encodings
+pub fn parse_dst(data: &[u8], options: &ParseOptions) -> Result<Pattern, DstError> {
+    parse_dst_variant(data, detect_variant(data), options)
+}
+
+/// Parse a DST-family file from bytes using the given stitch encoding
+///
+/// In strict mode the first warning raised while parsing is returned as an error.
+pub fn parse_dst_variant(
+    data: &[u8],
+    variant: DstVariant,
+    options: &ParseOptions,
+) -> Result<Pattern, DstError> {
+    parse_dst_monitored(data, variant, options, &mut LoadMonitor::none())
+}
+
+/// Parse a DST-family file, reporting progress to `monitor` and stopping
+/// with `Cancelled` once its token is set
+pub fn parse_dst_monitored(
+    data: &[u8],
+    variant: DstVariant,
+    options: &ParseOptions,
+    monitor: &mut LoadMonitor,
+) -> Result<Pattern, DstError> {
+    if data.len() < HEADER_SIZE {
+        return Err(DstError::short_header(data));
+    }
+
+    let (pattern, _) = decode_design(
+        data,
+        HEADER_SIZE,
+        variant,
+        options,
+        parse_header(data),
+        monitor,
+    )?;
+    Ok(pattern)
+}
+
+/// Decode the design whose records start at offset `start` of `data`, given
+/// what its header, if it has one, held
+fn decode_design(
+    data: &[u8],
+    start: usize,
+    variant: DstVariant,
+    options: &ParseOptions,
+    (metadata, mut warnings): (PatternMetadata, Vec<ParseWarning>),
+    monitor: &mut LoadMonitor,
+) -> Result<(Pattern, Decoded), DstError> {
+    let mut pattern = Pattern::new();
+    let mut decoded = parse_stitches(
+        data,
+        start,
+        variant,
+        options,
+        &metadata,
+        &mut pattern,
+        monitor,
+    )?;
+    warnings.append(&mut decoded.warnings);
+    check_header_counts(
+        &metadata,
+        decoded.records,
+        pattern.color_changes,
+        &mut warnings,
+    );
+    check_strict(options, &warnings)?;
+    pattern.metadata = metadata;
+    pattern.warnings = warnings;
+
+    // Calculate bounds, statistics and color blocks
+    pattern.calculate_bounds();
+    pattern.calculate_statistics();
+    pattern.calculate_color_blocks();
+
+    Ok((pattern, decoded))
+}
+
+/// Parse a DST file that may hold several designs one after another, as
+/// multi-design tapes are written
+///
+/// Whatever follows a design's End is read as the next design when it is
+/// complete: either a whole DST file, header and all, after any padding, or
+/// a run of records that finishes with its own End, continuing in the same
+/// encoding. Each design starts from the origin. Anything else is left as a
+/// `DataAfterEnd` warning on the last design read, so the first pattern is
+/// always what `parse_dst` returns.
+pub fn parse_dst_multi(data: &[u8], options: &ParseOptions) -> Result<Vec<Pattern>, DstError> {
+    if data.len() < HEADER_SIZE {
+        return Err(DstError::short_header(data));
+    }
+    // Whether the data after an End is another design is only known once it
+    // has been read, so strict mode judges the designs afterwards
+    let lenient = ParseOptions {
+        strict: false,
+        ..options.clone()
+    };
+    let monitor = &mut LoadMonitor::none();
+
+    let mut variant = detect_variant(data);
+    let header = parse_header(data);
+    let (pattern, mut decoded) =
+        decode_design(data, HEADER_SIZE, variant, &lenient, header, monitor)?;
+    let mut patterns = vec![pattern];
+
+    while decoded.ended && decoded.end < data.len() {
+        let after = decoded.end;
+        let padding = data[after..].iter().take_while(|&&b| is_padding(b)).count();
+        let file = &data[after + padding..];
+        let headed = file.starts_with(b"LA:") && file.len() >= HEADER_SIZE;
+        let (next, next_decoded) = if headed {
+            variant = detect_variant(file);
+            let (metadata, mut warnings) = parse_header(file);
+            for warning in &mut warnings {
+                if let ParseWarning::InvalidHeaderRecord { offset, .. } = warning {
+                    *offset += after + padding;
+                }
+            }
+            let start = after + padding + HEADER_SIZE;
+            let header = (metadata, warnings);
+            decode_design(data, start, variant, &lenient, header, monitor)?
+        } else {
+            let header = (PatternMetadata::default(), Vec::new());
+            decode_design(data, after, variant, &lenient, header, monitor)?
+        };
+        // Bare records need more proof than a header that they are a design
+        let complete = next_decoded.ended
+            && (headed || {
+                let clean = next
+                    .warnings
+                    .iter()
+                    .all(|w| matches!(w, ParseWarning::DataAfterEnd { .. }));
+                clean && well_framed(&data[after..next_decoded.end], variant)
+            });
+        if !complete {
+            break;
+        }
+
+        // The trailing data is a design after all
+        let last = patterns.last_mut().expect("the first design was read");
+        last.warnings
+            .retain(|w| !matches!(w, ParseWarning::DataAfterEnd { .. }));
+        patterns.push(next);
+        decoded = next_decoded;
+    }
+
+    for pattern in &patterns {
+        check_strict(options, &pattern.warnings)?;
+    }
+    Ok(patterns)
+}
+
+/// In strict mode, the first warning raised while parsing as an error
+fn check_strict(options: &ParseOptions, warnings: &[ParseWarning]) -> Result<(), DstError> {
+    match warnings.first() {
+        Some(warning) if options.strict => Err(DstError::Strict(warning.clone())),
+        _ => Ok(()),
+    }
+}
+
+/// Read a DST-family file's metadata, bounds, statistics and color blocks
+/// without keeping its stitches
+///
+/// Decodes exactly as `parse_dst_monitored` does, so the figures match those
+/// of the parsed pattern, but memory stays flat however many records the
+/// file holds.
+pub fn read_dst_info(
+    data: &[u8],
+    variant: DstVariant,
+    options: &ParseOptions,
+    monitor: &mut LoadMonitor,
+) -> Result<PatternInfo, DstError> {
+    if data.len() < HEADER_SIZE {
+        return Err(DstError::short_header(data));
+    }
+
+    let (metadata, mut warnings) = parse_header(data);
+    let mut summary = RunningSummary::new();
+    let mut decoded = parse_stitches(
+        data,
+        HEADER_SIZE,
+        variant,
+        options,
+        &metadata,
+        &mut summary,
+        monitor,
+    )?;
+    warnings.append(&mut decoded.warnings);
+    let mut info = summary.finish(metadata, Vec::new());
+    check_header_counts(
+        &info.metadata,
+        decoded.records,
+        info.color_changes,
+        &mut warnings,
+    );
+    check_strict(options, &warnings)?;
+    info.warnings = warnings;
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::summary::feed_all;
+    use crate::dst::types::Bounds;
+    use crate::dst::writer::encode_record;
+
+    #[test]
+    fn test_decode_dx() {
+        // Test zero displacement
+        assert_eq!(decode_dx(0, 0, 0), 0);
+
+        // Test positive displacement (+1)
+        assert_eq!(decode_dx(0b00000001, 0, 0), 1);
+
+        // Test negative displacement (-1)
+        assert_eq!(decode_dx(0b00000010, 0, 0), -1);
+    }
+
+    #[test]
+    fn test_decode_dy() {
+        // Test zero displacement
+        assert_eq!(decode_dy(0, 0, 0), 0);
+
+        // Note: Y is inverted in DST format
+        assert_eq!(decode_dy(0b10000000, 0, 0), -1);
+        assert_eq!(decode_dy(0b01000000, 0, 0), 1);
+    }
+
+    /// Build a DST file with a blank header followed by the given records
+    fn build_dst(records: &[[u8; 3]]) -> Vec<u8> {
+        let mut data = vec![b' '; HEADER_SIZE];
+        for record in records {
+            data.extend_from_slice(record);
+        }
+        data
+    }
+
+    /// Build a standard Tajima header from its text records, padded to 512 bytes
+    fn build_header(records: &[&str]) -> Vec<u8> {
+        let mut header = Vec::new();
+        for record in records {
+            header.extend_from_slice(record.as_bytes());
+            header.push(b'\r');
+        }
+        header.push(HEADER_TERMINATOR);
+        header.resize(HEADER_SIZE, b' ');
+        header
+    }
+
+    #[test]
+    fn test_parse_header_extended_fields() {
+        let header = build_header(&[
+            "LA:TEST DESIGN     ",
+            "ST:   1234",
+            "CO:  3",
+            "+X:  512",
+            "-X:  488",
+            "+Y:  300",
+            "-Y:  297",
+            "AX:+   24",
+            "AY:-    3",
+            "MX:+    0",
+            "MY:-   15",
+            "PD:******",
+        ]);
+
+        let (metadata, warnings) = parse_header(&header);
+        assert!(warnings.is_empty());
+        assert_eq!(metadata.label.as_deref(), Some("TEST DESIGN"));
+        assert_eq!(metadata.stitch_count, Some(1234));
+        assert_eq!(metadata.color_count, Some(3));
+        assert_eq!(metadata.extent_plus_x, Some(512));
+        assert_eq!(metadata.extent_minus_x, Some(488));
+        assert_eq!(metadata.extent_plus_y, Some(300));
+        assert_eq!(metadata.extent_minus_y, Some(297));
+        assert_eq!(metadata.end_offset_x, Some(24));
+        assert_eq!(metadata.end_offset_y, Some(-3));
+        assert_eq!(metadata.multi_start_x, Some(0));
+        assert_eq!(metadata.multi_start_y, Some(-15));
+        assert_eq!(metadata.previous_design.as_deref(), Some("******"));
+    }
+
+    #[test]
+    fn test_parse_header_missing_fields() {
+        let header = build_header(&["LA:SHORT", "ST:     10", "AX:+", "-Y:garbage"]);
+
+        let (metadata, warnings) = parse_header(&header);
+        assert_eq!(metadata.label.as_deref(), Some("SHORT"));
+        assert_eq!(metadata.stitch_count, Some(10));
+        assert_eq!(metadata.color_count, None);
+        assert_eq!(metadata.end_offset_x, None);
+        assert_eq!(metadata.extent_minus_y, None);
+        assert_eq!(metadata.previous_design, None);
+
+        // Offsets count from the start of the file, past each record's CR
+        assert_eq!(
+            warnings,
+            vec![
+                ParseWarning::InvalidHeaderRecord {
+                    offset: 20,
+                    key: "AX".to_string()
+                },
+                ParseWarning::InvalidHeaderRecord {
+                    offset: 25,
+                    key: "-Y".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_thread_colors_none() {
+        let header = build_header(&["LA:PLAIN", "CO:  1"]);
+        let pattern = parse_dst(&header, &ParseOptions::default()).unwrap();
+
+        assert!(pattern.metadata.thread_colors.is_empty());
+        assert!(pattern.thread_color_for_block(0).is_none());
+    }
+
+    #[test]
+    fn test_thread_colors_single() {
+        let header = build_header(&["LA:ONE", "CO:  0", "#TC1A2B3C Madeira Rayon 1147"]);
+        let (metadata, _) = parse_header(&header);
+
+        assert_eq!(
+            metadata.thread_colors,
+            vec![ThreadColor {
+                rgb: [0x1A, 0x2B, 0x3C],
+                description: Some("Madeira Rayon".to_string()),
+                catalog_number: Some("1147".to_string()),
+                needle: None,
+                basting: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_thread_colors_more_than_blocks() {
+        let mut data = build_header(&[
+            "LA:MANY",
+            "CO:  1",
+            "TC:#FF0000,Red,1037",
+            "TC:#00ff00,Green,",
+            "#TC0000FF Blue",
+        ]);
+        data.extend_from_slice(&encode_record(10, 0, 0));
+        data.extend_from_slice(&encode_record(0, 0, 0xC0));
+        data.extend_from_slice(&encode_record(10, 0, 0));
+        data.extend_from_slice(&encode_record(0, 0, 0xF0));
+
+        let pattern = parse_dst(&data, &ParseOptions::default()).unwrap();
+        let colors = &pattern.metadata.thread_colors;
+
+        assert_eq!(colors.len(), 3);
+        assert_eq!(colors[0].rgb, [0xFF, 0, 0]);
+        assert_eq!(colors[0].catalog_number.as_deref(), Some("1037"));
+        assert_eq!(colors[1].description.as_deref(), Some("Green"));
+        assert_eq!(colors[1].catalog_number, None);
+        assert_eq!(colors[2].description.as_deref(), Some("Blue"));
+
+        // Two blocks take the first two colors; the extra entry is kept but unused
+        assert_eq!(pattern.thread_color_for_block(1).unwrap().rgb, [0, 0xFF, 0]);
+        assert_eq!(pattern.color_changes, 1);
+    }
+
+    #[test]
+    fn test_encode_record_round_trip() {
+        for dx in -121..=121 {
+            let b = encode_record(dx, -dx, 0);
+            assert_eq!(decode_dx(b[0], b[1], b[2]), dx);
+            assert_eq!(decode_dy(b[0], b[1], b[2]), -dx);
+        }
+    }
+
+    #[test]
+    fn test_statistics_counters() {
+        let data = build_dst(&[
+            encode_record(30, 40, 0),    // first record, not measured
+            encode_record(30, 40, 0),    // 5.0mm
+            encode_record(10, 0, 0),     // 1.0mm
+            encode_record(100, 0, 0x80), // jump
+            encode_record(0, 0, 0xC0),   // color change
+            encode_record(0, 20, 0),     // 2.0mm
+            encode_record(0, 0, 0xF0),   // end
+        ]);
+
+        let pattern = parse_dst(&data, &ParseOptions::default()).unwrap();
+        let stats = &pattern.statistics;
+
+        assert_eq!(pattern.stitches.len(), 7);
+        assert_eq!(stats.real_stitch_count, 4);
+        assert_eq!(stats.jump_count, 1);
+        assert_eq!(stats.trim_count, 0);
+        assert_eq!(stats.color_change_count, 1);
+        assert!((stats.total_thread_length_mm - 8.0).abs() < 1e-9);
+        assert!((stats.min_stitch_length_mm - 1.0).abs() < 1e-9);
+        assert!((stats.max_stitch_length_mm - 5.0).abs() < 1e-9);
+        assert!((stats.avg_stitch_length_mm - 8.0 / 3.0).abs() < 1e-9);
+
+        let expected_minutes = 4.0 / 800.0 + 15.0 / 60.0;
+        assert!((stats.estimated_time_minutes - expected_minutes).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_statistics_empty_pattern() {
+        let pattern = parse_dst(&build_dst(&[]), &ParseOptions::default()).unwrap();
+        let stats = &pattern.statistics;
+
+        assert_eq!(stats.real_stitch_count, 0);
+        assert_eq!(stats.total_thread_length_mm, 0.0);
+        assert_eq!(stats.avg_stitch_length_mm, 0.0);
+        assert!(pattern.bounds.is_none());
+    }
+
+    /// One logical design as (dx, dy, command) steps in pattern space
+    const VARIANT_DESIGN: [(i32, i32, StitchCommand); 6] = [
+        (10, 20, StitchCommand::Stitch),
+        (-50, 5, StitchCommand::Move),
+        (3, -7, StitchCommand::Stitch),
+        (0, 0, StitchCommand::ColorChange),
+        (-1, 1, StitchCommand::Stitch),
+        (0, 0, StitchCommand::End),
+    ];
+
+    type Encoder = fn(i32, i32, StitchCommand) -> [u8; 3];
+
+    fn encode_tajima(dx: i32, dy: i32, command: StitchCommand) -> [u8; 3] {
+        let control = match command {
+            StitchCommand::Move => 0x80,
+            StitchCommand::ColorChange => 0xC0,
+            StitchCommand::End => 0xF0,
+            _ => 0,
+        };
+        encode_record(dx, dy, control)
+    }
+
+    fn encode_dsb(dx: i32, dy: i32, command: StitchCommand) -> [u8; 3] {
+        let control = match command {
+            StitchCommand::Stitch => 0x00,
+            StitchCommand::Move => 0x01,
+            StitchCommand::Trim => DSB_TRIM,
+            StitchCommand::ColorChange => DSB_NEEDLE_FIRST,
+            _ => 0xF8,
+        };
+        let signs = if dx < 0 { 0x40 } else { 0 } | if dy > 0 { 0x20 } else { 0 };
+        [
+            control | signs,
+            dy.unsigned_abs() as u8,
+            dx.unsigned_abs() as u8,
+        ]
+    }
+
+    fn encode_dsz(dx: i32, dy: i32, command: StitchCommand) -> [u8; 3] {
+        let control = match command {
+            StitchCommand::Stitch => 0x00,
+            StitchCommand::Move => 0x01,
+            StitchCommand::Trim => DSZ_TRIM,
+            StitchCommand::ColorChange => DSZ_NEEDLE_FIRST,
+            _ => DSZ_END,
+        };
+        let signs = if dx < 0 { 0x20 } else { 0 } | if dy > 0 { 0x40 } else { 0 };
+        [
+            dy.unsigned_abs() as u8,
+            dx.unsigned_abs() as u8,
+            control | signs,
+        ]
+    }
+
+    const ENCODERS: [(DstVariant, Encoder); 3] = [
+        (DstVariant::Tajima, encode_tajima),
+        (DstVariant::Barudan, encode_dsb),
+        (DstVariant::Zsk, encode_dsz),
+    ];
+
+    fn encode_design(encode: Encoder) -> Vec<u8> {
+        let records: Vec<_> = VARIANT_DESIGN
+            .iter()
+            .map(|&(dx, dy, command)| encode(dx, dy, command))
+            .collect();
+        build_dst(&records)
+    }
+
+    #[test]
+    fn test_variants_decode_same_design() {
+        let expected: Vec<_> = [
+            (10.0, 20.0, StitchCommand::Stitch),
+            (-40.0, 25.0, StitchCommand::Move),
+            (-37.0, 18.0, StitchCommand::Stitch),
+            (-37.0, 18.0, StitchCommand::ColorChange),
+            (-38.0, 19.0, StitchCommand::Stitch),
+            (-38.0, 19.0, StitchCommand::End),
+        ]
+        .iter()
+        .map(|&(x, y, command)| Stitch::new(x, y, command))
+        .collect();
+
+        for (variant, encode) in ENCODERS {
+            let pattern =
+                parse_dst_variant(&encode_design(encode), variant, &ParseOptions::default())
+                    .unwrap();
+            assert_eq!(pattern.stitches, expected, "{:?}", variant);
+        }
+    }
+
+    #[test]
+    fn test_variant_trims() {
+        let dsb = build_dst(&[encode_dsb(0, 0, StitchCommand::Trim)]);
+        let dsz = build_dst(&[encode_dsz(0, 0, StitchCommand::Trim)]);
+
+        let dsb = parse_dst_variant(&dsb, DstVariant::Barudan, &ParseOptions::default()).unwrap();
+        let dsz = parse_dst_variant(&dsz, DstVariant::Zsk, &ParseOptions::default()).unwrap();
+        assert_eq!(dsb.stitches[0].command, StitchCommand::Trim);
+        assert_eq!(dsz.stitches[0].command, StitchCommand::Trim);
+    }
+
+    #[test]
+    fn test_detect_variant() {
+        for (variant, encode) in ENCODERS {
+            let data = encode_design(encode);
+            assert_eq!(detect_variant(&data), variant);
+
+            // A renamed file still decodes with the right geometry
+            assert_eq!(
+                parse_dst(&data, &ParseOptions::default()).unwrap().stitches,
+                parse_dst_variant(&data, variant, &ParseOptions::default())
+                    .unwrap()
+                    .stitches
+            );
+        }
+
+        assert_eq!(detect_variant(&build_dst(&[])), DstVariant::Tajima);
+    }
+
+    #[test]
+    fn test_well_formed_file_has_no_warnings() {
+        let mut data = build_header(&["LA:CLEAN", "ST:      3", "CO:  1"]);
+        data.extend_from_slice(&encode_record(10, 0, 0));
+        data.extend_from_slice(&encode_record(0, 0, 0xC0));
+        data.extend_from_slice(&encode_record(0, 0, 0xF0));
+        // Padding after End is expected and not reported
+        data.extend_from_slice(&[0x1A, 0x00, 0x00]);
+
+        assert!(parse_dst(&data, &ParseOptions::default())
+            .unwrap()
+            .warnings
+            .is_empty());
+    }
+
+    #[test]
+    fn test_header_count_mismatch_warnings() {
+        let mut data = build_header(&["LA:WRONG", "ST:     99", "CO:  4"]);
+        data.extend_from_slice(&encode_record(10, 0, 0));
+        data.extend_from_slice(&encode_record(0, 0, 0xF0));
+
+        let pattern = parse_dst(&data, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            pattern.warnings,
+            vec![
+                ParseWarning::StitchCountMismatch {
+                    header: 99,
+                    decoded: 2
+                },
+                ParseWarning::ColorCountMismatch {
+                    header: 4,
+                    decoded: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_truncated_record_warning() {
+        let mut data = build_dst(&[encode_record(10, 0, 0), encode_record(5, 5, 0)]);
+        data.truncate(data.len() - 1);
+
+        let pattern = parse_dst(&data, &ParseOptions::default()).unwrap();
+        assert_eq!(pattern.stitches.len(), 2);
+        assert_eq!(
+            pattern.stitches[1],
+            Stitch::new(10.0, 0.0, StitchCommand::End)
+        );
+        assert_eq!(
+            pattern.warnings,
+            vec![
+                ParseWarning::TruncatedRecord {
+                    offset: HEADER_SIZE + 3,
+                    length: 2
+                },
+                ParseWarning::MissingEnd {
+                    offset: HEADER_SIZE + 5
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_end_warning() {
+        let pattern = parse_dst(
+            &build_dst(&[encode_record(10, 0, 0)]),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        // An End is made up where the last record left the needle
+        assert_eq!(
+            pattern.stitches,
+            vec![
+                Stitch::new(10.0, 0.0, StitchCommand::Stitch),
+                Stitch::new(10.0, 0.0, StitchCommand::End),
+            ]
+        );
+        assert_eq!(pattern.statistics.real_stitch_count, 1);
+        assert_eq!(
+            pattern.warnings,
+            vec![ParseWarning::MissingEnd {
+                offset: HEADER_SIZE + 3
+            }]
+        );
+    }
+
+    #[test]
+    fn test_data_after_end_warning() {
+        let data = build_dst(&[
+            encode_record(10, 0, 0),
+            encode_record(0, 0, 0xF0),
+            encode_record(20, 20, 0),
+        ]);
+
+        let pattern = parse_dst(&data, &ParseOptions::default()).unwrap();
+        assert_eq!(pattern.stitches.len(), 2);
+        assert_eq!(
+            pattern.warnings,
+            vec![ParseWarning::DataAfterEnd {
+                offset: HEADER_SIZE + 6,
+                length: 3
+            }]
+        );
+
+        // A record that never ends is not another design
+        let patterns = parse_dst_multi(&data, &ParseOptions::default()).unwrap();
+        assert_eq!(patterns.len(), 1);
+        assert_eq!(patterns[0].warnings, pattern.warnings);
+    }
+
+    #[test]
+    fn test_multi_design_concatenated_files() {
+        let mut data = build_header(&["LA:FIRST"]);
+        data.extend_from_slice(&encode_record(10, 0, 0));
+        data.extend_from_slice(&encode_record(0, 0, 0xF0));
+        data.extend_from_slice(&[0x00; 7]);
+        let second = data.len();
+        data.extend_from_slice(&build_header(&["LA:SECOND"]));
+        data.extend_from_slice(&encode_record(0, 30, 0));
+        data.extend_from_slice(&encode_record(0, 0, 0xF0));
+
+        // The padding and the second header say nothing about the encoding
+        assert_eq!(detect_variant(&data), DstVariant::Tajima);
+
+        // parse_dst still reads just the first, with the rest left over
+        let first = parse_dst(&data, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            first.warnings,
+            vec![ParseWarning::DataAfterEnd {
+                offset: HEADER_SIZE + 6,
+                length: data.len() - HEADER_SIZE - 6,
+            }]
+        );
+
+        let strict = ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        };
+        let patterns = parse_dst_multi(&data, &strict).unwrap();
+        assert_eq!(patterns.len(), 2);
+        assert_eq!(patterns[0].metadata.label.as_deref(), Some("FIRST"));
+        assert_eq!(patterns[0].stitches, first.stitches);
+        assert!(patterns[0].warnings.is_empty());
+        assert_eq!(patterns[1].metadata.label.as_deref(), Some("SECOND"));
+        assert_eq!(
+            patterns[1].stitches,
+            vec![
+                Stitch::new(0.0, 30.0, StitchCommand::Stitch),
+                Stitch::new(0.0, 30.0, StitchCommand::End),
+            ]
+        );
+
+        // A garbled header in the second design is reported where it is
+        data[second + 3] = 0xFF;
+        let patterns = parse_dst_multi(&data, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            patterns[1].warnings,
+            vec![ParseWarning::InvalidHeaderRecord {
+                offset: second,
+                key: "LA".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_multi_design_bare_records() {
+        let data = build_dst(&[
+            encode_record(10, 0, 0),
+            encode_record(0, 0, 0xF0),
+            encode_record(0, 20, 0),
+            encode_record(5, 0, 0),
+            encode_record(0, 0, 0xF0),
+        ]);
+        let patterns = parse_dst_multi(&data, &ParseOptions::default()).unwrap();
+        assert_eq!(patterns.len(), 2);
+        assert!(patterns.iter().all(|p| p.warnings.is_empty()));
+        assert_eq!(patterns[0].stitches.len(), 2);
+        // Each design starts from the origin
+        assert_eq!(
+            patterns[1].stitches,
+            vec![
+                Stitch::new(0.0, 20.0, StitchCommand::Stitch),
+                Stitch::new(5.0, 20.0, StitchCommand::Stitch),
+                Stitch::new(5.0, 20.0, StitchCommand::End),
+            ]
+        );
+
+        // Records without the Tajima framing bits are garbage, not a design
+        let mut data = data;
+        let garbled = data.len() - 6;
+        data[garbled + 2] &= !0x03;
+        let patterns = parse_dst_multi(&data, &ParseOptions::default()).unwrap();
+        assert_eq!(patterns.len(), 1);
+        assert_eq!(
+            patterns[0].warnings,
+            vec![ParseWarning::DataAfterEnd {
+                offset: HEADER_SIZE + 6,
+                length: 9,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_strict_mode_rejects_header_mismatch() {
+        let mut data = build_header(&["LA:WRONG", "ST:     99"]);
+        data.extend_from_slice(&encode_record(10, 0, 0));
+        data.extend_from_slice(&encode_record(0, 0, 0xF0));
+
+        let strict = ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        };
+        assert!(matches!(
+            parse_dst(&data, &strict),
+            Err(DstError::Strict(ParseWarning::StitchCountMismatch {
+                header: 99,
+                decoded: 2
+            }))
+        ));
+        assert_eq!(
+            parse_dst(&data, &ParseOptions::default())
+                .unwrap()
+                .warnings
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_unknown_control_codes() {
+        let dsb = build_dst(&[encode_dsb(5, 0, StitchCommand::Stitch), [0x05, 0, 0]]);
+        let dsz = build_dst(&[[0, 5, 0x05], encode_dsz(0, 0, StitchCommand::End)]);
+        let lenient = ParseOptions::default();
+        let strict = ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        };
+
+        // Lenient mode keeps the fallback command and records the code
+        let pattern = parse_dst_variant(&dsb, DstVariant::Barudan, &lenient).unwrap();
+        assert_eq!(pattern.stitches.last().unwrap().command, StitchCommand::End);
+        assert_eq!(
+            pattern.warnings,
+            vec![ParseWarning::UnknownControl {
+                offset: HEADER_SIZE + 3,
+                code: 0x05
+            }]
+        );
+        let pattern = parse_dst_variant(&dsz, DstVariant::Zsk, &lenient).unwrap();
+        assert_eq!(pattern.stitches[0].command, StitchCommand::Stitch);
+        assert_eq!(pattern.warnings.len(), 1);
+
+        assert!(parse_dst_variant(&dsb, DstVariant::Barudan, &strict).is_err());
+        assert!(parse_dst_variant(&dsz, DstVariant::Zsk, &strict).is_err());
+    }
+
+    #[test]
+    fn test_max_stitches_limit() {
+        let data = build_dst(&[encode_record(1, 0, 0); 10]);
+        let options = ParseOptions {
+            max_stitches: Some(4),
+            ..ParseOptions::default()
+        };
+
+        // Four records, then the End that closes the design
+        let pattern = parse_dst(&data, &options).unwrap();
+        assert_eq!(pattern.stitches.len(), 5);
+        assert_eq!(
+            pattern.stitches.last(),
+            Some(&Stitch::new(4.0, 0.0, StitchCommand::End))
+        );
+        assert_eq!(
+            pattern.warnings,
+            vec![ParseWarning::StitchLimitReached { limit: 4 }]
+        );
+    }
+
+    /// A stitch, `jumps` jump records of +10 X each, then a landing stitch
+    fn jump_run(jumps: usize) -> Vec<u8> {
+        let mut records = vec![encode_record(5, 5, 0)];
+        records.extend(std::iter::repeat_n(encode_record(10, 0, 0x80), jumps));
+        records.push(encode_record(1, 0, 0));
+        records.push(encode_record(0, 0, 0xF0));
+        build_dst(&records)
+    }
+
+    #[test]
+    fn test_jump_runs_below_threshold_stay_moves() {
+        let pattern = parse_dst(&jump_run(2), &ParseOptions::default()).unwrap();
+
+        use StitchCommand::*;
+        let commands: Vec<_> = pattern.stitches.iter().map(|s| s.command).collect();
+        assert_eq!(commands, vec![Stitch, Move, Move, Stitch, End]);
+        assert_eq!(pattern.statistics.trim_count, 0);
+    }
+
+    #[test]
+    fn test_jump_runs_collapse_to_trim() {
+        for jumps in [3, 7] {
+            let pattern = parse_dst(&jump_run(jumps), &ParseOptions::default()).unwrap();
+            let landing_x = 5.0 + 10.0 * jumps as f64;
+
+            use StitchCommand::*;
+            let decoded: Vec<_> = pattern
+                .stitches
+                .iter()
+                .map(|s| (s.x, s.y, s.command))
+                .collect();
+            assert_eq!(
+                decoded,
+                vec![
+                    (5.0, 5.0, Stitch),
+                    (5.0, 5.0, Trim),
+                    (landing_x, 5.0, Move),
+                    (landing_x + 1.0, 5.0, Stitch),
+                    (landing_x + 1.0, 5.0, End),
+                ],
+                "{} jumps",
+                jumps
+            );
+            assert_eq!(pattern.statistics.trim_count, 1);
+            assert_eq!(pattern.statistics.jump_count, 1);
+        }
+    }
+
+    #[test]
+    fn test_trim_threshold_is_configurable() {
+        let options = ParseOptions {
+            trim_jump_threshold: None,
+            ..ParseOptions::default()
+        };
+        let pattern = parse_dst(&jump_run(7), &options).unwrap();
+        assert_eq!(pattern.statistics.trim_count, 0);
+        assert_eq!(pattern.statistics.jump_count, 7);
+
+        let options = ParseOptions {
+            trim_jump_threshold: Some(2),
+            ..ParseOptions::default()
+        };
+        let pattern = parse_dst(&jump_run(2), &options).unwrap();
+        assert_eq!(pattern.statistics.trim_count, 1);
+    }
+
+    #[test]
+    fn test_header_count_uses_records_before_collapsing() {
+        let mut data = build_header(&["LA:TRIMS", "ST:      6"]);
+        data.extend_from_slice(&jump_run(3)[HEADER_SIZE..]);
+
+        let pattern = parse_dst(&data, &ParseOptions::default()).unwrap();
+        assert_eq!(pattern.stitches.len(), 5);
+        assert!(pattern.warnings.is_empty());
+    }
+
+    /// Deterministic pseudo-random bytes
+    fn garbage(length: usize, mut seed: u32) -> Vec<u8> {
+        (0..length)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                seed as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_header_count_limits_garbage() {
+        let mut data = build_header(&["LA:CORRUPT", "ST:    100"]);
+        // Plain stitch records only, so nothing stops the stream except the guard
+        data.extend(
+            garbage(30_000, 0x9E3779B9)
+                .chunks(3)
+                .flat_map(|c| [c[0], c[1], 0x03]),
+        );
+
+        let pattern = parse_dst(&data, &ParseOptions::default()).unwrap();
+        assert_eq!(pattern.stitches.len(), 401);
+        assert!(pattern
+            .warnings
+            .contains(&ParseWarning::StitchLimitReached { limit: 400 }));
+    }
+
+    #[test]
+    fn test_coordinates_stay_in_physical_range() {
+        // Each record moves +121 in X; 5m is reached after about 413 records
+        let data = build_dst(&vec![encode_record(121, 0, 0); 1000]);
+
+        let pattern = parse_dst(&data, &ParseOptions::default()).unwrap();
+        assert_eq!(pattern.stitches.len(), 414);
+        assert!(pattern.bounds.unwrap().max_x <= MAX_COORDINATE);
+        assert!(matches!(
+            pattern.warnings.last(),
+            Some(ParseWarning::CoordinateOutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn test_random_bytes_are_bounded() {
+        let start = std::time::Instant::now();
+        let options = ParseOptions {
+            max_stitches: Some(10_000),
+            ..ParseOptions::default()
+        };
+
+        for seed in 1..=20u32 {
+            let mut data = vec![b' '; HEADER_SIZE];
+            data.extend(garbage(200_000, seed.wrapping_mul(0x2545F491)));
+            for variant in [DstVariant::Tajima, DstVariant::Barudan, DstVariant::Zsk] {
+                let pattern = parse_dst_variant(&data, variant, &options).unwrap();
+                assert!(pattern.stitches.len() <= 10_001);
+                if let Some(bounds) = pattern.bounds {
+                    assert!(bounds.max_x.abs().max(bounds.min_x.abs()) <= MAX_COORDINATE);
+                    assert!(bounds.max_y.abs().max(bounds.min_y.abs()) <= MAX_COORDINATE);
+                }
+            }
+        }
+
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+    }
+
+    /// Inputs that reached corners of the parser under fuzzing, kept as
+    /// regressions
+    #[test]
+    fn test_corrupt_input_regressions() {
+        let options = ParseOptions::default();
+
+        // One byte short of a header, and a header with no records at all
+        let error = parse_dst(&[b' '; HEADER_SIZE - 1], &options).unwrap_err();
+        assert_eq!(error.offset(), Some(HEADER_SIZE - 1));
+        assert_eq!(
+            error.to_string(),
+            "Invalid DST file: data ends at offset 511, but at least 512 bytes are needed"
+        );
+        let empty = parse_dst(&[b' '; HEADER_SIZE], &options).unwrap();
+        assert!(empty.stitches.is_empty());
+        assert_eq!(
+            empty.warnings,
+            vec![ParseWarning::MissingEnd {
+                offset: HEADER_SIZE
+            }]
+        );
+
+        // A label whose last character is cut in two by the end of the header
+        let mut data = b"LA:".to_vec();
+        data.resize(HEADER_SIZE - 1, b'A');
+        data.extend_from_slice(&[0xC3, 0xA9, 0x00, 0x03]);
+        data.extend_from_slice(&encode_record(0, 0, 0xF0));
+        let pattern = parse_dst(&data, &options).unwrap();
+        assert_eq!(pattern.stitches.len(), 2);
+        assert!(pattern.metadata.label.unwrap().ends_with('\u{FFFD}'));
+        assert_eq!(
+            pattern.warnings,
+            vec![ParseWarning::InvalidHeaderRecord {
+                offset: 0,
+                key: "LA".to_string()
+            }]
+        );
+
+        // Counts that are not numbers, or too large for their field
+        let mut data = build_header(&["LA:GARBLED", "ST:12x4", "CO:99999999999"]);
+        data.extend_from_slice(&encode_record(0, 0, 0xF0));
+        let pattern = parse_dst(&data, &options).unwrap();
+        assert_eq!(pattern.metadata.stitch_count, None);
+        assert_eq!(pattern.metadata.color_count, None);
+        let offsets: Vec<_> = pattern.warnings.iter().map(|w| w.offset()).collect();
+        assert_eq!(offsets, vec![Some(11), Some(19)]);
+
+        // Strict mode names the offset in its error
+        let strict = ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        };
+        let error = parse_dst(&data, &strict).unwrap_err();
+        assert_eq!(error.offset(), Some(11));
+        assert_eq!(
+            error.to_string(),
+            "Header record ST at offset 11 could not be read"
+        );
+
+        // Nothing after End is decoded, whatever it holds
+        let mut data = build_dst(&[encode_record(10, 0, 0), encode_record(0, 0, 0xF0)]);
+        data.extend(garbage(999, 7));
+        let pattern = parse_dst_variant(&data, DstVariant::Tajima, &options).unwrap();
+        assert_eq!(pattern.stitches.len(), 2);
+        assert_eq!(
+            pattern.warnings,
+            vec![ParseWarning::DataAfterEnd {
+                offset: HEADER_SIZE + 6,
+                length: 999
+            }]
+        );
+    }
+
+    #[test]
+    fn test_cancellation_stops_within_one_interval() {
+        use crate::progress::{CancelToken, PROGRESS_INTERVAL};
+
+        // Back-and-forth stitches so coordinates stay in range
+        let records: Vec<_> = (0..600_000)
+            .map(|i| encode_record(if i % 2 == 0 { 1 } else { -1 }, 0, 0))
+            .collect();
+        let data = build_dst(&records);
+        let options = ParseOptions {
+            header_stitch_factor: None,
+            ..ParseOptions::default()
+        };
+        let token = CancelToken::new();
+        let mut reports = Vec::new();
+        let mut report = |progress: LoadProgress| {
+            reports.push(progress);
+            if progress.stitches_decoded >= 100_000 {
+                token.cancel();
+            }
+        };
+
+        let mut monitor = LoadMonitor::new(Some(&token), Some(&mut report));
+        let result = parse_dst_monitored(&data, DstVariant::Tajima, &options, &mut monitor);
+        assert!(matches!(result, Err(DstError::Cancelled)));
+
+        // The flag is read right after the report that set it
+        let last = reports.last().unwrap();
+        assert!(last.stitches_decoded < 100_000 + PROGRESS_INTERVAL);
+        assert_eq!(
+            last.bytes_processed,
+            HEADER_SIZE + last.stitches_decoded * 3
+        );
+        assert_eq!(last.total_bytes, data.len());
+
+        // A token cancelled up front stops before the first record
+        let mut monitor = LoadMonitor::new(Some(&token), None);
+        assert!(matches!(
+            parse_dst_monitored(&data, DstVariant::Tajima, &options, &mut monitor),
+            Err(DstError::Cancelled)
+        ));
+    }
+
+    /// The decoder and pattern figures as they were before records were
+    /// streamed, kept to check the streaming versions against
+    mod reference {
+        use super::super::*;
+        use crate::dst::types::{
+            BlockStatistics, Bounds, ColorBlock, PatternStatistics, UNITS_PER_MM,
+        };
+        use crate::dst::TimeEstimator;
+
+        pub fn parse(
+            data: &[u8],
+            variant: DstVariant,
+            options: &ParseOptions,
+        ) -> Result<Pattern, DstError> {
+            if data.len() < HEADER_SIZE {
+                return Err(DstError::short_header(data));
+            }
+            let mut pattern = Pattern::new();
+            (pattern.metadata, pattern.warnings) = parse_header(data);
+            let data = &data[HEADER_SIZE..];
+
+            let (mut position, mut x, mut y, mut sequin_mode) = (0, 0.0, 0.0, false);
+            let header_limit = options
+                .header_stitch_factor
+                .zip(pattern.metadata.stitch_count.filter(|&n| n > 0))
+                .map(|(factor, declared)| (declared as usize).saturating_mul(factor));
+            let limit = [options.max_stitches, header_limit]
+                .into_iter()
+                .flatten()
+                .min();
+            let mut ended = false;
+            loop {
+                let records = pattern.stitches.len();
+                if limit.is_some_and(|limit| records >= limit) {
+                    let warning = ParseWarning::StitchLimitReached { limit: records };
+                    pattern.warnings.push(warning);
+                    break;
+                }
+                let offset = HEADER_SIZE + position;
+                let Some(&[b0, b1, b2]) = data.get(position..position + 3) else {
+                    if position < data.len() {
+                        pattern.warnings.push(ParseWarning::TruncatedRecord {
+                            offset,
+                            length: data.len() - position,
+                        });
+                    }
+                    let offset = HEADER_SIZE + data.len();
+                    pattern.warnings.push(ParseWarning::MissingEnd { offset });
+                    break;
+                };
+                position += 3;
+                let record = match variant {
+                    DstVariant::Tajima => decode_tajima_record([b0, b1, b2], &mut sequin_mode),
+                    DstVariant::Barudan => decode_dsb_record([b0, b1, b2]),
+                    DstVariant::Zsk => decode_dsz_record([b0, b1, b2]),
+                };
+                if let Some(code) = record.unknown_control {
+                    let warning = ParseWarning::UnknownControl { offset, code };
+                    pattern.warnings.push(warning);
+                }
+                let (next_x, next_y) = (x + record.dx as f64, y + record.dy as f64);
+                if next_x.abs() > MAX_COORDINATE || next_y.abs() > MAX_COORDINATE {
+                    let warning = ParseWarning::CoordinateOutOfRange { offset };
+                    pattern.warnings.push(warning);
+                    break;
+                }
+                (x, y) = (next_x, next_y);
+                pattern.add_stitch(x, y, record.command);
+                if record.command == StitchCommand::End {
+                    ended = true;
+                    let trailing = &data[position..];
+                    if !trailing.iter().copied().all(is_padding) {
+                        pattern.warnings.push(ParseWarning::DataAfterEnd {
+                            offset: HEADER_SIZE + position,
+                            length: trailing.len(),
+                        });
+                    }
+                    break;
+                }
+            }
+
+            let records = pattern.stitches.len();
+            if !ended && records > 0 {
+                pattern.add_stitch(x, y, StitchCommand::End);
+            }
+            if let (DstVariant::Tajima, Some(threshold)) = (variant, options.trim_jump_threshold) {
+                let stitches = std::mem::take(&mut pattern.stitches);
+                pattern.stitches = collapse_jump_runs(stitches, threshold);
+            }
+            let mut warnings = std::mem::take(&mut pattern.warnings);
+            check_header_counts(
+                &pattern.metadata,
+                records,
+                pattern.color_changes,
+                &mut warnings,
+            );
+            check_strict(options, &warnings)?;
+            pattern.warnings = warnings;
+
+            pattern.bounds = bounds(&pattern.stitches);
+            pattern.statistics = statistics(&pattern.stitches);
+            pattern.color_blocks = color_blocks(&pattern);
+            Ok(pattern)
+        }
+
+        fn collapse_jump_runs(stitches: Vec<Stitch>, threshold: usize) -> Vec<Stitch> {
+            let mut collapsed = Vec::with_capacity(stitches.len());
+            let mut index = 0;
+            while index < stitches.len() {
+                let run = stitches[index..]
+                    .iter()
+                    .take_while(|s| s.command == StitchCommand::Move)
+                    .count();
+                if run == 0 {
+                    collapsed.push(stitches[index].clone());
+                    index += 1;
+                    continue;
+                }
+                if run >= threshold.max(1) {
+                    let (x, y) = collapsed.last().map_or((0.0, 0.0), |s: &Stitch| (s.x, s.y));
+                    let landing = &stitches[index + run - 1];
+                    collapsed.push(Stitch::new(x, y, StitchCommand::Trim));
+                    if (landing.x, landing.y) != (x, y) {
+                        collapsed.push(Stitch::new(landing.x, landing.y, StitchCommand::Move));
+                    }
+                } else {
+                    collapsed.extend_from_slice(&stitches[index..index + run]);
+                }
+                index += run;
+            }
+            collapsed
+        }
+
+        fn length_mm(stitches: &[Stitch], index: usize) -> Option<f64> {
+            let prev = stitches.get(index.checked_sub(1)?)?;
+            let stitch = &stitches[index];
+            Some((stitch.x - prev.x).hypot(stitch.y - prev.y) / UNITS_PER_MM)
+        }
+
+        fn bounds(stitches: &[Stitch]) -> Option<Bounds> {
+            let mut bounds = Bounds::new();
+            for stitch in stitches {
+                bounds.update(stitch.x, stitch.y);
+            }
+            (!stitches.is_empty()).then_some(bounds)
+        }
+
+        fn statistics(stitches: &[Stitch]) -> PatternStatistics {
+            let mut stats = PatternStatistics::default();
+            let mut measured = 0u32;
+            for (index, stitch) in stitches.iter().enumerate() {
+                match stitch.command {
+                    StitchCommand::Stitch => {
+                        stats.real_stitch_count += 1;
+                        if let Some(length) = length_mm(stitches, index) {
+                            if measured == 0 || length < stats.min_stitch_length_mm {
+                                stats.min_stitch_length_mm = length;
+                            }
+                            if length > stats.max_stitch_length_mm {
+                                stats.max_stitch_length_mm = length;
+                            }
+                            stats.total_thread_length_mm += length;
+                            measured += 1;
+                        }
+                    }
+                    StitchCommand::Move => stats.jump_count += 1,
+                    StitchCommand::Trim => stats.trim_count += 1,
+                    StitchCommand::ColorChange => stats.color_change_count += 1,
+                    StitchCommand::SequinEject => stats.sequin_count += 1,
+                    _ => {}
+                }
+            }
+            stats.update_totals(measured);
+            stats
+        }
+
+        fn color_blocks(pattern: &Pattern) -> Vec<ColorBlock> {
+            let stitches = &pattern.stitches;
+            let mut ranges: Vec<(usize, usize)> = Vec::new();
+            let mut start = 0;
+            for (i, stitch) in stitches.iter().enumerate() {
+                if stitch.command == StitchCommand::ColorChange && i > start {
+                    ranges.push((start, i));
+                    start = i;
+                }
+            }
+            if start < stitches.len() {
+                ranges.push((start, stitches.len()));
+            }
+            let has_stitches = |&(start, end): &(usize, usize)| {
+                stitches[start..end]
+                    .iter()
+                    .any(|s| s.command == StitchCommand::Stitch)
+            };
+            if ranges.len() > 1 && !has_stitches(ranges.last().unwrap()) {
+                let (_, end) = ranges.pop().unwrap();
+                ranges.last_mut().unwrap().1 = end;
+            }
+
+            let mut blocks = Vec::new();
+            for (index, (start, end)) in ranges.into_iter().enumerate() {
+                let mut stats = BlockStatistics::default();
+                let mut bounds = Bounds::new();
+                let mut color_changes = 0u32;
+                for (i, stitch) in stitches.iter().enumerate().take(end).skip(start) {
+                    bounds.update(stitch.x, stitch.y);
+                    match stitch.command {
+                        StitchCommand::Stitch => {
+                            stats.stitch_count += 1;
+                            stats.thread_length_mm += length_mm(stitches, i).unwrap_or(0.0);
+                        }
+                        StitchCommand::Move => stats.jump_count += 1,
+                        StitchCommand::Trim => stats.trim_count += 1,
+                        StitchCommand::ColorChange => color_changes += 1,
+                        _ => {}
+                    }
+                }
+                stats.estimated_minutes = TimeEstimator::default().minutes(
+                    stats.stitch_count,
+                    stats.trim_count,
+                    color_changes,
+                );
+                stats.bounds = (start < end).then_some(bounds);
+                blocks.push(ColorBlock {
+                    index,
+                    start,
+                    end,
+                    stitch_count: stats.stitch_count,
+                    bounds: stats.bounds.clone(),
+                    color: pattern.metadata.thread_colors.get(index).cloned(),
+                    statistics: stats,
+                });
+            }
+            blocks
+        }
+    }
+
+    #[test]
+    fn test_streaming_matches_reference() {
+        let mut colored = build_header(&["LA:COLORS", "ST:    505", "TC:#FF0000,Red,1"]);
+        colored.extend_from_slice(&long_design(500)[HEADER_SIZE..]);
+        let mut padded = jump_run(4);
+        padded.extend_from_slice(&[0, 0, 0x1A, 0x42]);
+        let mut files = vec![
+            include_bytes!("../../tests/fixtures/dst/sequins.dst").to_vec(),
+            long_design(2_000),
+            colored,
+            jump_run(1),
+            jump_run(2),
+            jump_run(3),
+            jump_run(9),
+            padded,
+            build_dst(&[encode_record(3, 4, 0)]),
+            build_dst(&vec![encode_record(121, 0, 0); 1000]),
+        ];
+        for seed in [0x51ED270B, 0x9E3779B9, 0x2545F491, 0xDEADBEEF] {
+            files.push(garbage(3_000, seed));
+        }
+        let option_sets = [
+            ParseOptions::default(),
+            ParseOptions {
+                trim_jump_threshold: None,
+                ..ParseOptions::default()
+            },
+            ParseOptions {
+                trim_jump_threshold: Some(2),
+                ..ParseOptions::default()
+            },
+            ParseOptions {
+                max_stitches: Some(7),
+                header_stitch_factor: None,
+                ..ParseOptions::default()
+            },
+            ParseOptions {
+                strict: true,
+                ..ParseOptions::default()
+            },
+        ];
+
+        for (file, data) in files.iter().enumerate() {
+            for variant in [DstVariant::Tajima, DstVariant::Barudan, DstVariant::Zsk] {
+                for options in &option_sets {
+                    let context = format!("file {} {:?} {:?}", file, variant, options);
+                    let (new, old) = (
+                        parse_dst_variant(data, variant, options),
+                        reference::parse(data, variant, options),
+                    );
+                    let (new, old) = match (new, old) {
+                        (Ok(new), Ok(old)) => (new, old),
+                        (Err(new), Err(old)) => {
+                            assert_eq!(new.to_string(), old.to_string(), "{}", context);
+                            continue;
+                        }
+                        (new, old) => panic!("{}: {:?} against {:?}", context, new, old),
+                    };
+                    // Serialized floats round-trip, so equal JSON is equal bits
+                    let json = |pattern: &Pattern| serde_json::to_value(pattern).unwrap();
+                    assert_eq!(json(&new), json(&old), "{}", context);
+
+                    // The figures again, folded straight from the stream
+                    let info =
+                        read_dst_info(data, variant, options, &mut LoadMonitor::none()).unwrap();
+                    let (figures, old) = (serde_json::to_value(&info).unwrap(), json(&old));
+                    for key in ["bounds", "statistics", "color_blocks", "warnings"] {
+                        assert_eq!(figures[key], old[key], "{} {}", context, key);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_iterator_yields_records_unchanged() {
+        let data = long_design(300);
+        let options = ParseOptions {
+            trim_jump_threshold: None,
+            ..ParseOptions::default()
+        };
+        let (metadata, _) = parse_header(&data);
+        let mut records = DstStitchIter::new(
+            &data[HEADER_SIZE..],
+            DstVariant::Tajima,
+            &options,
+            &metadata,
+        );
+        let stitches: Vec<Stitch> = records.by_ref().collect();
+        assert_eq!(records.records(), stitches.len());
+        assert_eq!(records.progress().bytes_processed, data.len());
+        assert!(records.warnings().is_empty());
+        assert!(records.next().is_none());
+
+        let pattern = parse_dst_variant(&data, DstVariant::Tajima, &options).unwrap();
+        assert_eq!(stitches, pattern.stitches);
+
+        // Any sink can take the records without a pattern in between
+        let (bounds, summary) = feed_all(&stitches, (Bounds::new(), RunningSummary::new()));
+        assert_eq!(Some(bounds), pattern.bounds);
+        assert_eq!(
+            serde_json::to_value(summary.statistics()).unwrap(),
+            serde_json::to_value(&pattern.statistics).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_get_bit() {
+        assert_eq!(get_bit(0b00000001, 0), 1);
+        assert_eq!(get_bit(0b00000010, 1), 1);
+        assert_eq!(get_bit(0b00000001, 1), 0);
+    }
+
+    /// A DST of `stitches` back-and-forth stitches in three colors with
+    /// jump runs between them
+    fn long_design(stitches: usize) -> Vec<u8> {
+        let mut records = Vec::with_capacity(stitches + 16);
+        for i in 0..stitches {
+            let dx = if i % 2 == 0 { 20 } else { -20 };
+            records.push(encode_record(dx, (i % 3) as i32 - 1, 0));
+            if i == stitches / 3 || i == stitches * 2 / 3 {
+                records.extend(std::iter::repeat_n(encode_record(30, 0, 0x80), 4));
+                records.push(encode_record(0, 0, 0xC0));
+            }
+        }
+        records.push(encode_record(0, 0, 0xC0));
+        records.push(encode_record(0, 0, 0xF0));
+        build_dst(&records)
+    }
+
+    #[test]
+    fn test_info_matches_parsed_pattern() {
+        let files = [
+            long_design(500),
+            jump_run(7),
+            build_dst(&[encode_record(3, 4, 0)]),
+            garbage(3_000, 0x51ED270B),
+        ];
+        let figures = |pattern: serde_json::Value| {
+            [
+                "bounds",
+                "statistics",
+                "color_changes",
+                "color_blocks",
+                "warnings",
+            ]
+            .map(|key| pattern[key].clone())
+        };
+        for data in &files {
+            for variant in [DstVariant::Tajima, DstVariant::Barudan, DstVariant::Zsk] {
+                let options = ParseOptions::default();
+                let Ok(pattern) = parse_dst_variant(data, variant, &options) else {
+                    continue;
+                };
+                let info =
+                    read_dst_info(data, variant, &options, &mut LoadMonitor::none()).unwrap();
+                assert_eq!(info.stitch_count, pattern.stitches.len());
+                assert_eq!(
+                    figures(serde_json::to_value(&info).unwrap()),
+                    figures(serde_json::to_value(&pattern).unwrap()),
+                    "{:?}",
+                    variant
+                );
+            }
+        }
+
+        let strict = ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        };
+        let truncated = &jump_run(3)[..HEADER_SIZE + 7];
+        assert!(matches!(
+            read_dst_info(
+                truncated,
+                DstVariant::Tajima,
+                &strict,
+                &mut LoadMonitor::none()
+            ),
+            Err(DstError::Strict(ParseWarning::TruncatedRecord { .. }))
+        ));
+    }
+}
@@ -0,0 +1,441 @@
+// summary.rs - Stitch sinks, and statistics folded from stitches without keeping them
+
+use super::time::TimeEstimator;
+use super::types::{
+    BlockStatistics, Bounds, ColorBlock, ParseWarning, Pattern, PatternMetadata, PatternStatistics,
+    Stitch, StitchCommand, ThreadColor, UNITS_PER_MM,
+};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// Receives stitch records in sewing order, from a parser as it decodes
+/// them or from a pattern's stitch list
+///
+/// Building a pattern, its bounds, statistics and color blocks, a density
+/// grid and an info-only summary are all sinks, so one pass over the records
+/// can feed any of them.
+pub trait StitchSink {
+    fn push(&mut self, stitch: &Stitch);
+}
+
+impl StitchSink for Pattern {
+    fn push(&mut self, stitch: &Stitch) {
+        self.add_stitch(stitch.x, stitch.y, stitch.command);
+    }
+}
+
+impl StitchSink for Bounds {
+    fn push(&mut self, stitch: &Stitch) {
+        self.update(stitch.x, stitch.y);
+    }
+}
+
+/// Feeds two sinks from the same pass
+impl<A: StitchSink, B: StitchSink> StitchSink for (A, B) {
+    fn push(&mut self, stitch: &Stitch) {
+        self.0.push(stitch);
+        self.1.push(stitch);
+    }
+}
+
+/// Pass every stitch of a list to `sink`
+pub fn feed_all<'a, S: StitchSink>(
+    stitches: impl IntoIterator<Item = &'a Stitch>,
+    mut sink: S,
+) -> S {
+    for stitch in stitches {
+        sink.push(stitch);
+    }
+    sink
+}
+
+/// Replaces each run of `threshold` or more consecutive jumps with a Trim at
+/// the run's starting point followed by one Move to its landing point, on
+/// the way to another sink
+///
+/// Holds at most `threshold` jumps, however long a run is.
+pub struct JumpRunCollapser<'a, S: StitchSink> {
+    sink: &'a mut S,
+    threshold: usize,
+    /// The run so far; past the threshold only its latest jump is kept
+    run: Vec<Stitch>,
+    run_len: usize,
+    /// Position of the last record passed on
+    last: (f64, f64),
+}
+
+impl<'a, S: StitchSink> JumpRunCollapser<'a, S> {
+    pub fn new(sink: &'a mut S, threshold: usize) -> Self {
+        let threshold = threshold.max(1);
+        Self {
+            sink,
+            threshold,
+            run: Vec::with_capacity(threshold),
+            run_len: 0,
+            last: (0.0, 0.0),
+        }
+    }
+
+    fn emit(&mut self, stitch: &Stitch) {
+        self.last = (stitch.x, stitch.y);
+        self.sink.push(stitch);
+    }
+
+    fn flush(&mut self) {
+        if self.run_len >= self.threshold {
+            let (x, y) = self.last;
+            let landing = self.run.pop().expect("a run has a landing");
+            self.emit(&Stitch::new(x, y, StitchCommand::Trim));
+            // Trim-only runs return to where they started
+            if (landing.x, landing.y) != (x, y) {
+                self.emit(&landing);
+            }
+        } else {
+            let run = std::mem::take(&mut self.run);
+            for stitch in &run {
+                self.emit(stitch);
+            }
+            self.run = run;
+        }
+        self.run.clear();
+        self.run_len = 0;
+    }
+
+    /// Pass on a run still open when the records end
+    pub fn finish(mut self) {
+        self.flush();
+    }
+}
+
+impl<S: StitchSink> StitchSink for JumpRunCollapser<'_, S> {
+    fn push(&mut self, stitch: &Stitch) {
+        if stitch.command == StitchCommand::Move {
+            if self.run.len() < self.threshold {
+                self.run.push(stitch.clone());
+            } else {
+                *self.run.last_mut().unwrap() = stitch.clone();
+            }
+            self.run_len += 1;
+            return;
+        }
+        self.flush();
+        self.emit(stitch);
+    }
+}
+
+/// Everything about a pattern except its stitches
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct PatternInfo {
+    pub metadata: PatternMetadata,
+    pub bounds: Option<Bounds>,
+    pub statistics: PatternStatistics,
+    pub color_changes: u32,
+    pub color_blocks: Vec<ColorBlock>,
+    pub warnings: Vec<ParseWarning>,
+    /// Number of records, as `Pattern::stitches` would hold
+    pub stitch_count: usize,
+}
+
+impl PatternInfo {
+    /// Give the blocks their colors from a thread list found after parsing
+    pub fn set_thread_colors(&mut self, colors: Vec<ThreadColor>) {
+        for block in &mut self.color_blocks {
+            block.color = colors.get(block.index).cloned();
+        }
+        self.metadata.thread_colors = colors;
+    }
+}
+
+/// Figures for one color block while its records arrive
+#[derive(Debug)]
+struct BlockTally {
+    start: usize,
+    end: usize,
+    statistics: BlockStatistics,
+    bounds: Bounds,
+    color_changes: u32,
+}
+
+impl BlockTally {
+    fn new(start: usize) -> Self {
+        Self {
+            start,
+            end: start,
+            statistics: BlockStatistics::default(),
+            bounds: Bounds::new(),
+            color_changes: 0,
+        }
+    }
+
+    /// Fold a trailing block with no real stitches into this one
+    fn absorb(&mut self, other: BlockTally) {
+        self.end = other.end;
+        let (stats, more) = (&mut self.statistics, other.statistics);
+        stats.stitch_count += more.stitch_count;
+        stats.jump_count += more.jump_count;
+        stats.trim_count += more.trim_count;
+        stats.thread_length_mm += more.thread_length_mm;
+        self.bounds.update(other.bounds.min_x, other.bounds.min_y);
+        self.bounds.update(other.bounds.max_x, other.bounds.max_y);
+        self.color_changes += other.color_changes;
+    }
+
+    fn into_block(self, index: usize, thread_colors: &[ThreadColor]) -> ColorBlock {
+        let mut statistics = self.statistics;
+        statistics.estimated_minutes = TimeEstimator::default().minutes(
+            statistics.stitch_count,
+            statistics.trim_count,
+            self.color_changes,
+        );
+        statistics.bounds = (self.start < self.end).then_some(self.bounds);
+        ColorBlock {
+            index,
+            start: self.start,
+            end: self.end,
+            stitch_count: statistics.stitch_count,
+            bounds: statistics.bounds.clone(),
+            color: thread_colors.get(index).cloned(),
+            statistics,
+        }
+    }
+}
+
+/// Folds records into the same bounds, statistics and color blocks that
+/// `Pattern` computes from its stitch list, keeping only the previous
+/// record and one tally per color block
+#[derive(Debug)]
+pub struct RunningSummary {
+    records: usize,
+    previous: Option<(f64, f64)>,
+    bounds: Bounds,
+    statistics: PatternStatistics,
+    measured: u32,
+    blocks: Vec<BlockTally>,
+    block: BlockTally,
+}
+
+impl Default for RunningSummary {
+    fn default() -> Self {
+        Self {
+            records: 0,
+            previous: None,
+            bounds: Bounds::new(),
+            statistics: PatternStatistics::default(),
+            measured: 0,
+            blocks: Vec::new(),
+            block: BlockTally::new(0),
+        }
+    }
+}
+
+impl RunningSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Extent of every record; None before the first
+    pub fn bounds(&self) -> Option<Bounds> {
+        (self.records > 0).then(|| self.bounds.clone())
+    }
+
+    /// Counts, lengths, thread use and run time of the records so far
+    pub fn statistics(&self) -> PatternStatistics {
+        let mut statistics = self.statistics.clone();
+        statistics.update_totals(self.measured);
+        statistics
+    }
+
+    /// Split the records into color blocks, colored from `thread_colors`
+    ///
+    /// A ColorChange as the very first record does not open an empty block,
+    /// and a trailing block with no real stitches (e.g. a ColorChange right
+    /// before End) is folded into the block before it.
+    pub fn color_blocks(self, thread_colors: &[ThreadColor]) -> Vec<ColorBlock> {
+        let mut tallies = self.blocks;
+        let last = self.block;
+        if last.start < last.end {
+            match tallies.last_mut() {
+                Some(before) if last.statistics.stitch_count == 0 => before.absorb(last),
+                _ => tallies.push(last),
+            }
+        }
+        tallies
+            .into_iter()
+            .enumerate()
+            .map(|(index, tally)| tally.into_block(index, thread_colors))
+            .collect()
+    }
+
+    pub fn finish(self, metadata: PatternMetadata, warnings: Vec<ParseWarning>) -> PatternInfo {
+        let (bounds, statistics, stitch_count) = (self.bounds(), self.statistics(), self.records);
+        PatternInfo {
+            color_blocks: self.color_blocks(&metadata.thread_colors),
+            metadata,
+            bounds,
+            color_changes: statistics.color_change_count,
+            statistics,
+            warnings,
+            stitch_count,
+        }
+    }
+}
+
+impl StitchSink for RunningSummary {
+    fn push(&mut self, stitch: &Stitch) {
+        let index = self.records;
+        let length = self
+            .previous
+            .map(|(x, y)| (stitch.x - x).hypot(stitch.y - y) / UNITS_PER_MM);
+        self.previous = Some((stitch.x, stitch.y));
+        self.records += 1;
+        self.bounds.update(stitch.x, stitch.y);
+
+        // A color change opens a new block unless it is the block's first record
+        if stitch.command == StitchCommand::ColorChange && index > self.block.start {
+            let done = std::mem::replace(&mut self.block, BlockTally::new(index));
+            self.blocks.push(done);
+        }
+        let block = &mut self.block;
+        block.end = index + 1;
+        block.bounds.update(stitch.x, stitch.y);
+
+        let stats = &mut self.statistics;
+        match stitch.command {
+            StitchCommand::Stitch => {
+                stats.real_stitch_count += 1;
+                block.statistics.stitch_count += 1;
+                block.statistics.thread_length_mm += length.unwrap_or(0.0);
+                // The first record has no predecessor to measure from
+                if let Some(length) = length {
+                    if self.measured == 0 || length < stats.min_stitch_length_mm {
+                        stats.min_stitch_length_mm = length;
+                    }
+                    if length > stats.max_stitch_length_mm {
+                        stats.max_stitch_length_mm = length;
+                    }
+                    stats.total_thread_length_mm += length;
+                    self.measured += 1;
+                }
+            }
+            StitchCommand::Move => {
+                stats.jump_count += 1;
+                block.statistics.jump_count += 1;
+            }
+            StitchCommand::Trim => {
+                stats.trim_count += 1;
+                block.statistics.trim_count += 1;
+            }
+            StitchCommand::ColorChange => {
+                stats.color_change_count += 1;
+                block.color_changes += 1;
+            }
+            StitchCommand::SequinEject => stats.sequin_count += 1,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StitchCommand::{ColorChange, End, Move, SequinEject, SequinMode, Trim};
+
+    const SEW: StitchCommand = StitchCommand::Stitch;
+
+    const RECORDS: &[(f64, f64, StitchCommand)] = &[
+        (0.0, 0.0, ColorChange),
+        (0.0, 0.0, SEW),
+        (30.0, 0.0, SEW),
+        (30.0, 40.0, SEW),
+        (60.0, 40.0, Move),
+        (90.0, 40.0, Move),
+        (90.0, 40.0, Trim),
+        (90.0, 40.0, ColorChange),
+        (90.0, 10.0, SEW),
+        (-20.0, 10.0, SEW),
+        (-20.0, 10.0, SequinMode),
+        (-10.0, 10.0, SequinEject),
+        (-10.0, 10.0, ColorChange),
+        (-10.0, 50.0, Move),
+        (-10.0, 50.0, End),
+    ];
+
+    fn summarize(pattern: &Pattern) -> PatternInfo {
+        feed_all(&pattern.stitches, RunningSummary::new())
+            .finish(pattern.metadata.clone(), Vec::new())
+    }
+
+    #[test]
+    fn test_folds_blocks_and_statistics() {
+        let mut pattern = Pattern::new();
+        for &(x, y, command) in RECORDS {
+            pattern.add_stitch(x, y, command);
+        }
+        pattern.metadata.thread_colors = vec![ThreadColor::new([1, 2, 3])];
+
+        let info = summarize(&pattern);
+        assert_eq!(info.stitch_count, RECORDS.len());
+        assert_eq!(
+            info.bounds,
+            Some(Bounds {
+                min_x: -20.0,
+                min_y: 0.0,
+                max_x: 90.0,
+                max_y: 50.0
+            })
+        );
+        let stats = &info.statistics;
+        assert_eq!(
+            (stats.real_stitch_count, stats.jump_count, stats.trim_count),
+            (5, 3, 1)
+        );
+        assert_eq!((stats.color_change_count, stats.sequin_count), (3, 1));
+        assert_eq!(info.color_changes, 3);
+        assert_eq!(stats.total_thread_length_mm, 21.0);
+        assert_eq!(
+            (stats.min_stitch_length_mm, stats.max_stitch_length_mm),
+            (0.0, 11.0)
+        );
+        assert_eq!(stats.avg_stitch_length_mm, 21.0 / 5.0);
+
+        // The leading color change opens no block, and the trailing block
+        // without stitches folds into the one before it
+        let spans: Vec<_> = info
+            .color_blocks
+            .iter()
+            .map(|b| (b.start, b.end, b.stitch_count, b.statistics.jump_count))
+            .collect();
+        assert_eq!(spans, vec![(0, 7, 3, 2), (7, 15, 2, 1)]);
+        assert_eq!(info.color_blocks[0].statistics.thread_length_mm, 7.0);
+        assert_eq!(info.color_blocks[1].statistics.thread_length_mm, 14.0);
+        assert_eq!(
+            info.color_blocks[0].color,
+            Some(ThreadColor::new([1, 2, 3]))
+        );
+        assert_eq!(info.color_blocks[1].color, None);
+
+        let empty = summarize(&Pattern::new());
+        assert!(empty.bounds.is_none() && empty.color_blocks.is_empty());
+    }
+
+    #[test]
+    fn test_collapser_bounds_long_runs() {
+        let mut out = Pattern::new();
+        let mut collapser = JumpRunCollapser::new(&mut out, 3);
+        collapser.push(&Stitch::new(5.0, 5.0, SEW));
+        for i in 1..=1000 {
+            collapser.push(&Stitch::new(5.0 + i as f64, 5.0, Move));
+            assert!(collapser.run.len() <= 3);
+        }
+        collapser.push(&Stitch::new(1005.0, 5.0, SEW));
+        // Two jumps stay jumps
+        collapser.push(&Stitch::new(1006.0, 5.0, Move));
+        collapser.push(&Stitch::new(1007.0, 5.0, Move));
+        collapser.finish();
+
+        let commands: Vec<_> = out.stitches.iter().map(|s| s.command).collect();
+        assert_eq!(commands, vec![SEW, Trim, Move, SEW, Move, Move]);
+        assert_eq!((out.stitches[1].x, out.stitches[2].x), (5.0, 1005.0));
+    }
+}
@@ -0,0 +1,169 @@
+// tape.rs - Headerless T01/T03/T09 tape formats sharing the DST displacement bits
+
+use crate::dst::parser::{decode_dx, decode_dy, DstError};
+use crate::dst::types::{Pattern, StitchCommand};
+
+/// Punched-tape dialects of the DST record encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TapeFormat {
+    /// Tajima tape: DST records without sequin mode
+    T01,
+    /// Happy tape: the DST sequin bit pattern (0x43) is a trim
+    T03,
+    /// ZSK TC tape: a jump with no displacement is a trim
+    T09,
+}
+
+/// Decode the command of a tape record from its third byte
+fn decode_command(format: TapeFormat, b2: u8, dx: i32, dy: i32) -> StitchCommand {
+    if b2 & 0b11110011 == 0b11110011 {
+        StitchCommand::End
+    } else if b2 & 0b11000011 == 0b11000011 {
+        StitchCommand::ColorChange
+    } else if b2 & 0b10000011 == 0b10000011 {
+        if format == TapeFormat::T09 && dx == 0 && dy == 0 {
+            StitchCommand::Trim
+        } else {
+            StitchCommand::Move
+        }
+    } else if b2 & 0b01000011 == 0b01000011 && format == TapeFormat::T03 {
+        StitchCommand::Trim
+    } else {
+        StitchCommand::Stitch
+    }
+}
+
+/// Parse a headerless tape stream in the given dialect
+///
+/// Tapes have no header, so the metadata counts are derived from the decoded
+/// stream and an End is appended when the tape simply runs out.
+pub fn parse_tape(data: &[u8], format: TapeFormat) -> Result<Pattern, DstError> {
+    if data.len() < 3 {
+        return Err(DstError::InsufficientData {
+            offset: data.len(),
+            needed: 3,
+        });
+    }
+
+    let mut pattern = Pattern::new();
+
+    let mut current_x = 0.0f64;
+    let mut current_y = 0.0f64;
+    let mut ended = false;
+
+    for record in data.chunks_exact(3) {
+        let (b0, b1, b2) = (record[0], record[1], record[2]);
+        let dx = decode_dx(b0, b1, b2);
+        let dy = decode_dy(b0, b1, b2);
+        let command = decode_command(format, b2, dx, dy);
+
+        current_x += dx as f64;
+        current_y += dy as f64;
+        pattern.add_stitch(current_x, current_y, command);
+
+        if command == StitchCommand::End {
+            ended = true;
+            break;
+        }
+    }
+
+    if !ended {
+        pattern.add_stitch(current_x, current_y, StitchCommand::End);
+    }
+
+    pattern.metadata.stitch_count = Some(pattern.stitches.len() as u32);
+    pattern.metadata.color_count = Some(pattern.color_changes);
+
+    // Calculate bounds, statistics and color blocks
+    pattern.calculate_bounds();
+    pattern.calculate_statistics();
+    pattern.calculate_color_blocks();
+
+    Ok(pattern)
+}
+
+/// Parse a Tajima T01 tape file from bytes
+pub fn parse_t01(data: &[u8]) -> Result<Pattern, DstError> {
+    parse_tape(data, TapeFormat::T01)
+}
+
+/// Parse a Happy T03 tape file from bytes
+pub fn parse_t03(data: &[u8]) -> Result<Pattern, DstError> {
+    parse_tape(data, TapeFormat::T03)
+}
+
+/// Parse a ZSK TC T09 tape file from bytes
+pub fn parse_t09(data: &[u8]) -> Result<Pattern, DstError> {
+    parse_tape(data, TapeFormat::T09)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::writer::encode_record;
+
+    fn commands(pattern: &Pattern) -> Vec<StitchCommand> {
+        pattern.stitches.iter().map(|s| s.command).collect()
+    }
+
+    fn tape(records: &[[u8; 3]]) -> Vec<u8> {
+        records.concat()
+    }
+
+    #[test]
+    fn test_t01_headerless_stream() {
+        let data = tape(&[
+            encode_record(10, 5, 0),
+            encode_record(40, 0, 0x80),
+            encode_record(0, 0, 0xC0),
+            encode_record(-3, 2, 0),
+            encode_record(0, 0, 0xF0),
+        ]);
+        let pattern = parse_t01(&data).unwrap();
+
+        use StitchCommand::*;
+        assert_eq!(
+            commands(&pattern),
+            vec![Stitch, Move, ColorChange, Stitch, End]
+        );
+        let last = pattern.stitches.last().unwrap();
+        assert_eq!((last.x, last.y), (47.0, 7.0));
+        assert_eq!(pattern.metadata.color_count, Some(1));
+        assert_eq!(pattern.metadata.stitch_count, Some(5));
+    }
+
+    #[test]
+    fn test_format_specific_trims() {
+        let data = tape(&[
+            encode_record(10, 0, 0),
+            encode_record(0, 0, 0x40),
+            encode_record(0, 0, 0x80),
+            encode_record(10, 0, 0),
+        ]);
+
+        use StitchCommand::*;
+        assert_eq!(
+            commands(&parse_t01(&data).unwrap()),
+            vec![Stitch, Stitch, Move, Stitch, End]
+        );
+        assert_eq!(
+            commands(&parse_t03(&data).unwrap()),
+            vec![Stitch, Trim, Move, Stitch, End]
+        );
+        assert_eq!(
+            commands(&parse_t09(&data).unwrap()),
+            vec![Stitch, Stitch, Trim, Stitch, End]
+        );
+    }
+
+    #[test]
+    fn test_insufficient_data() {
+        assert!(matches!(
+            parse_t01(&[0x00]),
+            Err(DstError::InsufficientData {
+                offset: 1,
+                needed: 3
+            })
+        ));
+    }
+}
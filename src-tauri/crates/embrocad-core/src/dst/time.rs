@@ -0,0 +1,288 @@
+// time.rs - Run time estimates from machine speed, stop penalties and long-stitch slowdown
+
+use super::types::{Pattern, StitchCommand};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Machine speed assumed when none is configured (stitches per minute)
+const DEFAULT_MACHINE_SPEED_SPM: f64 = 800.0;
+
+/// Time lost to each color change when none is configured (seconds)
+const DEFAULT_COLOR_CHANGE_SECONDS: f64 = 15.0;
+
+/// Records between samples of a run time curve when none is given
+pub const DEFAULT_SAMPLE_EVERY: usize = 100;
+
+/// Machine figures behind run time estimates
+///
+/// The defaults are the ones design statistics are computed with: 800
+/// stitches per minute, 15 seconds per color change, free trims and no
+/// long-stitch slowdown.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct TimeEstimator {
+    /// Top speed, in stitches per minute
+    pub speed_spm: f64,
+    /// Seconds each trim stops the machine
+    pub trim_seconds: f64,
+    /// Seconds each color change stops the machine
+    pub color_change_seconds: f64,
+    /// Stitches longer than this many mm sew proportionally slower, as the
+    /// machine caps how fast the frame travels; around 6mm on most machines
+    pub slowdown_above_mm: Option<f64>,
+}
+
+impl Default for TimeEstimator {
+    fn default() -> Self {
+        Self {
+            speed_spm: DEFAULT_MACHINE_SPEED_SPM,
+            trim_seconds: 0.0,
+            color_change_seconds: DEFAULT_COLOR_CHANGE_SECONDS,
+            slowdown_above_mm: None,
+        }
+    }
+}
+
+/// Run time of a design split by what the machine is doing
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct TimeEstimate {
+    pub minutes: f64,
+    pub stitch_minutes: f64,
+    pub trim_minutes: f64,
+    pub color_change_minutes: f64,
+    /// Stitches long enough to sew below top speed
+    pub slowed_stitches: u32,
+}
+
+/// The machine has finished record `index` after `seconds`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct TimePoint {
+    pub index: usize,
+    pub seconds: f64,
+}
+
+/// Run time against progress through the records, for scrubbing a
+/// simulation by time
+///
+/// Points rise in both index and seconds. Every stop is sampled on both
+/// sides, so a color change shows as a step in time at one record, and
+/// stitching between samples runs close to linearly.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct RunTimeline {
+    pub points: Vec<TimePoint>,
+    pub total_seconds: f64,
+}
+
+impl TimeEstimator {
+    /// Minutes for the given counts with every stitch at top speed
+    ///
+    /// Statistics that are updated from counts alone use this.
+    pub fn minutes(&self, stitches: u32, trims: u32, color_changes: u32) -> f64 {
+        (stitches as f64 / self.speed_spm)
+            + (trims as f64 * self.trim_seconds + color_changes as f64 * self.color_change_seconds)
+                / 60.0
+    }
+
+    /// Minutes for one stitch of `length_mm`, and whether it is slowed
+    fn stitch_minutes(&self, length_mm: f64) -> (f64, bool) {
+        let full_speed = 1.0 / self.speed_spm;
+        match self.slowdown_above_mm {
+            Some(limit) if limit > 0.0 && length_mm > limit => {
+                (full_speed * length_mm / limit, true)
+            }
+            _ => (full_speed, false),
+        }
+    }
+
+    /// Seconds the machine spends on each record of `pattern`, in order
+    fn record_seconds<'a>(&'a self, pattern: &'a Pattern) -> impl Iterator<Item = f64> + 'a {
+        pattern
+            .stitches
+            .iter()
+            .enumerate()
+            .map(move |(index, stitch)| match stitch.command {
+                StitchCommand::Stitch => {
+                    let length = pattern.record_length_mm(index).unwrap_or(0.0);
+                    self.stitch_minutes(length).0 * 60.0
+                }
+                StitchCommand::Trim => self.trim_seconds,
+                StitchCommand::ColorChange => self.color_change_seconds,
+                _ => 0.0,
+            })
+    }
+
+    /// Sample the run time of `pattern` at every `sample_every` records
+    /// (at least 1) and on both sides of each trim and color change
+    pub fn timeline(&self, pattern: &Pattern, sample_every: usize) -> RunTimeline {
+        let sample_every = sample_every.max(1);
+        let last = pattern.stitches.len().saturating_sub(1);
+        let mut timeline = RunTimeline::default();
+        let mut elapsed = 0.0;
+        for (index, seconds) in self.record_seconds(pattern).enumerate() {
+            let command = pattern.stitches[index].command;
+            let stop = matches!(command, StitchCommand::Trim | StitchCommand::ColorChange);
+            let sampled = timeline.points.last().map(|p| p.index + 1) == Some(index);
+            if stop && index > 0 && !sampled {
+                timeline.points.push(TimePoint {
+                    index: index - 1,
+                    seconds: elapsed,
+                });
+            }
+            elapsed += seconds;
+            if stop || index % sample_every == 0 || index == last {
+                timeline.points.push(TimePoint {
+                    index,
+                    seconds: elapsed,
+                });
+            }
+        }
+        timeline.total_seconds = elapsed;
+        timeline
+    }
+
+    /// The last record of `pattern` the machine has finished after
+    /// `seconds`, with the time it finished; None before the first
+    pub fn record_at(&self, pattern: &Pattern, seconds: f64) -> Option<TimePoint> {
+        let mut elapsed = 0.0;
+        let mut reached = None;
+        for (index, spent) in self.record_seconds(pattern).enumerate() {
+            elapsed += spent;
+            if elapsed > seconds {
+                break;
+            }
+            reached = Some(TimePoint {
+                index,
+                seconds: elapsed,
+            });
+        }
+        reached
+    }
+
+    /// Walk the records of `pattern`, timing each stitch by its length
+    pub fn estimate(&self, pattern: &Pattern) -> TimeEstimate {
+        let mut estimate = TimeEstimate::default();
+        let (mut trims, mut color_changes) = (0u32, 0u32);
+
+        for (index, stitch) in pattern.stitches.iter().enumerate() {
+            match stitch.command {
+                StitchCommand::Stitch => {
+                    let length = pattern.record_length_mm(index).unwrap_or(0.0);
+                    let (minutes, slowed) = self.stitch_minutes(length);
+                    estimate.stitch_minutes += minutes;
+                    estimate.slowed_stitches += u32::from(slowed);
+                }
+                StitchCommand::Trim => trims += 1,
+                StitchCommand::ColorChange => color_changes += 1,
+                _ => {}
+            }
+        }
+
+        estimate.trim_minutes = trims as f64 * self.trim_seconds / 60.0;
+        estimate.color_change_minutes = color_changes as f64 * self.color_change_seconds / 60.0;
+        estimate.minutes =
+            estimate.stitch_minutes + estimate.trim_minutes + estimate.color_change_minutes;
+        estimate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StitchCommand::*;
+
+    /// Stitches 3mm apart, then 12mm apart, with a trim and a color change
+    fn design() -> Pattern {
+        let mut pattern = Pattern::new();
+        for &(x, command) in &[
+            (0.0, Stitch),
+            (30.0, Stitch),
+            (60.0, Stitch),
+            (60.0, Trim),
+            (60.0, ColorChange),
+            (180.0, Stitch),
+            (300.0, Stitch),
+            (300.0, End),
+        ] {
+            pattern.add_stitch(x, 0.0, command);
+        }
+        pattern.calculate_statistics();
+        pattern
+    }
+
+    #[test]
+    fn test_defaults_match_statistics() {
+        let pattern = design();
+        let estimate = TimeEstimator::default().estimate(&pattern);
+
+        assert_eq!(estimate.slowed_stitches, 0);
+        assert_eq!(estimate.trim_minutes, 0.0);
+        assert!((estimate.minutes - (5.0 / 800.0 + 15.0 / 60.0)).abs() < 1e-12);
+        assert!((estimate.minutes - pattern.statistics.estimated_time_minutes).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_long_stitch_slowdown() {
+        let estimator = TimeEstimator {
+            speed_spm: 600.0,
+            trim_seconds: 6.0,
+            color_change_seconds: 30.0,
+            slowdown_above_mm: Some(6.0),
+        };
+        let estimate = estimator.estimate(&design());
+
+        // The two 12mm stitches take twice as long as the other three
+        assert_eq!(estimate.slowed_stitches, 2);
+        assert!((estimate.stitch_minutes - (3.0 + 2.0 * 2.0) / 600.0).abs() < 1e-12);
+        assert!((estimate.trim_minutes - 0.1).abs() < 1e-12);
+        assert!((estimate.color_change_minutes - 0.5).abs() < 1e-12);
+
+        // At or below the limit nothing slows down
+        let relaxed = TimeEstimator {
+            slowdown_above_mm: Some(12.0),
+            ..estimator
+        };
+        let estimate = relaxed.estimate(&design());
+        assert_eq!(estimate.slowed_stitches, 0);
+        assert!((estimate.stitch_minutes - 5.0 / 600.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_timeline_steps_at_stops() {
+        let pattern = design();
+        let estimator = TimeEstimator::default();
+        let timeline = estimator.timeline(&pattern, 2);
+        let stitch = 60.0 / 800.0;
+
+        let expected_seconds = pattern.statistics.estimated_time_minutes * 60.0;
+        assert!((timeline.total_seconds - expected_seconds).abs() < 1e-9);
+        let last = timeline.points.last().unwrap();
+        assert_eq!(last.index, 7);
+        assert!((last.seconds - expected_seconds).abs() < 1e-9);
+
+        let indices: Vec<usize> = timeline.points.iter().map(|p| p.index).collect();
+        assert_eq!(indices, vec![0, 2, 3, 4, 6, 7]);
+        for pair in timeline.points.windows(2) {
+            assert!(pair[0].index < pair[1].index);
+            assert!(pair[0].seconds <= pair[1].seconds);
+        }
+        // The free trim is flat; the color change is a 15 second step
+        let at = |index: usize| timeline.points.iter().find(|p| p.index == index).unwrap();
+        assert!((at(3).seconds - at(2).seconds).abs() < 1e-12);
+        assert!((at(4).seconds - at(3).seconds - 15.0).abs() < 1e-9);
+        assert!((at(2).seconds - 3.0 * stitch).abs() < 1e-12);
+
+        // Lookups land on the record finished by then
+        assert_eq!(estimator.record_at(&pattern, -1.0), None);
+        assert_eq!(
+            estimator.record_at(&pattern, 2.5 * stitch).unwrap().index,
+            1
+        );
+        assert_eq!(estimator.record_at(&pattern, 10.0).unwrap().index, 3);
+        assert_eq!(estimator.record_at(&pattern, 15.33).unwrap().index, 5);
+        assert_eq!(estimator.record_at(&pattern, 1e6).unwrap().index, 7);
+    }
+}
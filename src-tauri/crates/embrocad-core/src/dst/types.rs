@@ -0,0 +1,617 @@
+// types.rs - Data structures for embroidery patterns, stitches, and metadata
+
+use super::summary::{feed_all, RunningSummary};
+use super::time::TimeEstimator;
+use crate::svg::SvgImportOptions;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Represents the type of command for a stitch operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "SCREAMING_SNAKE_CASE"))]
+#[allow(dead_code)]
+pub enum StitchCommand {
+    /// Regular stitch - needle penetrates fabric
+    Stitch,
+    /// Jump/Move without stitching
+    Move,
+    /// Trim the thread
+    Trim,
+    /// Color change - switch to next thread
+    ColorChange,
+    /// Sequin mode toggle
+    SequinMode,
+    /// Sequin eject
+    SequinEject,
+    /// End of pattern
+    End,
+}
+
+impl StitchCommand {
+    /// The command's serialized name, e.g. `COLOR_CHANGE`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StitchCommand::Stitch => "STITCH",
+            StitchCommand::Move => "MOVE",
+            StitchCommand::Trim => "TRIM",
+            StitchCommand::ColorChange => "COLOR_CHANGE",
+            StitchCommand::SequinMode => "SEQUIN_MODE",
+            StitchCommand::SequinEject => "SEQUIN_EJECT",
+            StitchCommand::End => "END",
+        }
+    }
+
+    /// One-byte code used by the binary stitch encodings, in declaration order
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    /// The command a binary stitch code stands for
+    #[allow(dead_code)]
+    pub fn from_code(code: u8) -> Option<Self> {
+        let command = match code {
+            0 => StitchCommand::Stitch,
+            1 => StitchCommand::Move,
+            2 => StitchCommand::Trim,
+            3 => StitchCommand::ColorChange,
+            4 => StitchCommand::SequinMode,
+            5 => StitchCommand::SequinEject,
+            6 => StitchCommand::End,
+            _ => return None,
+        };
+        Some(command)
+    }
+}
+
+/// Represents a single stitch with coordinates and command type
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Stitch {
+    pub x: f64,
+    pub y: f64,
+    pub command: StitchCommand,
+}
+
+impl Stitch {
+    pub fn new(x: f64, y: f64, command: StitchCommand) -> Self {
+        Self { x, y, command }
+    }
+
+    /// Position rounded to whole 0.1mm machine units, as machine formats store it
+    ///
+    /// Coordinates stay f64 in memory because scaling and rotation leave
+    /// fractions that must not accumulate rounding error between edits.
+    pub fn units(&self) -> (i32, i32) {
+        (self.x.round() as i32, self.y.round() as i32)
+    }
+}
+
+/// A thread color declared by the design file
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ThreadColor {
+    pub rgb: [u8; 3],
+    pub description: Option<String>,
+    pub catalog_number: Option<String>,
+    /// Needle of a multi-needle machine the thread is loaded on, from 1
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub needle: Option<u8>,
+    /// Marks a basting block, sewn to hold the fabric and removed afterwards
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub basting: bool,
+}
+
+impl ThreadColor {
+    pub fn new(rgb: [u8; 3]) -> Self {
+        Self {
+            rgb,
+            description: None,
+            catalog_number: None,
+            needle: None,
+            basting: false,
+        }
+    }
+}
+
+/// Metadata extracted from DST file header
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PatternMetadata {
+    pub label: Option<String>,
+    pub stitch_count: Option<u32>,
+    pub color_count: Option<u32>,
+    /// Declared design extents (+X, -X, +Y, -Y) in 0.1mm, as written by the digitizer
+    pub extent_plus_x: Option<i32>,
+    pub extent_minus_x: Option<i32>,
+    pub extent_plus_y: Option<i32>,
+    pub extent_minus_y: Option<i32>,
+    /// Offset of the last needle position from the start (AX, AY)
+    pub end_offset_x: Option<i32>,
+    pub end_offset_y: Option<i32>,
+    /// Multi-start offsets (MX, MY)
+    pub multi_start_x: Option<i32>,
+    pub multi_start_y: Option<i32>,
+    /// Previous design reference (PD)
+    pub previous_design: Option<String>,
+    /// Thread colors in sewing order, one per color block
+    pub thread_colors: Vec<ThreadColor>,
+    /// Free-form notes and settings strings embedded in the file
+    pub notes: Vec<String>,
+    /// Cumulative (X, Y) scale applied since the file was loaded
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub scale: Option<[f64; 2]>,
+    /// Companion color file the thread colors were read from, when they did
+    /// not come from the design itself
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub companion_file: Option<String>,
+}
+
+/// Bounding box of the pattern
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Bounds {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl Bounds {
+    pub fn new() -> Self {
+        Self {
+            min_x: f64::INFINITY,
+            min_y: f64::INFINITY,
+            max_x: f64::NEG_INFINITY,
+            max_y: f64::NEG_INFINITY,
+        }
+    }
+
+    pub fn update(&mut self, x: f64, y: f64) {
+        if x < self.min_x {
+            self.min_x = x;
+        }
+        if x > self.max_x {
+            self.max_x = x;
+        }
+        if y < self.min_y {
+            self.min_y = y;
+        }
+        if y > self.max_y {
+            self.max_y = y;
+        }
+    }
+
+    pub fn width(&self) -> f64 {
+        self.max_x - self.min_x
+    }
+
+    pub fn height(&self) -> f64 {
+        self.max_y - self.min_y
+    }
+}
+
+impl Default for Bounds {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// DST coordinates are stored in 0.1mm units
+pub const UNITS_PER_MM: f64 = 10.0;
+
+/// Top thread used per unit of stitch path, allowing for fabric thickness
+pub const DEFAULT_TOP_THREAD_MULTIPLIER: f64 = 1.25;
+
+/// Bobbin thread used per unit of stitch path
+pub const DEFAULT_BOBBIN_THREAD_MULTIPLIER: f64 = 0.33;
+
+/// Calculated statistics for the pattern
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PatternStatistics {
+    pub real_stitch_count: u32,
+    pub jump_count: u32,
+    pub trim_count: u32,
+    pub color_change_count: u32,
+    /// Sequin eject records, one sequin each
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sequin_count: u32,
+    /// Sum of all stitch lengths, excluding jumps
+    pub total_thread_length_mm: f64,
+    /// Estimated top thread consumption using the default multiplier
+    pub top_thread_m: f64,
+    /// Estimated bobbin thread consumption using the default multiplier
+    pub bobbin_thread_m: f64,
+    pub min_stitch_length_mm: f64,
+    pub max_stitch_length_mm: f64,
+    pub avg_stitch_length_mm: f64,
+    pub estimated_time_minutes: f64,
+}
+
+/// Quoting figures for a single color block
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BlockStatistics {
+    pub stitch_count: u32,
+    pub jump_count: u32,
+    pub trim_count: u32,
+    /// Sum of the lengths of Stitch records, excluding jumps
+    pub thread_length_mm: f64,
+    /// Stitching time plus the color change that opens the block, if any
+    pub estimated_minutes: f64,
+    pub bounds: Option<Bounds>,
+}
+
+/// A run of consecutive stitches sewn with one thread color
+///
+/// Each block after the first begins at its ColorChange record. `start` and
+/// `end` index into `Pattern::stitches` as a half-open range.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ColorBlock {
+    pub index: usize,
+    pub start: usize,
+    pub end: usize,
+    pub stitch_count: u32,
+    pub bounds: Option<Bounds>,
+    pub color: Option<ThreadColor>,
+    pub statistics: BlockStatistics,
+}
+
+/// A recoverable irregularity found while parsing; the pattern is still usable
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum ParseWarning {
+    #[error("Header declares {header} stitches but {decoded} were decoded")]
+    StitchCountMismatch { header: u32, decoded: u32 },
+    #[error("Header declares {header} color changes but {decoded} were decoded")]
+    ColorCountMismatch { header: u32, decoded: u32 },
+    #[error("Stitch data ends with a partial {length}-byte record at offset {offset}")]
+    TruncatedRecord { offset: usize, length: usize },
+    #[error("Stitch data ends at offset {offset} without an End command; one was added")]
+    MissingEnd { offset: usize },
+    #[error("{length} bytes of stitch data follow the End command at offset {offset}")]
+    DataAfterEnd { offset: usize, length: usize },
+    #[error("Unknown control code 0x{code:02X} at offset {offset}")]
+    UnknownControl { offset: usize, code: u8 },
+    #[error("Stopped decoding after {limit} stitches")]
+    StitchLimitReached { limit: usize },
+    #[error("Stopped decoding at offset {offset}: coordinates left the physical range")]
+    CoordinateOutOfRange { offset: usize },
+    #[error("Unknown command \"{command}\" on line {line}")]
+    UnknownCommand { line: usize, command: String },
+    #[error("Header record {key} at offset {offset} could not be read")]
+    InvalidHeaderRecord { offset: usize, key: String },
+}
+
+impl ParseWarning {
+    /// Byte offset in the file the irregularity was found at, when it has one
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            Self::TruncatedRecord { offset, .. }
+            | Self::MissingEnd { offset }
+            | Self::DataAfterEnd { offset, .. }
+            | Self::UnknownControl { offset, .. }
+            | Self::CoordinateOutOfRange { offset }
+            | Self::InvalidHeaderRecord { offset, .. } => Some(*offset),
+            _ => None,
+        }
+    }
+}
+
+/// Controls how tolerant parsing is of irregular files
+///
+/// The default is lenient: irregularities become warnings and the design still
+/// opens. Strict mode turns the first warning into an error, for validating
+/// files before they are sent to customers.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ParseOptions {
+    pub strict: bool,
+    /// Stop decoding once this many stitch records have been read
+    pub max_stitches: Option<usize>,
+    /// Stop decoding once the records exceed the header's stitch count by this factor
+    pub header_stitch_factor: Option<usize>,
+    /// Runs of at least this many consecutive jumps are read as a trim; None disables
+    pub trim_jump_threshold: Option<usize>,
+    /// Move the design so its bounds midpoint sits at the hoop origin
+    pub center_on_load: bool,
+    /// Read thread colors from an EDR, INF, COL or RGB file beside a design
+    /// that declares none; needs the `fs` feature
+    pub companion_colors: bool,
+    /// How SVG artwork is converted to stitches
+    pub svg: SvgImportOptions,
+}
+
+/// No real design comes close to this many stitches
+pub const DEFAULT_MAX_STITCHES: usize = 2_000_000;
+
+/// Headers can undercount, but not by this much
+pub const DEFAULT_HEADER_STITCH_FACTOR: usize = 4;
+
+/// Machines cut the thread after three consecutive jumps
+pub const DEFAULT_TRIM_JUMP_THRESHOLD: usize = 3;
+
+/// Coordinates beyond 5 meters from the origin cannot come from a real hoop
+pub const MAX_COORDINATE: f64 = 5000.0 * UNITS_PER_MM;
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            strict: false,
+            max_stitches: Some(DEFAULT_MAX_STITCHES),
+            header_stitch_factor: Some(DEFAULT_HEADER_STITCH_FACTOR),
+            trim_jump_threshold: Some(DEFAULT_TRIM_JUMP_THRESHOLD),
+            center_on_load: false,
+            companion_colors: true,
+            svg: SvgImportOptions::default(),
+        }
+    }
+}
+
+/// The complete embroidery pattern
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Pattern {
+    pub stitches: Vec<Stitch>,
+    pub metadata: PatternMetadata,
+    pub bounds: Option<Bounds>,
+    pub statistics: PatternStatistics,
+    pub color_changes: u32,
+    pub color_blocks: Vec<ColorBlock>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub warnings: Vec<ParseWarning>,
+}
+
+impl Pattern {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a stitch to the pattern
+    pub fn add_stitch(&mut self, x: f64, y: f64, command: StitchCommand) {
+        self.stitches.push(Stitch::new(x, y, command));
+
+        // Track color changes
+        if command == StitchCommand::ColorChange {
+            self.color_changes += 1;
+        }
+    }
+
+    /// Thread color assigned to a color block, if the file declared one
+    pub fn thread_color_for_block(&self, block_index: usize) -> Option<&ThreadColor> {
+        self.metadata.thread_colors.get(block_index)
+    }
+
+    /// Calculate the bounds of the pattern
+    pub fn calculate_bounds(&mut self) {
+        if !self.stitches.is_empty() {
+            self.bounds = Some(feed_all(&self.stitches, Bounds::new()));
+        }
+    }
+
+    /// Bounds, statistics and color blocks of the stitch list in one pass
+    fn summarize(&self) -> RunningSummary {
+        feed_all(&self.stitches, RunningSummary::new())
+    }
+
+    /// Group consecutive stitches between ColorChange commands into blocks
+    ///
+    /// A ColorChange as the very first record does not open an empty block,
+    /// and a trailing block with no real stitches (e.g. a ColorChange right
+    /// before End) is folded into the block before it.
+    pub fn color_blocks(&self) -> Vec<ColorBlock> {
+        self.summarize().color_blocks(&self.metadata.thread_colors)
+    }
+
+    /// Length in mm of the record at `index`, measured from the record before it
+    pub fn record_length_mm(&self, index: usize) -> Option<f64> {
+        let prev = self.stitches.get(index.checked_sub(1)?)?;
+        let stitch = self.stitches.get(index)?;
+        Some((stitch.x - prev.x).hypot(stitch.y - prev.y) / UNITS_PER_MM)
+    }
+
+    /// Statistics for the half-open stitch range of one block
+    ///
+    /// Lengths are measured from the previous record even across the block
+    /// boundary, so block lengths add up to the pattern's total.
+    pub fn block_statistics(&self, start: usize, end: usize) -> BlockStatistics {
+        let mut stats = BlockStatistics::default();
+        let mut bounds = Bounds::new();
+        let mut color_changes = 0u32;
+
+        for index in start..end {
+            let stitch = &self.stitches[index];
+            bounds.update(stitch.x, stitch.y);
+
+            match stitch.command {
+                StitchCommand::Stitch => {
+                    stats.stitch_count += 1;
+                    stats.thread_length_mm += self.record_length_mm(index).unwrap_or(0.0);
+                }
+                StitchCommand::Move => stats.jump_count += 1,
+                StitchCommand::Trim => stats.trim_count += 1,
+                StitchCommand::ColorChange => color_changes += 1,
+                _ => {}
+            }
+        }
+
+        stats.estimated_minutes =
+            TimeEstimator::default().minutes(stats.stitch_count, stats.trim_count, color_changes);
+        stats.bounds = (start < end).then_some(bounds);
+        stats
+    }
+
+    /// Recompute the serialized color block list
+    pub fn calculate_color_blocks(&mut self) {
+        self.color_blocks = self.color_blocks();
+    }
+
+    /// Calculate stitch counts, thread length and estimated run time
+    pub fn calculate_statistics(&mut self) {
+        self.statistics = self.summarize().statistics();
+    }
+}
+
+impl PatternStatistics {
+    /// Refresh the average, thread and time figures from the counts and
+    /// total length; `measured` is the number of Stitch records with a
+    /// predecessor to measure from
+    pub fn update_totals(&mut self, measured: u32) {
+        self.avg_stitch_length_mm = if measured > 0 {
+            self.total_thread_length_mm / measured as f64
+        } else {
+            0.0
+        };
+
+        let path_m = self.total_thread_length_mm / 1000.0;
+        self.top_thread_m = path_m * DEFAULT_TOP_THREAD_MULTIPLIER;
+        self.bobbin_thread_m = path_m * DEFAULT_BOBBIN_THREAD_MULTIPLIER;
+
+        // Counts only, so edits can refresh it without walking the records
+        self.estimated_time_minutes = TimeEstimator::default().minutes(
+            self.real_stitch_count,
+            self.trim_count,
+            self.color_change_count,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern_from(records: &[(f64, f64, StitchCommand)]) -> Pattern {
+        let mut pattern = Pattern::new();
+        for &(x, y, command) in records {
+            pattern.add_stitch(x, y, command);
+        }
+        pattern
+    }
+
+    #[test]
+    fn test_color_blocks_three_colors() {
+        use StitchCommand::*;
+        let mut pattern = pattern_from(&[
+            (0.0, 0.0, Stitch),
+            (10.0, 5.0, Stitch),
+            (10.0, 5.0, ColorChange),
+            (20.0, 0.0, Move),
+            (30.0, 10.0, Stitch),
+            (30.0, 10.0, ColorChange),
+            (-5.0, 2.0, Stitch),
+            (-6.0, 3.0, Stitch),
+            (-7.0, 4.0, Stitch),
+            (-7.0, 4.0, End),
+        ]);
+        pattern.metadata.thread_colors =
+            vec![ThreadColor::new([255, 0, 0]), ThreadColor::new([0, 0, 255])];
+        pattern.calculate_color_blocks();
+
+        let blocks = &pattern.color_blocks;
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(
+            (blocks[0].start, blocks[0].end, blocks[0].stitch_count),
+            (0, 2, 2)
+        );
+        assert_eq!(
+            (blocks[1].start, blocks[1].end, blocks[1].stitch_count),
+            (2, 5, 1)
+        );
+        assert_eq!(
+            (blocks[2].start, blocks[2].end, blocks[2].stitch_count),
+            (5, 10, 3)
+        );
+        assert_eq!(
+            blocks.iter().map(|b| b.index).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+
+        let bounds = blocks[1].bounds.as_ref().unwrap();
+        assert_eq!((bounds.min_x, bounds.max_x), (10.0, 30.0));
+        assert_eq!((bounds.min_y, bounds.max_y), (0.0, 10.0));
+
+        assert_eq!(blocks[0].color.as_ref().unwrap().rgb, [255, 0, 0]);
+        assert_eq!(blocks[1].color.as_ref().unwrap().rgb, [0, 0, 255]);
+        assert!(blocks[2].color.is_none());
+    }
+
+    #[test]
+    fn test_color_blocks_monochrome() {
+        use StitchCommand::*;
+        let pattern = pattern_from(&[(0.0, 0.0, Stitch), (1.0, 1.0, Stitch), (1.0, 1.0, End)]);
+
+        let blocks = pattern.color_blocks();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(
+            (blocks[0].start, blocks[0].end, blocks[0].stitch_count),
+            (0, 3, 2)
+        );
+    }
+
+    #[test]
+    fn test_color_blocks_leading_and_trailing_color_change() {
+        use StitchCommand::*;
+        let pattern = pattern_from(&[
+            (0.0, 0.0, ColorChange),
+            (1.0, 0.0, Stitch),
+            (2.0, 0.0, Stitch),
+            (2.0, 0.0, ColorChange),
+            (2.0, 0.0, End),
+        ]);
+
+        let blocks = pattern.color_blocks();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(
+            (blocks[0].start, blocks[0].end, blocks[0].stitch_count),
+            (0, 5, 2)
+        );
+    }
+
+    #[test]
+    fn test_block_statistics_known_geometry() {
+        use StitchCommand::*;
+        let mut pattern = pattern_from(&[
+            (0.0, 0.0, Stitch),
+            (30.0, 40.0, Stitch),  // 5.0mm
+            (30.0, 100.0, Stitch), // 6.0mm
+            (30.0, 100.0, ColorChange),
+            (100.0, 100.0, Move),   // jump, not thread
+            (100.0, 130.0, Stitch), // 3.0mm
+            (140.0, 160.0, Stitch), // 5.0mm
+            (140.0, 160.0, End),
+        ]);
+        pattern.calculate_statistics();
+        pattern.calculate_color_blocks();
+        let blocks = &pattern.color_blocks;
+
+        assert_eq!(blocks.len(), 2);
+        let first = &blocks[0].statistics;
+        assert_eq!((first.stitch_count, first.jump_count), (3, 0));
+        assert_eq!(first.thread_length_mm, 11.0);
+        assert_eq!(first.estimated_minutes, 3.0 / 800.0);
+        assert_eq!(first.bounds, blocks[0].bounds);
+
+        let second = &blocks[1].statistics;
+        assert_eq!((second.stitch_count, second.jump_count), (2, 1));
+        assert_eq!(second.thread_length_mm, 8.0);
+        assert_eq!(second.estimated_minutes, 2.0 / 800.0 + 15.0 / 60.0);
+        let bounds = second.bounds.as_ref().unwrap();
+        assert_eq!((bounds.min_x, bounds.max_x), (30.0, 140.0));
+        assert_eq!((bounds.min_y, bounds.max_y), (100.0, 160.0));
+
+        // Block figures add up to the pattern totals
+        assert_eq!(
+            first.thread_length_mm + second.thread_length_mm,
+            pattern.statistics.total_thread_length_mm
+        );
+    }
+
+    #[test]
+    fn test_color_blocks_empty_pattern() {
+        assert!(Pattern::new().color_blocks().is_empty());
+    }
+}
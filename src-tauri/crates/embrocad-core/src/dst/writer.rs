@@ -0,0 +1,419 @@
+// writer.rs - DST file writer with header generation and stitch encoding
+
+use crate::dst::types::{Pattern, StitchCommand, UNITS_PER_MM};
+
+/// DST header size in bytes
+const HEADER_SIZE: usize = 512;
+
+/// Largest displacement a single record can encode on either axis
+pub const MAX_DISPLACEMENT: i32 = 121;
+
+/// Control bits OR-ed into byte 2 of a record
+const CONTROL_STITCH: u8 = 0x00;
+const CONTROL_JUMP: u8 = 0x80;
+const CONTROL_COLOR_CHANGE: u8 = 0xC0;
+const CONTROL_SEQUIN_MODE: u8 = 0x40;
+const CONTROL_END: u8 = 0xF0;
+
+/// DST has no trim opcode; machines cut on a run of three jumps that go nowhere
+const TRIM_JUMPS: [(i32, i32); 3] = [(2, 2), (-4, -4), (2, 2)];
+
+/// Encode a displacement into a DST record with the given control bits in byte 2
+///
+/// Both components must be within ±121. Y is inverted to match the parser.
+pub fn encode_record(dx: i32, dy: i32, control: u8) -> [u8; 3] {
+    let mut b = [0u8, 0u8, 0x03 | control];
+    let (mut x, mut y) = (dx, -dy);
+
+    // (weight, byte, positive bit, negative bit) from largest to smallest
+    let x_digits = [
+        (81, 2, 2, 3),
+        (27, 1, 2, 3),
+        (9, 0, 2, 3),
+        (3, 1, 0, 1),
+        (1, 0, 0, 1),
+    ];
+    let y_digits = [
+        (81, 2, 5, 4),
+        (27, 1, 5, 4),
+        (9, 0, 5, 4),
+        (3, 1, 7, 6),
+        (1, 0, 7, 6),
+    ];
+
+    for (weight, byte, pos, neg) in x_digits {
+        if x > weight / 2 {
+            b[byte] |= 1 << pos;
+            x -= weight;
+        } else if x < -(weight / 2) {
+            b[byte] |= 1 << neg;
+            x += weight;
+        }
+    }
+    for (weight, byte, pos, neg) in y_digits {
+        if y > weight / 2 {
+            b[byte] |= 1 << pos;
+            y -= weight;
+        } else if y < -(weight / 2) {
+            b[byte] |= 1 << neg;
+            y += weight;
+        }
+    }
+    b
+}
+
+/// Control bits for a stitch command; trims travel as jumps after `TRIM_JUMPS`
+/// and ejects are jumps made in sequin mode
+fn control_bits(command: StitchCommand) -> u8 {
+    match command {
+        StitchCommand::Stitch => CONTROL_STITCH,
+        StitchCommand::Move | StitchCommand::Trim | StitchCommand::SequinEject => CONTROL_JUMP,
+        StitchCommand::ColorChange => CONTROL_COLOR_CHANGE,
+        StitchCommand::SequinMode => CONTROL_SEQUIN_MODE,
+        StitchCommand::End => CONTROL_END,
+    }
+}
+
+/// Encode all stitches as 3-byte records, splitting long displacements into jumps
+///
+/// In sequin mode every jump record sews as an eject, so jumps that are not
+/// ejects leave sequin mode around themselves, and ejects outside a sequin
+/// section get a section of their own. Sequin runs read from a DST file need
+/// neither and are written back record for record.
+fn encode_stitches(pattern: &Pattern) -> Vec<[u8; 3]> {
+    let mut records = Vec::with_capacity(pattern.stitches.len() + 1);
+    let (mut current_x, mut current_y) = (0i64, 0i64);
+    let mut sequin_mode = false;
+    let toggle = encode_record(0, 0, CONTROL_SEQUIN_MODE);
+
+    for stitch in &pattern.stitches {
+        // Track rounded absolute positions so fractional coordinates never drift
+        let target_x = stitch.x.round() as i64;
+        let target_y = stitch.y.round() as i64;
+        let limit = MAX_DISPLACEMENT as i64;
+
+        let mut jumps = Vec::new();
+        if stitch.command == StitchCommand::Trim {
+            jumps.extend(TRIM_JUMPS);
+        }
+        loop {
+            let dx = target_x - current_x;
+            let dy = target_y - current_y;
+            if dx.abs() <= limit && dy.abs() <= limit {
+                break;
+            }
+            let step_x = dx.clamp(-limit, limit);
+            let step_y = dy.clamp(-limit, limit);
+            jumps.push((step_x as i32, step_y as i32));
+            current_x += step_x;
+            current_y += step_y;
+        }
+
+        let last = ((target_x - current_x) as i32, (target_y - current_y) as i32);
+        let mut record = Some(last);
+        match stitch.command {
+            // A trim that stays put is only its jumps
+            StitchCommand::Trim if last == (0, 0) => record = None,
+            StitchCommand::Move | StitchCommand::Trim => jumps.extend(record.take()),
+            _ => {}
+        }
+
+        let leave_sequins = sequin_mode && !jumps.is_empty();
+        if leave_sequins {
+            records.push(toggle);
+        }
+        records.extend(
+            jumps
+                .iter()
+                .map(|&(dx, dy)| encode_record(dx, dy, CONTROL_JUMP)),
+        );
+        if leave_sequins {
+            records.push(toggle);
+        }
+
+        if let Some((dx, dy)) = record {
+            let lone_eject = stitch.command == StitchCommand::SequinEject && !sequin_mode;
+            if lone_eject {
+                records.push(toggle);
+            }
+            records.push(encode_record(dx, dy, control_bits(stitch.command)));
+            if lone_eject {
+                records.push(toggle);
+            }
+        }
+        if stitch.command == StitchCommand::SequinMode {
+            sequin_mode = !sequin_mode;
+        }
+
+        current_x = target_x;
+        current_y = target_y;
+
+        if stitch.command == StitchCommand::End {
+            return records;
+        }
+    }
+
+    records.push(encode_record(0, 0, CONTROL_END));
+    records
+}
+
+/// Format a signed header value as a sign followed by a right-aligned number
+fn signed_field(value: i64) -> String {
+    let sign = if value < 0 { '-' } else { '+' };
+    format!("{}{:>5}", sign, value.abs())
+}
+
+/// Build the 512-byte Tajima header
+fn write_header(pattern: &Pattern, record_count: usize) -> Vec<u8> {
+    let label: String = pattern
+        .metadata
+        .label
+        .as_deref()
+        .unwrap_or("")
+        .chars()
+        .take(16)
+        .collect();
+
+    // Header extents and offsets use the file's Y-up orientation
+    let (plus_x, minus_x, plus_y, minus_y) = match &pattern.bounds {
+        Some(b) => (b.max_x, -b.min_x, -b.min_y, b.max_y),
+        None => (0.0, 0.0, 0.0, 0.0),
+    };
+    let (end_x, end_y) = pattern
+        .stitches
+        .last()
+        .map(|s| (s.x.round() as i64, -(s.y.round() as i64)))
+        .unwrap_or((0, 0));
+
+    let mut fields = vec![
+        format!("LA:{:<16}", label),
+        format!("ST:{:>7}", record_count),
+        format!("CO:{:>3}", pattern.statistics.color_change_count),
+        format!("+X:{:>5}", plus_x.max(0.0).round() as i64),
+        format!("-X:{:>5}", minus_x.max(0.0).round() as i64),
+        format!("+Y:{:>5}", plus_y.max(0.0).round() as i64),
+        format!("-Y:{:>5}", minus_y.max(0.0).round() as i64),
+        format!("AX:{}", signed_field(end_x)),
+        format!("AY:{}", signed_field(end_y)),
+        format!("MX:{}", signed_field(0)),
+        format!("MY:{}", signed_field(0)),
+        format!(
+            "PD:{}",
+            pattern
+                .metadata
+                .previous_design
+                .as_deref()
+                .unwrap_or("******")
+        ),
+    ];
+
+    for color in &pattern.metadata.thread_colors {
+        let [r, g, b] = color.rgb;
+        fields.push(format!(
+            "TC:#{:02x}{:02x}{:02x},{},{}",
+            r,
+            g,
+            b,
+            color.description.as_deref().unwrap_or(""),
+            color.catalog_number.as_deref().unwrap_or("")
+        ));
+    }
+
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    for field in fields {
+        // Leave room for the terminator; drop records that would overflow
+        if header.len() + field.len() + 2 > HEADER_SIZE {
+            break;
+        }
+        header.extend_from_slice(field.as_bytes());
+        header.push(b'\r');
+    }
+    header.push(0x1A);
+    header.resize(HEADER_SIZE, b' ');
+    header
+}
+
+/// Write a pattern as a Tajima DST file
+///
+/// Long stitches are split into shorter stitches first, so they still sew
+/// instead of turning into jumps.
+pub fn write_dst(pattern: &Pattern) -> Vec<u8> {
+    let mut pattern = pattern.clone();
+    pattern.split_long_stitches(MAX_DISPLACEMENT as f64 / UNITS_PER_MM);
+    let pattern = &pattern;
+    let records = encode_stitches(pattern);
+
+    let mut data = write_header(pattern, records.len());
+    data.reserve(records.len() * 3);
+    for record in &records {
+        data.extend_from_slice(record);
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::{parse_dst, ParseOptions};
+
+    fn sample_pattern() -> Pattern {
+        let mut pattern = Pattern::new();
+        pattern.metadata.label = Some("ROUNDTRIP".to_string());
+        pattern.add_stitch(10.0, 0.0, StitchCommand::Stitch);
+        pattern.add_stitch(10.0, 25.0, StitchCommand::Stitch);
+        pattern.add_stitch(-50.0, 40.0, StitchCommand::Move);
+        pattern.add_stitch(-50.0, 40.0, StitchCommand::ColorChange);
+        pattern.add_stitch(-30.0, -20.0, StitchCommand::Stitch);
+        pattern.add_stitch(-29.0, -21.0, StitchCommand::Stitch);
+        pattern.add_stitch(-29.0, -21.0, StitchCommand::End);
+        pattern.calculate_bounds();
+        pattern.calculate_statistics();
+        pattern
+    }
+
+    #[test]
+    fn test_round_trip_preserves_stitches() {
+        let original = sample_pattern();
+        let first = parse_dst(&write_dst(&original), &ParseOptions::default()).unwrap();
+        let second = parse_dst(&write_dst(&first), &ParseOptions::default()).unwrap();
+
+        assert_eq!(first.stitches, original.stitches);
+        assert_eq!(second.stitches, first.stitches);
+        assert_eq!(second.bounds, first.bounds);
+        assert_eq!(second.metadata.label.as_deref(), Some("ROUNDTRIP"));
+    }
+
+    #[test]
+    fn test_header_fields() {
+        let original = sample_pattern();
+        let data = write_dst(&original);
+        let parsed = parse_dst(&data, &ParseOptions::default()).unwrap();
+
+        assert_eq!(data.len(), HEADER_SIZE + 7 * 3);
+        assert_eq!(parsed.metadata.stitch_count, Some(7));
+        assert_eq!(parsed.metadata.color_count, Some(1));
+        assert_eq!(parsed.metadata.extent_plus_x, Some(10));
+        assert_eq!(parsed.metadata.extent_minus_x, Some(50));
+        assert_eq!(parsed.metadata.extent_plus_y, Some(21));
+        assert_eq!(parsed.metadata.extent_minus_y, Some(40));
+        assert_eq!(parsed.metadata.end_offset_x, Some(-29));
+        assert_eq!(parsed.metadata.end_offset_y, Some(21));
+    }
+
+    #[test]
+    fn test_long_displacement_split_by_command() {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(200.0, 0.0, StitchCommand::Move);
+        pattern.add_stitch(300.0, -130.0, StitchCommand::Stitch);
+        pattern.add_stitch(300.0, -130.0, StitchCommand::End);
+
+        let parsed = parse_dst(&write_dst(&pattern), &ParseOptions::default()).unwrap();
+        let commands: Vec<_> = parsed.stitches.iter().map(|s| s.command).collect();
+
+        // Jumps split into jumps, stitches into stitches
+        assert_eq!(
+            commands,
+            vec![
+                StitchCommand::Move,
+                StitchCommand::Move,
+                StitchCommand::Stitch,
+                StitchCommand::Stitch,
+                StitchCommand::End
+            ]
+        );
+        let middle = &parsed.stitches[2];
+        assert_eq!((middle.x, middle.y), (250.0, -65.0));
+        let last = &parsed.stitches[3];
+        assert_eq!((last.x, last.y), (300.0, -130.0));
+    }
+
+    #[test]
+    fn test_trim_uses_three_jump_convention() {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(10.0, 0.0, StitchCommand::Stitch);
+        pattern.add_stitch(10.0, 0.0, StitchCommand::Trim);
+        pattern.add_stitch(60.0, 20.0, StitchCommand::Move);
+        pattern.add_stitch(70.0, 20.0, StitchCommand::Stitch);
+        pattern.add_stitch(70.0, 20.0, StitchCommand::Trim);
+        pattern.add_stitch(70.0, 20.0, StitchCommand::End);
+
+        let data = write_dst(&pattern);
+        assert_eq!(data.len(), HEADER_SIZE + 10 * 3);
+        assert_eq!(
+            &data[HEADER_SIZE + 3..HEADER_SIZE + 6],
+            &encode_record(2, 2, CONTROL_JUMP)
+        );
+
+        let parsed = parse_dst(&data, &ParseOptions::default()).unwrap();
+        assert_eq!(parsed.stitches, pattern.stitches);
+    }
+
+    #[test]
+    fn test_sequin_fixture_round_trips() {
+        let fixture = include_bytes!("../../tests/fixtures/dst/sequins.dst");
+        let original = parse_dst(fixture, &ParseOptions::default()).unwrap();
+        let commands: Vec<_> = original.stitches.iter().map(|s| s.command).collect();
+        let count = |command| commands.iter().filter(|&&c| c == command).count();
+        assert_eq!(count(StitchCommand::SequinMode), 4);
+        assert_eq!(count(StitchCommand::SequinEject), 9);
+        assert_eq!(count(StitchCommand::Move), 1);
+
+        let data = write_dst(&original);
+        assert_eq!(&data[HEADER_SIZE..], &fixture[HEADER_SIZE..]);
+        let parsed = parse_dst(&data, &ParseOptions::default()).unwrap();
+        assert_eq!(parsed.stitches, original.stitches);
+    }
+
+    #[test]
+    fn test_jumps_leave_sequin_mode() {
+        use StitchCommand::*;
+        let mut pattern = Pattern::new();
+        for &(x, command) in &[
+            (0.0, SequinMode),
+            (30.0, SequinEject),
+            (80.0, Move),
+            (110.0, SequinEject),
+            (110.0, SequinMode),
+            (140.0, SequinEject),
+            (140.0, End),
+        ] {
+            pattern.add_stitch(x, 0.0, command);
+        }
+
+        let parsed = parse_dst(&write_dst(&pattern), &ParseOptions::default()).unwrap();
+        let commands: Vec<_> = parsed.stitches.iter().map(|s| s.command).collect();
+        // The move is wrapped in toggles and the last eject gets a section of its own
+        assert_eq!(
+            commands,
+            vec![
+                SequinMode,
+                SequinEject,
+                SequinMode,
+                Move,
+                SequinMode,
+                SequinEject,
+                SequinMode,
+                SequinMode,
+                SequinEject,
+                SequinMode,
+                End
+            ]
+        );
+        let ejects: Vec<_> = parsed
+            .stitches
+            .iter()
+            .filter(|s| s.command == SequinEject)
+            .map(|s| s.x)
+            .collect();
+        assert_eq!(ejects, vec![30.0, 110.0, 140.0]);
+    }
+
+    #[test]
+    fn test_missing_end_is_appended() {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(5.0, 5.0, StitchCommand::Stitch);
+
+        let parsed = parse_dst(&write_dst(&pattern), &ParseOptions::default()).unwrap();
+        assert_eq!(parsed.stitches.last().unwrap().command, StitchCommand::End);
+    }
+}
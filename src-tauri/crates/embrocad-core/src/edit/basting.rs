@@ -0,0 +1,241 @@
+// basting.rs - Basting box generated around a design as its own first color block
+
+use crate::dst::{Pattern, Stitch, StitchCommand, ThreadColor, MAX_COORDINATE, UNITS_PER_MM};
+use crate::edit::{EditError, StitchEdit, StitchPatch};
+use crate::export::block_rgb;
+use crate::generate::run_between;
+
+/// Thread of a generated basting block: white, which shows on most fabric
+/// and leaves no dye behind
+fn basting_thread() -> ThreadColor {
+    ThreadColor {
+        description: Some("Basting".to_string()),
+        basting: true,
+        ..ThreadColor::new([255, 255, 255])
+    }
+}
+
+impl Pattern {
+    /// Sew a rectangle of long stitches `margin_mm` outside the design
+    /// before anything else, optionally with a cross through its center to
+    /// line the hoop up by
+    ///
+    /// The basting gets a color block of its own, separated from the design
+    /// by a trim, and its thread is marked `basting` so it can be found and
+    /// deleted later.
+    pub fn add_basting(
+        &mut self,
+        margin_mm: f64,
+        stitch_len_mm: f64,
+        center_cross: bool,
+    ) -> Result<StitchEdit, EditError> {
+        if !(stitch_len_mm > 0.0 && margin_mm >= 0.0) {
+            return Err(EditError::InvalidBasting);
+        }
+        let Some(bounds) = self.bounds.clone() else {
+            return Err(EditError::EmptyDesign);
+        };
+        let margin = margin_mm * UNITS_PER_MM;
+        let step = stitch_len_mm * UNITS_PER_MM;
+        let (left, top) = (bounds.min_x - margin, bounds.min_y - margin);
+        let (right, bottom) = (bounds.max_x + margin, bounds.max_y + margin);
+        if let Some(&(x, y)) = [(left, top), (right, bottom)]
+            .iter()
+            .find(|(x, y)| x.abs() > MAX_COORDINATE || y.abs() > MAX_COORDINATE)
+        {
+            return Err(EditError::OutOfRange { x, y });
+        }
+
+        let corners = [(left, top), (right, top), (right, bottom), (left, bottom)];
+        let mut inserted = vec![
+            Stitch::new(left, top, StitchCommand::Move),
+            Stitch::new(left, top, StitchCommand::Stitch),
+        ];
+        for (i, &from) in corners.iter().enumerate() {
+            inserted.extend(run_between(from, corners[(i + 1) % 4], step));
+        }
+        if center_cross {
+            let (center_x, center_y) = ((left + right) / 2.0, (top + bottom) / 2.0);
+            for (from, to) in [
+                ((left, center_y), (right, center_y)),
+                ((center_x, top), (center_x, bottom)),
+            ] {
+                inserted.push(Stitch::new(from.0, from.1, StitchCommand::Move));
+                inserted.extend(run_between(from, to, step));
+            }
+        }
+
+        let last = inserted.last().map(|s| (s.x, s.y)).unwrap_or_default();
+        inserted.push(Stitch::new(last.0, last.1, StitchCommand::Trim));
+        let first = &self.stitches[0];
+        // A design opening with a color change already separates the blocks
+        if first.command != StitchCommand::ColorChange {
+            inserted.push(Stitch::new(last.0, last.1, StitchCommand::ColorChange));
+            if first.command != StitchCommand::Move {
+                inserted.push(Stitch::new(first.x, first.y, StitchCommand::Move));
+            }
+        }
+
+        // Declare the colors the design shows now, so they stay with their blocks
+        let mut colors: Vec<ThreadColor> = (0..self.color_blocks.len())
+            .map(|block| {
+                self.thread_color_for_block(block)
+                    .cloned()
+                    .unwrap_or_else(|| ThreadColor::new(block_rgb(self, block)))
+            })
+            .collect();
+        colors.insert(0, basting_thread());
+        self.metadata.thread_colors = colors;
+
+        let undo = self.apply_patches(vec![StitchPatch {
+            start: 0,
+            replaced: 0,
+            stitches: inserted,
+        }]);
+        Ok(StitchEdit {
+            warnings: Vec::new(),
+            undo,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::Bounds;
+
+    /// A red block and one without a declared color, 20mm by 10mm from (10mm, 5mm)
+    fn design() -> Pattern {
+        let mut pattern = Pattern::new();
+        for &(x, y, command) in &[
+            (100.0, 50.0, StitchCommand::Stitch),
+            (300.0, 50.0, StitchCommand::Stitch),
+            (300.0, 50.0, StitchCommand::ColorChange),
+            (300.0, 150.0, StitchCommand::Stitch),
+            (100.0, 150.0, StitchCommand::Stitch),
+            (100.0, 150.0, StitchCommand::End),
+        ] {
+            pattern.add_stitch(x, y, command);
+        }
+        pattern.metadata.thread_colors = vec![ThreadColor::new([255, 0, 0])];
+        pattern.calculate_bounds();
+        pattern.calculate_statistics();
+        pattern.calculate_color_blocks();
+        pattern
+    }
+
+    fn stitch_bounds(records: &[Stitch]) -> Bounds {
+        let mut bounds = Bounds::new();
+        for stitch in records
+            .iter()
+            .filter(|s| s.command == StitchCommand::Stitch)
+        {
+            bounds.update(stitch.x, stitch.y);
+        }
+        bounds
+    }
+
+    #[test]
+    fn test_box_sits_margin_outside_bounds() {
+        let mut pattern = design();
+        let original = pattern.clone();
+        let edit = pattern.add_basting(5.0, 4.0, false).unwrap();
+
+        assert_eq!(pattern.color_blocks.len(), 3);
+        let block = &pattern.color_blocks[0];
+        let basting = &pattern.stitches[block.start..block.end];
+        assert_eq!(
+            stitch_bounds(basting),
+            Bounds {
+                min_x: 50.0,
+                min_y: 0.0,
+                max_x: 350.0,
+                max_y: 200.0,
+            }
+        );
+        // The run closes on its first corner in stitches no longer than asked
+        let stitches: Vec<_> = basting
+            .iter()
+            .filter(|s| s.command == StitchCommand::Stitch)
+            .collect();
+        assert_eq!(
+            (stitches[0].x, stitches[0].y),
+            (stitches.last().unwrap().x, stitches.last().unwrap().y)
+        );
+        for pair in stitches.windows(2) {
+            assert!((pair[1].x - pair[0].x).hypot(pair[1].y - pair[0].y) <= 40.0 + 1e-9);
+        }
+
+        // Trim, color change and a jump to where the design begins
+        let tail: Vec<_> = pattern.stitches[block.end - 1..block.end + 2]
+            .iter()
+            .map(|s| s.command)
+            .collect();
+        assert_eq!(
+            tail,
+            vec![
+                StitchCommand::Trim,
+                StitchCommand::ColorChange,
+                StitchCommand::Move
+            ]
+        );
+        assert_eq!(
+            pattern.stitches[block.end + 2..],
+            original.stitches[..],
+            "the design follows unchanged"
+        );
+
+        let colors = &pattern.metadata.thread_colors;
+        assert!(colors[0].basting);
+        assert_eq!(colors[1].rgb, [255, 0, 0]);
+        // The second block had no declared color and keeps the one it showed
+        assert_eq!(colors[2].rgb, block_rgb(&original, 1));
+        assert!(!colors[1].basting && !colors[2].basting);
+
+        pattern.apply_patches(edit.undo);
+        assert_eq!(pattern.stitches, original.stitches);
+    }
+
+    #[test]
+    fn test_center_cross() {
+        let mut pattern = design();
+        pattern.add_basting(0.0, 10.0, true).unwrap();
+
+        let block = &pattern.color_blocks[0];
+        let basting = &pattern.stitches[block.start..block.end];
+        assert_eq!(
+            stitch_bounds(basting),
+            Bounds {
+                min_x: 100.0,
+                min_y: 50.0,
+                max_x: 300.0,
+                max_y: 150.0,
+            }
+        );
+        // The arms cross at the center of the box
+        assert!(basting
+            .iter()
+            .any(|s| s.command == StitchCommand::Stitch && (s.x, s.y) == (200.0, 100.0)));
+        let jumps = basting
+            .iter()
+            .filter(|s| s.command == StitchCommand::Move)
+            .count();
+        assert_eq!(jumps, 3);
+    }
+
+    #[test]
+    fn test_rejects_empty_design_and_bad_lengths() {
+        assert_eq!(
+            Pattern::new().add_basting(5.0, 4.0, false),
+            Err(EditError::EmptyDesign)
+        );
+        assert_eq!(
+            design().add_basting(5.0, 0.0, false),
+            Err(EditError::InvalidBasting)
+        );
+        assert_eq!(
+            design().add_basting(-1.0, 4.0, false),
+            Err(EditError::InvalidBasting)
+        );
+    }
+}
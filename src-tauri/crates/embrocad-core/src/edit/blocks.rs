@@ -0,0 +1,551 @@
+// blocks.rs - Splitting, merging, deleting, reordering and recoloring color blocks
+
+use crate::dst::{Pattern, Stitch, StitchCommand, ThreadColor};
+use crate::edit::{EditError, StitchEdit, StitchPatch};
+use crate::export::block_rgb;
+use crate::optimize::{overlaps, record_bounds};
+
+impl Pattern {
+    /// Start a new color block at record `index` by inserting a ColorChange
+    /// before it, preceded by a Trim when `trim` is set
+    ///
+    /// The new block takes `color`, or keeps the thread of the block it was
+    /// split from. Both parts must keep at least one stitch.
+    pub fn split_block(
+        &mut self,
+        index: usize,
+        trim: bool,
+        color: Option<ThreadColor>,
+    ) -> Result<StitchEdit, EditError> {
+        self.check_index(index)?;
+        let block = self
+            .color_blocks
+            .iter()
+            .find(|b| b.start <= index && index < b.end)
+            .ok_or(EditError::EmptySplit { index })?;
+        let has_stitches =
+            |records: &[Stitch]| records.iter().any(|s| s.command == StitchCommand::Stitch);
+        if !has_stitches(&self.stitches[block.start..index])
+            || !has_stitches(&self.stitches[index..block.end])
+        {
+            return Err(EditError::EmptySplit { index });
+        }
+
+        // The machine changes thread where the previous record left the needle
+        let previous = &self.stitches[index - 1];
+        let mut inserted = Vec::new();
+        if trim {
+            inserted.push(Stitch::new(previous.x, previous.y, StitchCommand::Trim));
+        }
+        inserted.push(Stitch::new(
+            previous.x,
+            previous.y,
+            StitchCommand::ColorChange,
+        ));
+        let color_change = index + inserted.len() - 1;
+
+        let undo = self.apply_patches(vec![StitchPatch {
+            start: index,
+            replaced: 0,
+            stitches: inserted,
+        }]);
+        self.split_block_at(color_change, color);
+        Ok(StitchEdit {
+            warnings: Vec::new(),
+            undo,
+        })
+    }
+
+    /// Remove the ColorChange opening block `block`, so it is sewn with the
+    /// thread of the block before it
+    pub fn merge_blocks(&mut self, block: usize) -> Result<StitchEdit, EditError> {
+        let start = match self.color_blocks.get(block) {
+            Some(range) if block > 0 => range.start,
+            _ => return Err(EditError::NoSuchBlock { block }),
+        };
+
+        let warnings = self.merge_blocks_at(&[start]);
+        let undo = self.apply_patches(vec![StitchPatch {
+            start,
+            replaced: 1,
+            stitches: Vec::new(),
+        }]);
+        Ok(StitchEdit { warnings, undo })
+    }
+
+    /// Remove color block `block` with the ColorChange that opens it
+    ///
+    /// The block after it is entered with a jump from where the previous
+    /// block ended, or from the origin when the first block goes. Deleting
+    /// the only block leaves just an End at the origin.
+    pub fn delete_block(&mut self, block: usize) -> Result<StitchEdit, EditError> {
+        if block >= self.color_blocks.len() {
+            return Err(EditError::NoSuchBlock { block });
+        }
+        let patch = self.blocks_removed(block, block);
+        if block < self.metadata.thread_colors.len() {
+            self.metadata.thread_colors.remove(block);
+        }
+        let undo = self.apply_patches(vec![patch]);
+        Ok(StitchEdit {
+            warnings: Vec::new(),
+            undo,
+        })
+    }
+
+    /// The patch removing blocks `first..=last`, which must exist, with the
+    /// ColorChange that opens them; their thread colors are left for the
+    /// caller
+    pub(super) fn blocks_removed(&self, first: usize, last: usize) -> StitchPatch {
+        let (start, end) = (self.color_blocks[first].start, self.color_blocks[last].end);
+        let has_end = self.is_terminal_end(self.stitches.len() - 1);
+        let (from_x, from_y) = match start {
+            0 => (0.0, 0.0),
+            _ => (self.stitches[start - 1].x, self.stitches[start - 1].y),
+        };
+
+        let mut inserted = Vec::new();
+        let replaced = match self.color_blocks.get(last + 1) {
+            Some(next) => {
+                // The next block's ColorChange still sits where the deleted
+                // block ended, so it is replaced along with the block
+                let records = &self.stitches[next.start + 1..next.end];
+                let target = records
+                    .iter()
+                    .find(|s| s.command == StitchCommand::Stitch)
+                    .or(records.first());
+                if start > 0 {
+                    inserted.push(Stitch::new(from_x, from_y, StitchCommand::ColorChange));
+                }
+                if let Some(target) = target.filter(|t| (t.x, t.y) != (from_x, from_y)) {
+                    inserted.push(Stitch::new(target.x, target.y, StitchCommand::Move));
+                }
+                next.start + 1 - start
+            }
+            None => {
+                if has_end {
+                    inserted.push(Stitch::new(from_x, from_y, StitchCommand::End));
+                }
+                end - start
+            }
+        };
+        StitchPatch {
+            start,
+            replaced,
+            stitches: inserted,
+        }
+    }
+
+    /// Give color block `block` the thread `color`
+    pub fn set_block_color(
+        &mut self,
+        block: usize,
+        color: ThreadColor,
+    ) -> Result<StitchEdit, EditError> {
+        self.set_block_colors(vec![(block, color)])
+    }
+
+    /// Give each listed color block its thread
+    ///
+    /// Blocks before one without a declared color get the one they are drawn
+    /// in now, so assigning some colors leaves the others as they look.
+    pub fn set_block_colors(
+        &mut self,
+        colors: Vec<(usize, ThreadColor)>,
+    ) -> Result<StitchEdit, EditError> {
+        if let Some(&(block, _)) = colors
+            .iter()
+            .find(|(block, _)| *block >= self.color_blocks.len())
+        {
+            return Err(EditError::NoSuchBlock { block });
+        }
+        for (block, color) in colors {
+            while self.metadata.thread_colors.len() < block {
+                let rgb = block_rgb(self, self.metadata.thread_colors.len());
+                self.metadata.thread_colors.push(ThreadColor::new(rgb));
+            }
+            match self.metadata.thread_colors.get_mut(block) {
+                Some(existing) => *existing = color,
+                None => self.metadata.thread_colors.push(color),
+            }
+        }
+        self.calculate_color_blocks();
+
+        // No stitch changes, but the history needs a patch to keep the old colors
+        Ok(StitchEdit {
+            warnings: Vec::new(),
+            undo: vec![StitchPatch {
+                start: 0,
+                replaced: 0,
+                stitches: Vec::new(),
+            }],
+        })
+    }
+
+    /// Sew the color blocks in `order`, a permutation of the block indices
+    ///
+    /// Thread colors follow their blocks and a block whose predecessor
+    /// changed is entered with a jump. Unless `allow_overlap` is set, an
+    /// order is refused if it would sew a block before one it overlaps and
+    /// used to cover, using the same bounds test as `optimize_colors`.
+    pub fn reorder_blocks(
+        &mut self,
+        order: &[usize],
+        allow_overlap: bool,
+    ) -> Result<(), EditError> {
+        let blocks = self.block_records();
+        let mut seen = vec![false; blocks.len()];
+        let permutation = order.len() == blocks.len()
+            && order
+                .iter()
+                .all(|&block| block < blocks.len() && !std::mem::replace(&mut seen[block], true));
+        if !permutation {
+            return Err(EditError::InvalidOrder {
+                count: blocks.len(),
+            });
+        }
+        if order.iter().enumerate().all(|(at, &block)| at == block) {
+            return Ok(());
+        }
+
+        if !allow_overlap {
+            let bounds: Vec<_> = blocks
+                .iter()
+                .map(|records| record_bounds(records))
+                .collect();
+            let mut position = vec![0; blocks.len()];
+            for (at, &block) in order.iter().enumerate() {
+                position[block] = at;
+            }
+            for (block, upper) in bounds.iter().enumerate() {
+                for (covered, lower) in bounds[..block].iter().enumerate() {
+                    if position[block] < position[covered] && overlaps(upper, lower) {
+                        return Err(EditError::WouldUncover { block, covered });
+                    }
+                }
+            }
+        }
+
+        self.rebuild_in_order(&blocks, &vec![None; blocks.len()], order);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::Stitch;
+    use crate::edit::tests::{assert_consistent, two_blocks};
+    use crate::edit::EditWarning;
+    use StitchCommand::*;
+
+    /// One 2mm square per block at the given x offsets
+    fn squares(layout: &[([u8; 3], f64)]) -> Pattern {
+        let mut pattern = Pattern::new();
+        for (index, &(color, x)) in layout.iter().enumerate() {
+            if index > 0 {
+                let last = pattern.stitches.last().unwrap().clone();
+                pattern.add_stitch(last.x, last.y, ColorChange);
+            }
+            for (dx, dy) in [(0.0, 0.0), (20.0, 0.0), (20.0, 20.0), (0.0, 20.0)] {
+                pattern.add_stitch(x + dx, dy, Stitch);
+            }
+            pattern.metadata.thread_colors.push(ThreadColor::new(color));
+        }
+        pattern.add_stitch(0.0, 20.0, End);
+        pattern.refresh_all();
+        pattern
+    }
+
+    const RED: [u8; 3] = [255, 0, 0];
+    const GREEN: [u8; 3] = [0, 255, 0];
+    const BLUE: [u8; 3] = [0, 0, 255];
+
+    #[test]
+    fn test_split_block_inherits_color() {
+        let mut pattern = two_blocks();
+        pattern.split_block(5, false, None).unwrap();
+
+        assert_eq!(pattern.stitches[5].command, ColorChange);
+        assert_eq!(pattern.color_changes, 2);
+        let blocks: Vec<_> = pattern
+            .color_blocks
+            .iter()
+            .map(|b| (b.start, b.end))
+            .collect();
+        assert_eq!(blocks, vec![(0, 5), (5, 11), (11, 22)]);
+        let colors: Vec<_> = pattern
+            .color_blocks
+            .iter()
+            .map(|b| b.color.as_ref().unwrap().rgb)
+            .collect();
+        assert_eq!(colors, vec![[255, 0, 0], [255, 0, 0], [0, 0, 255]]);
+        assert_eq!(pattern.color_blocks[1].statistics.stitch_count, 5);
+        assert_consistent(&pattern);
+    }
+
+    #[test]
+    fn test_split_block_with_trim_and_color() {
+        let mut pattern = two_blocks();
+        let green = ThreadColor::new([0, 255, 0]);
+        pattern.split_block(15, true, Some(green.clone())).unwrap();
+
+        let previous = pattern.stitches[14].clone();
+        assert_eq!(
+            pattern.stitches[15],
+            Stitch::new(previous.x, previous.y, Trim)
+        );
+        assert_eq!(pattern.stitches[16].command, ColorChange);
+        assert_eq!(pattern.color_blocks[2].start, 16);
+        assert_eq!(pattern.color_blocks[2].color, Some(green));
+        assert_eq!(pattern.metadata.thread_colors.len(), 3);
+        assert_eq!(pattern.statistics.trim_count, 1);
+        assert_consistent(&pattern);
+    }
+
+    #[test]
+    fn test_split_at_first_and_last_stitch() {
+        let mut pattern = two_blocks();
+        let end = pattern.stitches.len() - 1;
+
+        // Nothing would be left before the first stitch or after the End
+        assert_eq!(
+            pattern.split_block(0, false, None),
+            Err(EditError::EmptySplit { index: 0 })
+        );
+        assert_eq!(
+            pattern.split_block(end, false, None),
+            Err(EditError::EmptySplit { index: end })
+        );
+        // Nor in front of an existing color change
+        assert_eq!(
+            pattern.split_block(10, false, None),
+            Err(EditError::EmptySplit { index: 10 })
+        );
+        assert_eq!(pattern.color_changes, 1);
+
+        // The last real stitch can go on its own
+        pattern.split_block(end - 1, false, None).unwrap();
+        let last = pattern.color_blocks.last().unwrap();
+        assert_eq!((last.start, last.end), (end - 1, end + 2));
+        assert_eq!(last.stitch_count, 1);
+        assert_consistent(&pattern);
+    }
+
+    #[test]
+    fn test_merge_blocks() {
+        let mut pattern = two_blocks();
+        pattern.split_block(5, false, None).unwrap();
+
+        let edit = pattern.merge_blocks(2).unwrap();
+        assert_eq!(
+            edit.warnings,
+            vec![EditWarning::BlocksMerged {
+                index: 11,
+                block: 2
+            }]
+        );
+        assert_eq!(pattern.color_changes, 1);
+        let colors: Vec<_> = pattern
+            .color_blocks
+            .iter()
+            .map(|b| b.color.as_ref().unwrap().rgb)
+            .collect();
+        assert_eq!(colors, vec![[255, 0, 0], [255, 0, 0]]);
+        assert_consistent(&pattern);
+
+        assert_eq!(
+            pattern.merge_blocks(0),
+            Err(EditError::NoSuchBlock { block: 0 })
+        );
+        assert_eq!(
+            pattern.merge_blocks(2),
+            Err(EditError::NoSuchBlock { block: 2 })
+        );
+    }
+
+    #[test]
+    fn test_reorder_blocks() {
+        let mut pattern = squares(&[(RED, 0.0), (GREEN, 100.0), (BLUE, 200.0)]);
+        let stitches = pattern.statistics.real_stitch_count;
+        pattern.reorder_blocks(&[2, 0, 1], false).unwrap();
+
+        let firsts: Vec<_> = pattern
+            .color_blocks
+            .iter()
+            .map(|b| {
+                let records = &pattern.stitches[b.start..b.end];
+                records.iter().find(|s| s.command == Stitch).unwrap().x
+            })
+            .collect();
+        assert_eq!(firsts, vec![200.0, 0.0, 100.0]);
+        let colors: Vec<_> = pattern
+            .metadata
+            .thread_colors
+            .iter()
+            .map(|c| c.rgb)
+            .collect();
+        assert_eq!(colors, vec![BLUE, RED, GREEN]);
+        assert_eq!(pattern.statistics.real_stitch_count, stitches);
+        assert_eq!(pattern.color_changes, 2);
+        assert_eq!(pattern.stitches.last().unwrap().command, End);
+
+        // Red no longer follows the block it was sewn after, so it is
+        // entered with a jump; green still follows red
+        let red = &pattern.stitches[pattern.color_blocks[1].start..];
+        assert_eq!((red[1].command, red[1].x, red[1].y), (Move, 0.0, 0.0));
+        let green = &pattern.stitches[pattern.color_blocks[2].start..];
+        assert_eq!(green[1].command, Stitch);
+        assert_consistent(&pattern);
+    }
+
+    #[test]
+    fn test_reorder_refuses_to_uncover_blocks() {
+        // The green square half covers the red one
+        let mut pattern = squares(&[(RED, 0.0), (GREEN, 10.0), (BLUE, 200.0)]);
+        assert_eq!(
+            pattern.reorder_blocks(&[1, 0, 2], false),
+            Err(EditError::WouldUncover {
+                block: 1,
+                covered: 0
+            })
+        );
+        pattern.reorder_blocks(&[2, 0, 1], false).unwrap();
+
+        // Blue, red, green: sewing green before red needs the override
+        assert!(pattern.reorder_blocks(&[2, 0, 1], false).is_err());
+        pattern.reorder_blocks(&[2, 0, 1], true).unwrap();
+        let colors: Vec<_> = pattern
+            .metadata
+            .thread_colors
+            .iter()
+            .map(|c| c.rgb)
+            .collect();
+        assert_eq!(colors, vec![GREEN, BLUE, RED]);
+    }
+
+    #[test]
+    fn test_reorder_rejects_invalid_orders() {
+        let mut pattern = squares(&[(RED, 0.0), (GREEN, 100.0)]);
+        for order in [&[0][..], &[0, 0][..], &[1, 2][..], &[0, 1, 2][..]] {
+            assert_eq!(
+                pattern.reorder_blocks(order, false),
+                Err(EditError::InvalidOrder { count: 2 })
+            );
+        }
+        let before = pattern.stitches.clone();
+        pattern.reorder_blocks(&[0, 1], false).unwrap();
+        assert_eq!(pattern.stitches, before);
+    }
+
+    #[test]
+    fn test_delete_middle_block_bridges_to_next() {
+        let mut pattern = two_blocks();
+        pattern.split_block(5, false, None).unwrap();
+        let colors = pattern.metadata.thread_colors.clone();
+        let previous_end = pattern.stitches[4].clone();
+        let next_first = pattern.stitches[12].clone();
+
+        let edit = pattern.delete_block(1).unwrap();
+        assert_eq!(pattern.stitches[4], previous_end);
+        assert_eq!(
+            pattern.stitches[5],
+            Stitch::new(previous_end.x, previous_end.y, ColorChange)
+        );
+        assert_eq!(
+            pattern.stitches[6],
+            Stitch::new(next_first.x, next_first.y, Move)
+        );
+        assert_eq!(pattern.stitches[7], next_first);
+        assert_eq!(pattern.color_changes, 1);
+        assert_eq!(
+            pattern.metadata.thread_colors,
+            vec![colors[0].clone(), colors[2].clone()]
+        );
+        assert_eq!(pattern.statistics.real_stitch_count, 14);
+        assert_consistent(&pattern);
+
+        pattern.apply_patches(edit.undo);
+        let mut expected = two_blocks();
+        expected.split_block(5, false, None).unwrap();
+        assert_eq!(pattern.stitches, expected.stitches);
+    }
+
+    #[test]
+    fn test_delete_first_and_last_block() {
+        let mut pattern = two_blocks();
+        let next_first = pattern.stitches[11].clone();
+        pattern.delete_block(0).unwrap();
+        assert_eq!(
+            pattern.stitches[0],
+            Stitch::new(next_first.x, next_first.y, Move)
+        );
+        assert_eq!(pattern.stitches[1], next_first);
+        assert_eq!(pattern.color_changes, 0);
+        assert_eq!(pattern.metadata.thread_colors.len(), 1);
+        assert_eq!(pattern.metadata.thread_colors[0].rgb, [0, 0, 255]);
+        assert_eq!(pattern.bounds.as_ref().unwrap().min_x, next_first.x);
+        assert_consistent(&pattern);
+
+        let mut pattern = two_blocks();
+        pattern.delete_block(1).unwrap();
+        assert_eq!(pattern.stitches.len(), 11);
+        assert_eq!(pattern.stitches[10], Stitch::new(45.0, 0.0, End));
+        assert_eq!(pattern.color_blocks.len(), 1);
+        assert_consistent(&pattern);
+
+        assert_eq!(
+            pattern.delete_block(1),
+            Err(EditError::NoSuchBlock { block: 1 })
+        );
+    }
+
+    #[test]
+    fn test_delete_only_block() {
+        let mut pattern = two_blocks();
+        pattern.delete_block(1).unwrap();
+        pattern.delete_block(0).unwrap();
+
+        assert_eq!(pattern.stitches, vec![Stitch::new(0.0, 0.0, End)]);
+        assert_eq!(pattern.color_changes, 0);
+        assert_eq!(pattern.color_blocks.len(), 1);
+        assert!(pattern.metadata.thread_colors.is_empty());
+        assert_eq!(pattern.statistics.real_stitch_count, 0);
+        assert_consistent(&pattern);
+    }
+
+    #[test]
+    fn test_set_block_color_keeps_other_blocks() {
+        let mut pattern = two_blocks();
+        pattern.split_block(5, false, None).unwrap();
+        pattern.metadata.thread_colors.clear();
+        pattern.calculate_color_blocks();
+        let drawn: Vec<_> = (0..3).map(|block| block_rgb(&pattern, block)).collect();
+
+        let poppy = ThreadColor {
+            rgb: [0xD8, 0x1E, 0x2A],
+            description: Some("Isacord 40 Poppy".to_string()),
+            catalog_number: Some("1703".to_string()),
+            needle: None,
+            basting: false,
+        };
+        pattern.set_block_color(1, poppy.clone()).unwrap();
+        let colors: Vec<_> = pattern
+            .metadata
+            .thread_colors
+            .iter()
+            .map(|c| c.rgb)
+            .collect();
+        assert_eq!(colors, vec![drawn[0], poppy.rgb]);
+        assert_eq!(pattern.color_blocks[1].color, Some(poppy.clone()));
+        assert_eq!(block_rgb(&pattern, 2), drawn[2]);
+
+        let svg = crate::export::write_svg(&pattern, &Default::default());
+        assert!(svg.contains("#d81e2a"));
+
+        assert_eq!(
+            pattern.set_block_color(3, poppy),
+            Err(EditError::NoSuchBlock { block: 3 })
+        );
+    }
+}
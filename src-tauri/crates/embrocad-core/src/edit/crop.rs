@@ -0,0 +1,388 @@
+// crop.rs - Cropping a design to a rectangle, clipping or dropping stitches at the edge
+
+use crate::dst::{Bounds, Pattern, Stitch, StitchCommand};
+use crate::edit::EditError;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// What happens to stitches that cross the crop edge
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum CropMode {
+    /// Cut them at the edge, so the stitching runs right up to it
+    #[default]
+    Clip,
+    /// Drop them whole, keeping only stitches with both ends inside
+    Filter,
+}
+
+/// What a crop removed
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct CropReport {
+    /// Stitches and sequins with nothing left inside the rectangle
+    pub removed_stitches: usize,
+    /// Stitches shortened to end at the edge
+    pub clipped_stitches: usize,
+    /// Original indices of color blocks left without stitches, which are
+    /// deleted with their thread colors
+    pub removed_blocks: Vec<usize>,
+}
+
+/// A record kept by the crop, tagged with the color block it belongs to
+struct Kept {
+    block: usize,
+    stitch: Stitch,
+    /// Where a sewn record expects the needle to come from; the needle is
+    /// jumped there first when it is anywhere else
+    from: Option<(f64, f64)>,
+}
+
+/// The part of the segment `p`-`q` inside `rect`, as parameters along it
+/// from 0 at `p` to 1 at `q`, by Liang-Barsky clipping
+fn clip_segment(rect: &Bounds, p: (f64, f64), q: (f64, f64)) -> Option<(f64, f64)> {
+    let (dx, dy) = (q.0 - p.0, q.1 - p.1);
+    let (mut enter, mut leave) = (0.0f64, 1.0f64);
+    for (step, room) in [
+        (-dx, p.0 - rect.min_x),
+        (dx, rect.max_x - p.0),
+        (-dy, p.1 - rect.min_y),
+        (dy, rect.max_y - p.1),
+    ] {
+        if step == 0.0 {
+            if room < 0.0 {
+                return None;
+            }
+        } else if step < 0.0 {
+            enter = enter.max(room / step);
+        } else {
+            leave = leave.min(room / step);
+        }
+    }
+    (enter <= leave).then_some((enter, leave))
+}
+
+impl Pattern {
+    /// Remove everything outside `rect` (0.1mm units)
+    ///
+    /// Where the stitching leaves the rectangle it stops at the edge, and it
+    /// resumes with a jump to where it comes back in. Trims, color changes
+    /// and the End outside are kept at the last point inside. Color blocks
+    /// left without stitches are deleted with their thread colors, and
+    /// everything derived from the stitches is recalculated.
+    pub fn crop(&mut self, rect: &Bounds, mode: CropMode) -> Result<CropReport, EditError> {
+        if !(rect.width() > 0.0 && rect.height() > 0.0) {
+            return Err(EditError::InvalidCrop);
+        }
+        let inside = |(x, y): (f64, f64)| {
+            rect.min_x <= x && x <= rect.max_x && rect.min_y <= y && y <= rect.max_y
+        };
+        let clamp = |(x, y): (f64, f64)| {
+            (
+                x.clamp(rect.min_x, rect.max_x),
+                y.clamp(rect.min_y, rect.max_y),
+            )
+        };
+
+        // The first record is a penetration where the design starts, not a
+        // stitch from the origin
+        let first = self.stitches.first().map_or((0.0, 0.0), |s| (s.x, s.y));
+        let start = inside(first).then_some(first);
+        let mut needle = start;
+        let mut previous = first;
+        let mut block = 0;
+        let mut sewn_before = vec![false];
+        let mut sewn_after = vec![false];
+        let mut kept: Vec<Kept> = Vec::with_capacity(self.stitches.len());
+        let mut report = CropReport::default();
+
+        for stitch in &self.stitches {
+            let (p, q) = (previous, (stitch.x, stitch.y));
+            previous = q;
+            let synced = needle == Some(p);
+            match stitch.command {
+                StitchCommand::Stitch => {
+                    sewn_before[block] = true;
+                    let span = match mode {
+                        CropMode::Clip => clip_segment(rect, p, q),
+                        CropMode::Filter => (inside(p) && inside(q)).then_some((0.0, 1.0)),
+                    };
+                    // Ends left in place are copied, so they stay exact
+                    let at = |t: f64| {
+                        if t == 0.0 {
+                            p
+                        } else if t == 1.0 {
+                            q
+                        } else {
+                            (p.0 + (q.0 - p.0) * t, p.1 + (q.1 - p.1) * t)
+                        }
+                    };
+                    let Some((enter, leave)) = span.filter(|&(a, b)| a < b || p == q) else {
+                        report.removed_stitches += 1;
+                        continue;
+                    };
+                    if (enter, leave) != (0.0, 1.0) {
+                        report.clipped_stitches += 1;
+                    }
+                    let (from, to) = (at(enter), at(leave));
+                    kept.push(Kept {
+                        block,
+                        stitch: Stitch::new(to.0, to.1, StitchCommand::Stitch),
+                        from: Some(from),
+                    });
+                    needle = Some(to);
+                    sewn_after[block] = true;
+                }
+                // Travel outside is made up later by a jump straight to
+                // wherever sewing resumes
+                StitchCommand::Move => {
+                    if synced && inside(q) {
+                        kept.push(Kept {
+                            block,
+                            stitch: stitch.clone(),
+                            from: None,
+                        });
+                        needle = Some(q);
+                    }
+                }
+                StitchCommand::SequinEject => {
+                    sewn_before[block] = true;
+                    if !inside(q) {
+                        report.removed_stitches += 1;
+                        continue;
+                    }
+                    kept.push(Kept {
+                        block,
+                        stitch: stitch.clone(),
+                        from: Some(if synced { p } else { q }),
+                    });
+                    needle = Some(q);
+                    sewn_after[block] = true;
+                }
+                command => {
+                    if command == StitchCommand::ColorChange {
+                        block += 1;
+                        sewn_before.push(false);
+                        sewn_after.push(false);
+                    }
+                    let here = if inside(q) {
+                        q
+                    } else {
+                        needle.unwrap_or_else(|| clamp(q))
+                    };
+                    kept.push(Kept {
+                        block,
+                        stitch: Stitch::new(here.0, here.1, command),
+                        from: None,
+                    });
+                    needle = Some(here);
+                }
+            }
+        }
+        if !sewn_after.contains(&true) {
+            return Err(EditError::NothingInCrop);
+        }
+
+        // Blocks that had stitches and lost them all go, taking the
+        // ColorChange that opens them; the first block kept opens with none
+        let keep: Vec<bool> = sewn_before
+            .iter()
+            .zip(&sewn_after)
+            .map(|(&before, &after)| after || !before)
+            .collect();
+        let first_kept = keep.iter().position(|&k| k);
+        let mut stitches = Vec::with_capacity(kept.len());
+        let mut needle = start;
+        for record in kept {
+            let command = record.stitch.command;
+            let wanted = match command {
+                StitchCommand::End => true,
+                StitchCommand::ColorChange => {
+                    keep[record.block] && Some(record.block) != first_kept
+                }
+                _ => keep[record.block],
+            };
+            if !wanted {
+                continue;
+            }
+            if let Some(from) = record.from.filter(|&from| needle != Some(from)) {
+                stitches.push(Stitch::new(from.0, from.1, StitchCommand::Move));
+            }
+            needle = Some((record.stitch.x, record.stitch.y));
+            stitches.push(record.stitch);
+        }
+
+        report.removed_blocks = (0..keep.len()).filter(|&b| !keep[b]).collect();
+        for &removed in report.removed_blocks.iter().rev() {
+            if removed < self.metadata.thread_colors.len() {
+                self.metadata.thread_colors.remove(removed);
+            }
+        }
+        self.stitches = stitches;
+        self.refresh_all();
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::ThreadColor;
+    use crate::edit::tests::assert_consistent;
+    use StitchCommand::{ColorChange, End, Move, Stitch, Trim};
+
+    fn points(pattern: &Pattern) -> Vec<(f64, f64, StitchCommand)> {
+        pattern
+            .stitches
+            .iter()
+            .map(|s| (s.x, s.y, s.command))
+            .collect()
+    }
+
+    /// A 0-40 crop box in both directions
+    fn box_40() -> Bounds {
+        Bounds {
+            min_x: 0.0,
+            min_y: 0.0,
+            max_x: 40.0,
+            max_y: 40.0,
+        }
+    }
+
+    /// A red run crossing x = 40 and back in the middle of its block, then
+    /// a green block entirely right of it
+    fn straddling() -> Pattern {
+        let mut pattern = Pattern::new();
+        for &(x, y, command) in &[
+            (0.0, 10.0, Stitch),
+            (20.0, 10.0, Stitch),
+            (60.0, 10.0, Stitch),
+            (60.0, 30.0, Stitch),
+            (20.0, 30.0, Stitch),
+            (10.0, 30.0, Stitch),
+            (10.0, 30.0, Trim),
+            (80.0, 20.0, Move),
+            (80.0, 20.0, ColorChange),
+            (90.0, 20.0, Stitch),
+            (90.0, 30.0, Stitch),
+            (90.0, 30.0, End),
+        ] {
+            pattern.add_stitch(x, y, command);
+        }
+        pattern.metadata.thread_colors =
+            vec![ThreadColor::new([255, 0, 0]), ThreadColor::new([0, 255, 0])];
+        pattern.refresh_all();
+        pattern
+    }
+
+    #[test]
+    fn test_clip_straddling_block() {
+        let mut pattern = straddling();
+        let report = pattern.crop(&box_40(), CropMode::Clip).unwrap();
+
+        assert_eq!(
+            points(&pattern),
+            vec![
+                (0.0, 10.0, Stitch),
+                (20.0, 10.0, Stitch),
+                // Out to the edge, then a jump to where the run comes back
+                (40.0, 10.0, Stitch),
+                (40.0, 30.0, Move),
+                (20.0, 30.0, Stitch),
+                (10.0, 30.0, Stitch),
+                (10.0, 30.0, Trim),
+                (10.0, 30.0, End),
+            ]
+        );
+        assert_eq!(
+            report,
+            CropReport {
+                removed_stitches: 3,
+                clipped_stitches: 2,
+                removed_blocks: vec![1],
+            }
+        );
+        assert_eq!(pattern.color_blocks.len(), 1);
+        assert_eq!(pattern.metadata.thread_colors.len(), 1);
+        assert_eq!(pattern.metadata.thread_colors[0].rgb, [255, 0, 0]);
+        assert_eq!(pattern.bounds.as_ref().unwrap().max_x, 40.0);
+        assert_consistent(&pattern);
+    }
+
+    #[test]
+    fn test_filter_drops_whole_stitches() {
+        let mut pattern = straddling();
+        let report = pattern.crop(&box_40(), CropMode::Filter).unwrap();
+
+        assert_eq!(
+            points(&pattern),
+            vec![
+                (0.0, 10.0, Stitch),
+                (20.0, 10.0, Stitch),
+                (20.0, 30.0, Move),
+                (10.0, 30.0, Stitch),
+                (10.0, 30.0, Trim),
+                (10.0, 30.0, End),
+            ]
+        );
+        assert_eq!(report.removed_stitches, 5);
+        assert_eq!(report.clipped_stitches, 0);
+        assert_eq!(report.removed_blocks, vec![1]);
+        assert_consistent(&pattern);
+    }
+
+    #[test]
+    fn test_crop_keeps_inner_design_and_first_block_removal() {
+        // A rectangle around everything changes nothing
+        let original = straddling();
+        let mut pattern = original.clone();
+        let all = Bounds {
+            min_x: -10.0,
+            min_y: -10.0,
+            max_x: 100.0,
+            max_y: 100.0,
+        };
+        let report = pattern.crop(&all, CropMode::Clip).unwrap();
+        assert_eq!(report, CropReport::default());
+        assert_eq!(pattern.stitches, original.stitches);
+
+        // Keeping only the green block drops the red one and the
+        // ColorChange between them
+        let mut pattern = original.clone();
+        let right = Bounds {
+            min_x: 70.0,
+            min_y: 0.0,
+            max_x: 100.0,
+            max_y: 40.0,
+        };
+        let report = pattern.crop(&right, CropMode::Clip).unwrap();
+        assert_eq!(report.removed_blocks, vec![0]);
+        assert_eq!(
+            points(&pattern),
+            vec![
+                (80.0, 20.0, Move),
+                (90.0, 20.0, Stitch),
+                (90.0, 30.0, Stitch),
+                (90.0, 30.0, End),
+            ]
+        );
+        assert_eq!(pattern.metadata.thread_colors[0].rgb, [0, 255, 0]);
+        assert_consistent(&pattern);
+
+        let empty = Bounds {
+            min_x: 200.0,
+            min_y: 200.0,
+            max_x: 300.0,
+            max_y: 300.0,
+        };
+        assert_eq!(
+            pattern.clone().crop(&empty, CropMode::Clip),
+            Err(EditError::NothingInCrop)
+        );
+        assert_eq!(
+            pattern.crop(&Bounds::new(), CropMode::Clip),
+            Err(EditError::InvalidCrop)
+        );
+    }
+}
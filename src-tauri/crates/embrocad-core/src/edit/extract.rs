@@ -0,0 +1,509 @@
+// extract.rs - Copying part of a design out as a standalone pattern, or cutting it away
+
+use crate::dst::{Bounds, Pattern, PatternMetadata, Stitch, StitchCommand, ThreadColor};
+use crate::edit::crop::CropMode;
+use crate::edit::{EditError, StitchEdit, StitchPatch};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Part of a design to extract
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum Selection {
+    /// Records `start..end`
+    StitchRange { start: usize, end: usize },
+    /// Whole color blocks, sewn in their original order however they are
+    /// listed
+    Blocks { blocks: Vec<usize> },
+    /// Stitches with both ends inside a rectangle in 0.1mm units
+    Rect(Bounds),
+}
+
+impl Pattern {
+    /// A new pattern holding just `selection`, at the same coordinates or
+    /// with `recenter` moved so its bounds midpoint sits at the origin
+    ///
+    /// The selection keeps the thread colors of the blocks it covers, and
+    /// ends with an End where its last record leaves the needle. Stitches
+    /// whose previous record was left out are reached with a jump.
+    pub fn extract(&self, selection: &Selection, recenter: bool) -> Result<Pattern, EditError> {
+        let (stitches, thread_colors) = match selection {
+            Selection::StitchRange { start, end } => self.extract_range(*start, *end)?,
+            Selection::Blocks { blocks } => self.extract_blocks(blocks)?,
+            Selection::Rect(rect) => {
+                let mut cropped = self.clone();
+                cropped.crop(rect, CropMode::Filter).map_err(|e| match e {
+                    EditError::NothingInCrop => EditError::EmptySelection,
+                    e => e,
+                })?;
+                let colors = std::mem::take(&mut cropped.metadata.thread_colors);
+                (cropped.stitches, colors)
+            }
+        };
+
+        let mut stitches: Vec<Stitch> = stitches
+            .into_iter()
+            .filter(|s| s.command != StitchCommand::End)
+            .collect();
+        if !stitches.iter().any(|s| {
+            matches!(
+                s.command,
+                StitchCommand::Stitch | StitchCommand::SequinEject
+            )
+        }) {
+            return Err(EditError::EmptySelection);
+        }
+        let last = stitches[stitches.len() - 1].clone();
+        stitches.push(Stitch::new(last.x, last.y, StitchCommand::End));
+
+        let mut pattern = Pattern {
+            stitches,
+            metadata: PatternMetadata {
+                label: self.metadata.label.clone(),
+                thread_colors,
+                ..PatternMetadata::default()
+            },
+            ..Pattern::new()
+        };
+        pattern.refresh_all();
+        if recenter {
+            pattern.center();
+        }
+        Ok(pattern)
+    }
+
+    /// Remove `selection` from the design, as cutting it out does
+    ///
+    /// Whole blocks go with their ColorChange and thread color, as
+    /// `delete_block` removes them. The records of a range or rectangle go
+    /// one by one: a removed ColorChange merges its block into the one
+    /// before it, and stitching that resumes after a gap is reached with a
+    /// jump. Blocks a rectangle empties keep their ColorChange.
+    pub fn delete_selection(&mut self, selection: &Selection) -> Result<StitchEdit, EditError> {
+        let indices = match selection {
+            Selection::Blocks { blocks } => return self.delete_blocks(blocks),
+            Selection::StitchRange { start, end } => {
+                if start >= end {
+                    return Err(EditError::EmptySelection);
+                }
+                self.check_index(end - 1)?;
+                (*start..*end)
+                    .filter(|&index| !self.is_terminal_end(index))
+                    .collect()
+            }
+            Selection::Rect(rect) => self.sewn_inside(rect)?,
+        };
+        if indices.is_empty() {
+            return Err(EditError::EmptySelection);
+        }
+
+        let warnings = self.merge_blocks_at(&indices);
+        let mut patches: Vec<StitchPatch> = Vec::new();
+        for index in indices {
+            match patches.last_mut() {
+                Some(run) if run.start + run.replaced == index => run.replaced += 1,
+                _ => patches.push(StitchPatch {
+                    start: index,
+                    replaced: 1,
+                    stitches: Vec::new(),
+                }),
+            }
+        }
+        for patch in &mut patches {
+            let (start, end) = (patch.start, patch.start + patch.replaced);
+            let resumes = self
+                .stitches
+                .get(end)
+                .is_some_and(|s| s.command == StitchCommand::Stitch);
+            if start > 0 && resumes {
+                let (before, from) = (&self.stitches[start - 1], &self.stitches[end - 1]);
+                if (before.x, before.y) != (from.x, from.y) {
+                    patch
+                        .stitches
+                        .push(Stitch::new(from.x, from.y, StitchCommand::Move));
+                }
+            }
+        }
+
+        Ok(StitchEdit {
+            warnings,
+            undo: self.apply_patches(patches),
+        })
+    }
+
+    /// Remove the listed blocks in one edit, each run of neighbouring
+    /// blocks as `delete_block` would remove it
+    fn delete_blocks(&mut self, blocks: &[usize]) -> Result<StitchEdit, EditError> {
+        let mut blocks = blocks.to_vec();
+        blocks.sort_unstable();
+        blocks.dedup();
+        if let Some(&block) = blocks.iter().find(|&&b| b >= self.color_blocks.len()) {
+            return Err(EditError::NoSuchBlock { block });
+        }
+        if blocks.is_empty() {
+            return Err(EditError::EmptySelection);
+        }
+
+        let mut runs: Vec<(usize, usize)> = Vec::new();
+        for &block in &blocks {
+            match runs.last_mut() {
+                Some((_, last)) if *last + 1 == block => *last = block,
+                _ => runs.push((block, block)),
+            }
+        }
+        let patches = runs
+            .iter()
+            .map(|&(first, last)| self.blocks_removed(first, last))
+            .collect();
+        for &block in blocks.iter().rev() {
+            if block < self.metadata.thread_colors.len() {
+                self.metadata.thread_colors.remove(block);
+            }
+        }
+        Ok(StitchEdit {
+            warnings: Vec::new(),
+            undo: self.apply_patches(patches),
+        })
+    }
+
+    /// Indices of the stitches with both ends inside `rect` and the sequins
+    /// dropped inside it
+    fn sewn_inside(&self, rect: &Bounds) -> Result<Vec<usize>, EditError> {
+        if !(rect.width() > 0.0 && rect.height() > 0.0) {
+            return Err(EditError::InvalidCrop);
+        }
+        let inside = |stitch: &Stitch| {
+            rect.min_x <= stitch.x
+                && stitch.x <= rect.max_x
+                && rect.min_y <= stitch.y
+                && stitch.y <= rect.max_y
+        };
+        Ok(self
+            .stitches
+            .iter()
+            .enumerate()
+            .filter(|&(index, stitch)| match stitch.command {
+                // The first record is a penetration, not a stitch from the origin
+                StitchCommand::Stitch => {
+                    inside(stitch) && (index == 0 || inside(&self.stitches[index - 1]))
+                }
+                StitchCommand::SequinEject => inside(stitch),
+                _ => false,
+            })
+            .map(|(index, _)| index)
+            .collect())
+    }
+
+    /// Records `start..end` with any ColorChange at either end dropped, and
+    /// the colors of the blocks between
+    fn extract_range(
+        &self,
+        start: usize,
+        end: usize,
+    ) -> Result<(Vec<Stitch>, Vec<ThreadColor>), EditError> {
+        if start >= end {
+            return Err(EditError::EmptySelection);
+        }
+        self.check_index(end - 1)?;
+        let is_change = |index: usize| self.stitches[index].command == StitchCommand::ColorChange;
+        let (mut start, mut end) = (start, end);
+        while start < end && is_change(start) {
+            start += 1;
+        }
+        while start < end && is_change(end - 1) {
+            end -= 1;
+        }
+        if start == end {
+            return Err(EditError::EmptySelection);
+        }
+
+        let block_of = |index: usize| {
+            self.color_blocks
+                .partition_point(|b| b.end <= index)
+                .min(self.color_blocks.len().saturating_sub(1))
+        };
+        let colors = self
+            .metadata
+            .thread_colors
+            .iter()
+            .take(block_of(end - 1) + 1)
+            .skip(block_of(start))
+            .cloned()
+            .collect();
+        Ok((self.joined(std::slice::from_ref(&(start..end))), colors))
+    }
+
+    /// The listed blocks joined by ColorChanges, with their colors
+    fn extract_blocks(
+        &self,
+        blocks: &[usize],
+    ) -> Result<(Vec<Stitch>, Vec<ThreadColor>), EditError> {
+        let mut blocks = blocks.to_vec();
+        blocks.sort_unstable();
+        blocks.dedup();
+        let mut ranges = Vec::with_capacity(blocks.len());
+        for &block in &blocks {
+            let range = self
+                .color_blocks
+                .get(block)
+                .ok_or(EditError::NoSuchBlock { block })?;
+            // Each block but the first opens with its ColorChange
+            let start = range.start + usize::from(block > 0);
+            ranges.push(start..range.end);
+        }
+        let colors = blocks
+            .iter()
+            .filter_map(|&block| self.metadata.thread_colors.get(block).cloned())
+            .collect();
+        Ok((self.joined(&ranges), colors))
+    }
+
+    /// The records of `ranges` in order, each range after the first opened
+    /// by a ColorChange where the one before it ended
+    ///
+    /// A Stitch whose original predecessor is not the record now before it
+    /// is reached with a jump to where it was sewn from, so no thread is
+    /// drawn across the gap.
+    fn joined(&self, ranges: &[Range<usize>]) -> Vec<Stitch> {
+        let mut stitches: Vec<Stitch> = Vec::new();
+        for range in ranges {
+            if let Some(last) = stitches.last().cloned() {
+                stitches.push(Stitch::new(last.x, last.y, StitchCommand::ColorChange));
+            }
+            for index in range.clone() {
+                let stitch = &self.stitches[index];
+                if stitch.command == StitchCommand::Stitch && index > 0 {
+                    let from = &self.stitches[index - 1];
+                    let moved = stitches
+                        .last()
+                        .is_some_and(|last| (last.x, last.y) != (from.x, from.y));
+                    if moved {
+                        stitches.push(Stitch::new(from.x, from.y, StitchCommand::Move));
+                    }
+                }
+                stitches.push(stitch.clone());
+            }
+        }
+        stitches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::Stitch;
+    use crate::edit::tests::assert_consistent;
+    use StitchCommand::*;
+
+    const RED: [u8; 3] = [255, 0, 0];
+    const GREEN: [u8; 3] = [0, 255, 0];
+    const BLUE: [u8; 3] = [0, 0, 255];
+
+    /// Red, green and blue 2mm squares 5mm apart, each entered with a jump
+    fn three_blocks() -> Pattern {
+        let mut pattern = Pattern::new();
+        for (index, (color, x)) in [(RED, 0.0), (GREEN, 50.0), (BLUE, 100.0)]
+            .into_iter()
+            .enumerate()
+        {
+            if index > 0 {
+                let last = pattern.stitches.last().unwrap().clone();
+                pattern.add_stitch(last.x, last.y, Trim);
+                pattern.add_stitch(last.x, last.y, ColorChange);
+                pattern.add_stitch(x, 0.0, Move);
+            }
+            for (dx, dy) in [
+                (0.0, 0.0),
+                (20.0, 0.0),
+                (20.0, 20.0),
+                (0.0, 20.0),
+                (0.0, 0.0),
+            ] {
+                pattern.add_stitch(x + dx, dy, Stitch);
+            }
+            pattern.metadata.thread_colors.push(ThreadColor::new(color));
+        }
+        pattern.add_stitch(100.0, 0.0, End);
+        pattern.metadata.label = Some("Swatches".to_string());
+        pattern.refresh_all();
+        pattern
+    }
+
+    #[test]
+    fn test_extract_middle_block() {
+        let source = three_blocks();
+        let selection = Selection::Blocks { blocks: vec![1] };
+        let motif = source.extract(&selection, false).unwrap();
+
+        let records: Vec<_> = motif
+            .stitches
+            .iter()
+            .map(|s| (s.x, s.y, s.command))
+            .collect();
+        assert_eq!(records[0], (50.0, 0.0, Move));
+        assert_eq!(records.last(), Some(&(50.0, 0.0, End)));
+        assert_eq!(motif.color_blocks.len(), 1);
+        assert_eq!(motif.metadata.thread_colors.len(), 1);
+        assert_eq!(motif.metadata.thread_colors[0].rgb, GREEN);
+        assert_eq!(motif.metadata.label.as_deref(), Some("Swatches"));
+
+        let stats = &motif.statistics;
+        assert_eq!(stats.real_stitch_count, 5);
+        assert_eq!(stats.color_change_count, 0);
+        assert_eq!(stats.trim_count, 1);
+        // Four 2mm sides, the first stitch sewn in place
+        assert!((stats.total_thread_length_mm - 8.0).abs() < 1e-9);
+        assert_eq!(
+            motif.bounds,
+            Some(Bounds {
+                min_x: 50.0,
+                min_y: 0.0,
+                max_x: 70.0,
+                max_y: 20.0,
+            })
+        );
+        assert_consistent(&motif);
+        // The source is untouched
+        assert_eq!(source.color_blocks.len(), 3);
+
+        let centred = source.extract(&selection, true).unwrap();
+        let bounds = centred.bounds.unwrap();
+        assert_eq!((bounds.min_x, bounds.max_x), (-10.0, 10.0));
+        assert_eq!(centred.statistics.real_stitch_count, 5);
+    }
+
+    #[test]
+    fn test_extract_blocks_and_ranges() {
+        let source = three_blocks();
+
+        // Red and blue: blue's jump starts where red ended
+        let outer = source
+            .extract(&Selection::Blocks { blocks: vec![2, 0] }, false)
+            .unwrap();
+        let colors: Vec<_> = outer.metadata.thread_colors.iter().map(|t| t.rgb).collect();
+        assert_eq!(colors, vec![RED, BLUE]);
+        assert_eq!(outer.color_blocks.len(), 2);
+        assert_eq!(outer.statistics.real_stitch_count, 10);
+        assert_consistent(&outer);
+
+        // From red's last stitch into green: the range opens mid-block and
+        // is entered in place rather than from the stitch before it
+        let range = source
+            .extract(&Selection::StitchRange { start: 4, end: 10 }, false)
+            .unwrap();
+        let colors: Vec<_> = range.metadata.thread_colors.iter().map(|t| t.rgb).collect();
+        assert_eq!(colors, vec![RED, GREEN]);
+        assert_eq!(range.stitches[0], Stitch::new(0.0, 0.0, Stitch));
+        assert_eq!(range.color_blocks.len(), 2);
+        assert_consistent(&range);
+
+        // A range of nothing but a ColorChange selects nothing
+        let error = |selection: Selection| source.extract(&selection, false).err();
+        assert_eq!(
+            error(Selection::StitchRange { start: 6, end: 7 }),
+            Some(EditError::EmptySelection)
+        );
+        assert_eq!(
+            error(Selection::Blocks { blocks: vec![3] }),
+            Some(EditError::NoSuchBlock { block: 3 })
+        );
+        assert_eq!(
+            error(Selection::StitchRange { start: 0, end: 99 }),
+            Some(EditError::NoSuchStitch { index: 98 })
+        );
+
+        let rect = Bounds {
+            min_x: 40.0,
+            min_y: -10.0,
+            max_x: 80.0,
+            max_y: 30.0,
+        };
+        let boxed = source.extract(&Selection::Rect(rect), false).unwrap();
+        assert_eq!(boxed.metadata.thread_colors.len(), 1);
+        assert_eq!(boxed.metadata.thread_colors[0].rgb, GREEN);
+        assert_eq!(boxed.statistics.real_stitch_count, 5);
+    }
+
+    #[test]
+    fn test_delete_selection() {
+        let source = three_blocks();
+        let colors = |pattern: &Pattern| -> Vec<[u8; 3]> {
+            pattern
+                .metadata
+                .thread_colors
+                .iter()
+                .map(|t| t.rgb)
+                .collect()
+        };
+
+        // Green goes; blue is entered with a jump from where red ended
+        let mut pattern = source.clone();
+        let edit = pattern
+            .delete_selection(&Selection::Blocks { blocks: vec![1] })
+            .unwrap();
+        assert_eq!(pattern.stitches.len(), 15);
+        assert_eq!(pattern.stitches[6], Stitch::new(0.0, 0.0, ColorChange));
+        assert_eq!(pattern.stitches[7], Stitch::new(100.0, 0.0, Move));
+        assert_eq!(colors(&pattern), vec![RED, BLUE]);
+        assert_eq!(pattern.statistics.real_stitch_count, 10);
+        assert_consistent(&pattern);
+        pattern.apply_patches(edit.undo);
+        assert_eq!(pattern.stitches, source.stitches);
+
+        // Red and blue at once leave green as the only block
+        let mut pattern = source.clone();
+        pattern
+            .delete_selection(&Selection::Blocks { blocks: vec![2, 0] })
+            .unwrap();
+        assert_eq!(pattern.stitches.len(), 9);
+        assert_eq!(pattern.stitches[0], Stitch::new(50.0, 0.0, Move));
+        assert_eq!(colors(&pattern), vec![GREEN]);
+        assert_eq!(pattern.color_blocks.len(), 1);
+        assert_consistent(&pattern);
+
+        // Three sides of the green square: the last is reached with a jump
+        let mut pattern = source.clone();
+        pattern
+            .delete_selection(&Selection::StitchRange { start: 9, end: 12 })
+            .unwrap();
+        assert_eq!(pattern.stitches.len(), 20);
+        assert_eq!(pattern.stitches[9], Stitch::new(50.0, 20.0, Move));
+        assert_eq!(pattern.statistics.real_stitch_count, 12);
+        assert_consistent(&pattern);
+
+        // A rectangle empties the green block but keeps its ColorChange
+        let mut pattern = source.clone();
+        let rect = Bounds {
+            min_x: 40.0,
+            min_y: -10.0,
+            max_x: 80.0,
+            max_y: 30.0,
+        };
+        pattern.delete_selection(&Selection::Rect(rect)).unwrap();
+        assert_eq!(pattern.statistics.real_stitch_count, 10);
+        assert_eq!(pattern.color_blocks.len(), 3);
+        assert_eq!(colors(&pattern), vec![RED, GREEN, BLUE]);
+        assert_consistent(&pattern);
+
+        let mut pattern = source.clone();
+        let mut error = |selection: Selection| pattern.delete_selection(&selection).err();
+        assert_eq!(
+            error(Selection::Blocks { blocks: vec![3] }),
+            Some(EditError::NoSuchBlock { block: 3 })
+        );
+        assert_eq!(
+            error(Selection::StitchRange { start: 21, end: 22 }),
+            Some(EditError::EmptySelection)
+        );
+        let far = Bounds {
+            min_x: 500.0,
+            min_y: 500.0,
+            max_x: 600.0,
+            max_y: 600.0,
+        };
+        assert_eq!(error(Selection::Rect(far)), Some(EditError::EmptySelection));
+        assert_eq!(pattern.stitches, source.stitches);
+    }
+}
@@ -0,0 +1,435 @@
+// mod.rs - Stitch and color block edits of a loaded pattern
+
+mod basting;
+mod blocks;
+mod crop;
+mod extract;
+mod nudge;
+mod paste;
+mod stitches;
+
+pub use crop::{CropMode, CropReport};
+pub use extract::Selection;
+
+use crate::dst::{Bounds, Pattern, Stitch, StitchCommand, ThreadColor};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Replace `replaced` records starting at `start` with `stitches`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StitchPatch {
+    pub start: usize,
+    pub replaced: usize,
+    pub stitches: Vec<Stitch>,
+}
+
+/// Why a stitch edit was refused; the pattern is left unchanged
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum EditError {
+    #[error("Stitch {index} does not exist")]
+    NoSuchStitch { index: usize },
+    #[error("The End record must stay the last record")]
+    EndNotLast,
+    #[error("({x}, {y}) is outside the coordinate range")]
+    OutOfRange { x: f64, y: f64 },
+    #[error("Color block {block} does not exist or has no block before it")]
+    NoSuchBlock { block: usize },
+    #[error("Splitting at stitch {index} would leave a color block without stitches")]
+    EmptySplit { index: usize },
+    #[error("The new order must list each of the {count} color blocks once")]
+    InvalidOrder { count: usize },
+    #[error("Color block {block} overlaps block {covered} and would be sewn under it")]
+    WouldUncover { block: usize, covered: usize },
+    #[error("The design has no stitches")]
+    EmptyDesign,
+    #[error("Basting needs a positive stitch length and a margin of zero or more")]
+    InvalidBasting,
+    #[error("Outlines need a positive stitch length and alpha, and an offset of zero or more")]
+    InvalidOutline,
+    #[error("The design's stitches do not enclose an area to outline")]
+    NoArea,
+    #[error("The crop rectangle needs a positive width and height")]
+    InvalidCrop,
+    #[error("Nothing of the design is inside the crop rectangle")]
+    NothingInCrop,
+    #[error("The selection has no stitches")]
+    EmptySelection,
+    #[error("The grid spacing must be positive")]
+    InvalidGrid,
+}
+
+/// Side effects of an edit the user should know about
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum EditWarning {
+    #[error("Removing the color change at stitch {index} merged color block {block} into the one before it")]
+    BlocksMerged { index: usize, block: usize },
+}
+
+/// What an edit changed: warnings for the user and the patches that undo it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StitchEdit {
+    pub warnings: Vec<EditWarning>,
+    pub undo: Vec<StitchPatch>,
+}
+
+/// Apply patches sorted by `start`, non-overlapping and indexed against the
+/// records before any of them applies; returns patches that undo them, in
+/// the same form
+pub fn apply_patches(stitches: &mut Vec<Stitch>, patches: Vec<StitchPatch>) -> Vec<StitchPatch> {
+    let mut undo = Vec::with_capacity(patches.len());
+    let mut shift = 0isize;
+    let mut inverse = |patch: &StitchPatch, removed: Vec<Stitch>, shift: &mut isize| {
+        undo.push(StitchPatch {
+            start: patch.start.saturating_add_signed(*shift),
+            replaced: patch.stitches.len(),
+            stitches: removed,
+        });
+        *shift += patch.stitches.len() as isize - patch.replaced as isize;
+    };
+
+    if let [patch] = patches.as_slice() {
+        let end = patch.start + patch.replaced;
+        let removed = stitches
+            .splice(patch.start..end, patch.stitches.iter().cloned())
+            .collect();
+        inverse(patch, removed, &mut shift);
+        return undo;
+    }
+
+    // One pass over the records, so many scattered patches stay linear
+    let mut rebuilt = Vec::with_capacity(stitches.len());
+    let mut records = std::mem::take(stitches).into_iter();
+    let mut next = 0;
+    for patch in &patches {
+        rebuilt.extend(records.by_ref().take(patch.start - next));
+        let removed: Vec<Stitch> = records.by_ref().take(patch.replaced).collect();
+        rebuilt.extend(patch.stitches.iter().cloned());
+        next = patch.start + patch.replaced;
+        inverse(patch, removed, &mut shift);
+    }
+    rebuilt.extend(records);
+    *stitches = rebuilt;
+    undo
+}
+
+/// A record the patch's block is found by: the first one replaced, or the
+/// one an insertion follows, since records inserted before a ColorChange
+/// belong to the block before it
+fn anchor(patch: &StitchPatch) -> usize {
+    patch.start - usize::from(patch.replaced == 0)
+}
+
+/// Counts and lengths over a run of records
+#[derive(Debug, Default)]
+struct RunStatistics {
+    stitches: u32,
+    jumps: u32,
+    trims: u32,
+    sequins: u32,
+    measured: u32,
+    length_mm: f64,
+    min_mm: f64,
+    max_mm: f64,
+}
+
+impl Pattern {
+    fn run_statistics(&self, start: usize, end: usize) -> RunStatistics {
+        let mut run = RunStatistics {
+            min_mm: f64::INFINITY,
+            max_mm: f64::NEG_INFINITY,
+            ..RunStatistics::default()
+        };
+        for index in start..end.min(self.stitches.len()) {
+            match self.stitches[index].command {
+                StitchCommand::Stitch => {
+                    run.stitches += 1;
+                    if let Some(length) = self.record_length_mm(index) {
+                        run.measured += 1;
+                        run.length_mm += length;
+                        run.min_mm = run.min_mm.min(length);
+                        run.max_mm = run.max_mm.max(length);
+                    }
+                }
+                StitchCommand::Move => run.jumps += 1,
+                StitchCommand::Trim => run.trims += 1,
+                StitchCommand::SequinEject => run.sequins += 1,
+                _ => {}
+            }
+        }
+        run
+    }
+
+    /// Apply patches and bring counters, bounds, statistics and color blocks
+    /// up to date; returns the patches that undo them
+    ///
+    /// A single patch that neither adds nor removes ColorChange or End
+    /// records is accounted for incrementally, touching only its own color
+    /// block. Anything else recalculates from scratch.
+    pub fn apply_patches(&mut self, patches: Vec<StitchPatch>) -> Vec<StitchPatch> {
+        let structural = |command: StitchCommand| {
+            matches!(command, StitchCommand::ColorChange | StitchCommand::End)
+        };
+        let incremental = match patches.as_slice() {
+            [patch] => {
+                let old = &self.stitches[patch.start..patch.start + patch.replaced];
+                patch.start > 0
+                    && !old
+                        .iter()
+                        .chain(&patch.stitches)
+                        .any(|s| structural(s.command))
+                    && self.incremental_block(anchor(patch)).is_some()
+            }
+            _ => false,
+        };
+
+        if !incremental {
+            let undo = apply_patches(&mut self.stitches, patches);
+            self.refresh_all();
+            return undo;
+        }
+
+        let patch = &patches[0];
+        let (start, replaced, inserted) = (patch.start, patch.replaced, patch.stitches.len());
+        let block_anchor = anchor(patch);
+        let old_bounds = self.bounds.clone();
+        let removed: Vec<(f64, f64)> = self.stitches[start..start + replaced]
+            .iter()
+            .map(|s| (s.x, s.y))
+            .collect();
+        // The record after the patch is measured from a new predecessor
+        let before = self.run_statistics(start, start + replaced + 1);
+        let undo = apply_patches(&mut self.stitches, patches);
+        let after = self.run_statistics(start, start + inserted + 1);
+
+        // Bounds only need a rescan when a removed point sat on the edge
+        let inside = |b: &Bounds| {
+            removed
+                .iter()
+                .all(|&(x, y)| b.min_x < x && x < b.max_x && b.min_y < y && y < b.max_y)
+        };
+        match old_bounds {
+            Some(mut bounds) if inside(&bounds) => {
+                for stitch in &self.stitches[start..start + inserted] {
+                    bounds.update(stitch.x, stitch.y);
+                }
+                self.bounds = Some(bounds);
+            }
+            _ => self.calculate_bounds(),
+        }
+
+        let stats = &mut self.statistics;
+        let on_extreme = before.measured > 0
+            && (before.min_mm <= stats.min_stitch_length_mm
+                || before.max_mm >= stats.max_stitch_length_mm);
+        let measured_before =
+            stats.real_stitch_count - u32::from(self.stitches[0].command == StitchCommand::Stitch);
+        if on_extreme || measured_before == before.measured {
+            self.calculate_statistics();
+        } else {
+            stats.real_stitch_count = stats.real_stitch_count - before.stitches + after.stitches;
+            stats.jump_count = stats.jump_count - before.jumps + after.jumps;
+            stats.trim_count = stats.trim_count - before.trims + after.trims;
+            stats.sequin_count = stats.sequin_count - before.sequins + after.sequins;
+            stats.total_thread_length_mm += after.length_mm - before.length_mm;
+            if after.measured > 0 {
+                stats.min_stitch_length_mm = stats.min_stitch_length_mm.min(after.min_mm);
+                stats.max_stitch_length_mm = stats.max_stitch_length_mm.max(after.max_mm);
+            }
+            stats.update_totals(measured_before - before.measured + after.measured);
+        }
+
+        self.metadata.stitch_count = self
+            .metadata
+            .stitch_count
+            .map(|_| self.stitches.len() as u32);
+        self.update_block(block_anchor, inserted as isize - replaced as isize);
+        undo
+    }
+
+    /// Index of the color block containing record `anchor` when it can be
+    /// updated on its own: the blocks are current, and it is not a last
+    /// block whose stitches decide whether a trailing ColorChange is folded in
+    fn incremental_block(&self, anchor: usize) -> Option<usize> {
+        let blocks = &self.color_blocks;
+        if blocks.last()?.end != self.stitches.len() {
+            return None;
+        }
+        let block = blocks
+            .partition_point(|b| b.end <= anchor)
+            .min(blocks.len() - 1);
+        let range = &blocks[block];
+        let folded = self.stitches[range.start + 1..range.end]
+            .iter()
+            .any(|s| s.command == StitchCommand::ColorChange);
+        let last = block + 1 == blocks.len();
+        (!last || (block == 0 && !folded)).then_some(block)
+    }
+
+    /// Resize the block containing record `anchor` by `delta` records,
+    /// shift the ones after it and recount it
+    fn update_block(&mut self, anchor: usize, delta: isize) {
+        let block = self
+            .color_blocks
+            .partition_point(|b| b.end <= anchor)
+            .min(self.color_blocks.len() - 1);
+        for (index, later) in self.color_blocks.iter_mut().enumerate().skip(block) {
+            if index > block {
+                later.start = later.start.saturating_add_signed(delta);
+            }
+            later.end = later.end.saturating_add_signed(delta);
+        }
+        let (start, end) = (self.color_blocks[block].start, self.color_blocks[block].end);
+        let statistics = self.block_statistics(start, end);
+        let range = &mut self.color_blocks[block];
+        range.stitch_count = statistics.stitch_count;
+        range.bounds = statistics.bounds.clone();
+        range.statistics = statistics;
+    }
+
+    fn refresh_all(&mut self) {
+        self.color_changes = self
+            .stitches
+            .iter()
+            .filter(|s| s.command == StitchCommand::ColorChange)
+            .count() as u32;
+        let metadata = &mut self.metadata;
+        metadata.stitch_count = metadata.stitch_count.map(|_| self.stitches.len() as u32);
+        metadata.color_count = metadata.color_count.map(|_| self.color_changes);
+        self.calculate_bounds();
+        self.calculate_statistics();
+        self.calculate_color_blocks();
+    }
+
+    fn check_index(&self, index: usize) -> Result<(), EditError> {
+        if index < self.stitches.len() {
+            Ok(())
+        } else {
+            Err(EditError::NoSuchStitch { index })
+        }
+    }
+
+    fn is_terminal_end(&self, index: usize) -> bool {
+        index + 1 == self.stitches.len() && self.stitches[index].command == StitchCommand::End
+    }
+
+    /// The block a ColorChange at `index` opens, if it opens one
+    fn block_opened_at(&self, index: usize) -> Option<usize> {
+        self.color_blocks
+            .iter()
+            .position(|b| b.start == index && b.index > 0)
+    }
+
+    /// Drop the thread colors of blocks opened by ColorChange records that
+    /// are about to go, warning about each merge
+    fn merge_blocks_at(&mut self, indices: &[usize]) -> Vec<EditWarning> {
+        let mut merged: Vec<(usize, usize)> = indices
+            .iter()
+            .filter(|&&index| self.stitches[index].command == StitchCommand::ColorChange)
+            .filter_map(|&index| self.block_opened_at(index).map(|block| (index, block)))
+            .collect();
+        merged.sort_by_key(|&(_, block)| std::cmp::Reverse(block));
+        for &(_, block) in &merged {
+            if block < self.metadata.thread_colors.len() {
+                self.metadata.thread_colors.remove(block);
+            }
+        }
+        merged.reverse();
+        merged
+            .into_iter()
+            .map(|(index, block)| EditWarning::BlocksMerged { index, block })
+            .collect()
+    }
+
+    /// Give a block opened by a new ColorChange at `index` its thread
+    /// color: `color` when given, otherwise that of the block it was split
+    /// from. Nothing is assigned when the blocks before it have no colors.
+    fn split_block_at(&mut self, index: usize, color: Option<ThreadColor>) {
+        let Some(block) = self.block_opened_at(index) else {
+            return;
+        };
+        let colors = &mut self.metadata.thread_colors;
+        if block <= colors.len() {
+            let color = color.unwrap_or_else(|| colors[block - 1].clone());
+            colors.insert(block, color);
+            self.calculate_color_blocks();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::Stitch;
+    use StitchCommand::*;
+
+    /// Two blocks of ten records zigzagging right with growing steps, so
+    /// every stitch length differs
+    pub(super) fn two_blocks() -> Pattern {
+        let mut pattern = Pattern::new();
+        for i in 0..20 {
+            let command = if i == 10 { ColorChange } else { Stitch };
+            let x = (i * (i + 1) / 2) as f64;
+            pattern.add_stitch(x, (i % 3) as f64 * 4.0, command);
+        }
+        pattern.add_stitch(190.0, 4.0, End);
+        pattern.metadata.thread_colors =
+            vec![ThreadColor::new([255, 0, 0]), ThreadColor::new([0, 0, 255])];
+        pattern.refresh_all();
+        pattern
+    }
+
+    /// Everything an edit keeps current, serialized for comparison
+    fn derived(pattern: &Pattern) -> serde_json::Value {
+        serde_json::json!({
+            "bounds": pattern.bounds,
+            "color_blocks": pattern.color_blocks,
+            "color_changes": pattern.color_changes,
+            "real": pattern.statistics.real_stitch_count,
+            "jumps": pattern.statistics.jump_count,
+            "min": pattern.statistics.min_stitch_length_mm,
+            "max": pattern.statistics.max_stitch_length_mm,
+        })
+    }
+
+    /// Incremental bookkeeping must agree with a full recalculation
+    pub(super) fn assert_consistent(pattern: &Pattern) {
+        let mut fresh = pattern.clone();
+        fresh.refresh_all();
+        assert_eq!(derived(pattern), derived(&fresh));
+        let total = pattern.statistics.total_thread_length_mm;
+        assert!((total - fresh.statistics.total_thread_length_mm).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_patches_round_trip() {
+        let pattern = two_blocks();
+        let mut stitches = pattern.stitches.clone();
+        let patches = vec![
+            StitchPatch {
+                start: 2,
+                replaced: 3,
+                stitches: Vec::new(),
+            },
+            StitchPatch {
+                start: 8,
+                replaced: 0,
+                stitches: vec![Stitch::new(50.0, 50.0, Move); 2],
+            },
+            StitchPatch {
+                start: 15,
+                replaced: 1,
+                stitches: vec![Stitch::new(-5.0, 0.0, Trim)],
+            },
+        ];
+        let undo = apply_patches(&mut stitches, patches);
+        assert_eq!(stitches.len(), pattern.stitches.len() - 1);
+        assert_eq!(stitches[5], Stitch::new(50.0, 50.0, Move));
+        assert_eq!(stitches[14].command, Trim);
+
+        apply_patches(&mut stitches, undo);
+        assert_eq!(stitches, pattern.stitches);
+    }
+}
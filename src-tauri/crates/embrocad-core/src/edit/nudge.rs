@@ -0,0 +1,248 @@
+// nudge.rs - Moving or snapping selected records while keeping the stitching connected
+
+use crate::dst::{Pattern, Stitch, StitchCommand, MAX_COORDINATE, UNITS_PER_MM};
+use crate::edit::{EditError, Selection, StitchEdit, StitchPatch};
+use crate::generate::MAX_STITCH_MM;
+
+/// Records that put the needle somewhere, as opposed to ones that act
+/// wherever it already is
+fn positional(command: StitchCommand) -> bool {
+    matches!(
+        command,
+        StitchCommand::Stitch | StitchCommand::Move | StitchCommand::SequinEject
+    )
+}
+
+impl Pattern {
+    /// Move the selected records by (`dx`, `dy`) in 0.1mm units
+    ///
+    /// Stitches into and out of the selection stretch to follow it. One
+    /// stretched past the longest stitch a machine can make is instead
+    /// reached with a jump, keeping its own length. Trims and color changes
+    /// stay with the needle wherever it ends up.
+    pub fn nudge(
+        &mut self,
+        selection: &Selection,
+        dx: f64,
+        dy: f64,
+    ) -> Result<StitchEdit, EditError> {
+        self.shift_selected(selection, |(x, y)| (x + dx, y + dy))
+    }
+
+    /// Move each selected record to the nearest point of a square grid of
+    /// `grid` (0.1mm) anchored at the origin, connecting as `nudge` does
+    pub fn snap_to_grid(
+        &mut self,
+        selection: &Selection,
+        grid: f64,
+    ) -> Result<StitchEdit, EditError> {
+        if !(grid.is_finite() && grid > 0.0) {
+            return Err(EditError::InvalidGrid);
+        }
+        let snap = |value: f64| (value / grid).round() * grid;
+        self.shift_selected(selection, |(x, y)| (snap(x), snap(y)))
+    }
+
+    /// Which records `selection` covers; a rectangle covers the records
+    /// whose point lies inside it
+    fn selected_records(&self, selection: &Selection) -> Result<Vec<bool>, EditError> {
+        let mut selected = vec![false; self.stitches.len()];
+        match selection {
+            Selection::StitchRange { start, end } => {
+                if start >= end {
+                    return Err(EditError::EmptySelection);
+                }
+                self.check_index(end - 1)?;
+                selected[*start..*end].fill(true);
+            }
+            Selection::Blocks { blocks } => {
+                for &block in blocks {
+                    let range = self
+                        .color_blocks
+                        .get(block)
+                        .ok_or(EditError::NoSuchBlock { block })?;
+                    selected[range.start..range.end].fill(true);
+                }
+            }
+            Selection::Rect(rect) => {
+                for (selected, stitch) in selected.iter_mut().zip(&self.stitches) {
+                    *selected = rect.min_x <= stitch.x
+                        && stitch.x <= rect.max_x
+                        && rect.min_y <= stitch.y
+                        && stitch.y <= rect.max_y;
+                }
+            }
+        }
+        Ok(selected)
+    }
+
+    /// Move the selected points with `to`, then reconnect the stitching
+    fn shift_selected(
+        &mut self,
+        selection: &Selection,
+        to: impl Fn((f64, f64)) -> (f64, f64),
+    ) -> Result<StitchEdit, EditError> {
+        let selected = self.selected_records(selection)?;
+        let moves = |index: usize| selected[index] && positional(self.stitches[index].command);
+        if !(0..self.stitches.len()).any(moves) {
+            return Err(EditError::EmptySelection);
+        }
+
+        // Records acting where the needle is follow the record before them
+        let mut placed: Vec<(f64, f64)> = Vec::with_capacity(self.stitches.len());
+        for (index, stitch) in self.stitches.iter().enumerate() {
+            let here = (stitch.x, stitch.y);
+            let follows = index > 0 && !positional(stitch.command) && {
+                let before = &self.stitches[index - 1];
+                here == (before.x, before.y)
+            };
+            let (x, y) = if moves(index) {
+                to(here)
+            } else if follows {
+                placed[index - 1]
+            } else {
+                here
+            };
+            if !(x.abs() <= MAX_COORDINATE && y.abs() <= MAX_COORDINATE) {
+                return Err(EditError::OutOfRange { x, y });
+            }
+            placed.push((x, y));
+        }
+
+        let longest = MAX_STITCH_MM * UNITS_PER_MM;
+        let length = |a: (f64, f64), b: (f64, f64)| (b.0 - a.0).hypot(b.1 - a.1);
+        let mut patches: Vec<StitchPatch> = Vec::new();
+        for (index, stitch) in self.stitches.iter().enumerate() {
+            let mut records = Vec::new();
+            if stitch.command == StitchCommand::Stitch && index > 0 {
+                let from = (self.stitches[index - 1].x, self.stitches[index - 1].y);
+                let was = length(from, (stitch.x, stitch.y));
+                let now = length(placed[index - 1], placed[index]);
+                if now > longest && now > was {
+                    // Sewn from where it started, moved along with it
+                    let start = if moves(index) { to(from) } else { from };
+                    records.push(Stitch::new(start.0, start.1, StitchCommand::Move));
+                }
+            }
+            let (x, y) = placed[index];
+            if records.is_empty() && (x, y) == (stitch.x, stitch.y) {
+                continue;
+            }
+            records.push(Stitch::new(x, y, stitch.command));
+            match patches.last_mut() {
+                Some(run) if run.start + run.replaced == index => {
+                    run.replaced += 1;
+                    run.stitches.extend(records);
+                }
+                _ => patches.push(StitchPatch {
+                    start: index,
+                    replaced: 1,
+                    stitches: records,
+                }),
+            }
+        }
+
+        Ok(StitchEdit {
+            warnings: Vec::new(),
+            undo: self.apply_patches(patches),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::{Stitch, ThreadColor};
+    use crate::edit::tests::assert_consistent;
+    use StitchCommand::*;
+
+    /// Two rows of five stitches 2mm apart, the second 10mm below the first
+    fn rows() -> Pattern {
+        let mut pattern = Pattern::new();
+        for i in 0..5 {
+            pattern.add_stitch(i as f64 * 20.0, 0.0, Stitch);
+        }
+        pattern.add_stitch(80.0, 0.0, Trim);
+        pattern.add_stitch(80.0, 0.0, ColorChange);
+        pattern.add_stitch(0.0, 100.0, Move);
+        for i in 0..5 {
+            pattern.add_stitch(i as f64 * 20.0, 100.0, Stitch);
+        }
+        pattern.add_stitch(80.0, 100.0, End);
+        pattern.metadata.thread_colors =
+            vec![ThreadColor::new([255, 0, 0]), ThreadColor::new([0, 0, 255])];
+        pattern.refresh_all();
+        pattern
+    }
+
+    #[test]
+    fn test_nudge_whole_block() {
+        let mut pattern = rows();
+        let jumps = pattern.statistics.jump_count;
+        let edit = pattern
+            .nudge(&Selection::Blocks { blocks: vec![1] }, 500.0, 0.0)
+            .unwrap();
+
+        // The block's own jump takes it there; nothing is inserted
+        assert_eq!(pattern.stitches.len(), 14);
+        assert_eq!(pattern.statistics.jump_count, jumps);
+        assert_eq!(pattern.stitches[6], Stitch::new(80.0, 0.0, ColorChange));
+        assert_eq!(pattern.stitches[7], Stitch::new(500.0, 100.0, Move));
+        assert_eq!(pattern.stitches[13], Stitch::new(580.0, 100.0, End));
+        assert_eq!(pattern.bounds.as_ref().unwrap().max_x, 580.0);
+        assert_consistent(&pattern);
+
+        pattern.apply_patches(edit.undo);
+        assert_eq!(pattern.stitches, rows().stitches);
+    }
+
+    #[test]
+    fn test_nudge_mid_block_run() {
+        // A small step stretches the stitches either side of the run
+        let mut pattern = rows();
+        let run = Selection::StitchRange { start: 1, end: 3 };
+        pattern.nudge(&run, 0.0, 10.0).unwrap();
+        assert_eq!(pattern.stitches.len(), 14);
+        assert_eq!(pattern.stitches[1], Stitch::new(20.0, 10.0, Stitch));
+        assert_eq!(pattern.stitches[3], Stitch::new(60.0, 0.0, Stitch));
+        assert_consistent(&pattern);
+
+        // Past the longest stitch, both ends are bridged with jumps
+        let mut pattern = rows();
+        let jumps = pattern.statistics.jump_count;
+        let real = pattern.statistics.real_stitch_count;
+        pattern.nudge(&run, 0.0, 200.0).unwrap();
+        assert_eq!(pattern.stitches.len(), 16);
+        assert_eq!(pattern.stitches[1], Stitch::new(0.0, 200.0, Move));
+        assert_eq!(pattern.stitches[2], Stitch::new(20.0, 200.0, Stitch));
+        assert_eq!(pattern.stitches[4], Stitch::new(40.0, 0.0, Move));
+        assert_eq!(pattern.statistics.jump_count, jumps + 2);
+        assert_eq!(pattern.statistics.real_stitch_count, real);
+        assert_consistent(&pattern);
+    }
+
+    #[test]
+    fn test_snap_to_grid() {
+        let mut pattern = rows();
+        let first_row = Selection::StitchRange { start: 0, end: 5 };
+        pattern.snap_to_grid(&first_row, 30.0).unwrap();
+        let xs: Vec<f64> = pattern.stitches[..5].iter().map(|s| s.x).collect();
+        assert_eq!(xs, vec![0.0, 30.0, 30.0, 60.0, 90.0]);
+        // The trim and color change follow the needle to the last point
+        assert_eq!(pattern.stitches[6], Stitch::new(90.0, 0.0, ColorChange));
+        assert_consistent(&pattern);
+
+        let mut pattern = rows();
+        let error = pattern.snap_to_grid(&first_row, 0.0).err();
+        assert_eq!(error, Some(EditError::InvalidGrid));
+        let error = pattern
+            .nudge(&Selection::StitchRange { start: 5, end: 7 }, 10.0, 0.0)
+            .err();
+        assert_eq!(error, Some(EditError::EmptySelection));
+        assert!(matches!(
+            pattern.nudge(&first_row, MAX_COORDINATE, 0.0),
+            Err(EditError::OutOfRange { .. })
+        ));
+        assert_eq!(pattern.stitches, rows().stitches);
+    }
+}
@@ -0,0 +1,133 @@
+// paste.rs - Sewing a copied fragment after the rest of a design
+
+use crate::dst::{Pattern, Stitch, StitchCommand, MAX_COORDINATE};
+use crate::edit::{EditError, StitchEdit, StitchPatch};
+
+impl Pattern {
+    /// Sew `fragment` shifted by (`offset_x`, `offset_y`) in 0.1mm units
+    /// after everything else, before the closing End
+    ///
+    /// As with `append`, the seam gets a Trim, a ColorChange when
+    /// `as_new_block` is set, and a Move to the first pasted record.
+    /// Without a ColorChange the fragment's first block is sewn in the
+    /// current thread and its thread color is dropped.
+    pub fn paste(
+        &mut self,
+        fragment: &Pattern,
+        offset_x: f64,
+        offset_y: f64,
+        as_new_block: bool,
+    ) -> Result<StitchEdit, EditError> {
+        let incoming: Vec<Stitch> = fragment
+            .stitches
+            .iter()
+            .filter(|s| s.command != StitchCommand::End)
+            .map(|s| Stitch::new(s.x + offset_x, s.y + offset_y, s.command))
+            .collect();
+        if !incoming.iter().any(|s| {
+            matches!(
+                s.command,
+                StitchCommand::Stitch | StitchCommand::SequinEject
+            )
+        }) {
+            return Err(EditError::EmptySelection);
+        }
+        if let Some(s) = incoming
+            .iter()
+            .find(|s| !(s.x.abs() <= MAX_COORDINATE && s.y.abs() <= MAX_COORDINATE))
+        {
+            return Err(EditError::OutOfRange { x: s.x, y: s.y });
+        }
+
+        let has_end = self
+            .stitches
+            .last()
+            .is_some_and(|s| s.command == StitchCommand::End);
+        let start = self.stitches.len() - usize::from(has_end);
+        let mut inserted = Vec::with_capacity(incoming.len() + 4);
+        let mut colors = fragment.metadata.thread_colors.iter().cloned();
+        if let Some(last) = self.stitches[..start].last() {
+            let first = &incoming[0];
+            inserted.push(Stitch::new(last.x, last.y, StitchCommand::Trim));
+            if as_new_block {
+                inserted.push(Stitch::new(last.x, last.y, StitchCommand::ColorChange));
+            } else {
+                colors.next();
+            }
+            inserted.push(Stitch::new(first.x, first.y, StitchCommand::Move));
+        }
+        inserted.extend(incoming);
+        if has_end {
+            let last = inserted[inserted.len() - 1].clone();
+            inserted.push(Stitch::new(last.x, last.y, StitchCommand::End));
+        }
+
+        self.metadata.thread_colors.extend(colors);
+        Ok(StitchEdit {
+            warnings: Vec::new(),
+            undo: self.apply_patches(vec![StitchPatch {
+                start,
+                replaced: usize::from(has_end),
+                stitches: inserted,
+            }]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::tests::{assert_consistent, two_blocks};
+    use crate::edit::Selection;
+    use StitchCommand::{ColorChange, End, Move, Trim};
+
+    #[test]
+    fn test_paste_copy_of_block() {
+        let mut pattern = two_blocks();
+        let copy = pattern
+            .extract(&Selection::Blocks { blocks: vec![1] }, false)
+            .unwrap();
+        let (records, sewn) = (pattern.stitches.len(), pattern.statistics.real_stitch_count);
+        let copied = copy.stitches.len() - 1;
+
+        let edit = pattern.paste(&copy, 0.0, 100.0, true).unwrap();
+        // Trim, ColorChange and Move at the seam, then the copy and End
+        assert_eq!(pattern.stitches.len(), records + 3 + copied);
+        let seam = &pattern.stitches[records - 1..records + 2];
+        let commands: Vec<_> = seam.iter().map(|s| s.command).collect();
+        assert_eq!(commands, vec![Trim, ColorChange, Move]);
+        assert_eq!((seam[0].x, seam[0].y), (190.0, 4.0));
+        assert_eq!(pattern.stitches.last().unwrap().command, End);
+        assert_eq!(pattern.color_blocks.len(), 3);
+        assert_eq!(pattern.metadata.thread_colors.len(), 3);
+        assert_eq!(pattern.metadata.thread_colors[2].rgb, [0, 0, 255]);
+        assert_eq!(pattern.statistics.real_stitch_count, sewn + 9);
+        assert_consistent(&pattern);
+
+        pattern.apply_patches(edit.undo);
+        assert_eq!(pattern.stitches, two_blocks().stitches);
+
+        // Into the current block: the copy's color is not added
+        pattern.paste(&copy, 0.0, 100.0, false).unwrap();
+        assert_eq!(pattern.color_blocks.len(), 2);
+        assert_eq!(pattern.stitches.len(), records + 2 + copied);
+        assert_consistent(&pattern);
+    }
+
+    #[test]
+    fn test_paste_refuses_bad_fragments() {
+        let mut pattern = two_blocks();
+        let mut empty = Pattern::new();
+        empty.add_stitch(0.0, 0.0, End);
+        assert_eq!(
+            pattern.paste(&empty, 0.0, 0.0, false).err(),
+            Some(EditError::EmptySelection)
+        );
+        let copy = two_blocks();
+        assert!(matches!(
+            pattern.paste(&copy, MAX_COORDINATE, 0.0, false),
+            Err(EditError::OutOfRange { .. })
+        ));
+        assert_eq!(pattern.stitches, two_blocks().stitches);
+    }
+}
@@ -0,0 +1,233 @@
+// stitches.rs - Deleting, moving, inserting and retyping single records
+
+use crate::dst::{Pattern, Stitch, StitchCommand, MAX_COORDINATE};
+use crate::edit::{EditError, StitchEdit, StitchPatch};
+
+impl Pattern {
+    /// Remove the records at `indices`; the closing End cannot be removed
+    pub fn delete_stitches(&mut self, indices: &[usize]) -> Result<StitchEdit, EditError> {
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        for &index in &indices {
+            self.check_index(index)?;
+            if self.is_terminal_end(index) {
+                return Err(EditError::EndNotLast);
+            }
+        }
+
+        let warnings = self.merge_blocks_at(&indices);
+        let mut patches: Vec<StitchPatch> = Vec::new();
+        for index in indices {
+            match patches.last_mut() {
+                Some(run) if run.start + run.replaced == index => run.replaced += 1,
+                _ => patches.push(StitchPatch {
+                    start: index,
+                    replaced: 1,
+                    stitches: Vec::new(),
+                }),
+            }
+        }
+
+        Ok(StitchEdit {
+            warnings,
+            undo: self.apply_patches(patches),
+        })
+    }
+
+    /// Move the record at `index` to (`x`, `y`) in 0.1mm units
+    pub fn move_stitch(&mut self, index: usize, x: f64, y: f64) -> Result<StitchEdit, EditError> {
+        self.check_index(index)?;
+        if !(x.abs() <= MAX_COORDINATE && y.abs() <= MAX_COORDINATE) {
+            return Err(EditError::OutOfRange { x, y });
+        }
+
+        let command = self.stitches[index].command;
+        Ok(StitchEdit {
+            warnings: Vec::new(),
+            undo: self.apply_patches(vec![StitchPatch {
+                start: index,
+                replaced: 1,
+                stitches: vec![Stitch::new(x, y, command)],
+            }]),
+        })
+    }
+
+    /// Insert a record after `after`, or first when `after` is None
+    ///
+    /// End may only be added as the last record of a pattern without one.
+    pub fn insert_stitch(
+        &mut self,
+        after: Option<usize>,
+        x: f64,
+        y: f64,
+        command: StitchCommand,
+    ) -> Result<StitchEdit, EditError> {
+        if let Some(after) = after {
+            self.check_index(after)?;
+            if self.stitches[after].command == StitchCommand::End {
+                return Err(EditError::EndNotLast);
+            }
+        }
+        let at = after.map_or(0, |after| after + 1);
+        if command == StitchCommand::End && at != self.stitches.len() {
+            return Err(EditError::EndNotLast);
+        }
+        if !(x.abs() <= MAX_COORDINATE && y.abs() <= MAX_COORDINATE) {
+            return Err(EditError::OutOfRange { x, y });
+        }
+
+        let undo = self.apply_patches(vec![StitchPatch {
+            start: at,
+            replaced: 0,
+            stitches: vec![Stitch::new(x, y, command)],
+        }]);
+        if command == StitchCommand::ColorChange {
+            self.split_block_at(at, None);
+        }
+        Ok(StitchEdit {
+            warnings: Vec::new(),
+            undo,
+        })
+    }
+
+    /// Change the command of the record at `index`
+    ///
+    /// The closing End keeps its command, and only the last record may
+    /// become End.
+    pub fn change_command(
+        &mut self,
+        index: usize,
+        command: StitchCommand,
+    ) -> Result<StitchEdit, EditError> {
+        self.check_index(index)?;
+        let old = self.stitches[index].clone();
+        if old.command == command {
+            return Ok(StitchEdit::default());
+        }
+        if self.is_terminal_end(index)
+            || (command == StitchCommand::End && index + 1 != self.stitches.len())
+        {
+            return Err(EditError::EndNotLast);
+        }
+
+        let warnings = self.merge_blocks_at(&[index]);
+        let undo = self.apply_patches(vec![StitchPatch {
+            start: index,
+            replaced: 1,
+            stitches: vec![Stitch::new(old.x, old.y, command)],
+        }]);
+        if command == StitchCommand::ColorChange {
+            self.split_block_at(index, None);
+        }
+        Ok(StitchEdit { warnings, undo })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::Stitch;
+    use crate::edit::tests::{assert_consistent, two_blocks};
+    use crate::edit::EditWarning;
+    use StitchCommand::*;
+
+    #[test]
+    fn test_move_updates_incrementally() {
+        let mut pattern = two_blocks();
+        pattern.move_stitch(4, 10.0, 30.0).unwrap();
+        assert_eq!(pattern.bounds.as_ref().unwrap().max_y, 30.0);
+        assert_consistent(&pattern);
+
+        // Moving a point off the edge needs a rescan of the bounds
+        pattern.move_stitch(4, 10.0, 0.0).unwrap();
+        assert_eq!(pattern.bounds.as_ref().unwrap().max_y, 8.0);
+        assert_consistent(&pattern);
+
+        pattern.move_stitch(15, 120.0, 2.0).unwrap();
+        assert_consistent(&pattern);
+        assert_eq!(
+            pattern.move_stitch(3, MAX_COORDINATE + 1.0, 0.0),
+            Err(EditError::OutOfRange {
+                x: MAX_COORDINATE + 1.0,
+                y: 0.0
+            })
+        );
+    }
+
+    #[test]
+    fn test_insert_and_change_command() {
+        let mut pattern = two_blocks();
+        pattern.insert_stitch(Some(3), 8.0, 6.0, Move).unwrap();
+        assert_eq!(pattern.stitches[4], Stitch::new(8.0, 6.0, Move));
+        assert_eq!(pattern.statistics.jump_count, 1);
+        assert_eq!(pattern.color_blocks[1].start, 11);
+        assert_consistent(&pattern);
+
+        pattern.change_command(4, Trim).unwrap();
+        assert_eq!(pattern.statistics.trim_count, 1);
+        assert_consistent(&pattern);
+
+        // A new color change splits a block and copies its thread color
+        pattern.change_command(6, ColorChange).unwrap();
+        assert_eq!(pattern.color_changes, 2);
+        assert_eq!(pattern.color_blocks.len(), 3);
+        assert_eq!(pattern.metadata.thread_colors.len(), 3);
+        assert_eq!(
+            pattern.color_blocks[1].color.as_ref().unwrap().rgb,
+            [255, 0, 0]
+        );
+        assert_consistent(&pattern);
+    }
+
+    #[test]
+    fn test_end_stays_terminal() {
+        let mut pattern = two_blocks();
+        let end = pattern.stitches.len() - 1;
+        assert_eq!(
+            pattern.delete_stitches(&[3, end]),
+            Err(EditError::EndNotLast)
+        );
+        assert_eq!(
+            pattern.change_command(end, Stitch),
+            Err(EditError::EndNotLast)
+        );
+        assert_eq!(pattern.change_command(5, End), Err(EditError::EndNotLast));
+        assert_eq!(
+            pattern.insert_stitch(Some(end), 0.0, 0.0, Stitch),
+            Err(EditError::EndNotLast)
+        );
+        assert_eq!(
+            pattern.insert_stitch(Some(5), 0.0, 0.0, End),
+            Err(EditError::EndNotLast)
+        );
+        assert_eq!(
+            pattern.delete_stitches(&[end + 1]),
+            Err(EditError::NoSuchStitch { index: end + 1 })
+        );
+        assert_eq!(pattern.stitches.len(), 21);
+    }
+
+    #[test]
+    fn test_deleting_color_change_merges_blocks() {
+        let mut pattern = two_blocks();
+        let original = pattern.clone();
+        let edit = pattern.delete_stitches(&[2, 3, 10, 12]).unwrap();
+
+        assert_eq!(
+            edit.warnings,
+            vec![EditWarning::BlocksMerged {
+                index: 10,
+                block: 1
+            }]
+        );
+        assert_eq!(pattern.stitches.len(), 17);
+        assert_eq!(pattern.color_changes, 0);
+        assert_eq!(pattern.color_blocks.len(), 1);
+        assert_eq!(pattern.metadata.thread_colors.len(), 1);
+        assert_consistent(&pattern);
+
+        pattern.apply_patches(edit.undo);
+        assert_eq!(pattern.stitches, original.stitches);
+    }
+}
@@ -0,0 +1,7 @@
+// mod.rs - EXP module exports for the Melco/Bernina format parser and writer
+
+mod parser;
+mod writer;
+
+pub use parser::parse_exp;
+pub use writer::write_exp;
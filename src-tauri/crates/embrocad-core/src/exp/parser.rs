@@ -0,0 +1,186 @@
+// parser.rs - Melco/Bernina EXP embroidery file format parser
+
+use crate::dst::{Pattern, StitchCommand};
+
+/// Escape byte introducing a control record
+const ESCAPE: u8 = 0x80;
+
+/// Control codes following the escape byte
+const CONTROL_COLOR_CHANGE: u8 = 0x01;
+const CONTROL_STITCH: u8 = 0x02;
+const CONTROL_JUMP: u8 = 0x04;
+const CONTROL_TRIM: u8 = 0x80;
+
+/// Error type for EXP parsing
+#[derive(Debug, thiserror::Error)]
+pub enum ExpError {
+    #[error("Invalid EXP file: no stitch data")]
+    InsufficientData,
+}
+
+/// Decode a signed byte displacement pair, inverting Y to match the DST parser
+fn decode_delta(bx: u8, by: u8) -> (f64, f64) {
+    (bx as i8 as f64, -(by as i8 as f64))
+}
+
+/// Parse EXP stitch records from the file
+///
+/// Records are two signed bytes (dx, dy). A record starting with 0x80 is an
+/// escape whose second byte selects the command for the following record:
+/// 0x01 color change, 0x02 stitch, 0x04 jump and 0x80 trim.
+fn parse_stitches(data: &[u8], pattern: &mut Pattern) {
+    let mut records = data.chunks_exact(2);
+
+    let mut current_x = 0.0f64;
+    let mut current_y = 0.0f64;
+
+    while let Some(record) = records.next() {
+        if record[0] != ESCAPE {
+            let (dx, dy) = decode_delta(record[0], record[1]);
+            current_x += dx;
+            current_y += dy;
+            pattern.add_stitch(current_x, current_y, StitchCommand::Stitch);
+            continue;
+        }
+
+        let control = record[1];
+        let Some(operand) = records.next() else {
+            break;
+        };
+        let (dx, dy) = decode_delta(operand[0], operand[1]);
+
+        match control {
+            CONTROL_TRIM => {
+                pattern.add_stitch(current_x, current_y, StitchCommand::Trim);
+            }
+            CONTROL_COLOR_CHANGE => {
+                pattern.add_stitch(current_x, current_y, StitchCommand::ColorChange);
+                if dx != 0.0 || dy != 0.0 {
+                    current_x += dx;
+                    current_y += dy;
+                    pattern.add_stitch(current_x, current_y, StitchCommand::Move);
+                }
+            }
+            CONTROL_JUMP => {
+                current_x += dx;
+                current_y += dy;
+                pattern.add_stitch(current_x, current_y, StitchCommand::Move);
+            }
+            CONTROL_STITCH => {
+                current_x += dx;
+                current_y += dy;
+                pattern.add_stitch(current_x, current_y, StitchCommand::Stitch);
+            }
+            // Unknown escapes carry a displacement; keep the needle position in sync
+            _ => {
+                current_x += dx;
+                current_y += dy;
+                pattern.add_stitch(current_x, current_y, StitchCommand::Move);
+            }
+        }
+    }
+
+    pattern.add_stitch(current_x, current_y, StitchCommand::End);
+}
+
+/// Parse an EXP file from bytes
+///
+/// EXP has no header, so the metadata counts are derived from the decoded stream.
+pub fn parse_exp(data: &[u8]) -> Result<Pattern, ExpError> {
+    if data.len() < 2 {
+        return Err(ExpError::InsufficientData);
+    }
+
+    let mut pattern = Pattern::new();
+
+    parse_stitches(data, &mut pattern);
+
+    pattern.metadata.stitch_count = Some(pattern.stitches.len() as u32);
+    pattern.metadata.color_count = Some(pattern.color_changes);
+
+    // Calculate bounds, statistics and color blocks
+    pattern.calculate_bounds();
+    pattern.calculate_statistics();
+    pattern.calculate_color_blocks();
+
+    Ok(pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoded(pattern: &Pattern) -> Vec<(f64, f64, StitchCommand)> {
+        pattern
+            .stitches
+            .iter()
+            .map(|s| (s.x, s.y, s.command))
+            .collect()
+    }
+
+    #[test]
+    fn test_signed_stitches() {
+        // +10/+10 (Y inverted), then -127/+127
+        let pattern = parse_exp(&[0x0A, 0x0A, 0x81, 0x7F]).unwrap();
+
+        assert_eq!(
+            decoded(&pattern),
+            vec![
+                (10.0, -10.0, StitchCommand::Stitch),
+                (-117.0, -137.0, StitchCommand::Stitch),
+                (-117.0, -137.0, StitchCommand::End),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_control_records() {
+        use StitchCommand::*;
+        let data = [
+            0x05, 0x00, // stitch +5
+            0x80, 0x04, 0x64, 0x00, // jump +100
+            0x80, 0x80, 0x00, 0x00, // trim
+            0x80, 0x01, 0x00, 0x00, // color change in place
+            0x80, 0x01, 0x0A, 0xF6, // color change then move (+10, +10)
+            0x80, 0x02, 0x01, 0x00, // escaped stitch +1
+        ];
+        let pattern = parse_exp(&data).unwrap();
+
+        assert_eq!(
+            decoded(&pattern),
+            vec![
+                (5.0, 0.0, Stitch),
+                (105.0, 0.0, Move),
+                (105.0, 0.0, Trim),
+                (105.0, 0.0, ColorChange),
+                (105.0, 0.0, ColorChange),
+                (115.0, 10.0, Move),
+                (116.0, 10.0, Stitch),
+                (116.0, 10.0, End),
+            ]
+        );
+        assert_eq!(pattern.color_changes, 2);
+        assert_eq!(pattern.statistics.trim_count, 1);
+        assert_eq!(pattern.statistics.jump_count, 2);
+    }
+
+    #[test]
+    fn test_metadata_from_stream() {
+        let data = [0x01, 0x01, 0x80, 0x01, 0x00, 0x00, 0x01, 0x01];
+        let pattern = parse_exp(&data).unwrap();
+
+        assert_eq!(pattern.metadata.stitch_count, Some(4));
+        assert_eq!(pattern.metadata.color_count, Some(1));
+        assert_eq!(pattern.color_blocks.len(), 2);
+    }
+
+    #[test]
+    fn test_truncated_escape() {
+        let pattern = parse_exp(&[0x01, 0x00, 0x80, 0x04]).unwrap();
+        assert_eq!(pattern.stitches.len(), 2);
+        assert!(matches!(
+            parse_exp(&[0x01]),
+            Err(ExpError::InsufficientData)
+        ));
+    }
+}
@@ -0,0 +1,124 @@
+// writer.rs - Melco/Bernina EXP writer with escape-coded control records
+
+use crate::dst::{Pattern, StitchCommand, UNITS_PER_MM};
+
+/// Escape byte introducing a control record
+const ESCAPE: u8 = 0x80;
+
+/// Control codes following the escape byte
+const CONTROL_COLOR_CHANGE: u8 = 0x01;
+const CONTROL_JUMP: u8 = 0x04;
+const CONTROL_TRIM: u8 = 0x80;
+
+/// Largest displacement per axis; -128 would read as the escape byte
+pub const MAX_EXP_DISPLACEMENT: i32 = 127;
+
+/// Encode a displacement pair as signed bytes, inverting Y to match the parser
+fn encode_delta(dx: i32, dy: i32) -> [u8; 2] {
+    [dx as i8 as u8, (-dy) as i8 as u8]
+}
+
+/// Append jump records covering (dx, dy), each within the byte range
+fn push_jumps(out: &mut Vec<u8>, mut dx: i32, mut dy: i32) {
+    while dx != 0 || dy != 0 {
+        let step_x = dx.clamp(-MAX_EXP_DISPLACEMENT, MAX_EXP_DISPLACEMENT);
+        let step_y = dy.clamp(-MAX_EXP_DISPLACEMENT, MAX_EXP_DISPLACEMENT);
+        out.extend_from_slice(&[ESCAPE, CONTROL_JUMP]);
+        out.extend_from_slice(&encode_delta(step_x, step_y));
+        dx -= step_x;
+        dy -= step_y;
+    }
+}
+
+/// Encode a pattern as an EXP file
+///
+/// EXP has no header or end marker. Stitches are plain byte pairs; jumps,
+/// trims and color changes are 0x80 escapes with the command in the second
+/// byte. Long stitches are split into a chain first and long moves into
+/// several jumps, since each record covers at most ±12.7mm per axis.
+pub fn write_exp(pattern: &Pattern) -> Vec<u8> {
+    let mut pattern = pattern.clone();
+    pattern.split_long_stitches(MAX_EXP_DISPLACEMENT as f64 / UNITS_PER_MM);
+
+    let mut out = Vec::with_capacity(pattern.stitches.len() * 2);
+    let (mut current_x, mut current_y) = (0i32, 0i32);
+
+    for stitch in &pattern.stitches {
+        let (x, y) = stitch.units();
+        let (dx, dy) = (x - current_x, y - current_y);
+
+        match stitch.command {
+            StitchCommand::Stitch => {
+                // Rounding can leave a split stitch one unit over the limit
+                let (step_x, step_y) = (
+                    dx.clamp(-MAX_EXP_DISPLACEMENT, MAX_EXP_DISPLACEMENT),
+                    dy.clamp(-MAX_EXP_DISPLACEMENT, MAX_EXP_DISPLACEMENT),
+                );
+                push_jumps(&mut out, dx - step_x, dy - step_y);
+                out.extend_from_slice(&encode_delta(step_x, step_y));
+            }
+            StitchCommand::Move => push_jumps(&mut out, dx, dy),
+            command => {
+                // Controls happen in place, so travel there first
+                push_jumps(&mut out, dx, dy);
+                let control = match command {
+                    StitchCommand::Trim => CONTROL_TRIM,
+                    StitchCommand::ColorChange => CONTROL_COLOR_CHANGE,
+                    _ => continue,
+                };
+                out.extend_from_slice(&[ESCAPE, control, 0x00, 0x00]);
+            }
+        }
+        (current_x, current_y) = (x, y);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exp::parse_exp;
+    use StitchCommand::*;
+
+    #[test]
+    fn test_round_trip_through_parser() {
+        let mut pattern = Pattern::new();
+        for &(x, y, command) in &[
+            (0.0, 0.0, Stitch),
+            (50.0, -30.0, Stitch),
+            (50.0, -30.0, Trim),
+            (-400.0, 250.0, Move),
+            (-400.0, 250.0, ColorChange),
+            (-390.0, 260.0, Stitch),
+            (-200.0, 260.0, Stitch),
+            (-200.0, 260.0, End),
+        ] {
+            pattern.add_stitch(x, y, command);
+        }
+        pattern.calculate_bounds();
+        pattern.calculate_statistics();
+
+        let parsed = parse_exp(&write_exp(&pattern)).unwrap();
+
+        assert_eq!(parsed.bounds, pattern.bounds);
+        // The 53mm move arrives as five jumps and the 19mm stitch as two
+        assert_eq!(parsed.statistics.real_stitch_count, 5);
+        assert_eq!(parsed.statistics.jump_count, 5);
+        assert_eq!(parsed.statistics.trim_count, 1);
+        assert_eq!(parsed.color_changes, 1);
+    }
+
+    #[test]
+    fn test_escaped_controls() {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(10.0, 10.0, Stitch);
+        pattern.add_stitch(10.0, 10.0, Trim);
+        pattern.add_stitch(10.0, 10.0, ColorChange);
+        pattern.add_stitch(10.0, 10.0, End);
+
+        assert_eq!(
+            write_exp(&pattern),
+            vec![0x0A, 0xF6, 0x80, 0x80, 0x00, 0x00, 0x80, 0x01, 0x00, 0x00]
+        );
+    }
+}
@@ -0,0 +1,133 @@
+// animation.rs - Stitch-out animation frames built on the PNG renderer
+
+use super::png::{fit_size, render_png_frames, PngOptions, RenderError};
+use crate::dst::{Pattern, StitchCommand};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// How frame boundaries are chosen along the stitch sequence
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum FrameSpacing {
+    /// The same number of stitches between frames
+    #[default]
+    Even,
+    /// One frame at the end of each color block
+    ColorBlocks,
+}
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AnimationOptions {
+    pub spacing: FrameSpacing,
+    pub png: PngOptions,
+}
+
+/// Record counts at which frames are taken, ascending and without repeats
+///
+/// Only Stitch records count towards the spacing, so a frame never shows
+/// nothing new but a jump.
+pub fn frame_cutoffs(pattern: &Pattern, frames: usize, spacing: FrameSpacing) -> Vec<usize> {
+    let mut cutoffs: Vec<usize> = match spacing {
+        FrameSpacing::Even => {
+            let stitches: Vec<usize> = (0..pattern.stitches.len())
+                .filter(|&i| pattern.stitches[i].command == StitchCommand::Stitch)
+                .collect();
+            (1..=frames)
+                .filter_map(|frame| {
+                    let shown = (stitches.len() * frame).div_ceil(frames);
+                    shown.checked_sub(1).map(|last| stitches[last] + 1)
+                })
+                .collect()
+        }
+        FrameSpacing::ColorBlocks => pattern.color_blocks().iter().map(|b| b.end).collect(),
+    };
+    cutoffs.dedup();
+    cutoffs
+}
+
+/// Render PNG frames of `pattern` stitching out, with the longest side at `size_px`
+///
+/// `progress` is called with (frames done, total) as each frame is encoded.
+pub fn render_animation(
+    pattern: &Pattern,
+    frames: usize,
+    size_px: u32,
+    options: &AnimationOptions,
+    progress: impl FnMut(usize, usize),
+) -> Result<Vec<Vec<u8>>, RenderError> {
+    let cutoffs = frame_cutoffs(pattern, frames, options.spacing);
+    let (width, height) = fit_size(pattern, size_px);
+    render_png_frames(pattern, width, height, &options.png, &cutoffs, progress)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StitchCommand::*;
+
+    fn build(records: &[(f64, f64, StitchCommand)]) -> Pattern {
+        let mut pattern = Pattern::new();
+        for &(x, y, command) in records {
+            pattern.add_stitch(x, y, command);
+        }
+        pattern
+    }
+
+    const TWO_BLOCKS: [(f64, f64, StitchCommand); 9] = [
+        (0.0, 0.0, Stitch),
+        (10.0, 0.0, Stitch),
+        (20.0, 0.0, Stitch),
+        (20.0, 0.0, Trim),
+        (80.0, 40.0, Move),
+        (90.0, 40.0, Move),
+        (90.0, 40.0, ColorChange),
+        (90.0, 50.0, Stitch),
+        (90.0, 60.0, Stitch),
+    ];
+
+    #[test]
+    fn test_jumps_do_not_consume_frames() {
+        let pattern = build(&TWO_BLOCKS);
+
+        // Five stitches over five frames: each frame adds one stitch, and the
+        // jumps ride along with the stitch after them
+        assert_eq!(
+            frame_cutoffs(&pattern, 5, FrameSpacing::Even),
+            vec![1, 2, 3, 8, 9]
+        );
+        // Asking for more frames than stitches doesn't repeat frames
+        assert_eq!(frame_cutoffs(&pattern, 20, FrameSpacing::Even).len(), 5);
+    }
+
+    #[test]
+    fn test_color_block_spacing() {
+        let pattern = build(&TWO_BLOCKS);
+
+        assert_eq!(
+            frame_cutoffs(&pattern, 0, FrameSpacing::ColorBlocks),
+            vec![6, 9]
+        );
+    }
+
+    #[test]
+    fn test_renders_each_frame_with_progress() {
+        let pattern = build(&TWO_BLOCKS);
+        let mut reported = Vec::new();
+
+        let frames = render_animation(
+            &pattern,
+            3,
+            32,
+            &AnimationOptions::default(),
+            |done, total| reported.push((done, total)),
+        )
+        .unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(reported, vec![(1, 3), (2, 3), (3, 3)]);
+        // The finished design differs from the first frame
+        assert_ne!(frames[0], frames[2]);
+    }
+}
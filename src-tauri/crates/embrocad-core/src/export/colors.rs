@@ -0,0 +1,112 @@
+// colors.rs - EDR and COL color files written beside a saved design
+
+use crate::dst::Pattern;
+use crate::export::block_rgb;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Companion color file `save_design` can write next to a DST
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum ColorSidecar {
+    Edr,
+    Col,
+}
+
+impl ColorSidecar {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Edr => "edr",
+            Self::Col => "col",
+        }
+    }
+
+    pub fn write(self, pattern: &Pattern) -> Vec<u8> {
+        match self {
+            Self::Edr => write_edr(pattern),
+            Self::Col => write_col(pattern).into_bytes(),
+        }
+    }
+}
+
+/// The color of every block as drawn, with palette colors for blocks the
+/// design leaves unassigned
+fn block_colors(pattern: &Pattern) -> Vec<[u8; 3]> {
+    (0..pattern.color_blocks().len())
+        .map(|block| block_rgb(pattern, block))
+        .collect()
+}
+
+/// Encode the block colors as an Embird EDR file: red, green, blue and a
+/// zero pad byte per block
+pub fn write_edr(pattern: &Pattern) -> Vec<u8> {
+    block_colors(pattern)
+        .into_iter()
+        .flat_map(|[r, g, b]| [r, g, b, 0])
+        .collect()
+}
+
+/// Encode the block colors as a COL file: the count, then one
+/// `index,red,green,blue` line per block, with CRLF line ends
+pub fn write_col(pattern: &Pattern) -> String {
+    let colors = block_colors(pattern);
+    let mut text = format!("{}\r\n", colors.len());
+    for (index, [r, g, b]) in colors.into_iter().enumerate() {
+        text.push_str(&format!("{},{},{},{}\r\n", index, r, g, b));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::companion::{parse_col, parse_edr};
+    use crate::dst::{StitchCommand, ThreadColor};
+    use crate::export::DEFAULT_PALETTE;
+
+    /// Three blocks, only the first two with declared colors
+    fn three_blocks() -> Pattern {
+        let mut pattern = Pattern::new();
+        for (x, command) in [
+            (0.0, StitchCommand::Stitch),
+            (10.0, StitchCommand::ColorChange),
+            (20.0, StitchCommand::Stitch),
+            (30.0, StitchCommand::ColorChange),
+            (40.0, StitchCommand::Stitch),
+            (40.0, StitchCommand::End),
+        ] {
+            pattern.add_stitch(x, 0.0, command);
+        }
+        pattern.metadata.thread_colors = vec![
+            ThreadColor::new([200, 16, 46]),
+            ThreadColor::new([0, 89, 179]),
+        ];
+        pattern.calculate_color_blocks();
+        pattern
+    }
+
+    #[test]
+    fn test_edr_round_trip() {
+        let pattern = three_blocks();
+        let data = write_edr(&pattern);
+        assert_eq!(data.len(), 12);
+
+        let rgbs: Vec<_> = parse_edr(&data).iter().map(|c| c.rgb).collect();
+        assert_eq!(rgbs, vec![[200, 16, 46], [0, 89, 179], DEFAULT_PALETTE[2]]);
+    }
+
+    #[test]
+    fn test_col_round_trip() {
+        let pattern = three_blocks();
+        let text = write_col(&pattern);
+        assert!(text.starts_with("3\r\n0,200,16,46\r\n"));
+
+        let rgbs: Vec<_> = parse_col(text.as_bytes())
+            .unwrap()
+            .iter()
+            .map(|c| c.rgb)
+            .collect();
+        assert_eq!(rgbs, vec![[200, 16, 46], [0, 89, 179], DEFAULT_PALETTE[2]]);
+    }
+}
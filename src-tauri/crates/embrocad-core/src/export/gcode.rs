@@ -0,0 +1,236 @@
+// gcode.rs - G-code toolpaths of stitch paths for pen plotters
+
+use super::block_rgb;
+use crate::dst::{Bounds, Pattern, StitchCommand, UNITS_PER_MM};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// Where the design sits relative to the machine's 0,0
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum GcodeOrigin {
+    /// Bottom-left corner of the bounds at 0,0, for machines homed at a corner
+    #[default]
+    BottomLeft,
+    /// Bounds center at 0,0
+    Center,
+    /// The design's own origin, usually where the machine started stitching
+    Design,
+}
+
+/// Toolpath options for `write_gcode`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct GcodeOptions {
+    /// Drawing feed rate in mm/min
+    pub feed_rate: f64,
+    /// Pen height while traveling; 0 keeps the pen down throughout
+    pub z_lift_mm: f64,
+    /// Uniform scale applied after placing the origin
+    pub scale: f64,
+    pub origin: GcodeOrigin,
+}
+
+impl Default for GcodeOptions {
+    fn default() -> Self {
+        Self {
+            feed_rate: 1500.0,
+            z_lift_mm: 2.0,
+            scale: 1.0,
+            origin: GcodeOrigin::BottomLeft,
+        }
+    }
+}
+
+/// A millimeter value with at most three decimals
+fn number(value: f64) -> String {
+    let text = format!("{:.3}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" => "0".to_string(),
+        _ => text.to_string(),
+    }
+}
+
+/// Name of a block's thread for the pause comment
+fn thread_name(pattern: &Pattern, block: usize) -> String {
+    let [r, g, b] = block_rgb(pattern, block);
+    let hex = format!("#{:02x}{:02x}{:02x}", r, g, b);
+    let Some(color) = pattern.thread_color_for_block(block) else {
+        return hex;
+    };
+    match (&color.description, &color.catalog_number) {
+        (Some(name), Some(code)) => format!("{} {} ({})", name, code, hex),
+        (Some(name), None) => format!("{} ({})", name, hex),
+        (None, Some(code)) => format!("{} ({})", code, hex),
+        (None, None) => hex,
+    }
+}
+
+/// Render `pattern` as G-code: G1 for stitches, G0 for travel between them
+///
+/// The pen lifts to `z_lift_mm` at each run of Move and Trim records and
+/// drops again at the next stitch. Color changes become an M0 pause naming
+/// the next thread. Y is flipped so the design reads the right way up on a
+/// Y-up machine bed.
+pub fn write_gcode(pattern: &Pattern, options: &GcodeOptions) -> String {
+    let mut bounds = Bounds::new();
+    for stitch in &pattern.stitches {
+        bounds.update(stitch.x, stitch.y);
+    }
+    if pattern.stitches.is_empty() {
+        bounds.update(0.0, 0.0);
+    }
+    // The design point mapped to 0,0; bounds.max_y is the bottom in Y-down coordinates
+    let (origin_x, origin_y) = match options.origin {
+        GcodeOrigin::BottomLeft => (bounds.min_x, bounds.max_y),
+        GcodeOrigin::Center => (
+            (bounds.min_x + bounds.max_x) / 2.0,
+            (bounds.min_y + bounds.max_y) / 2.0,
+        ),
+        GcodeOrigin::Design => (0.0, 0.0),
+    };
+    let scale = options.scale / UNITS_PER_MM;
+    let position = |x: f64, y: f64| {
+        format!(
+            "X{} Y{}",
+            number((x - origin_x) * scale),
+            number((origin_y - y) * scale)
+        )
+    };
+
+    let mut gcode = String::new();
+    let _ = writeln!(gcode, "; EmbroCAD stitch path");
+    let _ = writeln!(gcode, "G21 ; millimeters");
+    let _ = writeln!(gcode, "G90 ; absolute positioning");
+    let _ = writeln!(
+        gcode,
+        "G0 Z{} ; start with the pen up",
+        number(options.z_lift_mm)
+    );
+    let _ = writeln!(gcode, "G1 F{}", number(options.feed_rate));
+
+    let mut pen_down = false;
+    let mut at: Option<(f64, f64)> = None;
+    let mut block = 0;
+
+    for (index, stitch) in pattern.stitches.iter().enumerate() {
+        let point = (stitch.x, stitch.y);
+        match stitch.command {
+            StitchCommand::Stitch => {
+                if !pen_down {
+                    // Travel to where the stitch starts, then drop the pen
+                    let start = &pattern.stitches[index.saturating_sub(1)];
+                    if at != Some((start.x, start.y)) {
+                        let _ = writeln!(gcode, "G0 {}", position(start.x, start.y));
+                    }
+                    let _ = writeln!(gcode, "G1 Z0");
+                    pen_down = true;
+                    at = Some((start.x, start.y));
+                }
+                if at != Some(point) {
+                    let _ = writeln!(gcode, "G1 {}", position(stitch.x, stitch.y));
+                }
+                at = Some(point);
+            }
+            StitchCommand::Move | StitchCommand::Trim => {
+                if pen_down {
+                    let _ = writeln!(gcode, "G0 Z{}", number(options.z_lift_mm));
+                    pen_down = false;
+                }
+                if at != Some(point) {
+                    let _ = writeln!(gcode, "G0 {}", position(stitch.x, stitch.y));
+                }
+                at = Some(point);
+            }
+            StitchCommand::ColorChange => {
+                block += 1;
+                let _ = writeln!(gcode, "M0 ; change to {}", thread_name(pattern, block));
+            }
+            _ => {}
+        }
+    }
+
+    if pen_down {
+        let _ = writeln!(gcode, "G0 Z{}", number(options.z_lift_mm));
+    }
+    let _ = writeln!(gcode, "M2 ; end of program");
+    gcode
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::ThreadColor;
+    use StitchCommand::*;
+
+    fn sample() -> Pattern {
+        let mut pattern = Pattern::new();
+        for &(x, y, command) in &[
+            (0.0, 0.0, Stitch),
+            (100.0, 0.0, Stitch),
+            (100.0, -50.0, Stitch),
+            (100.0, -50.0, Trim),
+            (200.0, -50.0, Move),
+            (200.0, -50.0, ColorChange),
+            (200.0, 0.0, Stitch),
+            (200.0, 0.0, End),
+        ] {
+            pattern.add_stitch(x, y, command);
+        }
+        let mut red = ThreadColor::new([255, 0, 0]);
+        red.description = Some("Red".to_string());
+        let mut gold = ThreadColor::new([232, 169, 0]);
+        gold.description = Some("Gold".to_string());
+        gold.catalog_number = Some("1025".to_string());
+        pattern.metadata.thread_colors = vec![red, gold];
+        pattern
+    }
+
+    #[test]
+    fn test_lifts_only_around_travel() {
+        let gcode = write_gcode(&sample(), &GcodeOptions::default());
+        let body: Vec<&str> = gcode.lines().skip(5).collect();
+
+        assert_eq!(
+            body,
+            vec![
+                "G0 X0 Y0",
+                "G1 Z0",
+                "G1 X10 Y0",
+                "G1 X10 Y5",
+                "G0 Z2",
+                "G0 X20 Y5",
+                "M0 ; change to Gold 1025 (#e8a900)",
+                "G1 Z0",
+                "G1 X20 Y0",
+                "G0 Z2",
+                "M2 ; end of program",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scale_and_origin() {
+        let options = GcodeOptions {
+            scale: 2.0,
+            origin: GcodeOrigin::Design,
+            ..GcodeOptions::default()
+        };
+        let gcode = write_gcode(&sample(), &options);
+
+        // Design Y is down, so -5mm on screen is +5mm on the bed, then doubled
+        assert!(gcode.contains("G1 X20 Y10\n"));
+        assert!(gcode.contains("G0 X40 Y10\n"));
+
+        let options = GcodeOptions {
+            origin: GcodeOrigin::Center,
+            ..GcodeOptions::default()
+        };
+        let gcode = write_gcode(&sample(), &options);
+        assert!(gcode.contains("G1 X0 Y-2.5\n"));
+    }
+}
@@ -0,0 +1,220 @@
+// mod.rs - Export of patterns to non-embroidery formats for preview and print
+
+mod animation;
+mod colors;
+mod gcode;
+mod png;
+mod stitch_list;
+mod svg;
+mod worksheet;
+
+pub use animation::{render_animation, AnimationOptions};
+pub use colors::ColorSidecar;
+pub use gcode::{write_gcode, GcodeOptions};
+pub use png::{fit_size, render_png, PngOptions, RenderMode};
+#[cfg(feature = "serde")]
+pub use stitch_list::export_json;
+pub use stitch_list::{export_csv, StitchListOptions};
+pub use svg::{write_svg, SvgOptions};
+pub use worksheet::{write_worksheet, WorksheetOptions};
+
+use crate::dst::{write_dst, Pattern};
+use crate::exp::write_exp;
+use crate::jef::write_jef;
+use crate::pes::write_pes;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Sequin size drawn for ejects when none is given; 3mm is the most common
+pub const DEFAULT_SEQUIN_DIAMETER_MM: f64 = 3.0;
+
+/// Thread colors used for blocks without a color in the file, in the same
+/// order as the frontend palette
+pub const DEFAULT_PALETTE: [[u8; 3]; 15] = [
+    [0, 0, 0],       // Black
+    [26, 26, 140],   // Navy Blue
+    [10, 95, 28],    // Dark Green
+    [140, 26, 26],   // Dark Red
+    [140, 26, 107],  // Purple
+    [92, 77, 26],    // Brown
+    [140, 140, 140], // Gray
+    [77, 77, 77],    // Dark Gray
+    [51, 102, 204],  // Blue
+    [51, 204, 102],  // Green
+    [204, 51, 51],   // Red
+    [204, 102, 204], // Pink
+    [204, 204, 51],  // Yellow
+    [230, 230, 230], // White
+    [26, 26, 26],    // Charcoal
+];
+
+/// Output formats supported by `encode_design`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum ExportFormat {
+    Dst,
+    Svg,
+    Png,
+    Csv,
+    /// Needs the `serde` feature
+    #[cfg(feature = "serde")]
+    Json,
+    Pes,
+    Exp,
+    Jef,
+    Gcode,
+}
+
+impl ExportFormat {
+    /// Format implied by a lowercase file extension, if it names one
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        let format = match extension {
+            "dst" => Self::Dst,
+            "svg" => Self::Svg,
+            "png" => Self::Png,
+            "csv" => Self::Csv,
+            #[cfg(feature = "serde")]
+            "json" => Self::Json,
+            "pes" => Self::Pes,
+            "exp" => Self::Exp,
+            "jef" => Self::Jef,
+            "gcode" | "nc" => Self::Gcode,
+            _ => return None,
+        };
+        Some(format)
+    }
+
+    /// File extension written for this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Dst => "dst",
+            Self::Svg => "svg",
+            Self::Png => "png",
+            Self::Csv => "csv",
+            #[cfg(feature = "serde")]
+            Self::Json => "json",
+            Self::Pes => "pes",
+            Self::Exp => "exp",
+            Self::Jef => "jef",
+            Self::Gcode => "gcode",
+        }
+    }
+}
+
+/// Per-format settings for `encode_design`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ExportOptions {
+    pub svg: SvgOptions,
+    pub png: PngOptions,
+    /// Longest side of a PNG export
+    pub size_px: u32,
+    pub stitch_list: StitchListOptions,
+    pub gcode: GcodeOptions,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            svg: SvgOptions::default(),
+            png: PngOptions::default(),
+            size_px: 1024,
+            stitch_list: StitchListOptions::default(),
+            gcode: GcodeOptions::default(),
+        }
+    }
+}
+
+/// Encode a pattern in one of the export formats
+pub fn encode_design(
+    pattern: &Pattern,
+    format: ExportFormat,
+    options: &ExportOptions,
+) -> Result<Vec<u8>, String> {
+    let data = match format {
+        ExportFormat::Dst => write_dst(pattern),
+        ExportFormat::Svg => write_svg(pattern, &options.svg).into_bytes(),
+        ExportFormat::Png => {
+            let (width, height) = fit_size(pattern, options.size_px);
+            render_png(pattern, width, height, &options.png).map_err(|e| e.to_string())?
+        }
+        ExportFormat::Csv => export_csv(pattern, &options.stitch_list).into_bytes(),
+        #[cfg(feature = "serde")]
+        ExportFormat::Json => export_json(pattern, &options.stitch_list).into_bytes(),
+        ExportFormat::Pes => write_pes(pattern),
+        ExportFormat::Exp => write_exp(pattern),
+        ExportFormat::Jef => write_jef(pattern),
+        ExportFormat::Gcode => write_gcode(pattern, &options.gcode).into_bytes(),
+    };
+    Ok(data)
+}
+
+/// RGB for a color block, falling back to the default palette
+pub fn block_rgb(pattern: &Pattern, block_index: usize) -> [u8; 3] {
+    pattern
+        .thread_color_for_block(block_index)
+        .map(|color| color.rgb)
+        .unwrap_or(DEFAULT_PALETTE[block_index % DEFAULT_PALETTE.len()])
+}
+
+/// Position of the entry in `palette` closest to `rgb` by squared RGB distance
+///
+/// Ties go to the earliest entry; an empty palette yields 0.
+pub fn nearest_palette_index(rgb: [u8; 3], palette: impl IntoIterator<Item = [u8; 3]>) -> usize {
+    let distance = |other: [u8; 3]| -> i32 {
+        (0..3)
+            .map(|i| (rgb[i] as i32 - other[i] as i32).pow(2))
+            .sum()
+    };
+    palette
+        .into_iter()
+        .enumerate()
+        .min_by_key(|&(_, other)| distance(other))
+        .map_or(0, |(index, _)| index)
+}
+
+/// Cache file name for a thumbnail of the file contents `data` at `size` pixels
+pub fn thumbnail_file_name(data: &[u8], size: u32, mode: RenderMode) -> String {
+    let hash: String = Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let mode = match mode {
+        RenderMode::Flat => "flat",
+        RenderMode::Realistic => "realistic",
+    };
+    format!("{}-{}-{}.png", hash, size, mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_extension() {
+        for format in [
+            ExportFormat::Dst,
+            ExportFormat::Svg,
+            ExportFormat::Png,
+            ExportFormat::Csv,
+            ExportFormat::Json,
+            ExportFormat::Pes,
+            ExportFormat::Exp,
+            ExportFormat::Jef,
+            ExportFormat::Gcode,
+        ] {
+            assert_eq!(
+                ExportFormat::from_extension(format.extension()),
+                Some(format)
+            );
+        }
+        assert_eq!(
+            ExportFormat::from_extension("nc"),
+            Some(ExportFormat::Gcode)
+        );
+        assert_eq!(ExportFormat::from_extension("vp3"), None);
+    }
+}
@@ -0,0 +1,410 @@
+// png.rs - Anti-aliased raster previews of stitch paths
+
+use super::{block_rgb, DEFAULT_SEQUIN_DIAMETER_MM};
+use crate::dst::{Bounds, Pattern, StitchCommand, UNITS_PER_MM};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Thread diameter of 40wt embroidery thread
+pub const DEFAULT_THREAD_WEIGHT_MM: f64 = 0.4;
+
+/// Opacity of one realistic stitch, so overlapping stitches build up
+const REALISTIC_ALPHA: f64 = 0.8;
+
+/// Largest lightness shift applied to a realistic stitch (0.08 = 8%)
+const SHEEN_VARIATION: f64 = 0.08;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RenderError {
+    #[error("Image size must be at least 1x1 pixels, got {width}x{height}")]
+    EmptyImage { width: u32, height: u32 },
+    #[error("PNG encoding failed: {0}")]
+    Encoding(#[from] png::EncodingError),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum RenderMode {
+    /// Fixed-width polylines, quickest to read at small sizes
+    #[default]
+    Flat,
+    /// Thread-width stitches with overlap buildup and sheen
+    Realistic,
+}
+
+/// Rendering options for `render_png`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct PngOptions {
+    pub mode: RenderMode,
+    /// Line width in flat mode
+    pub line_width_px: f64,
+    /// Thread diameter in realistic mode, scaled with the design
+    pub thread_weight_mm: f64,
+    /// Empty border kept around the design on every side
+    pub padding_px: u32,
+    /// Fill behind the design; transparent when unset
+    pub background: Option<[u8; 3]>,
+    /// Sequin ejects are drawn as discs this wide, scaled with the design
+    pub sequin_diameter_mm: f64,
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        Self {
+            mode: RenderMode::Flat,
+            line_width_px: 1.5,
+            thread_weight_mm: DEFAULT_THREAD_WEIGHT_MM,
+            padding_px: 4,
+            background: None,
+            sequin_diameter_mm: DEFAULT_SEQUIN_DIAMETER_MM,
+        }
+    }
+}
+
+/// Straight-alpha RGBA canvas
+struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32, background: Option<[u8; 3]>) -> Self {
+        let [r, g, b] = background.unwrap_or([0, 0, 0]);
+        let a = if background.is_some() { 255 } else { 0 };
+        let pixels = [r, g, b, a].repeat(width as usize * height as usize);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Composite `rgb` at `coverage` over the pixel at (x, y)
+    fn blend(&mut self, x: u32, y: u32, rgb: [u8; 3], coverage: f64) {
+        let offset = (y as usize * self.width as usize + x as usize) * 4;
+        let pixel = &mut self.pixels[offset..offset + 4];
+        let below = pixel[3] as f64 / 255.0;
+        let alpha = coverage + below * (1.0 - coverage);
+        for channel in 0..3 {
+            let value =
+                rgb[channel] as f64 * coverage + pixel[channel] as f64 * below * (1.0 - coverage);
+            pixel[channel] = (value / alpha).round() as u8;
+        }
+        pixel[3] = (alpha * 255.0).round() as u8;
+    }
+
+    /// Draw a round-capped line, shading each pixel by its distance to the segment
+    fn line(
+        &mut self,
+        (x0, y0): (f64, f64),
+        (x1, y1): (f64, f64),
+        width: f64,
+        rgb: [u8; 3],
+        opacity: f64,
+    ) {
+        let half = width / 2.0;
+        let reach = half + 1.0;
+        let clamp = |v: f64, max: u32| v.clamp(0.0, max as f64 - 1.0) as u32;
+        let (left, right) = (
+            clamp(x0.min(x1) - reach, self.width),
+            clamp(x0.max(x1) + reach, self.width),
+        );
+        let (top, bottom) = (
+            clamp(y0.min(y1) - reach, self.height),
+            clamp(y0.max(y1) + reach, self.height),
+        );
+
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let length_sq = dx * dx + dy * dy;
+        for py in top..=bottom {
+            for px in left..=right {
+                // Distance from the pixel center to the nearest point on the segment
+                let (cx, cy) = (px as f64 + 0.5 - x0, py as f64 + 0.5 - y0);
+                let t = if length_sq == 0.0 {
+                    0.0
+                } else {
+                    ((cx * dx + cy * dy) / length_sq).clamp(0.0, 1.0)
+                };
+                let distance = (cx - t * dx).hypot(cy - t * dy);
+                let coverage = (half + 0.5 - distance).clamp(0.0, 1.0) * opacity;
+                if coverage > 0.0 {
+                    self.blend(px, py, rgb, coverage);
+                }
+            }
+        }
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, png::EncodingError> {
+        let mut data = Vec::new();
+        let mut encoder = png::Encoder::new(&mut data, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.pixels)?;
+        Ok(data)
+    }
+}
+
+/// `rgb` lightened or darkened by up to `SHEEN_VARIATION`, varying per stitch
+///
+/// The shift comes from a hash of the stitch index so renders are repeatable.
+fn sheen(rgb: [u8; 3], index: usize) -> [u8; 3] {
+    let hash = (index as u32).wrapping_mul(2_654_435_761) >> 16;
+    let shift = (hash % 2001) as f64 / 1000.0 - 1.0;
+    let amount = shift * SHEEN_VARIATION;
+    rgb.map(|channel| {
+        let channel = channel as f64;
+        let shifted = if amount > 0.0 {
+            channel + (255.0 - channel) * amount
+        } else {
+            channel * (1.0 + amount)
+        };
+        shifted.round() as u8
+    })
+}
+
+/// Image size with the longest side at `longest_px` and the design's aspect ratio
+pub fn fit_size(pattern: &Pattern, longest_px: u32) -> (u32, u32) {
+    let mut bounds = Bounds::new();
+    for stitch in &pattern.stitches {
+        bounds.update(stitch.x, stitch.y);
+    }
+    let (width, height) = (bounds.width(), bounds.height());
+    if pattern.stitches.is_empty() || width <= 0.0 || height <= 0.0 {
+        return (longest_px, longest_px);
+    }
+    let shorter = |ratio: f64| ((longest_px as f64 * ratio).round() as u32).max(1);
+    if width >= height {
+        (longest_px, shorter(height / width))
+    } else {
+        (shorter(width / height), longest_px)
+    }
+}
+
+/// Render `pattern` as PNG bytes, fit and centered inside the padding
+///
+/// Each color block is drawn as anti-aliased polylines, or in realistic mode
+/// as translucent thread-width capsules with a per-stitch sheen. Jumps and
+/// trims are skipped. Stitch coordinates are Y-down like image rows, so no
+/// flip is needed.
+pub fn render_png(
+    pattern: &Pattern,
+    width_px: u32,
+    height_px: u32,
+    options: &PngOptions,
+) -> Result<Vec<u8>, RenderError> {
+    let cutoffs = [pattern.stitches.len()];
+    let mut frames = render_png_frames(pattern, width_px, height_px, options, &cutoffs, |_, _| {})?;
+    Ok(frames.remove(0))
+}
+
+/// Render one PNG per entry of `cutoffs`, each showing the records before
+/// that index
+///
+/// Frames are drawn incrementally on one canvas, so `cutoffs` must be
+/// ascending. `progress` is called with (frames done, total) after each one.
+pub fn render_png_frames(
+    pattern: &Pattern,
+    width_px: u32,
+    height_px: u32,
+    options: &PngOptions,
+    cutoffs: &[usize],
+    mut progress: impl FnMut(usize, usize),
+) -> Result<Vec<Vec<u8>>, RenderError> {
+    if width_px == 0 || height_px == 0 {
+        return Err(RenderError::EmptyImage {
+            width: width_px,
+            height: height_px,
+        });
+    }
+    let mut canvas = Canvas::new(width_px, height_px, options.background);
+
+    let mut bounds = Bounds::new();
+    for stitch in &pattern.stitches {
+        bounds.update(stitch.x, stitch.y);
+    }
+    if pattern.stitches.is_empty() {
+        bounds.update(0.0, 0.0);
+    }
+    // Sequins reach past their eject points by their radius
+    let sequin_radius = options.sequin_diameter_mm.max(0.0) / 2.0 * UNITS_PER_MM;
+    let has_sequins = pattern
+        .stitches
+        .iter()
+        .any(|s| s.command == StitchCommand::SequinEject);
+    if has_sequins {
+        bounds.update(bounds.min_x - sequin_radius, bounds.min_y - sequin_radius);
+        bounds.update(bounds.max_x + sequin_radius, bounds.max_y + sequin_radius);
+    }
+
+    let padding = options.padding_px as f64;
+    let available_x = (width_px as f64 - 2.0 * padding).max(1.0);
+    let available_y = (height_px as f64 - 2.0 * padding).max(1.0);
+    // A design that is a line or a point only constrains the other axis
+    let scale = match (bounds.width() > 0.0, bounds.height() > 0.0) {
+        (true, true) => (available_x / bounds.width()).min(available_y / bounds.height()),
+        (true, false) => available_x / bounds.width(),
+        (false, true) => available_y / bounds.height(),
+        (false, false) => 1.0,
+    };
+    let origin_x = (width_px as f64 - bounds.width() * scale) / 2.0;
+    let origin_y = (height_px as f64 - bounds.height() * scale) / 2.0;
+    let to_pixel = |x: f64, y: f64| {
+        (
+            origin_x + (x - bounds.min_x) * scale,
+            origin_y + (y - bounds.min_y) * scale,
+        )
+    };
+
+    let (width, opacity) = match options.mode {
+        RenderMode::Flat => (options.line_width_px, 1.0),
+        RenderMode::Realistic => (
+            (options.thread_weight_mm * UNITS_PER_MM * scale).max(1.0),
+            REALISTIC_ALPHA,
+        ),
+    };
+
+    let mut colors = Vec::with_capacity(pattern.stitches.len());
+    for block in pattern.color_blocks() {
+        let rgb = block_rgb(pattern, block.index);
+        colors.extend(std::iter::repeat_n(rgb, block.end - block.start));
+    }
+
+    let mut frames = Vec::with_capacity(cutoffs.len());
+    let mut pending = cutoffs.iter().peekable();
+    for index in 0..=pattern.stitches.len() {
+        while pending.next_if(|&&cutoff| cutoff <= index).is_some() {
+            frames.push(canvas.encode()?);
+            progress(frames.len(), cutoffs.len());
+        }
+        let Some((stitch, &rgb)) = pattern.stitches.get(index).zip(colors.get(index)) else {
+            break;
+        };
+        if index > 0 && stitch.command == StitchCommand::Stitch {
+            let prev = &pattern.stitches[index - 1];
+            let rgb = match options.mode {
+                RenderMode::Flat => rgb,
+                RenderMode::Realistic => sheen(rgb, index),
+            };
+            let from = to_pixel(prev.x, prev.y);
+            canvas.line(from, to_pixel(stitch.x, stitch.y), width, rgb, opacity);
+        }
+        if stitch.command == StitchCommand::SequinEject {
+            // A zero-length round-capped line is a disc
+            let center = to_pixel(stitch.x, stitch.y);
+            let diameter = (2.0 * sequin_radius * scale).max(1.0);
+            canvas.line(center, center, diameter, rgb, opacity);
+        }
+    }
+    // Cutoffs past the end all show the finished design
+    for _ in pending {
+        frames.push(canvas.encode()?);
+        progress(frames.len(), cutoffs.len());
+    }
+
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StitchCommand::*;
+
+    fn decode(data: &[u8]) -> (png::OutputInfo, Vec<u8>) {
+        let mut reader = png::Decoder::new(data).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        pixels.truncate(info.buffer_size());
+        (info, pixels)
+    }
+
+    #[test]
+    fn test_image_has_requested_size() {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(0.0, 0.0, Stitch);
+        pattern.add_stitch(300.0, 100.0, Stitch);
+
+        let (info, pixels) = decode(&render_png(&pattern, 64, 48, &PngOptions::default()).unwrap());
+        assert_eq!((info.width, info.height), (64, 48));
+        // The diagonal passes through the image center
+        let center = (24 * 64 + 32) * 4;
+        assert!(pixels[center + 3] > 0);
+        // The padding stays clear
+        assert_eq!(pixels[3], 0);
+    }
+
+    #[test]
+    fn test_blank_pattern_renders_background_only() {
+        let options = PngOptions {
+            background: Some([255, 255, 255]),
+            ..PngOptions::default()
+        };
+
+        let (info, pixels) = decode(&render_png(&Pattern::new(), 16, 16, &options).unwrap());
+        assert_eq!((info.width, info.height), (16, 16));
+        assert!(pixels.iter().all(|&channel| channel == 255));
+    }
+
+    #[test]
+    fn test_realistic_mode_matches_golden_image() {
+        use sha2::{Digest, Sha256};
+
+        let mut pattern = Pattern::new();
+        // A short satin column in two overlapping colors
+        for i in 0..12 {
+            let x = i as f64 * 4.0;
+            pattern.add_stitch(x, if i % 2 == 0 { 0.0 } else { 30.0 }, Stitch);
+        }
+        pattern.add_stitch(44.0, 30.0, ColorChange);
+        for i in 0..12 {
+            let y = i as f64 * 4.0 - 5.0;
+            pattern.add_stitch(if i % 2 == 0 { 10.0 } else { 35.0 }, y, Stitch);
+        }
+        let options = PngOptions {
+            mode: RenderMode::Realistic,
+            background: Some([255, 255, 255]),
+            ..PngOptions::default()
+        };
+
+        let rendered = render_png(&pattern, 48, 48, &options).unwrap();
+        let golden = include_bytes!("../../tests/fixtures/export/realistic.png");
+        assert_eq!(Sha256::digest(&rendered), Sha256::digest(golden));
+    }
+
+    #[test]
+    fn test_sequins_render_as_discs() {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(0.0, 0.0, SequinMode);
+        pattern.add_stitch(0.0, 0.0, SequinEject);
+        pattern.add_stitch(100.0, 0.0, SequinEject);
+        pattern.add_stitch(100.0, 0.0, SequinMode);
+        let options = PngOptions {
+            padding_px: 0,
+            sequin_diameter_mm: 10.0,
+            ..PngOptions::default()
+        };
+
+        // 10mm between the ejects plus a 5mm radius each side: 20mm across 40px
+        let (_, pixels) = decode(&render_png(&pattern, 40, 20, &options).unwrap());
+        let alpha = |x: usize, y: usize| pixels[(y * 40 + x) * 4 + 3];
+        assert_eq!(alpha(10, 10), 255);
+        assert_eq!(alpha(30, 10), 255);
+        // Between the discs and outside them stays clear
+        assert_eq!(alpha(20, 2), 0);
+        assert_eq!(alpha(0, 0), 0);
+    }
+
+    #[test]
+    fn test_zero_size_is_rejected() {
+        assert!(matches!(
+            render_png(&Pattern::new(), 0, 16, &PngOptions::default()),
+            Err(RenderError::EmptyImage {
+                width: 0,
+                height: 16
+            })
+        ));
+    }
+}
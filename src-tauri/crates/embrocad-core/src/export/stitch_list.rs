@@ -0,0 +1,159 @@
+// stitch_list.rs - Plain CSV and JSON stitch lists for spreadsheets and scripts
+
+use crate::dst::{Pattern, StitchCommand, UNITS_PER_MM};
+#[cfg(feature = "serde")]
+use crate::dst::{PatternMetadata, PatternStatistics};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Header row of `export_csv`
+pub const CSV_HEADER: &str = "index,x_mm,y_mm,command,color_block";
+
+/// Formatting options shared by the CSV and JSON stitch lists
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct StitchListOptions {
+    /// Decimal places for millimeter coordinates
+    pub precision: usize,
+}
+
+impl Default for StitchListOptions {
+    fn default() -> Self {
+        Self { precision: 2 }
+    }
+}
+
+/// One record of a stitch list, in millimeters
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct StitchRow {
+    pub index: usize,
+    pub x_mm: f64,
+    pub y_mm: f64,
+    pub command: StitchCommand,
+    pub color_block: usize,
+}
+
+/// Full JSON export: the stitch rows plus the pattern's header data
+#[cfg(feature = "serde")]
+#[derive(Debug, Serialize)]
+pub struct StitchList<'a> {
+    pub metadata: &'a PatternMetadata,
+    pub statistics: &'a PatternStatistics,
+    pub stitches: Vec<StitchRow>,
+}
+
+/// Every record with its color block, rounded to `precision` decimals
+pub fn stitch_rows(pattern: &Pattern, precision: usize) -> Vec<StitchRow> {
+    let factor = 10f64.powi(precision as i32);
+    let round = |v: f64| (v / UNITS_PER_MM * factor).round() / factor;
+
+    let mut rows = Vec::with_capacity(pattern.stitches.len());
+    for block in pattern.color_blocks() {
+        for index in block.start..block.end {
+            let stitch = &pattern.stitches[index];
+            rows.push(StitchRow {
+                index,
+                x_mm: round(stitch.x),
+                y_mm: round(stitch.y),
+                command: stitch.command,
+                color_block: block.index,
+            });
+        }
+    }
+    rows
+}
+
+/// Quote a CSV field when it holds a delimiter, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// One CSV row per record under `CSV_HEADER`, preceded by a `label` row when
+/// the design has one
+pub fn export_csv(pattern: &Pattern, options: &StitchListOptions) -> String {
+    let mut csv = String::new();
+    if let Some(label) = &pattern.metadata.label {
+        csv.push_str(&format!("label,{}\n", csv_field(label)));
+    }
+    csv.push_str(CSV_HEADER);
+    csv.push('\n');
+
+    let precision = options.precision;
+    for row in stitch_rows(pattern, precision) {
+        csv.push_str(&format!(
+            "{},{:.*},{:.*},{},{}\n",
+            row.index,
+            precision,
+            row.x_mm,
+            precision,
+            row.y_mm,
+            row.command.as_str(),
+            row.color_block,
+        ));
+    }
+    csv
+}
+
+/// The stitch rows plus metadata and statistics as pretty-printed JSON
+#[cfg(feature = "serde")]
+pub fn export_json(pattern: &Pattern, options: &StitchListOptions) -> String {
+    let list = StitchList {
+        metadata: &pattern.metadata,
+        statistics: &pattern.statistics,
+        stitches: stitch_rows(pattern, options.precision),
+    };
+    // Plain data with string keys always serializes
+    serde_json::to_string_pretty(&list).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StitchCommand::*;
+
+    fn sample() -> Pattern {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(0.0, 0.0, Stitch);
+        pattern.add_stitch(12.345, -7.0, Stitch);
+        pattern.add_stitch(12.345, -7.0, ColorChange);
+        pattern.add_stitch(30.0, 0.0, Stitch);
+        pattern.add_stitch(30.0, 0.0, End);
+        pattern.metadata.label = Some("Rose, \"small\"".to_string());
+        pattern.calculate_statistics();
+        pattern
+    }
+
+    #[test]
+    fn test_csv_rows_and_quoted_label() {
+        let options = StitchListOptions { precision: 3 };
+
+        assert_eq!(
+            export_csv(&sample(), &options),
+            "label,\"Rose, \"\"small\"\"\"\n\
+             index,x_mm,y_mm,command,color_block\n\
+             0,0.000,0.000,STITCH,0\n\
+             1,1.235,-0.700,STITCH,0\n\
+             2,1.235,-0.700,COLOR_CHANGE,1\n\
+             3,3.000,0.000,STITCH,1\n\
+             4,3.000,0.000,END,1\n"
+        );
+    }
+
+    #[test]
+    fn test_json_includes_metadata_and_statistics() {
+        let json = export_json(&sample(), &StitchListOptions::default());
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["metadata"]["label"], "Rose, \"small\"");
+        assert_eq!(value["statistics"]["real_stitch_count"], 3);
+        assert_eq!(value["stitches"][1]["x_mm"], 1.23);
+        assert_eq!(value["stitches"][2]["command"], "COLOR_CHANGE");
+        assert_eq!(value["stitches"][4]["color_block"], 1);
+    }
+}
@@ -0,0 +1,255 @@
+// svg.rs - SVG rendering of stitch paths in millimeter units
+
+use super::{block_rgb, DEFAULT_SEQUIN_DIAMETER_MM};
+use crate::dst::{Bounds, Pattern, StitchCommand, UNITS_PER_MM};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// Rendering options for `write_svg`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct SvgOptions {
+    /// Draw jumps and trims as dashed segments
+    pub include_jumps: bool,
+    pub stroke_width_mm: f64,
+    /// Fill behind the design; transparent when unset
+    pub background: Option<[u8; 3]>,
+    /// Sequin ejects are drawn as discs this wide
+    pub sequin_diameter_mm: f64,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            include_jumps: false,
+            stroke_width_mm: 0.4,
+            background: None,
+            sequin_diameter_mm: DEFAULT_SEQUIN_DIAMETER_MM,
+        }
+    }
+}
+
+/// A coordinate in 0.1mm units as a trimmed millimeter string
+fn mm(value: f64) -> String {
+    let text = format!("{:.2}", value / UNITS_PER_MM);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" => "0".to_string(),
+        _ => text.to_string(),
+    }
+}
+
+fn hex(rgb: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2])
+}
+
+/// Append a segment from `from` to `to`, starting a new subpath unless the
+/// path already ends at `from`
+fn push_segment(d: &mut String, pen: &mut Option<(f64, f64)>, from: (f64, f64), to: (f64, f64)) {
+    if *pen != Some(from) {
+        let _ = write!(d, "M{} {}", mm(from.0), mm(from.1));
+    }
+    let _ = write!(d, "L{} {}", mm(to.0), mm(to.1));
+    *pen = Some(to);
+}
+
+/// Render `pattern` as an SVG document with one path per color block
+///
+/// Coordinates are in millimeters with the viewBox covering the stitch
+/// bounds plus half a stroke on each side, so the document prints at the
+/// design's real size.
+pub fn write_svg(pattern: &Pattern, options: &SvgOptions) -> String {
+    let mut bounds = Bounds::new();
+    for stitch in &pattern.stitches {
+        bounds.update(stitch.x, stitch.y);
+    }
+    if pattern.stitches.is_empty() {
+        bounds = Bounds {
+            min_x: 0.0,
+            min_y: 0.0,
+            max_x: 0.0,
+            max_y: 0.0,
+        };
+    }
+
+    let has_sequins = pattern
+        .stitches
+        .iter()
+        .any(|s| s.command == StitchCommand::SequinEject);
+    let sequin_radius = options.sequin_diameter_mm.max(0.0) / 2.0 * UNITS_PER_MM;
+    let mut pad = options.stroke_width_mm / 2.0 * UNITS_PER_MM;
+    if has_sequins {
+        pad = pad.max(sequin_radius);
+    }
+    let (x, y) = (bounds.min_x - pad, bounds.min_y - pad);
+    let (width, height) = (bounds.width() + 2.0 * pad, bounds.height() + 2.0 * pad);
+
+    let mut svg = String::new();
+    let _ = writeln!(svg, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}mm" height="{h}mm" viewBox="{} {} {w} {h}">"#,
+        mm(x),
+        mm(y),
+        w = mm(width),
+        h = mm(height),
+    );
+    if let Some(background) = options.background {
+        let _ = writeln!(
+            svg,
+            r#"  <rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+            mm(x),
+            mm(y),
+            mm(width),
+            mm(height),
+            hex(background),
+        );
+    }
+
+    let stroke_width = mm(options.stroke_width_mm * UNITS_PER_MM);
+    let jump_width = mm(options.stroke_width_mm / 2.0 * UNITS_PER_MM);
+    for block in pattern.color_blocks() {
+        let mut stitches = String::new();
+        let mut jumps = String::new();
+        let mut sequins = Vec::new();
+        let (mut stitch_pen, mut jump_pen) = (None, None);
+
+        for index in block.start..block.end {
+            let stitch = &pattern.stitches[index];
+            if stitch.command == StitchCommand::SequinEject {
+                sequins.push((stitch.x, stitch.y));
+            }
+            let Some(prev) = index.checked_sub(1).map(|i| &pattern.stitches[i]) else {
+                continue;
+            };
+            let (from, to) = ((prev.x, prev.y), (stitch.x, stitch.y));
+            match stitch.command {
+                StitchCommand::Stitch if from != to => {
+                    push_segment(&mut stitches, &mut stitch_pen, from, to);
+                }
+                StitchCommand::Move | StitchCommand::Trim
+                    if options.include_jumps && from != to =>
+                {
+                    push_segment(&mut jumps, &mut jump_pen, from, to);
+                }
+                _ => {}
+            }
+        }
+
+        let color = hex(block_rgb(pattern, block.index));
+        if !stitches.is_empty() {
+            let _ = writeln!(
+                svg,
+                r#"  <path d="{stitches}" fill="none" stroke="{color}" stroke-width="{stroke_width}" stroke-linecap="round" stroke-linejoin="round"/>"#,
+            );
+        }
+        if !jumps.is_empty() {
+            let _ = writeln!(
+                svg,
+                r#"  <path class="jumps" d="{jumps}" fill="none" stroke="{color}" stroke-width="{jump_width}" stroke-dasharray="1 1" stroke-opacity="0.5"/>"#,
+            );
+        }
+        for (cx, cy) in sequins {
+            let _ = writeln!(
+                svg,
+                r#"  <circle class="sequin" cx="{}" cy="{}" r="{}" fill="{color}"/>"#,
+                mm(cx),
+                mm(cy),
+                mm(sequin_radius),
+            );
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::ThreadColor;
+    use StitchCommand::*;
+
+    fn two_blocks() -> Pattern {
+        let mut pattern = Pattern::new();
+        for &(x, y, command) in &[
+            (0.0, 0.0, Stitch),
+            (100.0, 0.0, Stitch),
+            (100.0, 50.0, Stitch),
+            (100.0, 50.0, Trim),
+            (200.0, 50.0, Move),
+            (200.0, 50.0, ColorChange),
+            (200.0, 50.0, Stitch),
+            (200.0, -25.0, Stitch),
+            (200.0, -25.0, End),
+        ] {
+            pattern.add_stitch(x, y, command);
+        }
+        pattern.metadata.thread_colors = vec![ThreadColor::new([255, 0, 0])];
+        pattern
+    }
+
+    #[test]
+    fn test_matches_golden_file() {
+        let options = SvgOptions {
+            include_jumps: true,
+            stroke_width_mm: 0.4,
+            background: Some([255, 255, 255]),
+            sequin_diameter_mm: 3.0,
+        };
+
+        assert_eq!(
+            write_svg(&two_blocks(), &options),
+            include_str!("../../tests/fixtures/export/two_blocks.svg")
+        );
+    }
+
+    #[test]
+    fn test_scales_to_millimeters() {
+        let svg = write_svg(&two_blocks(), &SvgOptions::default());
+
+        // 20mm x 7.5mm of stitches plus a 0.2mm half stroke on each side
+        assert!(svg.contains(r#"width="20.4mm" height="7.9mm" viewBox="-0.2 -2.7 20.4 7.9""#));
+        assert!(svg.contains(r#"d="M0 0L10 0L10 5""#));
+        // The second block has no color in the file so takes the palette's second entry
+        assert!(svg.contains(r##"d="M20 5L20 -2.5" fill="none" stroke="#1a1a8c""##));
+        assert!(!svg.contains("jumps"));
+        assert!(!svg.contains("<rect"));
+    }
+
+    #[test]
+    fn test_sequins_are_discs() {
+        let mut pattern = Pattern::new();
+        for &(x, command) in &[
+            (0.0, Stitch),
+            (0.0, SequinMode),
+            (30.0, SequinEject),
+            (60.0, SequinEject),
+            (60.0, SequinMode),
+            (60.0, End),
+        ] {
+            pattern.add_stitch(x, 0.0, command);
+        }
+        let options = SvgOptions {
+            sequin_diameter_mm: 4.0,
+            ..SvgOptions::default()
+        };
+        let svg = write_svg(&pattern, &options);
+
+        assert_eq!(svg.matches("<circle").count(), 2);
+        assert!(svg.contains(r##"<circle class="sequin" cx="3" cy="0" r="2" fill="#"##));
+        // The viewBox reaches the rim of the outermost discs
+        assert!(svg.contains(r#"viewBox="-2 -2 10 4""#));
+    }
+
+    #[test]
+    fn test_empty_pattern_is_valid_document() {
+        let svg = write_svg(&Pattern::new(), &SvgOptions::default());
+
+        assert!(svg.starts_with("<?xml"));
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(!svg.contains("<path"));
+    }
+}
@@ -0,0 +1,505 @@
+// worksheet.rs - One-page PDF design worksheet with vector stitch paths
+
+use super::{block_rgb, DEFAULT_SEQUIN_DIAMETER_MM};
+use crate::dst::{Bounds, Pattern, StitchCommand, TimeEstimator, UNITS_PER_MM};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// PDF points per millimeter
+const POINTS_PER_MM: f64 = 72.0 / 25.4;
+
+const MM_PER_INCH: f64 = 25.4;
+
+/// Page margin on every side, in points
+const MARGIN: f64 = 40.0;
+
+/// Share of the usable page height given to the design drawing
+const DRAWING_SHARE: f64 = 0.5;
+
+/// Body text size and line spacing, in points
+const FONT_SIZE: f64 = 10.0;
+const LEADING: f64 = 15.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum PaperSize {
+    #[default]
+    A4,
+    Letter,
+}
+
+impl PaperSize {
+    /// Width and height in points
+    fn dimensions(self) -> (f64, f64) {
+        match self {
+            PaperSize::A4 => (595.28, 841.89),
+            PaperSize::Letter => (612.0, 792.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct WorksheetOptions {
+    pub paper: PaperSize,
+    /// Machine the run time is estimated for; the design statistics'
+    /// estimate when unset
+    pub machine: Option<TimeEstimator>,
+}
+
+/// Escape text for a PDF string literal, replacing what WinAnsi can't show
+fn pdf_text(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '(' | ')' | '\\' => format!("\\{}", c),
+            c if c.is_ascii() && !c.is_ascii_control() => c.to_string(),
+            _ => "?".to_string(),
+        })
+        .collect()
+}
+
+/// Page content drawn bottom-up in PDF's Y-up point space
+struct Page {
+    content: String,
+}
+
+impl Page {
+    fn text(&mut self, x: f64, y: f64, size: f64, bold: bool, text: &str) {
+        let font = if bold { "F2" } else { "F1" };
+        let _ = writeln!(
+            self.content,
+            "BT /{} {:.1} Tf {:.2} {:.2} Td ({}) Tj ET",
+            font,
+            size,
+            x,
+            y,
+            pdf_text(text)
+        );
+    }
+
+    fn fill_rgb(&mut self, [r, g, b]: [u8; 3]) {
+        let _ = writeln!(
+            self.content,
+            "{:.3} {:.3} {:.3} rg",
+            r as f64 / 255.0,
+            g as f64 / 255.0,
+            b as f64 / 255.0
+        );
+    }
+
+    fn stroke_rgb(&mut self, [r, g, b]: [u8; 3]) {
+        let _ = writeln!(
+            self.content,
+            "{:.3} {:.3} {:.3} RG",
+            r as f64 / 255.0,
+            g as f64 / 255.0,
+            b as f64 / 255.0
+        );
+    }
+
+    /// Fill a circle, drawn as four Bezier quarter arcs
+    fn disc(&mut self, x: f64, y: f64, r: f64) {
+        let k = r * 0.5523;
+        let _ = write!(self.content, "{:.2} {:.2} m", x + r, y);
+        // Counter-clockwise from the right: both control points, then the end
+        for [c1x, c1y, c2x, c2y, ex, ey] in [
+            [r, k, k, r, 0.0, r],
+            [-k, r, -r, k, -r, 0.0],
+            [-r, -k, -k, -r, 0.0, -r],
+            [k, -r, r, -k, r, 0.0],
+        ] {
+            let _ = write!(
+                self.content,
+                " {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c",
+                x + c1x,
+                y + c1y,
+                x + c2x,
+                y + c2y,
+                x + ex,
+                y + ey
+            );
+        }
+        let _ = writeln!(self.content, " f");
+    }
+}
+
+/// Draw each color block's stitches as stroked paths fit into the box
+fn draw_design(page: &mut Page, pattern: &Pattern, (x, y, width, height): (f64, f64, f64, f64)) {
+    let mut bounds = Bounds::new();
+    for stitch in &pattern.stitches {
+        bounds.update(stitch.x, stitch.y);
+    }
+
+    page.stroke_rgb([160, 160, 160]);
+    let _ = writeln!(
+        page.content,
+        "0.5 w {:.2} {:.2} {:.2} {:.2} re S",
+        x, y, width, height
+    );
+    if pattern.stitches.is_empty() {
+        return;
+    }
+
+    // Real size when it fits, shrunk to the box otherwise
+    let inner = 8.0;
+    let fit = ((width - 2.0 * inner) / bounds.width().max(1.0))
+        .min((height - 2.0 * inner) / bounds.height().max(1.0));
+    let scale = fit.min(POINTS_PER_MM / UNITS_PER_MM);
+    let offset_x = x + (width - bounds.width() * scale) / 2.0;
+    let offset_y = y + (height - bounds.height() * scale) / 2.0;
+    // Stitch Y grows downward, PDF Y upward
+    let point = |sx: f64, sy: f64| {
+        (
+            offset_x + (sx - bounds.min_x) * scale,
+            offset_y + (bounds.max_y - sy) * scale,
+        )
+    };
+
+    let _ = writeln!(page.content, "1 J 1 j 0.6 w");
+    for block in pattern.color_blocks() {
+        page.stroke_rgb(block_rgb(pattern, block.index));
+        let mut drawing = false;
+        for index in block.start.max(1)..block.end {
+            let stitch = &pattern.stitches[index];
+            if stitch.command != StitchCommand::Stitch {
+                drawing = false;
+                continue;
+            }
+            if !drawing {
+                let prev = &pattern.stitches[index - 1];
+                let (px, py) = point(prev.x, prev.y);
+                let _ = writeln!(page.content, "{:.2} {:.2} m", px, py);
+                drawing = true;
+            }
+            let (sx, sy) = point(stitch.x, stitch.y);
+            let _ = writeln!(page.content, "{:.2} {:.2} l", sx, sy);
+        }
+        let _ = writeln!(page.content, "S");
+
+        let radius = (DEFAULT_SEQUIN_DIAMETER_MM / 2.0 * UNITS_PER_MM * scale).max(0.5);
+        let sequins = pattern.stitches[block.start..block.end]
+            .iter()
+            .filter(|s| s.command == StitchCommand::SequinEject);
+        for (index, stitch) in sequins.enumerate() {
+            if index == 0 {
+                page.fill_rgb(block_rgb(pattern, block.index));
+            }
+            let (sx, sy) = point(stitch.x, stitch.y);
+            page.disc(sx, sy, radius);
+        }
+    }
+}
+
+/// The label, size, counts and color sequence of one design
+fn draw_details(
+    page: &mut Page,
+    pattern: &Pattern,
+    options: &WorksheetOptions,
+    left: f64,
+    mut y: f64,
+    bottom: f64,
+) {
+    let mut bounds = Bounds::new();
+    for stitch in &pattern.stitches {
+        bounds.update(stitch.x, stitch.y);
+    }
+    let (width_mm, height_mm) = if pattern.stitches.is_empty() {
+        (0.0, 0.0)
+    } else {
+        (
+            bounds.width() / UNITS_PER_MM,
+            bounds.height() / UNITS_PER_MM,
+        )
+    };
+    let stats = &pattern.statistics;
+    let minutes = options
+        .machine
+        .as_ref()
+        .map_or(stats.estimated_time_minutes, |m| {
+            m.estimate(pattern).minutes
+        });
+    let blocks = if pattern.color_blocks.is_empty() {
+        pattern.color_blocks()
+    } else {
+        pattern.color_blocks.clone()
+    };
+
+    page.fill_rgb([0, 0, 0]);
+    let lines = [
+        format!(
+            "Size: {:.1} x {:.1} mm ({:.2} x {:.2} in)",
+            width_mm,
+            height_mm,
+            width_mm / MM_PER_INCH,
+            height_mm / MM_PER_INCH
+        ),
+        format!("Stitches: {}", stats.real_stitch_count),
+        format!(
+            "Colors: {}   Trims: {}   Jumps: {}",
+            blocks.len(),
+            stats.trim_count,
+            stats.jump_count
+        ),
+        format!("Estimated run time: {:.0} min", minutes.ceil()),
+    ];
+    let sequins = format!("Sequins: {}", stats.sequin_count);
+    let lines = lines
+        .iter()
+        .chain((stats.sequin_count > 0).then_some(&sequins));
+    for line in lines {
+        page.text(left, y, FONT_SIZE, false, line);
+        y -= LEADING;
+    }
+
+    y -= LEADING / 2.0;
+    page.text(left, y, FONT_SIZE + 2.0, true, "Color sequence");
+    y -= LEADING + 2.0;
+
+    for (position, block) in blocks.iter().enumerate() {
+        if y < bottom + LEADING {
+            let more = format!("... and {} more", blocks.len() - position);
+            page.fill_rgb([0, 0, 0]);
+            page.text(left, y, FONT_SIZE, false, &more);
+            break;
+        }
+        page.fill_rgb(block_rgb(pattern, block.index));
+        let _ = writeln!(page.content, "{:.2} {:.2} 16 10 re f", left, y - 1.0);
+        page.stroke_rgb([0, 0, 0]);
+        let _ = writeln!(page.content, "0.3 w {:.2} {:.2} 16 10 re S", left, y - 1.0);
+
+        let color = block.color.as_ref();
+        let name = color
+            .and_then(|c| c.description.as_deref())
+            .unwrap_or("Unnamed");
+        let code = color
+            .and_then(|c| c.catalog_number.as_deref())
+            .unwrap_or("-");
+        let mut line = format!(
+            "{}. {}   Code: {}   {} stitches",
+            position + 1,
+            name,
+            code,
+            block.stitch_count
+        );
+        if let Some(needle) = color.and_then(|c| c.needle) {
+            let _ = write!(line, "   Needle {}", needle);
+        }
+        page.fill_rgb([0, 0, 0]);
+        page.text(left + 24.0, y, FONT_SIZE, false, &line);
+        y -= LEADING;
+    }
+}
+
+/// Produce a one-page PDF worksheet for `pattern`
+///
+/// The page holds the label as a title, the stitch paths drawn as vectors in
+/// thread colors, and the design's size, counts, run time and color sequence
+/// with swatches and thread codes.
+pub fn write_worksheet(pattern: &Pattern, options: &WorksheetOptions) -> Vec<u8> {
+    let (page_width, page_height) = options.paper.dimensions();
+    let mut page = Page {
+        content: String::new(),
+    };
+
+    let title = pattern
+        .metadata
+        .label
+        .as_deref()
+        .unwrap_or("Untitled design");
+    let mut y = page_height - MARGIN - 18.0;
+    page.fill_rgb([0, 0, 0]);
+    page.text(MARGIN, y, 18.0, true, title);
+    y -= 14.0;
+
+    let drawing_height = (y - MARGIN) * DRAWING_SHARE;
+    let drawing = (
+        MARGIN,
+        y - drawing_height,
+        page_width - 2.0 * MARGIN,
+        drawing_height,
+    );
+    draw_design(&mut page, pattern, drawing);
+
+    let details_top = drawing.1 - LEADING * 1.5;
+    draw_details(&mut page, pattern, options, MARGIN, details_top, MARGIN);
+
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] \
+             /Resources << /Font << /F1 5 0 R /F2 6 0 R >> >> /Contents 4 0 R >>",
+            page_width, page_height
+        ),
+        format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            page.content.len(),
+            page.content
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+    ];
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", index + 1, object).as_bytes());
+    }
+
+    let xref = pdf.len();
+    let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(table, "{:010} 00000 n ", offset);
+    }
+    let _ = write!(
+        table,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    );
+    pdf.extend_from_slice(table.as_bytes());
+    pdf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::ThreadColor;
+    use StitchCommand::*;
+
+    /// Check the cross-reference table points at each object header, the part
+    /// of the file a reader needs to be exact; the repo carries no PDF reader
+    /// crate to open it fully
+    fn assert_valid_xref(pdf: &[u8]) -> usize {
+        let text = String::from_utf8_lossy(pdf);
+        let start: usize = text
+            .rsplit("startxref\n")
+            .next()
+            .and_then(|tail| tail.lines().next())
+            .and_then(|line| line.parse().ok())
+            .unwrap();
+        assert!(text[start..].starts_with("xref\n"));
+
+        let mut lines = text[start..].lines().skip(1);
+        let count: usize = lines.next().unwrap()[2..].parse().unwrap();
+        let entries: Vec<&str> = lines.take(count).collect();
+        for (number, entry) in entries.iter().enumerate().skip(1) {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(text[offset..].starts_with(&format!("{} 0 obj", number)));
+        }
+        count - 1
+    }
+
+    fn sample() -> Pattern {
+        let mut pattern = Pattern::new();
+        for &(x, y, command) in &[
+            (0.0, 0.0, Stitch),
+            (300.0, 0.0, Stitch),
+            (300.0, 200.0, Stitch),
+            (300.0, 200.0, ColorChange),
+            (0.0, 200.0, Stitch),
+            (0.0, 200.0, End),
+        ] {
+            pattern.add_stitch(x, y, command);
+        }
+        pattern.metadata.label = Some("Badge (front)".to_string());
+        let mut red = ThreadColor::new([237, 23, 31]);
+        red.description = Some("Red".to_string());
+        red.catalog_number = Some("1147".to_string());
+        pattern.metadata.thread_colors = vec![red];
+        pattern.calculate_bounds();
+        pattern.calculate_statistics();
+        pattern.calculate_color_blocks();
+        pattern
+    }
+
+    #[test]
+    fn test_worksheet_is_well_formed_pdf() {
+        let pdf = write_worksheet(&sample(), &WorksheetOptions::default());
+
+        assert!(pdf.starts_with(b"%PDF-1.4\n"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+        assert_eq!(assert_valid_xref(&pdf), 6);
+
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("(Badge \\(front\\)) Tj"));
+        assert!(text.contains("Size: 30.0 x 20.0 mm \\(1.18 x 0.79 in\\)"));
+        assert!(text.contains("1. Red   Code: 1147   3 stitches"));
+        // Vector paths, not an image
+        assert!(text.contains(" l\n"));
+        assert!(!text.contains("/Image"));
+    }
+
+    #[test]
+    fn test_worksheet_uses_machine_speed() {
+        let options = WorksheetOptions {
+            machine: Some(TimeEstimator {
+                speed_spm: 1.0,
+                trim_seconds: 0.0,
+                color_change_seconds: 0.0,
+                slowdown_above_mm: None,
+            }),
+            ..WorksheetOptions::default()
+        };
+        let text = String::from_utf8_lossy(&write_worksheet(&sample(), &options)).into_owned();
+        assert!(text.contains("(Estimated run time: 4 min) Tj"));
+    }
+
+    #[test]
+    fn test_worksheet_lists_needles() {
+        let mut pattern = sample();
+        pattern.metadata.thread_colors[0].needle = Some(7);
+        pattern.calculate_color_blocks();
+
+        let text =
+            String::from_utf8_lossy(&write_worksheet(&pattern, &WorksheetOptions::default()))
+                .into_owned();
+        assert!(text.contains("1. Red   Code: 1147   3 stitches   Needle 7"));
+        assert!(text.contains("2. Unnamed   Code: -   1 stitches) Tj"));
+    }
+
+    #[test]
+    fn test_worksheet_shows_sequins() {
+        let text =
+            String::from_utf8_lossy(&write_worksheet(&sample(), &Default::default())).into_owned();
+        assert!(!text.contains("Sequins"));
+
+        let mut pattern = Pattern::new();
+        for &(x, command) in &[
+            (0.0, Stitch),
+            (0.0, SequinMode),
+            (30.0, SequinEject),
+            (60.0, SequinEject),
+            (60.0, SequinMode),
+            (60.0, End),
+        ] {
+            pattern.add_stitch(x, 0.0, command);
+        }
+        pattern.calculate_statistics();
+        pattern.calculate_color_blocks();
+
+        let pdf = write_worksheet(&pattern, &WorksheetOptions::default());
+        assert_valid_xref(&pdf);
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("(Sequins: 2) Tj"));
+        assert_eq!(text.matches(" c f\n").count(), 2);
+    }
+
+    #[test]
+    fn test_paper_size_and_empty_design() {
+        let options = WorksheetOptions {
+            paper: PaperSize::Letter,
+            ..WorksheetOptions::default()
+        };
+        let pdf = write_worksheet(&Pattern::new(), &options);
+
+        assert_valid_xref(&pdf);
+        assert!(String::from_utf8_lossy(&pdf).contains("/MediaBox [0 0 612.00 792.00]"));
+    }
+}
@@ -0,0 +1,571 @@
+// format.rs - Design format identification, and reading a design of any format
+
+#[cfg(feature = "fs")]
+use crate::companion::find_companion_colors;
+use crate::csv::parse_csv;
+use crate::dst::{
+    detect_variant, parse_dst_monitored, parse_t01, parse_t03, parse_t09, DstVariant, ParseOptions,
+    Pattern,
+};
+use crate::exp::parse_exp;
+use crate::hus::{parse_hus, parse_vip};
+use crate::jef::parse_jef;
+use crate::legacy::{parse_10o, parse_ksm};
+use crate::packed::PackedStitches;
+use crate::pcs::parse_pcs;
+use crate::pes::parse_pes;
+use crate::progress::LoadMonitor;
+use crate::sew::parse_sew;
+use crate::svg::parse_svg;
+use crate::vp3::parse_vp3;
+use crate::xxx::parse_xxx;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "fs")]
+use std::fs;
+use std::path::Path;
+
+/// Every design format the loader can identify
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum DesignFormat {
+    Dst,
+    Dsb,
+    Dsz,
+    Pes,
+    Pec,
+    Exp,
+    Jef,
+    Vp3,
+    Xxx,
+    Hus,
+    Vip,
+    Sew,
+    Pcs,
+    T01,
+    T03,
+    T09,
+    #[cfg_attr(feature = "serde", serde(rename = "10o"))]
+    TenO,
+    Ksm,
+    Csv,
+    Svg,
+}
+
+/// HUS and VIP start with a 32-bit little-endian magic number
+const HUS_MAGIC: u32 = 0x00C8AF5B;
+const VIP_MAGIC: u32 = 0x0190FC5D;
+
+/// JEF stores a 14-digit YYYYMMDDHHMMSS date after two 32-bit header fields
+const JEF_DATE_OFFSET: usize = 8;
+const JEF_DATE_LENGTH: usize = 14;
+
+/// DST-family headers are 512 bytes starting with the label record
+const DST_HEADER_SIZE: usize = 512;
+
+impl DesignFormat {
+    /// Format implied by a lowercase file extension, if it names one
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        let format = match extension {
+            "dst" => Self::Dst,
+            "dsb" => Self::Dsb,
+            "dsz" => Self::Dsz,
+            "pes" => Self::Pes,
+            "pec" => Self::Pec,
+            "exp" => Self::Exp,
+            "jef" => Self::Jef,
+            "vp3" => Self::Vp3,
+            "xxx" => Self::Xxx,
+            "hus" => Self::Hus,
+            "vip" => Self::Vip,
+            "sew" => Self::Sew,
+            "pcs" => Self::Pcs,
+            "t01" => Self::T01,
+            "t03" => Self::T03,
+            "t09" => Self::T09,
+            "10o" => Self::TenO,
+            "ksm" => Self::Ksm,
+            "csv" => Self::Csv,
+            "svg" => Self::Svg,
+            _ => return None,
+        };
+        Some(format)
+    }
+
+    /// Short name used in messages
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Dst => "DST",
+            Self::Dsb => "DSB",
+            Self::Dsz => "DSZ",
+            Self::Pes => "PES",
+            Self::Pec => "PEC",
+            Self::Exp => "EXP",
+            Self::Jef => "JEF",
+            Self::Vp3 => "VP3",
+            Self::Xxx => "XXX",
+            Self::Hus => "HUS",
+            Self::Vip => "VIP",
+            Self::Sew => "SEW",
+            Self::Pcs => "PCS",
+            Self::T01 => "T01",
+            Self::T03 => "T03",
+            Self::T09 => "T09",
+            Self::TenO => "10o",
+            Self::Ksm => "KSM",
+            Self::Csv => "CSV",
+            Self::Svg => "SVG",
+        }
+    }
+}
+
+/// Identify a design from its leading bytes
+///
+/// Only formats with a recognisable signature are detected; headerless formats
+/// such as EXP or the tape formats return None so the caller can fall back to
+/// the file extension. DST-family files are told apart by their stitch encoding.
+pub fn detect_format(data: &[u8]) -> Option<DesignFormat> {
+    if data.starts_with(b"#PES") {
+        return Some(DesignFormat::Pes);
+    }
+    if data.starts_with(b"#PEC") {
+        return Some(DesignFormat::Pec);
+    }
+    if data.starts_with(b"%vsm%") {
+        return Some(DesignFormat::Vp3);
+    }
+
+    let magic = data
+        .get(..4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    match magic {
+        Some(HUS_MAGIC) => return Some(DesignFormat::Hus),
+        Some(VIP_MAGIC) => return Some(DesignFormat::Vip),
+        _ => {}
+    }
+
+    if data.starts_with(b"LA:") && data.len() >= DST_HEADER_SIZE {
+        return Some(match detect_variant(data) {
+            DstVariant::Tajima => DesignFormat::Dst,
+            DstVariant::Barudan => DesignFormat::Dsb,
+            DstVariant::Zsk => DesignFormat::Dsz,
+        });
+    }
+
+    let jef_date = data.get(JEF_DATE_OFFSET..JEF_DATE_OFFSET + JEF_DATE_LENGTH);
+    if jef_date.is_some_and(|date| date.iter().all(u8::is_ascii_digit)) {
+        return Some(DesignFormat::Jef);
+    }
+
+    // SVG is the only text format that opens with markup
+    let text = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+    let start = text
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(text.len());
+    let markup = &text[start..];
+    if markup.starts_with(b"<?xml") || markup.starts_with(b"<svg") {
+        return Some(DesignFormat::Svg);
+    }
+
+    None
+}
+
+/// Format of a design file from its signature, falling back to the extension
+pub fn design_format(path: &str, data: &[u8]) -> DesignFormat {
+    let extension = Path::new(path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    detect_format(data)
+        .or_else(|| DesignFormat::from_extension(&extension))
+        .unwrap_or(DesignFormat::Dst)
+}
+
+/// Pick a parser from the file signature, falling back to the extension
+///
+/// DST-family files report progress and honour cancellation record by record;
+/// other formats only check the token once they have been parsed. `path` is
+/// only used for its extension and, with the `fs` feature, to find companion
+/// color files.
+pub fn parse_design(
+    path: &str,
+    data: &[u8],
+    options: &ParseOptions,
+    monitor: &mut LoadMonitor,
+) -> Result<LoadedDesign, String> {
+    let format = design_format(path, data);
+
+    let parsed = match format {
+        DesignFormat::Dst => parse_dst_monitored(data, detect_variant(data), options, monitor)
+            .map_err(|e| e.to_string()),
+        DesignFormat::Dsb => parse_dst_monitored(data, DstVariant::Barudan, options, monitor)
+            .map_err(|e| e.to_string()),
+        DesignFormat::Dsz => {
+            parse_dst_monitored(data, DstVariant::Zsk, options, monitor).map_err(|e| e.to_string())
+        }
+        DesignFormat::Pes | DesignFormat::Pec => parse_pes(data).map_err(|e| e.to_string()),
+        DesignFormat::Exp => parse_exp(data).map_err(|e| e.to_string()),
+        DesignFormat::Jef => parse_jef(data).map_err(|e| e.to_string()),
+        DesignFormat::Vp3 => parse_vp3(data).map_err(|e| e.to_string()),
+        DesignFormat::Xxx => parse_xxx(data).map_err(|e| e.to_string()),
+        DesignFormat::Hus => parse_hus(data).map_err(|e| e.to_string()),
+        DesignFormat::Vip => parse_vip(data).map_err(|e| e.to_string()),
+        DesignFormat::Sew => parse_sew(data).map_err(|e| e.to_string()),
+        DesignFormat::Pcs => parse_pcs(data).map_err(|e| e.to_string()),
+        DesignFormat::T01 => parse_t01(data).map_err(|e| e.to_string()),
+        DesignFormat::T03 => parse_t03(data).map_err(|e| e.to_string()),
+        DesignFormat::T09 => parse_t09(data).map_err(|e| e.to_string()),
+        DesignFormat::TenO => parse_10o(data).map_err(|e| e.to_string()),
+        DesignFormat::Ksm => parse_ksm(data).map_err(|e| e.to_string()),
+        DesignFormat::Csv => parse_csv(data).map_err(|e| e.to_string()),
+        DesignFormat::Svg => parse_svg(data, &options.svg).map_err(|e| e.to_string()),
+    };
+    if monitor.is_cancelled() {
+        return Err("Load cancelled".to_string());
+    }
+    let mut pattern = parsed.map_err(|e| format!("Failed to parse {}: {}", format.name(), e))?;
+
+    #[cfg(feature = "fs")]
+    if options.companion_colors && pattern.metadata.thread_colors.is_empty() {
+        if let Some((file, colors)) = find_companion_colors(Path::new(path)) {
+            pattern.metadata.thread_colors = colors;
+            pattern.metadata.companion_file = Some(file.to_string_lossy().into_owned());
+            pattern.calculate_color_blocks();
+        }
+    }
+    if options.center_on_load {
+        pattern.center();
+    }
+    monitor.finish(data.len(), pattern.stitches.len());
+
+    Ok(LoadedDesign::new(format, pattern))
+}
+
+/// Read and parse a design file without progress reporting
+#[cfg(feature = "fs")]
+pub fn read_design(path: &str, options: &ParseOptions) -> Result<LoadedDesign, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read file: {}", e))?;
+
+    parse_design(path, &data, options, &mut LoadMonitor::none())
+}
+
+/// A parsed design together with the format it was read as
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct LoadedDesign {
+    pub format: DesignFormat,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub pattern: Pattern,
+    /// The stitches in compact form; `stitches` is then left empty
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub packed: Option<PackedStitches>,
+}
+
+impl LoadedDesign {
+    pub fn new(format: DesignFormat, pattern: Pattern) -> Self {
+        Self {
+            format,
+            pattern,
+            packed: None,
+        }
+    }
+
+    /// Move the stitches into their packed form for the IPC response
+    pub fn pack(&mut self) {
+        self.packed = Some(self.pattern.to_packed());
+        self.pattern.stitches = Vec::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::{write_dst, StitchCommand, ThreadColor};
+
+    #[test]
+    fn test_detect_signatures() {
+        assert_eq!(detect_format(b"#PES0001\0\0\0\0"), Some(DesignFormat::Pes));
+        assert_eq!(detect_format(b"#PEC0001"), Some(DesignFormat::Pec));
+        assert_eq!(detect_format(b"%vsm%\0"), Some(DesignFormat::Vp3));
+        assert_eq!(
+            detect_format(&HUS_MAGIC.to_le_bytes()),
+            Some(DesignFormat::Hus)
+        );
+        assert_eq!(
+            detect_format(&VIP_MAGIC.to_le_bytes()),
+            Some(DesignFormat::Vip)
+        );
+
+        let mut jef = vec![0x74, 0, 0, 0, 0x14, 0, 0, 0];
+        jef.extend_from_slice(b"20240131120000");
+        assert_eq!(detect_format(&jef), Some(DesignFormat::Jef));
+
+        let mut dst = b"LA:TEST\r".to_vec();
+        dst.resize(DST_HEADER_SIZE, b' ');
+        assert_eq!(detect_format(&dst), Some(DesignFormat::Dst));
+
+        assert_eq!(
+            detect_format(b"\xEF\xBB\xBF\n<?xml version=\"1.0\"?><svg/>"),
+            Some(DesignFormat::Svg)
+        );
+        assert_eq!(detect_format(b"<svg></svg>"), Some(DesignFormat::Svg));
+    }
+
+    #[test]
+    fn test_headerless_data_is_not_detected() {
+        assert_eq!(detect_format(&[]), None);
+        assert_eq!(detect_format(&[0x0A, 0x05, 0x80, 0x01, 0x00, 0x00]), None);
+        // A short file that merely starts like a DST label
+        assert_eq!(detect_format(b"LA:SHORT"), None);
+    }
+
+    #[test]
+    fn test_from_extension() {
+        assert_eq!(
+            DesignFormat::from_extension("10o"),
+            Some(DesignFormat::TenO)
+        );
+        assert_eq!(DesignFormat::from_extension("pec"), Some(DesignFormat::Pec));
+        assert_eq!(DesignFormat::from_extension("png"), None);
+    }
+
+    /// The same design saved in each headerless legacy format
+    const LEGACY_FIXTURES: [(&str, &[u8]); 6] = [
+        (
+            "design.10o",
+            include_bytes!("../tests/fixtures/legacy/design.10o"),
+        ),
+        (
+            "design.ksm",
+            include_bytes!("../tests/fixtures/legacy/design.ksm"),
+        ),
+        (
+            "design.t01",
+            include_bytes!("../tests/fixtures/legacy/design.t01"),
+        ),
+        (
+            "design.t03",
+            include_bytes!("../tests/fixtures/legacy/design.t03"),
+        ),
+        (
+            "design.t09",
+            include_bytes!("../tests/fixtures/legacy/design.t09"),
+        ),
+        (
+            "design.exp",
+            include_bytes!("../tests/fixtures/legacy/design.exp"),
+        ),
+    ];
+
+    #[test]
+    fn test_legacy_fixtures_decode_same_design() {
+        use StitchCommand::*;
+        let expected = vec![
+            (10.0, 20.0, Stitch),
+            (-40.0, 25.0, Move),
+            (-37.0, 18.0, Stitch),
+            (-37.0, 18.0, ColorChange),
+            (-38.0, 19.0, Stitch),
+            (-38.0, 19.0, End),
+        ];
+
+        for (name, data) in LEGACY_FIXTURES {
+            let pattern = parse_design(
+                name,
+                data,
+                &ParseOptions::default(),
+                &mut LoadMonitor::none(),
+            )
+            .unwrap()
+            .pattern;
+            let decoded: Vec<_> = pattern
+                .stitches
+                .iter()
+                .map(|s| (s.x, s.y, s.command))
+                .collect();
+
+            assert_eq!(decoded, expected, "{}", name);
+            assert_eq!(pattern.metadata.stitch_count, Some(6), "{}", name);
+            assert_eq!(pattern.metadata.color_count, Some(1), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_load_errors_name_the_offset() {
+        let mut short = b"LA:SHORT\r".to_vec();
+        short.resize(300, b' ');
+        let error = parse_design(
+            "short.dst",
+            &short,
+            &ParseOptions::default(),
+            &mut LoadMonitor::none(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error,
+            "Failed to parse DST: Invalid DST file: data ends at offset 300, \
+             but at least 512 bytes are needed"
+        );
+    }
+
+    #[test]
+    fn test_corrupted_fixtures_never_panic() {
+        let dst: (&str, &[u8]) = (
+            "sequins.dst",
+            include_bytes!("../tests/fixtures/dst/sequins.dst"),
+        );
+        let options = ParseOptions {
+            companion_colors: false,
+            ..ParseOptions::default()
+        };
+        let load = |name: &str, data: &[u8]| {
+            let _ = parse_design(name, data, &options, &mut LoadMonitor::none());
+        };
+
+        // Every truncation of each fixture, and each with one byte inverted
+        for (name, data) in LEGACY_FIXTURES.into_iter().chain([dst]) {
+            for end in 0..data.len() {
+                load(name, &data[..end]);
+            }
+            for index in 0..data.len() {
+                let mut corrupted = data.to_vec();
+                corrupted[index] ^= 0xFF;
+                load(name, &corrupted);
+            }
+        }
+    }
+
+    #[test]
+    fn test_detection_overrides_lying_extension() {
+        // A PEC stream saved with a .dst extension by a web store
+        let mut pec = vec![b' '; 532];
+        pec[48] = 0;
+        pec[49] = 5;
+        pec.extend_from_slice(&[0x0A, 0x0A, 0xFF, 0x00]);
+        let pes = [b"#PEC0001".as_slice(), &pec].concat();
+        let loaded = parse_design(
+            "renamed.dst",
+            &pes,
+            &ParseOptions::default(),
+            &mut LoadMonitor::none(),
+        )
+        .unwrap();
+        assert_eq!(loaded.format, DesignFormat::Pec);
+        assert_eq!(loaded.pattern.statistics.real_stitch_count, 1);
+
+        // A DST file renamed to .exp
+        let mut dst = b"LA:RENAMED\r".to_vec();
+        dst.resize(512, b' ');
+        dst.extend_from_slice(&[0x00, 0x00, 0xF3]);
+        assert_eq!(
+            parse_design(
+                "design.exp",
+                &dst,
+                &ParseOptions::default(),
+                &mut LoadMonitor::none()
+            )
+            .unwrap()
+            .format,
+            DesignFormat::Dst
+        );
+
+        // Headerless formats still follow the extension
+        let (name, data) = LEGACY_FIXTURES[0];
+        assert_eq!(
+            parse_design(
+                name,
+                data,
+                &ParseOptions::default(),
+                &mut LoadMonitor::none()
+            )
+            .unwrap()
+            .format,
+            DesignFormat::TenO
+        );
+    }
+
+    #[test]
+    fn test_companion_colors_on_load() {
+        let dir = std::env::temp_dir().join(format!("embrocad-sidecar-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("logo.dst");
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(0.0, 0.0, StitchCommand::Stitch);
+        pattern.add_stitch(10.0, 0.0, StitchCommand::ColorChange);
+        pattern.add_stitch(20.0, 0.0, StitchCommand::Stitch);
+        pattern.add_stitch(20.0, 0.0, StitchCommand::End);
+        let data = write_dst(&pattern);
+        fs::write(dir.join("logo.edr"), [255, 0, 0, 0, 0, 0, 255, 0]).unwrap();
+
+        let path = path.to_string_lossy();
+        let load = |options: &ParseOptions| {
+            parse_design(&path, &data, options, &mut LoadMonitor::none())
+                .unwrap()
+                .pattern
+        };
+        let loaded = load(&ParseOptions::default());
+        let colors: Vec<_> = loaded
+            .color_blocks
+            .iter()
+            .map(|b| b.color.clone())
+            .collect();
+        assert_eq!(
+            colors,
+            vec![
+                Some(ThreadColor::new([255, 0, 0])),
+                Some(ThreadColor::new([0, 0, 255]))
+            ]
+        );
+        let source = loaded.metadata.companion_file.unwrap();
+        assert!(source.ends_with("logo.edr"));
+
+        let options = ParseOptions {
+            companion_colors: false,
+            ..ParseOptions::default()
+        };
+        let plain = load(&options);
+        assert!(plain.metadata.thread_colors.is_empty());
+        assert_eq!(plain.metadata.companion_file, None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_center_on_load() {
+        let options = ParseOptions {
+            center_on_load: true,
+            ..ParseOptions::default()
+        };
+        for (name, data) in LEGACY_FIXTURES {
+            let pattern = parse_design(name, data, &options, &mut LoadMonitor::none())
+                .unwrap()
+                .pattern;
+            let bounds = pattern.bounds.unwrap();
+
+            assert_eq!(bounds.min_x, -bounds.max_x, "{}", name);
+            assert_eq!(bounds.min_y, -bounds.max_y, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_loaded_design_serializes_flat() {
+        let (name, data) = LEGACY_FIXTURES[2];
+        let json = serde_json::to_value(
+            parse_design(
+                name,
+                data,
+                &ParseOptions::default(),
+                &mut LoadMonitor::none(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(json["format"], "t01");
+        assert!(json["stitches"].is_array());
+        assert!(json["metadata"].is_object());
+    }
+}
@@ -0,0 +1,375 @@
+// fill.rs - Tatami fill of polygons with holes, sewn in rows at an angle
+
+use super::{check_stitch_len, fragment, run_between, GenerateError};
+use crate::dst::{Pattern, Stitch, StitchCommand, UNITS_PER_MM};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+type Point = (f64, f64);
+
+/// How a polygon is filled
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct FillOptions {
+    /// Direction of the rows, counter-clockwise from the x axis
+    pub angle_deg: f64,
+    /// Distance between rows
+    pub spacing_mm: f64,
+    /// Longest stitch along a row
+    pub stitch_len_mm: f64,
+    /// Walk every edge once before filling
+    pub underlay: bool,
+}
+
+impl Default for FillOptions {
+    fn default() -> Self {
+        Self {
+            angle_deg: 0.0,
+            spacing_mm: 0.4,
+            stitch_len_mm: 3.5,
+            underlay: false,
+        }
+    }
+}
+
+/// Where a row meets a ring: its x, and which edge of which ring it crossed
+#[derive(Debug, Clone, Copy)]
+struct Crossing {
+    x: f64,
+    ring: usize,
+    edge: usize,
+}
+
+/// A stretch of row inside the shape
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    row: usize,
+    y: f64,
+    start: Crossing,
+    end: Crossing,
+}
+
+fn rotate((x, y): Point, (sin, cos): (f64, f64)) -> Point {
+    (x * cos - y * sin, x * sin + y * cos)
+}
+
+fn distance(p: Point, q: Point) -> f64 {
+    (p.0 - q.0).hypot(p.1 - q.1)
+}
+
+/// The rows through `rings` at `spacing`, split where they cross an edge
+fn segments(rings: &[Vec<Point>], spacing: f64) -> Vec<Segment> {
+    let (min_y, max_y) = rings[0]
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), p| {
+            (lo.min(p.1), hi.max(p.1))
+        });
+
+    let mut segments = Vec::new();
+    let mut row = 0;
+    loop {
+        let y = min_y + spacing * (row as f64 + 0.5);
+        if y >= max_y {
+            break;
+        }
+        let mut crossings = Vec::new();
+        for (r, ring) in rings.iter().enumerate() {
+            for e in 0..ring.len() {
+                let (a, b) = (ring[e], ring[(e + 1) % ring.len()]);
+                if (a.1 > y) != (b.1 > y) {
+                    let x = a.0 + (y - a.1) * (b.0 - a.0) / (b.1 - a.1);
+                    crossings.push(Crossing {
+                        x,
+                        ring: r,
+                        edge: e,
+                    });
+                }
+            }
+        }
+        crossings.sort_by(|p, q| p.x.total_cmp(&q.x));
+        segments.extend(crossings.chunks_exact(2).map(|pair| Segment {
+            row,
+            y,
+            start: pair[0],
+            end: pair[1],
+        }));
+        row += 1;
+    }
+    segments
+}
+
+/// Corners passed walking around `ring` from a point on edge `from` to one
+/// on edge `to`, whichever way round is shorter
+fn ring_walk(ring: &[Point], (p, from): (Point, usize), (q, to): (Point, usize)) -> Vec<Point> {
+    if from == to {
+        return Vec::new();
+    }
+    let n = ring.len();
+    let forward: Vec<Point> = (1..=(to + n - from) % n)
+        .map(|k| ring[(from + k) % n])
+        .collect();
+    let backward: Vec<Point> = (0..(from + n - to) % n)
+        .map(|k| ring[(from + n - k) % n])
+        .collect();
+    let length = |corners: &[Point]| {
+        let mut path = vec![p];
+        path.extend_from_slice(corners);
+        path.push(q);
+        path.windows(2).map(|w| distance(w[0], w[1])).sum::<f64>()
+    };
+    if length(&forward) <= length(&backward) {
+        forward
+    } else {
+        backward
+    }
+}
+
+/// Penetrations along a row from `from` to `to`, on a grid shifted by a
+/// third of a stitch each row so neighbouring rows don't line up
+fn row_stitches(from: f64, to: f64, y: f64, row: usize, step: f64) -> Vec<Point> {
+    let phase = (row % 3) as f64 * step / 3.0;
+    let (lo, hi) = (from.min(to), from.max(to));
+    let first = ((lo - phase) / step).floor() as i64 + 1;
+    let mut xs: Vec<f64> = (first..)
+        .map(|k| phase + k as f64 * step)
+        .take_while(|&x| x < hi)
+        .filter(|&x| x - lo > 1e-6 && hi - x > 1e-6)
+        .collect();
+    if from > to {
+        xs.reverse();
+    }
+    xs.push(to);
+    xs.into_iter().map(|x| (x, y)).collect()
+}
+
+/// Fill `outline` less any `holes` (points in mm) with rows of running stitch
+///
+/// Rows run at `angle_deg` and are taken nearest first, so simple shapes
+/// sew back and forth. Moving to the next row follows the edge both ends
+/// lie on; between different edges, such as across a hole, the needle
+/// jumps. With `underlay` each ring is walked once before the fill.
+pub fn fill_polygon(
+    outline: &[Point],
+    holes: &[Vec<Point>],
+    options: &FillOptions,
+) -> Result<Pattern, GenerateError> {
+    check_stitch_len(options.stitch_len_mm)?;
+    if options.spacing_mm.is_nan() || options.spacing_mm <= 0.0 {
+        return Err(GenerateError::InvalidSpacing);
+    }
+    // Work with rows along x, and turn the stitches back at the end
+    let turn = options.angle_deg.to_radians().sin_cos();
+    let unturn = (-turn.0, turn.1);
+    let rings: Vec<Vec<Point>> = std::iter::once(outline)
+        .chain(holes.iter().map(Vec::as_slice))
+        .map(|ring| {
+            let mut points: Vec<Point> = ring
+                .iter()
+                .map(|&(x, y)| rotate((x * UNITS_PER_MM, y * UNITS_PER_MM), unturn))
+                .collect();
+            points.dedup();
+            if points.len() > 1 && points.first() == points.last() {
+                points.pop();
+            }
+            points
+        })
+        .collect();
+    if rings.iter().any(|ring| ring.len() < 3) {
+        return Err(GenerateError::TooFewPoints);
+    }
+    let step = options.stitch_len_mm * UNITS_PER_MM;
+
+    let mut stitches: Vec<Stitch> = Vec::new();
+    let add = |stitches: &mut Vec<Stitch>, point: Point, command: StitchCommand| {
+        let (x, y) = rotate(point, turn);
+        stitches.push(Stitch::new(x, y, command));
+    };
+
+    if options.underlay {
+        for ring in &rings {
+            if !stitches.is_empty() {
+                add(&mut stitches, ring[0], StitchCommand::Move);
+            }
+            add(&mut stitches, ring[0], StitchCommand::Stitch);
+            for (i, &from) in ring.iter().enumerate() {
+                for stitch in run_between(from, ring[(i + 1) % ring.len()], step) {
+                    add(&mut stitches, (stitch.x, stitch.y), StitchCommand::Stitch);
+                }
+            }
+        }
+    }
+
+    let mut remaining = segments(&rings, options.spacing_mm * UNITS_PER_MM);
+    let mut current: Option<(Point, Crossing)> = None;
+    while !remaining.is_empty() {
+        // The nearest unsewn end comes next
+        let (index, reversed) = match current {
+            None => (0, false),
+            Some((position, _)) => remaining
+                .iter()
+                .enumerate()
+                .flat_map(|(i, s)| {
+                    [
+                        (i, false, distance(position, (s.start.x, s.y))),
+                        (i, true, distance(position, (s.end.x, s.y))),
+                    ]
+                })
+                .min_by(|a, b| a.2.total_cmp(&b.2))
+                .map(|(i, reversed, _)| (i, reversed))
+                .expect("segments remain"),
+        };
+        let segment = remaining.swap_remove(index);
+        let (from, to) = if reversed {
+            (segment.end, segment.start)
+        } else {
+            (segment.start, segment.end)
+        };
+        let start = (from.x, segment.y);
+
+        match current {
+            Some((position, at)) if at.ring == from.ring => {
+                let ring = &rings[at.ring];
+                let mut leg = position;
+                for corner in ring_walk(ring, (position, at.edge), (start, from.edge))
+                    .into_iter()
+                    .chain([start])
+                {
+                    for stitch in run_between(leg, corner, step) {
+                        add(&mut stitches, (stitch.x, stitch.y), StitchCommand::Stitch);
+                    }
+                    leg = corner;
+                }
+            }
+            _ => {
+                if !stitches.is_empty() {
+                    add(&mut stitches, start, StitchCommand::Move);
+                }
+                add(&mut stitches, start, StitchCommand::Stitch);
+            }
+        }
+        for point in row_stitches(from.x, to.x, segment.y, segment.row, step) {
+            add(&mut stitches, point, StitchCommand::Stitch);
+        }
+        current = Some(((to.x, segment.y), to));
+    }
+
+    Ok(fragment(stitches))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn penetrations(pattern: &Pattern) -> Vec<Point> {
+        pattern
+            .stitches
+            .iter()
+            .filter(|s| s.command == StitchCommand::Stitch)
+            .map(|s| (s.x, s.y))
+            .collect()
+    }
+
+    /// Even-odd test of `point` against a closed ring
+    fn inside(ring: &[Point], (x, y): Point) -> bool {
+        let mut inside = false;
+        for (i, &a) in ring.iter().enumerate() {
+            let b = ring[(i + 1) % ring.len()];
+            if (a.1 > y) != (b.1 > y) && x < a.0 + (y - a.1) * (b.0 - a.0) / (b.1 - a.1) {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    /// Distance from `point` to the nearest edge of a ring
+    fn to_edge(ring: &[Point], p: Point) -> f64 {
+        (0..ring.len())
+            .map(|i| {
+                let (a, b) = (ring[i], ring[(i + 1) % ring.len()]);
+                let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+                let t =
+                    (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / (dx * dx + dy * dy)).clamp(0.0, 1.0);
+                distance(p, (a.0 + dx * t, a.1 + dy * t))
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    fn scaled(ring: &[Point]) -> Vec<Point> {
+        ring.iter().map(|&(x, y)| (x * 10.0, y * 10.0)).collect()
+    }
+
+    #[test]
+    fn test_rectangle_rows_and_coverage() {
+        let rectangle = [(-10.0, -5.0), (10.0, -5.0), (10.0, 5.0), (-10.0, 5.0)];
+        let options = FillOptions {
+            spacing_mm: 0.5,
+            stitch_len_mm: 3.0,
+            ..FillOptions::default()
+        };
+        let pattern = fill_polygon(&rectangle, &[], &options).unwrap();
+        let points = penetrations(&pattern);
+
+        // 10mm at 0.5mm is 20 rows, each from edge to edge
+        let mut rows: Vec<f64> = points.iter().map(|p| p.1).collect();
+        rows.dedup();
+        assert_eq!(rows.len(), 20);
+        assert_eq!(rows[0], -47.5);
+        assert_eq!(rows[19], 47.5);
+
+        let bounds = pattern.bounds.clone().unwrap();
+        assert_eq!((bounds.min_x, bounds.max_x), (-100.0, 100.0));
+        assert!(points
+            .windows(2)
+            .all(|w| distance(w[0], w[1]) <= 30.0 + 1e-9));
+        // Nothing but stitches: every row starts where the last one ended
+        assert_eq!(pattern.stitches.len(), points.len() + 1);
+    }
+
+    #[test]
+    fn test_fill_around_hole_at_angle() {
+        let outer = [(-15.0, -15.0), (15.0, -15.0), (15.0, 15.0), (-15.0, 15.0)];
+        let hole = vec![(-5.0, -5.0), (5.0, -5.0), (5.0, 5.0), (-5.0, 5.0)];
+        let options = FillOptions {
+            angle_deg: 30.0,
+            spacing_mm: 1.0,
+            stitch_len_mm: 3.0,
+            underlay: false,
+        };
+        let pattern = fill_polygon(&outer, std::slice::from_ref(&hole), &options).unwrap();
+        let points = penetrations(&pattern);
+        let (outer, hole) = (scaled(&outer), scaled(&hole));
+
+        for &point in &points {
+            assert!(inside(&outer, point) || to_edge(&outer, point) <= 30.0);
+            assert!(!inside(&hole, point) || to_edge(&hole, point) <= 30.0);
+        }
+        // The fill reaches all four sides of the hole
+        for (x, y) in [(-100.0, 0.0), (100.0, 0.0), (0.0, -100.0), (0.0, 100.0)] {
+            assert!(points.iter().any(|&p| distance(p, (x, y)) < 20.0));
+        }
+        assert!(pattern.stitches.iter().all(|s| s.x.abs() <= 150.0 + 1e-6));
+    }
+
+    #[test]
+    fn test_underlay_walks_edges_first() {
+        let triangle = [(0.0, 0.0), (20.0, 0.0), (10.0, 15.0)];
+        let options = FillOptions {
+            underlay: true,
+            ..FillOptions::default()
+        };
+        let pattern = fill_polygon(&triangle, &[], &options).unwrap();
+        let plain = fill_polygon(&triangle, &[], &FillOptions::default()).unwrap();
+        let points = penetrations(&pattern);
+
+        assert_eq!(points[0], (0.0, 0.0));
+        // 20mm, 18.03mm and 18.03mm edges at 3.5mm take 6 stitches each
+        assert_eq!(points[18], (0.0, 0.0));
+        assert_eq!(points.len(), penetrations(&plain).len() + 19);
+
+        assert_eq!(
+            fill_polygon(&triangle[..2], &[], &options).unwrap_err(),
+            GenerateError::TooFewPoints
+        );
+    }
+}
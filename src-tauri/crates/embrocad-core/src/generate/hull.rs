@@ -0,0 +1,203 @@
+// hull.rs - Convex and concave hulls of point sets, and outward polygon offsets
+//
+// Polygons are returned counter-clockwise in the plain mathematical sense
+// (interior on the left of each edge), whatever way the y axis points on
+// screen.
+
+type Point = (f64, f64);
+
+/// Twice the signed area of the triangle `o`, `a`, `b`; positive when `b`
+/// lies left of the line from `o` to `a`
+fn cross(o: Point, a: Point, b: Point) -> f64 {
+    (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+}
+
+/// Whether the segments `a`-`b` and `c`-`d` cross at a point inside both;
+/// segments that only share an endpoint do not
+fn segments_cross(a: Point, b: Point, c: Point, d: Point) -> bool {
+    let (d1, d2) = (cross(a, b, c), cross(a, b, d));
+    let (d3, d4) = (cross(c, d, a), cross(c, d, b));
+    d1 * d2 < 0.0 && d3 * d4 < 0.0
+}
+
+/// Convex hull by Andrew's monotone chain
+///
+/// Fewer than three points come back as they are, deduplicated.
+pub fn convex_hull(points: &[Point]) -> Vec<Point> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    sorted.dedup();
+    if sorted.len() < 3 {
+        return sorted;
+    }
+
+    let mut hull = chain(sorted.iter().copied());
+    let mut upper = chain(sorted.iter().rev().copied());
+    // Each chain ends on the point the other starts from
+    hull.pop();
+    upper.pop();
+    hull.append(&mut upper);
+    hull
+}
+
+/// One side of the monotone chain: the points kept turning left
+fn chain(points: impl Iterator<Item = Point>) -> Vec<Point> {
+    let mut kept: Vec<Point> = Vec::new();
+    for point in points {
+        while kept.len() >= 2 && cross(kept[kept.len() - 2], kept[kept.len() - 1], point) <= 0.0 {
+            kept.pop();
+        }
+        kept.push(point);
+    }
+    kept
+}
+
+/// Concave hull by digging into the convex hull: any edge longer than
+/// `max_edge` is split at the point closest to it on its inner side, as long
+/// as the two new edges cross nothing, until no edge can be dug further
+///
+/// Every point is weighed, so none is ever left outside the result; points
+/// lying on an edge split it where they are.
+pub fn concave_hull(points: &[Point], max_edge: f64) -> Vec<Point> {
+    let mut hull = convex_hull(points);
+    if hull.len() < 3 || max_edge <= 0.0 {
+        return hull;
+    }
+    let mut candidates = points.to_vec();
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    candidates.dedup();
+    candidates.retain(|p| !hull.contains(p));
+
+    let mut edge = 0;
+    while edge < hull.len() {
+        let (a, b) = (hull[edge], hull[(edge + 1) % hull.len()]);
+        if (b.0 - a.0).hypot(b.1 - a.1) > max_edge {
+            if let Some(k) = dig(&hull, edge, &candidates) {
+                // Check the first of the two new edges next
+                hull.insert(edge + 1, candidates.swap_remove(k));
+                continue;
+            }
+        }
+        edge += 1;
+    }
+    hull
+}
+
+/// The candidate to split edge `edge` of `hull` at: the one nearest the
+/// edge among those on it or inside it, provided the new edges cross no
+/// other
+fn dig(hull: &[Point], edge: usize, candidates: &[Point]) -> Option<usize> {
+    let n = hull.len();
+    let (a, b) = (hull[edge], hull[(edge + 1) % n]);
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length_sq = dx * dx + dy * dy;
+
+    // Nearest by distance to the edge's line, among points whose foot falls
+    // on the edge; no point can then lie in the triangle cut away, nor on
+    // the edge itself
+    let (k, _) = candidates
+        .iter()
+        .enumerate()
+        .filter_map(|(k, &p)| {
+            let t = ((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length_sq;
+            let depth = cross(a, b, p);
+            (t > 0.0 && t < 1.0 && depth >= 0.0).then_some((k, depth))
+        })
+        .min_by(|x, y| x.1.total_cmp(&y.1))?;
+
+    let p = candidates[k];
+    let crosses = (0..n).filter(|&j| j != edge).any(|j| {
+        let (c, d) = (hull[j], hull[(j + 1) % n]);
+        segments_cross(a, p, c, d) || segments_cross(p, b, c, d)
+    });
+    (!crosses).then_some(k)
+}
+
+/// Move every edge of a counter-clockwise `polygon` outward by `distance`
+///
+/// Corners are mitered, except outer corners sharper than 60 degrees, which
+/// are beveled so the outline does not run out into a spike.
+pub fn offset_polygon(polygon: &[Point], distance: f64) -> Vec<Point> {
+    let n = polygon.len();
+    let normal = |a: Point, b: Point| {
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let length = dx.hypot(dy);
+        (dy / length, -dx / length)
+    };
+
+    let mut offset = Vec::with_capacity(n);
+    for i in 0..n {
+        let (prev, vertex, next) = (polygon[(i + n - 1) % n], polygon[i], polygon[(i + 1) % n]);
+        let (n1, n2) = (normal(prev, vertex), normal(vertex, next));
+        let cos = n1.0 * n2.0 + n1.1 * n2.1;
+        let convex = cross(prev, vertex, next) > 0.0;
+        if convex && cos < -0.5 {
+            offset.push((vertex.0 + n1.0 * distance, vertex.1 + n1.1 * distance));
+            offset.push((vertex.0 + n2.0 * distance, vertex.1 + n2.1 * distance));
+        } else {
+            // Inner corners keep the miter, capped at twice the distance
+            let scale = distance / (1.0 + cos).max(0.5);
+            offset.push((
+                vertex.0 + (n1.0 + n2.0) * scale,
+                vertex.1 + (n1.1 + n2.1) * scale,
+            ));
+        }
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convex_hull_drops_interior_and_collinear_points() {
+        let points = [
+            (0.0, 0.0),
+            (5.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+            (5.0, 5.0),
+            (0.0, 10.0),
+            (0.0, 0.0),
+        ];
+        assert_eq!(
+            convex_hull(&points),
+            vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]
+        );
+        assert_eq!(convex_hull(&[(1.0, 1.0), (1.0, 1.0)]), vec![(1.0, 1.0)]);
+    }
+
+    #[test]
+    fn test_concave_hull_keeps_points_on_edges() {
+        // A 10 by 2 bar of points 1 apart; digging into its long sides
+        // would cut off the points along them
+        let mut points = Vec::new();
+        for x in 0..=10 {
+            for y in 0..=2 {
+                points.push((x as f64, y as f64));
+            }
+        }
+        let hull = concave_hull(&points, 3.0);
+        assert!(hull.len() > 4);
+        for (i, &(x, y)) in hull.iter().enumerate() {
+            let next = hull[(i + 1) % hull.len()];
+            let along_side =
+                (y == next.1 && (y == 0.0 || y == 2.0)) || (x == next.0 && (x == 0.0 || x == 10.0));
+            assert!(along_side, "edge {:?} to {:?} cuts the bar", (x, y), next);
+            assert!((next.0 - x).hypot(next.1 - y) <= 3.0);
+        }
+    }
+
+    #[test]
+    fn test_offset_square() {
+        let square = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        let offset = offset_polygon(&square, 2.0);
+        let expected = [(-2.0, -2.0), (12.0, -2.0), (12.0, 12.0), (-2.0, 12.0)];
+
+        assert_eq!(offset.len(), 4);
+        for (got, want) in offset.iter().zip(expected) {
+            assert!((got.0 - want.0).abs() < 1e-9 && (got.1 - want.1).abs() < 1e-9);
+        }
+    }
+}
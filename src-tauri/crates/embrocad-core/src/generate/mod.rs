@@ -0,0 +1,196 @@
+// mod.rs - Stitch generators that build new blocks from design geometry
+
+mod fill;
+mod hull;
+mod outline;
+mod primitives;
+mod satin;
+
+pub use fill::{fill_polygon, FillOptions};
+pub use outline::OutlineOptions;
+pub use primitives::{running_polyline, Primitive};
+
+use crate::dst::{Pattern, Stitch, StitchCommand, ThreadColor};
+
+/// Longest stitch a generator will make, the most one DST record can move
+pub const MAX_STITCH_MM: f64 = 12.1;
+
+/// Error type for stitch generators
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum GenerateError {
+    #[error("Stitch length must be above 0 and at most {MAX_STITCH_MM}mm")]
+    InvalidStitchLength,
+    #[error("Shapes need a positive size")]
+    InvalidSize,
+    #[error("A polyline needs at least two distinct points")]
+    TooFewPoints,
+    #[error("Satin density must be positive")]
+    InvalidDensity,
+    #[error("Fill row spacing must be positive")]
+    InvalidSpacing,
+    #[error("A {width_mm}mm satin is wider than the {MAX_STITCH_MM}mm longest stitch")]
+    TooWide { width_mm: f64 },
+}
+
+/// Check a stitch length in mm against `MAX_STITCH_MM`
+fn check_stitch_len(stitch_len_mm: f64) -> Result<(), GenerateError> {
+    if stitch_len_mm > 0.0 && stitch_len_mm <= MAX_STITCH_MM {
+        Ok(())
+    } else {
+        Err(GenerateError::InvalidStitchLength)
+    }
+}
+
+/// Running stitches from `from` to `to`, evenly spaced at most `step`
+/// apart; `from` itself is not included and the last one lands exactly on `to`
+pub fn run_between(from: (f64, f64), to: (f64, f64), step: f64) -> impl Iterator<Item = Stitch> {
+    let length = (to.0 - from.0).hypot(to.1 - from.1);
+    let count = ((length / step).ceil() as usize).max(1);
+    (1..=count).map(move |i| {
+        let (x, y) = if i == count {
+            to
+        } else {
+            let t = i as f64 / count as f64;
+            (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t)
+        };
+        Stitch::new(x, y, StitchCommand::Stitch)
+    })
+}
+
+/// Zigzag penetrations alternating across a column of `width` centered on
+/// `stroke`, evenly spaced no more than `spacing` apart along it
+pub fn satin_along(stroke: &[(f64, f64)], width: f64, spacing: f64) -> Vec<(f64, f64)> {
+    let lengths: Vec<f64> = stroke
+        .windows(2)
+        .map(|pair| (pair[1].0 - pair[0].0).hypot(pair[1].1 - pair[0].1))
+        .collect();
+    let total: f64 = lengths.iter().sum();
+    let count = ((total / spacing).ceil() as usize).max(1);
+    let half = width / 2.0;
+
+    let (mut segment, mut segment_start) = (0, 0.0);
+    (0..=count)
+        .map(|i| {
+            let along = total * i as f64 / count as f64;
+            while segment + 1 < lengths.len() && along > segment_start + lengths[segment] {
+                segment_start += lengths[segment];
+                segment += 1;
+            }
+            let (a, b) = (stroke[segment], stroke[segment + 1]);
+            let length = lengths[segment];
+            let t = ((along - segment_start) / length).min(1.0);
+            let (ux, uy) = ((b.0 - a.0) / length, (b.1 - a.1) / length);
+            let side = if i % 2 == 0 { half } else { -half };
+            (
+                a.0 + (b.0 - a.0) * t - uy * side,
+                a.1 + (b.1 - a.1) * t + ux * side,
+            )
+        })
+        .collect()
+}
+
+/// A polyline measured along its length
+pub struct Path {
+    points: Vec<(f64, f64)>,
+    /// Distance from the start to each point
+    distances: Vec<f64>,
+}
+
+impl Path {
+    /// Measure `points`, which must not be empty
+    pub fn new(points: Vec<(f64, f64)>) -> Self {
+        let mut distances = Vec::with_capacity(points.len());
+        let mut total = 0.0;
+        for (i, point) in points.iter().enumerate() {
+            if i > 0 {
+                let previous = points[i - 1];
+                total += (point.0 - previous.0).hypot(point.1 - previous.1);
+            }
+            distances.push(total);
+        }
+        Self { points, distances }
+    }
+
+    pub fn length(&self) -> f64 {
+        self.distances.last().copied().unwrap_or(0.0)
+    }
+
+    /// The point `along` from the start, clamped to the ends
+    pub fn at(&self, along: f64) -> (f64, f64) {
+        let next = self.distances.partition_point(|&d| d < along);
+        if next == 0 {
+            return self.points[0];
+        }
+        if next == self.points.len() {
+            return self.points[next - 1];
+        }
+        let (a, b) = (self.points[next - 1], self.points[next]);
+        let span = self.distances[next] - self.distances[next - 1];
+        let t = (along - self.distances[next - 1]) / span;
+        (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+    }
+
+    /// The point at `fraction` of the length
+    pub fn at_fraction(&self, fraction: f64) -> (f64, f64) {
+        self.at(self.length() * fraction)
+    }
+}
+
+/// A pattern sewing `stitches` in order and ending where they do, ready to
+/// append to another
+fn fragment(stitches: impl IntoIterator<Item = Stitch>) -> Pattern {
+    let mut pattern = Pattern::new();
+    for stitch in stitches {
+        pattern.add_stitch(stitch.x, stitch.y, stitch.command);
+    }
+    let (x, y) = pattern.stitches.last().map_or((0.0, 0.0), |s| (s.x, s.y));
+    pattern.add_stitch(x, y, StitchCommand::End);
+    pattern.metadata.stitch_count = Some(pattern.stitches.len() as u32);
+    pattern.calculate_bounds();
+    pattern.calculate_statistics();
+    pattern.calculate_color_blocks();
+    pattern
+}
+
+/// Fragments sewn in one thread color
+pub type ColorRuns = ([u8; 3], Vec<Pattern>);
+
+/// Sew each color's fragments in turn, one block per color
+///
+/// Fragments are reached by trimmed jumps and colors are separated by color
+/// changes. Fragments without a penetration are left out, and so are colors
+/// left with none.
+pub fn join_blocks(blocks: &[ColorRuns]) -> Pattern {
+    let mut pattern = Pattern::new();
+    for (rgb, fragments) in blocks {
+        let mut first = true;
+        for fragment in fragments {
+            let stitches = &fragment.stitches;
+            if !stitches.iter().any(|s| s.command == StitchCommand::Stitch) {
+                continue;
+            }
+            if let Some(last) = pattern.stitches.last().cloned() {
+                pattern.add_stitch(last.x, last.y, StitchCommand::Trim);
+                if first {
+                    pattern.add_stitch(last.x, last.y, StitchCommand::ColorChange);
+                }
+            }
+            pattern.add_stitch(stitches[0].x, stitches[0].y, StitchCommand::Move);
+            for stitch in stitches.iter().filter(|s| s.command != StitchCommand::End) {
+                pattern.add_stitch(stitch.x, stitch.y, stitch.command);
+            }
+            first = false;
+        }
+        if !first {
+            pattern.metadata.thread_colors.push(ThreadColor::new(*rgb));
+        }
+    }
+    let (x, y) = pattern.stitches.last().map_or((0.0, 0.0), |s| (s.x, s.y));
+    pattern.add_stitch(x, y, StitchCommand::End);
+
+    pattern.metadata.stitch_count = Some(pattern.stitches.len() as u32);
+    pattern.calculate_bounds();
+    pattern.calculate_statistics();
+    pattern.calculate_color_blocks();
+    pattern
+}
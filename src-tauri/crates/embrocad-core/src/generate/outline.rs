@@ -0,0 +1,257 @@
+// outline.rs - Running-stitch outline around the hull of a design's needle penetrations
+
+use super::hull::{concave_hull, convex_hull, offset_polygon};
+use super::run_between;
+use crate::dst::{Pattern, Stitch, StitchCommand, MAX_COORDINATE, UNITS_PER_MM};
+use crate::edit::{EditError, StitchEdit, StitchPatch};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// How an outline is traced around a design
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct OutlineOptions {
+    /// Distance kept from the outermost penetrations
+    pub offset_mm: f64,
+    /// Longest stitch of the running outline
+    pub stitch_len_mm: f64,
+    /// Follow notches in the design instead of spanning them straight
+    pub concave: bool,
+    /// Concave outlines dig into edges longer than this; smaller hugs tighter
+    pub alpha_mm: f64,
+}
+
+impl Default for OutlineOptions {
+    fn default() -> Self {
+        Self {
+            offset_mm: 3.0,
+            stitch_len_mm: 2.5,
+            concave: false,
+            alpha_mm: 5.0,
+        }
+    }
+}
+
+impl Pattern {
+    /// A closed running stitch around every needle penetration, kept
+    /// `offset_mm` outside them, starting and ending on the same point
+    ///
+    /// Nothing is added to the pattern; see `generate_outline` for that.
+    pub fn outline_stitches(&self, options: &OutlineOptions) -> Result<Vec<Stitch>, EditError> {
+        if !(options.stitch_len_mm > 0.0 && options.alpha_mm > 0.0 && options.offset_mm >= 0.0) {
+            return Err(EditError::InvalidOutline);
+        }
+        let points: Vec<(f64, f64)> = self
+            .stitches
+            .iter()
+            .filter(|s| s.command == StitchCommand::Stitch)
+            .map(|s| (s.x, s.y))
+            .collect();
+        if points.is_empty() {
+            return Err(EditError::EmptyDesign);
+        }
+        let hull = if options.concave {
+            concave_hull(&points, options.alpha_mm * UNITS_PER_MM)
+        } else {
+            convex_hull(&points)
+        };
+        if hull.len() < 3 {
+            return Err(EditError::NoArea);
+        }
+
+        let polygon = offset_polygon(&hull, options.offset_mm * UNITS_PER_MM);
+        if let Some(&(x, y)) = polygon
+            .iter()
+            .find(|(x, y)| x.abs() > MAX_COORDINATE || y.abs() > MAX_COORDINATE)
+        {
+            return Err(EditError::OutOfRange { x, y });
+        }
+        let step = options.stitch_len_mm * UNITS_PER_MM;
+        let mut outline = vec![Stitch::new(
+            polygon[0].0,
+            polygon[0].1,
+            StitchCommand::Stitch,
+        )];
+        for (i, &from) in polygon.iter().enumerate() {
+            outline.extend(run_between(from, polygon[(i + 1) % polygon.len()], step));
+        }
+        Ok(outline)
+    }
+
+    /// Sew the outline of `outline_stitches` last, as a color block of its
+    /// own entered after a trim
+    pub fn generate_outline(&mut self, options: &OutlineOptions) -> Result<StitchEdit, EditError> {
+        let outline = self.outline_stitches(options)?;
+        let has_end = self
+            .stitches
+            .last()
+            .is_some_and(|s| s.command == StitchCommand::End);
+        let end = self.stitches.len() - usize::from(has_end);
+        let last = end
+            .checked_sub(1)
+            .map(|i| &self.stitches[i])
+            .expect("an outlined design has stitches");
+
+        let mut inserted = Vec::with_capacity(outline.len() + 4);
+        if last.command != StitchCommand::Trim {
+            inserted.push(Stitch::new(last.x, last.y, StitchCommand::Trim));
+        }
+        inserted.push(Stitch::new(last.x, last.y, StitchCommand::ColorChange));
+        inserted.push(Stitch::new(outline[0].x, outline[0].y, StitchCommand::Move));
+        inserted.extend(outline);
+        if has_end {
+            let finish = inserted.last().map(|s| (s.x, s.y)).unwrap_or_default();
+            inserted.push(Stitch::new(finish.0, finish.1, StitchCommand::End));
+        }
+
+        let undo = self.apply_patches(vec![StitchPatch {
+            start: end,
+            replaced: usize::from(has_end),
+            stitches: inserted,
+        }]);
+        Ok(StitchEdit {
+            warnings: Vec::new(),
+            undo,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Penetrations 2mm apart filling a plus: two 60mm by 20mm bars crossing
+    /// at the origin, so the inner corners sit at (+-10mm, +-10mm)
+    fn plus() -> Pattern {
+        let mut pattern = Pattern::new();
+        for row in -15..=15 {
+            for column in -15..=15 {
+                let (x, y) = (column as f64 * 20.0, row as f64 * 20.0);
+                if x.abs() <= 100.0 || y.abs() <= 100.0 {
+                    pattern.add_stitch(x, y, StitchCommand::Stitch);
+                }
+            }
+        }
+        pattern.add_stitch(300.0, 300.0, StitchCommand::End);
+        pattern.calculate_bounds();
+        pattern.calculate_statistics();
+        pattern.calculate_color_blocks();
+        pattern
+    }
+
+    /// Distance from (x, y) to the nearest outline stitch
+    fn distance_to(outline: &[Stitch], x: f64, y: f64) -> f64 {
+        outline
+            .iter()
+            .map(|s| (s.x - x).hypot(s.y - y))
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// Even-odd test of (x, y) against the closed outline
+    fn encloses(outline: &[Stitch], x: f64, y: f64) -> bool {
+        let mut inside = false;
+        for pair in outline.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            if (a.y > y) != (b.y > y) && x < a.x + (y - a.y) * (b.x - a.x) / (b.y - a.y) {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    #[test]
+    fn test_concave_outline_hugs_plus() {
+        let pattern = plus();
+        let options = OutlineOptions {
+            offset_mm: 2.0,
+            stitch_len_mm: 1.0,
+            ..OutlineOptions::default()
+        };
+        let convex = pattern.outline_stitches(&options).unwrap();
+        let concave = pattern
+            .outline_stitches(&OutlineOptions {
+                concave: true,
+                ..options
+            })
+            .unwrap();
+
+        for outline in [&convex, &concave] {
+            assert_eq!(
+                (outline[0].x, outline[0].y),
+                (outline.last().unwrap().x, outline.last().unwrap().y)
+            );
+            for pair in outline.windows(2) {
+                assert!((pair[1].x - pair[0].x).hypot(pair[1].y - pair[0].y) <= 10.0 + 1e-9);
+            }
+            for stitch in &pattern.stitches[..pattern.stitches.len() - 1] {
+                assert!(encloses(outline, stitch.x, stitch.y));
+            }
+        }
+
+        for (x, y) in [
+            (100.0, 100.0),
+            (-100.0, 100.0),
+            (100.0, -100.0),
+            (-100.0, -100.0),
+        ] {
+            // The hull spans each notch about 14mm past its corner...
+            assert!(distance_to(&convex, x, y) > 140.0);
+            // ...while the concave outline turns in close to it
+            assert!(distance_to(&concave, x, y) < 60.0);
+        }
+    }
+
+    #[test]
+    fn test_generate_appends_block() {
+        let mut pattern = plus();
+        let original = pattern.clone();
+        let edit = pattern
+            .generate_outline(&OutlineOptions::default())
+            .unwrap();
+
+        assert_eq!(pattern.color_blocks.len(), 2);
+        let end = original.stitches.len() - 1;
+        assert_eq!(pattern.stitches[..end], original.stitches[..end]);
+        let commands: Vec<_> = pattern.stitches[end..end + 3]
+            .iter()
+            .map(|s| s.command)
+            .collect();
+        assert_eq!(
+            commands,
+            vec![
+                StitchCommand::Trim,
+                StitchCommand::ColorChange,
+                StitchCommand::Move
+            ]
+        );
+        assert_eq!(
+            pattern.stitches.last().map(|s| s.command),
+            Some(StitchCommand::End)
+        );
+
+        pattern.apply_patches(edit.undo);
+        assert_eq!(pattern.stitches, original.stitches);
+    }
+
+    #[test]
+    fn test_rejects_designs_without_area() {
+        let mut line = Pattern::new();
+        line.add_stitch(0.0, 0.0, StitchCommand::Stitch);
+        line.add_stitch(100.0, 0.0, StitchCommand::Stitch);
+        let options = OutlineOptions::default();
+
+        assert_eq!(line.outline_stitches(&options), Err(EditError::NoArea));
+        assert_eq!(
+            Pattern::new().outline_stitches(&options),
+            Err(EditError::EmptyDesign)
+        );
+        assert_eq!(
+            plus().outline_stitches(&OutlineOptions {
+                stitch_len_mm: 0.0,
+                ..options
+            }),
+            Err(EditError::InvalidOutline)
+        );
+    }
+}
@@ -0,0 +1,296 @@
+// primitives.rs - Basic drawing shapes turned into stitch fragments
+//
+// Sizes and positions are in mm; the fragments are in the usual 0.1mm units.
+// Closed shapes start and end on the same penetration.
+
+use super::fill::{fill_polygon, FillOptions};
+use super::satin::satin_column;
+use super::{check_stitch_len, fragment, run_between, satin_along, GenerateError, MAX_STITCH_MM};
+use crate::dst::{Pattern, Stitch, StitchCommand, UNITS_PER_MM};
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+use std::f64::consts::TAU;
+
+/// A shape the drawing tools can add to a design
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum Primitive {
+    Rectangle {
+        width_mm: f64,
+        height_mm: f64,
+        stitch_len_mm: f64,
+    },
+    Circle {
+        radius_mm: f64,
+        stitch_len_mm: f64,
+    },
+    Polyline {
+        points: Vec<[f64; 2]>,
+        stitch_len_mm: f64,
+    },
+    SatinLine {
+        from: [f64; 2],
+        to: [f64; 2],
+        width_mm: f64,
+        /// Zigzag lines per mm along the line
+        density: f64,
+    },
+    SatinColumn {
+        rail_a: Vec<[f64; 2]>,
+        rail_b: Vec<[f64; 2]>,
+        /// Zigzag lines per mm along the rails
+        density: f64,
+        /// Walk the center line before the satin
+        #[cfg_attr(feature = "serde", serde(default))]
+        underlay: bool,
+    },
+    Fill {
+        outline: Vec<[f64; 2]>,
+        #[cfg_attr(feature = "serde", serde(default))]
+        holes: Vec<Vec<[f64; 2]>>,
+        #[cfg_attr(feature = "serde", serde(default))]
+        options: FillOptions,
+    },
+}
+
+impl Primitive {
+    /// Stitch the shape
+    pub fn to_pattern(&self) -> Result<Pattern, GenerateError> {
+        match self {
+            Primitive::Rectangle {
+                width_mm,
+                height_mm,
+                stitch_len_mm,
+            } => running_rectangle(*width_mm, *height_mm, *stitch_len_mm),
+            Primitive::Circle {
+                radius_mm,
+                stitch_len_mm,
+            } => running_circle(*radius_mm, *stitch_len_mm),
+            Primitive::Polyline {
+                points,
+                stitch_len_mm,
+            } => {
+                let points: Vec<_> = points.iter().map(|&[x, y]| (x, y)).collect();
+                running_polyline(&points, *stitch_len_mm)
+            }
+            Primitive::SatinLine {
+                from,
+                to,
+                width_mm,
+                density,
+            } => satin_line((from[0], from[1]), (to[0], to[1]), *width_mm, *density),
+            Primitive::SatinColumn {
+                rail_a,
+                rail_b,
+                density,
+                underlay,
+            } => {
+                let rail =
+                    |points: &[[f64; 2]]| points.iter().map(|&[x, y]| (x, y)).collect::<Vec<_>>();
+                satin_column(&rail(rail_a), &rail(rail_b), *density, *underlay)
+            }
+            Primitive::Fill {
+                outline,
+                holes,
+                options,
+            } => {
+                let ring =
+                    |points: &[[f64; 2]]| points.iter().map(|&[x, y]| (x, y)).collect::<Vec<_>>();
+                let holes: Vec<_> = holes.iter().map(|hole| ring(hole)).collect();
+                fill_polygon(&ring(outline), &holes, options)
+            }
+        }
+    }
+}
+
+/// Running stitch through `points`, no stitch longer than `stitch_len_mm`
+///
+/// Repeated points are skipped; a polyline whose last point is its first
+/// comes out closed.
+pub fn running_polyline(
+    points: &[(f64, f64)],
+    stitch_len_mm: f64,
+) -> Result<Pattern, GenerateError> {
+    check_stitch_len(stitch_len_mm)?;
+    let mut points: Vec<(f64, f64)> = points
+        .iter()
+        .map(|&(x, y)| (x * UNITS_PER_MM, y * UNITS_PER_MM))
+        .collect();
+    points.dedup();
+    if points.len() < 2 {
+        return Err(GenerateError::TooFewPoints);
+    }
+
+    let step = stitch_len_mm * UNITS_PER_MM;
+    let first = Stitch::new(points[0].0, points[0].1, StitchCommand::Stitch);
+    let runs = points
+        .windows(2)
+        .flat_map(|pair| run_between(pair[0], pair[1], step));
+    Ok(fragment(std::iter::once(first).chain(runs)))
+}
+
+/// Running stitch around a `width_mm` by `height_mm` rectangle centered on
+/// the origin, starting and ending at its top left corner
+pub fn running_rectangle(
+    width_mm: f64,
+    height_mm: f64,
+    stitch_len_mm: f64,
+) -> Result<Pattern, GenerateError> {
+    if !(width_mm > 0.0 && height_mm > 0.0) {
+        return Err(GenerateError::InvalidSize);
+    }
+    let (x, y) = (width_mm / 2.0, height_mm / 2.0);
+    running_polyline(
+        &[(-x, -y), (x, -y), (x, y), (-x, y), (-x, -y)],
+        stitch_len_mm,
+    )
+}
+
+/// Running stitch around a circle of `radius_mm` centered on the origin,
+/// starting and ending at its rightmost point
+///
+/// Penetrations sit on the circle, spaced so no chord is longer than
+/// `stitch_len_mm`.
+pub fn running_circle(radius_mm: f64, stitch_len_mm: f64) -> Result<Pattern, GenerateError> {
+    check_stitch_len(stitch_len_mm)?;
+    if radius_mm.is_nan() || radius_mm <= 0.0 {
+        return Err(GenerateError::InvalidSize);
+    }
+    // Each chord is shorter than the arc it cuts, so arcs of at most the
+    // stitch length are enough; at least a triangle
+    let count = ((TAU * radius_mm / stitch_len_mm).ceil() as usize).max(3);
+    let radius = radius_mm * UNITS_PER_MM;
+    let stitches = (0..=count).map(|i| {
+        // The last penetration repeats the first exactly
+        let angle = TAU * (i % count) as f64 / count as f64;
+        Stitch::new(
+            radius * angle.cos(),
+            radius * angle.sin(),
+            StitchCommand::Stitch,
+        )
+    });
+    Ok(fragment(stitches))
+}
+
+/// A satin column `width_mm` wide from `from` to `to`, with `density`
+/// zigzag lines per mm
+pub fn satin_line(
+    from: (f64, f64),
+    to: (f64, f64),
+    width_mm: f64,
+    density: f64,
+) -> Result<Pattern, GenerateError> {
+    if width_mm.is_nan() || width_mm <= 0.0 {
+        return Err(GenerateError::InvalidSize);
+    }
+    if density.is_nan() || density <= 0.0 {
+        return Err(GenerateError::InvalidDensity);
+    }
+    // Each zig crosses the width while moving one line along
+    if width_mm.hypot(1.0 / density) > MAX_STITCH_MM {
+        return Err(GenerateError::TooWide { width_mm });
+    }
+    if from == to {
+        return Err(GenerateError::TooFewPoints);
+    }
+    let stroke = [
+        (from.0 * UNITS_PER_MM, from.1 * UNITS_PER_MM),
+        (to.0 * UNITS_PER_MM, to.1 * UNITS_PER_MM),
+    ];
+    let points = satin_along(&stroke, width_mm * UNITS_PER_MM, UNITS_PER_MM / density);
+    Ok(fragment(
+        points
+            .into_iter()
+            .map(|(x, y)| Stitch::new(x, y, StitchCommand::Stitch)),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Needle penetrations of a fragment, without its End
+    fn penetrations(pattern: &Pattern) -> Vec<(f64, f64)> {
+        pattern
+            .stitches
+            .iter()
+            .filter(|s| s.command == StitchCommand::Stitch)
+            .map(|s| (s.x, s.y))
+            .collect()
+    }
+
+    fn longest(points: &[(f64, f64)]) -> f64 {
+        points
+            .windows(2)
+            .map(|pair| (pair[1].0 - pair[0].0).hypot(pair[1].1 - pair[0].1))
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn test_rectangle_perimeter_and_closure() {
+        // Sides of 30mm and 20mm at 4mm take 8 and 5 stitches each
+        let pattern = running_rectangle(30.0, 20.0, 4.0).unwrap();
+        let points = penetrations(&pattern);
+
+        assert_eq!(points.len(), 1 + 2 * (8 + 5));
+        assert_eq!(points[0], (-150.0, -100.0));
+        assert_eq!(points[0], *points.last().unwrap());
+        assert!(longest(&points) <= 40.0 + 1e-9);
+        assert_eq!(pattern.stitches.last().unwrap().command, StitchCommand::End);
+        assert_eq!(pattern.color_blocks.len(), 1);
+    }
+
+    #[test]
+    fn test_circle_perimeter_and_closure() {
+        // A 62.8mm circumference at 2mm takes 32 stitches
+        let pattern = running_circle(10.0, 2.0).unwrap();
+        let points = penetrations(&pattern);
+
+        assert_eq!(points.len(), 32 + 1);
+        assert_eq!(points[0], *points.last().unwrap());
+        assert!(longest(&points) <= 20.0);
+        for &(x, y) in &points {
+            assert!((x.hypot(y) - 100.0).abs() < 1e-9);
+        }
+        assert_eq!(running_circle(0.1, 2.0).unwrap().stitches.len(), 3 + 1 + 1);
+    }
+
+    #[test]
+    fn test_polyline_stitch_count() {
+        // 10mm then 5mm at 3mm take 4 and 2 stitches
+        let points = [(0.0, 0.0), (10.0, 0.0), (10.0, 0.0), (10.0, 5.0)];
+        let pattern = running_polyline(&points, 3.0).unwrap();
+        let penetrations = penetrations(&pattern);
+
+        assert_eq!(penetrations.len(), 1 + 4 + 2);
+        assert_eq!(*penetrations.last().unwrap(), (100.0, 50.0));
+        assert!(longest(&penetrations) <= 30.0 + 1e-9);
+
+        assert_eq!(
+            running_polyline(&[(1.0, 1.0), (1.0, 1.0)], 3.0).unwrap_err(),
+            GenerateError::TooFewPoints
+        );
+        assert_eq!(
+            running_polyline(&points, 15.0).unwrap_err(),
+            GenerateError::InvalidStitchLength
+        );
+    }
+
+    #[test]
+    fn test_satin_line_zigzags_across_width() {
+        // 10mm at 4 lines per mm is 40 zigzag lines
+        let pattern = satin_line((0.0, 0.0), (10.0, 0.0), 2.0, 4.0).unwrap();
+        let points = penetrations(&pattern);
+
+        assert_eq!(points.len(), 41);
+        for (i, &(x, y)) in points.iter().enumerate() {
+            assert!((x - i as f64 * 2.5).abs() < 1e-9);
+            assert_eq!(y, if i % 2 == 0 { 10.0 } else { -10.0 });
+        }
+        assert_eq!(
+            satin_line((0.0, 0.0), (10.0, 0.0), 13.0, 4.0).unwrap_err(),
+            GenerateError::TooWide { width_mm: 13.0 }
+        );
+    }
+}
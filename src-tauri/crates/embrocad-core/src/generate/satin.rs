@@ -0,0 +1,175 @@
+// satin.rs - Satin columns zigzagging between two guide rails
+
+use super::{fragment, GenerateError, Path, MAX_STITCH_MM};
+use crate::dst::{Pattern, Stitch, StitchCommand, UNITS_PER_MM};
+
+/// Stitch length of the center-walk underlay
+const UNDERLAY_STITCH_MM: f64 = 2.5;
+
+/// Zigzag between `rail_a` and `rail_b` (points in mm) with `density`
+/// lines per mm
+///
+/// Both rails are sampled at the same fraction of their own length, so
+/// rails of different lengths still meet line for line. Rails drawn in
+/// opposite directions are matched up end to end. With `underlay` a running
+/// stitch first walks the center line from the start to the far end and
+/// the satin is sewn back over it, finishing where the column began.
+pub fn satin_column(
+    rail_a: &[(f64, f64)],
+    rail_b: &[(f64, f64)],
+    density: f64,
+    underlay: bool,
+) -> Result<Pattern, GenerateError> {
+    if density.is_nan() || density <= 0.0 {
+        return Err(GenerateError::InvalidDensity);
+    }
+    let to_units = |rail: &[(f64, f64)]| {
+        let mut points: Vec<_> = rail
+            .iter()
+            .map(|&(x, y)| (x * UNITS_PER_MM, y * UNITS_PER_MM))
+            .collect();
+        points.dedup();
+        points
+    };
+    let (a, mut b) = (to_units(rail_a), to_units(rail_b));
+    if a.len() < 2 || b.len() < 2 {
+        return Err(GenerateError::TooFewPoints);
+    }
+    let distance = |p: (f64, f64), q: (f64, f64)| (p.0 - q.0).hypot(p.1 - q.1);
+    let (a_start, a_end) = (a[0], a[a.len() - 1]);
+    if distance(a_start, b[b.len() - 1]) + distance(a_end, b[0])
+        < distance(a_start, b[0]) + distance(a_end, b[b.len() - 1])
+    {
+        b.reverse();
+    }
+    let (a, b) = (Path::new(a), Path::new(b));
+
+    let mean_length = (a.length() + b.length()) / 2.0;
+    let lines = ((mean_length / UNITS_PER_MM * density).ceil() as usize).max(1);
+    let mut zigzag: Vec<(f64, f64)> = (0..=lines)
+        .map(|i| {
+            let fraction = i as f64 / lines as f64;
+            if i % 2 == 0 {
+                a.at_fraction(fraction)
+            } else {
+                b.at_fraction(fraction)
+            }
+        })
+        .collect();
+    let widest = zigzag
+        .windows(2)
+        .map(|pair| distance(pair[0], pair[1]))
+        .fold(0.0, f64::max);
+    if widest > MAX_STITCH_MM * UNITS_PER_MM {
+        return Err(GenerateError::TooWide {
+            width_mm: widest / UNITS_PER_MM,
+        });
+    }
+
+    let mut stitches = Vec::new();
+    if underlay {
+        let center = Path::new(
+            (0..=lines)
+                .map(|i| {
+                    let fraction = i as f64 / lines as f64;
+                    let (p, q) = (a.at_fraction(fraction), b.at_fraction(fraction));
+                    ((p.0 + q.0) / 2.0, (p.1 + q.1) / 2.0)
+                })
+                .collect(),
+        );
+        let step = UNDERLAY_STITCH_MM * UNITS_PER_MM;
+        let walk = ((center.length() / step).ceil() as usize).max(1);
+        stitches.extend((0..=walk).map(|i| {
+            let (x, y) = center.at_fraction(i as f64 / walk as f64);
+            Stitch::new(x, y, StitchCommand::Stitch)
+        }));
+        zigzag.reverse();
+    }
+    stitches.extend(
+        zigzag
+            .into_iter()
+            .map(|(x, y)| Stitch::new(x, y, StitchCommand::Stitch)),
+    );
+    Ok(fragment(stitches))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn penetrations(pattern: &Pattern) -> Vec<(f64, f64)> {
+        pattern
+            .stitches
+            .iter()
+            .filter(|s| s.command == StitchCommand::Stitch)
+            .map(|s| (s.x, s.y))
+            .collect()
+    }
+
+    #[test]
+    fn test_parallel_rails_space_evenly() {
+        // 50mm rails 4mm apart at 5 lines per mm
+        let pattern = satin_column(
+            &[(0.0, 0.0), (20.0, 0.0), (50.0, 0.0)],
+            &[(0.0, 4.0), (50.0, 4.0)],
+            5.0,
+            false,
+        )
+        .unwrap();
+        let points = penetrations(&pattern);
+
+        assert_eq!(points.len(), 251);
+        for (i, pair) in points.windows(2).enumerate() {
+            let step = pair[1].0 - pair[0].0;
+            assert!((step - 2.0).abs() < 0.02, "line {i} advances {step}");
+            assert_eq!((pair[0].1 - pair[1].1).abs(), 40.0);
+        }
+    }
+
+    #[test]
+    fn test_rails_of_different_lengths_and_directions() {
+        // A 40mm rail against a 20mm one drawn the other way round
+        let pattern = satin_column(
+            &[(0.0, 0.0), (40.0, 0.0)],
+            &[(30.0, 5.0), (10.0, 5.0)],
+            2.0,
+            false,
+        )
+        .unwrap();
+        let points = penetrations(&pattern);
+
+        assert_eq!(points.len(), 61);
+        assert_eq!(points[0], (0.0, 0.0));
+        assert_eq!(points[1].1, 50.0);
+        assert!(points[1].0 < 110.0);
+        // Both rails are spaced in proportion to their length
+        assert!((points[2].0 - 400.0 / 60.0 * 2.0).abs() < 1e-9);
+        assert!((points[3].0 - (100.0 + 200.0 / 60.0 * 3.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_center_walk_underlay_comes_first() {
+        let rails = ([(0.0, 0.0), (10.0, 0.0)], [(0.0, 4.0), (10.0, 4.0)]);
+        let plain = satin_column(&rails.0, &rails.1, 4.0, false).unwrap();
+        let pattern = satin_column(&rails.0, &rails.1, 4.0, true).unwrap();
+        let points = penetrations(&pattern);
+
+        // 10mm of walk at 2.5mm, along the middle of the column
+        let walk = &points[..5];
+        for (i, &(x, y)) in walk.iter().enumerate() {
+            assert!((x - i as f64 * 25.0).abs() < 1e-9);
+            assert_eq!(y, 20.0);
+        }
+        // Then the same satin, sewn back towards the start
+        let mut satin = penetrations(&plain);
+        satin.reverse();
+        assert_eq!(points[5..], satin[..]);
+
+        // Zigs 14mm across and 0.25mm along are too long to sew
+        let too_wide = satin_column(&rails.0, &[(0.0, 14.0), (10.0, 14.0)], 4.0, false);
+        assert!(matches!(
+            too_wide,
+            Err(GenerateError::TooWide { width_mm }) if (width_mm - 14.0f64.hypot(0.25)).abs() < 1e-9
+        ));
+    }
+}
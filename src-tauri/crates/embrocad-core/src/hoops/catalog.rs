@@ -0,0 +1,91 @@
+// catalog.rs - Standard embroidery hoop sizes by machine brand
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum HoopShape {
+    Rectangle,
+    /// Tubular round hoops; `width_mm` and `height_mm` both hold the diameter
+    Round,
+}
+
+/// Inner sewing field of a hoop, in mm
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Hoop {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub brand: &'static str,
+    pub shape: HoopShape,
+    pub width_mm: f64,
+    pub height_mm: f64,
+}
+
+impl Hoop {
+    pub fn area_mm2(&self) -> f64 {
+        match self.shape {
+            HoopShape::Rectangle => self.width_mm * self.height_mm,
+            HoopShape::Round => std::f64::consts::PI * self.width_mm * self.height_mm / 4.0,
+        }
+    }
+}
+
+const fn rectangle(
+    id: &'static str,
+    name: &'static str,
+    brand: &'static str,
+    w: f64,
+    h: f64,
+) -> Hoop {
+    Hoop {
+        id,
+        name,
+        brand,
+        shape: HoopShape::Rectangle,
+        width_mm: w,
+        height_mm: h,
+    }
+}
+
+const fn round(id: &'static str, name: &'static str, diameter: f64) -> Hoop {
+    Hoop {
+        id,
+        name,
+        brand: "Tajima",
+        shape: HoopShape::Round,
+        width_mm: diameter,
+        height_mm: diameter,
+    }
+}
+
+/// Hoops offered in the UI, in no particular order
+pub const HOOPS: &[Hoop] = &[
+    rectangle("brother_4x4", "Brother 4x4\"", "Brother", 100.0, 100.0),
+    rectangle("brother_5x7", "Brother 5x7\"", "Brother", 130.0, 180.0),
+    rectangle("brother_6x10", "Brother 6x10\"", "Brother", 160.0, 260.0),
+    rectangle("brother_8x12", "Brother 8x12\"", "Brother", 200.0, 300.0),
+    rectangle("janome_sq14", "Janome SQ14", "Janome", 140.0, 140.0),
+    rectangle("janome_re20", "Janome RE20", "Janome", 170.0, 200.0),
+    rectangle("janome_sq23", "Janome SQ23", "Janome", 230.0, 230.0),
+    rectangle("janome_re28", "Janome RE28", "Janome", 200.0, 280.0),
+    round("tajima_round_90", "Tajima tubular 9cm", 90.0),
+    round("tajima_round_120", "Tajima tubular 12cm", 120.0),
+    round("tajima_round_150", "Tajima tubular 15cm", 150.0),
+    round("tajima_round_180", "Tajima tubular 18cm", 180.0),
+    round("tajima_round_210", "Tajima tubular 21cm", 210.0),
+    rectangle(
+        "tajima_360x360",
+        "Tajima tubular 36x36cm",
+        "Tajima",
+        360.0,
+        360.0,
+    ),
+];
+
+/// Look up a catalog hoop by its id
+pub fn find_hoop(id: &str) -> Option<&'static Hoop> {
+    HOOPS.iter().find(|hoop| hoop.id == id)
+}
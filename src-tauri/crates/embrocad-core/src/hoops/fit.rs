@@ -0,0 +1,178 @@
+// fit.rs - Check design bounds against hoop sewing fields
+
+use crate::dst::{Bounds, UNITS_PER_MM};
+use crate::hoops::catalog::{Hoop, HoopShape, HOOPS};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// Keep the needle this far from the hoop frame
+pub const DEFAULT_HOOP_MARGIN_MM: f64 = 5.0;
+
+/// Result of placing a design in a hoop
+///
+/// The design origin sits at the hoop centre, as machines position DST and
+/// most other formats. Clearances are in mm after the safety margin, so a
+/// negative value is how far the design overruns that side.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct HoopFit {
+    pub hoop: Hoop,
+    pub fits: bool,
+    pub margin_mm: f64,
+    pub left_mm: f64,
+    pub right_mm: f64,
+    pub top_mm: f64,
+    pub bottom_mm: f64,
+}
+
+/// Place `bounds` (0.1mm units, Y-down) in `hoop` with `margin_mm` of clearance
+pub fn check_hoop_fit(bounds: &Bounds, hoop: &Hoop, margin_mm: f64) -> HoopFit {
+    let half_width = hoop.width_mm / 2.0 - margin_mm;
+    let half_height = hoop.height_mm / 2.0 - margin_mm;
+    let (min_x, max_x) = (bounds.min_x / UNITS_PER_MM, bounds.max_x / UNITS_PER_MM);
+    let (min_y, max_y) = (bounds.min_y / UNITS_PER_MM, bounds.max_y / UNITS_PER_MM);
+
+    let left_mm = half_width + min_x;
+    let right_mm = half_width - max_x;
+    let top_mm = half_height + min_y;
+    let bottom_mm = half_height - max_y;
+
+    let inside_rectangle = left_mm >= 0.0 && right_mm >= 0.0 && top_mm >= 0.0 && bottom_mm >= 0.0;
+    let fits = match hoop.shape {
+        HoopShape::Rectangle => inside_rectangle,
+        HoopShape::Round => {
+            // Every corner of the bounding box must clear the circle
+            let reach_x = min_x.abs().max(max_x.abs());
+            let reach_y = min_y.abs().max(max_y.abs());
+            inside_rectangle && reach_x.hypot(reach_y) <= half_width
+        }
+    };
+
+    HoopFit {
+        hoop: hoop.clone(),
+        fits,
+        margin_mm,
+        left_mm,
+        right_mm,
+        top_mm,
+        bottom_mm,
+    }
+}
+
+/// The rectangle a design centred in `hoop` may fill with `margin_mm` of
+/// clearance, in 0.1mm units; for round hoops the largest square inside
+/// the circle
+pub fn hoop_field(hoop: &Hoop, margin_mm: f64) -> Bounds {
+    let mut half_width = hoop.width_mm / 2.0 - margin_mm;
+    let mut half_height = hoop.height_mm / 2.0 - margin_mm;
+    if hoop.shape == HoopShape::Round {
+        half_width *= std::f64::consts::FRAC_1_SQRT_2;
+        half_height *= std::f64::consts::FRAC_1_SQRT_2;
+    }
+    Bounds {
+        min_x: -half_width * UNITS_PER_MM,
+        min_y: -half_height * UNITS_PER_MM,
+        max_x: half_width * UNITS_PER_MM,
+        max_y: half_height * UNITS_PER_MM,
+    }
+}
+
+/// Every catalog hoop the design fits in, smallest first
+pub fn suggest_hoops(bounds: &Bounds, margin_mm: f64) -> Vec<HoopFit> {
+    let mut fits: Vec<HoopFit> = HOOPS
+        .iter()
+        .map(|hoop| check_hoop_fit(bounds, hoop, margin_mm))
+        .filter(|fit| fit.fits)
+        .collect();
+    fits.sort_by(|a, b| a.hoop.area_mm2().total_cmp(&b.hoop.area_mm2()));
+    fits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hoops::catalog::find_hoop;
+
+    /// A centred design of the given size in mm
+    fn centred(width_mm: f64, height_mm: f64) -> Bounds {
+        Bounds {
+            min_x: -width_mm * 5.0,
+            min_y: -height_mm * 5.0,
+            max_x: width_mm * 5.0,
+            max_y: height_mm * 5.0,
+        }
+    }
+
+    #[test]
+    fn test_catalog_ids_are_unique() {
+        for (i, hoop) in HOOPS.iter().enumerate() {
+            assert!(HOOPS[i + 1..].iter().all(|other| other.id != hoop.id));
+        }
+        assert!(find_hoop("brother_5x7").is_some());
+        assert!(find_hoop("missing").is_none());
+    }
+
+    #[test]
+    fn test_fit_respects_margin() {
+        let hoop = find_hoop("brother_4x4").unwrap();
+
+        let fit = check_hoop_fit(&centred(90.0, 80.0), hoop, DEFAULT_HOOP_MARGIN_MM);
+        assert!(fit.fits);
+        assert_eq!((fit.left_mm, fit.right_mm), (0.0, 0.0));
+        assert_eq!((fit.top_mm, fit.bottom_mm), (5.0, 5.0));
+
+        let fit = check_hoop_fit(&centred(95.0, 80.0), hoop, DEFAULT_HOOP_MARGIN_MM);
+        assert!(!fit.fits);
+        assert_eq!(fit.right_mm, -2.5);
+        assert!(check_hoop_fit(&centred(95.0, 80.0), hoop, 0.0).fits);
+    }
+
+    #[test]
+    fn test_off_centre_design() {
+        // 40mm wide but shifted 20mm right of the origin
+        let bounds = Bounds {
+            min_x: 0.0,
+            min_y: -100.0,
+            max_x: 400.0,
+            max_y: 100.0,
+        };
+        let fit = check_hoop_fit(&bounds, find_hoop("brother_4x4").unwrap(), 5.0);
+
+        assert!(fit.fits);
+        assert_eq!((fit.left_mm, fit.right_mm), (45.0, 5.0));
+    }
+
+    #[test]
+    fn test_round_hoop_checks_corners() {
+        // An 80mm square fits a 120mm circle's bounding box, not the circle
+        let hoop = find_hoop("tajima_round_120").unwrap();
+        assert!(!check_hoop_fit(&centred(80.0, 80.0), hoop, 5.0).fits);
+        assert!(check_hoop_fit(&centred(70.0, 70.0), hoop, 5.0).fits);
+    }
+
+    #[test]
+    fn test_hoop_field() {
+        let field = hoop_field(find_hoop("brother_5x7").unwrap(), 5.0);
+        assert_eq!(
+            (field.min_x, field.min_y, field.max_x, field.max_y),
+            (-600.0, -850.0, 600.0, 850.0)
+        );
+
+        // The corners of a round hoop's square sit on the margin circle
+        let field = hoop_field(find_hoop("tajima_round_120").unwrap(), 5.0);
+        assert!((field.max_x.hypot(field.max_y) - 550.0).abs() < 1e-9);
+        assert_eq!(field.width(), field.height());
+    }
+
+    #[test]
+    fn test_suggest_hoops_smallest_first() {
+        let suggestions = suggest_hoops(&centred(150.0, 190.0), DEFAULT_HOOP_MARGIN_MM);
+        let ids: Vec<_> = suggestions.iter().map(|fit| fit.hoop.id).collect();
+
+        assert_eq!(ids[0], "janome_re20");
+        assert!(!ids.contains(&"brother_5x7"));
+        assert!(suggestions
+            .windows(2)
+            .all(|w| w[0].hoop.area_mm2() <= w[1].hoop.area_mm2()));
+    }
+}
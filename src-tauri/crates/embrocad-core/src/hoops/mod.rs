@@ -0,0 +1,7 @@
+// mod.rs - Hoop module exports for the standard hoop catalog and fit checks
+
+mod catalog;
+mod fit;
+
+pub use catalog::find_hoop;
+pub use fit::{check_hoop_fit, hoop_field, suggest_hoops, HoopFit, DEFAULT_HOOP_MARGIN_MM};
@@ -0,0 +1,325 @@
+// compress.rs - Decompressor for the Huffman-coded LZ streams used by HUS/VIP files
+
+/// Error type for stream decompression
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum DecompressError {
+    #[error("Invalid Huffman table at bit {0}")]
+    InvalidTable(usize),
+    #[error("Invalid Huffman code at bit {0}")]
+    UnknownCode(usize),
+    #[error("Back-reference before start of output at bit {0}")]
+    DistanceOutOfRange(usize),
+}
+
+/// Widest code the lookup tables support; codes are looked up from a 16-bit window
+const MAX_CODE_WIDTH: u8 = 16;
+
+/// Symbol 510 terminates the stream
+const END_SYMBOL: u16 = 510;
+
+/// Length symbols start at 256 and encode match lengths from 3 upward
+const LENGTH_SYMBOL_BIAS: u16 = 253;
+
+/// Canonical-order lookup table built from code lengths; ties go to the lowest symbol
+struct Huffman {
+    default: u16,
+    lengths: Vec<u8>,
+    table: Option<Vec<u16>>,
+    width: u8,
+}
+
+impl Huffman {
+    /// A degenerate table that always yields one symbol without consuming bits
+    fn constant(value: u16) -> Self {
+        Self {
+            default: value,
+            lengths: Vec::new(),
+            table: None,
+            width: 0,
+        }
+    }
+
+    fn from_lengths(lengths: Vec<u8>, bit: usize) -> Result<Self, DecompressError> {
+        let width = lengths.iter().copied().max().unwrap_or(0);
+        if width == 0 || width > MAX_CODE_WIDTH {
+            return Err(DecompressError::InvalidTable(bit));
+        }
+
+        let mut table = Vec::with_capacity(1 << width);
+        for bit_length in 1..=width {
+            let size = 1usize << (width - bit_length);
+            for (symbol, _) in lengths.iter().enumerate().filter(|(_, &l)| l == bit_length) {
+                table.extend(std::iter::repeat_n(symbol as u16, size));
+            }
+        }
+
+        Ok(Self {
+            default: 0,
+            lengths,
+            table: Some(table),
+            width,
+        })
+    }
+
+    /// Look up the symbol for a 16-bit window, returning it with its code length
+    fn lookup(&self, window: u32, bit: usize) -> Result<(u16, u8), DecompressError> {
+        let Some(table) = &self.table else {
+            return Ok((self.default, 0));
+        };
+        let symbol = *table
+            .get((window >> (MAX_CODE_WIDTH - self.width)) as usize)
+            .ok_or(DecompressError::UnknownCode(bit))?;
+        Ok((symbol, self.lengths[symbol as usize]))
+    }
+}
+
+/// MSB-first bit reader; reads past the end yield zero bits
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn peek(&self, count: u8) -> u32 {
+        let mut value = 0u32;
+        for i in 0..count as usize {
+            let bit = self.position + i;
+            let byte = self.data.get(bit / 8).copied().unwrap_or(0);
+            value = (value << 1) | ((byte >> (7 - bit % 8)) & 1) as u32;
+        }
+        value
+    }
+
+    fn slide(&mut self, count: u8) {
+        self.position += count as usize;
+    }
+
+    fn pop(&mut self, count: u8) -> u32 {
+        let value = self.peek(count);
+        self.slide(count);
+        value
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.position >= self.data.len() * 8
+    }
+}
+
+struct Decompressor<'a> {
+    bits: BitReader<'a>,
+    block_elements: i64,
+    characters: Huffman,
+    distances: Huffman,
+}
+
+impl<'a> Decompressor<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            bits: BitReader { data, position: 0 },
+            block_elements: 0,
+            characters: Huffman::constant(0),
+            distances: Huffman::constant(0),
+        }
+    }
+
+    /// 3-bit value extended by a unary run of 1 bits when all three are set
+    fn read_variable_length(&mut self) -> u8 {
+        let mut value = self.bits.pop(3) as u8;
+        if value == 7 {
+            for _ in 0..13 {
+                if self.bits.pop(1) == 0 {
+                    break;
+                }
+                value += 1;
+            }
+        }
+        value
+    }
+
+    fn load_character_length_huffman(&mut self) -> Result<Huffman, DecompressError> {
+        let count = self.bits.pop(5) as usize;
+        if count == 0 {
+            return Ok(Huffman::constant(self.bits.pop(5) as u16));
+        }
+
+        let mut lengths = vec![0u8; count];
+        let mut index = 0;
+        while index < count {
+            // After the third length, a 2-bit count of zero lengths follows
+            if index == 3 {
+                index += self.bits.pop(2) as usize;
+            }
+            let slot = lengths
+                .get_mut(index)
+                .ok_or(DecompressError::InvalidTable(self.bits.position))?;
+            *slot = self.read_variable_length();
+            index += 1;
+        }
+        Huffman::from_lengths(lengths, self.bits.position)
+    }
+
+    fn load_character_huffman(
+        &mut self,
+        length_huffman: &Huffman,
+    ) -> Result<Huffman, DecompressError> {
+        let count = self.bits.pop(9) as usize;
+        if count == 0 {
+            return Ok(Huffman::constant(self.bits.pop(9) as u16));
+        }
+
+        let mut lengths = vec![0u8; count];
+        let mut index = 0;
+        while index < count {
+            let (code, width) = length_huffman.lookup(self.bits.peek(16), self.bits.position)?;
+            self.bits.slide(width);
+
+            // Codes 0-2 are runs of zero lengths; larger codes are a length plus 2
+            match code {
+                0 => index += 1,
+                1 => index += 3 + self.bits.pop(4) as usize,
+                2 => index += 20 + self.bits.pop(9) as usize,
+                _ => {
+                    lengths[index] = u8::try_from(code - 2)
+                        .map_err(|_| DecompressError::InvalidTable(self.bits.position))?;
+                    index += 1;
+                }
+            }
+        }
+        Huffman::from_lengths(lengths, self.bits.position)
+    }
+
+    fn load_distance_huffman(&mut self) -> Result<Huffman, DecompressError> {
+        let count = self.bits.pop(5) as usize;
+        if count == 0 {
+            return Ok(Huffman::constant(self.bits.pop(5) as u16));
+        }
+
+        let lengths = (0..count).map(|_| self.read_variable_length()).collect();
+        Huffman::from_lengths(lengths, self.bits.position)
+    }
+
+    fn load_block(&mut self) -> Result<(), DecompressError> {
+        self.block_elements = self.bits.pop(16) as i64;
+        let length_huffman = self.load_character_length_huffman()?;
+        self.characters = self.load_character_huffman(&length_huffman)?;
+        self.distances = self.load_distance_huffman()?;
+        Ok(())
+    }
+
+    fn next_token(&mut self) -> Result<u16, DecompressError> {
+        if self.block_elements <= 0 {
+            self.load_block()?;
+        }
+        self.block_elements -= 1;
+
+        let (symbol, width) = self
+            .characters
+            .lookup(self.bits.peek(16), self.bits.position)?;
+        self.bits.slide(width);
+        Ok(symbol)
+    }
+
+    fn next_distance(&mut self) -> Result<usize, DecompressError> {
+        let (symbol, width) = self
+            .distances
+            .lookup(self.bits.peek(16), self.bits.position)?;
+        self.bits.slide(width);
+        if symbol == 0 {
+            return Ok(0);
+        }
+
+        let extra = (symbol - 1) as u8;
+        if extra > 30 {
+            return Err(DecompressError::DistanceOutOfRange(self.bits.position));
+        }
+        Ok((1usize << extra) + self.bits.pop(extra) as usize)
+    }
+}
+
+/// Decompress a HUS/VIP stream, producing at most `expected_size` bytes
+pub fn decompress(data: &[u8], expected_size: usize) -> Result<Vec<u8>, DecompressError> {
+    let mut decompressor = Decompressor::new(data);
+    let mut output = Vec::with_capacity(expected_size.min(data.len() * 8));
+
+    while !decompressor.bits.is_exhausted() && output.len() < expected_size {
+        let token = decompressor.next_token()?;
+        if token < 256 {
+            output.push(token as u8);
+            continue;
+        }
+        if token == END_SYMBOL {
+            break;
+        }
+
+        let length = (token - LENGTH_SYMBOL_BIAS) as usize;
+        let back = decompressor.next_distance()? + 1;
+        let start = output
+            .len()
+            .checked_sub(back)
+            .ok_or(DecompressError::DistanceOutOfRange(
+                decompressor.bits.position,
+            ))?;
+
+        // Copy byte by byte so overlapping references repeat freshly written data
+        for i in start..start + length {
+            output.push(output[i]);
+        }
+    }
+
+    output.truncate(expected_size);
+    Ok(output)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// Produce a stream that stores every byte as a literal.
+    ///
+    /// The block header declares a constant length table of 10, which gives all
+    /// 256 literals an 8-bit code mapping each byte to itself.
+    pub fn compress_literals(data: &[u8]) -> Vec<u8> {
+        let mut out = (data.len() as u16).to_be_bytes().to_vec();
+        out.extend_from_slice(&[0x02, 0xA0, 0x01, 0xFE]);
+        out.extend_from_slice(data);
+        out
+    }
+
+    #[test]
+    fn test_literal_round_trip() {
+        let data: Vec<u8> = (0..=255u8).chain(0..=255u8).collect();
+        let compressed = compress_literals(&data);
+
+        assert_eq!(decompress(&compressed, data.len()).unwrap(), data);
+        assert_eq!(decompress(&compressed, 10).unwrap(), data[..10]);
+    }
+
+    #[test]
+    fn test_huffman_table_lookup() {
+        // Symbol 1 has a 1-bit code (0), symbols 0 and 2 have 2-bit codes (10, 11)
+        let huffman = Huffman::from_lengths(vec![2, 1, 2], 0).unwrap();
+
+        assert_eq!(huffman.lookup(0x0000, 0), Ok((1, 1)));
+        assert_eq!(huffman.lookup(0x8000, 0), Ok((0, 2)));
+        assert_eq!(huffman.lookup(0xC000, 0), Ok((2, 2)));
+        assert!(Huffman::from_lengths(vec![0, 0], 0).is_err());
+        assert!(Huffman::from_lengths(vec![17], 0).is_err());
+    }
+
+    #[test]
+    fn test_corrupt_streams_do_not_panic() {
+        // Deterministic pseudo-random garbage of many lengths
+        let mut seed = 0x2545F491u32;
+        for length in 0..200 {
+            let data: Vec<u8> = (0..length)
+                .map(|_| {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 17;
+                    seed ^= seed << 5;
+                    seed as u8
+                })
+                .collect();
+            let _ = decompress(&data, 4096);
+        }
+    }
+}
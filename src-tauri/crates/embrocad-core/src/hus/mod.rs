@@ -0,0 +1,7 @@
+// mod.rs - HUS/VIP module exports for the Husqvarna Viking format parsers
+
+mod compress;
+mod palette;
+mod parser;
+
+pub use parser::{parse_hus, parse_vip};
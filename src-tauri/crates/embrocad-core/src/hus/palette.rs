@@ -0,0 +1,49 @@
+// palette.rs - Standard Husqvarna Viking thread palette
+
+use crate::dst::ThreadColor;
+
+/// Husqvarna palette, indexed by the color numbers stored in HUS files
+pub const HUS_PALETTE: [([u8; 3], &str, &str); 29] = [
+    ([0x00, 0x00, 0x00], "Black", "026"),
+    ([0x00, 0x00, 0xE7], "Blue", "005"),
+    ([0x00, 0xC6, 0x00], "Green", "002"),
+    ([0xFF, 0x00, 0x00], "Red", "014"),
+    ([0x84, 0x00, 0x84], "Purple", "008"),
+    ([0xFF, 0xFF, 0x00], "Yellow", "020"),
+    ([0x84, 0x84, 0x84], "Grey", "024"),
+    ([0x84, 0x84, 0xE7], "Light Blue", "006"),
+    ([0x00, 0xFF, 0x84], "Light Green", "003"),
+    ([0xFF, 0x7B, 0x31], "Orange", "017"),
+    ([0xFF, 0x8C, 0xA5], "Pink", "011"),
+    ([0x84, 0x52, 0x00], "Brown", "028"),
+    ([0xFF, 0xFF, 0xFF], "White", "022"),
+    ([0x00, 0x00, 0x84], "Dark Blue", "004"),
+    ([0x00, 0x84, 0x00], "Dark Green", "001"),
+    ([0x7B, 0x00, 0x00], "Dark Red", "013"),
+    ([0xFF, 0x63, 0x84], "Light Red", "015"),
+    ([0x52, 0x29, 0x52], "Dark Purple", "007"),
+    ([0xFF, 0x00, 0xFF], "Light Purple", "009"),
+    ([0xFF, 0xDE, 0x00], "Dark Yellow", "019"),
+    ([0xFF, 0xFF, 0x9C], "Light Yellow", "021"),
+    ([0x52, 0x52, 0x52], "Dark Grey", "025"),
+    ([0xD6, 0xD6, 0xD6], "Light Grey", "023"),
+    ([0xFF, 0x52, 0x08], "Dark Orange", "016"),
+    ([0xFF, 0x9C, 0x5A], "Light Orange", "018"),
+    ([0xFF, 0x52, 0xB5], "Dark Pink", "010"),
+    ([0xFF, 0xC6, 0xDE], "Light Pink", "012"),
+    ([0x52, 0x31, 0x00], "Dark Brown", "027"),
+    ([0xB5, 0xA5, 0x84], "Light Brown", "029"),
+];
+
+/// Look up a Husqvarna palette entry, wrapping out-of-range indices
+pub fn hus_thread(index: u16) -> ThreadColor {
+    let (rgb, name, code) = HUS_PALETTE[index as usize % HUS_PALETTE.len()];
+
+    ThreadColor {
+        rgb,
+        description: Some(name.to_string()),
+        catalog_number: Some(code.to_string()),
+        needle: None,
+        basting: false,
+    }
+}
@@ -0,0 +1,248 @@
+// parser.rs - Husqvarna Viking HUS and VIP embroidery file format parsers
+
+use crate::binary::ByteReader;
+use crate::dst::{Pattern, StitchCommand};
+use crate::hus::compress::{decompress, DecompressError};
+use crate::hus::palette::hus_thread;
+
+/// Offset of the color index table in HUS files
+const HUS_COLOR_TABLE_OFFSET: usize = 42;
+
+/// Attribute codes in the command stream
+const ATTR_STITCH: u8 = 0x80;
+const ATTR_JUMP: u8 = 0x81;
+const ATTR_COLOR_CHANGE: u8 = 0x84;
+const ATTR_TRIM: u8 = 0x88;
+const ATTR_END: u8 = 0x90;
+
+/// Error type for HUS/VIP parsing
+#[derive(Debug, thiserror::Error)]
+pub enum HusError {
+    #[error("Invalid HUS/VIP file: insufficient data")]
+    InsufficientData,
+    #[error("Invalid HUS/VIP file: stream offsets out of order")]
+    InvalidOffsets,
+    #[error("Corrupt {0} stream: {1}")]
+    CorruptStream(&'static str, DecompressError),
+}
+
+/// Fields shared by the HUS and VIP headers
+struct Header {
+    stitch_count: usize,
+    color_count: usize,
+    attribute_offset: usize,
+    x_offset: usize,
+    y_offset: usize,
+}
+
+fn read_header(reader: &mut ByteReader) -> Option<Header> {
+    let _magic = reader.u32_le()?;
+    let stitch_count = reader.u32_le()? as usize;
+    let color_count = reader.u32_le()? as usize;
+    // Declared extents (+X, +Y, -X, -Y) are recomputed from the stitches
+    reader.skip(8)?;
+    let attribute_offset = reader.u32_le()? as usize;
+    let x_offset = reader.u32_le()? as usize;
+    let y_offset = reader.u32_le()? as usize;
+    // 8-byte design name and an unknown 16-bit field
+    reader.skip(10)?;
+
+    Some(Header {
+        stitch_count,
+        color_count,
+        attribute_offset,
+        x_offset,
+        y_offset,
+    })
+}
+
+/// Decompress the attribute, X and Y streams and rebuild the stitch list
+fn parse_streams(data: &[u8], header: &Header, pattern: &mut Pattern) -> Result<(), HusError> {
+    if !(header.attribute_offset <= header.x_offset
+        && header.x_offset <= header.y_offset
+        && header.y_offset <= data.len())
+    {
+        return Err(HusError::InvalidOffsets);
+    }
+
+    let expand = |name, range: std::ops::Range<usize>| {
+        decompress(&data[range], header.stitch_count).map_err(|e| HusError::CorruptStream(name, e))
+    };
+    let attributes = expand("attribute", header.attribute_offset..header.x_offset)?;
+    let xs = expand("X", header.x_offset..header.y_offset)?;
+    let ys = expand("Y", header.y_offset..data.len())?;
+
+    let mut current_x = 0.0f64;
+    let mut current_y = 0.0f64;
+
+    for ((&attribute, &bx), &by) in attributes.iter().zip(&xs).zip(&ys) {
+        let dx = bx as i8 as f64;
+        let dy = -(by as i8 as f64);
+
+        match attribute {
+            ATTR_STITCH | ATTR_JUMP | ATTR_COLOR_CHANGE => {
+                current_x += dx;
+                current_y += dy;
+                let command = match attribute {
+                    ATTR_STITCH => StitchCommand::Stitch,
+                    ATTR_JUMP => StitchCommand::Move,
+                    _ => StitchCommand::ColorChange,
+                };
+                pattern.add_stitch(current_x, current_y, command);
+            }
+            ATTR_TRIM => {
+                if dx != 0.0 || dy != 0.0 {
+                    current_x += dx;
+                    current_y += dy;
+                    pattern.add_stitch(current_x, current_y, StitchCommand::Move);
+                }
+                pattern.add_stitch(current_x, current_y, StitchCommand::Trim);
+            }
+            ATTR_END => break,
+            _ => {}
+        }
+    }
+
+    pattern.add_stitch(current_x, current_y, StitchCommand::End);
+    Ok(())
+}
+
+fn finish(mut pattern: Pattern, header: &Header) -> Pattern {
+    pattern.metadata.stitch_count = Some(header.stitch_count as u32);
+    pattern.metadata.color_count = Some(header.color_count.saturating_sub(1) as u32);
+
+    // Calculate bounds, statistics and color blocks
+    pattern.calculate_bounds();
+    pattern.calculate_statistics();
+    pattern.calculate_color_blocks();
+    pattern
+}
+
+/// Parse a HUS file from bytes
+pub fn parse_hus(data: &[u8]) -> Result<Pattern, HusError> {
+    let mut reader = ByteReader::new(data);
+    let header = read_header(&mut reader).ok_or(HusError::InsufficientData)?;
+
+    let mut pattern = Pattern::new();
+
+    // Color indices into the Husqvarna palette follow the header
+    reader.seek(HUS_COLOR_TABLE_OFFSET);
+    for _ in 0..header.color_count {
+        let index = reader.u16_le().ok_or(HusError::InsufficientData)?;
+        pattern.metadata.thread_colors.push(hus_thread(index));
+    }
+
+    parse_streams(data, &header, &mut pattern)?;
+    Ok(finish(pattern, &header))
+}
+
+/// Parse a VIP file from bytes
+///
+/// VIP shares the HUS stream layout, but stores its colors as an obfuscated RGB
+/// table that is not decoded here, so blocks fall back to the default palette.
+pub fn parse_vip(data: &[u8]) -> Result<Pattern, HusError> {
+    let mut reader = ByteReader::new(data);
+    let header = read_header(&mut reader).ok_or(HusError::InsufficientData)?;
+
+    let mut pattern = Pattern::new();
+    parse_streams(data, &header, &mut pattern)?;
+    Ok(finish(pattern, &header))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hus::compress::tests::compress_literals;
+
+    const ATTRIBUTES: [u8; 7] = [
+        ATTR_STITCH,
+        ATTR_STITCH,
+        ATTR_JUMP,
+        ATTR_COLOR_CHANGE,
+        ATTR_STITCH,
+        ATTR_TRIM,
+        ATTR_END,
+    ];
+    const XS: [u8; 7] = [10, 10, 100, 0, 5, 0, 0];
+    const YS: [u8; 7] = [0, 0xF6, 0, 0, 0, 0, 0];
+
+    fn build(magic: u32, colors: &[u16], header_size: usize) -> Vec<u8> {
+        let streams = [
+            compress_literals(&ATTRIBUTES),
+            compress_literals(&XS),
+            compress_literals(&YS),
+        ];
+        let attribute_offset = header_size + colors.len() * 2;
+        let x_offset = attribute_offset + streams[0].len();
+        let y_offset = x_offset + streams[1].len();
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&magic.to_le_bytes());
+        data.extend_from_slice(&(ATTRIBUTES.len() as u32).to_le_bytes());
+        data.extend_from_slice(&(colors.len() as u32).to_le_bytes());
+        data.extend_from_slice(&[0; 8]);
+        for offset in [attribute_offset, x_offset, y_offset] {
+            data.extend_from_slice(&(offset as u32).to_le_bytes());
+        }
+        data.resize(header_size, 0);
+        for color in colors {
+            data.extend_from_slice(&color.to_le_bytes());
+        }
+        for stream in &streams {
+            data.extend_from_slice(stream);
+        }
+        data
+    }
+
+    fn assert_stitches(pattern: &Pattern) {
+        use StitchCommand::*;
+        let decoded: Vec<_> = pattern
+            .stitches
+            .iter()
+            .map(|s| (s.x, s.y, s.command))
+            .collect();
+        assert_eq!(
+            decoded,
+            vec![
+                (10.0, 0.0, Stitch),
+                (20.0, 10.0, Stitch),
+                (120.0, 10.0, Move),
+                (120.0, 10.0, ColorChange),
+                (125.0, 10.0, Stitch),
+                (125.0, 10.0, Trim),
+                (125.0, 10.0, End),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_hus() {
+        let pattern = parse_hus(&build(0x00C8AF5B, &[3, 1], HUS_COLOR_TABLE_OFFSET)).unwrap();
+
+        assert_stitches(&pattern);
+        assert_eq!(pattern.color_blocks.len(), 2);
+        let colors = &pattern.metadata.thread_colors;
+        assert_eq!(colors[0].description.as_deref(), Some("Red"));
+        assert_eq!(colors[1].rgb, [0x00, 0x00, 0xE7]);
+    }
+
+    #[test]
+    fn test_parse_vip() {
+        let pattern = parse_vip(&build(0x0190FC5D, &[], 64)).unwrap();
+
+        assert_stitches(&pattern);
+        assert!(pattern.metadata.thread_colors.is_empty());
+    }
+
+    #[test]
+    fn test_corrupt_and_truncated_files() {
+        assert!(matches!(
+            parse_hus(&[0; 10]),
+            Err(HusError::InsufficientData)
+        ));
+
+        let mut data = build(0x00C8AF5B, &[0], HUS_COLOR_TABLE_OFFSET);
+        data[20..24].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(parse_hus(&data), Err(HusError::InvalidOffsets)));
+    }
+}
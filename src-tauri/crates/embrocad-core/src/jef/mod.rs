@@ -0,0 +1,11 @@
+// mod.rs - Janome JEF format parser, writer, shared stitch encoding and thread palette
+
+mod palette;
+mod parser;
+mod stitches;
+mod writer;
+
+pub use palette::janome_thread;
+pub use parser::parse_jef;
+pub use stitches::decode_stitches;
+pub use writer::write_jef;
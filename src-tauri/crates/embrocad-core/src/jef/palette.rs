@@ -0,0 +1,99 @@
+// palette.rs - Standard Janome thread palette
+
+use crate::dst::ThreadColor;
+
+/// Janome palette, indexed by the color numbers in JEF/SEW files; index 0 is unused
+pub const JANOME_PALETTE: [([u8; 3], &str, &str); 79] = [
+    ([0x00, 0x00, 0x00], "Unknown", ""),
+    ([0x00, 0x00, 0x00], "Black", "002"),
+    ([0xFF, 0xFF, 0xFF], "White", "001"),
+    ([0xFF, 0xFF, 0x17], "Yellow", "204"),
+    ([0xFF, 0x66, 0x00], "Orange", "203"),
+    ([0x2F, 0x59, 0x33], "Olive Green", "219"),
+    ([0x23, 0x73, 0x36], "Green", "226"),
+    ([0x65, 0xC2, 0xC8], "Sky", "217"),
+    ([0xAB, 0x5A, 0x96], "Purple", "208"),
+    ([0xF6, 0x69, 0xA0], "Pink", "201"),
+    ([0xFF, 0x00, 0x00], "Red", "225"),
+    ([0xB1, 0x70, 0x4E], "Brown", "214"),
+    ([0x0B, 0x2F, 0x84], "Blue", "207"),
+    ([0xE4, 0xC3, 0x5D], "Gold", "003"),
+    ([0x48, 0x1A, 0x05], "Dark Brown", "205"),
+    ([0xAC, 0x9C, 0xC7], "Pale Violet", "209"),
+    ([0xFC, 0xF2, 0x94], "Pale Yellow", "210"),
+    ([0xF9, 0x99, 0xB7], "Pale Pink", "211"),
+    ([0xFA, 0xB3, 0x81], "Peach", "212"),
+    ([0xC9, 0xA4, 0x80], "Beige", "213"),
+    ([0x97, 0x05, 0x33], "Wine Red", "215"),
+    ([0xA0, 0xB8, 0xCC], "Pale Sky", "216"),
+    ([0x7F, 0xC2, 0x1C], "Yellow Green", "218"),
+    ([0xE5, 0xE5, 0xE5], "Silver Gray", "220"),
+    ([0x88, 0x9B, 0x9B], "Gray", "221"),
+    ([0x98, 0xD6, 0xBD], "Pale Aqua", "227"),
+    ([0xB2, 0xE1, 0xE3], "Baby Blue", "228"),
+    ([0x36, 0x8B, 0xA0], "Powder Blue", "229"),
+    ([0x4F, 0x83, 0xAB], "Bright Blue", "230"),
+    ([0x38, 0x6A, 0x91], "Slate Blue", "231"),
+    ([0x07, 0x16, 0x50], "Navy Blue", "232"),
+    ([0xF9, 0x99, 0xA2], "Salmon Pink", "233"),
+    ([0xF9, 0x67, 0x6B], "Coral", "234"),
+    ([0xE3, 0x31, 0x1F], "Burnt Orange", "235"),
+    ([0xE2, 0xA1, 0x88], "Cinnamon", "236"),
+    ([0xB5, 0x94, 0x74], "Umber", "237"),
+    ([0xE4, 0xCF, 0x99], "Blond", "238"),
+    ([0xFF, 0xCB, 0x00], "Sunflower", "239"),
+    ([0xE1, 0xAD, 0xD4], "Orchid Pink", "240"),
+    ([0xC3, 0x00, 0x7E], "Peony Purple", "241"),
+    ([0x80, 0x00, 0x4B], "Burgundy", "242"),
+    ([0x54, 0x05, 0x71], "Royal Purple", "243"),
+    ([0xB1, 0x05, 0x25], "Cardinal Red", "244"),
+    ([0xCA, 0xE0, 0xC0], "Opal Green", "245"),
+    ([0x89, 0x98, 0x56], "Moss Green", "246"),
+    ([0x5C, 0x94, 0x1A], "Meadow Green", "247"),
+    ([0x00, 0x31, 0x14], "Dark Green", "248"),
+    ([0x5D, 0xAE, 0x94], "Aquamarine", "249"),
+    ([0x4C, 0xBF, 0x8F], "Emerald Green", "250"),
+    ([0x00, 0x77, 0x72], "Peacock Green", "251"),
+    ([0x59, 0x5B, 0x61], "Dark Gray", "252"),
+    ([0xFF, 0xFF, 0xF2], "Ivory White", "253"),
+    ([0xB1, 0x58, 0x1E], "Hazel", "254"),
+    ([0xCB, 0x8A, 0x07], "Toast", "255"),
+    ([0x98, 0x6C, 0x80], "Salmon", "256"),
+    ([0x98, 0x69, 0x2D], "Cocoa Brown", "257"),
+    ([0x4D, 0x34, 0x19], "Sienna", "258"),
+    ([0x4C, 0x33, 0x0B], "Sepia", "259"),
+    ([0x33, 0x20, 0x0A], "Dark Sepia", "260"),
+    ([0x52, 0x3A, 0x97], "Violet Blue", "261"),
+    ([0x0D, 0x21, 0x7E], "Blue Ink", "262"),
+    ([0x1E, 0x77, 0xAC], "Sola Blue", "263"),
+    ([0xB2, 0xDD, 0x53], "Green Dust", "264"),
+    ([0xF3, 0x36, 0x89], "Crimson", "265"),
+    ([0xDE, 0x64, 0x9E], "Floral Pink", "266"),
+    ([0x98, 0x41, 0x61], "Wine", "267"),
+    ([0x4C, 0x56, 0x12], "Olive Drab", "268"),
+    ([0x4C, 0x88, 0x1F], "Meadow", "269"),
+    ([0xE4, 0xDE, 0x79], "Mustard", "270"),
+    ([0xCB, 0x8A, 0x1A], "Yellow Ochre", "271"),
+    ([0xCB, 0xA2, 0x1C], "Old Gold", "272"),
+    ([0xFF, 0x98, 0x05], "Honey Dew", "273"),
+    ([0xFC, 0xB2, 0x57], "Tangerine", "274"),
+    ([0xFF, 0xE5, 0x05], "Canary Yellow", "275"),
+    ([0xF0, 0x33, 0x1F], "Vermilion", "202"),
+    ([0x1A, 0x84, 0x2D], "Bright Green", "206"),
+    ([0x38, 0x6C, 0xAE], "Ocean Blue", "222"),
+    ([0xE3, 0xC4, 0xB4], "Beige Gray", "223"),
+    ([0xE3, 0xAC, 0x81], "Bamboo", "224"),
+];
+
+/// Look up a Janome palette entry, wrapping out-of-range indices
+pub fn janome_thread(index: u16) -> ThreadColor {
+    let (rgb, name, code) = JANOME_PALETTE[index as usize % JANOME_PALETTE.len()];
+
+    ThreadColor {
+        rgb,
+        description: Some(name.to_string()),
+        catalog_number: (!code.is_empty()).then(|| code.to_string()),
+        needle: None,
+        basting: false,
+    }
+}
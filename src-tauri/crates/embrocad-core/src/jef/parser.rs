@@ -0,0 +1,84 @@
+// parser.rs - Janome JEF embroidery file format parser
+
+use crate::binary::ByteReader;
+use crate::dst::Pattern;
+use crate::jef::{decode_stitches, janome_thread};
+
+/// Offset of the color count in the header
+pub const JEF_COLOR_COUNT_OFFSET: usize = 24;
+
+/// Color indices start here, followed by one thread type per color
+pub const JEF_COLOR_TABLE_OFFSET: usize = 116;
+
+/// Error type for JEF parsing
+#[derive(Debug, thiserror::Error)]
+pub enum JefError {
+    #[error("Invalid JEF file: insufficient data")]
+    InsufficientData,
+    #[error("Invalid JEF file: stitch offset {0} is out of range")]
+    InvalidStitchOffset(usize),
+}
+
+/// Parse a JEF file from bytes
+///
+/// The header starts with the stitch data offset; the color count sits at
+/// byte 24 and the 32-bit Janome palette indices at byte 116.
+pub fn parse_jef(data: &[u8]) -> Result<Pattern, JefError> {
+    if data.len() < JEF_COLOR_TABLE_OFFSET {
+        return Err(JefError::InsufficientData);
+    }
+
+    let mut reader = ByteReader::new(data);
+    let stitch_offset = reader.u32_le().ok_or(JefError::InsufficientData)? as usize;
+    if stitch_offset < JEF_COLOR_TABLE_OFFSET || stitch_offset > data.len() {
+        return Err(JefError::InvalidStitchOffset(stitch_offset));
+    }
+
+    let mut pattern = Pattern::new();
+    reader
+        .seek(JEF_COLOR_COUNT_OFFSET)
+        .ok_or(JefError::InsufficientData)?;
+    let color_count = reader.u32_le().ok_or(JefError::InsufficientData)?;
+    reader
+        .seek(JEF_COLOR_TABLE_OFFSET)
+        .ok_or(JefError::InsufficientData)?;
+    for _ in 0..color_count {
+        let index = reader.i32_le().ok_or(JefError::InsufficientData)?;
+        pattern
+            .metadata
+            .thread_colors
+            .push(janome_thread(index.max(0) as u16));
+    }
+
+    decode_stitches(&data[stitch_offset..], &mut pattern);
+
+    pattern.metadata.stitch_count = Some(pattern.stitches.len() as u32);
+    pattern.metadata.color_count = Some(pattern.color_changes);
+
+    // Calculate bounds, statistics and color blocks
+    pattern.calculate_bounds();
+    pattern.calculate_statistics();
+    pattern.calculate_color_blocks();
+
+    Ok(pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_input() {
+        assert!(matches!(
+            parse_jef(&[0; 16]),
+            Err(JefError::InsufficientData)
+        ));
+
+        let mut data = vec![0; JEF_COLOR_TABLE_OFFSET];
+        data[..4].copy_from_slice(&9999u32.to_le_bytes());
+        assert!(matches!(
+            parse_jef(&data),
+            Err(JefError::InvalidStitchOffset(9999))
+        ));
+    }
+}
@@ -0,0 +1,168 @@
+// stitches.rs - Encoder and decoder for the 2-byte delta stitch stream shared by JEF and SEW
+
+use crate::dst::{Pattern, StitchCommand};
+
+/// Escape byte introducing a control record
+const ESCAPE: u8 = 0x80;
+
+/// Control codes following the escape byte
+const CONTROL_COLOR_CHANGE: u8 = 0x01;
+const CONTROL_MOVE: u8 = 0x02;
+const CONTROL_MOVE_ALT: u8 = 0x04;
+const CONTROL_END: u8 = 0x10;
+
+/// Largest displacement per axis of one record
+pub const MAX_JEF_DISPLACEMENT: i32 = 127;
+
+/// Decode a JEF-family stitch stream into absolute stitches, appending End
+///
+/// Records are signed (dx, dy) byte pairs with Y inverted. A 0x80 escape is
+/// followed by a control byte and a displacement pair: 0x01 color change,
+/// 0x02/0x04 move (a zero-length move is the trim marker) and 0x10 end.
+pub fn decode_stitches(data: &[u8], pattern: &mut Pattern) {
+    let mut records = data.chunks_exact(2);
+
+    let mut current_x = 0.0f64;
+    let mut current_y = 0.0f64;
+
+    while let Some(record) = records.next() {
+        if record[0] != ESCAPE {
+            current_x += record[0] as i8 as f64;
+            current_y -= record[1] as i8 as f64;
+            pattern.add_stitch(current_x, current_y, StitchCommand::Stitch);
+            continue;
+        }
+
+        let control = record[1];
+        let Some(operand) = records.next() else {
+            break;
+        };
+        let dx = operand[0] as i8 as f64;
+        let dy = -(operand[1] as i8 as f64);
+
+        match control {
+            CONTROL_COLOR_CHANGE => {
+                pattern.add_stitch(current_x, current_y, StitchCommand::ColorChange);
+            }
+            CONTROL_MOVE | CONTROL_MOVE_ALT if dx == 0.0 && dy == 0.0 => {
+                pattern.add_stitch(current_x, current_y, StitchCommand::Trim);
+            }
+            CONTROL_MOVE | CONTROL_MOVE_ALT => {
+                current_x += dx;
+                current_y += dy;
+                pattern.add_stitch(current_x, current_y, StitchCommand::Move);
+            }
+            CONTROL_END => break,
+            _ => {}
+        }
+    }
+
+    pattern.add_stitch(current_x, current_y, StitchCommand::End);
+}
+
+/// Append escaped move records covering (dx, dy), each within the byte range
+fn push_moves(out: &mut Vec<u8>, mut dx: i32, mut dy: i32) {
+    while dx != 0 || dy != 0 {
+        let step_x = dx.clamp(-MAX_JEF_DISPLACEMENT, MAX_JEF_DISPLACEMENT);
+        let step_y = dy.clamp(-MAX_JEF_DISPLACEMENT, MAX_JEF_DISPLACEMENT);
+        out.extend_from_slice(&[
+            ESCAPE,
+            CONTROL_MOVE,
+            step_x as i8 as u8,
+            (-step_y) as i8 as u8,
+        ]);
+        dx -= step_x;
+        dy -= step_y;
+    }
+}
+
+/// Encode stitches as a JEF-family stream ending with the end escape
+///
+/// The inverse of `decode_stitches`: a Trim becomes a zero-length move,
+/// and displacements beyond one record are split into several moves.
+pub fn encode_stitches(pattern: &Pattern) -> Vec<u8> {
+    let mut out = Vec::with_capacity(pattern.stitches.len() * 2 + 4);
+    let (mut current_x, mut current_y) = (0i32, 0i32);
+
+    for stitch in &pattern.stitches {
+        let (x, y) = stitch.units();
+        let (dx, dy) = (x - current_x, y - current_y);
+
+        match stitch.command {
+            StitchCommand::Stitch => {
+                let step_x = dx.clamp(-MAX_JEF_DISPLACEMENT, MAX_JEF_DISPLACEMENT);
+                let step_y = dy.clamp(-MAX_JEF_DISPLACEMENT, MAX_JEF_DISPLACEMENT);
+                push_moves(&mut out, dx - step_x, dy - step_y);
+                out.extend_from_slice(&[step_x as i8 as u8, (-step_y) as i8 as u8]);
+            }
+            StitchCommand::Move => push_moves(&mut out, dx, dy),
+            StitchCommand::Trim => {
+                push_moves(&mut out, dx, dy);
+                out.extend_from_slice(&[ESCAPE, CONTROL_MOVE, 0x00, 0x00]);
+            }
+            StitchCommand::ColorChange => {
+                push_moves(&mut out, dx, dy);
+                out.extend_from_slice(&[ESCAPE, CONTROL_COLOR_CHANGE, 0x00, 0x00]);
+            }
+            _ => push_moves(&mut out, dx, dy),
+        }
+        (current_x, current_y) = (x, y);
+    }
+
+    out.extend_from_slice(&[ESCAPE, CONTROL_END, 0x00, 0x00]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_control_records() {
+        use StitchCommand::*;
+        let data = [
+            0x0A, 0x05, // stitch (+10, -5)
+            0x80, 0x02, 0x00, 0x00, // trim
+            0x80, 0x02, 0x32, 0xCE, // move (+50, +50)
+            0x80, 0x01, 0x00, 0x00, // color change
+            0xFF, 0x01, // stitch (-1, -1)
+            0x80, 0x10, 0x00, 0x00, // end
+            0x05, 0x05, // ignored after end
+        ];
+        let mut pattern = Pattern::new();
+        decode_stitches(&data, &mut pattern);
+
+        let decoded: Vec<_> = pattern
+            .stitches
+            .iter()
+            .map(|s| (s.x, s.y, s.command))
+            .collect();
+        assert_eq!(
+            decoded,
+            vec![
+                (10.0, -5.0, Stitch),
+                (10.0, -5.0, Trim),
+                (60.0, 45.0, Move),
+                (60.0, 45.0, ColorChange),
+                (59.0, 44.0, Stitch),
+                (59.0, 44.0, End),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encode_inverts_decode() {
+        let data = [
+            0x0A, 0x05, // stitch (+10, -5)
+            0x80, 0x02, 0x00, 0x00, // trim
+            0x80, 0x02, 0x32, 0xCE, // move (+50, +50)
+            0x80, 0x01, 0x00, 0x00, // color change
+            0xFF, 0x01, // stitch (-1, -1)
+            0x80, 0x10, 0x00, 0x00, // end
+        ];
+        let mut pattern = Pattern::new();
+        decode_stitches(&data, &mut pattern);
+
+        assert_eq!(encode_stitches(&pattern), data);
+    }
+}
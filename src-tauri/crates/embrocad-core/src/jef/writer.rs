@@ -0,0 +1,214 @@
+// writer.rs - Janome JEF writer with hoop selection and palette matching
+
+use crate::dst::{Bounds, Pattern, UNITS_PER_MM};
+use crate::export::{block_rgb, nearest_palette_index};
+use crate::jef::palette::JANOME_PALETTE;
+use crate::jef::parser::JEF_COLOR_TABLE_OFFSET;
+use crate::jef::stitches::{encode_stitches, MAX_JEF_DISPLACEMENT};
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Header flags word written by Janome software
+const JEF_FLAGS: u32 = 0x14;
+
+/// Thread type stored after each color index
+const JEF_THREAD_TYPE: u32 = 0x0D;
+
+/// JEF hoop codes with their sizes in 0.1mm, smallest first
+const JEF_HOOPS: [(u32, i32, i32); 5] = [
+    (2, 500, 500),   // 50x50
+    (1, 1100, 1100), // 110x110
+    (0, 1260, 1100), // 126x110
+    (3, 1400, 2000), // 140x200
+    (4, 2300, 2000), // 230x200
+];
+
+/// The hoops whose clearances follow the design extents, in header order
+const JEF_CLEARANCE_HOOPS: [usize; 4] = [1, 0, 3, 4];
+
+/// Format Unix seconds as the header's YYYYMMDDHHMMSS date in UTC
+fn jef_date(unix_secs: u64) -> String {
+    let days = (unix_secs / 86_400) as i64;
+    let secs = unix_secs % 86_400;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Seconds since the Unix epoch
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// The Unix epoch: SystemTime::now panics on wasm32-unknown-unknown
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+fn unix_now() -> u64 {
+    0
+}
+
+/// Encode a pattern as a JEF file stamped with the current time
+///
+/// On wasm32-unknown-unknown, which has no clock, the stamp is the Unix
+/// epoch; use `write_jef_at` with the time from JavaScript instead.
+pub fn write_jef(pattern: &Pattern) -> Vec<u8> {
+    write_jef_at(pattern, unix_now())
+}
+
+/// Encode a pattern as a JEF file with the header date set from `unix_secs`
+///
+/// The smallest hoop that holds the design is recorded, along with the
+/// design's extents from its center and its clearance in the other hoops.
+/// Colors map to the nearest Janome palette entries.
+pub fn write_jef_at(pattern: &Pattern, unix_secs: u64) -> Vec<u8> {
+    let mut pattern = pattern.clone();
+    pattern.split_long_stitches(MAX_JEF_DISPLACEMENT as f64 / UNITS_PER_MM);
+
+    let mut bounds = Bounds::new();
+    for stitch in &pattern.stitches {
+        bounds.update(stitch.x, stitch.y);
+    }
+    if pattern.stitches.is_empty() {
+        bounds.update(0.0, 0.0);
+    }
+    let (width, height) = (
+        bounds.width().round() as i32,
+        bounds.height().round() as i32,
+    );
+    let (half_width, half_height) = ((width + 1) / 2, (height + 1) / 2);
+
+    let blocks = pattern.color_blocks();
+    let stitches = encode_stitches(&pattern);
+    let stitch_offset = JEF_COLOR_TABLE_OFFSET + blocks.len() * 8;
+
+    let hoop = JEF_HOOPS
+        .iter()
+        .find(|&&(_, w, h)| width <= w && height <= h)
+        .unwrap_or(&JEF_HOOPS[JEF_HOOPS.len() - 1]);
+
+    let mut data = Vec::with_capacity(stitch_offset + stitches.len());
+    data.extend_from_slice(&(stitch_offset as u32).to_le_bytes());
+    data.extend_from_slice(&JEF_FLAGS.to_le_bytes());
+    data.extend_from_slice(jef_date(unix_secs).as_bytes());
+    data.extend_from_slice(&[0, 0]);
+    data.extend_from_slice(&(blocks.len() as u32).to_le_bytes());
+    // Point count: one per stitch record, two per escape record
+    data.extend_from_slice(&((stitches.len() / 2) as u32).to_le_bytes());
+    data.extend_from_slice(&hoop.0.to_le_bytes());
+
+    // Left, top, right and bottom extents from the design center
+    for extent in [half_width, half_height, half_width, half_height] {
+        data.extend_from_slice(&extent.to_le_bytes());
+    }
+    for index in JEF_CLEARANCE_HOOPS {
+        let (_, hoop_width, hoop_height) = JEF_HOOPS[index];
+        let fits = width <= hoop_width && height <= hoop_height;
+        let (x, y) = (hoop_width / 2 - half_width, hoop_height / 2 - half_height);
+        for clearance in [x, y, x, y] {
+            let clearance = if fits { clearance } else { -1 };
+            data.extend_from_slice(&clearance.to_le_bytes());
+        }
+    }
+    debug_assert_eq!(data.len(), JEF_COLOR_TABLE_OFFSET);
+
+    for block in &blocks {
+        let rgb = block_rgb(&pattern, block.index);
+        let index = nearest_palette_index(rgb, JANOME_PALETTE[1..].iter().map(|entry| entry.0));
+        data.extend_from_slice(&(index as u32 + 1).to_le_bytes());
+    }
+    for _ in &blocks {
+        data.extend_from_slice(&JEF_THREAD_TYPE.to_le_bytes());
+    }
+
+    data.extend(stitches);
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::{StitchCommand, ThreadColor};
+    use crate::format::{detect_format, DesignFormat};
+    use crate::jef::parse_jef;
+    use StitchCommand::*;
+
+    #[test]
+    fn test_round_trip_through_parser() {
+        let mut pattern = Pattern::new();
+        for &(x, y, command) in &[
+            (0.0, 0.0, Stitch),
+            (60.0, 40.0, Stitch),
+            (60.0, 40.0, Trim),
+            (600.0, -300.0, Move),
+            (600.0, -300.0, ColorChange),
+            (610.0, -290.0, Stitch),
+            (330.0, -290.0, Stitch),
+            (330.0, -290.0, End),
+        ] {
+            pattern.add_stitch(x, y, command);
+        }
+        pattern.metadata.thread_colors = vec![
+            ThreadColor::new([250, 2, 3]),
+            ThreadColor::new([250, 250, 250]),
+        ];
+        pattern.calculate_bounds();
+        pattern.calculate_statistics();
+
+        let data = write_jef_at(&pattern, 1_706_702_400);
+        assert_eq!(detect_format(&data), Some(DesignFormat::Jef));
+        let parsed = parse_jef(&data).unwrap();
+
+        assert_eq!(parsed.bounds, pattern.bounds);
+        // The 28mm stitch is split in three
+        assert_eq!(parsed.statistics.real_stitch_count, 6);
+        assert_eq!(parsed.statistics.trim_count, 1);
+        assert_eq!(parsed.color_changes, 1);
+        let colors: Vec<_> = parsed
+            .metadata
+            .thread_colors
+            .iter()
+            .map(|c| c.description.as_deref().unwrap())
+            .collect();
+        assert_eq!(colors, vec!["Red", "White"]);
+    }
+
+    #[test]
+    fn test_header_date_and_hoop() {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(0.0, 0.0, Stitch);
+        pattern.add_stitch(1200.0, 300.0, Stitch);
+
+        let data = write_jef_at(&pattern, 1_706_702_400);
+        assert_eq!(&data[8..22], b"20240131120000");
+        // 120x30mm is too wide for 110x110 but fits 126x110
+        assert_eq!(
+            u32::from_le_bytes([data[32], data[33], data[34], data[35]]),
+            0
+        );
+        // The 110x110 clearance is marked as not fitting
+        assert_eq!(
+            i32::from_le_bytes([data[52], data[53], data[54], data[55]]),
+            -1
+        );
+    }
+}
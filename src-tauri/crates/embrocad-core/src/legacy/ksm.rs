@@ -0,0 +1,108 @@
+// ksm.rs - Pfaff KSM embroidery file format parser
+
+use super::{build_pattern, LegacyError, Record};
+use crate::dst::{Pattern, StitchCommand};
+
+/// Stitch records follow a 512-byte block that machines leave unused
+const STITCH_DATA_OFFSET: usize = 0x200;
+
+/// Sign flags in the control byte
+const NEGATE_X: u8 = 0x40;
+const NEGATE_Y: u8 = 0x20;
+
+/// Command flag; when clear the record is a stitch, or a jump if 0x10 is set
+const COMMAND: u8 = 0x80;
+const JUMP: u8 = 0x10;
+
+/// Command codes, after the sign flags are masked off
+const CONTROL_TRIM: u8 = 0x81;
+const CONTROL_COLOR_CHANGE: u8 = 0x88;
+const CONTROL_END: u8 = 0x9F;
+
+/// Decode one record: the Y and X magnitudes, then the control byte
+fn decode_record(record: &[u8]) -> Record {
+    let (y, x, control) = (record[0] as f64, record[1] as f64, record[2]);
+
+    let dx = if control & NEGATE_X != 0 { -x } else { x };
+    // File Y points up; invert to match the DST parser
+    let dy = if control & NEGATE_Y != 0 { y } else { -y };
+
+    if control & COMMAND == 0 {
+        let command = if control & JUMP != 0 {
+            StitchCommand::Move
+        } else {
+            StitchCommand::Stitch
+        };
+        return Record { dx, dy, command };
+    }
+
+    let command = match control & !(NEGATE_X | NEGATE_Y) {
+        CONTROL_TRIM => StitchCommand::Trim,
+        CONTROL_COLOR_CHANGE => StitchCommand::ColorChange,
+        CONTROL_END => StitchCommand::End,
+        // Unknown commands still move the needle
+        _ => StitchCommand::Move,
+    };
+    match command {
+        StitchCommand::Move => Record { dx, dy, command },
+        _ => Record {
+            dx: 0.0,
+            dy: 0.0,
+            command,
+        },
+    }
+}
+
+/// Parse a Pfaff KSM file from bytes
+pub fn parse_ksm(data: &[u8]) -> Result<Pattern, LegacyError> {
+    let records = data
+        .get(STITCH_DATA_OFFSET..)
+        .filter(|d| d.len() >= 3)
+        .ok_or(LegacyError::InsufficientData)?;
+
+    Ok(build_pattern(records.chunks_exact(3).map(decode_record)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_signs_and_commands() {
+        let mut data = vec![0; STITCH_DATA_OFFSET];
+        data.extend_from_slice(&[
+            5, 10, 0x00, // stitch (+10, -5)
+            5, 10, 0x70, // jump (-10, +5)
+            0, 0, 0x81, // trim
+            0, 0, 0x88, // color change
+            1, 1, 0x40, // stitch (-1, -1)
+        ]);
+        let pattern = parse_ksm(&data).unwrap();
+
+        use StitchCommand::*;
+        let decoded: Vec<_> = pattern
+            .stitches
+            .iter()
+            .map(|s| (s.x, s.y, s.command))
+            .collect();
+        assert_eq!(
+            decoded,
+            vec![
+                (10.0, -5.0, Stitch),
+                (0.0, 0.0, Move),
+                (0.0, 0.0, Trim),
+                (0.0, 0.0, ColorChange),
+                (-1.0, -1.0, Stitch),
+                (-1.0, -1.0, End),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_insufficient_data() {
+        assert!(matches!(
+            parse_ksm(&[0; 64]),
+            Err(LegacyError::InsufficientData)
+        ));
+    }
+}
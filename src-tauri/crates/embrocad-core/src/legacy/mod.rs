@@ -0,0 +1,58 @@
+// mod.rs - Legacy headerless punch format parsers (Toyota 10o, Pfaff KSM)
+
+mod ksm;
+mod ten_o;
+
+pub use ksm::parse_ksm;
+pub use ten_o::parse_10o;
+
+use crate::dst::{Pattern, StitchCommand};
+
+/// Error type for legacy format parsing
+#[derive(Debug, thiserror::Error)]
+pub enum LegacyError {
+    #[error("Invalid design file: no stitch data")]
+    InsufficientData,
+}
+
+/// A decoded record: unsigned magnitudes already signed and Y inverted
+struct Record {
+    dx: f64,
+    dy: f64,
+    command: StitchCommand,
+}
+
+/// Accumulate decoded records into absolute stitches, stopping at End
+///
+/// Neither format has a header, so the metadata counts are derived from the
+/// decoded stream, and an End is appended when the data simply runs out.
+fn build_pattern(records: impl Iterator<Item = Record>) -> Pattern {
+    let mut pattern = Pattern::new();
+
+    let mut current_x = 0.0f64;
+    let mut current_y = 0.0f64;
+
+    for record in records {
+        current_x += record.dx;
+        current_y += record.dy;
+        pattern.add_stitch(current_x, current_y, record.command);
+
+        if record.command == StitchCommand::End {
+            break;
+        }
+    }
+
+    if pattern.stitches.last().map(|s| s.command) != Some(StitchCommand::End) {
+        pattern.add_stitch(current_x, current_y, StitchCommand::End);
+    }
+
+    pattern.metadata.stitch_count = Some(pattern.stitches.len() as u32);
+    pattern.metadata.color_count = Some(pattern.color_changes);
+
+    // Calculate bounds, statistics and color blocks
+    pattern.calculate_bounds();
+    pattern.calculate_statistics();
+    pattern.calculate_color_blocks();
+
+    pattern
+}
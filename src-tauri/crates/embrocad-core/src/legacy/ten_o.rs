@@ -0,0 +1,103 @@
+// ten_o.rs - Toyota 10o embroidery file format parser
+
+use super::{build_pattern, LegacyError, Record};
+use crate::dst::{Pattern, StitchCommand};
+
+/// Sign flags in the control byte
+const NEGATE_X: u8 = 0x20;
+const NEGATE_Y: u8 = 0x40;
+
+/// Command flag; when clear the record is a stitch, or a jump if bit 0 is set
+const COMMAND: u8 = 0x80;
+const JUMP: u8 = 0x01;
+
+/// Command codes, which carry no displacement
+const CONTROL_COLOR_CHANGE: u8 = 0x85;
+const CONTROL_STOP: u8 = 0x8A;
+const CONTROL_TRIM: u8 = 0x87;
+const CONTROL_END: u8 = 0x8F;
+
+/// Decode one record: control byte, then the Y and X magnitudes
+fn decode_record(record: &[u8]) -> Record {
+    let (control, y, x) = (record[0], record[1] as f64, record[2] as f64);
+
+    if control & COMMAND != 0 {
+        let command = match control {
+            CONTROL_COLOR_CHANGE | CONTROL_STOP => StitchCommand::ColorChange,
+            CONTROL_TRIM => StitchCommand::Trim,
+            CONTROL_END => StitchCommand::End,
+            // Unknown commands are skipped in place
+            _ => StitchCommand::Move,
+        };
+        return Record {
+            dx: 0.0,
+            dy: 0.0,
+            command,
+        };
+    }
+
+    let dx = if control & NEGATE_X != 0 { -x } else { x };
+    // File Y points up; invert to match the DST parser
+    let dy = if control & NEGATE_Y != 0 { y } else { -y };
+    let command = if control & JUMP != 0 {
+        StitchCommand::Move
+    } else {
+        StitchCommand::Stitch
+    };
+
+    Record { dx, dy, command }
+}
+
+/// Parse a Toyota 10o file from bytes
+pub fn parse_10o(data: &[u8]) -> Result<Pattern, LegacyError> {
+    if data.len() < 3 {
+        return Err(LegacyError::InsufficientData);
+    }
+
+    Ok(build_pattern(data.chunks_exact(3).map(decode_record)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_signs_and_commands() {
+        let data = [
+            0x00, 5, 10, // stitch (+10, -5)
+            0x61, 5, 10, // jump (-10, +5)
+            0x87, 0, 0, // trim
+            0x85, 0, 0, // color change
+            0x20, 1, 1, // stitch (-1, -1)
+            0x8F, 0, 0, // end
+        ];
+        let pattern = parse_10o(&data).unwrap();
+
+        use StitchCommand::*;
+        let decoded: Vec<_> = pattern
+            .stitches
+            .iter()
+            .map(|s| (s.x, s.y, s.command))
+            .collect();
+        assert_eq!(
+            decoded,
+            vec![
+                (10.0, -5.0, Stitch),
+                (0.0, 0.0, Move),
+                (0.0, 0.0, Trim),
+                (0.0, 0.0, ColorChange),
+                (-1.0, -1.0, Stitch),
+                (-1.0, -1.0, End),
+            ]
+        );
+        assert_eq!(pattern.metadata.color_count, Some(1));
+    }
+
+    #[test]
+    fn test_insufficient_data() {
+        assert!(matches!(
+            parse_10o(&[0x00]),
+            Err(LegacyError::InsufficientData)
+        ));
+    }
+}
@@ -0,0 +1,374 @@
+// font.rs - Built-in single-stroke block font, sewn as satin columns
+//
+// Glyphs are drawn in font units with y up from the baseline and a cap
+// height of 10; each stroke is a centerline the satin column follows.
+
+/// Height of capitals in font units
+pub const CAP_HEIGHT: f64 = 10.0;
+
+/// Satin column width in font units
+pub const COLUMN_WIDTH: f64 = 1.2;
+
+/// Space between neighbouring glyph boxes in font units
+pub const LETTER_GAP: f64 = 1.5;
+
+/// Advance of a space in font units
+const SPACE_WIDTH: f64 = 5.0;
+
+type Stroke = &'static [(f64, f64)];
+
+/// Width of the glyph box and the strokes drawn in it, in sewing order
+#[derive(Debug, Clone, Copy)]
+pub struct Glyph {
+    pub width: f64,
+    pub strokes: &'static [Stroke],
+}
+
+/// The glyph for `c`; lowercase letters use the capitals
+pub fn glyph(c: char) -> Option<Glyph> {
+    let (width, strokes): (f64, &'static [Stroke]) = match c.to_ascii_uppercase() {
+        ' ' => (SPACE_WIDTH, &[]),
+        'A' => (
+            8.0,
+            &[
+                &[(0.0, 0.0), (4.0, 10.0), (8.0, 0.0)],
+                &[(1.6, 4.0), (6.4, 4.0)],
+            ],
+        ),
+        'B' => (
+            6.5,
+            &[
+                &[
+                    (0.0, 0.0),
+                    (0.0, 10.0),
+                    (4.5, 10.0),
+                    (6.0, 9.0),
+                    (6.0, 6.5),
+                    (4.5, 5.2),
+                    (0.0, 5.2),
+                ],
+                &[(4.5, 5.2), (6.5, 4.0), (6.5, 1.3), (5.0, 0.0), (0.0, 0.0)],
+            ],
+        ),
+        'C' => (
+            7.0,
+            &[&[
+                (7.0, 8.5),
+                (5.5, 10.0),
+                (1.5, 10.0),
+                (0.0, 8.5),
+                (0.0, 1.5),
+                (1.5, 0.0),
+                (5.5, 0.0),
+                (7.0, 1.5),
+            ]],
+        ),
+        'D' => (
+            7.0,
+            &[&[
+                (0.0, 0.0),
+                (0.0, 10.0),
+                (4.5, 10.0),
+                (7.0, 7.5),
+                (7.0, 2.5),
+                (4.5, 0.0),
+                (0.0, 0.0),
+            ]],
+        ),
+        'E' => (
+            6.0,
+            &[
+                &[(6.0, 10.0), (0.0, 10.0), (0.0, 0.0), (6.0, 0.0)],
+                &[(0.0, 5.0), (4.5, 5.0)],
+            ],
+        ),
+        'F' => (
+            6.0,
+            &[
+                &[(6.0, 10.0), (0.0, 10.0), (0.0, 0.0)],
+                &[(0.0, 5.0), (4.5, 5.0)],
+            ],
+        ),
+        'G' => (
+            7.0,
+            &[&[
+                (7.0, 8.5),
+                (5.5, 10.0),
+                (1.5, 10.0),
+                (0.0, 8.5),
+                (0.0, 1.5),
+                (1.5, 0.0),
+                (5.5, 0.0),
+                (7.0, 1.5),
+                (7.0, 4.5),
+                (4.0, 4.5),
+            ]],
+        ),
+        'H' => (
+            7.0,
+            &[
+                &[(0.0, 0.0), (0.0, 10.0)],
+                &[(7.0, 10.0), (7.0, 0.0)],
+                &[(0.0, 5.0), (7.0, 5.0)],
+            ],
+        ),
+        'I' => (0.0, &[&[(0.0, 0.0), (0.0, 10.0)]]),
+        'J' => (
+            6.0,
+            &[&[(6.0, 10.0), (6.0, 2.0), (4.0, 0.0), (2.0, 0.0), (0.0, 2.0)]],
+        ),
+        'K' => (
+            7.0,
+            &[
+                &[(0.0, 0.0), (0.0, 10.0)],
+                &[(7.0, 10.0), (0.0, 3.5)],
+                &[(2.3, 5.5), (7.0, 0.0)],
+            ],
+        ),
+        'L' => (6.0, &[&[(0.0, 10.0), (0.0, 0.0), (6.0, 0.0)]]),
+        'M' => (
+            8.0,
+            &[&[(0.0, 0.0), (0.0, 10.0), (4.0, 3.0), (8.0, 10.0), (8.0, 0.0)]],
+        ),
+        'N' => (7.0, &[&[(0.0, 0.0), (0.0, 10.0), (7.0, 0.0), (7.0, 10.0)]]),
+        'O' => (
+            8.0,
+            &[&[
+                (1.5, 0.0),
+                (0.0, 1.5),
+                (0.0, 8.5),
+                (1.5, 10.0),
+                (6.5, 10.0),
+                (8.0, 8.5),
+                (8.0, 1.5),
+                (6.5, 0.0),
+                (1.5, 0.0),
+            ]],
+        ),
+        'P' => (
+            7.0,
+            &[&[
+                (0.0, 0.0),
+                (0.0, 10.0),
+                (5.0, 10.0),
+                (7.0, 8.5),
+                (7.0, 6.0),
+                (5.0, 4.5),
+                (0.0, 4.5),
+            ]],
+        ),
+        'Q' => (
+            8.0,
+            &[
+                &[
+                    (1.5, 0.0),
+                    (0.0, 1.5),
+                    (0.0, 8.5),
+                    (1.5, 10.0),
+                    (6.5, 10.0),
+                    (8.0, 8.5),
+                    (8.0, 1.5),
+                    (6.5, 0.0),
+                    (1.5, 0.0),
+                ],
+                &[(5.0, 2.5), (8.5, -1.0)],
+            ],
+        ),
+        'R' => (
+            7.0,
+            &[
+                &[
+                    (0.0, 0.0),
+                    (0.0, 10.0),
+                    (5.0, 10.0),
+                    (7.0, 8.5),
+                    (7.0, 6.0),
+                    (5.0, 4.5),
+                    (0.0, 4.5),
+                ],
+                &[(4.0, 4.5), (7.0, 0.0)],
+            ],
+        ),
+        'S' => (
+            7.0,
+            &[&[
+                (7.0, 8.5),
+                (5.5, 10.0),
+                (1.5, 10.0),
+                (0.0, 8.5),
+                (0.0, 6.5),
+                (1.5, 5.0),
+                (5.5, 5.0),
+                (7.0, 3.5),
+                (7.0, 1.5),
+                (5.5, 0.0),
+                (1.5, 0.0),
+                (0.0, 1.5),
+            ]],
+        ),
+        'T' => (
+            8.0,
+            &[&[(0.0, 10.0), (8.0, 10.0)], &[(4.0, 10.0), (4.0, 0.0)]],
+        ),
+        'U' => (
+            7.0,
+            &[&[
+                (0.0, 10.0),
+                (0.0, 1.5),
+                (1.5, 0.0),
+                (5.5, 0.0),
+                (7.0, 1.5),
+                (7.0, 10.0),
+            ]],
+        ),
+        'V' => (8.0, &[&[(0.0, 10.0), (4.0, 0.0), (8.0, 10.0)]]),
+        'W' => (
+            10.0,
+            &[&[
+                (0.0, 10.0),
+                (2.5, 0.0),
+                (5.0, 7.0),
+                (7.5, 0.0),
+                (10.0, 10.0),
+            ]],
+        ),
+        'X' => (
+            7.0,
+            &[&[(0.0, 10.0), (7.0, 0.0)], &[(7.0, 10.0), (0.0, 0.0)]],
+        ),
+        'Y' => (
+            8.0,
+            &[
+                &[(0.0, 10.0), (4.0, 5.0), (8.0, 10.0)],
+                &[(4.0, 5.0), (4.0, 0.0)],
+            ],
+        ),
+        'Z' => (7.0, &[&[(0.0, 10.0), (7.0, 10.0), (0.0, 0.0), (7.0, 0.0)]]),
+        '0' => (
+            6.0,
+            &[&[
+                (1.5, 0.0),
+                (0.0, 1.5),
+                (0.0, 8.5),
+                (1.5, 10.0),
+                (4.5, 10.0),
+                (6.0, 8.5),
+                (6.0, 1.5),
+                (4.5, 0.0),
+                (1.5, 0.0),
+            ]],
+        ),
+        '1' => (2.5, &[&[(0.0, 8.0), (2.5, 10.0), (2.5, 0.0)]]),
+        '2' => (
+            6.0,
+            &[&[
+                (0.0, 8.5),
+                (1.5, 10.0),
+                (4.5, 10.0),
+                (6.0, 8.5),
+                (6.0, 6.5),
+                (0.0, 0.0),
+                (6.0, 0.0),
+            ]],
+        ),
+        '3' => (
+            6.0,
+            &[
+                &[
+                    (0.0, 8.5),
+                    (1.5, 10.0),
+                    (4.5, 10.0),
+                    (6.0, 8.5),
+                    (6.0, 6.5),
+                    (4.5, 5.2),
+                    (2.0, 5.2),
+                ],
+                &[
+                    (4.5, 5.2),
+                    (6.0, 3.8),
+                    (6.0, 1.5),
+                    (4.5, 0.0),
+                    (1.5, 0.0),
+                    (0.0, 1.5),
+                ],
+            ],
+        ),
+        '4' => (6.0, &[&[(4.5, 0.0), (4.5, 10.0), (0.0, 3.0), (6.0, 3.0)]]),
+        '5' => (
+            6.0,
+            &[&[
+                (6.0, 10.0),
+                (0.5, 10.0),
+                (0.0, 5.5),
+                (4.5, 5.8),
+                (6.0, 4.3),
+                (6.0, 1.5),
+                (4.5, 0.0),
+                (1.5, 0.0),
+                (0.0, 1.5),
+            ]],
+        ),
+        '6' => (
+            6.0,
+            &[&[
+                (5.5, 10.0),
+                (2.0, 10.0),
+                (0.0, 7.5),
+                (0.0, 1.5),
+                (1.5, 0.0),
+                (4.5, 0.0),
+                (6.0, 1.5),
+                (6.0, 4.0),
+                (4.5, 5.5),
+                (1.5, 5.5),
+                (0.0, 4.0),
+            ]],
+        ),
+        '7' => (6.0, &[&[(0.0, 10.0), (6.0, 10.0), (2.0, 0.0)]]),
+        '8' => (
+            6.0,
+            &[&[
+                (1.5, 5.2),
+                (0.0, 6.5),
+                (0.0, 8.7),
+                (1.3, 10.0),
+                (4.7, 10.0),
+                (6.0, 8.7),
+                (6.0, 6.5),
+                (4.5, 5.2),
+                (1.5, 5.2),
+                (0.0, 3.8),
+                (0.0, 1.3),
+                (1.3, 0.0),
+                (4.7, 0.0),
+                (6.0, 1.3),
+                (6.0, 3.8),
+                (4.5, 5.2),
+            ]],
+        ),
+        '9' => (
+            6.0,
+            &[&[
+                (0.5, 0.0),
+                (4.0, 0.0),
+                (6.0, 2.5),
+                (6.0, 8.5),
+                (4.5, 10.0),
+                (1.5, 10.0),
+                (0.0, 8.5),
+                (0.0, 6.0),
+                (1.5, 4.5),
+                (4.5, 4.5),
+                (6.0, 6.0),
+            ]],
+        ),
+        '-' => (4.0, &[&[(0.0, 4.0), (4.0, 4.0)]]),
+        '.' => (0.0, &[&[(0.0, 0.0), (0.0, 0.8)]]),
+        '!' => (
+            0.0,
+            &[&[(0.0, 10.0), (0.0, 3.0)], &[(0.0, 0.8), (0.0, 0.0)]],
+        ),
+        _ => return None,
+    };
+    Some(Glyph { width, strokes })
+}
@@ -0,0 +1,212 @@
+// mod.rs - Lettering sewn as satin columns from the built-in font
+
+mod font;
+
+use crate::cleanup::{tie_stitches, TieStyle, DEFAULT_TIE_LENGTH_MM};
+use crate::dst::{Pattern, StitchCommand, UNITS_PER_MM};
+use crate::generate::satin_along;
+use font::{glyph, Glyph, CAP_HEIGHT, COLUMN_WIDTH, LETTER_GAP};
+
+/// Distance between satin penetrations along a column, whatever the letter size
+const SATIN_SPACING_MM: f64 = 0.4;
+
+/// Error type for lettering
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum LetteringError {
+    #[error("There is no text to sew")]
+    EmptyText,
+    #[error("The font has no glyph for '{0}'")]
+    UnsupportedCharacter(char),
+    #[error("Letter height must be positive")]
+    InvalidHeight,
+    #[error("Text can bend by less than a full circle either way")]
+    InvalidArc,
+}
+
+/// Unit vector from `from` towards `to`
+fn heading(from: (f64, f64), to: (f64, f64)) -> (f64, f64) {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = dx.hypot(dy);
+    (dx / length, dy / length)
+}
+
+/// Sew `text` in satin capitals `height_mm` tall, left to right from the
+/// origin with the baseline on y = 0
+///
+/// Glyph boxes are `spacing_mm` further apart than the font's own gap.
+/// Each letter is tied in and off, and letters are joined by trimmed jumps;
+/// strokes within a letter are joined by plain jumps. With `arc_deg` the
+/// baseline bends into a circular arc spanning that angle, upward for
+/// positive angles and downward for negative ones.
+pub fn generate_text(
+    text: &str,
+    height_mm: f64,
+    spacing_mm: f64,
+    arc_deg: Option<f64>,
+) -> Result<Pattern, LetteringError> {
+    if height_mm.is_nan() || height_mm <= 0.0 {
+        return Err(LetteringError::InvalidHeight);
+    }
+    if arc_deg.is_some_and(|arc| arc.is_nan() || arc.abs() >= 360.0) {
+        return Err(LetteringError::InvalidArc);
+    }
+    let glyphs = text
+        .chars()
+        .map(|c| glyph(c).ok_or(LetteringError::UnsupportedCharacter(c)))
+        .collect::<Result<Vec<Glyph>, _>>()?;
+    if glyphs.iter().all(|g| g.strokes.is_empty()) {
+        return Err(LetteringError::EmptyText);
+    }
+
+    // Font units to 0.1mm
+    let scale = height_mm * UNITS_PER_MM / CAP_HEIGHT;
+    let spacing = spacing_mm * UNITS_PER_MM;
+    let mut letters: Vec<Vec<Vec<(f64, f64)>>> = Vec::new();
+    let mut pen = 0.0;
+    let mut width: f64 = 0.0;
+    for g in &glyphs {
+        if !g.strokes.is_empty() {
+            let columns = g.strokes.iter().map(|stroke| {
+                let placed: Vec<_> = stroke
+                    .iter()
+                    .map(|&(x, y)| (pen + x * scale, -y * scale))
+                    .collect();
+                satin_along(
+                    &placed,
+                    COLUMN_WIDTH * scale,
+                    SATIN_SPACING_MM * UNITS_PER_MM,
+                )
+            });
+            letters.push(columns.collect());
+            width = width.max(pen + g.width * scale);
+        }
+        pen += (g.width + LETTER_GAP) * scale + spacing;
+    }
+
+    if let Some(arc) = arc_deg.filter(|&arc| arc != 0.0 && width > 0.0) {
+        // Wrap the baseline around a circle whose center sits below it for
+        // upward arcs and above it for downward ones
+        let radius = width / arc.to_radians();
+        let center = (width / 2.0, radius);
+        for point in letters.iter_mut().flatten().flatten() {
+            let angle = (point.0 - center.0) / radius;
+            let distance = radius - point.1;
+            *point = (
+                center.0 + distance * angle.sin(),
+                center.1 - distance * angle.cos(),
+            );
+        }
+    }
+
+    let tie = DEFAULT_TIE_LENGTH_MM * UNITS_PER_MM;
+    let mut pattern = Pattern::new();
+    for letter in &letters {
+        if let Some(last) = pattern.stitches.last().cloned() {
+            pattern.add_stitch(last.x, last.y, StitchCommand::Trim);
+        }
+        for (column, points) in letter.iter().enumerate() {
+            let first = points[0];
+            pattern.add_stitch(first.0, first.1, StitchCommand::Move);
+            pattern.add_stitch(first.0, first.1, StitchCommand::Stitch);
+            if column == 0 {
+                for (x, y) in tie_stitches(TieStyle::Back3, first, heading(first, points[1]), tie) {
+                    pattern.add_stitch(x, y, StitchCommand::Stitch);
+                }
+            }
+            for &(x, y) in &points[1..] {
+                pattern.add_stitch(x, y, StitchCommand::Stitch);
+            }
+        }
+        let points = letter.last().expect("letters have strokes");
+        let (end, before) = (points[points.len() - 1], points[points.len() - 2]);
+        for (x, y) in tie_stitches(TieStyle::Back3, end, heading(end, before), tie) {
+            pattern.add_stitch(x, y, StitchCommand::Stitch);
+        }
+    }
+    let (x, y) = pattern.stitches.last().map_or((0.0, 0.0), |s| (s.x, s.y));
+    pattern.add_stitch(x, y, StitchCommand::End);
+
+    pattern.metadata.label = Some(text.trim().to_string());
+    pattern.metadata.stitch_count = Some(pattern.stitches.len() as u32);
+    pattern.calculate_bounds();
+    pattern.calculate_statistics();
+    pattern.calculate_color_blocks();
+    Ok(pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stitch records of each letter, split at the trims between them
+    fn stitches_per_letter(pattern: &Pattern) -> Vec<usize> {
+        pattern
+            .stitches
+            .split(|s| s.command == StitchCommand::Trim)
+            .map(|letter| {
+                letter
+                    .iter()
+                    .filter(|s| s.command == StitchCommand::Stitch)
+                    .count()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_ab_bounds_and_stitch_counts() {
+        let pattern = generate_text("AB", 10.0, 0.0, None).unwrap();
+
+        // A: legs of 21.54mm and a 4.8mm bar at 0.4mm give 55 and 13
+        // penetrations; B: 25.29mm and 12.02mm give 65 and 32; each letter
+        // adds three tie stitches at either end
+        assert_eq!(
+            stitches_per_letter(&pattern),
+            vec![55 + 13 + 6, 65 + 32 + 6]
+        );
+        assert_eq!(pattern.color_blocks.len(), 1);
+
+        // 10mm capitals, widened by at most half the 1.2mm column on each side
+        let bounds = pattern.bounds.clone().unwrap();
+        assert!((bounds.min_y + 106.0).abs() < 1e-9);
+        assert!((0.0..=6.0).contains(&bounds.max_y));
+        assert!((-6.0..=0.0).contains(&bounds.min_x));
+        // B's box ends 8 + 1.5 + 6.5 font units from the origin
+        assert!((bounds.max_x - 166.0).abs() < 1e-9);
+
+        let spaced = generate_text("AB", 10.0, 2.0, None).unwrap();
+        let spaced_bounds = spaced.bounds.clone().unwrap();
+        assert!((spaced_bounds.max_x - 186.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_arc_bends_baseline() {
+        let straight = generate_text("HHH", 10.0, 0.0, None).unwrap();
+        let arched = generate_text("hhh", 10.0, 0.0, Some(90.0)).unwrap();
+        let (flat, bent) = (straight.bounds.unwrap(), arched.bounds.unwrap());
+
+        assert_eq!(arched.stitches.len(), straight.stitches.len());
+        assert!(bent.max_y - bent.min_y > flat.max_y - flat.min_y);
+        // The ends of an upward arc drop below the middle
+        assert!(arched.stitches[1].y > 0.0);
+    }
+
+    #[test]
+    fn test_rejects_bad_input() {
+        assert_eq!(
+            generate_text("A~", 10.0, 0.0, None).unwrap_err(),
+            LetteringError::UnsupportedCharacter('~')
+        );
+        assert_eq!(
+            generate_text("  ", 10.0, 0.0, None).unwrap_err(),
+            LetteringError::EmptyText
+        );
+        assert_eq!(
+            generate_text("A", 0.0, 0.0, None).unwrap_err(),
+            LetteringError::InvalidHeight
+        );
+        assert_eq!(
+            generate_text("A", 10.0, 0.0, Some(360.0)).unwrap_err(),
+            LetteringError::InvalidArc
+        );
+    }
+}
@@ -0,0 +1,76 @@
+// lib.rs - Embroidery design formats, editing and export without the desktop app
+
+//! Reading, editing and writing machine embroidery designs.
+//!
+//! Every format is read into a [`dst::Pattern`]: absolute stitch positions
+//! in 0.1mm units with Y pointing down, the file's metadata and thread
+//! colors, and the bounds, statistics and color blocks derived from them.
+//! Edits, transforms and analyses work on patterns, and writers encode them
+//! again.
+//!
+//! - [`format`]: identify a file and parse it with the right reader
+//! - [`dst`]: the DST family (DST, DSB, DSZ) and tape formats, and the
+//!   pattern types shared by all formats
+//! - [`pes`], [`jef`], [`exp`], [`vp3`], [`xxx`], [`hus`], [`sew`], [`pcs`],
+//!   [`legacy`], [`csv`], [`svg`]: the other readers, and writers where the
+//!   format has one
+//! - [`export`]: PNG, SVG, G-code, stitch lists and worksheets
+//! - [`transform`], [`edit`], [`cleanup`], [`optimize`]: changing designs
+//! - [`analysis`], [`machines`], [`hoops`], [`threads`]: checking them
+//!
+//! ```no_run
+//! use embrocad_core::dst::ParseOptions;
+//! use embrocad_core::export::{encode_design, ExportFormat, ExportOptions};
+//! use embrocad_core::format::read_design;
+//!
+//! let design = read_design("logo.pes", &ParseOptions::default())?;
+//! println!("{} stitches", design.pattern.statistics.real_stitch_count);
+//! let dst = encode_design(&design.pattern, ExportFormat::Dst, &ExportOptions::default())?;
+//! std::fs::write("logo.dst", dst).map_err(|e| e.to_string())?;
+//! # Ok::<(), String>(())
+//! ```
+//!
+//! # Features
+//!
+//! - `serde` (default): `Serialize` and `Deserialize` for the public types,
+//!   the JSON stitch list and the packed stitch form sent to the app's
+//!   frontend
+//! - `fs` (default): [`format::read_design`], companion color files found
+//!   beside a design, and [`batch`] conversion of folders. Without it the
+//!   crate builds for `wasm32-unknown-unknown`; parse bytes with
+//!   [`format::parse_design`] instead
+
+pub mod analysis;
+#[cfg(feature = "fs")]
+pub mod batch;
+pub mod binary;
+pub mod cleanup;
+pub mod companion;
+pub mod csv;
+pub mod digitize;
+pub mod dst;
+pub mod edit;
+pub mod exp;
+pub mod export;
+pub mod format;
+pub mod generate;
+pub mod hoops;
+pub mod hus;
+pub mod jef;
+pub mod legacy;
+pub mod lettering;
+pub mod machines;
+pub mod needle_assignment;
+pub mod optimize;
+pub mod packed;
+pub mod pcs;
+pub mod pes;
+pub mod progress;
+pub mod sew;
+pub mod svg;
+pub mod threads;
+pub mod transform;
+pub mod units;
+pub mod view;
+pub mod vp3;
+pub mod xxx;
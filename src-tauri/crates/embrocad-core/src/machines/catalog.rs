@@ -0,0 +1,104 @@
+// catalog.rs - Hard limits of common embroidery machines
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// What a machine accepts, from the manufacturer's specifications
+///
+/// Custom profiles come from the frontend with the same fields; limits that
+/// are None are not checked.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MachineProfile {
+    pub id: Cow<'static, str>,
+    pub name: Cow<'static, str>,
+    /// Needles, and so thread colors the machine holds at once
+    pub needles: u32,
+    /// Sewing field, centred on the design origin
+    pub field_width_mm: f64,
+    pub field_height_mm: f64,
+    pub max_stitch_mm: f64,
+    /// Longest single jump record
+    pub max_jump_mm: f64,
+    pub max_color_changes: Option<u32>,
+    pub max_stitches: Option<u32>,
+}
+
+#[allow(clippy::too_many_arguments)]
+const fn machine(
+    id: &'static str,
+    name: &'static str,
+    needles: u32,
+    field_width_mm: f64,
+    field_height_mm: f64,
+    max_stitch_mm: f64,
+    max_jump_mm: f64,
+    max_color_changes: Option<u32>,
+    max_stitches: Option<u32>,
+) -> MachineProfile {
+    MachineProfile {
+        id: Cow::Borrowed(id),
+        name: Cow::Borrowed(name),
+        needles,
+        field_width_mm,
+        field_height_mm,
+        max_stitch_mm,
+        max_jump_mm,
+        max_color_changes,
+        max_stitches,
+    }
+}
+
+/// Machines offered in the UI
+pub const MACHINES: &[MachineProfile] = &[
+    machine(
+        "brother_pe800",
+        "Brother PE800",
+        1,
+        130.0,
+        180.0,
+        12.7,
+        12.7,
+        Some(127),
+        Some(500_000),
+    ),
+    machine(
+        "janome_mc500e",
+        "Janome MC500E",
+        1,
+        200.0,
+        280.0,
+        12.7,
+        12.7,
+        Some(99),
+        Some(1_000_000),
+    ),
+    machine(
+        "tajima_tmbp",
+        "Tajima TMBP-SC1501",
+        15,
+        360.0,
+        500.0,
+        12.1,
+        12.1,
+        Some(254),
+        Some(2_000_000),
+    ),
+    machine(
+        "barudan_bext",
+        "Barudan BEXT-S1501C",
+        15,
+        450.0,
+        520.0,
+        12.7,
+        12.7,
+        Some(254),
+        Some(2_000_000),
+    ),
+];
+
+/// Look up a built-in machine by its id
+pub fn find_machine(id: &str) -> Option<&'static MachineProfile> {
+    MACHINES.iter().find(|machine| machine.id == id)
+}
@@ -0,0 +1,7 @@
+// mod.rs - Machine module exports for the built-in profiles and limit checks
+
+mod catalog;
+mod validate;
+
+pub use catalog::{find_machine, MachineProfile, MACHINES};
+pub use validate::{validate_for_machine, MachineViolation};
@@ -0,0 +1,275 @@
+// validate.rs - Check a design against a machine's hard limits
+
+use crate::dst::{Pattern, StitchCommand, UNITS_PER_MM};
+use crate::export::block_rgb;
+use crate::machines::catalog::MachineProfile;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// One way a design breaks a machine's limits
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum MachineViolation {
+    JumpTooLong {
+        index: usize,
+        length_mm: f64,
+        max_mm: f64,
+    },
+    StitchTooLong {
+        index: usize,
+        length_mm: f64,
+        max_mm: f64,
+    },
+    TooManyColorChanges {
+        count: u32,
+        max: u32,
+    },
+    TooManyStitches {
+        count: u32,
+        max: u32,
+    },
+    /// The design reaches beyond the field when centred on the origin
+    ExceedsField {
+        width_mm: f64,
+        height_mm: f64,
+        field_width_mm: f64,
+        field_height_mm: f64,
+    },
+    /// More distinct thread colors than needles, so threads must be swapped
+    /// mid-design; only reported for multi-needle machines
+    MoreColorsThanNeedles {
+        colors: usize,
+        needles: u32,
+    },
+}
+
+/// Every limit of `machine` that `pattern` breaks, per-record violations in
+/// stitch order first
+pub fn validate_for_machine(pattern: &Pattern, machine: &MachineProfile) -> Vec<MachineViolation> {
+    let mut violations = Vec::new();
+
+    for (index, stitch) in pattern.stitches.iter().enumerate() {
+        let Some(length_mm) = pattern.record_length_mm(index) else {
+            continue;
+        };
+        match stitch.command {
+            StitchCommand::Move if length_mm > machine.max_jump_mm => {
+                violations.push(MachineViolation::JumpTooLong {
+                    index,
+                    length_mm,
+                    max_mm: machine.max_jump_mm,
+                });
+            }
+            StitchCommand::Stitch if length_mm > machine.max_stitch_mm => {
+                violations.push(MachineViolation::StitchTooLong {
+                    index,
+                    length_mm,
+                    max_mm: machine.max_stitch_mm,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    let color_changes = pattern.color_blocks.len().saturating_sub(1) as u32;
+    if let Some(max) = machine.max_color_changes.filter(|&max| color_changes > max) {
+        violations.push(MachineViolation::TooManyColorChanges {
+            count: color_changes,
+            max,
+        });
+    }
+
+    let stitches = pattern.statistics.real_stitch_count;
+    if let Some(max) = machine.max_stitches.filter(|&max| stitches > max) {
+        violations.push(MachineViolation::TooManyStitches {
+            count: stitches,
+            max,
+        });
+    }
+
+    if let Some(bounds) = &pattern.bounds {
+        let reach_x = bounds.min_x.abs().max(bounds.max_x.abs()) / UNITS_PER_MM;
+        let reach_y = bounds.min_y.abs().max(bounds.max_y.abs()) / UNITS_PER_MM;
+        if reach_x * 2.0 > machine.field_width_mm || reach_y * 2.0 > machine.field_height_mm {
+            violations.push(MachineViolation::ExceedsField {
+                width_mm: bounds.width() / UNITS_PER_MM,
+                height_mm: bounds.height() / UNITS_PER_MM,
+                field_width_mm: machine.field_width_mm,
+                field_height_mm: machine.field_height_mm,
+            });
+        }
+    }
+
+    if machine.needles > 1 {
+        let colors: HashSet<[u8; 3]> = (0..pattern.color_blocks.len())
+            .map(|block| block_rgb(pattern, block))
+            .collect();
+        if colors.len() > machine.needles as usize {
+            violations.push(MachineViolation::MoreColorsThanNeedles {
+                colors: colors.len(),
+                needles: machine.needles,
+            });
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::ThreadColor;
+    use crate::machines::catalog::find_machine;
+    use std::borrow::Cow;
+
+    /// A generous custom machine, tightened per test
+    fn machine() -> MachineProfile {
+        MachineProfile {
+            id: Cow::Borrowed("test"),
+            name: Cow::Borrowed("Test"),
+            needles: 4,
+            field_width_mm: 100.0,
+            field_height_mm: 100.0,
+            max_stitch_mm: 10.0,
+            max_jump_mm: 10.0,
+            max_color_changes: Some(10),
+            max_stitches: Some(1000),
+        }
+    }
+
+    /// Records given as (x, y, command) in 0.1mm, with one color per block
+    fn pattern(records: &[(f64, f64, StitchCommand)]) -> Pattern {
+        let mut pattern = Pattern::new();
+        for &(x, y, command) in records {
+            pattern.add_stitch(x, y, command);
+        }
+        pattern.calculate_bounds();
+        pattern.calculate_statistics();
+        pattern.calculate_color_blocks();
+        pattern.metadata.thread_colors = (0..pattern.color_blocks.len())
+            .map(|block| ThreadColor::new([block as u8 * 40, 0, 0]))
+            .collect();
+        pattern.calculate_color_blocks();
+        pattern
+    }
+
+    use StitchCommand::*;
+
+    #[test]
+    fn test_design_within_limits() {
+        let design = pattern(&[(0.0, 0.0, Stitch), (50.0, 0.0, Stitch), (50.0, 0.0, End)]);
+        assert_eq!(validate_for_machine(&design, &machine()), vec![]);
+        let pe800 = find_machine("brother_pe800").unwrap();
+        assert_eq!(validate_for_machine(&design, pe800), vec![]);
+    }
+
+    #[test]
+    fn test_long_jumps_and_stitches() {
+        let design = pattern(&[
+            (0.0, 0.0, Stitch),
+            (150.0, 0.0, Move),
+            (150.0, 80.0, Stitch),
+            (150.0, 190.0, Stitch),
+            (150.0, 190.0, End),
+        ]);
+        assert_eq!(
+            validate_for_machine(&design, &machine()),
+            vec![
+                MachineViolation::JumpTooLong {
+                    index: 1,
+                    length_mm: 15.0,
+                    max_mm: 10.0
+                },
+                MachineViolation::StitchTooLong {
+                    index: 3,
+                    length_mm: 11.0,
+                    max_mm: 10.0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_counts() {
+        let mut records = vec![(0.0, 0.0, Stitch)];
+        for i in 1..=3 {
+            records.push((0.0, 0.0, ColorChange));
+            records.push((i as f64, 0.0, Stitch));
+        }
+        let design = pattern(&records);
+
+        let tight = MachineProfile {
+            max_color_changes: Some(2),
+            max_stitches: Some(3),
+            ..machine()
+        };
+        assert_eq!(
+            validate_for_machine(&design, &tight),
+            vec![
+                MachineViolation::TooManyColorChanges { count: 3, max: 2 },
+                MachineViolation::TooManyStitches { count: 4, max: 3 },
+            ]
+        );
+
+        let unlimited = MachineProfile {
+            max_color_changes: None,
+            max_stitches: None,
+            ..tight
+        };
+        assert_eq!(validate_for_machine(&design, &unlimited), vec![]);
+    }
+
+    #[test]
+    fn test_field_is_centred_on_origin() {
+        // 60mm wide, but reaching 70mm to the right of the origin
+        let row = |from: i32| -> Vec<_> {
+            (0..=12)
+                .map(|i| ((from + i * 50) as f64, 0.0, Stitch))
+                .collect()
+        };
+        let design = pattern(&row(100));
+        assert_eq!(
+            validate_for_machine(&design, &machine()),
+            vec![MachineViolation::ExceedsField {
+                width_mm: 60.0,
+                height_mm: 0.0,
+                field_width_mm: 100.0,
+                field_height_mm: 100.0,
+            }]
+        );
+
+        let centred = pattern(&row(-300));
+        assert_eq!(validate_for_machine(&centred, &machine()), vec![]);
+    }
+
+    #[test]
+    fn test_more_colors_than_needles() {
+        let mut records = vec![(0.0, 0.0, Stitch)];
+        for _ in 0..4 {
+            records.push((0.0, 0.0, ColorChange));
+            records.push((0.0, 0.0, Stitch));
+        }
+        let mut design = pattern(&records);
+        assert_eq!(
+            validate_for_machine(&design, &machine()),
+            vec![MachineViolation::MoreColorsThanNeedles {
+                colors: 5,
+                needles: 4
+            }]
+        );
+
+        // A repeated color shares a needle
+        design.metadata.thread_colors[4] = design.metadata.thread_colors[0].clone();
+        design.calculate_color_blocks();
+        assert_eq!(validate_for_machine(&design, &machine()), vec![]);
+
+        // Single-needle machines stop for every color anyway
+        let single = MachineProfile {
+            needles: 1,
+            ..machine()
+        };
+        assert_eq!(validate_for_machine(&design, &single), vec![]);
+    }
+}
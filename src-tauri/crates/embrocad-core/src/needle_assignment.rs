@@ -0,0 +1,241 @@
+// needle_assignment.rs - Map color blocks to the needles of a multi-needle machine
+
+use crate::dst::{Pattern, ThreadColor};
+use crate::export::block_rgb;
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+/// How needles are chosen for the color blocks
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "kind", content = "needles", rename_all = "snake_case")
+)]
+pub enum NeedleStrategy {
+    /// Needle 1 for the first block, 2 for the second and so on
+    Sequential,
+    /// A new needle per distinct color; blocks repeating a color share its needle
+    ByColorReuse,
+    /// One needle per block, as given
+    Manual(Vec<u8>),
+}
+
+/// Error type for needle assignment
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum NeedleError {
+    #[error("The machine must have at least one needle")]
+    NoNeedles,
+    #[error("The design needs {required} needles but the machine has {needle_count}")]
+    TooFewNeedles { required: usize, needle_count: u8 },
+    #[error("Expected one needle for each of the {expected} color blocks, got {actual}")]
+    WrongCount { expected: usize, actual: usize },
+    #[error("Needle {needle} for color block {block} is not between 1 and {needle_count}")]
+    NoSuchNeedle {
+        block: usize,
+        needle: u8,
+        needle_count: u8,
+    },
+}
+
+/// The needle each color block would use under `strategy`, checked against
+/// the machine's `needle_count`
+pub fn plan_needles(
+    pattern: &Pattern,
+    strategy: &NeedleStrategy,
+    needle_count: u8,
+) -> Result<Vec<u8>, NeedleError> {
+    if needle_count == 0 {
+        return Err(NeedleError::NoNeedles);
+    }
+    let blocks = pattern.color_blocks.len();
+
+    let needles = match strategy {
+        NeedleStrategy::Sequential => {
+            if blocks > needle_count as usize {
+                return Err(NeedleError::TooFewNeedles {
+                    required: blocks,
+                    needle_count,
+                });
+            }
+            (1..=blocks as u8).collect()
+        }
+        NeedleStrategy::ByColorReuse => {
+            let mut loaded: Vec<[u8; 3]> = Vec::new();
+            let positions: Vec<usize> = (0..blocks)
+                .map(|block| {
+                    let rgb = block_rgb(pattern, block);
+                    loaded.iter().position(|&c| c == rgb).unwrap_or_else(|| {
+                        loaded.push(rgb);
+                        loaded.len() - 1
+                    })
+                })
+                .collect();
+            if loaded.len() > needle_count as usize {
+                return Err(NeedleError::TooFewNeedles {
+                    required: loaded.len(),
+                    needle_count,
+                });
+            }
+            positions.iter().map(|&i| i as u8 + 1).collect()
+        }
+        NeedleStrategy::Manual(needles) => {
+            if needles.len() != blocks {
+                return Err(NeedleError::WrongCount {
+                    expected: blocks,
+                    actual: needles.len(),
+                });
+            }
+            if let Some((block, &needle)) = needles
+                .iter()
+                .enumerate()
+                .find(|&(_, &needle)| needle == 0 || needle > needle_count)
+            {
+                return Err(NeedleError::NoSuchNeedle {
+                    block,
+                    needle,
+                    needle_count,
+                });
+            }
+            needles.clone()
+        }
+    };
+    Ok(needles)
+}
+
+/// Put each color block's thread on a needle, returning the needles
+///
+/// Blocks without a declared color get the one they are drawn in, so the
+/// needle has a thread to travel with.
+pub fn assign_needles(
+    pattern: &mut Pattern,
+    strategy: &NeedleStrategy,
+    needle_count: u8,
+) -> Result<Vec<u8>, NeedleError> {
+    let needles = plan_needles(pattern, strategy, needle_count)?;
+    let colors = needles
+        .iter()
+        .enumerate()
+        .map(|(block, &needle)| {
+            let mut color = pattern
+                .thread_color_for_block(block)
+                .cloned()
+                .unwrap_or_else(|| ThreadColor::new(block_rgb(pattern, block)));
+            color.needle = Some(needle);
+            (block, color)
+        })
+        .collect();
+    pattern
+        .set_block_colors(colors)
+        .expect("needles are planned for existing blocks only");
+    Ok(needles)
+}
+
+/// The needle of each color block, None where none is assigned
+pub fn block_needles(pattern: &Pattern) -> Vec<Option<u8>> {
+    (0..pattern.color_blocks.len())
+        .map(|block| pattern.thread_color_for_block(block).and_then(|c| c.needle))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::StitchCommand;
+
+    const RED: [u8; 3] = [255, 0, 0];
+    const WHITE: [u8; 3] = [255, 255, 255];
+    const BLUE: [u8; 3] = [0, 0, 255];
+
+    /// One short block per color, in order
+    fn design(colors: &[[u8; 3]]) -> Pattern {
+        let mut pattern = Pattern::new();
+        for block in 0..colors.len() {
+            let x = block as f64 * 10.0;
+            if block > 0 {
+                pattern.add_stitch(x - 5.0, 0.0, StitchCommand::ColorChange);
+            }
+            pattern.add_stitch(x, 0.0, StitchCommand::Stitch);
+            pattern.add_stitch(x + 5.0, 0.0, StitchCommand::Stitch);
+        }
+        pattern.add_stitch(colors.len() as f64 * 10.0, 0.0, StitchCommand::End);
+        pattern.metadata.thread_colors = colors.iter().copied().map(ThreadColor::new).collect();
+        pattern.calculate_color_blocks();
+        pattern
+    }
+
+    #[test]
+    fn test_reuse_shares_needles_for_repeated_colors() {
+        // A flag sewn red, white, blue with red and white stripes returning
+        let mut pattern = design(&[RED, WHITE, RED, BLUE, WHITE, RED]);
+
+        let needles = assign_needles(&mut pattern, &NeedleStrategy::ByColorReuse, 3).unwrap();
+        assert_eq!(needles, vec![1, 2, 1, 3, 2, 1]);
+        assert_eq!(
+            block_needles(&pattern),
+            vec![Some(1), Some(2), Some(1), Some(3), Some(2), Some(1)]
+        );
+        assert_eq!(pattern.metadata.thread_colors[3].rgb, BLUE);
+
+        // Sequential loads every block on its own needle
+        assert_eq!(
+            plan_needles(&pattern, &NeedleStrategy::Sequential, 3),
+            Err(NeedleError::TooFewNeedles {
+                required: 6,
+                needle_count: 3
+            })
+        );
+        assert_eq!(
+            plan_needles(&pattern, &NeedleStrategy::ByColorReuse, 2),
+            Err(NeedleError::TooFewNeedles {
+                required: 3,
+                needle_count: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_sequential_and_undeclared_colors() {
+        let mut pattern = design(&[RED, BLUE, WHITE]);
+        pattern.metadata.thread_colors.truncate(1);
+        pattern.calculate_color_blocks();
+
+        let needles = assign_needles(&mut pattern, &NeedleStrategy::Sequential, 15).unwrap();
+        assert_eq!(needles, vec![1, 2, 3]);
+        assert_eq!(pattern.metadata.thread_colors.len(), 3);
+        assert_eq!(
+            pattern.metadata.thread_colors[2].rgb,
+            crate::export::DEFAULT_PALETTE[2]
+        );
+    }
+
+    #[test]
+    fn test_manual_needles_are_checked() {
+        let pattern = design(&[RED, BLUE]);
+        let manual = NeedleStrategy::Manual;
+
+        assert_eq!(
+            plan_needles(&pattern, &manual(vec![4, 4]), 4),
+            Ok(vec![4, 4])
+        );
+        assert_eq!(
+            plan_needles(&pattern, &manual(vec![1]), 4),
+            Err(NeedleError::WrongCount {
+                expected: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            plan_needles(&pattern, &manual(vec![1, 5]), 4),
+            Err(NeedleError::NoSuchNeedle {
+                block: 1,
+                needle: 5,
+                needle_count: 4
+            })
+        );
+        assert_eq!(
+            plan_needles(&pattern, &manual(vec![1, 2]), 0),
+            Err(NeedleError::NoNeedles)
+        );
+    }
+}
@@ -0,0 +1,271 @@
+// colors.rs - Group same-color blocks to cut color changes where layering allows
+
+use crate::dst::{Bounds, Pattern, Stitch, StitchCommand};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// A block moved up to sew straight after an earlier block of its color
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct BlockMerge {
+    pub block: usize,
+    pub after_block: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum SkipReason {
+    /// Moving the block would sew it before a block it overlaps
+    Overlaps { block: usize },
+}
+
+/// A same-color block that stayed where it was
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct BlockSkip {
+    pub block: usize,
+    pub after_block: usize,
+    pub reason: SkipReason,
+}
+
+/// What `optimize_colors` changed, with block indices from the original order
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ColorSortReport {
+    pub color_changes_before: u32,
+    pub color_changes_after: u32,
+    pub order: Vec<usize>,
+    pub merged: Vec<BlockMerge>,
+    pub skipped: Vec<BlockSkip>,
+}
+
+/// Whether two blocks' penetration bounds touch or intersect
+pub fn overlaps(a: &Option<Bounds>, b: &Option<Bounds>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            a.min_x <= b.max_x && b.min_x <= a.max_x && a.min_y <= b.max_y && b.min_y <= a.max_y
+        }
+        _ => false,
+    }
+}
+
+/// Penetration bounds of one block's records
+pub fn record_bounds(records: &[Stitch]) -> Option<Bounds> {
+    let mut bounds: Option<Bounds> = None;
+    for stitch in records
+        .iter()
+        .filter(|s| s.command == StitchCommand::Stitch)
+    {
+        bounds
+            .get_or_insert_with(Bounds::new)
+            .update(stitch.x, stitch.y);
+    }
+    bounds
+}
+
+impl Pattern {
+    /// Move later blocks up next to earlier blocks of the same thread color
+    ///
+    /// A block only moves if its bounds miss every block it would jump ahead
+    /// of, so nothing that was sewn on top of something else changes layer.
+    /// Blocks without a declared thread color are never grouped.
+    pub fn optimize_colors(&mut self) -> ColorSortReport {
+        let blocks = self.block_records();
+        let colors: Vec<Option<[u8; 3]>> = (0..blocks.len())
+            .map(|b| self.thread_color_for_block(b).map(|c| c.rgb))
+            .collect();
+        let bounds: Vec<Option<Bounds>> = blocks.iter().map(|r| record_bounds(r)).collect();
+
+        let mut report = ColorSortReport {
+            color_changes_before: self.color_changes,
+            ..ColorSortReport::default()
+        };
+
+        let mut order: Vec<usize> = (0..blocks.len()).collect();
+        let mut position = 0;
+        while position < order.len() {
+            let Some(color) = colors[order[position]] else {
+                position += 1;
+                continue;
+            };
+
+            let mut group_end = position;
+            while group_end + 1 < order.len() && colors[order[group_end + 1]] == Some(color) {
+                group_end += 1;
+            }
+
+            for candidate_at in group_end + 2..order.len() {
+                let candidate = order[candidate_at];
+                if colors[candidate] != Some(color) {
+                    continue;
+                }
+
+                let after_block = order[group_end];
+                let blocker = order[group_end + 1..candidate_at]
+                    .iter()
+                    .find(|&&between| overlaps(&bounds[candidate], &bounds[between]));
+                if let Some(&block) = blocker {
+                    report.skipped.push(BlockSkip {
+                        block: candidate,
+                        after_block,
+                        reason: SkipReason::Overlaps { block },
+                    });
+                    continue;
+                }
+
+                order.remove(candidate_at);
+                order.insert(group_end + 1, candidate);
+                group_end += 1;
+                report.merged.push(BlockMerge {
+                    block: candidate,
+                    after_block,
+                });
+            }
+            position = group_end + 1;
+        }
+
+        if !report.merged.is_empty() {
+            self.rebuild_in_order(&blocks, &colors, &order);
+        }
+        report.color_changes_after = self.color_changes;
+        report.order = order;
+        report
+    }
+
+    /// Rebuild the stitch list from blocks in a new order
+    ///
+    /// Consecutive blocks of one color are joined with a trim instead of a
+    /// color change, and a block whose predecessor changed is entered with a
+    /// jump so no stitch is drawn across the seam. Pass `None` colors to keep
+    /// every block separate.
+    pub fn rebuild_in_order(
+        &mut self,
+        blocks: &[Vec<Stitch>],
+        colors: &[Option<[u8; 3]>],
+        order: &[usize],
+    ) {
+        let thread_colors = self.metadata.thread_colors.clone();
+        let mut stitches: Vec<Stitch> = Vec::with_capacity(self.stitches.len());
+        let mut new_colors = Vec::new();
+        let mut color_changes = 0;
+
+        for (position, &block) in order.iter().enumerate() {
+            let records = &blocks[block];
+            if position > 0 {
+                let previous = order[position - 1];
+                let (x, y) = stitches.last().map_or((0.0, 0.0), |s| (s.x, s.y));
+                if colors[block].is_some() && colors[block] == colors[previous] {
+                    stitches.push(Stitch::new(x, y, StitchCommand::Trim));
+                } else {
+                    stitches.push(Stitch::new(x, y, StitchCommand::ColorChange));
+                    color_changes += 1;
+                    new_colors.extend(thread_colors.get(block).cloned());
+                }
+                let entry = records.first().filter(|s| s.command != StitchCommand::Move);
+                if let (true, Some(first)) = (previous + 1 != block, entry) {
+                    stitches.push(Stitch::new(first.x, first.y, StitchCommand::Move));
+                }
+            } else {
+                new_colors.extend(thread_colors.get(block).cloned());
+            }
+            stitches.extend(records.iter().cloned());
+        }
+
+        let (x, y) = stitches.last().map_or((0.0, 0.0), |s| (s.x, s.y));
+        stitches.push(Stitch::new(x, y, StitchCommand::End));
+
+        self.stitches = stitches;
+        self.color_changes = color_changes;
+        self.metadata.color_count = Some(color_changes);
+        self.metadata.thread_colors = new_colors;
+
+        // Calculate bounds, statistics and color blocks
+        self.calculate_bounds();
+        self.calculate_statistics();
+        self.calculate_color_blocks();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::ThreadColor;
+
+    const RED: [u8; 3] = [255, 0, 0];
+    const BLUE: [u8; 3] = [0, 0, 255];
+
+    /// One 2mm square of stitches per block, at the given x offsets
+    fn blocks(layout: &[([u8; 3], f64)]) -> Pattern {
+        let mut pattern = Pattern::new();
+        for (index, &(color, x)) in layout.iter().enumerate() {
+            if index > 0 {
+                let last = pattern.stitches.last().unwrap().clone();
+                pattern.add_stitch(last.x, last.y, StitchCommand::ColorChange);
+            }
+            pattern.add_stitch(x, 0.0, StitchCommand::Move);
+            for (dx, dy) in [(0.0, 0.0), (20.0, 0.0), (20.0, 20.0), (0.0, 20.0)] {
+                pattern.add_stitch(x + dx, dy, StitchCommand::Stitch);
+            }
+            pattern.metadata.thread_colors.push(ThreadColor::new(color));
+        }
+        let last = pattern.stitches.last().unwrap().clone();
+        pattern.add_stitch(last.x, last.y, StitchCommand::End);
+        pattern.calculate_bounds();
+        pattern.calculate_statistics();
+        pattern.calculate_color_blocks();
+        pattern
+    }
+
+    #[test]
+    fn test_groups_separate_blocks() {
+        let mut pattern = blocks(&[(RED, 0.0), (BLUE, 100.0), (RED, 200.0), (BLUE, 300.0)]);
+        let report = pattern.optimize_colors();
+
+        assert_eq!(report.order, vec![0, 2, 1, 3]);
+        assert_eq!(
+            (report.color_changes_before, report.color_changes_after),
+            (3, 1)
+        );
+        // Moving the second red block up leaves the two blues adjacent
+        assert_eq!(
+            report.merged,
+            vec![BlockMerge {
+                block: 2,
+                after_block: 0
+            }]
+        );
+        assert!(report.skipped.is_empty());
+
+        let colors: Vec<_> = pattern
+            .metadata
+            .thread_colors
+            .iter()
+            .map(|c| c.rgb)
+            .collect();
+        assert_eq!(colors, vec![RED, BLUE]);
+        assert_eq!(pattern.color_blocks.len(), 2);
+        assert_eq!(pattern.statistics.trim_count, 2);
+        assert_eq!(pattern.statistics.real_stitch_count, 16);
+    }
+
+    #[test]
+    fn test_overlap_vetoes_reorder() {
+        // The second red block sits on top of the blue one
+        let mut pattern = blocks(&[(RED, 0.0), (BLUE, 100.0), (RED, 110.0)]);
+        let original = pattern.stitches.clone();
+        let report = pattern.optimize_colors();
+
+        assert!(report.merged.is_empty());
+        assert_eq!(
+            report.skipped,
+            vec![BlockSkip {
+                block: 2,
+                after_block: 0,
+                reason: SkipReason::Overlaps { block: 1 }
+            }]
+        );
+        assert_eq!(report.color_changes_after, 2);
+        assert_eq!(pattern.stitches, original);
+    }
+}
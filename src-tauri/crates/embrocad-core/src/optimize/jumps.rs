@@ -0,0 +1,190 @@
+// jumps.rs - Reverse color blocks to shorten travel between them
+
+use crate::dst::{Pattern, Stitch, StitchCommand, UNITS_PER_MM};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// Travel between consecutive color blocks before and after optimizing
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct JumpReport {
+    pub before_mm: f64,
+    pub after_mm: f64,
+    /// Blocks that are (or would be) sewn backwards
+    pub reversed_blocks: Vec<usize>,
+}
+
+/// Where a color block starts and stops sewing
+struct BlockEnds {
+    first: (f64, f64),
+    last: (f64, f64),
+    /// A single unbroken run of stitches, which sews the same either way
+    reversible: bool,
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1) / UNITS_PER_MM
+}
+
+/// Endpoints of each color block, split at ColorChange like `reverse_block`
+fn block_ends(stitches: &[Stitch]) -> Vec<Option<BlockEnds>> {
+    let mut blocks = Vec::new();
+    let mut current: Option<BlockEnds> = None;
+    let mut broken = false;
+    // A Move landing right before the first stitch is where sewing starts
+    let mut landing: Option<(f64, f64)> = None;
+    for stitch in stitches {
+        match stitch.command {
+            StitchCommand::ColorChange | StitchCommand::End => {
+                blocks.push(current.take());
+                broken = false;
+                landing = None;
+                if stitch.command == StitchCommand::End {
+                    return blocks;
+                }
+            }
+            StitchCommand::Stitch => match &mut current {
+                Some(block) => {
+                    block.reversible &= !broken;
+                    block.last = (stitch.x, stitch.y);
+                    broken = false;
+                }
+                None => {
+                    current = Some(BlockEnds {
+                        first: landing.unwrap_or((stitch.x, stitch.y)),
+                        last: (stitch.x, stitch.y),
+                        reversible: true,
+                    });
+                }
+            },
+            // Jumps and trims inside a block break it into separate runs
+            _ => {
+                broken = current.is_some();
+                landing = Some((stitch.x, stitch.y));
+            }
+        }
+    }
+    blocks.push(current);
+    blocks
+}
+
+/// Sum of the travel from each block's last stitch to the next block's first
+fn travel(blocks: &[Option<BlockEnds>]) -> f64 {
+    let ends: Vec<_> = blocks.iter().flatten().collect();
+    ends.windows(2)
+        .map(|w| distance(w[0].last, w[1].first))
+        .sum()
+}
+
+impl Pattern {
+    /// Greedily reverse reversible blocks so each starts at the end nearest
+    /// the previous block's exit, returning the projected saving
+    ///
+    /// With `dry_run` the pattern is left untouched.
+    pub fn optimize_jumps(&mut self, dry_run: bool) -> JumpReport {
+        let mut blocks = block_ends(&self.stitches);
+        let mut report = JumpReport {
+            before_mm: travel(&blocks),
+            ..JumpReport::default()
+        };
+
+        let mut exit: Option<(f64, f64)> = None;
+        for (index, block) in blocks.iter_mut().enumerate() {
+            let Some(block) = block else { continue };
+            if let Some(exit) = exit {
+                if block.reversible && distance(exit, block.last) < distance(exit, block.first) {
+                    std::mem::swap(&mut block.first, &mut block.last);
+                    report.reversed_blocks.push(index);
+                }
+            }
+            exit = Some(block.last);
+        }
+        report.after_mm = travel(&blocks);
+
+        if !dry_run {
+            for &index in &report.reversed_blocks {
+                self.reverse_block(index);
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A horizontal running-stitch line from `from` to `to` along y
+    fn line(pattern: &mut Pattern, from: f64, to: f64, y: f64) {
+        pattern.add_stitch(from, y, StitchCommand::Move);
+        pattern.add_stitch(from, y, StitchCommand::Stitch);
+        let step = if to > from { 10.0 } else { -10.0 };
+        let mut x = from;
+        while (to - x).abs() > 1e-9 {
+            x += step;
+            pattern.add_stitch(x, y, StitchCommand::Stitch);
+        }
+    }
+
+    /// Three lines that all run left to right, so every other one is backwards
+    fn zigzag() -> Pattern {
+        let mut pattern = Pattern::new();
+        line(&mut pattern, 0.0, 100.0, 0.0);
+        pattern.add_stitch(100.0, 0.0, StitchCommand::ColorChange);
+        line(&mut pattern, 0.0, 100.0, 10.0);
+        pattern.add_stitch(100.0, 10.0, StitchCommand::ColorChange);
+        line(&mut pattern, 0.0, 100.0, 20.0);
+        pattern.add_stitch(100.0, 20.0, StitchCommand::End);
+        pattern.calculate_bounds();
+        pattern.calculate_statistics();
+        pattern.calculate_color_blocks();
+        pattern
+    }
+
+    #[test]
+    fn test_reverses_blocks_to_shorten_travel() {
+        let mut pattern = zigzag();
+        let report = pattern.optimize_jumps(false);
+
+        assert_eq!(report.reversed_blocks, vec![1]);
+        assert!((report.before_mm - 2.0 * 10.0f64.hypot(1.0)).abs() < 1e-9);
+        assert!((report.after_mm - 2.0).abs() < 1e-9);
+
+        // The second line now starts right above where the first ended
+        let start = pattern.color_blocks[1].start;
+        let entry = &pattern.stitches[start + 1];
+        assert_eq!(
+            (entry.x, entry.y, entry.command),
+            (100.0, 10.0, StitchCommand::Move)
+        );
+        assert!((pattern.optimize_jumps(true).before_mm - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_dry_run_leaves_pattern_untouched() {
+        let mut pattern = zigzag();
+        let report = pattern.optimize_jumps(true);
+
+        assert_eq!(report.reversed_blocks, vec![1]);
+        assert_eq!(pattern.stitches, zigzag().stitches);
+    }
+
+    #[test]
+    fn test_broken_blocks_are_not_reversed() {
+        let mut pattern = zigzag();
+        // A trim and jump inside the second block makes it unsafe to reverse
+        let index = pattern
+            .stitches
+            .iter()
+            .position(|s| s.x == 50.0 && s.y == 10.0)
+            .unwrap();
+        pattern
+            .stitches
+            .insert(index + 1, Stitch::new(50.0, 10.0, StitchCommand::Trim));
+
+        // Only the last line can still be turned around
+        let report = pattern.optimize_jumps(true);
+        assert_eq!(report.reversed_blocks, vec![2]);
+        assert!((report.after_mm - (10.0f64.hypot(1.0) + 1.0)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,7 @@
+// mod.rs - Sewing-order optimizations that reduce travel and thread waste
+
+mod colors;
+mod jumps;
+
+pub use colors::{overlaps, record_bounds, ColorSortReport};
+pub use jumps::JumpReport;
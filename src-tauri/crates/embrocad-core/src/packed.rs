@@ -0,0 +1,193 @@
+// packed.rs - Compact stitch arrays for sending large patterns over IPC
+
+use crate::analysis::UnderlayRun;
+use crate::dst::Pattern;
+#[cfg(feature = "serde")]
+use base64::engine::general_purpose::STANDARD;
+#[cfg(feature = "serde")]
+use base64::Engine;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer};
+
+/// Stitches as two flat arrays instead of one JSON object per stitch
+///
+/// Serialized as `{ count, coordinates, commands }` where both arrays are
+/// base64 strings. To unpack on the frontend, decode `coordinates` to bytes
+/// and view them as a little-endian `Float32Array` of `count * 2` values
+/// (x0, y0, x1, y1, ...) in 0.1mm; decode `commands` to a `Uint8Array` of
+/// `count` codes: 0 Stitch, 1 Move, 2 Trim, 3 ColorChange, 4 SequinMode,
+/// 5 SequinEject, 6 End. This takes 9 bytes per stitch before base64,
+/// against about 45 for the JSON objects.
+///
+/// `flags`, when present, is another base64 `Uint8Array` of `count` bit
+/// sets; `FLAG_UNDERLAY` marks stitches the frontend may hide as underlay.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct PackedStitches {
+    pub count: usize,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_f32s"))]
+    pub coordinates: Vec<f32>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_bytes"))]
+    pub commands: Vec<u8>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "serialize_flags",
+            skip_serializing_if = "Option::is_none"
+        )
+    )]
+    pub flags: Option<Vec<u8>>,
+}
+
+/// Bit in `PackedStitches::flags` for a stitch that is likely underlay
+pub const FLAG_UNDERLAY: u8 = 1;
+
+#[cfg(feature = "serde")]
+fn serialize_f32s<S: Serializer>(values: &[f32], serializer: S) -> Result<S::Ok, S::Error> {
+    let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+    serializer.serialize_str(&STANDARD.encode(bytes))
+}
+
+#[cfg(feature = "serde")]
+fn serialize_bytes<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&STANDARD.encode(bytes))
+}
+
+#[cfg(feature = "serde")]
+fn serialize_flags<S: Serializer>(
+    flags: &Option<Vec<u8>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match flags {
+        Some(bytes) => serialize_bytes(bytes, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+impl PackedStitches {
+    /// Set `FLAG_UNDERLAY` on the records of each run
+    pub fn flag_underlay(&mut self, runs: &[UnderlayRun]) {
+        let flags = self.flags.get_or_insert_with(|| vec![0; self.count]);
+        for run in runs {
+            for flag in &mut flags[run.start.min(self.count)..run.end.min(self.count)] {
+                *flag |= FLAG_UNDERLAY;
+            }
+        }
+    }
+}
+
+impl Pattern {
+    /// Pack the stitch list into flat coordinate and command arrays
+    ///
+    /// f32 keeps every coordinate of a real design exact to well under a
+    /// hundredth of a 0.1mm unit; see the round-trip tests.
+    pub fn to_packed(&self) -> PackedStitches {
+        PackedStitches {
+            count: self.stitches.len(),
+            coordinates: self
+                .stitches
+                .iter()
+                .flat_map(|s| [s.x as f32, s.y as f32])
+                .collect(),
+            commands: self.stitches.iter().map(|s| s.command.code()).collect(),
+            flags: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::{Stitch, StitchCommand, MAX_COORDINATE};
+
+    /// The frontend's side of the contract, decoding the serialized form
+    fn unpack(json: &serde_json::Value) -> Vec<Stitch> {
+        let decode = |key: &str| STANDARD.decode(json[key].as_str().unwrap()).unwrap();
+        let coordinates: Vec<f32> = decode("coordinates")
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+
+        decode("commands")
+            .into_iter()
+            .enumerate()
+            .map(|(i, code)| {
+                Stitch::new(
+                    coordinates[i * 2] as f64,
+                    coordinates[i * 2 + 1] as f64,
+                    StitchCommand::from_code(code).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_packed_round_trip() {
+        use StitchCommand::*;
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(0.0, 0.0, Stitch);
+        pattern.add_stitch(-120.0, 35.0, Move);
+        pattern.add_stitch(-120.0, 35.0, Trim);
+        pattern.add_stitch(-120.0, 35.0, ColorChange);
+        pattern.add_stitch(4000.0, -2500.0, SequinEject);
+        pattern.add_stitch(4000.0, -2500.0, End);
+
+        let json = serde_json::to_value(pattern.to_packed()).unwrap();
+        assert_eq!(json["count"], 6);
+        assert_eq!(unpack(&json), pattern.stitches);
+        assert!(json.get("flags").is_none());
+    }
+
+    #[test]
+    fn test_packed_underlay_flags() {
+        let mut pattern = Pattern::new();
+        for i in 0..6 {
+            pattern.add_stitch(i as f64 * 20.0, 0.0, StitchCommand::Stitch);
+        }
+        let mut packed = pattern.to_packed();
+        packed.flag_underlay(&[UnderlayRun {
+            block: 0,
+            start: 1,
+            end: 3,
+            confidence: 0.9,
+        }]);
+
+        let json = serde_json::to_value(&packed).unwrap();
+        let flags = STANDARD.decode(json["flags"].as_str().unwrap()).unwrap();
+        assert_eq!(flags, vec![0, FLAG_UNDERLAY, FLAG_UNDERLAY, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_packed_precision_at_physical_limit() {
+        // Whole units are exact in f32 across the full coordinate range
+        let mut pattern = Pattern::new();
+        for x in [
+            -MAX_COORDINATE,
+            -12345.0,
+            0.0,
+            1.0,
+            49_999.0,
+            MAX_COORDINATE,
+        ] {
+            pattern.add_stitch(x, -x, StitchCommand::Stitch);
+        }
+        let packed = pattern.to_packed();
+        for (stitch, pair) in pattern.stitches.iter().zip(packed.coordinates.chunks(2)) {
+            assert_eq!((pair[0] as f64, pair[1] as f64), (stitch.x, stitch.y));
+        }
+
+        // Fractions left by transforms stay within 0.01 unit and round to the same unit
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(12345.678, -49_999.37, StitchCommand::Stitch);
+        let packed = pattern.to_packed();
+        let stitch = &pattern.stitches[0];
+        let unpacked = Stitch::new(
+            packed.coordinates[0] as f64,
+            packed.coordinates[1] as f64,
+            stitch.command,
+        );
+        assert!((unpacked.x - stitch.x).abs() < 0.01);
+        assert!((unpacked.y - stitch.y).abs() < 0.01);
+        assert_eq!(unpacked.units(), stitch.units());
+    }
+}
@@ -0,0 +1,5 @@
+// mod.rs - PCS module exports for the Pfaff format parser
+
+mod parser;
+
+pub use parser::parse_pcs;
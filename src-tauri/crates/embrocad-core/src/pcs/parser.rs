@@ -0,0 +1,194 @@
+// parser.rs - Pfaff PCS embroidery file format parser
+
+use crate::binary::ByteReader;
+use crate::dst::{Pattern, StitchCommand, ThreadColor};
+
+/// The color table always has 16 slots of (r, g, b, 0)
+const PALETTE_SIZE: usize = 16;
+
+/// PCS coordinates are in 1/6 mm; this converts them to 0.1mm
+const UNIT_RATIO: f64 = 10.0 / 6.0;
+
+/// Record flag bits
+const FLAG_COLOR_CHANGE: u8 = 0x01;
+const FLAG_JUMP: u8 = 0x04;
+
+/// Error type for PCS parsing
+#[derive(Debug, thiserror::Error)]
+pub enum PcsError {
+    #[error("Invalid PCS file: insufficient data")]
+    InsufficientData,
+}
+
+/// Human-readable hoop name for the header's hoop byte
+fn hoop_name(hoop: u8) -> Option<&'static str> {
+    match hoop {
+        0 => Some("PCD"),
+        1 => Some("PCQ (Maxi)"),
+        2 => Some("PCS small hoop (80x80mm)"),
+        3 => Some("PCS large hoop (115x120mm)"),
+        _ => None,
+    }
+}
+
+/// Decode the 9-byte absolute stitch records
+///
+/// Each record is an unused byte, a 24-bit little-endian X, an unused byte,
+/// a 24-bit little-endian Y and a flag byte. Y is inverted to match DST.
+fn parse_stitches(reader: &mut ByteReader, stitch_count: u16, pattern: &mut Pattern) {
+    let mut current_x = 0.0f64;
+    let mut current_y = 0.0f64;
+
+    for _ in 0..stitch_count {
+        let (Some(_), Some(x), Some(_), Some(y), Some(flags)) = (
+            reader.u8(),
+            reader.i24_le(),
+            reader.u8(),
+            reader.i24_le(),
+            reader.u8(),
+        ) else {
+            break;
+        };
+
+        if flags & FLAG_COLOR_CHANGE != 0 {
+            pattern.add_stitch(current_x, current_y, StitchCommand::ColorChange);
+            continue;
+        }
+
+        current_x = x as f64 * UNIT_RATIO;
+        current_y = -(y as f64) * UNIT_RATIO;
+        let command = if flags & FLAG_JUMP != 0 {
+            StitchCommand::Move
+        } else {
+            StitchCommand::Stitch
+        };
+        pattern.add_stitch(current_x, current_y, command);
+    }
+
+    pattern.add_stitch(current_x, current_y, StitchCommand::End);
+}
+
+/// Parse a PCS file from bytes
+///
+/// Header: version byte, hoop byte, 16-bit color count, the 16-slot color
+/// table and a 16-bit stitch count, followed by the stitch records.
+pub fn parse_pcs(data: &[u8]) -> Result<Pattern, PcsError> {
+    let mut pattern = Pattern::new();
+    let mut reader = ByteReader::new(data);
+
+    let _version = reader.u8().ok_or(PcsError::InsufficientData)?;
+    let hoop = reader.u8().ok_or(PcsError::InsufficientData)?;
+    let color_count = reader.u16_le().ok_or(PcsError::InsufficientData)? as usize;
+
+    let palette = reader
+        .bytes(PALETTE_SIZE * 4)
+        .ok_or(PcsError::InsufficientData)?;
+    pattern.metadata.thread_colors = palette
+        .chunks_exact(4)
+        .take(color_count)
+        .map(|c| ThreadColor::new([c[0], c[1], c[2]]))
+        .collect();
+
+    let stitch_count = reader.u16_le().ok_or(PcsError::InsufficientData)?;
+
+    if let Some(name) = hoop_name(hoop) {
+        pattern.metadata.notes.push(format!("Hoop: {}", name));
+    }
+
+    parse_stitches(&mut reader, stitch_count, &mut pattern);
+
+    pattern.metadata.stitch_count = Some(pattern.stitches.len() as u32);
+    pattern.metadata.color_count = Some(pattern.color_changes);
+
+    // Calculate bounds, statistics and color blocks
+    pattern.calculate_bounds();
+    pattern.calculate_statistics();
+    pattern.calculate_color_blocks();
+
+    Ok(pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(x: i32, y: i32, flags: u8) -> Vec<u8> {
+        let mut out = vec![0];
+        out.extend_from_slice(&x.to_le_bytes()[..3]);
+        out.push(0);
+        out.extend_from_slice(&y.to_le_bytes()[..3]);
+        out.push(flags);
+        out
+    }
+
+    fn build_pcs(colors: &[[u8; 3]], records: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = vec![0x32, 0x03];
+        data.extend_from_slice(&(colors.len() as u16).to_le_bytes());
+        for slot in 0..PALETTE_SIZE {
+            let rgb = colors.get(slot).copied().unwrap_or_default();
+            data.extend_from_slice(&[rgb[0], rgb[1], rgb[2], 0]);
+        }
+        data.extend_from_slice(&(records.len() as u16).to_le_bytes());
+        for record in records {
+            data.extend_from_slice(record);
+        }
+        data
+    }
+
+    #[test]
+    fn test_color_change_increments_count() {
+        let data = build_pcs(
+            &[[255, 0, 0], [0, 0, 255]],
+            &[
+                record(0, 0, FLAG_JUMP),
+                record(6, 6, 0),
+                record(0, 0, FLAG_COLOR_CHANGE),
+                record(-12, 12, 0),
+            ],
+        );
+        let pattern = parse_pcs(&data).unwrap();
+
+        assert_eq!(pattern.color_changes, 1);
+        assert_eq!(pattern.metadata.color_count, Some(1));
+        assert_eq!(pattern.color_blocks.len(), 2);
+        assert_eq!(
+            pattern.color_blocks[1].color.as_ref().unwrap().rgb,
+            [0, 0, 255]
+        );
+        assert_eq!(
+            pattern.metadata.notes,
+            vec!["Hoop: PCS large hoop (115x120mm)"]
+        );
+    }
+
+    #[test]
+    fn test_absolute_coordinates_are_normalized() {
+        let data = build_pcs(
+            &[[0, 0, 0]],
+            &[record(6, 12, 0), record(-60, -6, FLAG_JUMP)],
+        );
+        let pattern = parse_pcs(&data).unwrap();
+
+        let points: Vec<_> = pattern
+            .stitches
+            .iter()
+            .map(|s| (s.x.round(), s.y.round(), s.command))
+            .collect();
+        assert_eq!(
+            points,
+            vec![
+                (10.0, -20.0, StitchCommand::Stitch),
+                (-100.0, 10.0, StitchCommand::Move),
+                (-100.0, 10.0, StitchCommand::End),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_insufficient_data() {
+        assert!(matches!(
+            parse_pcs(&[0x32, 0x03, 1, 0]),
+            Err(PcsError::InsufficientData)
+        ));
+    }
+}
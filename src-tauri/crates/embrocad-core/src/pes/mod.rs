@@ -0,0 +1,8 @@
+// mod.rs - PES/PEC module exports for the Brother format parser, writer and thread palette
+
+mod palette;
+mod parser;
+mod writer;
+
+pub use parser::parse_pes;
+pub use writer::write_pes;
@@ -0,0 +1,354 @@
+// parser.rs - PES/PEC embroidery file format parser with PEC stitch decoding
+
+use crate::dst::{Pattern, StitchCommand};
+use crate::pes::palette::pec_thread;
+
+/// Offset of the PEC block inside a standalone "#PEC0001" file
+const PEC_FILE_BLOCK_OFFSET: usize = 8;
+
+/// Offset of the color change count inside the PEC block
+pub const PEC_COLOR_COUNT_OFFSET: usize = 48;
+
+/// Offset of the stitch data inside the PEC block, after the 20-byte stitch block header
+pub const PEC_STITCH_OFFSET: usize = 532;
+
+/// Error type for PES/PEC parsing
+#[derive(Debug, thiserror::Error)]
+pub enum PesError {
+    #[error("Invalid PES file: insufficient data")]
+    InsufficientData,
+    #[error("Invalid PES file: missing #PES or #PEC signature")]
+    InvalidSignature,
+    #[error("Invalid PES file: PEC block offset {0} is out of range")]
+    InvalidPecOffset(usize),
+}
+
+/// Sign-extend a 7-bit short-form displacement
+fn signed7(value: u8) -> i32 {
+    if value > 63 {
+        value as i32 - 128
+    } else {
+        value as i32
+    }
+}
+
+/// Sign-extend a 12-bit long-form displacement
+fn signed12(value: u16) -> i32 {
+    let value = (value & 0x0FFF) as i32;
+    if value > 0x7FF {
+        value - 0x1000
+    } else {
+        value
+    }
+}
+
+/// Parse the PEC header: label and thread color indices
+fn parse_pec_header(pec: &[u8], pattern: &mut Pattern) -> Result<(), PesError> {
+    if pec.len() < PEC_STITCH_OFFSET {
+        return Err(PesError::InsufficientData);
+    }
+
+    // Label is at bytes 3-18 (LA: prefix at 0-2)
+    let label = String::from_utf8_lossy(&pec[3..19]);
+    let label = label.trim_end_matches(char::from(0)).trim();
+    if !label.is_empty() {
+        pattern.metadata.label = Some(label.to_string());
+    }
+
+    let color_changes = pec[PEC_COLOR_COUNT_OFFSET] as usize;
+    let first_color = PEC_COLOR_COUNT_OFFSET + 1;
+    let color_bytes = pec
+        .get(first_color..first_color + color_changes + 1)
+        .ok_or(PesError::InsufficientData)?;
+
+    pattern.metadata.color_count = Some(color_changes as u32);
+    pattern.metadata.thread_colors = color_bytes.iter().map(|&i| pec_thread(i)).collect();
+
+    Ok(())
+}
+
+/// Decode PEC stitch records into absolute stitches
+fn parse_pec_stitches(data: &[u8], pattern: &mut Pattern) {
+    let mut pos = 0;
+    let mut next = || {
+        let byte = data.get(pos).copied();
+        pos += 1;
+        byte
+    };
+
+    let mut current_x = 0.0f64;
+    let mut current_y = 0.0f64;
+
+    while let (Some(val1), Some(mut val2)) = (next(), next()) {
+        // End of stitch data
+        if val1 == 0xFF && val2 == 0x00 {
+            break;
+        }
+
+        // Color change, followed by one padding byte
+        if val1 == 0xFE && val2 == 0xB0 {
+            next();
+            pattern.add_stitch(current_x, current_y, StitchCommand::ColorChange);
+            continue;
+        }
+
+        let mut jump = false;
+        let mut trim = false;
+
+        // High bit selects the 12-bit long form, which also carries jump/trim flags
+        let dx = if val1 & 0x80 != 0 {
+            trim |= val1 & 0x20 != 0;
+            jump |= val1 & 0x10 != 0;
+            let dx = signed12(((val1 as u16) << 8) | val2 as u16);
+            let Some(byte) = next() else { break };
+            val2 = byte;
+            dx
+        } else {
+            signed7(val1)
+        };
+
+        let dy = if val2 & 0x80 != 0 {
+            trim |= val2 & 0x20 != 0;
+            jump |= val2 & 0x10 != 0;
+            let Some(val3) = next() else { break };
+            signed12(((val2 as u16) << 8) | val3 as u16)
+        } else {
+            signed7(val2)
+        };
+
+        current_x += dx as f64;
+        current_y += dy as f64;
+
+        // A trim flag wins over a jump flag; both mean cut, then move
+        if trim {
+            pattern.add_stitch(
+                current_x - dx as f64,
+                current_y - dy as f64,
+                StitchCommand::Trim,
+            );
+            pattern.add_stitch(current_x, current_y, StitchCommand::Move);
+        } else if jump {
+            pattern.add_stitch(current_x, current_y, StitchCommand::Move);
+        } else {
+            pattern.add_stitch(current_x, current_y, StitchCommand::Stitch);
+        }
+    }
+
+    pattern.add_stitch(current_x, current_y, StitchCommand::End);
+}
+
+/// Parse a PEC block (shared by PES files and standalone PEC files)
+fn parse_pec(pec: &[u8]) -> Result<Pattern, PesError> {
+    let mut pattern = Pattern::new();
+
+    parse_pec_header(pec, &mut pattern)?;
+    parse_pec_stitches(&pec[PEC_STITCH_OFFSET..], &mut pattern);
+
+    // Calculate bounds, statistics and color blocks
+    pattern.calculate_bounds();
+    pattern.calculate_statistics();
+    pattern.calculate_color_blocks();
+
+    Ok(pattern)
+}
+
+/// Parse a PES (or standalone PEC) file from bytes
+///
+/// Every PES version embeds a PEC block whose offset is stored at byte 8, so
+/// stitches and palette colors are read from there regardless of version.
+pub fn parse_pes(data: &[u8]) -> Result<Pattern, PesError> {
+    if data.len() < 12 {
+        return Err(PesError::InsufficientData);
+    }
+
+    let pec_offset = if data.starts_with(b"#PES") {
+        u32::from_le_bytes([data[8], data[9], data[10], data[11]]) as usize
+    } else if data.starts_with(b"#PEC") {
+        PEC_FILE_BLOCK_OFFSET
+    } else {
+        return Err(PesError::InvalidSignature);
+    };
+
+    if pec_offset >= data.len() {
+        return Err(PesError::InvalidPecOffset(pec_offset));
+    }
+
+    parse_pec(&data[pec_offset..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encode a displacement in PEC short form when possible, long form otherwise
+    fn encode_delta(value: i32, flags: u8, out: &mut Vec<u8>) {
+        if flags == 0 && (-64..=63).contains(&value) {
+            out.push((value & 0x7F) as u8);
+        } else {
+            let code = (value & 0x0FFF) as u16 | 0x8000 | ((flags as u16) << 8);
+            out.extend_from_slice(&code.to_be_bytes());
+        }
+    }
+
+    /// Build a PEC block with the given palette indices and encoded stitch bytes
+    fn build_pec(label: &str, colors: &[u8], stitches: &[u8]) -> Vec<u8> {
+        let mut pec = format!("LA:{:<16}\r", label).into_bytes();
+        pec.resize(PEC_COLOR_COUNT_OFFSET, b' ');
+        pec.push((colors.len() - 1) as u8);
+        pec.extend_from_slice(colors);
+        pec.resize(512, b' ');
+
+        // Stitch block header: length placeholder, marker, extents and start offsets
+        pec.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x31, 0xFF, 0xF0]);
+        pec.extend_from_slice(&[0; 8]);
+        pec.resize(PEC_STITCH_OFFSET, 0);
+        pec.extend_from_slice(stitches);
+        pec
+    }
+
+    fn build_pes(version: &[u8; 4], header_padding: usize, pec: &[u8]) -> Vec<u8> {
+        let mut data = b"#PES".to_vec();
+        data.extend_from_slice(version);
+        let pec_offset = (12 + header_padding) as u32;
+        data.extend_from_slice(&pec_offset.to_le_bytes());
+        data.resize(pec_offset as usize, 0xAA);
+        data.extend_from_slice(pec);
+        data
+    }
+
+    fn sample_stitches() -> Vec<u8> {
+        let mut s = Vec::new();
+        // Short stitch (+10, -5)
+        encode_delta(10, 0, &mut s);
+        encode_delta(-5, 0, &mut s);
+        // Long-form jump (+300, +20)
+        encode_delta(300, 0x10, &mut s);
+        encode_delta(20, 0x10, &mut s);
+        // Color change
+        s.extend_from_slice(&[0xFE, 0xB0, 0x02]);
+        // Trim then move (-100, 0)
+        encode_delta(-100, 0x20, &mut s);
+        encode_delta(0, 0x20, &mut s);
+        // Short stitch (+1, +1)
+        encode_delta(1, 0, &mut s);
+        encode_delta(1, 0, &mut s);
+        s.extend_from_slice(&[0xFF, 0x00]);
+        s
+    }
+
+    fn assert_sample(pattern: &Pattern) {
+        use StitchCommand::*;
+        let decoded: Vec<_> = pattern
+            .stitches
+            .iter()
+            .map(|s| (s.x, s.y, s.command))
+            .collect();
+
+        assert_eq!(
+            decoded,
+            vec![
+                (10.0, -5.0, Stitch),
+                (310.0, 15.0, Move),
+                (310.0, 15.0, ColorChange),
+                (310.0, 15.0, Trim),
+                (210.0, 15.0, Move),
+                (211.0, 16.0, Stitch),
+                (211.0, 16.0, End),
+            ]
+        );
+        assert_eq!(pattern.color_changes, 1);
+        assert_eq!(pattern.statistics.trim_count, 1);
+        assert_eq!(pattern.metadata.label.as_deref(), Some("PESTEST"));
+
+        let colors = &pattern.metadata.thread_colors;
+        assert_eq!(colors.len(), 2);
+        assert_eq!(colors[0].rgb, [237, 23, 31]);
+        assert_eq!(colors[0].description.as_deref(), Some("Red"));
+        assert_eq!(colors[1].description.as_deref(), Some("Black"));
+        assert_eq!(
+            pattern.color_blocks[1].color.as_ref().unwrap().rgb,
+            [0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_parse_pes_v1() {
+        let pec = build_pec("PESTEST", &[5, 20], &sample_stitches());
+        let pattern = parse_pes(&build_pes(b"0001", 8, &pec)).unwrap();
+        assert_sample(&pattern);
+    }
+
+    #[test]
+    fn test_parse_pes_v6() {
+        // Version 6 carries a much larger header before the PEC block
+        let pec = build_pec("PESTEST", &[5, 20], &sample_stitches());
+        let pattern = parse_pes(&build_pes(b"0060", 600, &pec)).unwrap();
+        assert_sample(&pattern);
+    }
+
+    #[test]
+    fn test_parse_standalone_pec() {
+        let mut data = b"#PEC0001".to_vec();
+        data.extend_from_slice(&build_pec("PESTEST", &[5, 20], &sample_stitches()));
+        assert_sample(&parse_pes(&data).unwrap());
+    }
+
+    #[test]
+    fn test_trim_and_jump_flags_together() {
+        use StitchCommand::*;
+        let mut s = Vec::new();
+        encode_delta(10, 0, &mut s);
+        encode_delta(0, 0, &mut s);
+        // One record with both jump and trim flags on x and only jump on y
+        encode_delta(200, 0x30, &mut s);
+        encode_delta(-40, 0x10, &mut s);
+        s.extend_from_slice(&[0xFF, 0x00]);
+
+        let mut data = b"#PEC0001".to_vec();
+        data.extend_from_slice(&build_pec("BOTH", &[5], &s));
+        let pattern = parse_pes(&data).unwrap();
+        let decoded: Vec<_> = pattern
+            .stitches
+            .iter()
+            .map(|s| (s.x, s.y, s.command))
+            .collect();
+
+        assert_eq!(
+            decoded,
+            vec![
+                (10.0, 0.0, Stitch),
+                (10.0, 0.0, Trim),
+                (210.0, -40.0, Move),
+                (210.0, -40.0, End),
+            ]
+        );
+        assert_eq!(pattern.statistics.trim_count, 1);
+    }
+
+    #[test]
+    fn test_signed_deltas() {
+        assert_eq!(signed7(0x7F), -1);
+        assert_eq!(signed7(0x3F), 63);
+        assert_eq!(signed12(0x8FFF), -1);
+        assert_eq!(signed12(0x87FF), 2047);
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert!(matches!(
+            parse_pes(b"#PES"),
+            Err(PesError::InsufficientData)
+        ));
+        assert!(matches!(
+            parse_pes(b"not a pes file"),
+            Err(PesError::InvalidSignature)
+        ));
+
+        let mut data = b"#PES0001".to_vec();
+        data.extend_from_slice(&9999u32.to_le_bytes());
+        assert!(matches!(
+            parse_pes(&data),
+            Err(PesError::InvalidPecOffset(9999))
+        ));
+    }
+}
@@ -0,0 +1,300 @@
+// writer.rs - PES v1 writer wrapping a PEC block with stitches and thumbnails
+
+use crate::dst::{Bounds, Pattern, StitchCommand, UNITS_PER_MM};
+use crate::export::{block_rgb, nearest_palette_index};
+use crate::pes::palette::PEC_PALETTE;
+use crate::pes::parser::{PEC_COLOR_COUNT_OFFSET, PEC_STITCH_OFFSET};
+
+/// PEC block offset of the truncated v1 header: signature, offset and 10 zero bytes
+const PES_V1_PEC_OFFSET: u32 = 22;
+
+/// Largest displacement of a long-form PEC record on either axis
+const MAX_PEC_DISPLACEMENT: i32 = 2047;
+
+/// Long-form flag bits, in the high byte of each 16-bit coordinate
+const FLAG_TRIM: u8 = 0x20;
+const FLAG_JUMP: u8 = 0x10;
+
+/// PEC thumbnails are 48x38 one-bit bitmaps, eight pixels per byte
+const THUMBNAIL_WIDTH: usize = 48;
+const THUMBNAIL_HEIGHT: usize = 38;
+const THUMBNAIL_BYTES: usize = THUMBNAIL_WIDTH / 8 * THUMBNAIL_HEIGHT;
+
+/// Index of the PEC palette entry closest to `rgb`, skipping the reserved entry 0
+fn nearest_pec_color(rgb: [u8; 3]) -> u8 {
+    let index = nearest_palette_index(rgb, PEC_PALETTE[1..].iter().map(|entry| entry.0));
+    index as u8 + 1
+}
+
+/// Append one coordinate, in the 7-bit short form when it fits and has no flags
+fn encode_delta(value: i32, flags: u8, out: &mut Vec<u8>) {
+    if flags == 0 && (-64..=63).contains(&value) {
+        out.push((value & 0x7F) as u8);
+    } else {
+        let code = (value & 0x0FFF) as u16 | 0x8000 | ((flags as u16) << 8);
+        out.extend_from_slice(&code.to_be_bytes());
+    }
+}
+
+/// Encode the stitch stream from the origin, ending with the 0xFF 0x00 marker
+///
+/// A Trim is carried by the trim flag of the move that follows it, matching how
+/// the parser expands a trim-flagged record into Trim plus Move.
+fn encode_stitches(pattern: &Pattern) -> Vec<u8> {
+    let mut out = Vec::with_capacity(pattern.stitches.len() * 2 + 2);
+    let (mut current_x, mut current_y) = (0i32, 0i32);
+    let mut pending_trim = false;
+    let mut color_changes = 0;
+
+    for stitch in &pattern.stitches {
+        let (x, y) = stitch.units();
+        let (dx, dy) = (x - current_x, y - current_y);
+        let moved = dx != 0 || dy != 0;
+
+        match stitch.command {
+            StitchCommand::Stitch => {
+                if pending_trim {
+                    encode_delta(0, FLAG_TRIM, &mut out);
+                    encode_delta(0, FLAG_TRIM, &mut out);
+                    pending_trim = false;
+                }
+                encode_delta(dx, 0, &mut out);
+                encode_delta(dy, 0, &mut out);
+            }
+            StitchCommand::Move => {
+                let flags = if pending_trim { FLAG_TRIM } else { FLAG_JUMP };
+                encode_delta(dx, flags, &mut out);
+                encode_delta(dy, flags, &mut out);
+                pending_trim = false;
+            }
+            command => {
+                // Other commands happen in place, so travel there first
+                if moved {
+                    encode_delta(dx, FLAG_JUMP, &mut out);
+                    encode_delta(dy, FLAG_JUMP, &mut out);
+                }
+                match command {
+                    StitchCommand::Trim => pending_trim = true,
+                    StitchCommand::ColorChange => {
+                        // The byte after the marker alternates between 2 and 1
+                        color_changes += 1;
+                        let toggle = if color_changes % 2 == 1 { 2 } else { 1 };
+                        out.extend_from_slice(&[0xFE, 0xB0, toggle]);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        (current_x, current_y) = (x, y);
+    }
+
+    out.extend_from_slice(&[0xFF, 0x00]);
+    out
+}
+
+/// Rasterize the records in `range` into a 48x38 thumbnail with a frame border
+///
+/// Pixels are packed least significant bit first, as Brother machines read them.
+fn thumbnail(pattern: &Pattern, bounds: &Bounds, range: std::ops::Range<usize>) -> Vec<u8> {
+    let mut bitmap = vec![0u8; THUMBNAIL_BYTES];
+    let mut set = |x: usize, y: usize| {
+        if x < THUMBNAIL_WIDTH && y < THUMBNAIL_HEIGHT {
+            bitmap[y * THUMBNAIL_WIDTH / 8 + x / 8] |= 1 << (x % 8);
+        }
+    };
+
+    for x in 2..THUMBNAIL_WIDTH - 2 {
+        set(x, 0);
+        set(x, THUMBNAIL_HEIGHT - 1);
+    }
+    for y in 2..THUMBNAIL_HEIGHT - 2 {
+        set(0, y);
+        set(THUMBNAIL_WIDTH - 1, y);
+    }
+
+    // Fit the design inside the frame, keeping its aspect ratio
+    let (left, top) = (3.0, 3.0);
+    let (inner_width, inner_height) = ((THUMBNAIL_WIDTH - 7) as f64, (THUMBNAIL_HEIGHT - 7) as f64);
+    let scale =
+        (inner_width / bounds.width().max(1.0)).min(inner_height / bounds.height().max(1.0));
+    let offset_x = left + (inner_width - bounds.width() * scale) / 2.0;
+    let offset_y = top + (inner_height - bounds.height() * scale) / 2.0;
+    let to_pixel = |x: f64, y: f64| {
+        (
+            offset_x + (x - bounds.min_x) * scale,
+            offset_y + (y - bounds.min_y) * scale,
+        )
+    };
+
+    for index in range.start.max(1)..range.end {
+        let (prev, stitch) = (&pattern.stitches[index - 1], &pattern.stitches[index]);
+        if stitch.command != StitchCommand::Stitch {
+            continue;
+        }
+        let (x0, y0) = to_pixel(prev.x, prev.y);
+        let (x1, y1) = to_pixel(stitch.x, stitch.y);
+        let steps = (x1 - x0).abs().max((y1 - y0).abs()).ceil().max(1.0) as usize;
+        for step in 0..=steps {
+            let t = step as f64 / steps as f64;
+            let x = x0 + (x1 - x0) * t;
+            let y = y0 + (y1 - y0) * t;
+            set(x.round() as usize, y.round() as usize);
+        }
+    }
+    bitmap
+}
+
+/// Build the PEC block: header with label and colors, stitches, then thumbnails
+fn write_pec(pattern: &Pattern) -> Vec<u8> {
+    let mut bounds = Bounds::new();
+    for stitch in &pattern.stitches {
+        bounds.update(stitch.x, stitch.y);
+    }
+    if pattern.stitches.is_empty() {
+        bounds.update(0.0, 0.0);
+    }
+    let blocks = pattern.color_blocks();
+
+    let label: String = pattern
+        .metadata
+        .label
+        .as_deref()
+        .unwrap_or("Untitled")
+        .chars()
+        .filter(|c| c.is_ascii() && !c.is_ascii_control())
+        .take(16)
+        .collect();
+    let mut pec = format!("LA:{:<16}\r", label).into_bytes();
+    pec.resize(32, b' ');
+    pec.extend_from_slice(&[0xFF, 0x00, (THUMBNAIL_WIDTH / 8) as u8]);
+    pec.push(THUMBNAIL_HEIGHT as u8);
+    pec.resize(PEC_COLOR_COUNT_OFFSET, b' ');
+
+    pec.push(blocks.len().saturating_sub(1) as u8);
+    for block in &blocks {
+        pec.push(nearest_pec_color(block_rgb(pattern, block.index)));
+    }
+    pec.resize(512, b' ');
+
+    let stitches = encode_stitches(pattern);
+    // The graphics offset counts from the start of the stitch block header
+    let graphics_offset = (PEC_STITCH_OFFSET - 512 + stitches.len()) as u32;
+    pec.extend_from_slice(&[0x00, 0x00]);
+    pec.extend_from_slice(&graphics_offset.to_le_bytes()[..3]);
+    pec.extend_from_slice(&[0x31, 0xFF, 0xF0]);
+    pec.extend_from_slice(&(bounds.width().round() as i16).to_le_bytes());
+    pec.extend_from_slice(&(bounds.height().round() as i16).to_le_bytes());
+    pec.extend_from_slice(&0x01E0u16.to_le_bytes());
+    pec.extend_from_slice(&0x01B0u16.to_le_bytes());
+    for min in [bounds.min_x, bounds.min_y] {
+        let start = 0x9000 | ((-min.round() as i32) & 0x0FFF) as u16;
+        pec.extend_from_slice(&start.to_be_bytes());
+    }
+    debug_assert_eq!(pec.len(), PEC_STITCH_OFFSET);
+    pec.extend_from_slice(&stitches);
+
+    // One thumbnail of the whole design, then one per color block
+    pec.extend(thumbnail(pattern, &bounds, 0..pattern.stitches.len()));
+    for block in &blocks {
+        pec.extend(thumbnail(pattern, &bounds, block.start..block.end));
+    }
+    pec
+}
+
+/// Encode a pattern as a PES v1 file
+///
+/// This is the truncated v1 layout: a 22-byte PES header pointing straight at
+/// the PEC block, which holds everything Brother home machines read. Colors
+/// map to the nearest Brother palette entries, and stitches longer than the
+/// 12-bit long form are split first.
+pub fn write_pes(pattern: &Pattern) -> Vec<u8> {
+    let mut pattern = pattern.clone();
+    pattern.split_long_stitches(MAX_PEC_DISPLACEMENT as f64 / UNITS_PER_MM);
+
+    let mut data = b"#PES0001".to_vec();
+    data.extend_from_slice(&PES_V1_PEC_OFFSET.to_le_bytes());
+    data.resize(PES_V1_PEC_OFFSET as usize, 0);
+    data.extend(write_pec(&pattern));
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::ThreadColor;
+    use crate::pes::parse_pes;
+    use StitchCommand::*;
+
+    fn points(pattern: &Pattern) -> Vec<(f64, f64, StitchCommand)> {
+        pattern
+            .stitches
+            .iter()
+            .map(|s| (s.x, s.y, s.command))
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip_through_parser() {
+        let mut pattern = Pattern::new();
+        let records = [
+            (0.0, 0.0, Stitch),
+            (40.0, -20.0, Stitch),
+            (140.0, -20.0, Stitch),
+            (140.0, -20.0, Trim),
+            (-300.0, 80.0, Move),
+            (-300.0, 80.0, ColorChange),
+            (-290.0, 81.0, Stitch),
+            (-290.0, 81.0, End),
+        ];
+        for &(x, y, command) in &records {
+            pattern.add_stitch(x, y, command);
+        }
+        pattern.metadata.label = Some("ROUNDTRIP".to_string());
+        pattern.metadata.thread_colors =
+            vec![ThreadColor::new([236, 24, 30]), ThreadColor::new([2, 2, 2])];
+
+        let parsed = parse_pes(&write_pes(&pattern)).unwrap();
+
+        assert_eq!(points(&parsed), records.to_vec());
+        assert_eq!(parsed.metadata.label.as_deref(), Some("ROUNDTRIP"));
+        let colors: Vec<_> = parsed
+            .metadata
+            .thread_colors
+            .iter()
+            .map(|c| c.description.as_deref().unwrap())
+            .collect();
+        assert_eq!(colors, vec!["Red", "Black"]);
+    }
+
+    #[test]
+    fn test_long_stitches_are_split() {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(0.0, 0.0, Stitch);
+        pattern.add_stitch(3000.0, 0.0, Stitch);
+
+        let parsed = parse_pes(&write_pes(&pattern)).unwrap();
+        let last = &parsed.stitches[parsed.stitches.len() - 2];
+        assert_eq!((last.x, last.y, last.command), (3000.0, 0.0, Stitch));
+        assert!(parsed.stitches.len() > 3);
+    }
+
+    #[test]
+    fn test_thumbnails_follow_stitches() {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(0.0, 0.0, Stitch);
+        pattern.add_stitch(100.0, 100.0, Stitch);
+        pattern.add_stitch(100.0, 100.0, End);
+
+        let data = write_pes(&pattern);
+        let pec = &data[PES_V1_PEC_OFFSET as usize..];
+        let offset = u32::from_le_bytes([pec[514], pec[515], pec[516], 0]) as usize;
+
+        // Stitch data ends right where the graphics start
+        assert_eq!(&pec[512 + offset - 2..512 + offset], &[0xFF, 0x00]);
+        // One overall thumbnail plus one for the single color block
+        assert_eq!(pec.len(), 512 + offset + 2 * THUMBNAIL_BYTES);
+        // The diagonal crosses the middle of the overall thumbnail
+        let graphics = &pec[512 + offset..];
+        assert_ne!(graphics[19 * 6 + 3], 0);
+    }
+}
@@ -0,0 +1,90 @@
+// progress.rs - Load progress reporting and cooperative cancellation
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Records decoded between progress reports and cancellation checks
+pub const PROGRESS_INTERVAL: usize = 16_384;
+
+/// Flag shared between a running load and whoever may abort it
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// How far a load has got, sent to the frontend while parsing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct LoadProgress {
+    pub bytes_processed: usize,
+    pub total_bytes: usize,
+    pub stitches_decoded: usize,
+}
+
+/// Progress sink and cancellation flag threaded through a parser loop
+pub struct LoadMonitor<'a> {
+    cancel: Option<&'a CancelToken>,
+    report: Option<&'a mut dyn FnMut(LoadProgress)>,
+    interval: usize,
+}
+
+impl<'a> LoadMonitor<'a> {
+    pub fn new(
+        cancel: Option<&'a CancelToken>,
+        report: Option<&'a mut dyn FnMut(LoadProgress)>,
+    ) -> Self {
+        Self {
+            cancel,
+            report,
+            interval: PROGRESS_INTERVAL,
+        }
+    }
+
+    /// A monitor that never reports and is never cancelled
+    pub fn none() -> Self {
+        Self::new(None, None)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_some_and(CancelToken::is_cancelled)
+    }
+
+    /// Called before each record is decoded; returns false once cancelled
+    ///
+    /// Only every `PROGRESS_INTERVAL`th record reports and reads the flag, so
+    /// the per-record cost is a single remainder.
+    pub fn tick(&mut self, progress: LoadProgress) -> bool {
+        if !progress.stitches_decoded.is_multiple_of(self.interval) {
+            return true;
+        }
+        if let Some(report) = self.report.as_mut() {
+            report(progress);
+        }
+        !self.is_cancelled()
+    }
+
+    /// Report the final figures once parsing has finished
+    pub fn finish(&mut self, total_bytes: usize, stitches_decoded: usize) {
+        if let Some(report) = self.report.as_mut() {
+            report(LoadProgress {
+                bytes_processed: total_bytes,
+                total_bytes,
+                stitches_decoded,
+            });
+        }
+    }
+}
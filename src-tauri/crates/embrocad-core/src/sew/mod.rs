@@ -0,0 +1,5 @@
+// mod.rs - SEW module exports for the Janome/Elna format parser
+
+mod parser;
+
+pub use parser::parse_sew;
@@ -0,0 +1,113 @@
+// parser.rs - Janome/Elna SEW embroidery file format parser
+
+use crate::binary::ByteReader;
+use crate::dst::Pattern;
+use crate::jef::{decode_stitches, janome_thread};
+
+/// Stitch data always starts at this fixed offset, after the color list
+const STITCH_DATA_OFFSET: usize = 0x1D78;
+
+/// Error type for SEW parsing
+#[derive(Debug, thiserror::Error)]
+pub enum SewError {
+    #[error("Invalid SEW file: insufficient data")]
+    InsufficientData,
+}
+
+/// Parse a SEW file from bytes
+///
+/// The file starts with a 16-bit color count and that many 16-bit Janome
+/// palette indices; the JEF-style stitch stream begins at 0x1D78.
+pub fn parse_sew(data: &[u8]) -> Result<Pattern, SewError> {
+    if data.len() < STITCH_DATA_OFFSET {
+        return Err(SewError::InsufficientData);
+    }
+
+    let mut pattern = Pattern::new();
+    let mut reader = ByteReader::new(data);
+
+    let color_count = reader.u16_le().ok_or(SewError::InsufficientData)?;
+    for _ in 0..color_count {
+        let index = reader.u16_le().ok_or(SewError::InsufficientData)?;
+        pattern.metadata.thread_colors.push(janome_thread(index));
+    }
+
+    decode_stitches(&data[STITCH_DATA_OFFSET..], &mut pattern);
+
+    pattern.metadata.stitch_count = Some(pattern.stitches.len() as u32);
+    pattern.metadata.color_count = Some(pattern.color_changes);
+
+    // Calculate bounds, statistics and color blocks
+    pattern.calculate_bounds();
+    pattern.calculate_statistics();
+    pattern.calculate_color_blocks();
+
+    Ok(pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::StitchCommand;
+
+    #[test]
+    fn test_parse_synthetic_sew() {
+        let mut data = Vec::new();
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&10u16.to_le_bytes());
+        data.extend_from_slice(&12u16.to_le_bytes());
+        data.resize(STITCH_DATA_OFFSET, 0);
+        data.extend_from_slice(&[
+            0x0A, 0x00, // stitch
+            0x80, 0x02, 0x00, 0x00, // trim
+            0x80, 0x04, 0x14, 0x00, // move
+            0x80, 0x01, 0x00, 0x00, // color change
+            0x0A, 0x00, // stitch
+            0x80, 0x10, 0x00, 0x00, // end
+        ]);
+
+        let pattern = parse_sew(&data).unwrap();
+        let commands: Vec<_> = pattern.stitches.iter().map(|s| s.command).collect();
+
+        assert_eq!(
+            commands,
+            vec![
+                StitchCommand::Stitch,
+                StitchCommand::Trim,
+                StitchCommand::Move,
+                StitchCommand::ColorChange,
+                StitchCommand::Stitch,
+                StitchCommand::End,
+            ]
+        );
+        assert_eq!(pattern.statistics.real_stitch_count, 2);
+        assert_eq!(pattern.statistics.trim_count, 1);
+        assert_eq!(pattern.color_blocks.len(), 2);
+        assert_eq!(
+            pattern.color_blocks[0]
+                .color
+                .as_ref()
+                .unwrap()
+                .description
+                .as_deref(),
+            Some("Red")
+        );
+        assert_eq!(
+            pattern.color_blocks[1]
+                .color
+                .as_ref()
+                .unwrap()
+                .description
+                .as_deref(),
+            Some("Blue")
+        );
+    }
+
+    #[test]
+    fn test_insufficient_data() {
+        assert!(matches!(
+            parse_sew(&[0; 64]),
+            Err(SewError::InsufficientData)
+        ));
+    }
+}
@@ -0,0 +1,7 @@
+// mod.rs - SVG module exports for vector artwork import
+
+mod parser;
+mod path;
+mod style;
+
+pub use parser::{parse_svg, SvgImportOptions};
@@ -0,0 +1,434 @@
+// parser.rs - SVG import, stitching each shape in its stroke or fill color
+
+use super::path::{parse_path, parse_points, Lexer, Point, Subpath};
+use super::style::{parse_length_mm, parse_paint, parse_transform, Transform};
+use crate::dst::Pattern;
+use crate::generate::{
+    fill_polygon, join_blocks, running_polyline, ColorRuns, FillOptions, GenerateError,
+};
+use roxmltree::Node;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Size of a user unit in an SVG that gives no physical size: a CSS pixel
+const PX_MM: f64 = 25.4 / 96.0;
+
+/// Elements whose contents are only drawn when referenced from elsewhere
+const UNDRAWN: &[&str] = &[
+    "defs",
+    "clipPath",
+    "mask",
+    "marker",
+    "pattern",
+    "symbol",
+    "linearGradient",
+    "radialGradient",
+    "style",
+    "title",
+    "desc",
+    "metadata",
+];
+
+/// How vector artwork is turned into stitches
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct SvgImportOptions {
+    /// Furthest a flattened curve may stray from the drawn one
+    pub tolerance_mm: f64,
+    /// Longest running stitch along a path
+    pub stitch_len_mm: f64,
+    /// Sew closed shapes that have a fill color as a fill instead of
+    /// running around them
+    pub fill_shapes: bool,
+    /// Rows for filled shapes
+    pub fill: FillOptions,
+}
+
+impl Default for SvgImportOptions {
+    fn default() -> Self {
+        Self {
+            tolerance_mm: 0.1,
+            stitch_len_mm: 2.5,
+            fill_shapes: false,
+            fill: FillOptions::default(),
+        }
+    }
+}
+
+/// Error type for SVG import
+#[derive(Debug, thiserror::Error)]
+pub enum SvgError {
+    #[error("Invalid SVG file: not UTF-8 text")]
+    InvalidText,
+    #[error("Invalid SVG file: {0}")]
+    InvalidXml(String),
+    #[error("The file is XML but not an SVG image")]
+    NotSvg,
+    #[error("Curve tolerance must be positive")]
+    InvalidTolerance,
+    #[error("Invalid path data: {0}")]
+    InvalidPath(String),
+    #[error("The image has no stroked or filled shapes to stitch")]
+    NoShapes,
+    #[error(transparent)]
+    Generate(#[from] GenerateError),
+}
+
+/// Stroke and fill, inherited down the tree unless an element sets its own
+#[derive(Debug, Clone, Copy)]
+struct Paint {
+    stroke: Option<[u8; 3]>,
+    fill: Option<[u8; 3]>,
+}
+
+impl Default for Paint {
+    /// SVG shapes are filled black and unstroked unless told otherwise
+    fn default() -> Self {
+        Self {
+            stroke: None,
+            fill: Some([0, 0, 0]),
+        }
+    }
+}
+
+/// One drawn element, its subpaths already mapped to mm
+struct Shape {
+    subpaths: Vec<Subpath>,
+    paint: Paint,
+}
+
+/// A presentation property, from the `style` attribute first and then
+/// from the attribute of the same name
+fn property<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attribute("style")
+        .and_then(|style| {
+            style.split(';').find_map(|declaration| {
+                let (key, value) = declaration.split_once(':')?;
+                (key.trim() == name).then(|| value.trim())
+            })
+        })
+        .or_else(|| node.attribute(name))
+}
+
+/// A coordinate attribute in user units; any unit suffix is ignored
+fn coordinate(node: Node, name: &str) -> f64 {
+    node.attribute(name)
+        .and_then(|value| Lexer::new(value).number())
+        .unwrap_or(0.0)
+}
+
+/// Map the outermost `svg` element's user units to mm
+///
+/// A `viewBox` is scaled to the width and height, keeping its aspect ratio
+/// as the default `preserveAspectRatio` does; without one, user units are
+/// CSS pixels.
+fn root_transform(root: Node) -> Transform {
+    let view_box: Vec<f64> = root
+        .attribute("viewBox")
+        .map(parse_points)
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|(a, b)| [a, b])
+        .collect();
+    let [x, y, width, height] = view_box[..] else {
+        return Transform::scale(PX_MM, PX_MM);
+    };
+    if !(width > 0.0 && height > 0.0) {
+        return Transform::scale(PX_MM, PX_MM);
+    }
+    let size = |name: &str, extent: f64| {
+        root.attribute(name)
+            .and_then(|value| parse_length_mm(value, PX_MM))
+            .map(|mm| mm / extent)
+    };
+    let scale = match (size("width", width), size("height", height)) {
+        (Some(sx), Some(sy)) => sx.min(sy),
+        (Some(s), None) | (None, Some(s)) => s,
+        (None, None) => PX_MM,
+    };
+    Transform::scale(scale, scale).then(&Transform::translate(-x, -y))
+}
+
+/// The subpaths `node` draws, in its own user units
+fn element_subpaths(node: Node) -> Result<Vec<Subpath>, SvgError> {
+    let n = |name: &str| coordinate(node, name);
+    let subpaths = match node.tag_name().name() {
+        "path" => {
+            parse_path(node.attribute("d").unwrap_or_default()).map_err(SvgError::InvalidPath)?
+        }
+        "rect" => {
+            let (x, y, w, h) = (n("x"), n("y"), n("width"), n("height"));
+            if w > 0.0 && h > 0.0 {
+                Subpath::polygon(&[(x, y), (x + w, y), (x + w, y + h), (x, y + h)], true)
+                    .into_iter()
+                    .collect()
+            } else {
+                Vec::new()
+            }
+        }
+        "circle" if n("r") > 0.0 => vec![Subpath::ellipse(n("cx"), n("cy"), n("r"), n("r"))],
+        "ellipse" if n("rx") > 0.0 && n("ry") > 0.0 => {
+            vec![Subpath::ellipse(n("cx"), n("cy"), n("rx"), n("ry"))]
+        }
+        "line" => Subpath::polygon(&[(n("x1"), n("y1")), (n("x2"), n("y2"))], false)
+            .into_iter()
+            .collect(),
+        name @ ("polyline" | "polygon") => Subpath::polygon(
+            &parse_points(node.attribute("points").unwrap_or_default()),
+            name == "polygon",
+        )
+        .into_iter()
+        .collect(),
+        _ => Vec::new(),
+    };
+    Ok(subpaths)
+}
+
+/// Gather the shapes under `node`, honouring transforms and paint from the
+/// groups around them
+fn collect_shapes(
+    node: Node,
+    outer: &Transform,
+    inherited: Paint,
+    shapes: &mut Vec<Shape>,
+) -> Result<(), SvgError> {
+    if !node.is_element()
+        || UNDRAWN.contains(&node.tag_name().name())
+        || property(node, "display") == Some("none")
+    {
+        return Ok(());
+    }
+    let transform = match node.attribute("transform") {
+        Some(text) => outer.then(&parse_transform(text)),
+        None => *outer,
+    };
+    let paint = Paint {
+        stroke: property(node, "stroke")
+            .and_then(parse_paint)
+            .unwrap_or(inherited.stroke),
+        fill: property(node, "fill")
+            .and_then(parse_paint)
+            .unwrap_or(inherited.fill),
+    };
+
+    let subpaths = element_subpaths(node)?;
+    if subpaths.is_empty() {
+        for child in node.children() {
+            collect_shapes(child, &transform, paint, shapes)?;
+        }
+    } else {
+        shapes.push(Shape {
+            subpaths: subpaths
+                .iter()
+                .map(|subpath| subpath.map(|p| transform.apply(p)))
+                .collect(),
+            paint,
+        });
+    }
+    Ok(())
+}
+
+/// Twice the signed area enclosed by `ring`
+fn ring_area(ring: &[Point]) -> f64 {
+    ring.windows(2)
+        .map(|pair| pair[0].0 * pair[1].1 - pair[1].0 * pair[0].1)
+        .sum()
+}
+
+/// File a stitched fragment under its color
+fn add_piece(blocks: &mut Vec<ColorRuns>, rgb: [u8; 3], piece: Pattern) {
+    match blocks.iter_mut().find(|(color, _)| *color == rgb) {
+        Some((_, pieces)) => pieces.push(piece),
+        None => blocks.push((rgb, vec![piece])),
+    }
+}
+
+/// Parse an SVG image into a design
+///
+/// Paths, basic shapes, polylines and polygons are flattened to within
+/// `tolerance_mm` and sewn as running stitch in their stroke color, or
+/// their fill color when they have no stroke. With `fill_shapes`, closed
+/// shapes with a fill color are filled instead, the largest subpath being
+/// the outline and the rest holes, and any stroke is run around them
+/// afterwards. Every color becomes one block, in the order colors first
+/// appear, so shapes of the same color are sewn together whatever their
+/// stacking; shapes within a block are joined by trimmed jumps.
+pub fn parse_svg(data: &[u8], options: &SvgImportOptions) -> Result<Pattern, SvgError> {
+    if options.tolerance_mm.is_nan() || options.tolerance_mm <= 0.0 {
+        return Err(SvgError::InvalidTolerance);
+    }
+    let text = std::str::from_utf8(data).map_err(|_| SvgError::InvalidText)?;
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let document =
+        roxmltree::Document::parse(text).map_err(|e| SvgError::InvalidXml(e.to_string()))?;
+    let root = document.root_element();
+    if root.tag_name().name() != "svg" {
+        return Err(SvgError::NotSvg);
+    }
+    let mut shapes = Vec::new();
+    collect_shapes(root, &root_transform(root), Paint::default(), &mut shapes)?;
+
+    let mut blocks: Vec<ColorRuns> = Vec::new();
+    for shape in &shapes {
+        let lines: Vec<Vec<Point>> = shape
+            .subpaths
+            .iter()
+            .map(|subpath| subpath.flatten(options.tolerance_mm))
+            .collect();
+
+        let mut filled = false;
+        if let (Some(rgb), true) = (shape.paint.fill, options.fill_shapes) {
+            let mut rings: Vec<Vec<Point>> = shape
+                .subpaths
+                .iter()
+                .zip(&lines)
+                .filter(|(subpath, line)| subpath.closed && ring_area(line).abs() > 1e-9)
+                .map(|(_, line)| line.clone())
+                .collect();
+            let outline = (0..rings.len()).max_by(|&a, &b| {
+                ring_area(&rings[a])
+                    .abs()
+                    .total_cmp(&ring_area(&rings[b]).abs())
+            });
+            if let Some(outline) = outline {
+                let outline = rings.swap_remove(outline);
+                add_piece(
+                    &mut blocks,
+                    rgb,
+                    fill_polygon(&outline, &rings, &options.fill)?,
+                );
+                filled = true;
+            }
+        }
+
+        let outline_color = if filled { None } else { shape.paint.fill };
+        let Some(rgb) = shape.paint.stroke.or(outline_color) else {
+            continue;
+        };
+        for line in &lines {
+            match running_polyline(line, options.stitch_len_mm) {
+                Ok(piece) => add_piece(&mut blocks, rgb, piece),
+                // A subpath that never leaves its start draws nothing
+                Err(GenerateError::TooFewPoints) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+    let pattern = join_blocks(&blocks);
+    if pattern.metadata.thread_colors.is_empty() {
+        return Err(SvgError::NoShapes);
+    }
+    Ok(pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::StitchCommand;
+
+    /// Length sewn in each color block, counting only stitch-to-stitch moves
+    fn sewn_length_per_block(pattern: &Pattern) -> Vec<f64> {
+        pattern
+            .stitches
+            .split(|s| s.command == StitchCommand::ColorChange)
+            .map(|block| {
+                block
+                    .windows(2)
+                    .filter(|pair| pair.iter().all(|s| s.command == StitchCommand::Stitch))
+                    .map(|pair| (pair[1].x - pair[0].x).hypot(pair[1].y - pair[0].y))
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_two_colored_paths() {
+        // 200 x 100 user units drawn 100mm x 50mm: half a mm per unit
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="100mm" height="50mm" viewBox="0 0 200 100">
+            <g transform="translate(20 0)" stroke="#ff0000" fill="none">
+                <path d="M0 20 h60"/>
+                <line x1="0" y1="30" x2="0" y2="50"/>
+            </g>
+            <path d="M20 40 v40 h30" style="stroke: blue; fill: none"/>
+            <defs><path d="M0 0 h100" stroke="green"/></defs>
+        </svg>"##;
+        let pattern = parse_svg(svg.as_bytes(), &SvgImportOptions::default()).unwrap();
+
+        assert_eq!(pattern.color_blocks.len(), 2);
+        let colors: Vec<_> = pattern
+            .metadata
+            .thread_colors
+            .iter()
+            .map(|t| t.rgb)
+            .collect();
+        assert_eq!(colors, vec![[255, 0, 0], [0, 0, 255]]);
+
+        // Red: 30mm and 10mm moved 10mm right; blue: 20mm then 15mm
+        let lengths = sewn_length_per_block(&pattern);
+        assert!((lengths[0] - 400.0).abs() < 1e-9);
+        assert!((lengths[1] - 350.0).abs() < 1e-9);
+        let first = pattern
+            .stitches
+            .iter()
+            .find(|s| s.command == StitchCommand::Stitch)
+            .unwrap();
+        assert_eq!((first.x, first.y), (100.0, 100.0));
+        // Each piece is reached by a jump, trimmed after the first
+        let count = |command| {
+            pattern
+                .stitches
+                .iter()
+                .filter(|s| s.command == command)
+                .count()
+        };
+        assert_eq!(count(StitchCommand::Move), 3);
+        assert_eq!(count(StitchCommand::Trim), 2);
+    }
+
+    #[test]
+    fn test_filled_shapes_with_holes() {
+        // A 40mm square with a 20mm square hole, filled black by default
+        let svg = r#"<svg width="40mm" height="40mm" viewBox="0 0 40 40">
+            <path d="M0 0h40v40h-40z M10 10h20v20h-20z"/>
+        </svg>"#;
+        let outlined = parse_svg(svg.as_bytes(), &SvgImportOptions::default()).unwrap();
+        assert_eq!(outlined.color_blocks.len(), 1);
+        assert!((sewn_length_per_block(&outlined)[0] - 2400.0).abs() < 1e-9);
+
+        let options = SvgImportOptions {
+            fill_shapes: true,
+            ..SvgImportOptions::default()
+        };
+        let filled = parse_svg(svg.as_bytes(), &options).unwrap();
+        assert_eq!(filled.metadata.thread_colors[0].rgb, [0, 0, 0]);
+        let inside_hole = filled.stitches.iter().any(|s| {
+            s.command == StitchCommand::Stitch
+                && (100.5..299.5).contains(&s.x)
+                && (100.5..299.5).contains(&s.y)
+        });
+        assert!(!inside_hole);
+        assert!(sewn_length_per_block(&filled)[0] > 2400.0 * 5.0);
+    }
+
+    #[test]
+    fn test_rejects_other_documents() {
+        let options = SvgImportOptions::default();
+        assert!(matches!(
+            parse_svg(b"<html></html>", &options),
+            Err(SvgError::NotSvg)
+        ));
+        assert!(matches!(
+            parse_svg(b"<svg><path d='M0 0' stroke='red'/></svg>", &options),
+            Err(SvgError::NoShapes)
+        ));
+        assert!(matches!(
+            parse_svg(b"<svg><path d='M0 0 Q1'/></svg>", &options),
+            Err(SvgError::InvalidPath(_))
+        ));
+        assert!(matches!(
+            parse_svg(b"<svg", &options),
+            Err(SvgError::InvalidXml(_))
+        ));
+    }
+}
@@ -0,0 +1,518 @@
+// path.rs - SVG path data and basic shapes as lines and cubic Béziers
+
+use std::f64::consts::{FRAC_PI_2, TAU};
+
+pub type Point = (f64, f64);
+
+/// Control point weight that makes four cubics a close circle
+const KAPPA: f64 = 0.552_284_749_831;
+
+/// One piece of a subpath, starting where the previous one ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Segment {
+    Line(Point),
+    Cubic(Point, Point, Point),
+}
+
+impl Segment {
+    fn end(self) -> Point {
+        match self {
+            Segment::Line(to) | Segment::Cubic(_, _, to) => to,
+        }
+    }
+}
+
+/// A connected run of segments from one moveto
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subpath {
+    pub start: Point,
+    pub segments: Vec<Segment>,
+    pub closed: bool,
+}
+
+impl Subpath {
+    fn new(start: Point) -> Self {
+        Self {
+            start,
+            segments: Vec::new(),
+            closed: false,
+        }
+    }
+
+    /// Straight lines through `points`, closed back to the first if `closed`
+    pub fn polygon(points: &[Point], closed: bool) -> Option<Self> {
+        let (&start, rest) = points.split_first()?;
+        Some(Self {
+            start,
+            segments: rest.iter().map(|&p| Segment::Line(p)).collect(),
+            closed,
+        })
+    }
+
+    /// An axis-aligned ellipse as four cubics, starting at its rightmost point
+    pub fn ellipse(cx: f64, cy: f64, rx: f64, ry: f64) -> Self {
+        let (kx, ky) = (rx * KAPPA, ry * KAPPA);
+        let segments = vec![
+            Segment::Cubic((cx + rx, cy + ky), (cx + kx, cy + ry), (cx, cy + ry)),
+            Segment::Cubic((cx - kx, cy + ry), (cx - rx, cy + ky), (cx - rx, cy)),
+            Segment::Cubic((cx - rx, cy - ky), (cx - kx, cy - ry), (cx, cy - ry)),
+            Segment::Cubic((cx + kx, cy - ry), (cx + rx, cy - ky), (cx + rx, cy)),
+        ];
+        Self {
+            start: (cx + rx, cy),
+            segments,
+            closed: true,
+        }
+    }
+
+    /// The same subpath with every point mapped through `f`
+    ///
+    /// Béziers stay Béziers under affine maps, so only control points move.
+    pub fn map(&self, f: impl Fn(Point) -> Point) -> Self {
+        Self {
+            start: f(self.start),
+            segments: self
+                .segments
+                .iter()
+                .map(|segment| match *segment {
+                    Segment::Line(to) => Segment::Line(f(to)),
+                    Segment::Cubic(c1, c2, to) => Segment::Cubic(f(c1), f(c2), f(to)),
+                })
+                .collect(),
+            closed: self.closed,
+        }
+    }
+
+    /// Points along the subpath, no further than `tolerance` from the curves;
+    /// a closed subpath ends back on its start
+    pub fn flatten(&self, tolerance: f64) -> Vec<Point> {
+        let mut points = vec![self.start];
+        let mut from = self.start;
+        for segment in &self.segments {
+            match *segment {
+                Segment::Line(to) => points.push(to),
+                Segment::Cubic(c1, c2, to) => {
+                    // Uniform steps stay within the tolerance once n² exceeds
+                    // 3/4 of the largest second difference over it
+                    let bend = |a: Point, b: Point, c: Point| {
+                        (a.0 - 2.0 * b.0 + c.0).hypot(a.1 - 2.0 * b.1 + c.1)
+                    };
+                    let m = bend(from, c1, c2).max(bend(c1, c2, to));
+                    let n = ((0.75 * m / tolerance).sqrt().ceil() as usize).max(1);
+                    for i in 1..n {
+                        points.push(cubic_at(from, c1, c2, to, i as f64 / n as f64));
+                    }
+                    points.push(to);
+                }
+            }
+            from = segment.end();
+        }
+        if self.closed && points.last() != Some(&self.start) {
+            points.push(self.start);
+        }
+        points
+    }
+}
+
+fn cubic_at(p0: Point, p1: Point, p2: Point, p3: Point, t: f64) -> Point {
+    let u = 1.0 - t;
+    let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+    (
+        a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0,
+        a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1,
+    )
+}
+
+/// Cubics tracing an elliptical arc from `from` to `to`, per the SVG
+/// endpoint parameterization, in pieces of at most a quarter turn
+fn arc_to_cubics(
+    from: Point,
+    radii: Point,
+    rotation_deg: f64,
+    large_arc: bool,
+    sweep: bool,
+    to: Point,
+) -> Vec<Segment> {
+    if from == to {
+        return Vec::new();
+    }
+    let (mut rx, mut ry) = (radii.0.abs(), radii.1.abs());
+    if rx == 0.0 || ry == 0.0 {
+        return vec![Segment::Line(to)];
+    }
+    let (sin_phi, cos_phi) = rotation_deg.to_radians().sin_cos();
+    let (hx, hy) = ((from.0 - to.0) / 2.0, (from.1 - to.1) / 2.0);
+    let x1 = cos_phi * hx + sin_phi * hy;
+    let y1 = -sin_phi * hx + cos_phi * hy;
+
+    // Radii too small to reach are scaled up until they just do
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let mut coefficient = (numerator / denominator).max(0.0).sqrt();
+    if large_arc == sweep {
+        coefficient = -coefficient;
+    }
+    let (cx1, cy1) = (coefficient * rx * y1 / ry, -coefficient * ry * x1 / rx);
+    let cx = cos_phi * cx1 - sin_phi * cy1 + (from.0 + to.0) / 2.0;
+    let cy = sin_phi * cx1 + cos_phi * cy1 + (from.1 + to.1) / 2.0;
+
+    let angle = |u: Point, v: Point| (u.0 * v.1 - u.1 * v.0).atan2(u.0 * v.0 + u.1 * v.1);
+    let start_vector = ((x1 - cx1) / rx, (y1 - cy1) / ry);
+    let end_vector = ((-x1 - cx1) / rx, (-y1 - cy1) / ry);
+    let theta = angle((1.0, 0.0), start_vector);
+    let mut delta = angle(start_vector, end_vector);
+    if !sweep && delta > 0.0 {
+        delta -= TAU;
+    } else if sweep && delta < 0.0 {
+        delta += TAU;
+    }
+
+    let count = ((delta.abs() / FRAC_PI_2).ceil() as usize).max(1);
+    let step = delta / count as f64;
+    let k = 4.0 / 3.0 * (step / 4.0).tan();
+    let point = |t: f64| {
+        let (s, c) = t.sin_cos();
+        (
+            cx + rx * c * cos_phi - ry * s * sin_phi,
+            cy + rx * c * sin_phi + ry * s * cos_phi,
+        )
+    };
+    let tangent = |t: f64| {
+        let (s, c) = t.sin_cos();
+        (
+            -rx * s * cos_phi - ry * c * sin_phi,
+            -rx * s * sin_phi + ry * c * cos_phi,
+        )
+    };
+    (0..count)
+        .map(|i| {
+            let (t1, t2) = (theta + step * i as f64, theta + step * (i + 1) as f64);
+            let (p1, p2) = (point(t1), point(t2));
+            let (d1, d2) = (tangent(t1), tangent(t2));
+            let end = if i + 1 == count { to } else { p2 };
+            Segment::Cubic(
+                (p1.0 + k * d1.0, p1.1 + k * d1.1),
+                (p2.0 - k * d2.0, p2.1 - k * d2.1),
+                end,
+            )
+        })
+        .collect()
+}
+
+/// Reads numbers and flags out of path data and point lists
+pub struct Lexer<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self {
+            bytes: text.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|b| b.is_ascii_whitespace() || *b == b',')
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_separators();
+        self.pos >= self.bytes.len()
+    }
+
+    /// The next number, which may run straight into the one after it as
+    /// in "1.5.5" or "3-4"
+    pub fn number(&mut self) -> Option<f64> {
+        self.skip_separators();
+        let start = self.pos;
+        let digits = |lexer: &mut Self| {
+            let from = lexer.pos;
+            while lexer.bytes.get(lexer.pos).is_some_and(u8::is_ascii_digit) {
+                lexer.pos += 1;
+            }
+            lexer.pos > from
+        };
+        if matches!(self.bytes.get(self.pos), Some(b'+' | b'-')) {
+            self.pos += 1;
+        }
+        let mut any = digits(self);
+        if self.bytes.get(self.pos) == Some(&b'.') {
+            self.pos += 1;
+            any |= digits(self);
+        }
+        if !any {
+            self.pos = start;
+            return None;
+        }
+        if matches!(self.bytes.get(self.pos), Some(b'e' | b'E')) {
+            let mantissa_end = self.pos;
+            self.pos += 1;
+            if matches!(self.bytes.get(self.pos), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            if !digits(self) {
+                self.pos = mantissa_end;
+            }
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos])
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    /// An arc flag, a single 0 or 1 that needs no separator after it
+    fn flag(&mut self) -> Option<bool> {
+        self.skip_separators();
+        let flag = match self.bytes.get(self.pos) {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return None,
+        };
+        self.pos += 1;
+        Some(flag)
+    }
+
+    fn point(&mut self) -> Option<Point> {
+        Some((self.number()?, self.number()?))
+    }
+
+    fn command(&mut self) -> Option<u8> {
+        self.skip_separators();
+        let byte = *self.bytes.get(self.pos)?;
+        if byte.is_ascii_alphabetic() {
+            self.pos += 1;
+            Some(byte)
+        } else {
+            None
+        }
+    }
+}
+
+/// Parse a `points` attribute into coordinate pairs
+pub fn parse_points(text: &str) -> Vec<Point> {
+    let mut lexer = Lexer::new(text);
+    let mut points = Vec::new();
+    while let Some(point) = lexer.point() {
+        points.push(point);
+    }
+    points
+}
+
+/// Parse path data into subpaths, in the path's own coordinates
+///
+/// Quadratic curves and arcs become cubics. Bad data is an error naming the
+/// byte where it goes wrong.
+pub fn parse_path(d: &str) -> Result<Vec<Subpath>, String> {
+    let mut lexer = Lexer::new(d);
+    let mut subpaths = Vec::new();
+    let mut current: Option<Subpath> = None;
+    let mut pos = (0.0, 0.0);
+    let mut command: Option<u8> = None;
+    // Reflected control points for the smooth curve commands
+    let mut last_cubic: Option<Point> = None;
+    let mut last_quad: Option<Point> = None;
+
+    while !lexer.at_end() {
+        let letter = match lexer.command() {
+            Some(letter) => letter,
+            None => match command {
+                // Extra coordinate pairs after a moveto are linetos
+                Some(b'M') => b'L',
+                Some(b'm') => b'l',
+                Some(letter) if !letter.eq_ignore_ascii_case(&b'z') => letter,
+                _ => {
+                    return Err(format!(
+                        "expected a command at byte {} of \"{}\"",
+                        lexer.pos, d
+                    ))
+                }
+            },
+        };
+        command = Some(letter);
+        let relative = letter.is_ascii_lowercase();
+        let offset = |p: Point| {
+            if relative {
+                (pos.0 + p.0, pos.1 + p.1)
+            } else {
+                p
+            }
+        };
+        let bad = |lexer: &Lexer| {
+            format!(
+                "bad '{}' arguments at byte {} of \"{}\"",
+                letter as char, lexer.pos, d
+            )
+        };
+
+        let mut segments = Vec::new();
+        let (mut cubic_control, mut quad_control) = (None, None);
+        match letter.to_ascii_uppercase() {
+            b'M' => {
+                let to = offset(lexer.point().ok_or_else(|| bad(&lexer))?);
+                subpaths.extend(current.take());
+                current = Some(Subpath::new(to));
+                pos = to;
+            }
+            b'Z' => {
+                if let Some(mut subpath) = current.take() {
+                    subpath.closed = true;
+                    pos = subpath.start;
+                    subpaths.push(subpath);
+                }
+            }
+            b'L' => segments.push(Segment::Line(offset(
+                lexer.point().ok_or_else(|| bad(&lexer))?,
+            ))),
+            b'H' => {
+                let x = lexer.number().ok_or_else(|| bad(&lexer))?;
+                let x = if relative { pos.0 + x } else { x };
+                segments.push(Segment::Line((x, pos.1)));
+            }
+            b'V' => {
+                let y = lexer.number().ok_or_else(|| bad(&lexer))?;
+                let y = if relative { pos.1 + y } else { y };
+                segments.push(Segment::Line((pos.0, y)));
+            }
+            b'C' | b'S' => {
+                let c1 = if letter.eq_ignore_ascii_case(&b'C') {
+                    offset(lexer.point().ok_or_else(|| bad(&lexer))?)
+                } else {
+                    last_cubic.map_or(pos, |c| (2.0 * pos.0 - c.0, 2.0 * pos.1 - c.1))
+                };
+                let c2 = offset(lexer.point().ok_or_else(|| bad(&lexer))?);
+                let to = offset(lexer.point().ok_or_else(|| bad(&lexer))?);
+                segments.push(Segment::Cubic(c1, c2, to));
+                cubic_control = Some(c2);
+            }
+            b'Q' | b'T' => {
+                let control = if letter.eq_ignore_ascii_case(&b'Q') {
+                    offset(lexer.point().ok_or_else(|| bad(&lexer))?)
+                } else {
+                    last_quad.map_or(pos, |c| (2.0 * pos.0 - c.0, 2.0 * pos.1 - c.1))
+                };
+                let to = offset(lexer.point().ok_or_else(|| bad(&lexer))?);
+                // Degree elevation: the cubic's controls sit 2/3 of the
+                // way to the quadratic one
+                segments.push(Segment::Cubic(
+                    (
+                        pos.0 + 2.0 / 3.0 * (control.0 - pos.0),
+                        pos.1 + 2.0 / 3.0 * (control.1 - pos.1),
+                    ),
+                    (
+                        to.0 + 2.0 / 3.0 * (control.0 - to.0),
+                        to.1 + 2.0 / 3.0 * (control.1 - to.1),
+                    ),
+                    to,
+                ));
+                quad_control = Some(control);
+            }
+            b'A' => {
+                let radii = lexer.point().ok_or_else(|| bad(&lexer))?;
+                let rotation = lexer.number().ok_or_else(|| bad(&lexer))?;
+                let large_arc = lexer.flag().ok_or_else(|| bad(&lexer))?;
+                let sweep = lexer.flag().ok_or_else(|| bad(&lexer))?;
+                let to = offset(lexer.point().ok_or_else(|| bad(&lexer))?);
+                segments = arc_to_cubics(pos, radii, rotation, large_arc, sweep, to);
+                if segments.is_empty() {
+                    // An arc to where it starts is drawn as nothing
+                    continue;
+                }
+            }
+            _ => return Err(format!("unknown command '{}'", letter as char)),
+        }
+        last_cubic = cubic_control;
+        last_quad = quad_control;
+
+        if let Some(&last) = segments.last() {
+            // Drawing after a closepath starts again from the closed start
+            let subpath = current.get_or_insert_with(|| Subpath::new(pos));
+            pos = last.end();
+            subpath.segments.extend(segments);
+        }
+    }
+    subpaths.extend(current);
+    Ok(subpaths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn length(points: &[Point]) -> f64 {
+        points
+            .windows(2)
+            .map(|pair| (pair[1].0 - pair[0].0).hypot(pair[1].1 - pair[0].1))
+            .sum()
+    }
+
+    #[test]
+    fn test_commands_relative_and_implicit() {
+        let subpaths = parse_path("m10,10 20,0 v10 H10 z M0 0L5-5.5.5 1").unwrap();
+        assert_eq!(subpaths.len(), 2);
+
+        let square = &subpaths[0];
+        assert!(square.closed);
+        assert_eq!(
+            square.flatten(0.1),
+            vec![
+                (10.0, 10.0),
+                (30.0, 10.0),
+                (30.0, 20.0),
+                (10.0, 20.0),
+                (10.0, 10.0)
+            ]
+        );
+        // "5-5.5.5" is three numbers
+        assert_eq!(
+            subpaths[1].flatten(0.1),
+            vec![(0.0, 0.0), (5.0, -5.5), (0.5, 1.0)]
+        );
+
+        assert!(parse_path("M0 0 L 5").is_err());
+        assert!(parse_path("10 10").is_err());
+    }
+
+    #[test]
+    fn test_curves_flatten_within_tolerance() {
+        // A quarter circle of radius 10 drawn as an arc
+        let arc = parse_path("M10 0 A10 10 0 0 1 0 10").unwrap();
+        for tolerance in [0.5, 0.01] {
+            let points = arc[0].flatten(tolerance);
+            for &(x, y) in &points {
+                assert!((x.hypot(y) - 10.0).abs() <= tolerance);
+            }
+        }
+        let points = arc[0].flatten(0.01);
+        assert!((length(&points) - 5.0 * std::f64::consts::PI).abs() < 0.01);
+
+        let coarse = parse_path("M0 0 Q 10 10 20 0").unwrap()[0].flatten(1.0);
+        let fine = parse_path("M0 0 Q 10 10 20 0").unwrap()[0].flatten(0.01);
+        assert!(coarse.len() < fine.len());
+        assert_eq!(*fine.last().unwrap(), (20.0, 0.0));
+        // The peak of the quadratic is half way to its control point
+        let peak = fine.iter().map(|p| p.1).fold(0.0, f64::max);
+        assert!((peak - 5.0).abs() < 0.05);
+
+        // Compact arc flags and a smooth cubic reflected through the joint
+        let compact = parse_path("M0 0a5 5 0 1010 0").unwrap();
+        assert_eq!(*compact[0].flatten(0.1).last().unwrap(), (10.0, 0.0));
+        let smooth = parse_path("M0 0 C0 10 10 10 10 0 S20 -10 20 0").unwrap();
+        match smooth[0].segments[1] {
+            Segment::Cubic(c1, _, _) => assert_eq!(c1, (10.0, -10.0)),
+            _ => panic!("expected a cubic"),
+        }
+    }
+}
@@ -0,0 +1,207 @@
+// style.rs - SVG transforms, paint colors and lengths
+
+use super::path::{Lexer, Point};
+
+/// An affine map `(x, y) -> (a x + c y + e, b x + d y + f)`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self::new(1.0, 0.0, 0.0, 1.0, 0.0, 0.0);
+
+    pub const fn new(a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Self {
+        Self { a, b, c, d, e, f }
+    }
+
+    pub fn translate(x: f64, y: f64) -> Self {
+        Self::new(1.0, 0.0, 0.0, 1.0, x, y)
+    }
+
+    pub fn scale(x: f64, y: f64) -> Self {
+        Self::new(x, 0.0, 0.0, y, 0.0, 0.0)
+    }
+
+    pub fn apply(&self, (x, y): Point) -> Point {
+        (
+            self.a * x + self.c * y + self.e,
+            self.b * x + self.d * y + self.f,
+        )
+    }
+
+    /// This transform applied after `inner`
+    pub fn then(&self, inner: &Transform) -> Transform {
+        Transform::new(
+            self.a * inner.a + self.c * inner.b,
+            self.b * inner.a + self.d * inner.b,
+            self.a * inner.c + self.c * inner.d,
+            self.b * inner.c + self.d * inner.d,
+            self.a * inner.e + self.c * inner.f + self.e,
+            self.b * inner.e + self.d * inner.f + self.f,
+        )
+    }
+}
+
+/// Parse a `transform` attribute; functions apply right to left, as nested
+/// groups would. Anything unreadable leaves the identity.
+pub fn parse_transform(text: &str) -> Transform {
+    let mut result = Transform::IDENTITY;
+    for item in text.split(')') {
+        let Some((name, args)) = item.split_once('(') else {
+            continue;
+        };
+        let mut lexer = Lexer::new(args);
+        let args: Vec<f64> = std::iter::from_fn(|| lexer.number()).collect();
+        let arg = |i: usize, default: f64| args.get(i).copied().unwrap_or(default);
+        let step = match (
+            name.trim_matches(|c: char| c.is_whitespace() || c == ','),
+            args.len(),
+        ) {
+            ("matrix", 6) => Transform::new(args[0], args[1], args[2], args[3], args[4], args[5]),
+            ("translate", 1 | 2) => Transform::translate(args[0], arg(1, 0.0)),
+            ("scale", 1 | 2) => Transform::scale(args[0], arg(1, args[0])),
+            ("rotate", 1 | 3) => {
+                let (sin, cos) = args[0].to_radians().sin_cos();
+                let (cx, cy) = (arg(1, 0.0), arg(2, 0.0));
+                Transform::translate(cx, cy)
+                    .then(&Transform::new(cos, sin, -sin, cos, 0.0, 0.0))
+                    .then(&Transform::translate(-cx, -cy))
+            }
+            ("skewX", 1) => Transform::new(1.0, 0.0, args[0].to_radians().tan(), 1.0, 0.0, 0.0),
+            ("skewY", 1) => Transform::new(1.0, args[0].to_radians().tan(), 0.0, 1.0, 0.0, 0.0),
+            _ => return Transform::IDENTITY,
+        };
+        result = result.then(&step);
+    }
+    result
+}
+
+/// Paint from a `stroke` or `fill` value: Some(None) for "none", None for
+/// values that do not name a color and so leave the inherited paint
+pub fn parse_paint(value: &str) -> Option<Option<[u8; 3]>> {
+    let value = value.trim().to_ascii_lowercase();
+    if value == "none" || value == "transparent" {
+        return Some(None);
+    }
+    if let Some(hex) = value.strip_prefix('#') {
+        let digits: Vec<u8> = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<_>>()?;
+        return match digits[..] {
+            [r, g, b] => Some(Some([r * 17, g * 17, b * 17])),
+            [r1, r2, g1, g2, b1, b2] => Some(Some([r1 * 16 + r2, g1 * 16 + g2, b1 * 16 + b2])),
+            _ => None,
+        };
+    }
+    if let Some(args) = value
+        .strip_prefix("rgb(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        let channels: Vec<u8> = args
+            .split(',')
+            .map(|channel| {
+                let channel = channel.trim();
+                let level = match channel.strip_suffix('%') {
+                    Some(percent) => percent.trim().parse::<f64>().ok()? * 2.55,
+                    None => channel.parse::<f64>().ok()?,
+                };
+                Some(level.round().clamp(0.0, 255.0) as u8)
+            })
+            .collect::<Option<_>>()?;
+        return match channels[..] {
+            [r, g, b] => Some(Some([r, g, b])),
+            _ => None,
+        };
+    }
+    let rgb = match value.as_str() {
+        "black" => [0, 0, 0],
+        "white" => [255, 255, 255],
+        "red" => [255, 0, 0],
+        "lime" => [0, 255, 0],
+        "green" => [0, 128, 0],
+        "blue" => [0, 0, 255],
+        "yellow" => [255, 255, 0],
+        "cyan" | "aqua" => [0, 255, 255],
+        "magenta" | "fuchsia" => [255, 0, 255],
+        "gray" | "grey" => [128, 128, 128],
+        "silver" => [192, 192, 192],
+        "maroon" => [128, 0, 0],
+        "olive" => [128, 128, 0],
+        "purple" => [128, 0, 128],
+        "teal" => [0, 128, 128],
+        "navy" => [0, 0, 128],
+        "orange" => [255, 165, 0],
+        "brown" => [165, 42, 42],
+        "pink" => [255, 192, 203],
+        _ => return None,
+    };
+    Some(Some(rgb))
+}
+
+/// A length in mm, given `px_mm` for unitless values and px; None for
+/// percentages and anything else that cannot be resolved on its own
+pub fn parse_length_mm(text: &str, px_mm: f64) -> Option<f64> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| c.is_ascii_alphabetic() || c == '%')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let value: f64 = number.trim().parse().ok()?;
+    let mm = match unit {
+        "" | "px" => px_mm,
+        "mm" => 1.0,
+        "cm" => 10.0,
+        "in" => 25.4,
+        "pt" => 25.4 / 72.0,
+        "pc" => 25.4 / 6.0,
+        _ => return None,
+    };
+    Some(value * mm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(p: Point, q: Point) -> bool {
+        (p.0 - q.0).abs() < 1e-9 && (p.1 - q.1).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_transform_lists_compose() {
+        // Scale first, then move
+        let t = parse_transform("translate(10, 5) scale(2)");
+        assert!(close(t.apply((1.0, 1.0)), (12.0, 7.0)));
+
+        let rotated = parse_transform("rotate(90 10 10)");
+        assert!(close(rotated.apply((20.0, 10.0)), (10.0, 20.0)));
+
+        let matrix = parse_transform("matrix(1 0 0 1 3 4),skewX(45)");
+        assert!(close(matrix.apply((0.0, 2.0)), (5.0, 6.0)));
+
+        assert_eq!(parse_transform("wobble(3)"), Transform::IDENTITY);
+    }
+
+    #[test]
+    fn test_paint_and_lengths() {
+        assert_eq!(parse_paint("#F00"), Some(Some([255, 0, 0])));
+        assert_eq!(parse_paint("#0080ff"), Some(Some([0, 128, 255])));
+        assert_eq!(parse_paint("rgb(10, 20, 100%)"), Some(Some([10, 20, 255])));
+        assert_eq!(parse_paint("Navy"), Some(Some([0, 0, 128])));
+        assert_eq!(parse_paint("none"), Some(None));
+        assert_eq!(parse_paint("url(#gradient)"), None);
+
+        assert_eq!(parse_length_mm("100mm", 0.5), Some(100.0));
+        assert_eq!(parse_length_mm("2in", 0.5), Some(50.8));
+        let inch = parse_length_mm("96", 25.4 / 96.0).unwrap();
+        assert!((inch - 25.4).abs() < 1e-9);
+        assert_eq!(parse_length_mm("100%", 0.5), None);
+    }
+}
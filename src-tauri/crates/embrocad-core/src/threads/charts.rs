@@ -0,0 +1,195 @@
+// charts.rs - Built-in thread charts as (rgb, name, catalog number) tables
+//
+// Each table holds the commonly stocked shades of a brand's chart. The RGB
+// values approximate the printed shade cards; screens and dye lots vary, so
+// they are meant for matching and preview rather than exact reproduction.
+
+/// Madeira Classic 40 rayon
+pub const MADEIRA_CLASSIC_40: &[([u8; 3], &str, &str)] = &[
+    ([0x00, 0x00, 0x00], "Black", "1000"),
+    ([0xFF, 0xFF, 0xFF], "White", "1001"),
+    ([0xF4, 0xF1, 0xE3], "Winter White", "1002"),
+    ([0xC8, 0xC8, 0xC6], "Silver Grey", "1011"),
+    ([0x7B, 0x7D, 0x80], "Steel Grey", "1041"),
+    ([0x4A, 0x4C, 0x4F], "Charcoal", "1012"),
+    ([0xFF, 0xE6, 0x2E], "Lemon", "1023"),
+    ([0xFF, 0xD1, 0x00], "Yellow", "1024"),
+    ([0xF7, 0xB5, 0x00], "Sunflower", "1124"),
+    ([0xD9, 0xA4, 0x1E], "Old Gold", "1070"),
+    ([0xFF, 0x8C, 0x00], "Tangerine", "1078"),
+    ([0xF2, 0x6B, 0x1D], "Orange", "1065"),
+    ([0xE8, 0x4A, 0x27], "Flame", "1021"),
+    ([0xE5, 0x1B, 0x24], "Poppy", "1037"),
+    ([0xC8, 0x10, 0x2E], "Christmas Red", "1147"),
+    ([0xB0, 0x12, 0x2C], "Cardinal", "1181"),
+    ([0x7A, 0x1F, 0x2B], "Burgundy", "1035"),
+    ([0xF7, 0xB9, 0xC8], "Baby Pink", "1117"),
+    ([0xF2, 0x79, 0xA0], "Rose", "1109"),
+    ([0xD6, 0x2B, 0x7B], "Fuchsia", "1110"),
+    ([0xB3, 0x9B, 0xD1], "Lavender", "1031"),
+    ([0x6A, 0x2C, 0x91], "Purple", "1122"),
+    ([0x3C, 0x1D, 0x5E], "Deep Purple", "1033"),
+    ([0xA8, 0xD4, 0xEE], "Baby Blue", "1028"),
+    ([0x41, 0x8F, 0xDE], "Sky Blue", "1029"),
+    ([0x00, 0x5E, 0xB8], "Royal Blue", "1076"),
+    ([0x1B, 0x2A, 0x5C], "Navy", "1043"),
+    ([0x00, 0x8C, 0x95], "Teal", "1090"),
+    ([0x9C, 0xD3, 0x6B], "Spring Green", "1047"),
+    ([0x3F, 0xA3, 0x3F], "Kelly Green", "1051"),
+    ([0x00, 0x6B, 0x3C], "Emerald", "1101"),
+    ([0x1E, 0x4D, 0x2B], "Forest Green", "1079"),
+    ([0x73, 0x74, 0x2A], "Olive", "1157"),
+    ([0xD8, 0xC3, 0x9A], "Beige", "1082"),
+    ([0xB0, 0x7A, 0x43], "Camel", "1126"),
+    ([0x7A, 0x4A, 0x25], "Chestnut", "1059"),
+    ([0x4B, 0x2E, 0x1F], "Dark Brown", "1058"),
+    ([0xF6, 0xC9, 0xA8], "Flesh", "1017"),
+];
+
+/// Isacord 40 polyester
+pub const ISACORD_40: &[([u8; 3], &str, &str)] = &[
+    ([0x00, 0x00, 0x00], "Black", "0020"),
+    ([0xFF, 0xFF, 0xFF], "White", "0015"),
+    ([0xF3, 0xF0, 0xE6], "Silky White", "0010"),
+    ([0xBE, 0xBE, 0xBA], "Sterling", "0142"),
+    ([0x84, 0x86, 0x89], "Whale", "0111"),
+    ([0x52, 0x54, 0x57], "Dark Pewter", "0132"),
+    ([0xFF, 0xEB, 0x3B], "Lemon", "0310"),
+    ([0xFF, 0xD4, 0x00], "Sunflower", "0506"),
+    ([0xF5, 0xB8, 0x00], "Goldenrod", "0600"),
+    ([0xD1, 0x9C, 0x27], "Old Gold", "0822"),
+    ([0xFF, 0x9E, 0x1B], "Tangerine", "1102"),
+    ([0xF4, 0x71, 0x20], "Orange", "1300"),
+    ([0xE0, 0x4E, 0x2A], "Paprika", "1311"),
+    ([0xD8, 0x1E, 0x2A], "Poppy", "1703"),
+    ([0xC8, 0x13, 0x2C], "Poinsettia", "1902"),
+    ([0xB5, 0x15, 0x34], "Lipstick", "1903"),
+    ([0x99, 0x14, 0x2E], "Cardinal", "1904"),
+    ([0x6E, 0x1C, 0x2B], "Wine", "2011"),
+    ([0xF6, 0xBE, 0xCC], "Pink Tulip", "2155"),
+    ([0xEE, 0x7D, 0xA3], "Azalea", "2520"),
+    ([0xD2, 0x2E, 0x7F], "Raspberry", "2506"),
+    ([0xB4, 0xA0, 0xD2], "Lavender", "3040"),
+    ([0x6E, 0x33, 0x93], "Purple", "2910"),
+    ([0x42, 0x20, 0x63], "Grape", "2920"),
+    ([0xAB, 0xD3, 0xEC], "Ice Cap", "3641"),
+    ([0x3F, 0x8E, 0xD8], "Blue Ribbon", "3710"),
+    ([0x00, 0x59, 0xB3], "Royal Blue", "3522"),
+    ([0x1C, 0x2B, 0x5A], "Navy", "3644"),
+    ([0x00, 0x8A, 0x94], "Teal", "4116"),
+    ([0xA3, 0xD6, 0x6E], "Mint", "5610"),
+    ([0x44, 0xA0, 0x43], "Kelly", "5513"),
+    ([0x00, 0x6A, 0x3E], "Emerald", "5324"),
+    ([0x22, 0x4F, 0x2D], "Forest Green", "5374"),
+    ([0x76, 0x75, 0x2C], "Olive", "6133"),
+    ([0xD6, 0xC2, 0x9C], "Sand", "0761"),
+    ([0xB2, 0x7B, 0x45], "Caramel", "1154"),
+    ([0x7C, 0x4B, 0x27], "Chestnut", "1876"),
+    ([0x4A, 0x2F, 0x20], "Espresso", "1874"),
+    ([0xF5, 0xC8, 0xA9], "Flesh", "1755"),
+];
+
+/// Robison-Anton Super Brite polyester
+pub const ROBISON_ANTON_SUPER_BRITE: &[([u8; 3], &str, &str)] = &[
+    ([0x00, 0x00, 0x00], "Black", "5596"),
+    ([0xFF, 0xFF, 0xFF], "White", "5597"),
+    ([0xF2, 0xEE, 0xE0], "Snow White", "5599"),
+    ([0xC4, 0xC5, 0xC4], "Silver", "5668"),
+    ([0x80, 0x82, 0x85], "Grey", "5662"),
+    ([0x4C, 0x4E, 0x52], "Smoke", "5643"),
+    ([0xFF, 0xE8, 0x2F], "Lemon", "5733"),
+    ([0xFF, 0xCE, 0x00], "Yellow", "5735"),
+    ([0xF4, 0xAE, 0x00], "Gold", "5724"),
+    ([0xD3, 0x9E, 0x23], "Old Gold", "5727"),
+    ([0xFF, 0x91, 0x12], "Tangerine", "5762"),
+    ([0xF0, 0x66, 0x1C], "Orange", "5764"),
+    ([0xDF, 0x3F, 0x26], "Red Orange", "5790"),
+    ([0xDE, 0x1F, 0x26], "Jay Red", "5780"),
+    ([0xC6, 0x12, 0x2D], "Red", "5781"),
+    ([0xAA, 0x14, 0x30], "Cardinal", "5782"),
+    ([0x74, 0x1C, 0x2C], "Burgundy", "5784"),
+    ([0xF5, 0xBA, 0xC9], "Pink", "5704"),
+    ([0xEC, 0x74, 0x9E], "Rose", "5707"),
+    ([0xD1, 0x27, 0x7C], "Magenta", "5708"),
+    ([0xB2, 0x9D, 0xD0], "Lilac", "5631"),
+    ([0x6B, 0x2E, 0x90], "Purple", "5638"),
+    ([0x3E, 0x1F, 0x60], "Dark Purple", "5639"),
+    ([0xA6, 0xD2, 0xEC], "Light Blue", "5613"),
+    ([0x3B, 0x8B, 0xD9], "Sky Blue", "5616"),
+    ([0x00, 0x5C, 0xB6], "Royal", "5623"),
+    ([0x1A, 0x29, 0x59], "Navy", "5627"),
+    ([0x00, 0x88, 0x92], "Teal", "5651"),
+    ([0x9E, 0xD2, 0x69], "Lime", "5684"),
+    ([0x40, 0x9F, 0x3E], "Kelly", "5686"),
+    ([0x00, 0x68, 0x3B], "Emerald", "5687"),
+    ([0x20, 0x4C, 0x2B], "Forest", "5690"),
+    ([0x74, 0x72, 0x2B], "Olive Drab", "5693"),
+    ([0xD7, 0xC1, 0x98], "Khaki", "5742"),
+    ([0xAE, 0x78, 0x42], "Tan", "5745"),
+    ([0x7B, 0x4A, 0x26], "Brown", "5748"),
+    ([0x49, 0x2D, 0x1F], "Dark Brown", "5749"),
+    ([0xF4, 0xC7, 0xA7], "Flesh", "5702"),
+];
+
+/// Brother Embroidery polyester, with the same swatches as the PEC palette
+pub const BROTHER_POLYESTER: &[([u8; 3], &str, &str)] = &[
+    ([0x00, 0x00, 0x00], "Black", "900"),
+    ([0xF0, 0xF0, 0xF0], "White", "001"),
+    ([0xA8, 0xA8, 0xA8], "Silver", "005"),
+    ([0x87, 0x87, 0x87], "Gray", "817"),
+    ([0x4F, 0x55, 0x56], "Pewter", "704"),
+    ([0x29, 0x31, 0x33], "Dark Gray", "707"),
+    ([0xFF, 0xFF, 0x00], "Yellow", "205"),
+    ([0xF0, 0xF9, 0x70], "Lemon Yellow", "202"),
+    ([0xFF, 0xF3, 0x6B], "Cream Yellow", "010"),
+    ([0xFF, 0xD9, 0x11], "Harvest Gold", "206"),
+    ([0xE8, 0xA9, 0x00], "Deep Gold", "214"),
+    ([0xBA, 0x98, 0x00], "Brass", "328"),
+    ([0xFE, 0xBA, 0x35], "Orange", "208"),
+    ([0xFE, 0x9E, 0x32], "Tangerine", "209"),
+    ([0xFE, 0xB3, 0x43], "Pumpkin", "126"),
+    ([0xFE, 0x37, 0x0F], "Vermilion", "030"),
+    ([0xED, 0x17, 0x1F], "Red", "800"),
+    ([0xF7, 0x38, 0x66], "Carmine", "807"),
+    ([0xB5, 0x4B, 0x64], "Amber Red", "333"),
+    ([0xC7, 0x01, 0x56], "Dark Fuchsia", "107"),
+    ([0xF6, 0x4A, 0x8A], "Deep Rose", "086"),
+    ([0xF9, 0x93, 0xBC], "Pink", "085"),
+    ([0xFC, 0xBB, 0xC5], "Salmon Pink", "079"),
+    ([0xFD, 0xD9, 0xDE], "Flesh Pink", "124"),
+    ([0xE4, 0x9A, 0xCB], "Light Lilac", "810"),
+    ([0x91, 0x36, 0x97], "Magenta", "620"),
+    ([0x91, 0x5F, 0xAC], "Lilac", "612"),
+    ([0x6A, 0x1C, 0x8A], "Violet", "613"),
+    ([0x4E, 0x29, 0x90], "Purple", "614"),
+    ([0x77, 0x01, 0x76], "Royal Purple", "869"),
+    ([0xB2, 0xAF, 0xD4], "Lavender", "804"),
+    ([0x68, 0x6A, 0xB0], "Wisteria Violet", "607"),
+    ([0xA8, 0xDE, 0xEB], "Light Blue", "017"),
+    ([0x25, 0x84, 0xBB], "Sky Blue", "019"),
+    ([0x4B, 0x6B, 0xAF], "Cornflower Blue", "070"),
+    ([0x0A, 0x55, 0xA3], "Blue", "405"),
+    ([0x09, 0x5B, 0xA6], "Electric Blue", "420"),
+    ([0x0B, 0x3D, 0x91], "Ultramarine", "406"),
+    ([0x0E, 0x1F, 0x7C], "Prussian Blue", "007"),
+    ([0x13, 0x4A, 0x46], "Peacock Blue", "415"),
+    ([0x00, 0x87, 0x77], "Teal Green", "534"),
+    ([0xA8, 0xDD, 0xC4], "Seacrest", "542"),
+    ([0x9E, 0xD6, 0x7D], "Mint Green", "502"),
+    ([0xE3, 0xF3, 0x5B], "Fresh Green", "027"),
+    ([0x70, 0xBC, 0x1F], "Lime Green", "513"),
+    ([0x66, 0xBA, 0x49], "Leaf Green", "509"),
+    ([0x2F, 0x7E, 0x20], "Moss Green", "515"),
+    ([0x00, 0x67, 0x3E], "Emerald Green", "507"),
+    ([0x00, 0x38, 0x22], "Deep Green", "808"),
+    ([0x43, 0x56, 0x07], "Dark Olive", "517"),
+    ([0x13, 0x2B, 0x1A], "Olive Green", "519"),
+    ([0xEF, 0xE3, 0xB9], "Beige", "843"),
+    ([0xD0, 0xA6, 0x60], "Khaki", "348"),
+    ([0xD8, 0xCC, 0xC6], "Warm Gray", "399"),
+    ([0xB2, 0x76, 0x24], "Light Brown", "323"),
+    ([0xD1, 0x5C, 0x00], "Reddish Brown", "337"),
+    ([0xD1, 0x54, 0x00], "Clay Brown", "339"),
+    ([0x7D, 0x6F, 0x00], "Russet Brown", "330"),
+    ([0x2A, 0x13, 0x01], "Dark Brown", "058"),
+];
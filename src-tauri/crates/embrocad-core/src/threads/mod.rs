@@ -0,0 +1,301 @@
+// mod.rs - Built-in thread charts and nearest-color matching in CIELAB
+
+mod charts;
+
+use crate::dst::{Pattern, ThreadColor};
+use crate::export::block_rgb;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use charts::{BROTHER_POLYESTER, ISACORD_40, MADEIRA_CLASSIC_40, ROBISON_ANTON_SUPER_BRITE};
+
+/// A thread line with a built-in chart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ThreadBrand {
+    #[cfg_attr(feature = "serde", serde(rename = "madeira_classic_40"))]
+    MadeiraClassic40,
+    #[cfg_attr(feature = "serde", serde(rename = "isacord_40"))]
+    Isacord40,
+    RobisonAntonSuperBrite,
+    BrotherPolyester,
+}
+
+impl ThreadBrand {
+    /// Name shown to the user and prefixed to thread descriptions
+    pub fn name(self) -> &'static str {
+        match self {
+            ThreadBrand::MadeiraClassic40 => "Madeira Classic 40",
+            ThreadBrand::Isacord40 => "Isacord 40",
+            ThreadBrand::RobisonAntonSuperBrite => "Robison-Anton Super Brite",
+            ThreadBrand::BrotherPolyester => "Brother Polyester",
+        }
+    }
+
+    fn chart(self) -> &'static [([u8; 3], &'static str, &'static str)] {
+        match self {
+            ThreadBrand::MadeiraClassic40 => MADEIRA_CLASSIC_40,
+            ThreadBrand::Isacord40 => ISACORD_40,
+            ThreadBrand::RobisonAntonSuperBrite => ROBISON_ANTON_SUPER_BRITE,
+            ThreadBrand::BrotherPolyester => BROTHER_POLYESTER,
+        }
+    }
+
+    /// Every thread in the brand's chart, in chart order
+    pub fn palette(self) -> Vec<CatalogThread> {
+        self.chart()
+            .iter()
+            .map(|&(rgb, name, code)| CatalogThread {
+                brand: self,
+                code,
+                name,
+                rgb,
+            })
+            .collect()
+    }
+
+    /// Look up a thread by its catalog number
+    pub fn find(self, code: &str) -> Option<CatalogThread> {
+        self.palette()
+            .into_iter()
+            .find(|thread| thread.code == code)
+    }
+
+    /// The chart's closest thread to `rgb` by CIE76 color difference
+    pub fn nearest(self, rgb: [u8; 3]) -> CatalogThread {
+        let target = lab(rgb);
+        self.palette()
+            .into_iter()
+            .min_by(|a, b| delta_e(lab(a.rgb), target).total_cmp(&delta_e(lab(b.rgb), target)))
+            .expect("thread charts are not empty")
+    }
+}
+
+/// One entry of a thread chart
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct CatalogThread {
+    pub brand: ThreadBrand,
+    pub code: &'static str,
+    pub name: &'static str,
+    pub rgb: [u8; 3],
+}
+
+impl CatalogThread {
+    /// The design color for this thread, described as "Brand Name" like the
+    /// colors read from VP3 and DST files
+    pub fn thread_color(&self) -> ThreadColor {
+        ThreadColor {
+            rgb: self.rgb,
+            description: Some(format!("{} {}", self.brand.name(), self.name)),
+            catalog_number: Some(self.code.to_string()),
+            needle: None,
+            basting: false,
+        }
+    }
+}
+
+/// A catalog thread chosen in the UI: a specific catalog number, or the
+/// brand's closest match to a color when `code` is None
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub struct ThreadRef {
+    pub brand: ThreadBrand,
+    pub code: Option<String>,
+}
+
+impl ThreadRef {
+    /// Resolve to a chart entry, matching `rgb` when no code is given
+    pub fn resolve(&self, rgb: [u8; 3]) -> Result<CatalogThread, String> {
+        match &self.code {
+            Some(code) => self
+                .brand
+                .find(code)
+                .ok_or_else(|| format!("{} has no thread {}", self.brand.name(), code)),
+            None => Ok(self.brand.nearest(rgb)),
+        }
+    }
+}
+
+/// One row of a conversion between thread brands
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct PaletteMatch {
+    pub block: usize,
+    /// The block's thread in the source brand: its catalog number when the
+    /// chart has it, otherwise the closest match to its color
+    pub original: CatalogThread,
+    pub matched: CatalogThread,
+    /// CIE76 difference between the two; above about 5 the change is plain to see
+    pub delta_e: f64,
+}
+
+/// Map every color block's thread in `from` to the closest one in `to`
+pub fn convert_palette(pattern: &Pattern, from: ThreadBrand, to: ThreadBrand) -> Vec<PaletteMatch> {
+    (0..pattern.color_blocks.len())
+        .map(|block| {
+            let color = pattern.thread_color_for_block(block);
+            let original = color
+                .and_then(|c| c.catalog_number.as_deref())
+                .and_then(|code| from.find(code))
+                .unwrap_or_else(|| from.nearest(block_rgb(pattern, block)));
+            let matched = to.nearest(original.rgb);
+            PaletteMatch {
+                block,
+                delta_e: delta_e(lab(original.rgb), lab(matched.rgb)),
+                original,
+                matched,
+            }
+        })
+        .collect()
+}
+
+/// Linear sRGB to CIE XYZ under D65, one row per X, Y and Z
+const SRGB_TO_XYZ: [[f64; 3]; 3] = [
+    [0.4124, 0.3576, 0.1805],
+    [0.2126, 0.7152, 0.0722],
+    [0.0193, 0.1192, 0.9505],
+];
+
+/// CIELAB coordinates of an sRGB color under the D65 white point
+///
+/// The white point is taken from the same matrix, so sRGB white lands
+/// exactly on the neutral axis.
+pub fn lab(rgb: [u8; 3]) -> [f64; 3] {
+    let linear = rgb.map(|channel| {
+        let value = channel as f64 / 255.0;
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    });
+    let [x, y, z] = SRGB_TO_XYZ.map(|row| {
+        let value: f64 = row.iter().zip(linear).map(|(k, c)| k * c).sum();
+        value / row.iter().sum::<f64>()
+    });
+
+    let f = |t: f64| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// CIE76 color difference: the Euclidean distance between two Lab colors
+pub fn delta_e(a: [f64; 3], b: [f64; 3]) -> f64 {
+    (0..3).map(|i| (a[i] - b[i]).powi(2)).sum::<f64>().sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lab_reference_points() {
+        let white = lab([255, 255, 255]);
+        assert!((white[0] - 100.0).abs() < 0.01);
+        assert!(white[1].abs() < 0.01 && white[2].abs() < 0.01);
+        assert!(lab([0, 0, 0]).iter().all(|v| v.abs() < 1e-9));
+
+        let red = lab([255, 0, 0]);
+        assert!((red[0] - 53.24).abs() < 0.05);
+        assert!((red[1] - 80.09).abs() < 0.05);
+        assert!((red[2] - 67.20).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_nearest_red_in_isacord() {
+        let thread = ThreadBrand::Isacord40.nearest([255, 0, 0]);
+        assert_eq!((thread.code, thread.name), ("1703", "Poppy"));
+
+        let color = thread.thread_color();
+        assert_eq!(color.description.as_deref(), Some("Isacord 40 Poppy"));
+        assert_eq!(color.catalog_number.as_deref(), Some("1703"));
+    }
+
+    #[test]
+    fn test_charts_match_their_own_swatches() {
+        let brands = [
+            ThreadBrand::MadeiraClassic40,
+            ThreadBrand::Isacord40,
+            ThreadBrand::RobisonAntonSuperBrite,
+            ThreadBrand::BrotherPolyester,
+        ];
+        for brand in brands {
+            for thread in brand.palette() {
+                assert_eq!(brand.nearest(thread.rgb).rgb, thread.rgb, "{}", thread.code);
+                assert_eq!(brand.find(thread.code), Some(thread.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_thread_ref_resolves_code_or_color() {
+        let by_code = ThreadRef {
+            brand: ThreadBrand::MadeiraClassic40,
+            code: Some("1147".to_string()),
+        };
+        assert_eq!(by_code.resolve([0, 0, 0]).unwrap().name, "Christmas Red");
+
+        let by_color = ThreadRef {
+            brand: ThreadBrand::BrotherPolyester,
+            code: None,
+        };
+        assert_eq!(by_color.resolve([237, 23, 31]).unwrap().code, "800");
+
+        let unknown = ThreadRef {
+            brand: ThreadBrand::Isacord40,
+            code: Some("9999".to_string()),
+        };
+        assert!(unknown.resolve([0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_convert_madeira_to_isacord() {
+        use crate::dst::StitchCommand;
+
+        let mut pattern = Pattern::new();
+        for (x, command) in [
+            (0.0, StitchCommand::Stitch),
+            (10.0, StitchCommand::ColorChange),
+            (20.0, StitchCommand::Stitch),
+            (30.0, StitchCommand::ColorChange),
+            (40.0, StitchCommand::Stitch),
+            (40.0, StitchCommand::End),
+        ] {
+            pattern.add_stitch(x, 0.0, command);
+        }
+        // Christmas Red by catalog number, navy by color only, then a block
+        // drawn from the default palette
+        let mut red = ThreadColor::new([0, 0, 0]);
+        red.catalog_number = Some("1147".to_string());
+        pattern.metadata.thread_colors = vec![red, ThreadColor::new([0x1B, 0x2A, 0x5C])];
+        pattern.calculate_color_blocks();
+
+        let rows = convert_palette(
+            &pattern,
+            ThreadBrand::MadeiraClassic40,
+            ThreadBrand::Isacord40,
+        );
+        let pairs: Vec<_> = rows
+            .iter()
+            .take(2)
+            .map(|row| (row.block, row.original.code, row.matched.code))
+            .collect();
+        assert_eq!(pairs, vec![(0, "1147", "1902"), (1, "1043", "3644")]);
+        assert!(rows[0].delta_e > 0.0 && rows[0].delta_e < 3.0);
+
+        let fallback = ThreadBrand::MadeiraClassic40.nearest(crate::export::DEFAULT_PALETTE[2]);
+        assert_eq!(rows[2].original, fallback);
+        assert_eq!(
+            rows[2].matched,
+            ThreadBrand::Isacord40.nearest(fallback.rgb)
+        );
+    }
+}
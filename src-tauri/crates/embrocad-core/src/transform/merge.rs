@@ -0,0 +1,140 @@
+// merge.rs - Combine several designs into one pattern
+
+use crate::dst::{Pattern, StitchCommand};
+
+impl Pattern {
+    /// Append `other` shifted by (`offset_x`, `offset_y`) in 0.1mm units
+    ///
+    /// The seam gets a Trim, an optional ColorChange, and a Move to the
+    /// first appended stitch. Only the combined pattern ends with End.
+    pub fn append(
+        &mut self,
+        other: &Pattern,
+        offset_x: f64,
+        offset_y: f64,
+        insert_color_change: bool,
+    ) {
+        while self
+            .stitches
+            .last()
+            .is_some_and(|s| s.command == StitchCommand::End)
+        {
+            self.stitches.pop();
+        }
+
+        let incoming: Vec<_> = other
+            .stitches
+            .iter()
+            .filter(|s| s.command != StitchCommand::End)
+            .collect();
+        if let (Some(last), Some(first)) = (self.stitches.last().cloned(), incoming.first()) {
+            self.add_stitch(last.x, last.y, StitchCommand::Trim);
+            if insert_color_change {
+                self.add_stitch(last.x, last.y, StitchCommand::ColorChange);
+            }
+            self.add_stitch(first.x + offset_x, first.y + offset_y, StitchCommand::Move);
+
+            // Without a color change the first appended block keeps the current thread
+            let skip = usize::from(!insert_color_change);
+            let colors = other.metadata.thread_colors.iter().skip(skip).cloned();
+            self.metadata.thread_colors.extend(colors);
+        } else if self.stitches.is_empty() {
+            self.metadata.thread_colors = other.metadata.thread_colors.clone();
+        }
+
+        for stitch in incoming {
+            self.add_stitch(stitch.x + offset_x, stitch.y + offset_y, stitch.command);
+        }
+
+        let (x, y) = self.stitches.last().map_or((0.0, 0.0), |s| (s.x, s.y));
+        self.add_stitch(x, y, StitchCommand::End);
+
+        self.metadata.stitch_count = Some(self.stitches.len() as u32);
+        self.metadata.color_count = Some(self.color_changes);
+
+        // Calculate bounds, statistics and color blocks
+        self.calculate_bounds();
+        self.calculate_statistics();
+        self.calculate_color_blocks();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dst::{Pattern, StitchCommand, ThreadColor};
+    use StitchCommand::*;
+
+    fn design(points: &[(f64, f64)], colors: &[[u8; 3]]) -> Pattern {
+        let mut pattern = Pattern::new();
+        for &(x, y) in points {
+            pattern.add_stitch(x, y, Stitch);
+        }
+        if let Some(&(x, y)) = points.last() {
+            pattern.add_stitch(x, y, End);
+        }
+        pattern.metadata.thread_colors = colors.iter().map(|&rgb| ThreadColor::new(rgb)).collect();
+        pattern
+    }
+
+    fn commands(pattern: &Pattern) -> Vec<(f64, f64, StitchCommand)> {
+        pattern
+            .stitches
+            .iter()
+            .map(|s| (s.x, s.y, s.command))
+            .collect()
+    }
+
+    #[test]
+    fn test_append_with_color_change() {
+        let mut monogram = design(&[(0.0, 0.0), (10.0, 0.0)], &[[255, 0, 0]]);
+        let border = design(&[(0.0, 0.0), (0.0, 5.0)], &[[0, 0, 255]]);
+        monogram.append(&border, 100.0, 50.0, true);
+
+        assert_eq!(
+            commands(&monogram),
+            vec![
+                (0.0, 0.0, Stitch),
+                (10.0, 0.0, Stitch),
+                (10.0, 0.0, Trim),
+                (10.0, 0.0, ColorChange),
+                (100.0, 50.0, Move),
+                (100.0, 50.0, Stitch),
+                (100.0, 55.0, Stitch),
+                (100.0, 55.0, End),
+            ]
+        );
+        assert_eq!(monogram.color_changes, 1);
+        assert_eq!(monogram.color_blocks.len(), 2);
+        assert_eq!(monogram.metadata.thread_colors.len(), 2);
+        assert_eq!(monogram.bounds.as_ref().unwrap().max_x, 100.0);
+    }
+
+    #[test]
+    fn test_append_same_color() {
+        let mut first = design(&[(0.0, 0.0)], &[[255, 0, 0]]);
+        first.append(&design(&[(1.0, 1.0)], &[[255, 0, 0]]), 0.0, 0.0, false);
+
+        assert_eq!(first.color_changes, 0);
+        assert_eq!(first.metadata.thread_colors.len(), 1);
+        let ends = first.stitches.iter().filter(|s| s.command == End).count();
+        assert_eq!(ends, 1);
+        assert_eq!(first.stitches.last().unwrap().command, End);
+    }
+
+    #[test]
+    fn test_append_empty_patterns() {
+        let border = design(&[(0.0, 0.0), (0.0, 5.0)], &[[0, 0, 255]]);
+
+        let mut empty = Pattern::new();
+        empty.append(&border, 10.0, 0.0, true);
+        assert_eq!(
+            commands(&empty),
+            vec![(10.0, 0.0, Stitch), (10.0, 5.0, Stitch), (10.0, 5.0, End)]
+        );
+        assert_eq!(empty.metadata.thread_colors.len(), 1);
+
+        let mut unchanged = border.clone();
+        unchanged.append(&Pattern::new(), 10.0, 0.0, true);
+        assert_eq!(commands(&unchanged), commands(&border));
+    }
+}
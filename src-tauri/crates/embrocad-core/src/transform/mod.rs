@@ -0,0 +1,160 @@
+// mod.rs - Geometric transforms applied to loaded patterns
+
+mod merge;
+mod repeat;
+mod reverse;
+mod rotate;
+mod scale;
+mod split;
+mod translate;
+
+pub use repeat::RepeatLayout;
+pub use rotate::{MirrorAxis, Pivot};
+pub use scale::DEFAULT_SCALE_UP_WARNING;
+
+use crate::analysis::DEFAULT_SHORT_STITCH_MM;
+use crate::dst::Pattern;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A transform requested by the frontend
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum TransformOperation {
+    Scale {
+        factor_x: f64,
+        factor_y: f64,
+    },
+    /// Counter-clockwise on screen; negative degrees turn clockwise
+    Rotate {
+        degrees: f64,
+        about: Pivot,
+    },
+    Mirror {
+        axis: MirrorAxis,
+    },
+    /// Shift by (dx, dy) in 0.1mm units
+    Translate {
+        dx: f64,
+        dy: f64,
+    },
+    /// Move the bounds midpoint to the origin
+    Center,
+    /// Sew the whole design backwards
+    Reverse,
+    /// Sew one color block backwards
+    ReverseBlock {
+        block: usize,
+    },
+    /// Break Stitch and Move records longer than the limit into chains
+    SplitLongStitches {
+        max_len_mm: f64,
+    },
+}
+
+impl TransformOperation {
+    /// Short name shown in the undo history
+    pub fn label(&self) -> &'static str {
+        match self {
+            TransformOperation::Scale { .. } => "Scale",
+            TransformOperation::Rotate { .. } => "Rotate",
+            TransformOperation::Mirror { .. } => "Mirror",
+            TransformOperation::Translate { .. } => "Move",
+            TransformOperation::Center => "Center",
+            TransformOperation::Reverse => "Reverse",
+            TransformOperation::ReverseBlock { .. } => "Reverse block",
+            TransformOperation::SplitLongStitches { .. } => "Split long stitches",
+        }
+    }
+}
+
+/// Thresholds for transform warnings
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct TransformOptions {
+    /// Fractional enlargement above which density loss is reported (0.2 = 20%)
+    pub scale_up_warning: f64,
+    pub min_stitch_mm: f64,
+}
+
+impl Default for TransformOptions {
+    fn default() -> Self {
+        Self {
+            scale_up_warning: DEFAULT_SCALE_UP_WARNING,
+            min_stitch_mm: DEFAULT_SHORT_STITCH_MM,
+        }
+    }
+}
+
+/// Side effects of a transform the user should know about
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum TransformWarning {
+    #[error("Scaled up by {factor_x:.2}x{factor_y:.2}; fill density drops accordingly")]
+    DensityReduced { factor_x: f64, factor_y: f64 },
+    #[error("{count} stitches are now shorter than {min_length_mm}mm")]
+    StitchesTooShort { count: usize, min_length_mm: f64 },
+    #[error("Color block {block} does not exist; nothing was changed")]
+    NoSuchBlock { block: usize },
+    #[error("Split {count} records longer than {max_len_mm}mm")]
+    StitchesSplit { count: usize, max_len_mm: f64 },
+}
+
+/// A transformed pattern plus any warnings raised along the way
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct TransformResult {
+    pub pattern: Pattern,
+    pub warnings: Vec<TransformWarning>,
+}
+
+/// Apply `operation` to `pattern`, recomputing bounds, statistics and blocks
+pub fn transform(
+    mut pattern: Pattern,
+    operation: &TransformOperation,
+    options: &TransformOptions,
+) -> TransformResult {
+    let warnings = match *operation {
+        TransformOperation::Scale { factor_x, factor_y } => {
+            pattern.scale(factor_x, factor_y, options)
+        }
+        TransformOperation::Rotate { degrees, about } => {
+            pattern.rotate(degrees, about);
+            Vec::new()
+        }
+        TransformOperation::Mirror { axis } => {
+            pattern.mirror(axis);
+            Vec::new()
+        }
+        TransformOperation::Translate { dx, dy } => {
+            pattern.translate(dx, dy);
+            Vec::new()
+        }
+        TransformOperation::Center => {
+            pattern.center();
+            Vec::new()
+        }
+        TransformOperation::Reverse => {
+            pattern.reverse();
+            Vec::new()
+        }
+        TransformOperation::ReverseBlock { block } => {
+            if pattern.reverse_block(block) {
+                Vec::new()
+            } else {
+                vec![TransformWarning::NoSuchBlock { block }]
+            }
+        }
+        TransformOperation::SplitLongStitches { max_len_mm } => {
+            match pattern.split_long_stitches(max_len_mm) {
+                0 => Vec::new(),
+                count => vec![TransformWarning::StitchesSplit { count, max_len_mm }],
+            }
+        }
+    };
+
+    TransformResult { pattern, warnings }
+}
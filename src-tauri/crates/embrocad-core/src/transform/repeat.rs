@@ -0,0 +1,224 @@
+// repeat.rs - Tile a design in a grid of copies across a larger hoop
+
+use crate::dst::{Pattern, Stitch, StitchCommand, UNITS_PER_MM};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Error type for repeat layouts
+#[derive(Debug, thiserror::Error)]
+pub enum RepeatError {
+    #[error("Layout needs at least one row and one column")]
+    EmptyLayout,
+    #[error("Design has no stitches to repeat")]
+    EmptyPattern,
+    #[error("Repeated design is {width_mm:.1}x{height_mm:.1}mm and does not fit the hoop")]
+    ExceedsHoop { width_mm: f64, height_mm: f64 },
+}
+
+/// Grid of copies; spacing is the gap between neighbouring copies' bounds
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RepeatLayout {
+    pub rows: usize,
+    pub cols: usize,
+    pub spacing_x_mm: f64,
+    pub spacing_y_mm: f64,
+    /// Shift every other row by half a column, brick style
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub stagger: bool,
+    /// Sew color 1 on every copy, then color 2, instead of copy by copy
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub color_sort: bool,
+    /// Refuse layouts larger than this (width, height) in mm
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_size_mm: Option<[f64; 2]>,
+}
+
+impl Pattern {
+    /// Stitches between color changes, without the ColorChange and End records
+    fn color_segments(&self) -> Vec<Vec<Stitch>> {
+        let mut segments = vec![Vec::new()];
+        for stitch in &self.stitches {
+            match stitch.command {
+                StitchCommand::ColorChange => segments.push(Vec::new()),
+                StitchCommand::End => break,
+                _ => segments.last_mut().unwrap().push(stitch.clone()),
+            }
+        }
+        segments
+    }
+
+    /// Tile the design in a grid, joining copies with a trim and a jump
+    pub fn repeat(&self, layout: &RepeatLayout) -> Result<Pattern, RepeatError> {
+        if layout.rows == 0 || layout.cols == 0 {
+            return Err(RepeatError::EmptyLayout);
+        }
+        let bounds = self.bounds.as_ref().ok_or(RepeatError::EmptyPattern)?;
+
+        let pitch_x = bounds.width() + layout.spacing_x_mm * UNITS_PER_MM;
+        let pitch_y = bounds.height() + layout.spacing_y_mm * UNITS_PER_MM;
+        let offsets: Vec<(f64, f64)> = (0..layout.rows)
+            .flat_map(|row| (0..layout.cols).map(move |col| (row, col)))
+            .map(|(row, col)| {
+                let stagger = if layout.stagger && row % 2 == 1 {
+                    0.5
+                } else {
+                    0.0
+                };
+                ((col as f64 + stagger) * pitch_x, row as f64 * pitch_y)
+            })
+            .collect();
+
+        let segments = self.color_segments();
+        let order: Vec<(usize, (f64, f64))> = if layout.color_sort {
+            (0..segments.len())
+                .flat_map(|segment| offsets.iter().map(move |&offset| (segment, offset)))
+                .collect()
+        } else {
+            offsets
+                .iter()
+                .flat_map(|&offset| (0..segments.len()).map(move |segment| (segment, offset)))
+                .collect()
+        };
+
+        let mut pattern = Pattern::new();
+        pattern.metadata.label = self.metadata.label.clone();
+        let mut previous_segment = None;
+        for (segment, (dx, dy)) in order {
+            let stitches = &segments[segment];
+            let Some(first) = stitches.first() else {
+                continue;
+            };
+
+            if let Some(last) = pattern.stitches.last().cloned() {
+                pattern.add_stitch(last.x, last.y, StitchCommand::Trim);
+                if previous_segment != Some(segment) {
+                    pattern.add_stitch(last.x, last.y, StitchCommand::ColorChange);
+                }
+                pattern.add_stitch(first.x + dx, first.y + dy, StitchCommand::Move);
+            }
+            if previous_segment != Some(segment) {
+                if let Some(color) = self.thread_color_for_block(segment) {
+                    pattern.metadata.thread_colors.push(color.clone());
+                }
+            }
+            for stitch in stitches {
+                pattern.add_stitch(stitch.x + dx, stitch.y + dy, stitch.command);
+            }
+            previous_segment = Some(segment);
+        }
+
+        let (x, y) = pattern.stitches.last().map_or((0.0, 0.0), |s| (s.x, s.y));
+        pattern.add_stitch(x, y, StitchCommand::End);
+        pattern.metadata.stitch_count = Some(pattern.stitches.len() as u32);
+        pattern.metadata.color_count = Some(pattern.color_changes);
+
+        // Calculate bounds, statistics and color blocks
+        pattern.calculate_bounds();
+        pattern.calculate_statistics();
+        pattern.calculate_color_blocks();
+
+        if let (Some([max_width, max_height]), Some(bounds)) = (layout.max_size_mm, &pattern.bounds)
+        {
+            let width_mm = bounds.width() / UNITS_PER_MM;
+            let height_mm = bounds.height() / UNITS_PER_MM;
+            if width_mm > max_width || height_mm > max_height {
+                return Err(RepeatError::ExceedsHoop {
+                    width_mm,
+                    height_mm,
+                });
+            }
+        }
+
+        Ok(pattern)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::ThreadColor;
+
+    /// Two 2mm-wide colors side by side
+    fn logo() -> Pattern {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(0.0, 0.0, StitchCommand::Stitch);
+        pattern.add_stitch(10.0, 10.0, StitchCommand::Stitch);
+        pattern.add_stitch(10.0, 10.0, StitchCommand::ColorChange);
+        pattern.add_stitch(20.0, 0.0, StitchCommand::Stitch);
+        pattern.add_stitch(20.0, 0.0, StitchCommand::End);
+        pattern.metadata.thread_colors =
+            vec![ThreadColor::new([255, 0, 0]), ThreadColor::new([0, 0, 255])];
+        pattern.calculate_bounds();
+        pattern.calculate_color_blocks();
+        pattern
+    }
+
+    fn layout(rows: usize, cols: usize) -> RepeatLayout {
+        RepeatLayout {
+            rows,
+            cols,
+            spacing_x_mm: 1.0,
+            spacing_y_mm: 1.0,
+            stagger: false,
+            color_sort: false,
+            max_size_mm: None,
+        }
+    }
+
+    #[test]
+    fn test_grid_copies_each_color() {
+        let tiled = logo().repeat(&layout(2, 3)).unwrap();
+        let bounds = tiled.bounds.as_ref().unwrap();
+
+        // Pitch is the 2mm design plus a 1mm gap
+        assert_eq!((bounds.max_x, bounds.max_y), (80.0, 30.0));
+        assert_eq!(tiled.color_changes, 11);
+        assert_eq!(tiled.metadata.thread_colors.len(), 12);
+        assert_eq!(tiled.statistics.trim_count, 11);
+        assert_eq!(tiled.stitches.last().unwrap().command, StitchCommand::End);
+    }
+
+    #[test]
+    fn test_color_sort_keeps_color_changes() {
+        let mut sorted = layout(2, 3);
+        sorted.color_sort = true;
+        let tiled = logo().repeat(&sorted).unwrap();
+
+        assert_eq!(tiled.color_changes, 1);
+        assert_eq!(tiled.color_blocks.len(), 2);
+        assert_eq!(tiled.metadata.thread_colors, logo().metadata.thread_colors);
+        assert_eq!(tiled.statistics.real_stitch_count, 18);
+    }
+
+    #[test]
+    fn test_stagger_shifts_odd_rows() {
+        let mut staggered = layout(2, 1);
+        staggered.stagger = true;
+        let tiled = logo().repeat(&staggered).unwrap();
+        let bounds = tiled.bounds.as_ref().unwrap();
+
+        assert_eq!((bounds.min_x, bounds.max_x), (0.0, 35.0));
+    }
+
+    #[test]
+    fn test_refuses_oversized_layouts() {
+        let mut limited = layout(2, 3);
+        limited.max_size_mm = Some([8.0, 3.0]);
+        assert!(logo().repeat(&limited).is_ok());
+
+        limited.max_size_mm = Some([7.9, 3.0]);
+        assert!(matches!(
+            logo().repeat(&limited),
+            Err(RepeatError::ExceedsHoop { .. })
+        ));
+        assert!(matches!(
+            logo().repeat(&layout(0, 3)),
+            Err(RepeatError::EmptyLayout)
+        ));
+        assert!(matches!(
+            Pattern::new().repeat(&layout(1, 1)),
+            Err(RepeatError::EmptyPattern)
+        ));
+    }
+}
@@ -0,0 +1,309 @@
+// reverse.rs - Reverse sewing order of a pattern or a single color block
+
+use crate::dst::{Pattern, Stitch, StitchCommand};
+
+/// Reverse one color block's records, given without ColorChange or End
+///
+/// The block is entered at its old exit point using the old entry command,
+/// and each segment keeps the command it had in the original direction.
+/// A trim that started a jump run moves to the run's other end, so it still
+/// comes before the jump. Applying this twice restores the input.
+fn reverse_records(records: &[Stitch]) -> Vec<Stitch> {
+    // trim_before[i] marks a Trim ahead of segment i (1-based); k + 1 is trailing
+    let mut segments: Vec<&Stitch> = Vec::new();
+    let mut trim_before = vec![false; records.len() + 2];
+    let mut leading_trim = None;
+    for record in records {
+        if record.command == StitchCommand::Trim {
+            trim_before[segments.len() + 1] = true;
+            if segments.is_empty() {
+                leading_trim = Some(record);
+            }
+        } else {
+            segments.push(record);
+        }
+    }
+
+    let k = segments.len();
+    if k == 0 {
+        return records.to_vec();
+    }
+
+    let mut reversed_trims = vec![false; k + 2];
+    reversed_trims[1] = trim_before[1];
+    reversed_trims[k + 1] = trim_before[k + 1];
+    for i in 2..=k {
+        if !trim_before[i] {
+            continue;
+        }
+        let mut run_end = i;
+        if segments[i - 1].command == StitchCommand::Move {
+            while run_end < k && segments[run_end].command == StitchCommand::Move {
+                run_end += 1;
+            }
+        }
+        reversed_trims[k + 2 - run_end] = true;
+    }
+
+    // Reversed segment 1 enters at the old exit; segment m retraces old segment k + 2 - m
+    let mut reversed: Vec<Stitch> = Vec::with_capacity(records.len() + 1);
+    for (m, &trim) in reversed_trims.iter().enumerate().take(k + 1).skip(1) {
+        let (position, command) = if m == 1 {
+            (segments[k - 1], segments[0].command)
+        } else {
+            let original = k + 2 - m;
+            (segments[original - 2], segments[original - 1].command)
+        };
+
+        if trim {
+            let at = reversed.last().or(leading_trim).unwrap_or(position);
+            reversed.push(Stitch::new(at.x, at.y, StitchCommand::Trim));
+        }
+        reversed.push(Stitch::new(position.x, position.y, command));
+    }
+    if reversed_trims[k + 1] {
+        let last = reversed.last().cloned().unwrap();
+        reversed.push(Stitch::new(last.x, last.y, StitchCommand::Trim));
+    }
+    reversed
+}
+
+impl Pattern {
+    /// Records of each color block, split at ColorChange and stopping at End
+    pub fn block_records(&self) -> Vec<Vec<Stitch>> {
+        let mut blocks = vec![Vec::new()];
+        for stitch in &self.stitches {
+            match stitch.command {
+                StitchCommand::ColorChange => blocks.push(Vec::new()),
+                StitchCommand::End => break,
+                _ => blocks.last_mut().unwrap().push(stitch.clone()),
+            }
+        }
+        blocks
+    }
+
+    /// The ColorChange records between the color blocks, in order
+    fn seam_records(&self) -> Vec<Stitch> {
+        self.stitches
+            .iter()
+            .take_while(|s| s.command != StitchCommand::End)
+            .filter(|s| s.command == StitchCommand::ColorChange)
+            .cloned()
+            .collect()
+    }
+
+    /// Rebuild the stitch list from blocks joined by `seams`, closed with End
+    ///
+    /// A seam missing from `seams` becomes a color change at the previous
+    /// record's position.
+    fn set_block_records(&mut self, blocks: Vec<Vec<Stitch>>, seams: Vec<Stitch>) {
+        let mut stitches: Vec<Stitch> = Vec::with_capacity(self.stitches.len());
+        let mut seams = seams.into_iter();
+        for (index, block) in blocks.into_iter().enumerate() {
+            if index > 0 {
+                let seam = seams.next().unwrap_or_else(|| {
+                    let (x, y) = stitches.last().map_or((0.0, 0.0), |s| (s.x, s.y));
+                    Stitch::new(x, y, StitchCommand::ColorChange)
+                });
+                stitches.push(seam);
+            }
+            stitches.extend(block);
+        }
+        let (x, y) = stitches.last().map_or((0.0, 0.0), |s| (s.x, s.y));
+        stitches.push(Stitch::new(x, y, StitchCommand::End));
+        self.stitches = stitches;
+
+        self.calculate_bounds();
+        self.calculate_statistics();
+        self.calculate_color_blocks();
+    }
+
+    /// Sew the whole design backwards: last color first, each block reversed
+    ///
+    /// Only the colors that belong to blocks swap ends; extra palette entries
+    /// stay where they are.
+    pub fn reverse(&mut self) {
+        let blocks: Vec<_> = self
+            .block_records()
+            .iter()
+            .rev()
+            .map(|block| reverse_records(block))
+            .collect();
+        let seams = self.seam_records().into_iter().rev().collect();
+        let colors = blocks.len().min(self.metadata.thread_colors.len());
+        self.metadata.thread_colors[..colors].reverse();
+        self.set_block_records(blocks, seams);
+    }
+
+    /// Reverse one color block in place; returns false if there is no such block
+    pub fn reverse_block(&mut self, block_index: usize) -> bool {
+        let mut blocks = self.block_records();
+        let Some(block) = blocks.get_mut(block_index) else {
+            return false;
+        };
+        *block = reverse_records(block);
+        let seams = self.seam_records();
+        self.set_block_records(blocks, seams);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::{Stitch, ThreadColor};
+    use StitchCommand::*;
+
+    fn sample() -> Pattern {
+        let mut pattern = Pattern::new();
+        for (x, y, command) in [
+            (0.0, 0.0, Stitch),
+            (10.0, 0.0, Stitch),
+            (10.0, 0.0, Trim),
+            (40.0, 0.0, Move),
+            (80.0, 0.0, Move),
+            (80.0, 10.0, Stitch),
+            (80.0, 10.0, ColorChange),
+            (0.0, 50.0, Move),
+            (5.0, 50.0, Stitch),
+            (5.0, 55.0, Stitch),
+            (5.0, 55.0, Trim),
+            (5.0, 55.0, End),
+        ] {
+            pattern.add_stitch(x, y, command);
+        }
+        pattern.metadata.thread_colors =
+            vec![ThreadColor::new([1, 1, 1]), ThreadColor::new([2, 2, 2])];
+        pattern.calculate_bounds();
+        pattern.calculate_statistics();
+        pattern.calculate_color_blocks();
+        pattern
+    }
+
+    fn commands(pattern: &Pattern) -> Vec<(f64, f64, StitchCommand)> {
+        pattern
+            .stitches
+            .iter()
+            .map(|s| (s.x, s.y, s.command))
+            .collect()
+    }
+
+    #[test]
+    fn test_reverse_block_keeps_trim_before_jump() {
+        let mut pattern = sample();
+        assert!(pattern.reverse_block(0));
+
+        assert_eq!(
+            commands(&pattern)[..7],
+            [
+                (80.0, 10.0, Stitch),
+                (80.0, 0.0, Stitch),
+                (80.0, 0.0, Trim),
+                (40.0, 0.0, Move),
+                (10.0, 0.0, Move),
+                (0.0, 0.0, Stitch),
+                (80.0, 10.0, ColorChange),
+            ]
+        );
+        assert_eq!(
+            pattern.statistics.trim_count,
+            sample().statistics.trim_count
+        );
+        assert!(!pattern.reverse_block(2));
+    }
+
+    #[test]
+    fn test_reverse_whole_pattern() {
+        let mut pattern = sample();
+        pattern.reverse();
+        let reversed = commands(&pattern);
+
+        assert_eq!(reversed[0], (5.0, 55.0, Move));
+        assert_eq!(reversed.last().unwrap().2, End);
+        assert_eq!(reversed.iter().filter(|s| s.2 == End).count(), 1);
+        assert_eq!(reversed.iter().filter(|s| s.2 == ColorChange).count(), 1);
+        assert_eq!(pattern.metadata.thread_colors[0].rgb, [2, 2, 2]);
+        assert_eq!(pattern.bounds, sample().bounds);
+    }
+
+    #[test]
+    fn test_double_reverse_restores_original() {
+        let original = sample();
+
+        let mut pattern = original.clone();
+        pattern.reverse();
+        pattern.reverse();
+        assert_eq!(commands(&pattern), commands(&original));
+        assert_eq!(
+            pattern.metadata.thread_colors,
+            original.metadata.thread_colors
+        );
+
+        for block in 0..2 {
+            let mut pattern = original.clone();
+            pattern.reverse_block(block);
+            pattern.reverse_block(block);
+            assert_eq!(commands(&pattern), commands(&original));
+        }
+    }
+
+    #[test]
+    fn test_reverse_block_leaves_other_blocks_untouched() {
+        // Color changes carrying their own displacement
+        let mut original = Pattern::new();
+        for (x, y, command) in [
+            (0.0, 0.0, Stitch),
+            (10.0, 0.0, Stitch),
+            (10.0, 5.0, Stitch),
+            (12.0, 7.0, ColorChange),
+            (20.0, 0.0, Move),
+            (25.0, 0.0, Stitch),
+            (25.0, 5.0, Stitch),
+            (26.0, 6.5, ColorChange),
+            (40.0, 0.0, Move),
+            (45.0, 0.0, Stitch),
+            (45.0, 0.0, End),
+        ] {
+            original.add_stitch(x, y, command);
+        }
+        let bits = |records: &[Stitch]| -> Vec<(u64, u64, StitchCommand)> {
+            records
+                .iter()
+                .map(|s| (s.x.to_bits(), s.y.to_bits(), s.command))
+                .collect()
+        };
+
+        let mut pattern = original.clone();
+        assert!(pattern.reverse_block(1));
+        let (blocks, before) = (pattern.block_records(), original.block_records());
+        assert_eq!(bits(&blocks[0]), bits(&before[0]));
+        assert_eq!(bits(&blocks[2]), bits(&before[2]));
+        assert_eq!(
+            bits(&pattern.seam_records()),
+            bits(&original.seam_records())
+        );
+
+        let mut pattern = original.clone();
+        pattern.reverse();
+        pattern.reverse();
+        assert_eq!(bits(&pattern.stitches), bits(&original.stitches));
+    }
+
+    #[test]
+    fn test_reverse_keeps_extra_palette_entries() {
+        let mut pattern = sample();
+        pattern
+            .metadata
+            .thread_colors
+            .push(ThreadColor::new([3, 3, 3]));
+        pattern.reverse();
+
+        let rgb: Vec<_> = pattern
+            .metadata
+            .thread_colors
+            .iter()
+            .map(|c| c.rgb)
+            .collect();
+        assert_eq!(rgb, [[2, 2, 2], [1, 1, 1], [3, 3, 3]]);
+    }
+}
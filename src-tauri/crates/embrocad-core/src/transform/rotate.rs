@@ -0,0 +1,177 @@
+// rotate.rs - Rotation and mirroring of pattern coordinates
+
+use crate::dst::Pattern;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Point a rotation turns around, in 0.1mm units
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Pivot {
+    /// Centre of the pattern bounds
+    Center,
+    /// The design origin (0, 0)
+    Origin,
+    Point {
+        x: f64,
+        y: f64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum MirrorAxis {
+    /// Flip left to right
+    Horizontal,
+    /// Flip top to bottom
+    Vertical,
+}
+
+impl Pattern {
+    /// Rotate every stitch counter-clockwise on screen by `degrees`
+    ///
+    /// Quarter turns swap coordinates instead of going through sin/cos, so
+    /// repeated 90° rotations return to the exact starting positions.
+    pub fn rotate(&mut self, degrees: f64, about: Pivot) {
+        let (cx, cy) = match about {
+            Pivot::Center => self.bounds_center(),
+            Pivot::Origin => (0.0, 0.0),
+            Pivot::Point { x, y } => (x, y),
+        };
+
+        let degrees = degrees.rem_euclid(360.0);
+        let (sin, cos) = if degrees == 0.0 {
+            (0.0, 1.0)
+        } else if degrees == 90.0 {
+            (1.0, 0.0)
+        } else if degrees == 180.0 {
+            (0.0, -1.0)
+        } else if degrees == 270.0 {
+            (-1.0, 0.0)
+        } else {
+            degrees.to_radians().sin_cos()
+        };
+
+        // Y points down, so a visual counter-clockwise turn flips the usual signs
+        for stitch in &mut self.stitches {
+            let (dx, dy) = (stitch.x - cx, stitch.y - cy);
+            stitch.x = cx + dx * cos + dy * sin;
+            stitch.y = cy - dx * sin + dy * cos;
+        }
+
+        self.calculate_bounds();
+        self.calculate_color_blocks();
+    }
+
+    /// Mirror every stitch across the centre of the pattern bounds
+    pub fn mirror(&mut self, axis: MirrorAxis) {
+        let (cx, cy) = self.bounds_center();
+        for stitch in &mut self.stitches {
+            match axis {
+                MirrorAxis::Horizontal => stitch.x = 2.0 * cx - stitch.x,
+                MirrorAxis::Vertical => stitch.y = 2.0 * cy - stitch.y,
+            }
+        }
+
+        self.calculate_bounds();
+        self.calculate_color_blocks();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::StitchCommand;
+
+    fn sample() -> Pattern {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(0.0, 0.0, StitchCommand::Stitch);
+        pattern.add_stitch(30.0, 0.0, StitchCommand::Stitch);
+        pattern.add_stitch(30.0, 10.0, StitchCommand::Move);
+        pattern.add_stitch(30.0, 10.0, StitchCommand::Trim);
+        pattern.add_stitch(13.7, 10.0, StitchCommand::Stitch);
+        pattern.add_stitch(13.7, 10.0, StitchCommand::End);
+        pattern.calculate_bounds();
+        pattern
+    }
+
+    fn points(pattern: &Pattern) -> Vec<(f64, f64, StitchCommand)> {
+        pattern
+            .stitches
+            .iter()
+            .map(|s| (s.x, s.y, s.command))
+            .collect()
+    }
+
+    #[test]
+    fn test_quarter_turn_about_origin() {
+        let mut pattern = sample();
+        pattern.rotate(90.0, Pivot::Origin);
+
+        // Right becomes up on screen, down becomes right
+        assert_eq!(pattern.stitches[1].x, 0.0);
+        assert_eq!(pattern.stitches[1].y, -30.0);
+        assert_eq!(
+            (pattern.stitches[2].x, pattern.stitches[2].y),
+            (10.0, -30.0)
+        );
+    }
+
+    #[test]
+    fn test_four_rotations_round_trip() {
+        let original = sample();
+        for about in [
+            Pivot::Center,
+            Pivot::Origin,
+            Pivot::Point { x: 3.3, y: -7.1 },
+        ] {
+            let mut pattern = original.clone();
+            for _ in 0..4 {
+                pattern.rotate(90.0, about);
+            }
+            for (a, b) in points(&pattern).iter().zip(points(&original)) {
+                assert!((a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9);
+                assert_eq!(a.2, b.2);
+            }
+        }
+
+        // Arbitrary angles still come back within tolerance
+        let mut pattern = original.clone();
+        for _ in 0..8 {
+            pattern.rotate(45.0, Pivot::Center);
+        }
+        for (a, b) in points(&pattern).iter().zip(points(&original)) {
+            assert!((a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_rotate_about_center_keeps_center() {
+        let mut pattern = sample();
+        pattern.rotate(-90.0, Pivot::Center);
+        let bounds = pattern.bounds.unwrap();
+
+        assert_eq!((bounds.min_x + bounds.max_x) / 2.0, 15.0);
+        assert_eq!((bounds.min_y + bounds.max_y) / 2.0, 5.0);
+        assert_eq!(bounds.max_x - bounds.min_x, 10.0);
+    }
+
+    #[test]
+    fn test_mirror() {
+        let mut pattern = sample();
+        pattern.mirror(MirrorAxis::Horizontal);
+        assert_eq!(pattern.stitches[0].x, 30.0);
+        assert_eq!(pattern.stitches[1].x, 0.0);
+        assert_eq!(pattern.stitches[0].y, 0.0);
+
+        pattern.mirror(MirrorAxis::Vertical);
+        assert_eq!(pattern.stitches[0].y, 10.0);
+        assert_eq!(pattern.stitches[3].command, StitchCommand::Trim);
+
+        pattern.mirror(MirrorAxis::Horizontal);
+        pattern.mirror(MirrorAxis::Vertical);
+        assert_eq!(points(&pattern), points(&sample()));
+    }
+}
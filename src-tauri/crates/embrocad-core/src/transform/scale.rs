@@ -0,0 +1,116 @@
+// scale.rs - Pattern scaling with density and minimum stitch length checks
+
+use crate::dst::{Pattern, StitchCommand};
+use crate::transform::{TransformOptions, TransformWarning};
+
+/// Enlargements beyond 20% visibly open up fills, since stitch count is kept
+pub const DEFAULT_SCALE_UP_WARNING: f64 = 0.2;
+
+impl Pattern {
+    /// Multiply every coordinate by the given factors and refresh derived data
+    ///
+    /// Stitches are stretched, not regenerated, so enlarging thins out fills
+    /// and shrinking can push stitches below the minimum sewable length.
+    pub fn scale(
+        &mut self,
+        factor_x: f64,
+        factor_y: f64,
+        options: &TransformOptions,
+    ) -> Vec<TransformWarning> {
+        for stitch in &mut self.stitches {
+            stitch.x *= factor_x;
+            stitch.y *= factor_y;
+        }
+
+        let [scale_x, scale_y] = self.metadata.scale.unwrap_or([1.0, 1.0]);
+        self.metadata.scale = Some([scale_x * factor_x, scale_y * factor_y]);
+
+        self.calculate_bounds();
+        self.calculate_statistics();
+        self.calculate_color_blocks();
+
+        let mut warnings = Vec::new();
+        let limit = 1.0 + options.scale_up_warning;
+        if factor_x.abs() > limit || factor_y.abs() > limit {
+            warnings.push(TransformWarning::DensityReduced { factor_x, factor_y });
+        }
+        if factor_x.abs() < 1.0 || factor_y.abs() < 1.0 {
+            let count = (0..self.stitches.len())
+                .filter(|&i| self.stitches[i].command == StitchCommand::Stitch)
+                .filter(|&i| {
+                    self.record_length_mm(i)
+                        .is_some_and(|length| length < options.min_stitch_mm)
+                })
+                .count();
+            if count > 0 {
+                warnings.push(TransformWarning::StitchesTooShort {
+                    count,
+                    min_length_mm: options.min_stitch_mm,
+                });
+            }
+        }
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stitches 1mm, 2mm and 0.5mm long along X
+    fn sample() -> Pattern {
+        let mut pattern = Pattern::new();
+        for x in [0.0, 10.0, 30.0, 35.0] {
+            pattern.add_stitch(x, 5.0, StitchCommand::Stitch);
+        }
+        pattern.add_stitch(35.0, 5.0, StitchCommand::End);
+        pattern.calculate_bounds();
+        pattern.calculate_statistics();
+        pattern.calculate_color_blocks();
+        pattern
+    }
+
+    #[test]
+    fn test_scale_recomputes_geometry() {
+        let mut pattern = sample();
+        let warnings = pattern.scale(2.0, 0.5, &TransformOptions::default());
+        let bounds = pattern.bounds.as_ref().unwrap();
+
+        assert_eq!((bounds.max_x, bounds.max_y), (70.0, 2.5));
+        assert!((pattern.statistics.total_thread_length_mm - 7.0).abs() < 1e-9);
+        assert!((pattern.color_blocks[0].statistics.thread_length_mm - 7.0).abs() < 1e-9);
+        assert_eq!(pattern.metadata.scale, Some([2.0, 0.5]));
+        // Y shrank, but there is no Y travel to shorten
+        assert_eq!(
+            warnings,
+            vec![TransformWarning::DensityReduced {
+                factor_x: 2.0,
+                factor_y: 0.5
+            }]
+        );
+
+        pattern.scale(0.5, 2.0, &TransformOptions::default());
+        assert_eq!(pattern.metadata.scale, Some([1.0, 1.0]));
+    }
+
+    #[test]
+    fn test_scale_up_threshold() {
+        let options = TransformOptions::default();
+        assert!(sample().scale(1.2, 1.2, &options).is_empty());
+        assert!(!sample().scale(1.25, 1.0, &options).is_empty());
+    }
+
+    #[test]
+    fn test_scale_down_reports_short_stitches() {
+        let warnings = sample().scale(0.5, 0.5, &TransformOptions::default());
+
+        // The 0.5mm stitch drops to 0.25mm; the leading stitch has no predecessor
+        assert_eq!(
+            warnings,
+            vec![TransformWarning::StitchesTooShort {
+                count: 1,
+                min_length_mm: 0.3
+            }]
+        );
+    }
+}
@@ -0,0 +1,101 @@
+// split.rs - Break displacements that exceed a record limit into shorter ones
+
+use crate::dst::{Pattern, Stitch, StitchCommand, UNITS_PER_MM};
+
+impl Pattern {
+    /// Replace Stitch and Move records longer than `max_len_mm` with a chain
+    /// of equal, evenly spaced records of the same command
+    ///
+    /// Returns how many records were split. DST records max out at ±12.1mm,
+    /// so the DST writer runs this before encoding.
+    pub fn split_long_stitches(&mut self, max_len_mm: f64) -> usize {
+        if max_len_mm.is_nan() || max_len_mm <= 0.0 {
+            return 0;
+        }
+
+        let limit = max_len_mm * UNITS_PER_MM;
+        let mut split = 0;
+        let mut stitches = Vec::with_capacity(self.stitches.len());
+        let (mut x, mut y) = (0.0f64, 0.0f64);
+
+        for stitch in &self.stitches {
+            let (dx, dy) = (stitch.x - x, stitch.y - y);
+            let length = dx.hypot(dy);
+            let splittable = matches!(stitch.command, StitchCommand::Stitch | StitchCommand::Move);
+
+            if splittable && length > limit {
+                let segments = (length / limit).ceil() as usize;
+                for i in 1..segments {
+                    let t = i as f64 / segments as f64;
+                    stitches.push(Stitch::new(x + dx * t, y + dy * t, stitch.command));
+                }
+                split += 1;
+            }
+            stitches.push(stitch.clone());
+            (x, y) = (stitch.x, stitch.y);
+        }
+
+        if split > 0 {
+            self.stitches = stitches;
+            self.calculate_bounds();
+            self.calculate_statistics();
+            self.calculate_color_blocks();
+        }
+        split
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_50mm_stitch() {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(0.0, 0.0, StitchCommand::Stitch);
+        pattern.add_stitch(300.0, 400.0, StitchCommand::Stitch);
+        pattern.add_stitch(300.0, 400.0, StitchCommand::End);
+
+        assert_eq!(pattern.split_long_stitches(12.1), 1);
+
+        // ceil(50 / 12.1) = 5 segments after the first stitch
+        let segments: Vec<_> = pattern.stitches[..6]
+            .windows(2)
+            .map(|w| (w[1].x - w[0].x, w[1].y - w[0].y))
+            .collect();
+        assert_eq!(segments.len(), 5);
+        assert!(segments.iter().all(|&(dx, dy)| dx.hypot(dy) <= 121.0));
+        assert!(pattern.stitches[..6]
+            .iter()
+            .all(|s| s.command == StitchCommand::Stitch));
+        let (sum_x, sum_y) = segments
+            .iter()
+            .fold((0.0, 0.0), |(x, y), &(dx, dy)| (x + dx, y + dy));
+        assert!((sum_x - 300.0).abs() < 1e-9 && (sum_y - 400.0).abs() < 1e-9);
+        assert!((pattern.statistics.total_thread_length_mm - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_split_jumps_and_leave_short_records() {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(10.0, 0.0, StitchCommand::Stitch);
+        pattern.add_stitch(10.0, 0.0, StitchCommand::Trim);
+        pattern.add_stitch(-240.0, 0.0, StitchCommand::Move);
+        pattern.add_stitch(-240.0, 5.0, StitchCommand::Stitch);
+
+        assert_eq!(pattern.split_long_stitches(12.1), 1);
+        let commands: Vec<_> = pattern.stitches.iter().map(|s| s.command).collect();
+        assert_eq!(
+            commands,
+            vec![
+                StitchCommand::Stitch,
+                StitchCommand::Trim,
+                StitchCommand::Move,
+                StitchCommand::Move,
+                StitchCommand::Move,
+                StitchCommand::Stitch,
+            ]
+        );
+        assert_eq!(pattern.split_long_stitches(12.1), 0);
+    }
+}
@@ -0,0 +1,111 @@
+// translate.rs - Pattern translation and centering on the hoop origin
+
+use crate::dst::Pattern;
+
+impl Pattern {
+    /// Centre of the bounds, or the origin for an empty pattern
+    pub fn bounds_center(&self) -> (f64, f64) {
+        self.bounds.as_ref().map_or((0.0, 0.0), |b| {
+            ((b.min_x + b.max_x) / 2.0, (b.min_y + b.max_y) / 2.0)
+        })
+    }
+
+    /// Shift every stitch, including End, by (`dx`, `dy`) in 0.1mm units
+    ///
+    /// Header extents and the AX/AY end offset are shifted along with the
+    /// stitches when the file declared them. Header Y points up.
+    pub fn translate(&mut self, dx: f64, dy: f64) {
+        for stitch in &mut self.stitches {
+            stitch.x += dx;
+            stitch.y += dy;
+        }
+
+        let (dx, dy) = (dx.round() as i32, dy.round() as i32);
+        let metadata = &mut self.metadata;
+        let shift = |value: &mut Option<i32>, delta: i32| {
+            if let Some(value) = value {
+                *value += delta;
+            }
+        };
+        shift(&mut metadata.extent_plus_x, dx);
+        shift(&mut metadata.extent_minus_x, -dx);
+        shift(&mut metadata.extent_plus_y, -dy);
+        shift(&mut metadata.extent_minus_y, dy);
+        shift(&mut metadata.end_offset_x, dx);
+        shift(&mut metadata.end_offset_y, -dy);
+
+        self.calculate_bounds();
+        self.calculate_color_blocks();
+    }
+
+    /// Translate so the bounds midpoint sits at the origin
+    pub fn center(&mut self) {
+        let (cx, cy) = self.bounds_center();
+        self.translate(-cx, -cy);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dst::{Pattern, StitchCommand};
+
+    fn sample() -> Pattern {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(100.0, 40.0, StitchCommand::Stitch);
+        pattern.add_stitch(160.0, 40.0, StitchCommand::Stitch);
+        pattern.add_stitch(160.0, 80.0, StitchCommand::Stitch);
+        pattern.add_stitch(160.0, 80.0, StitchCommand::End);
+        pattern.metadata.extent_plus_x = Some(160);
+        pattern.metadata.extent_minus_x = Some(-100);
+        pattern.metadata.extent_plus_y = Some(-40);
+        pattern.metadata.extent_minus_y = Some(80);
+        pattern.metadata.end_offset_x = Some(160);
+        pattern.metadata.end_offset_y = Some(-80);
+        pattern.calculate_bounds();
+        pattern
+    }
+
+    #[test]
+    fn test_center_makes_bounds_symmetric() {
+        let mut pattern = sample();
+        pattern.center();
+        let bounds = pattern.bounds.as_ref().unwrap();
+
+        assert_eq!((bounds.min_x, bounds.max_x), (-30.0, 30.0));
+        assert_eq!((bounds.min_y, bounds.max_y), (-20.0, 20.0));
+        assert_eq!(pattern.bounds_center(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_translate_keeps_metadata_consistent() {
+        let mut pattern = sample();
+        pattern.center();
+        let end = pattern.stitches.last().unwrap();
+        let metadata = &pattern.metadata;
+
+        assert_eq!(
+            (end.x, end.y, end.command),
+            (30.0, 20.0, StitchCommand::End)
+        );
+        assert_eq!(metadata.end_offset_x, Some(end.x as i32));
+        assert_eq!(metadata.end_offset_y, Some(-end.y as i32));
+        assert_eq!(
+            (metadata.extent_plus_x, metadata.extent_minus_x),
+            (Some(30), Some(30))
+        );
+        assert_eq!(
+            (metadata.extent_plus_y, metadata.extent_minus_y),
+            (Some(20), Some(20))
+        );
+    }
+
+    #[test]
+    fn test_translate_without_header() {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(0.0, 0.0, StitchCommand::Stitch);
+        pattern.translate(5.0, -5.0);
+
+        assert_eq!((pattern.stitches[0].x, pattern.stitches[0].y), (5.0, -5.0));
+        assert_eq!(pattern.metadata.end_offset_x, None);
+    }
+}
@@ -0,0 +1,194 @@
+// units.rs - Length units for command inputs and outputs
+//
+// Patterns keep coordinates in native 0.1mm units. Command parameters whose
+// names end in `_mm` are millimetres; other lengths and coordinates are
+// native unless the command takes a `unit`.
+
+use crate::dst::{Bounds, Pattern, UNITS_PER_MM};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+const MM_PER_INCH: f64 = 25.4;
+
+/// A unit lengths are given or reported in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Unit {
+    /// Tenths of a millimetre, as patterns store them
+    Native,
+    #[default]
+    Mm,
+    Inch,
+}
+
+impl Unit {
+    /// How many of this unit make a millimetre
+    pub fn per_mm(self) -> f64 {
+        match self {
+            Unit::Native => UNITS_PER_MM,
+            Unit::Mm => 1.0,
+            Unit::Inch => 1.0 / MM_PER_INCH,
+        }
+    }
+
+    pub fn from_mm(self, mm: f64) -> f64 {
+        mm * self.per_mm()
+    }
+
+    pub fn to_mm(self, value: f64) -> f64 {
+        value / self.per_mm()
+    }
+
+    pub fn from_native(self, native: f64) -> f64 {
+        self.from_mm(native / UNITS_PER_MM)
+    }
+
+    pub fn to_native(self, value: f64) -> f64 {
+        self.to_mm(value) * UNITS_PER_MM
+    }
+
+    /// Native bounds expressed in this unit
+    pub fn bounds_from_native(self, bounds: &Bounds) -> Bounds {
+        Bounds {
+            min_x: self.from_native(bounds.min_x),
+            min_y: self.from_native(bounds.min_y),
+            max_x: self.from_native(bounds.max_x),
+            max_y: self.from_native(bounds.max_y),
+        }
+    }
+
+    /// Bounds in this unit expressed natively
+    pub fn bounds_to_native(self, bounds: &Bounds) -> Bounds {
+        Bounds {
+            min_x: self.to_native(bounds.min_x),
+            min_y: self.to_native(bounds.min_y),
+            max_x: self.to_native(bounds.max_x),
+            max_y: self.to_native(bounds.max_y),
+        }
+    }
+
+    /// Short label shown after a value, e.g. "in"
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Unit::Native => "0.1mm",
+            Unit::Mm => "mm",
+            Unit::Inch => "in",
+        }
+    }
+
+    /// Decimal places worth showing for a length in this unit
+    pub fn decimals(self) -> usize {
+        match self {
+            Unit::Native => 0,
+            Unit::Mm => 1,
+            Unit::Inch => 2,
+        }
+    }
+
+    /// A length in this unit as the UI shows it, e.g. "3.94 in"
+    pub fn format(self, value: f64) -> String {
+        format!("{:.*} {}", self.decimals(), value, self.symbol())
+    }
+}
+
+/// The size and stitch lengths of a design in one unit
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct DesignStats {
+    pub unit: Unit,
+    pub bounds: Option<Bounds>,
+    pub width: f64,
+    pub height: f64,
+    /// Sum of all stitch lengths, excluding jumps
+    pub thread_length: f64,
+    pub min_stitch_length: f64,
+    pub max_stitch_length: f64,
+    pub avg_stitch_length: f64,
+    /// `width` and `height` formatted for display, e.g. "3.94 x 1.97 in"
+    pub size_label: String,
+}
+
+impl DesignStats {
+    pub fn new(pattern: &Pattern, unit: Unit) -> Self {
+        let bounds = pattern.bounds.as_ref().map(|b| unit.bounds_from_native(b));
+        let (width, height) = bounds
+            .as_ref()
+            .map_or((0.0, 0.0), |b| (b.width(), b.height()));
+        let stats = &pattern.statistics;
+        Self {
+            unit,
+            bounds,
+            width,
+            height,
+            thread_length: unit.from_mm(stats.total_thread_length_mm),
+            min_stitch_length: unit.from_mm(stats.min_stitch_length_mm),
+            max_stitch_length: unit.from_mm(stats.max_stitch_length_mm),
+            avg_stitch_length: unit.from_mm(stats.avg_stitch_length_mm),
+            size_label: format!("{:.*} x {}", unit.decimals(), width, unit.format(height)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::StitchCommand;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_conversions() {
+        for unit in [Unit::Native, Unit::Mm, Unit::Inch] {
+            assert!(close(unit.to_native(unit.from_native(1234.0)), 1234.0));
+            assert!(close(unit.to_mm(unit.from_mm(56.7)), 56.7));
+        }
+        assert!(close(Unit::Inch.from_mm(25.4), 1.0));
+        assert!(close(Unit::Inch.to_native(1.0), 254.0));
+        assert!(close(Unit::Native.from_mm(12.5), 125.0));
+        assert!(close(Unit::Mm.from_native(125.0), 12.5));
+        assert_eq!(Unit::Inch.format(100.0 / 25.4), "3.94 in");
+        assert_eq!(Unit::Mm.format(12.34), "12.3 mm");
+
+        let bounds = Bounds {
+            min_x: -254.0,
+            min_y: 0.0,
+            max_x: 254.0,
+            max_y: 127.0,
+        };
+        let inches = Unit::Inch.bounds_from_native(&bounds);
+        assert!(close(inches.min_x, -1.0) && close(inches.max_y, 0.5));
+        let back = Unit::Inch.bounds_to_native(&inches);
+        assert!(close(back.min_x, bounds.min_x) && close(back.max_y, bounds.max_y));
+
+        let json = serde_json::to_string(&[Unit::Native, Unit::Mm, Unit::Inch]).unwrap();
+        assert_eq!(json, r#"["native","mm","inch"]"#);
+    }
+
+    #[test]
+    fn test_stats_in_inches() {
+        // 100mm wide, 50mm tall
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(0.0, 0.0, StitchCommand::Stitch);
+        pattern.add_stitch(1000.0, 0.0, StitchCommand::Stitch);
+        pattern.add_stitch(1000.0, 500.0, StitchCommand::Stitch);
+        pattern.calculate_bounds();
+        pattern.calculate_statistics();
+
+        let stats = DesignStats::new(&pattern, Unit::Inch);
+        assert_eq!(format!("{:.2}", stats.width), "3.94");
+        assert_eq!(format!("{:.2}", stats.height), "1.97");
+        assert_eq!(stats.size_label, "3.94 x 1.97 in");
+        let mm = DesignStats::new(&pattern, Unit::Mm);
+        assert!(close(mm.width, 100.0));
+        assert!(close(stats.thread_length, mm.thread_length / 25.4));
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["unit"], "inch");
+        assert!(close(
+            json["bounds"]["max_x"].as_f64().unwrap(),
+            100.0 / 25.4
+        ));
+    }
+}
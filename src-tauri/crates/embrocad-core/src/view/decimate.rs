@@ -0,0 +1,147 @@
+// decimate.rs - Level-of-detail stitch reduction for zoomed-out rendering
+
+use crate::dst::{Pattern, Stitch, StitchCommand, UNITS_PER_MM};
+
+/// Distance from `p` to the segment `a`-`b`
+fn segment_distance(p: &Stitch, a: &Stitch, b: &Stitch) -> f64 {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let length_sq = dx * dx + dy * dy;
+    let t = if length_sq == 0.0 {
+        0.0
+    } else {
+        (((p.x - a.x) * dx + (p.y - a.y) * dy) / length_sq).clamp(0.0, 1.0)
+    };
+    (p.x - (a.x + t * dx)).hypot(p.y - (a.y + t * dy))
+}
+
+/// Flag the points of `run` that Douglas–Peucker keeps at `tolerance`
+///
+/// Iterative so that runs of hundreds of thousands of stitches cannot
+/// overflow the stack. Distances are to the segment rather than the line, so
+/// a path that doubles back on itself keeps its turning point.
+fn douglas_peucker(run: &[Stitch], tolerance: f64) -> Vec<bool> {
+    let mut keep = vec![false; run.len()];
+    let Some(last) = run.len().checked_sub(1) else {
+        return keep;
+    };
+    keep[0] = true;
+    keep[last] = true;
+
+    let mut pending = vec![(0, last)];
+    while let Some((first, last)) = pending.pop() {
+        let farthest = (first + 1..last)
+            .map(|i| (i, segment_distance(&run[i], &run[first], &run[last])))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((index, distance)) = farthest {
+            if distance > tolerance {
+                keep[index] = true;
+                pending.push((first, index));
+                pending.push((index, last));
+            }
+        }
+    }
+    keep
+}
+
+impl Pattern {
+    /// A reduced stitch list for drawing the design at overview zoom levels
+    ///
+    /// Each run of consecutive Stitch records is simplified with
+    /// Douglas–Peucker so no dropped point lies further than `tolerance_mm`
+    /// from the drawn path. Every other record is kept, so jumps, trims, color
+    /// changes and therefore block boundaries are unchanged. The pattern itself
+    /// is not modified.
+    pub fn decimate(&self, tolerance_mm: f64) -> Vec<Stitch> {
+        let tolerance = tolerance_mm.max(0.0) * UNITS_PER_MM;
+        let mut reduced = Vec::with_capacity(self.stitches.len());
+        let mut start = 0;
+
+        while start < self.stitches.len() {
+            let run = self.stitches[start..]
+                .iter()
+                .take_while(|s| s.command == StitchCommand::Stitch)
+                .count();
+            if run == 0 {
+                reduced.push(self.stitches[start].clone());
+                start += 1;
+                continue;
+            }
+
+            let stitches = &self.stitches[start..start + run];
+            let keep = douglas_peucker(stitches, tolerance);
+            reduced.extend(
+                stitches
+                    .iter()
+                    .zip(keep)
+                    .filter(|(_, kept)| *kept)
+                    .map(|(stitch, _)| stitch.clone()),
+            );
+            start += run;
+        }
+
+        reduced
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StitchCommand::*;
+
+    #[test]
+    fn test_straight_line_keeps_endpoints() {
+        let mut pattern = Pattern::new();
+        for i in 0..1000 {
+            pattern.add_stitch(i as f64 * 2.0, i as f64, Stitch);
+        }
+
+        let reduced = pattern.decimate(0.1);
+        assert_eq!(reduced.len(), 2);
+        assert_eq!(reduced[0], pattern.stitches[0]);
+        assert_eq!(reduced[1], pattern.stitches[999]);
+        assert_eq!(pattern.stitches.len(), 1000);
+    }
+
+    #[test]
+    fn test_zigzag_within_tolerance_collapses() {
+        // 0.3mm zigzag along X
+        let mut pattern = Pattern::new();
+        for i in 0..101 {
+            pattern.add_stitch(i as f64 * 10.0, (i % 2) as f64 * 3.0, Stitch);
+        }
+
+        assert_eq!(pattern.decimate(0.5).len(), 2);
+        assert_eq!(pattern.decimate(0.1).len(), 101);
+    }
+
+    #[test]
+    fn test_commands_and_blocks_are_kept() {
+        let mut pattern = Pattern::new();
+        for i in 0..10 {
+            pattern.add_stitch(i as f64, 0.0, Stitch);
+        }
+        pattern.add_stitch(9.0, 0.0, Trim);
+        pattern.add_stitch(50.0, 50.0, Move);
+        pattern.add_stitch(50.0, 50.0, ColorChange);
+        for i in 0..10 {
+            pattern.add_stitch(50.0, 50.0 + i as f64, Stitch);
+        }
+        pattern.add_stitch(50.0, 59.0, End);
+
+        let commands: Vec<_> = pattern.decimate(1.0).iter().map(|s| s.command).collect();
+        assert_eq!(
+            commands,
+            vec![Stitch, Stitch, Trim, Move, ColorChange, Stitch, Stitch, End]
+        );
+    }
+
+    #[test]
+    fn test_doubling_back_keeps_turn() {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(0.0, 0.0, Stitch);
+        pattern.add_stitch(100.0, 0.0, Stitch);
+        pattern.add_stitch(50.0, 0.0, Stitch);
+
+        assert_eq!(pattern.decimate(0.5).len(), 3);
+    }
+}
@@ -0,0 +1,257 @@
+// hit_test.rs - Uniform grid over stitch positions for canvas picking
+
+use crate::dst::Pattern;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// Average number of stitches the grid aims to put in each cell
+const TARGET_PER_CELL: f64 = 4.0;
+
+/// Upper bound on cells along either axis, so sparse designs stay small
+const MAX_CELLS_PER_AXIS: usize = 2048;
+
+/// A stitch found by a hit test
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct StitchHit {
+    /// Index into `Pattern::stitches`
+    pub index: usize,
+    /// Index of the color block containing the stitch
+    pub block: usize,
+}
+
+/// Stitch indices bucketed by grid cell, in 0.1mm pattern coordinates
+///
+/// Cells are stored compactly: the entries of cell `c` are
+/// `entries[cell_starts[c]..cell_starts[c + 1]]`, in ascending stitch order.
+#[derive(Debug, Clone)]
+pub struct StitchIndex {
+    origin: (f64, f64),
+    cell: f64,
+    cols: usize,
+    rows: usize,
+    cell_starts: Vec<usize>,
+    entries: Vec<usize>,
+    points: Vec<(f64, f64)>,
+    block_starts: Vec<usize>,
+}
+
+impl StitchIndex {
+    /// Index every record of `pattern`
+    pub fn new(pattern: &Pattern) -> Self {
+        let points: Vec<(f64, f64)> = pattern.stitches.iter().map(|s| (s.x, s.y)).collect();
+        let (mut min_x, mut min_y) = (f64::INFINITY, f64::INFINITY);
+        let (mut max_x, mut max_y) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for &(x, y) in &points {
+            (min_x, min_y) = (min_x.min(x), min_y.min(y));
+            (max_x, max_y) = (max_x.max(x), max_y.max(y));
+        }
+        if points.is_empty() {
+            (min_x, min_y, max_x, max_y) = (0.0, 0.0, 0.0, 0.0);
+        }
+
+        let (width, height) = (max_x - min_x, max_y - min_y);
+        let cells = (points.len() as f64 / TARGET_PER_CELL).max(1.0);
+        let cell = (width * height / cells)
+            .sqrt()
+            .max(width.max(height) / MAX_CELLS_PER_AXIS as f64)
+            .max(1.0);
+        let cols = (width / cell) as usize + 1;
+        let rows = (height / cell) as usize + 1;
+
+        let mut index = Self {
+            origin: (min_x, min_y),
+            cell,
+            cols,
+            rows,
+            cell_starts: vec![0; cols * rows + 1],
+            entries: vec![0; points.len()],
+            points,
+            block_starts: pattern.color_blocks().iter().map(|b| b.start).collect(),
+        };
+
+        // Counting sort of stitch indices into cells
+        let cells: Vec<usize> = index
+            .points
+            .iter()
+            .map(|&(x, y)| {
+                let (col, row) = index.cell_of(x, y);
+                row * cols + col
+            })
+            .collect();
+        for &cell in &cells {
+            index.cell_starts[cell + 1] += 1;
+        }
+        let mut total = 0;
+        for start in index.cell_starts.iter_mut() {
+            total += *start;
+            *start = total;
+        }
+        let mut next = index.cell_starts.clone();
+        for (stitch, &cell) in cells.iter().enumerate() {
+            index.entries[next[cell]] = stitch;
+            next[cell] += 1;
+        }
+
+        index
+    }
+
+    /// Grid cell containing (x, y), clamped to the grid
+    fn cell_of(&self, x: f64, y: f64) -> (usize, usize) {
+        let clamp = |value: f64, count: usize| (value.max(0.0) as usize).min(count - 1);
+        (
+            clamp(((x - self.origin.0) / self.cell).floor(), self.cols),
+            clamp(((y - self.origin.1) / self.cell).floor(), self.rows),
+        )
+    }
+
+    /// Stitch indices in every cell overlapping the rectangle
+    fn candidates(
+        &self,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+    ) -> impl Iterator<Item = usize> + '_ {
+        let (col_start, row_start) = self.cell_of(min_x, min_y);
+        let (col_end, row_end) = self.cell_of(max_x, max_y);
+        (row_start..=row_end).flat_map(move |row| {
+            let first = self.cell_starts[row * self.cols + col_start];
+            let last = self.cell_starts[row * self.cols + col_end + 1];
+            self.entries[first..last].iter().copied()
+        })
+    }
+
+    fn hit(&self, index: usize) -> StitchHit {
+        let block = self
+            .block_starts
+            .partition_point(|&start| start <= index)
+            .saturating_sub(1);
+        StitchHit { index, block }
+    }
+
+    /// The stitch closest to (x, y) within `max_dist`, preferring the one
+    /// sewn last when several are equally close, as it is drawn on top
+    pub fn nearest(&self, x: f64, y: f64, max_dist: f64) -> Option<StitchHit> {
+        if self.points.is_empty() || max_dist.is_nan() || max_dist < 0.0 {
+            return None;
+        }
+
+        let mut best: Option<(usize, f64)> = None;
+        for index in self.candidates(x - max_dist, y - max_dist, x + max_dist, y + max_dist) {
+            let (px, py) = self.points[index];
+            let distance = (px - x).hypot(py - y);
+            let closer = best.is_none_or(|(best_index, best_distance)| {
+                distance < best_distance || (distance == best_distance && index > best_index)
+            });
+            if distance <= max_dist && closer {
+                best = Some((index, distance));
+            }
+        }
+
+        best.map(|(index, _)| self.hit(index))
+    }
+
+    /// Every stitch inside the rectangle, edges included, in sewing order
+    pub fn in_rect(&self, min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Vec<StitchHit> {
+        if self.points.is_empty() || min_x > max_x || min_y > max_y {
+            return Vec::new();
+        }
+
+        let mut indices: Vec<usize> = self
+            .candidates(min_x, min_y, max_x, max_y)
+            .filter(|&index| {
+                let (x, y) = self.points[index];
+                (min_x..=max_x).contains(&x) && (min_y..=max_y).contains(&y)
+            })
+            .collect();
+        indices.sort_unstable();
+        indices.into_iter().map(|index| self.hit(index)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::StitchCommand;
+
+    /// A 100 x 100 grid of stitches 10 units apart, row by row, with a color
+    /// change at the start of row 50
+    fn grid_pattern() -> Pattern {
+        let mut pattern = Pattern::new();
+        for row in 0..100 {
+            for col in 0..100 {
+                let command = if row == 50 && col == 0 {
+                    StitchCommand::ColorChange
+                } else {
+                    StitchCommand::Stitch
+                };
+                pattern.add_stitch(col as f64 * 10.0, row as f64 * 10.0, command);
+            }
+        }
+        pattern
+    }
+
+    #[test]
+    fn test_nearest_stitch() {
+        let index = StitchIndex::new(&grid_pattern());
+
+        assert_eq!(
+            index.nearest(123.0, 456.0, 10.0),
+            Some(StitchHit {
+                index: 46 * 100 + 12,
+                block: 0
+            })
+        );
+        assert_eq!(
+            index.nearest(990.0, 990.0, 1.0),
+            Some(StitchHit {
+                index: 9999,
+                block: 1
+            })
+        );
+        // Out of reach, and far outside the grid
+        assert_eq!(index.nearest(125.0, 455.0, 4.0), None);
+        assert_eq!(index.nearest(-500.0, -500.0, 50.0), None);
+    }
+
+    #[test]
+    fn test_nearest_prefers_stitch_sewn_last() {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(0.0, 0.0, StitchCommand::Stitch);
+        pattern.add_stitch(40.0, 0.0, StitchCommand::Stitch);
+        pattern.add_stitch(0.0, 0.0, StitchCommand::Stitch);
+
+        let index = StitchIndex::new(&pattern);
+        assert_eq!(index.nearest(1.0, 0.0, 5.0).unwrap().index, 2);
+    }
+
+    #[test]
+    fn test_stitches_in_rect() {
+        let index = StitchIndex::new(&grid_pattern());
+
+        let hits = index.in_rect(485.0, 490.0, 510.0, 500.0);
+        let expected: Vec<(usize, usize)> = vec![
+            (4949, 0),
+            (4950, 0),
+            (4951, 0),
+            (5049, 1),
+            (5050, 1),
+            (5051, 1),
+        ];
+        assert_eq!(
+            hits.iter().map(|h| (h.index, h.block)).collect::<Vec<_>>(),
+            expected
+        );
+
+        assert_eq!(index.in_rect(-100.0, -100.0, 2000.0, 2000.0).len(), 10_000);
+        assert!(index.in_rect(2000.0, 0.0, 3000.0, 100.0).is_empty());
+    }
+
+    #[test]
+    fn test_empty_pattern() {
+        let index = StitchIndex::new(&Pattern::new());
+        assert_eq!(index.nearest(0.0, 0.0, 100.0), None);
+        assert!(index.in_rect(-1.0, -1.0, 1.0, 1.0).is_empty());
+    }
+}
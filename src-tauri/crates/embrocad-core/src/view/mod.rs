@@ -0,0 +1,6 @@
+// mod.rs - View module exports for viewport-only geometry
+
+mod decimate;
+mod hit_test;
+
+pub use hit_test::{StitchHit, StitchIndex};
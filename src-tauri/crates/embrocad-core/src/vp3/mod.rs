@@ -0,0 +1,5 @@
+// mod.rs - VP3 module exports for the Husqvarna/Pfaff format parser
+
+mod parser;
+
+pub use parser::parse_vp3;
@@ -0,0 +1,295 @@
+// parser.rs - Husqvarna/Pfaff VP3 embroidery file format parser
+
+use crate::binary::ByteReader;
+use crate::dst::{Pattern, StitchCommand, ThreadColor};
+
+/// File signature at the start of every VP3 file
+const SIGNATURE: &[u8] = b"%vsm%\0";
+
+/// VP3 absolute positions are in micrometers; patterns use 0.1mm units
+const POSITION_DIVISOR: f64 = 100.0;
+
+/// Shortest printable run in trailing data that is kept as a note
+const MIN_NOTE_LENGTH: usize = 4;
+
+/// Error type for VP3 parsing
+#[derive(Debug, thiserror::Error)]
+pub enum Vp3Error {
+    #[error("Invalid VP3 file: missing %vsm% signature")]
+    InvalidSignature,
+    #[error("Invalid VP3 file: truncated at byte {0}")]
+    Truncated(usize),
+}
+
+/// Read a string prefixed by its 16-bit big-endian length
+fn read_string(reader: &mut ByteReader) -> Option<String> {
+    let length = reader.u16_be()? as usize;
+    let bytes = reader.bytes(length)?;
+    Some(String::from_utf8_lossy(bytes).trim().to_string())
+}
+
+/// Read a thread definition; the last listed color is used for display
+fn read_thread(reader: &mut ByteReader) -> Option<ThreadColor> {
+    let color_count = reader.u8()?;
+    let _transition = reader.u8()?;
+
+    let mut rgb = [0u8; 3];
+    for _ in 0..color_count {
+        rgb.copy_from_slice(reader.bytes(3)?);
+        let _parts = reader.u8()?;
+        let _color_length = reader.u16_be()?;
+    }
+
+    let _thread_type = reader.u8()?;
+    let _weight = reader.u8()?;
+    let catalog = read_string(reader)?;
+    let description = read_string(reader)?;
+    let brand = read_string(reader)?;
+
+    let description = match (description.is_empty(), brand.is_empty()) {
+        (false, false) => Some(format!("{} {}", brand, description)),
+        (false, true) => Some(description),
+        (true, false) => Some(brand),
+        (true, true) => None,
+    };
+
+    Some(ThreadColor {
+        rgb,
+        description,
+        catalog_number: (!catalog.is_empty()).then_some(catalog),
+        needle: None,
+        basting: false,
+    })
+}
+
+/// Decode the stitch records of a single color block
+///
+/// Short records are two signed bytes. 0x80 escapes select a long jump with
+/// 16-bit big-endian deltas (0x01, terminated by 0x80 0x02) or a trim at the
+/// end of a color section (0x03).
+fn parse_block_stitches(data: &[u8], pattern: &mut Pattern, x: &mut f64, y: &mut f64) {
+    let mut reader = ByteReader::new(data);
+
+    while let Some(record) = reader.bytes(2) {
+        if record[0] != 0x80 {
+            *x += record[0] as i8 as f64;
+            *y += record[1] as i8 as f64;
+            pattern.add_stitch(*x, *y, StitchCommand::Stitch);
+            continue;
+        }
+
+        match record[1] {
+            0x01 => {
+                let (Some(dx), Some(dy)) = (reader.i16_be(), reader.i16_be()) else {
+                    break;
+                };
+                *x += dx as f64;
+                *y += dy as f64;
+                pattern.add_stitch(*x, *y, StitchCommand::Move);
+            }
+            0x03 => pattern.add_stitch(*x, *y, StitchCommand::Trim),
+            // 0x02 closes a long jump; nothing else is defined
+            _ => {}
+        }
+    }
+}
+
+/// Collect printable ASCII runs from trailing data as notes
+fn extract_notes(data: &[u8]) -> Vec<String> {
+    data.split(|b| !(b.is_ascii_graphic() || *b == b' '))
+        .map(|run| String::from_utf8_lossy(run).trim().to_string())
+        .filter(|run| run.len() >= MIN_NOTE_LENGTH)
+        .collect()
+}
+
+fn parse_body(reader: &mut ByteReader, pattern: &mut Pattern) -> Option<()> {
+    reader.skip(SIGNATURE.len())?;
+
+    let _producer = read_string(reader)?;
+    reader.skip(7)?;
+    let comment = read_string(reader)?;
+    if !comment.is_empty() {
+        pattern.metadata.notes.push(comment);
+    }
+
+    reader.skip(32)?;
+    let center_x = reader.i32_be()? as f64 / POSITION_DIVISOR;
+    let center_y = reader.i32_be()? as f64 / POSITION_DIVISOR;
+    reader.skip(27)?;
+    let _ = read_string(reader)?;
+    reader.skip(24)?;
+    let _ = read_string(reader)?;
+
+    let color_count = reader.u16_be()?;
+    let (mut x, mut y) = (0.0f64, 0.0f64);
+
+    for i in 0..color_count {
+        reader.skip(3)?;
+        let block_length = reader.u32_be()? as usize;
+        let block_end = reader.position().checked_add(block_length)?;
+
+        // Block start is absolute and Y-up; stitch deltas are already Y-down
+        let start_x = reader.i32_be()? as f64 / POSITION_DIVISOR + center_x;
+        let start_y = -(reader.i32_be()? as f64 / POSITION_DIVISOR + center_y);
+        if i > 0 {
+            pattern.add_stitch(x, y, StitchCommand::ColorChange);
+        }
+        x = start_x;
+        y = start_y;
+        pattern.add_stitch(x, y, StitchCommand::Move);
+
+        let thread = read_thread(reader)?;
+        pattern.metadata.thread_colors.push(thread);
+
+        reader.skip(15)?;
+        reader.skip(3)?;
+
+        let stitch_length = block_end.checked_sub(reader.position())?;
+        let stitches = reader.bytes(stitch_length)?;
+        parse_block_stitches(stitches, pattern, &mut x, &mut y);
+    }
+
+    pattern.add_stitch(x, y, StitchCommand::End);
+    pattern.metadata.notes.extend(extract_notes(reader.rest()));
+
+    Some(())
+}
+
+/// Parse a VP3 file from bytes
+pub fn parse_vp3(data: &[u8]) -> Result<Pattern, Vp3Error> {
+    if !data.starts_with(SIGNATURE) {
+        return Err(Vp3Error::InvalidSignature);
+    }
+
+    let mut pattern = Pattern::new();
+    let mut reader = ByteReader::new(data);
+
+    parse_body(&mut reader, &mut pattern).ok_or(Vp3Error::Truncated(reader.position()))?;
+
+    pattern.metadata.color_count = Some(pattern.color_changes);
+
+    // Calculate bounds, statistics and color blocks
+    pattern.calculate_bounds();
+    pattern.calculate_statistics();
+    pattern.calculate_color_blocks();
+
+    Ok(pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_string(out: &mut Vec<u8>, value: &str) {
+        out.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        out.extend_from_slice(value.as_bytes());
+    }
+
+    fn build_block(start: (i32, i32), rgb: [u8; 3], name: &str, stitches: &[u8]) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&start.0.to_be_bytes());
+        body.extend_from_slice(&start.1.to_be_bytes());
+
+        // Thread: one color, solid transition, type/weight, catalog/description/brand
+        body.extend_from_slice(&[1, 0]);
+        body.extend_from_slice(&rgb);
+        body.extend_from_slice(&[0, 0, 0]);
+        body.extend_from_slice(&[5, 40]);
+        push_string(&mut body, "1800");
+        push_string(&mut body, name);
+        push_string(&mut body, "Robison-Anton");
+
+        body.extend_from_slice(&[0; 15]);
+        body.extend_from_slice(&[0x0A, 0xF6, 0x00]);
+        body.extend_from_slice(stitches);
+
+        let mut block = vec![0x00, 0x05, 0x00];
+        block.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        block.extend_from_slice(&body);
+        block
+    }
+
+    fn build_vp3(blocks: &[Vec<u8>], trailing: &[u8]) -> Vec<u8> {
+        let mut data = SIGNATURE.to_vec();
+        push_string(&mut data, "Produced by Test");
+        data.extend_from_slice(&[0; 7]);
+        push_string(&mut data, "Customer logo");
+        data.extend_from_slice(&[0; 32]);
+        data.extend_from_slice(&0i32.to_be_bytes());
+        data.extend_from_slice(&0i32.to_be_bytes());
+        data.extend_from_slice(&[0; 27]);
+        push_string(&mut data, "");
+        data.extend_from_slice(&[0; 24]);
+        push_string(&mut data, "");
+        data.extend_from_slice(&(blocks.len() as u16).to_be_bytes());
+        for block in blocks {
+            data.extend_from_slice(block);
+        }
+        data.extend_from_slice(trailing);
+        data
+    }
+
+    #[test]
+    fn test_two_color_design() {
+        use StitchCommand::*;
+        let first = build_block(
+            (1000, -2000),
+            [0xFF, 0x00, 0x00],
+            "Red",
+            &[0x0A, 0x00, 0x00, 0xF6, 0x80, 0x03],
+        );
+        let second = build_block(
+            (-5000, 0),
+            [0x00, 0x00, 0xFF],
+            "Blue",
+            &[0x80, 0x01, 0x01, 0x2C, 0xFF, 0x38, 0x80, 0x02, 0x01, 0x01],
+        );
+        let data = build_vp3(&[first, second], b"\x00\x01Settings: hoop 130x180\x00");
+        let pattern = parse_vp3(&data).unwrap();
+
+        let decoded: Vec<_> = pattern
+            .stitches
+            .iter()
+            .map(|s| (s.x, s.y, s.command))
+            .collect();
+        assert_eq!(
+            decoded,
+            vec![
+                (10.0, 20.0, Move),
+                (20.0, 20.0, Stitch),
+                (20.0, 10.0, Stitch),
+                (20.0, 10.0, Trim),
+                (20.0, 10.0, ColorChange),
+                (-50.0, 0.0, Move),
+                (250.0, -200.0, Move),
+                (251.0, -199.0, Stitch),
+                (251.0, -199.0, End),
+            ]
+        );
+
+        assert_eq!(pattern.color_blocks.len(), 2);
+        let colors = &pattern.metadata.thread_colors;
+        assert_eq!(colors[0].rgb, [0xFF, 0, 0]);
+        assert_eq!(colors[1].description.as_deref(), Some("Robison-Anton Blue"));
+        assert_eq!(colors[1].catalog_number.as_deref(), Some("1800"));
+        assert_eq!(
+            pattern.metadata.notes,
+            vec![
+                "Customer logo".to_string(),
+                "Settings: hoop 130x180".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert!(matches!(
+            parse_vp3(b"PK\x03\x04"),
+            Err(Vp3Error::InvalidSignature)
+        ));
+        assert!(matches!(
+            parse_vp3(b"%vsm%\0\x00"),
+            Err(Vp3Error::Truncated(_))
+        ));
+    }
+}
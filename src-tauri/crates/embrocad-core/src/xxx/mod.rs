@@ -0,0 +1,5 @@
+// mod.rs - XXX module exports for the Singer/Compucon format parser
+
+mod parser;
+
+pub use parser::parse_xxx;
@@ -0,0 +1,198 @@
+// parser.rs - Singer/Compucon XXX embroidery file format parser
+
+use crate::binary::ByteReader;
+use crate::dst::{Pattern, StitchCommand, ThreadColor};
+
+/// Header offsets
+const STITCH_COUNT_OFFSET: usize = 0x17;
+const COLOR_COUNT_OFFSET: usize = 0x27;
+const PALETTE_OFFSET_OFFSET: usize = 0xFC;
+const STITCH_DATA_OFFSET: usize = 0x100;
+
+/// Record prefixes
+const LONG_MOVE_A: u8 = 0x7D;
+const LONG_MOVE_B: u8 = 0x7E;
+const COMMAND: u8 = 0x7F;
+
+/// Error type for XXX parsing
+#[derive(Debug, thiserror::Error)]
+pub enum XxxError {
+    #[error("Invalid XXX file: insufficient data")]
+    InsufficientData,
+}
+
+/// Decode the byte-pair stitch stream
+///
+/// Plain records are signed (dx, dy) bytes. 0x7D and 0x7E introduce a long
+/// move with 16-bit little-endian deltas, and 0x7F introduces a command byte
+/// followed by a short displacement: 0x01 move, 0x03 trim, 0x08 or 0x0A-0x17
+/// color change, 0x18 or 0x7F end.
+fn parse_stitches(data: &[u8], pattern: &mut Pattern) {
+    let mut reader = ByteReader::new(data);
+
+    let mut current_x = 0.0f64;
+    let mut current_y = 0.0f64;
+
+    while let Some(b1) = reader.u8() {
+        match b1 {
+            LONG_MOVE_A | LONG_MOVE_B => {
+                let (Some(dx), Some(dy)) = (reader.i16_le(), reader.i16_le()) else {
+                    break;
+                };
+                current_x += dx as f64;
+                current_y -= dy as f64;
+                pattern.add_stitch(current_x, current_y, StitchCommand::Move);
+            }
+            COMMAND => {
+                let (Some(command), Some(dx), Some(dy)) = (reader.u8(), reader.i8(), reader.i8())
+                else {
+                    break;
+                };
+                match command {
+                    0x01 => {
+                        current_x += dx as f64;
+                        current_y -= dy as f64;
+                        pattern.add_stitch(current_x, current_y, StitchCommand::Move);
+                    }
+                    0x03 => pattern.add_stitch(current_x, current_y, StitchCommand::Trim),
+                    0x08 | 0x0A..=0x17 => {
+                        pattern.add_stitch(current_x, current_y, StitchCommand::ColorChange)
+                    }
+                    0x18 | 0x7F => break,
+                    _ => {}
+                }
+            }
+            _ => {
+                let Some(b2) = reader.i8() else { break };
+                current_x += b1 as i8 as f64;
+                current_y -= b2 as f64;
+                pattern.add_stitch(current_x, current_y, StitchCommand::Stitch);
+            }
+        }
+    }
+
+    pattern.add_stitch(current_x, current_y, StitchCommand::End);
+}
+
+/// Read the trailing color table: two marker bytes, then (0, r, g, b) per color
+fn parse_palette(data: &[u8], color_count: usize) -> Vec<ThreadColor> {
+    let mut reader = ByteReader::new(data);
+    if reader.skip(2).is_none() {
+        return Vec::new();
+    }
+
+    let mut colors = Vec::with_capacity(color_count);
+    for _ in 0..color_count {
+        let Some(entry) = reader.bytes(4) else { break };
+        colors.push(ThreadColor::new([entry[1], entry[2], entry[3]]));
+    }
+    colors
+}
+
+/// Parse a XXX file from bytes
+pub fn parse_xxx(data: &[u8]) -> Result<Pattern, XxxError> {
+    if data.len() < STITCH_DATA_OFFSET {
+        return Err(XxxError::InsufficientData);
+    }
+
+    let mut pattern = Pattern::new();
+    let mut header = ByteReader::new(data);
+
+    header.seek(STITCH_COUNT_OFFSET);
+    let stitch_count = header.u32_le().ok_or(XxxError::InsufficientData)?;
+    header.seek(COLOR_COUNT_OFFSET);
+    let color_count = header.u16_le().ok_or(XxxError::InsufficientData)? as usize;
+    header.seek(PALETTE_OFFSET_OFFSET);
+    let palette_offset = header.u32_le().ok_or(XxxError::InsufficientData)? as usize;
+
+    pattern.metadata.stitch_count = Some(stitch_count);
+    pattern.metadata.color_count = Some(color_count.saturating_sub(1) as u32);
+
+    // Stitches run up to the color table, or to the end if the offset is bogus
+    let stitch_end = if (STITCH_DATA_OFFSET..=data.len()).contains(&palette_offset) {
+        palette_offset
+    } else {
+        data.len()
+    };
+    parse_stitches(&data[STITCH_DATA_OFFSET..stitch_end], &mut pattern);
+
+    if stitch_end < data.len() {
+        pattern.metadata.thread_colors = parse_palette(&data[stitch_end..], color_count);
+    }
+
+    // Calculate bounds, statistics and color blocks
+    pattern.calculate_bounds();
+    pattern.calculate_statistics();
+    pattern.calculate_color_blocks();
+
+    Ok(pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_xxx(stitch_count: u32, colors: &[[u8; 3]], stitches: &[u8]) -> Vec<u8> {
+        let mut data = vec![0u8; STITCH_DATA_OFFSET];
+        data[STITCH_COUNT_OFFSET..STITCH_COUNT_OFFSET + 4]
+            .copy_from_slice(&stitch_count.to_le_bytes());
+        data[COLOR_COUNT_OFFSET..COLOR_COUNT_OFFSET + 2]
+            .copy_from_slice(&(colors.len() as u16).to_le_bytes());
+
+        let palette_offset = (STITCH_DATA_OFFSET + stitches.len()) as u32;
+        data[PALETTE_OFFSET_OFFSET..PALETTE_OFFSET_OFFSET + 4]
+            .copy_from_slice(&palette_offset.to_le_bytes());
+
+        data.extend_from_slice(stitches);
+        data.extend_from_slice(&[0x00, 0x00]);
+        for rgb in colors {
+            data.push(0);
+            data.extend_from_slice(rgb);
+        }
+        data
+    }
+
+    #[test]
+    fn test_known_design() {
+        let stitches = [
+            0x0A, 0x0A, // stitch (+10, -10)
+            0x14, 0xF6, // stitch (+20, +10)
+            0x7F, 0x03, 0x00, 0x00, // trim
+            0x7E, 0x2C, 0x01, 0x38, 0xFF, // long move (+300, +200)
+            0x7F, 0x08, 0x00, 0x00, // color change
+            0x05, 0x00, // stitch (+5, 0)
+            0x7F, 0x01, 0xF6, 0x00, // move (-10, 0)
+            0x7F, 0x0A, 0x00, 0x00, // color change
+            0x00, 0x05, // stitch (0, -5)
+            0x7F, 0x7F, 0x00, 0x00, // end
+        ];
+        let colors = [[0xFF, 0, 0], [0, 0xFF, 0], [0, 0, 0xFF]];
+        let pattern = parse_xxx(&build_xxx(11, &colors, &stitches)).unwrap();
+
+        assert_eq!(pattern.metadata.stitch_count, Some(11));
+        assert_eq!(pattern.metadata.color_count, Some(2));
+        assert_eq!(pattern.color_changes, 2);
+        assert_eq!(pattern.color_blocks.len(), 3);
+        assert_eq!(pattern.statistics.real_stitch_count, 4);
+        assert_eq!(pattern.statistics.trim_count, 1);
+
+        let bounds = pattern.bounds.as_ref().unwrap();
+        assert_eq!((bounds.min_x, bounds.max_x), (10.0, 335.0));
+        assert_eq!((bounds.min_y, bounds.max_y), (-10.0, 200.0));
+
+        let block_colors: Vec<_> = pattern
+            .color_blocks
+            .iter()
+            .map(|b| b.color.as_ref().unwrap().rgb)
+            .collect();
+        assert_eq!(block_colors, colors.to_vec());
+    }
+
+    #[test]
+    fn test_insufficient_data() {
+        assert!(matches!(
+            parse_xxx(&[0; 16]),
+            Err(XxxError::InsufficientData)
+        ));
+    }
+}
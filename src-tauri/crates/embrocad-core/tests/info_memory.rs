@@ -0,0 +1,86 @@
+// info_memory.rs - Reading DST figures holds memory flat however long the design
+//
+// The counting allocator replaces the global one for this test binary only,
+// so it lives apart from the unit tests.
+
+use embrocad_core::dst::{
+    parse_dst, read_dst_info, write_dst, DstVariant, ParseOptions, Pattern, Stitch, StitchCommand,
+};
+use embrocad_core::progress::LoadMonitor;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+/// Counts the bytes each thread has allocated
+struct TrackingAlloc;
+
+thread_local! {
+    static LIVE: Cell<usize> = const { Cell::new(0) };
+    static PEAK: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for TrackingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = LIVE.try_with(|live| {
+            let now = live.get() + layout.size();
+            live.set(now);
+            let _ = PEAK.try_with(|peak| peak.set(peak.get().max(now)));
+        });
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _ = LIVE.try_with(|live| live.set(live.get().saturating_sub(layout.size())));
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: TrackingAlloc = TrackingAlloc;
+
+/// Most bytes this thread held at once while `f` ran, beyond what it held
+/// before
+fn peak_during<R>(f: impl FnOnce() -> R) -> usize {
+    let base = LIVE.with(Cell::get);
+    PEAK.with(|peak| peak.set(base));
+    drop(f());
+    PEAK.with(Cell::get) - base
+}
+
+/// A DST of `stitches` back-and-forth stitches in three colors with jumps
+/// between them
+fn long_design(stitches: usize) -> Vec<u8> {
+    let mut pattern = Pattern::new();
+    let (mut x, mut y) = (0.0, 0.0);
+    for i in 0..stitches {
+        x += if i % 2 == 0 { 20.0 } else { -20.0 };
+        y += (i % 3) as f64 - 1.0;
+        pattern.add_stitch(x, y, StitchCommand::Stitch);
+        if i == stitches / 3 || i == stitches * 2 / 3 {
+            pattern.add_stitch(x, y, StitchCommand::Trim);
+            pattern.add_stitch(x, y, StitchCommand::ColorChange);
+            x += 120.0;
+            pattern.add_stitch(x, y, StitchCommand::Move);
+        }
+    }
+    pattern.add_stitch(x, y, StitchCommand::End);
+    write_dst(&pattern)
+}
+
+#[test]
+fn test_info_memory_is_flat() {
+    let options = ParseOptions {
+        max_stitches: None,
+        ..ParseOptions::default()
+    };
+    let info_peak = |data: &[u8]| {
+        peak_during(|| read_dst_info(data, DstVariant::Tajima, &options, &mut LoadMonitor::none()))
+    };
+    let small = long_design(10_000);
+    let large = long_design(500_000);
+    let (small_peak, large_peak) = (info_peak(&small), info_peak(&large));
+    let parse_peak = peak_during(|| parse_dst(&large, &options));
+
+    assert!(large_peak < 16 * 1024, "{} B", large_peak);
+    assert!(large_peak <= small_peak + 1024, "{} B", large_peak);
+    assert!(parse_peak > 500_000 * std::mem::size_of::<Stitch>());
+}
@@ -0,0 +1,106 @@
+// lib.rs - wasm-bindgen wrapper that parses design bytes in the browser
+
+//! Design parsing for JavaScript, built for `wasm32-unknown-unknown`.
+//!
+//! ```js
+//! import init, { parseDesign } from "embrocad-wasm";
+//!
+//! await init();
+//! const design = parseDesign(file.name, new Uint8Array(await file.arrayBuffer()), false);
+//! const info = JSON.parse(design.metadata);
+//! draw(design.coordinates, design.commands);
+//! ```
+//!
+//! The stitches come as the same flat arrays the desktop app unpacks from
+//! its packed form, so the frontend can draw both the same way.
+
+use embrocad_core::dst::ParseOptions;
+use embrocad_core::format;
+use embrocad_core::progress::LoadMonitor;
+use wasm_bindgen::prelude::*;
+
+/// A parsed design: stitches as typed arrays, everything else as JSON
+#[wasm_bindgen]
+pub struct ParsedDesign {
+    coordinates: Vec<f32>,
+    commands: Vec<u8>,
+    metadata: String,
+}
+
+#[wasm_bindgen]
+impl ParsedDesign {
+    /// Number of stitch records
+    #[wasm_bindgen(getter)]
+    pub fn count(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// A new `Float32Array` of x0, y0, x1, y1, ... in 0.1mm, Y pointing down
+    #[wasm_bindgen(getter)]
+    pub fn coordinates(&self) -> Vec<f32> {
+        self.coordinates.clone()
+    }
+
+    /// A new `Uint8Array` of command codes: 0 Stitch, 1 Move, 2 Trim,
+    /// 3 ColorChange, 4 SequinMode, 5 SequinEject, 6 End
+    #[wasm_bindgen(getter)]
+    pub fn commands(&self) -> Vec<u8> {
+        self.commands.clone()
+    }
+
+    /// The design as the desktop app's load command returns it: format,
+    /// metadata, bounds, statistics, color blocks and warnings, with an
+    /// empty `stitches` list
+    #[wasm_bindgen(getter)]
+    pub fn metadata(&self) -> String {
+        self.metadata.clone()
+    }
+}
+
+/// Parse and pack a design, with errors as text so it can run off wasm too
+fn parse(name: &str, data: &[u8], strict: bool) -> Result<ParsedDesign, String> {
+    let options = ParseOptions {
+        strict,
+        ..ParseOptions::default()
+    };
+    let mut design = format::parse_design(name, data, &options, &mut LoadMonitor::none())?;
+    let packed = design.pattern.to_packed();
+    design.pattern.stitches = Vec::new();
+    Ok(ParsedDesign {
+        coordinates: packed.coordinates,
+        commands: packed.commands,
+        metadata: serde_json::to_string(&design).map_err(|e| e.to_string())?,
+    })
+}
+
+/// Parse the bytes of a design file of any supported format
+///
+/// `name` is only used for its extension, which picks the parser for
+/// formats without a signature. Damaged files are read as far as possible
+/// unless `strict` is set.
+#[wasm_bindgen(js_name = parseDesign)]
+pub fn parse_design(name: &str, data: &[u8], strict: bool) -> Result<ParsedDesign, JsError> {
+    parse(name, data, strict).map_err(|e| JsError::new(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packs_stitches_and_metadata() {
+        let fixture = include_bytes!("../../embrocad-core/tests/fixtures/legacy/design.t01");
+        let design = parse("design.t01", fixture, false).unwrap();
+        assert_eq!(design.count(), 6);
+        assert_eq!(design.commands(), vec![0, 1, 0, 3, 0, 6]);
+        assert_eq!(&design.coordinates()[..4], &[10.0, 20.0, -40.0, 25.0]);
+
+        let metadata: serde_json::Value = serde_json::from_str(&design.metadata()).unwrap();
+        assert_eq!(metadata["format"], "t01");
+        assert_eq!(metadata["stitches"], serde_json::json!([]));
+        assert!(metadata.get("packed").is_none());
+        assert_eq!(metadata["statistics"]["real_stitch_count"], 3);
+
+        assert!(parse("design.dst", b"not a design", false).is_err());
+    }
+}
@@ -0,0 +1,25 @@
+// web.rs - The wasm build parsing an embedded fixture, run with wasm-pack test
+
+#![cfg(target_arch = "wasm32")]
+
+use embrocad_wasm::parse_design;
+use wasm_bindgen_test::*;
+
+const SEQUINS: &[u8] = include_bytes!("../../embrocad-core/tests/fixtures/dst/sequins.dst");
+
+#[wasm_bindgen_test]
+fn parses_embedded_dst() {
+    let design = parse_design("sequins.dst", SEQUINS, false).unwrap();
+    let commands = design.commands();
+    assert_eq!(design.count(), commands.len());
+    assert_eq!(design.coordinates().len(), commands.len() * 2);
+    // 9 sequin ejects, and the record list ends in an End
+    assert_eq!(commands.iter().filter(|&&code| code == 5).count(), 9);
+    assert_eq!(commands.last(), Some(&6));
+    assert!(design.metadata().contains("\"format\":\"dst\""));
+}
+
+#[wasm_bindgen_test]
+fn rejects_truncated_file() {
+    assert!(parse_design("broken.dst", &SEQUINS[..100], false).is_err());
+}
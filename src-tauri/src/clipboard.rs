@@ -0,0 +1,272 @@
+// clipboard.rs - Cut, copy and paste of stitches between open designs
+
+use crate::dst::Pattern;
+use crate::edit::{EditWarning, Selection};
+use crate::session::{DesignId, DesignUpdate, Designs};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+/// Version of the clipboard text format
+const FRAGMENT_VERSION: u32 = 1;
+
+/// The stitches last cut or copied, shared by every open design
+///
+/// The fragment is a standalone pattern at the coordinates it was copied
+/// from, so pasting with no offset puts it back in place.
+#[derive(Debug, Default)]
+pub struct Clipboard(Mutex<Option<Pattern>>);
+
+/// The clipboard as text, for the frontend to put on the system clipboard
+/// so another window of the app can paste it
+#[derive(Serialize, Deserialize)]
+struct FragmentText {
+    embrocad_fragment: u32,
+    pattern: Pattern,
+}
+
+/// What copying put on the clipboard
+#[derive(Debug, Clone, Serialize)]
+pub struct Copied {
+    /// Real stitches in the fragment
+    pub stitch_count: u32,
+    /// Color blocks in the fragment
+    pub block_count: usize,
+    /// The fragment as JSON for the system clipboard
+    pub text: String,
+}
+
+/// What cutting put on the clipboard, and the warnings from removing it
+#[derive(Debug, Clone, Serialize)]
+pub struct Cut {
+    #[serde(flatten)]
+    pub copied: Copied,
+    pub warnings: Vec<EditWarning>,
+}
+
+/// Read text from the system clipboard written by `Copied::text`
+fn parse_fragment(text: &str) -> Result<Pattern, String> {
+    let fragment: FragmentText =
+        serde_json::from_str(text).map_err(|_| "The clipboard holds no stitches".to_string())?;
+    if fragment.embrocad_fragment != FRAGMENT_VERSION {
+        return Err(format!(
+            "The clipboard holds stitches from a newer version (format {})",
+            fragment.embrocad_fragment
+        ));
+    }
+    Ok(fragment.pattern)
+}
+
+impl Clipboard {
+    /// Hold `fragment`, replacing whatever was copied before
+    fn hold(&self, fragment: Pattern) -> Result<Copied, String> {
+        let text = FragmentText {
+            embrocad_fragment: FRAGMENT_VERSION,
+            pattern: fragment,
+        };
+        let copied = Copied {
+            stitch_count: text.pattern.statistics.real_stitch_count,
+            block_count: text.pattern.color_blocks.len(),
+            text: serde_json::to_string(&text).map_err(|e| e.to_string())?,
+        };
+        *self.0.lock().unwrap() = Some(text.pattern);
+        Ok(copied)
+    }
+
+    /// Copy `selection` of a design; the design is left as it is
+    pub fn copy(
+        &self,
+        designs: &Designs,
+        id: DesignId,
+        selection: &Selection,
+    ) -> Result<Copied, String> {
+        let fragment = designs.with(id, |d| {
+            d.pattern
+                .extract(selection, false)
+                .map_err(|e| e.to_string())
+        })??;
+        self.hold(fragment)
+    }
+
+    /// Copy `selection` of a design and remove it, as one undoable edit
+    pub fn cut(
+        &self,
+        designs: &Designs,
+        id: DesignId,
+        selection: &Selection,
+    ) -> Result<DesignUpdate<Cut>, String> {
+        let mut fragment = None;
+        let update = designs.edit_stitches(id, "Cut", |pattern| {
+            fragment = Some(pattern.extract(selection, false)?);
+            pattern.delete_selection(selection)
+        })?;
+        let copied = self.hold(fragment.expect("a successful cut extracts first"))?;
+        Ok(DesignUpdate {
+            summary: update.summary,
+            report: Cut {
+                copied,
+                warnings: update.report,
+            },
+        })
+    }
+
+    /// Sew the clipboard after everything else in a design, shifted by
+    /// (`offset_x`, `offset_y`) in 0.1mm units, as one undoable edit
+    ///
+    /// `text` from the system clipboard takes the place of what this window
+    /// copied. With `as_new_block` the pasted stitches start a color block
+    /// of their own; otherwise they continue in the current thread.
+    pub fn paste(
+        &self,
+        designs: &Designs,
+        id: DesignId,
+        offset_x: f64,
+        offset_y: f64,
+        as_new_block: bool,
+        text: Option<&str>,
+    ) -> Result<DesignUpdate<Vec<EditWarning>>, String> {
+        let fragment = match text {
+            Some(text) => parse_fragment(text)?,
+            None => self
+                .0
+                .lock()
+                .unwrap()
+                .clone()
+                .ok_or_else(|| "Nothing has been copied".to_string())?,
+        };
+        designs.edit_stitches(id, "Paste", |pattern| {
+            pattern.paste(&fragment, offset_x, offset_y, as_new_block)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::{StitchCommand, ThreadColor};
+    use crate::format::{DesignFormat, LoadedDesign};
+    use crate::session::OpenDesign;
+
+    /// A red row of ten stitches, then a blue one of five
+    fn open(designs: &Designs) -> DesignId {
+        let mut pattern = Pattern::new();
+        for i in 0..10 {
+            pattern.add_stitch(i as f64 * 10.0, 0.0, StitchCommand::Stitch);
+        }
+        pattern.add_stitch(90.0, 0.0, StitchCommand::Trim);
+        pattern.add_stitch(90.0, 0.0, StitchCommand::ColorChange);
+        pattern.add_stitch(0.0, 50.0, StitchCommand::Move);
+        for i in 0..5 {
+            pattern.add_stitch(i as f64 * 10.0, 50.0, StitchCommand::Stitch);
+        }
+        pattern.add_stitch(40.0, 50.0, StitchCommand::End);
+        pattern.metadata.thread_colors =
+            vec![ThreadColor::new([255, 0, 0]), ThreadColor::new([0, 0, 255])];
+        pattern.calculate_bounds();
+        pattern.calculate_statistics();
+        pattern.calculate_color_blocks();
+        designs.insert(OpenDesign::new(
+            None,
+            LoadedDesign::new(DesignFormat::Dst, pattern),
+        ))
+    }
+
+    fn counts(designs: &Designs, id: DesignId) -> (usize, u32, usize) {
+        designs
+            .with(id, |d| {
+                (
+                    d.pattern.stitches.len(),
+                    d.pattern.statistics.real_stitch_count,
+                    d.pattern.color_blocks.len(),
+                )
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_copy_paste_undo_redo() {
+        let designs = Designs::default();
+        let clipboard = Clipboard::default();
+        let id = open(&designs);
+        assert_eq!(counts(&designs, id), (19, 15, 2));
+
+        let blue = Selection::Blocks { blocks: vec![1] };
+        let copied = clipboard.copy(&designs, id, &blue).unwrap();
+        assert_eq!((copied.stitch_count, copied.block_count), (5, 1));
+        assert_eq!(counts(&designs, id), (19, 15, 2));
+
+        // Trim, ColorChange and Move at the seam, then the six copied records
+        let update = clipboard
+            .paste(&designs, id, 0.0, 100.0, true, None)
+            .unwrap();
+        assert_eq!(update.summary.statistics.real_stitch_count, 20);
+        assert_eq!(counts(&designs, id), (28, 20, 3));
+        let seam = designs
+            .with(id, |d| {
+                d.pattern.stitches[18..21]
+                    .iter()
+                    .map(|s| s.command)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap();
+        assert_eq!(
+            seam,
+            vec![
+                StitchCommand::Trim,
+                StitchCommand::ColorChange,
+                StitchCommand::Move
+            ]
+        );
+        let pasted = designs
+            .with(id, |d| d.pattern.stitches[21].clone())
+            .unwrap();
+        assert_eq!((pasted.x, pasted.y), (0.0, 150.0));
+
+        let step = designs.undo(id).unwrap();
+        assert_eq!(step.report.label.as_deref(), Some("Paste"));
+        assert_eq!(counts(&designs, id), (19, 15, 2));
+        assert_eq!(
+            designs.with(id, |d| d.pattern.metadata.thread_colors.len()),
+            Ok(2)
+        );
+
+        designs.redo(id).unwrap();
+        assert_eq!(counts(&designs, id), (28, 20, 3));
+        assert_eq!(
+            designs.with(id, |d| d.pattern.metadata.thread_colors.len()),
+            Ok(3)
+        );
+    }
+
+    #[test]
+    fn test_cut_and_paste_between_designs() {
+        let designs = Designs::default();
+        let clipboard = Clipboard::default();
+        let source = open(&designs);
+        let target = open(&designs);
+
+        let red = Selection::Blocks { blocks: vec![0] };
+        let cut = clipboard.cut(&designs, source, &red).unwrap();
+        assert_eq!(cut.report.copied.stitch_count, 10);
+        assert!(cut.report.warnings.is_empty());
+        assert_eq!(counts(&designs, source), (8, 5, 1));
+
+        // Pasted into the current block, from the system clipboard's copy
+        let text = cut.report.copied.text;
+        clipboard
+            .paste(&designs, target, 0.0, 0.0, false, Some(&text))
+            .unwrap();
+        assert_eq!(counts(&designs, target), (32, 25, 2));
+
+        designs.undo(source).unwrap();
+        assert_eq!(counts(&designs, source), (19, 15, 2));
+        designs.redo(source).unwrap();
+        assert_eq!(counts(&designs, source), (8, 5, 1));
+
+        assert!(clipboard
+            .paste(&designs, target, 0.0, 0.0, false, Some("[1, 2]"))
+            .is_err());
+        assert!(Clipboard::default()
+            .paste(&designs, target, 0.0, 0.0, false, None)
+            .is_err());
+    }
+}
@@ -1,7 +0,0 @@
-// mod.rs - DST module exports for parser and pattern types
-
-mod parser;
-mod types;
-
-pub use parser::parse_dst;
-pub use types::Pattern;
@@ -0,0 +1,556 @@
+// history.rs - Per-design undo/redo of pattern edits
+
+use crate::dst::{Bounds, ColorBlock, ParseWarning, Pattern, Stitch, ThreadColor};
+use crate::edit::{apply_patches, EditWarning, StitchEdit, StitchPatch};
+use crate::transform::{transform, Pivot, TransformOperation, TransformOptions, TransformWarning};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::mem;
+
+/// Memory the history of one design may hold before dropping old entries
+pub const DEFAULT_HISTORY_LIMIT: usize = 256 * 1024 * 1024;
+
+/// Coordinates must be whole multiples of this for an edit to be undone by
+/// its inverse; at the sizes allowed by `EXACT_MAGNITUDE` every sum and
+/// difference the geometric transforms compute is then exact in f64
+const EXACT_STEP: f64 = 1.0 / 65_536.0;
+
+/// Largest coordinate or offset magnitude the exactness argument covers
+const EXACT_MAGNITUDE: f64 = 16_777_216.0;
+
+/// Outcome of an undo or redo
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HistoryStep {
+    /// Label of the edit undone or redone; None when there was nothing to do
+    pub label: Option<String>,
+    pub undo_depth: usize,
+    pub redo_depth: usize,
+}
+
+/// How an edit is reverted and replayed
+#[derive(Debug)]
+enum Change {
+    /// A transform that can be applied backwards exactly. `other` holds
+    /// every field but the stitches from the other side of the edit, since
+    /// bounds, blocks and metadata are cheap to keep but not always exact
+    /// to recompute.
+    Inverse {
+        forward: TransformOperation,
+        inverse: TransformOperation,
+        other: Pattern,
+    },
+    /// Stitch patches leading to the other side of the edit, with every
+    /// field but the stitches from there
+    Patch {
+        patches: Vec<StitchPatch>,
+        other: Pattern,
+    },
+    /// The whole pattern from the other side of the edit
+    Snapshot(Pattern),
+}
+
+impl Change {
+    fn bytes(&self) -> usize {
+        match self {
+            Change::Inverse { other, .. } => pattern_bytes(other),
+            Change::Patch { patches, other } => {
+                let stitches: usize = patches.iter().map(|p| p.stitches.len()).sum();
+                pattern_bytes(other) + stitches * mem::size_of::<Stitch>()
+            }
+            Change::Snapshot(pattern) => pattern_bytes(pattern),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    label: String,
+    change: Change,
+    bytes: usize,
+}
+
+impl Entry {
+    fn new(label: &str, change: Change) -> Self {
+        Self {
+            label: label.to_string(),
+            bytes: change.bytes(),
+            change,
+        }
+    }
+
+    /// Move `pattern` to the other side of the edit; `backwards` picks the
+    /// inverse over the forward transform
+    fn swap(&mut self, pattern: &mut Pattern, backwards: bool) {
+        match &mut self.change {
+            Change::Inverse {
+                forward,
+                inverse,
+                other,
+            } => {
+                let operation = if backwards { inverse } else { forward };
+                let left = header(pattern);
+                let moved = transform(mem::take(pattern), operation, &TransformOptions::default());
+                *pattern = mem::replace(other, left);
+                pattern.stitches = moved.pattern.stitches;
+            }
+            Change::Patch { patches, other } => {
+                *patches = apply_patches(&mut pattern.stitches, mem::take(patches));
+                let stitches = mem::take(&mut pattern.stitches);
+                mem::swap(pattern, other);
+                pattern.stitches = stitches;
+            }
+            Change::Snapshot(other) => mem::swap(pattern, other),
+        }
+        self.bytes = self.change.bytes();
+    }
+}
+
+/// Copy of every field but the stitches
+fn header(pattern: &mut Pattern) -> Pattern {
+    let stitches = mem::take(&mut pattern.stitches);
+    let header = pattern.clone();
+    pattern.stitches = stitches;
+    header
+}
+
+/// Approximate heap and inline size of a pattern
+fn pattern_bytes(pattern: &Pattern) -> usize {
+    mem::size_of::<Pattern>()
+        + pattern.stitches.len() * mem::size_of::<Stitch>()
+        + pattern.color_blocks.len() * mem::size_of::<ColorBlock>()
+        + pattern.metadata.thread_colors.len() * mem::size_of::<ThreadColor>()
+        + pattern.warnings.len() * mem::size_of::<ParseWarning>()
+}
+
+fn is_exact(value: f64) -> bool {
+    value.abs() < EXACT_MAGNITUDE && (value / EXACT_STEP).fract() == 0.0
+}
+
+/// A nonzero power of two within 2^±16, so scaling by it and back is exact
+fn is_exact_factor(factor: f64) -> bool {
+    (1.0 / 65_536.0..=65_536.0).contains(&factor.abs()) && factor.abs().log2().fract() == 0.0
+}
+
+/// The transform with its pivot pinned down, and an inverse that undoes it
+/// bit for bit, or None when floating point rounding rules that out
+fn invertible(
+    pattern: &Pattern,
+    operation: &TransformOperation,
+) -> Option<(TransformOperation, TransformOperation)> {
+    let coordinates_exact = || {
+        pattern
+            .stitches
+            .iter()
+            .all(|s| is_exact(s.x) && is_exact(s.y))
+    };
+
+    match *operation {
+        TransformOperation::Translate { dx, dy } => {
+            (is_exact(dx) && is_exact(dy) && coordinates_exact()).then(|| {
+                let inverse = TransformOperation::Translate { dx: -dx, dy: -dy };
+                (operation.clone(), inverse)
+            })
+        }
+        TransformOperation::Center => {
+            let (cx, cy) = pattern.bounds_center();
+            invertible(pattern, &TransformOperation::Translate { dx: -cx, dy: -cy })
+        }
+        // The bounds midpoint stays put, so a second mirror undoes the first,
+        // provided the stored bounds are the ones the mirror will recompute
+        TransformOperation::Mirror { .. } => {
+            let mut bounds = Bounds::new();
+            for stitch in &pattern.stitches {
+                bounds.update(stitch.x, stitch.y);
+            }
+            let stored = pattern.bounds.as_ref();
+            let current = stored.is_none_or(|b| {
+                (b.min_x, b.min_y, b.max_x, b.max_y)
+                    == (bounds.min_x, bounds.min_y, bounds.max_x, bounds.max_y)
+            });
+            (current && coordinates_exact()).then(|| (operation.clone(), operation.clone()))
+        }
+        TransformOperation::Rotate { degrees, about } => {
+            let quarter_turn = degrees.rem_euclid(90.0) == 0.0;
+            let (x, y) = match about {
+                Pivot::Center => pattern.bounds_center(),
+                Pivot::Origin => (0.0, 0.0),
+                Pivot::Point { x, y } => (x, y),
+            };
+            (quarter_turn && is_exact(x) && is_exact(y) && coordinates_exact()).then(|| {
+                let about = Pivot::Point { x, y };
+                (
+                    TransformOperation::Rotate { degrees, about },
+                    TransformOperation::Rotate {
+                        degrees: -degrees,
+                        about,
+                    },
+                )
+            })
+        }
+        TransformOperation::Scale { factor_x, factor_y } => {
+            (is_exact_factor(factor_x) && is_exact_factor(factor_y)).then(|| {
+                let inverse = TransformOperation::Scale {
+                    factor_x: 1.0 / factor_x,
+                    factor_y: 1.0 / factor_y,
+                };
+                (operation.clone(), inverse)
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Undo and redo stacks for one open design
+///
+/// Geometric transforms that floating point can reverse exactly are kept as
+/// their inverse; every other edit keeps a full copy of the pattern. Once
+/// the entries take more than `limit` bytes the oldest are dropped.
+#[derive(Debug)]
+pub struct History {
+    undo: VecDeque<Entry>,
+    redo: Vec<Entry>,
+    /// Sum of `Entry::bytes` over both stacks
+    bytes: usize,
+    limit: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_LIMIT)
+    }
+}
+
+impl History {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            bytes: 0,
+            limit,
+        }
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.trim();
+    }
+
+    pub fn undo_depth(&self) -> usize {
+        self.undo.len()
+    }
+
+    pub fn redo_depth(&self) -> usize {
+        self.redo.len()
+    }
+
+    /// Labels of the edits that can be undone, oldest first
+    pub fn labels(&self) -> Vec<String> {
+        self.undo.iter().map(|entry| entry.label.clone()).collect()
+    }
+
+    fn push(&mut self, entry: Entry) {
+        self.bytes += entry.bytes;
+        self.bytes -= self.redo.drain(..).map(|e| e.bytes).sum::<usize>();
+        self.undo.push_back(entry);
+        self.trim();
+    }
+
+    /// Drop the oldest undo entries, then the furthest redo entries, until
+    /// the total fits the limit
+    fn trim(&mut self) {
+        while self.bytes > self.limit {
+            let dropped = match self.undo.pop_front() {
+                Some(entry) => entry,
+                None if !self.redo.is_empty() => self.redo.remove(0),
+                None => break,
+            };
+            self.bytes -= dropped.bytes;
+        }
+    }
+
+    /// Apply a transform and record it, as its inverse when that is exact
+    pub fn transform(
+        &mut self,
+        pattern: &mut Pattern,
+        operation: &TransformOperation,
+        options: &TransformOptions,
+    ) -> Vec<TransformWarning> {
+        let label = operation.label();
+        let Some((forward, inverse)) = invertible(pattern, operation) else {
+            let before = pattern.clone();
+            let result = transform(mem::take(pattern), operation, options);
+            *pattern = result.pattern;
+            self.push(Entry::new(label, Change::Snapshot(before)));
+            return result.warnings;
+        };
+
+        let other = header(pattern);
+        let result = transform(mem::take(pattern), &forward, options);
+        *pattern = result.pattern;
+        self.push(Entry::new(
+            label,
+            Change::Inverse {
+                forward,
+                inverse,
+                other,
+            },
+        ));
+        result.warnings
+    }
+
+    /// Run an arbitrary edit, keeping a copy of the pattern to undo it
+    ///
+    /// `edit` must leave the pattern untouched when it fails; nothing is
+    /// recorded then.
+    pub fn edit<R>(
+        &mut self,
+        pattern: &mut Pattern,
+        label: &str,
+        edit: impl FnOnce(&mut Pattern) -> Result<R, String>,
+    ) -> Result<R, String> {
+        let before = pattern.clone();
+        let report = edit(pattern)?;
+        self.push(Entry::new(label, Change::Snapshot(before)));
+        Ok(report)
+    }
+
+    /// Run a stitch edit, keeping only the patches that undo it
+    ///
+    /// Edits that change nothing are not recorded.
+    pub fn patch(
+        &mut self,
+        pattern: &mut Pattern,
+        label: &str,
+        edit: impl FnOnce(&mut Pattern) -> Result<StitchEdit, String>,
+    ) -> Result<Vec<EditWarning>, String> {
+        let other = header(pattern);
+        let edit = edit(pattern)?;
+        if !edit.undo.is_empty() {
+            let patches = edit.undo;
+            self.push(Entry::new(label, Change::Patch { patches, other }));
+        }
+        Ok(edit.warnings)
+    }
+
+    /// Revert the latest edit
+    pub fn undo(&mut self, pattern: &mut Pattern) -> HistoryStep {
+        let label = self.undo.pop_back().map(|mut entry| {
+            self.bytes -= entry.bytes;
+            entry.swap(pattern, true);
+            self.bytes += entry.bytes;
+            let label = entry.label.clone();
+            self.redo.push(entry);
+            label
+        });
+        self.trim();
+        self.step(label)
+    }
+
+    /// Replay the latest undone edit
+    pub fn redo(&mut self, pattern: &mut Pattern) -> HistoryStep {
+        let label = self.redo.pop().map(|mut entry| {
+            self.bytes -= entry.bytes;
+            entry.swap(pattern, false);
+            self.bytes += entry.bytes;
+            let label = entry.label.clone();
+            self.undo.push_back(entry);
+            label
+        });
+        self.trim();
+        self.step(label)
+    }
+
+    fn step(&self, label: Option<String>) -> HistoryStep {
+        HistoryStep {
+            label,
+            undo_depth: self.undo_depth(),
+            redo_depth: self.redo_depth(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cleanup::{cleanup, CleanupOptions};
+    use crate::dst::{parse_dst, write_dst, ParseOptions, StitchCommand};
+    use crate::transform::MirrorAxis;
+
+    /// A two-color zigzag written to DST and parsed back, as a loaded design is
+    fn parsed_pattern() -> Pattern {
+        let mut pattern = Pattern::new();
+        for i in 0..400 {
+            let command = if i == 200 {
+                StitchCommand::ColorChange
+            } else {
+                StitchCommand::Stitch
+            };
+            pattern.add_stitch((i % 50) as f64 * 7.0, (i / 50) as f64 * 9.0, command);
+        }
+        pattern.add_stitch(0.0, 0.0, StitchCommand::End);
+        parse_dst(&write_dst(&pattern), &ParseOptions::default()).unwrap()
+    }
+
+    fn operations() -> Vec<TransformOperation> {
+        vec![
+            TransformOperation::Translate {
+                dx: 35.0,
+                dy: -12.5,
+            },
+            TransformOperation::Rotate {
+                degrees: 90.0,
+                about: Pivot::Center,
+            },
+            TransformOperation::Mirror {
+                axis: MirrorAxis::Horizontal,
+            },
+            TransformOperation::Scale {
+                factor_x: 2.0,
+                factor_y: 0.5,
+            },
+            TransformOperation::Rotate {
+                degrees: 33.0,
+                about: Pivot::Origin,
+            },
+            TransformOperation::Translate { dx: 0.1, dy: 0.2 },
+            TransformOperation::Scale {
+                factor_x: 1.1,
+                factor_y: 1.1,
+            },
+            TransformOperation::Center,
+            TransformOperation::Reverse,
+        ]
+    }
+
+    /// Every field compared through serde, and stitch coordinates bit for bit
+    fn assert_identical(actual: &Pattern, expected: &Pattern) {
+        assert_eq!(
+            serde_json::to_value(actual).unwrap(),
+            serde_json::to_value(expected).unwrap()
+        );
+        let bits = |p: &Pattern| -> Vec<(u64, u64)> {
+            p.stitches
+                .iter()
+                .map(|s| (s.x.to_bits(), s.y.to_bits()))
+                .collect()
+        };
+        assert_eq!(bits(actual), bits(expected));
+    }
+
+    #[test]
+    fn test_stitch_edits_keep_patches_only() {
+        let original = parsed_pattern();
+        let mut pattern = original.clone();
+        let mut history = History::default();
+        history
+            .patch(&mut pattern, "Move stitch", |p| {
+                p.move_stitch(7, 3.0, 4.0).map_err(|e| e.to_string())
+            })
+            .unwrap();
+        let color_change = pattern
+            .stitches
+            .iter()
+            .position(|s| s.command == StitchCommand::ColorChange)
+            .unwrap();
+        let doomed = [0, 1, color_change, color_change + 100];
+        let warnings = history
+            .patch(&mut pattern, "Delete stitches", |p| {
+                p.delete_stitches(&doomed).map_err(|e| e.to_string())
+            })
+            .unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(history.bytes < pattern_bytes(&original) / 4);
+        let edited = pattern.clone();
+
+        history.undo(&mut pattern);
+        history.undo(&mut pattern);
+        assert_identical(&pattern, &original);
+        history.redo(&mut pattern);
+        history.redo(&mut pattern);
+        assert_identical(&pattern, &edited);
+    }
+
+    /// Nine transforms and a cleanup, ten edits in all
+    fn chain(history: &mut History, pattern: &mut Pattern) {
+        for operation in operations() {
+            history.transform(pattern, &operation, &TransformOptions::default());
+        }
+        history
+            .edit(pattern, "Cleanup", |pattern| {
+                let result = cleanup(mem::take(pattern), &CleanupOptions::default());
+                *pattern = result.pattern;
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_undo_all_restores_parsed_pattern() {
+        let original = parsed_pattern();
+        let mut pattern = original.clone();
+        let mut history = History::default();
+        chain(&mut history, &mut pattern);
+        assert_eq!(history.undo_depth(), 10);
+        let edited = pattern.clone();
+
+        for _ in 0..10 {
+            assert!(history.undo(&mut pattern).label.is_some());
+        }
+        assert_identical(&pattern, &original);
+        assert_eq!(history.undo(&mut pattern).label, None);
+
+        for _ in 0..10 {
+            history.redo(&mut pattern);
+        }
+        assert_identical(&pattern, &edited);
+        assert_eq!(history.redo_depth(), 0);
+    }
+
+    #[test]
+    fn test_exact_transforms_store_no_stitches() {
+        let mut pattern = parsed_pattern();
+        let mut history = History::default();
+        for operation in &operations()[..4] {
+            history.transform(&mut pattern, operation, &TransformOptions::default());
+        }
+
+        let stitch_bytes = pattern.stitches.len() * mem::size_of::<Stitch>();
+        assert!(history.bytes < stitch_bytes);
+
+        // A 33° turn cannot be reversed exactly and keeps a copy
+        history.transform(&mut pattern, &operations()[4], &TransformOptions::default());
+        assert!(history.bytes > stitch_bytes);
+    }
+
+    #[test]
+    fn test_new_edit_clears_redo() {
+        let mut pattern = parsed_pattern();
+        let mut history = History::default();
+        let operation = TransformOperation::Translate { dx: 10.0, dy: 0.0 };
+        history.transform(&mut pattern, &operation, &TransformOptions::default());
+        history.undo(&mut pattern);
+        assert_eq!(history.redo_depth(), 1);
+
+        history.transform(&mut pattern, &operation, &TransformOptions::default());
+        assert_eq!((history.undo_depth(), history.redo_depth()), (1, 0));
+    }
+
+    #[test]
+    fn test_limit_drops_oldest_entries() {
+        let mut pattern = parsed_pattern();
+        let snapshot = pattern_bytes(&pattern);
+        let mut history = History::new(snapshot * 3);
+        for _ in 0..5 {
+            let operation = TransformOperation::Rotate {
+                degrees: 33.0,
+                about: Pivot::Origin,
+            };
+            history.transform(&mut pattern, &operation, &TransformOptions::default());
+        }
+        assert_eq!(history.undo_depth(), 3);
+        assert!(history.bytes <= snapshot * 3);
+
+        history.set_limit(0);
+        assert_eq!((history.undo_depth(), history.bytes), (0, 0));
+    }
+}
@@ -1,21 +1,1628 @@
-// lib.rs - Tauri plugin setup and load_design command handler
+// lib.rs - Tauri plugin setup and design load/save command handlers
 
-mod dst;
+mod clipboard;
+mod history;
+mod library;
+mod project;
+mod recovery;
+mod session;
+mod settings;
+mod stream;
 
-use dst::{parse_dst, Pattern};
+use embrocad_core::{
+    analysis, batch, cleanup, companion, digitize, dst, edit, export, format, generate, hoops,
+    lettering, machines, needle_assignment, optimize, progress, threads, transform, units, view,
+};
+
+use analysis::{
+    analyze, compare_patterns, estimate_thread_usage, AnalysisOptions, BlockMeasurement,
+    DesignAnalysis, DesignDiff, Measurement, Risk, RiskThresholds, ThreadUsage, ThreadUsageOptions,
+    UnderlayOptions, UnderlayRun, DEFAULT_MATCH_TOLERANCE_MM,
+};
+use batch::{convert_batch as run_batch, BatchFileReport, BatchOptions};
+use cleanup::{cleanup, CleanupOptions, CleanupSummary};
+use clipboard::{Clipboard, Copied, Cut};
+use companion::find_companion_colors;
+use digitize::{digitize_image, DigitizeOptions};
+use dst::{
+    detect_variant, read_dst_info, write_dst, Bounds, DstVariant, ParseOptions, Pattern,
+    RunTimeline, Stitch, StitchCommand, ThreadColor, TimeEstimate, TimeEstimator, TimePoint,
+    DEFAULT_SAMPLE_EVERY, UNITS_PER_MM,
+};
+use edit::{CropMode, CropReport, EditWarning, Selection};
+use export::{
+    encode_design, render_animation, render_png, thumbnail_file_name, write_worksheet,
+    AnimationOptions, ColorSidecar, ExportFormat, ExportOptions, PngOptions, RenderMode,
+    WorksheetOptions,
+};
+use format::{design_format, parse_design, read_design, DesignFormat, LoadedDesign};
+use generate::{OutlineOptions, Primitive};
+use history::HistoryStep;
+use hoops::{find_hoop, hoop_field, HoopFit, DEFAULT_HOOP_MARGIN_MM};
+use lettering::generate_text as sew_text;
+use library::{
+    default_concurrency, DuplicateCluster, FolderWatcher, IndexReport, Library, LibraryChange,
+    LibraryEntry, LibraryQuery, WatchFolder, DEFAULT_DUPLICATE_THRESHOLD,
+};
+use machines::{find_machine, MachineProfile, MachineViolation, MACHINES};
+use needle_assignment::{block_needles, NeedleStrategy};
+use optimize::{ColorSortReport, JumpReport};
+use progress::{CancelToken, LoadMonitor, LoadProgress};
+use project::{Project, ReopenedDesign};
+use recovery::{AutosaveOptions, RecoverableSession, Recovery};
+use session::{DesignHandle, DesignId, DesignUpdate, Designs, OpenDesign, OpenDesignInfo};
+use settings::{Settings, SettingsStore};
+use std::collections::HashMap;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use stream::{stitch_chunks, DesignSummary, DEFAULT_CHUNK_SIZE};
+use tauri::ipc::{Channel, Response};
+use tauri::{Emitter, Manager, State};
+use threads::{
+    convert_palette as match_palette, CatalogThread, PaletteMatch, ThreadBrand, ThreadRef,
+};
+use transform::{RepeatLayout, TransformOperation, TransformOptions, TransformWarning};
+use units::{DesignStats, Unit};
+use view::StitchHit;
+
+/// Summarize a design file without keeping its stitches
+///
+/// DST-family files are folded record by record, so memory stays flat
+/// however large they are; other formats are parsed in full and their
+/// stitches dropped. Positions are as stored even with `center_on_load`.
+fn read_design_info(
+    path: &str,
+    data: &[u8],
+    options: &ParseOptions,
+) -> Result<DesignSummary, String> {
+    let format = design_format(path, data);
+    let variant = match format {
+        DesignFormat::Dst => detect_variant(data),
+        DesignFormat::Dsb => DstVariant::Barudan,
+        DesignFormat::Dsz => DstVariant::Zsk,
+        _ => {
+            let design = parse_design(path, data, options, &mut LoadMonitor::none())?;
+            return Ok(DesignSummary::new(design.format, &design.pattern));
+        }
+    };
+    let mut info = read_dst_info(data, variant, options, &mut LoadMonitor::none())
+        .map_err(|e| format!("Failed to parse {}: {}", format.name(), e))?;
+
+    if options.companion_colors && info.metadata.thread_colors.is_empty() {
+        if let Some((file, colors)) = find_companion_colors(Path::new(path)) {
+            info.set_thread_colors(colors);
+            info.metadata.companion_file = Some(file.to_string_lossy().into_owned());
+        }
+    }
+    Ok(DesignSummary::from_info(format, info))
+}
+
+/// Cancellation tokens of loads still running, keyed by the frontend's load id
+#[derive(Default)]
+struct PendingLoads(Mutex<HashMap<u32, CancelToken>>);
 
-/// Tauri command to load and parse a DST file
+/// Tauri command to load and parse a design file
 /// This is the single entry point for loading designs - no duplicate parsing
+///
+/// `options` is optional and defaults to the parsing settings; strictness and
+/// the stitch limit currently apply to the DST family, centering to every format.
+/// Parsing runs off the main thread, sending `LoadProgress` on `on_progress`
+/// (a channel without a handler ignores it); a load started with a `load_id`
+/// can be aborted with `cancel_load`. The design stays open in the backend;
+/// the returned handle carries its id and summary, and `get_design` fetches
+/// the stitches. The file is added to the library's recent designs.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn load_design(
+    loads: State<'_, PendingLoads>,
+    designs: State<'_, Designs>,
+    library: State<'_, Library>,
+    settings: State<'_, SettingsStore>,
+    path: String,
+    options: Option<ParseOptions>,
+    load_id: Option<u32>,
+    on_progress: Channel<LoadProgress>,
+) -> Result<DesignHandle, String> {
+    let options = options.unwrap_or_else(|| settings.get().parse_options());
+    let token = CancelToken::new();
+    if let Some(id) = load_id {
+        loads.0.lock().unwrap().insert(id, token.clone());
+    }
+
+    let result = tauri::async_runtime::spawn_blocking(move || -> Result<_, String> {
+        // Read the file once
+        let data = fs::read(&path).map_err(|e| format!("Failed to read file: {}", e))?;
+        let mut report = |progress: LoadProgress| {
+            let _ = on_progress.send(progress);
+        };
+        let mut monitor = LoadMonitor::new(Some(&token), Some(&mut report));
+        let design = parse_design(&path, &data, &options, &mut monitor)?;
+        let entry = LibraryEntry::new(&path, &data, &design);
+        Ok((OpenDesign::new(Some(path), design), entry))
+    })
+    .await;
+
+    if let Some(id) = load_id {
+        loads.0.lock().unwrap().remove(&id);
+    }
+    let (design, entry) = result.map_err(|e| format!("Load failed: {}", e))??;
+    // A library that cannot be saved must not fail the load
+    let _ = library.record_opened(entry);
+    let summary = design.summary();
+    Ok(DesignHandle {
+        id: designs.insert(design),
+        summary,
+    })
+}
+
+/// Tauri command to fetch an open design with its stitches
+///
+/// With `packed` set the stitches arrive as `PackedStitches` instead of objects.
+/// With `underlay` set as well, stitches that look like underlay are flagged
+/// in the packed form so the view can hide them.
+#[tauri::command]
+fn get_design(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    packed: Option<bool>,
+    underlay: Option<bool>,
+) -> Result<LoadedDesign, String> {
+    let mut design = designs.with(id, |d| d.loaded())?;
+    if packed.unwrap_or(false) {
+        let runs = underlay
+            .unwrap_or(false)
+            .then(|| design.pattern.detect_underlay(&UnderlayOptions::default()));
+        design.pack();
+        if let (Some(runs), Some(packed)) = (runs, design.packed.as_mut()) {
+            packed.flag_underlay(&runs);
+        }
+    }
+    Ok(design)
+}
+
+/// Tauri command to load a design in two stages for very large files
+///
+/// Returns the design's handle, then sends the stitches on `on_chunk` as
+/// binary chunks of `chunk_size` (see `stream::encode_chunk`). Each chunk
+/// carries its start index, so it may arrive before the handle resolves.
+#[tauri::command]
+async fn load_design_streamed(
+    designs: State<'_, Designs>,
+    library: State<'_, Library>,
+    settings: State<'_, SettingsStore>,
+    path: String,
+    options: Option<ParseOptions>,
+    chunk_size: Option<usize>,
+    on_chunk: Channel<Response>,
+) -> Result<DesignHandle, String> {
+    let chunk_size = chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE).max(1);
+    let options = options.unwrap_or_else(|| settings.get().parse_options());
+    let (design, entry) = tauri::async_runtime::spawn_blocking(move || {
+        let data = fs::read(&path).map_err(|e| format!("Failed to read file: {}", e))?;
+        let design = parse_design(&path, &data, &options, &mut LoadMonitor::none())?;
+        let entry = LibraryEntry::new(&path, &data, &design);
+        Ok::<_, String>((OpenDesign::new(Some(path), design), entry))
+    })
+    .await
+    .map_err(|e| format!("Load failed: {}", e))??;
+    let _ = library.record_opened(entry);
+
+    let summary = design.summary();
+    let stitches = design.pattern.stitches.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        for chunk in stitch_chunks(&stitches, chunk_size) {
+            if on_chunk.send(Response::new(chunk)).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(DesignHandle {
+        id: designs.insert(design),
+        summary,
+    })
+}
+
+/// Tauri command to read a design file's metadata, bounds, statistics and
+/// color blocks without loading its stitches, for library lists and info
+/// popovers; the design is not opened
+#[tauri::command]
+async fn get_design_info(
+    settings: State<'_, SettingsStore>,
+    path: String,
+) -> Result<DesignSummary, String> {
+    let options = settings.get().parse_options();
+    tauri::async_runtime::spawn_blocking(move || {
+        let data = fs::read(&path).map_err(|e| format!("Failed to read file: {}", e))?;
+        read_design_info(&path, &data, &options)
+    })
+    .await
+    .map_err(|e| format!("Reading design info failed: {}", e))?
+}
+
+/// Tauri command to release an open design; returns false for unknown ids
+#[tauri::command]
+fn close_design(designs: State<'_, Designs>, id: DesignId) -> bool {
+    designs.close(id)
+}
+
+/// Tauri command to list the designs the backend holds, oldest first
+#[tauri::command]
+fn list_open_designs(designs: State<'_, Designs>) -> Vec<OpenDesignInfo> {
+    designs.list()
+}
+
+/// Tauri command to abort a load started with `load_id`; unknown ids are ignored
+#[tauri::command]
+fn cancel_load(loads: State<'_, PendingLoads>, load_id: u32) {
+    if let Some(token) = loads.0.lock().unwrap().get(&load_id) {
+        token.cancel();
+    }
+}
+
+/// Tauri command to write an open design back to disk as a DST file
+///
+/// With `colors_sidecar` the block colors also go to an EDR or COL file of
+/// the same name, for software and machines that cannot read them from a DST.
+#[tauri::command]
+fn save_design(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    path: String,
+    colors_sidecar: Option<ColorSidecar>,
+) -> Result<(), String> {
+    let (data, colors) = designs.with(id, |d| {
+        let colors = colors_sidecar.map(|sidecar| sidecar.write(&d.pattern));
+        (write_dst(&d.pattern), colors)
+    })?;
+
+    fs::write(&path, data).map_err(|e| format!("Failed to write file: {}", e))?;
+    if let (Some(sidecar), Some(colors)) = (colors_sidecar, colors) {
+        let sidecar_path = Path::new(&path).with_extension(sidecar.extension());
+        fs::write(&sidecar_path, colors)
+            .map_err(|e| format!("Failed to write color file: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Tauri command to save open designs, with their colors and edit labels,
+/// as a project file
+///
+/// Saves the designs in `ids`, or every open design when omitted.
+#[tauri::command]
+fn save_project(
+    designs: State<'_, Designs>,
+    path: String,
+    ids: Option<Vec<DesignId>>,
+) -> Result<(), String> {
+    let ids = ids.unwrap_or_else(|| designs.list().iter().map(|d| d.id).collect());
+    let project = Project::save(&designs, &ids)?;
+    fs::write(&path, project.to_bytes()).map_err(|e| format!("Failed to write file: {}", e))
+}
+
+/// Tauri command to open every design saved in a project file
 #[tauri::command]
-fn load_design(path: String) -> Result<Pattern, String> {
-    // Read the file once
+fn open_project(designs: State<'_, Designs>, path: String) -> Result<Vec<ReopenedDesign>, String> {
     let data = fs::read(&path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let project = Project::from_bytes(&data).map_err(|e| e.to_string())?;
+    Ok(project.open(&designs))
+}
+
+/// Tauri command to write an open design to disk in a preview or machine format
+#[tauri::command]
+fn export_design(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    path: String,
+    format: ExportFormat,
+    options: Option<ExportOptions>,
+) -> Result<(), String> {
+    let options = options.unwrap_or_default();
+    let data = designs.with(id, |d| encode_design(&d.pattern, format, &options))??;
+
+    fs::write(&path, data).map_err(|e| format!("Failed to write file: {}", e))
+}
+
+/// Progress payload of the `batch-progress` event
+#[derive(Clone, serde::Serialize)]
+struct BatchProgress {
+    file: String,
+    done: usize,
+    total: usize,
+}
+
+/// Tauri command to convert every design in a folder to `target_format`
+///
+/// Emits `batch-progress` naming each file before it is converted. Files that
+/// fail to parse or write are reported and skipped; with `dry_run` set the
+/// report only lists what would be written.
+#[tauri::command]
+fn convert_batch(
+    app: tauri::AppHandle,
+    input_dir: String,
+    output_dir: String,
+    target_format: ExportFormat,
+    options: Option<BatchOptions>,
+) -> Result<Vec<BatchFileReport>, String> {
+    let options = options.unwrap_or_default();
+    run_batch(
+        Path::new(&input_dir),
+        Path::new(&output_dir),
+        target_format,
+        &options,
+        |file| {
+            let path = file.to_string_lossy();
+            let data = fs::read(file).map_err(|e| format!("Failed to read file: {}", e))?;
+            let pattern = parse_design(
+                &path,
+                &data,
+                &ParseOptions::default(),
+                &mut LoadMonitor::none(),
+            )?
+            .pattern;
+            let encoded = encode_design(&pattern, target_format, &options.export)?;
+            Ok((encoded, pattern.warnings))
+        },
+        |file, done, total| {
+            let file = file.to_string_lossy().into_owned();
+            let _ = app.emit("batch-progress", BatchProgress { file, done, total });
+        },
+    )
+    .map_err(|e| format!("Failed to read folder: {}", e))
+}
+
+/// Tauri command to write a printable one-page PDF worksheet of an open design
+///
+/// The run time is estimated at the saved machine speed unless `options`
+/// names a machine.
+#[tauri::command]
+fn export_worksheet(
+    designs: State<'_, Designs>,
+    settings: State<'_, SettingsStore>,
+    id: DesignId,
+    path: String,
+    options: Option<WorksheetOptions>,
+) -> Result<(), String> {
+    let mut options = options.unwrap_or_default();
+    if options.machine.is_none() {
+        options.machine = Some(settings.get().time_estimator());
+    }
+    let pdf = designs.with(id, |d| write_worksheet(&d.pattern, &options))?;
+    fs::write(&path, pdf).map_err(|e| format!("Failed to write file: {}", e))
+}
+
+/// Progress payload of the `animation-progress` event
+#[derive(Clone, serde::Serialize)]
+struct AnimationProgress {
+    done: usize,
+    total: usize,
+}
+
+/// Tauri command to write a stitch-out animation into `directory` as
+/// numbered PNG frames, returning their paths
+///
+/// Emits `animation-progress` after each frame is rendered.
+#[tauri::command]
+fn export_animation(
+    app: tauri::AppHandle,
+    designs: State<'_, Designs>,
+    id: DesignId,
+    directory: String,
+    frames: usize,
+    size: u32,
+    options: Option<AnimationOptions>,
+) -> Result<Vec<String>, String> {
+    let options = options.unwrap_or_default();
+    let images = designs
+        .with(id, |d| {
+            render_animation(&d.pattern, frames, size, &options, |done, total| {
+                let _ = app.emit("animation-progress", AnimationProgress { done, total });
+            })
+        })?
+        .map_err(|e| e.to_string())?;
+
+    let directory = Path::new(&directory);
+    fs::create_dir_all(directory).map_err(|e| format!("Failed to create directory: {}", e))?;
+    images
+        .iter()
+        .enumerate()
+        .map(|(index, image)| {
+            let path = directory.join(format!("frame_{:04}.png", index + 1));
+            fs::write(&path, image).map_err(|e| format!("Failed to write frame: {}", e))?;
+            Ok(path.to_string_lossy().into_owned())
+        })
+        .collect()
+}
+
+/// Tauri command to render a square PNG preview of a design file
+///
+/// Thumbnails are cached in the app data directory under the file's content
+/// hash, so renamed or re-opened files reuse them. Returns the PNG's path.
+#[tauri::command]
+fn generate_thumbnail(
+    app: tauri::AppHandle,
+    path: String,
+    size: u32,
+    mode: Option<RenderMode>,
+) -> Result<String, String> {
+    let mode = mode.unwrap_or_default();
+    let data = fs::read(&path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let cache_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("No app data directory: {}", e))?
+        .join("thumbnails");
+    let thumbnail = cache_dir.join(thumbnail_file_name(&data, size, mode));
+
+    if !thumbnail.exists() {
+        let pattern = read_design(&path, &ParseOptions::default())?.pattern;
+        let png =
+            render_png(&pattern, size, size, &PngOptions::default()).map_err(|e| e.to_string())?;
+        fs::create_dir_all(&cache_dir)
+            .and_then(|_| fs::write(&thumbnail, png))
+            .map_err(|e| format!("Failed to write thumbnail: {}", e))?;
+    }
+
+    Ok(thumbnail.to_string_lossy().into_owned())
+}
+
+/// Progress payload of the `library-progress` event
+#[derive(Clone, serde::Serialize)]
+struct IndexProgress {
+    done: usize,
+    total: usize,
+}
+
+/// Tauri command to add every design file in a folder to the library
+///
+/// Runs off the main thread, parsing up to `concurrency` files at once (one
+/// per core by default) and emitting `library-progress`. Files unchanged
+/// since they were last indexed are skipped; the report counts what was
+/// added, updated and removed and lists the files that failed.
+#[tauri::command]
+async fn index_folder(
+    app: tauri::AppHandle,
+    path: String,
+    recursive: bool,
+    concurrency: Option<usize>,
+) -> Result<IndexReport, String> {
+    let concurrency = concurrency.unwrap_or_else(default_concurrency);
+    tauri::async_runtime::spawn_blocking(move || {
+        let library = app.state::<Library>();
+        library.index_folder(
+            Path::new(&path),
+            recursive,
+            concurrency,
+            |file, data| {
+                parse_design(
+                    file,
+                    data,
+                    &ParseOptions::default(),
+                    &mut LoadMonitor::none(),
+                )
+            },
+            |done, total| {
+                let _ = app.emit("library-progress", IndexProgress { done, total });
+            },
+        )
+    })
+    .await
+    .map_err(|e| format!("Indexing failed: {}", e))?
+    .map_err(|e| format!("Failed to index folder: {}", e))
+}
+
+/// Update the library for paths the folder watcher saw change, emitting
+/// `library-changed` with what changed
+fn refresh_library(app: &tauri::AppHandle, paths: Vec<PathBuf>) {
+    let library = app.state::<Library>();
+    let mut changes = Vec::new();
+    for path in paths {
+        let refreshed = library.refresh_path(&path, |file, data| {
+            parse_design(
+                file,
+                data,
+                &ParseOptions::default(),
+                &mut LoadMonitor::none(),
+            )
+        });
+        match refreshed {
+            Ok(found) => changes.extend(found),
+            Err(e) => changes.push(LibraryChange::Failed {
+                path: path.to_string_lossy().into_owned(),
+                error: format!("Failed to update library: {}", e),
+            }),
+        }
+    }
+    if !changes.is_empty() {
+        let _ = app.emit("library-changed", changes);
+    }
+}
+
+/// Tauri command to follow a folder, indexing it now and refreshing the
+/// library whenever its design files change
+#[tauri::command]
+async fn add_watch_folder(
+    app: tauri::AppHandle,
+    watcher: State<'_, FolderWatcher>,
+    path: String,
+    recursive: bool,
+) -> Result<IndexReport, String> {
+    watcher.add(WatchFolder {
+        path: path.clone(),
+        recursive,
+    })?;
+    index_folder(app, path, recursive, None).await
+}
+
+/// Tauri command to stop following a folder; its designs stay in the library
+#[tauri::command]
+fn remove_watch_folder(watcher: State<'_, FolderWatcher>, path: String) -> Result<bool, String> {
+    watcher.remove(&path)
+}
+
+/// Tauri command to list the folders the library follows
+#[tauri::command]
+fn list_watch_folders(watcher: State<'_, FolderWatcher>) -> Vec<WatchFolder> {
+    watcher.list()
+}
+
+/// Tauri command to find library designs matching a text and filters
+#[tauri::command]
+fn search_library(
+    library: State<'_, Library>,
+    query: LibraryQuery,
+) -> Result<Vec<LibraryEntry>, String> {
+    library.search(&query)
+}
+
+/// Tauri command to group library designs that are copies of each other
+///
+/// Designs at least `threshold` alike (0.9 by default) are clustered, by
+/// file hash and by where their needle goes in, ignoring position. With
+/// `transforms` (the default) quarter turns and mirror images count as
+/// copies too.
+#[tauri::command]
+async fn find_duplicates(
+    app: tauri::AppHandle,
+    threshold: Option<f64>,
+    transforms: Option<bool>,
+) -> Result<Vec<DuplicateCluster>, String> {
+    let threshold = threshold.unwrap_or(DEFAULT_DUPLICATE_THRESHOLD);
+    tauri::async_runtime::spawn_blocking(move || {
+        app.state::<Library>()
+            .duplicates(threshold, transforms.unwrap_or(true))
+    })
+    .await
+    .map_err(|e| format!("Duplicate search failed: {}", e))
+}
+
+/// Tauri command to list the most recently opened designs, newest first
+#[tauri::command]
+fn get_recent(library: State<'_, Library>, limit: usize) -> Vec<LibraryEntry> {
+    library.recent(limit)
+}
+
+/// Tauri command to estimate top and bobbin thread use per color
+#[tauri::command]
+fn estimate_thread(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    options: Option<ThreadUsageOptions>,
+) -> Result<ThreadUsage, String> {
+    let options = options.unwrap_or_default();
+    designs.with(id, |d| estimate_thread_usage(&d.pattern, &options))
+}
+
+/// The given machine settings, or the saved machine speed's, checked
+fn machine_estimator(
+    settings: &SettingsStore,
+    machine_settings: Option<TimeEstimator>,
+) -> Result<TimeEstimator, String> {
+    let estimator = machine_settings.unwrap_or_else(|| settings.get().time_estimator());
+    if estimator.speed_spm.is_nan() || estimator.speed_spm <= 0.0 {
+        return Err("Machine speed must be positive".to_string());
+    }
+    Ok(estimator)
+}
+
+/// Tauri command to estimate the run time of an open design on a machine
+///
+/// Without machine settings the estimate uses the saved machine speed.
+#[tauri::command]
+fn estimate_time(
+    designs: State<'_, Designs>,
+    settings: State<'_, SettingsStore>,
+    id: DesignId,
+    machine_settings: Option<TimeEstimator>,
+) -> Result<TimeEstimate, String> {
+    let estimator = machine_estimator(&settings, machine_settings)?;
+    designs.with(id, |d| estimator.estimate(&d.pattern))
+}
+
+/// Tauri command to sample the run time of an open design for the stitch
+/// simulator's time slider
+///
+/// Samples fall every `sample_every` records, 100 by default, and on both
+/// sides of each trim and color change.
+#[tauri::command]
+fn simulate_runtime(
+    designs: State<'_, Designs>,
+    settings: State<'_, SettingsStore>,
+    id: DesignId,
+    machine_settings: Option<TimeEstimator>,
+    sample_every: Option<usize>,
+) -> Result<RunTimeline, String> {
+    let estimator = machine_estimator(&settings, machine_settings)?;
+    let sample_every = sample_every.unwrap_or(DEFAULT_SAMPLE_EVERY);
+    designs.with(id, |d| estimator.timeline(&d.pattern, sample_every))
+}
+
+/// Tauri command to find the last record of an open design the machine has
+/// finished after `seconds`; null before the first
+#[tauri::command]
+fn time_to_stitch(
+    designs: State<'_, Designs>,
+    settings: State<'_, SettingsStore>,
+    id: DesignId,
+    seconds: f64,
+    machine_settings: Option<TimeEstimator>,
+) -> Result<Option<TimePoint>, String> {
+    let estimator = machine_estimator(&settings, machine_settings)?;
+    designs.with(id, |d| estimator.record_at(&d.pattern, seconds))
+}
+
+/// Tauri command to analyze stitch quality of an open design or a file on disk
+#[tauri::command]
+fn analyze_design(
+    designs: State<'_, Designs>,
+    settings: State<'_, SettingsStore>,
+    id: Option<DesignId>,
+    path: Option<String>,
+    options: Option<AnalysisOptions>,
+) -> Result<DesignAnalysis, String> {
+    let options = options.unwrap_or_default();
+    match (id, path) {
+        (Some(id), _) => designs.with(id, |d| analyze(&d.pattern, &options)),
+        (None, Some(path)) => {
+            let pattern = read_design(&path, &settings.get().parse_options())?.pattern;
+            Ok(analyze(&pattern, &options))
+        }
+        (None, None) => Err("Either a design id or a path is required".to_string()),
+    }
+}
+
+/// Tauri command to find the places an open design is likely to break
+/// thread: dense spots, runs of tiny stitches, short stitches doubling
+/// back, and long stitches over dense stitching
+///
+/// Without thresholds the saved ones are used.
+#[tauri::command]
+fn analyze_risks(
+    designs: State<'_, Designs>,
+    settings: State<'_, SettingsStore>,
+    id: DesignId,
+    thresholds: Option<RiskThresholds>,
+) -> Result<Vec<Risk>, String> {
+    let thresholds = thresholds.unwrap_or_else(|| settings.get().risk_thresholds);
+    designs.with(id, |d| d.pattern.thread_break_risks(&thresholds))
+}
+
+/// Tauri command to list the runs of a design that look like underlay,
+/// each with how sure the guess is
+#[tauri::command]
+fn detect_underlay(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    options: Option<UnderlayOptions>,
+) -> Result<Vec<UnderlayRun>, String> {
+    let options = options.unwrap_or_default();
+    designs.with(id, |d| d.pattern.detect_underlay(&options))
+}
+
+/// Tauri command to report what changed from the design at `path_a` to the
+/// one at `path_b`
+///
+/// Penetrations count as unchanged within `tolerance_mm`, 0.5mm by default.
+#[tauri::command]
+async fn compare_designs(
+    path_a: String,
+    path_b: String,
+    tolerance_mm: Option<f64>,
+) -> Result<DesignDiff, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let options = ParseOptions::default();
+        let a = read_design(&path_a, &options)?.pattern;
+        let b = read_design(&path_b, &options)?.pattern;
+        Ok(compare_patterns(
+            &a,
+            &b,
+            tolerance_mm.unwrap_or(DEFAULT_MATCH_TOLERANCE_MM),
+        ))
+    })
+    .await
+    .map_err(|e| format!("Comparison failed: {}", e))?
+}
+
+/// Tauri command to check whether a design fits a catalog hoop, the
+/// default hoop setting when `hoop_id` is missing
+///
+/// `pattern_bounds` are in `unit`, native 0.1mm by default.
+#[tauri::command]
+fn check_hoop_fit(
+    settings: State<'_, SettingsStore>,
+    pattern_bounds: Bounds,
+    hoop_id: Option<String>,
+    margin_mm: Option<f64>,
+    unit: Option<Unit>,
+) -> Result<HoopFit, String> {
+    let hoop_id = hoop_id
+        .or_else(|| settings.get().hoop)
+        .ok_or_else(|| "No hoop given".to_string())?;
+    let hoop = find_hoop(&hoop_id).ok_or_else(|| format!("Unknown hoop: {}", hoop_id))?;
+    let bounds = unit
+        .unwrap_or(Unit::Native)
+        .bounds_to_native(&pattern_bounds);
+
+    Ok(hoops::check_hoop_fit(
+        &bounds,
+        hoop,
+        margin_mm.unwrap_or(DEFAULT_HOOP_MARGIN_MM),
+    ))
+}
+
+/// Tauri command to list every catalog hoop a design fits, smallest first
+///
+/// `pattern_bounds` are in `unit`, native 0.1mm by default.
+#[tauri::command]
+fn suggest_hoops(
+    pattern_bounds: Bounds,
+    margin_mm: Option<f64>,
+    unit: Option<Unit>,
+) -> Vec<HoopFit> {
+    let bounds = unit
+        .unwrap_or(Unit::Native)
+        .bounds_to_native(&pattern_bounds);
+    hoops::suggest_hoops(&bounds, margin_mm.unwrap_or(DEFAULT_HOOP_MARGIN_MM))
+}
+
+/// Tauri command to get the size and stitch lengths of an open design in
+/// `unit`, the saved preference by default
+#[tauri::command]
+fn get_design_stats(
+    designs: State<'_, Designs>,
+    settings: State<'_, SettingsStore>,
+    id: DesignId,
+    unit: Option<Unit>,
+) -> Result<DesignStats, String> {
+    let unit = unit.unwrap_or_else(|| settings.get().unit);
+    designs.with(id, |d| DesignStats::new(&d.pattern, unit))
+}
+
+/// Tauri command for the measure tool: the straight line, sewn path and
+/// needle drops from record `from_index` to `to_index`, in `unit` or the
+/// preferred unit
+#[tauri::command]
+fn measure(
+    designs: State<'_, Designs>,
+    settings: State<'_, SettingsStore>,
+    id: DesignId,
+    from_index: usize,
+    to_index: usize,
+    unit: Option<Unit>,
+) -> Result<Measurement, String> {
+    let unit = unit.unwrap_or_else(|| settings.get().unit);
+    designs
+        .with(id, |d| d.pattern.measure(from_index, to_index, unit))?
+        .ok_or_else(|| format!("Stitch {} does not exist", from_index.max(to_index)))
+}
+
+/// Tauri command to measure the sewn length, size and needle drops of one
+/// color block, in `unit` or the preferred unit
+#[tauri::command]
+fn measure_block(
+    designs: State<'_, Designs>,
+    settings: State<'_, SettingsStore>,
+    id: DesignId,
+    block_index: usize,
+    unit: Option<Unit>,
+) -> Result<BlockMeasurement, String> {
+    let unit = unit.unwrap_or_else(|| settings.get().unit);
+    designs
+        .with(id, |d| d.pattern.measure_block(block_index, unit))?
+        .ok_or_else(|| format!("Color block {} does not exist", block_index))
+}
+
+/// Tauri command to get the unit the user prefers lengths shown in
+#[tauri::command]
+fn get_unit_preference(settings: State<'_, SettingsStore>) -> Unit {
+    settings.get().unit
+}
+
+/// Tauri command to save the unit the user prefers lengths shown in
+#[tauri::command]
+fn set_unit_preference(settings: State<'_, SettingsStore>, unit: Unit) -> Result<(), String> {
+    settings
+        .update(|s| s.unit = unit)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Tauri command to list the built-in machine profiles
+#[tauri::command]
+fn list_machines() -> Vec<MachineProfile> {
+    MACHINES.to_vec()
+}
+
+/// Tauri command to check an open design against a machine's limits
+///
+/// Pass either a built-in `machine_id` or a custom `profile`; the profile
+/// wins when both are given.
+#[tauri::command]
+fn validate_for_machine(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    machine_id: Option<String>,
+    profile: Option<MachineProfile>,
+) -> Result<Vec<MachineViolation>, String> {
+    let machine = match (profile, machine_id) {
+        (Some(profile), _) => profile,
+        (None, Some(machine_id)) => find_machine(&machine_id)
+            .cloned()
+            .ok_or_else(|| format!("Unknown machine: {}", machine_id))?,
+        (None, None) => return Err("No machine given".to_string()),
+    };
+    designs.with(id, |d| machines::validate_for_machine(&d.pattern, &machine))
+}
+
+/// Tauri command to get a simplified stitch list for drawing at overview zoom
+///
+/// Only the display copy is reduced; no dropped point lies further than
+/// `tolerance_mm` from the drawn path.
+#[tauri::command]
+fn get_display_stitches(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    tolerance_mm: f64,
+) -> Result<Vec<Stitch>, String> {
+    designs.with(id, |d| d.pattern.decimate(tolerance_mm))
+}
+
+/// Tauri command to find the stitch nearest a canvas point
+///
+/// The point and `max_dist` are in `unit`, native 0.1mm by default.
+#[tauri::command]
+fn find_nearest_stitch(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    x: f64,
+    y: f64,
+    max_dist: f64,
+    unit: Option<Unit>,
+) -> Result<Option<StitchHit>, String> {
+    let unit = unit.unwrap_or(Unit::Native);
+    let (x, y, max_dist) = (
+        unit.to_native(x),
+        unit.to_native(y),
+        unit.to_native(max_dist),
+    );
+    designs.with(id, |d| d.index.nearest(x, y, max_dist))
+}
+
+/// Tauri command to list the stitches inside a canvas rectangle
+///
+/// The corners are in `unit`, native 0.1mm by default.
+#[tauri::command]
+fn find_stitches_in_rect(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    min_x: f64,
+    min_y: f64,
+    max_x: f64,
+    max_y: f64,
+    unit: Option<Unit>,
+) -> Result<Vec<StitchHit>, String> {
+    let rect = unit.unwrap_or(Unit::Native).bounds_to_native(&Bounds {
+        min_x,
+        min_y,
+        max_x,
+        max_y,
+    });
+    designs.with(id, |d| {
+        d.index
+            .in_rect(rect.min_x, rect.min_y, rect.max_x, rect.max_y)
+    })
+}
+
+/// Tauri command to transform an open design without re-reading the file
+#[tauri::command]
+fn transform_design(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    operation: TransformOperation,
+    options: Option<TransformOptions>,
+) -> Result<DesignUpdate<Vec<TransformWarning>>, String> {
+    designs.transform(id, &operation, &options.unwrap_or_default())
+}
+
+/// Tauri command to load several designs and open them as one pattern
+///
+/// `offsets` shifts each design in 0.1mm units; missing entries default to
+/// no shift. Designs are separated by a color change unless disabled. The
+/// merged design takes the format of the first file.
+#[tauri::command]
+fn merge_designs(
+    designs: State<'_, Designs>,
+    paths: Vec<String>,
+    offsets: Option<Vec<[f64; 2]>>,
+    insert_color_change: Option<bool>,
+) -> Result<DesignHandle, String> {
+    let offsets = offsets.unwrap_or_default();
+    let mut merged = Pattern::new();
+    let mut format = DesignFormat::Dst;
+
+    for (index, path) in paths.into_iter().enumerate() {
+        let design = read_design(&path, &ParseOptions::default())?;
+        if index == 0 {
+            format = design.format;
+        }
+        let [dx, dy] = offsets.get(index).copied().unwrap_or([0.0, 0.0]);
+        merged.append(&design.pattern, dx, dy, insert_color_change.unwrap_or(true));
+    }
+
+    let design = OpenDesign::new(None, LoadedDesign::new(format, merged));
+    let summary = design.summary();
+    Ok(DesignHandle {
+        id: designs.insert(design),
+        summary,
+    })
+}
+
+/// Tauri command to sew `text` in the built-in satin font and open it as a
+/// new design, ready to merge with another
+///
+/// `spacing_mm` widens the gap between letters; `arc_deg` bends the
+/// baseline through that angle, upward when positive.
+#[tauri::command]
+fn generate_text(
+    designs: State<'_, Designs>,
+    text: String,
+    height_mm: f64,
+    spacing_mm: Option<f64>,
+    arc_deg: Option<f64>,
+) -> Result<DesignHandle, String> {
+    let pattern = sew_text(&text, height_mm, spacing_mm.unwrap_or(0.0), arc_deg)
+        .map_err(|e| e.to_string())?;
+    let design = OpenDesign::new(None, LoadedDesign::new(DesignFormat::Dst, pattern));
+    let summary = design.summary();
+    Ok(DesignHandle {
+        id: designs.insert(design),
+        summary,
+    })
+}
+
+/// Tauri command to digitize a PNG or JPEG into fill blocks of at most
+/// `max_colors` colors and open it as a new design
+///
+/// Runs off the main thread, emitting `digitize-progress` as each color is
+/// traced and each region filled.
+#[tauri::command]
+async fn import_image(
+    app: tauri::AppHandle,
+    designs: State<'_, Designs>,
+    path: String,
+    max_colors: usize,
+    options: Option<DigitizeOptions>,
+) -> Result<DesignHandle, String> {
+    let options = options.unwrap_or_default();
+    let pattern = tauri::async_runtime::spawn_blocking(move || {
+        let data = fs::read(&path).map_err(|e| format!("Failed to read file: {}", e))?;
+        digitize_image(&data, max_colors, &options, |progress| {
+            let _ = app.emit("digitize-progress", progress);
+        })
+        .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| format!("Digitizing failed: {}", e))??;
+
+    let design = OpenDesign::new(None, LoadedDesign::new(DesignFormat::Dst, pattern));
+    let summary = design.summary();
+    Ok(DesignHandle {
+        id: designs.insert(design),
+        summary,
+    })
+}
+
+/// Tauri command to cut an open design down to a rectangle, or to the
+/// sewing field of catalog hoop `hoop_id` centred on the origin
+///
+/// `rect` is in `unit`, native 0.1mm by default. Stitches crossing the edge
+/// are clipped to it unless `mode` filters them out whole.
+#[tauri::command]
+fn crop_design(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    rect: Option<Bounds>,
+    hoop_id: Option<String>,
+    margin_mm: Option<f64>,
+    unit: Option<Unit>,
+    mode: Option<CropMode>,
+) -> Result<DesignUpdate<CropReport>, String> {
+    let rect = match (rect, hoop_id) {
+        (Some(rect), None) => unit.unwrap_or(Unit::Native).bounds_to_native(&rect),
+        (None, Some(hoop_id)) => {
+            let hoop = find_hoop(&hoop_id).ok_or_else(|| format!("Unknown hoop: {}", hoop_id))?;
+            hoop_field(hoop, margin_mm.unwrap_or(DEFAULT_HOOP_MARGIN_MM))
+        }
+        _ => return Err("Give either a rectangle or a hoop to crop to".to_string()),
+    };
+    designs.edit(id, "Crop", |pattern| {
+        pattern
+            .crop(&rect, mode.unwrap_or_default())
+            .map_err(|e| e.to_string())
+    })
+}
+
+/// Tauri command to copy part of an open design out as a new design, ready
+/// to save as a file of its own
+///
+/// A `rect` selection is in native 0.1mm units. With `recenter` the copy is
+/// moved so its middle sits at the origin; otherwise it keeps its place.
+#[tauri::command]
+fn extract_design(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    selection: Selection,
+    recenter: Option<bool>,
+) -> Result<DesignHandle, String> {
+    let (format, pattern) = designs.with(id, |d| {
+        d.pattern
+            .extract(&selection, recenter.unwrap_or(false))
+            .map(|pattern| (d.format, pattern))
+            .map_err(|e| e.to_string())
+    })??;
+    let design = OpenDesign::new(None, LoadedDesign::new(format, pattern));
+    let summary = design.summary();
+    Ok(DesignHandle {
+        id: designs.insert(design),
+        summary,
+    })
+}
+
+/// Tauri command to copy part of an open design to the clipboard
+///
+/// Returns the copied stitches as text the frontend can also put on the
+/// system clipboard, for pasting into another window.
+#[tauri::command]
+fn copy_selection(
+    designs: State<'_, Designs>,
+    clipboard: State<'_, Clipboard>,
+    id: DesignId,
+    selection: Selection,
+) -> Result<Copied, String> {
+    clipboard.copy(&designs, id, &selection)
+}
 
-    // Parse the DST data
-    let pattern = parse_dst(&data).map_err(|e| format!("Failed to parse DST: {}", e))?;
+/// Tauri command to move part of an open design to the clipboard
+#[tauri::command]
+fn cut_selection(
+    designs: State<'_, Designs>,
+    clipboard: State<'_, Clipboard>,
+    id: DesignId,
+    selection: Selection,
+) -> Result<DesignUpdate<Cut>, String> {
+    clipboard.cut(&designs, id, &selection)
+}
+
+/// Tauri command to sew the clipboard after the rest of an open design,
+/// offset by (`offset_x`, `offset_y`) in native 0.1mm units
+///
+/// `text` is clipboard text copied in another window; without it the
+/// stitches this window copied are pasted.
+#[tauri::command]
+fn paste(
+    designs: State<'_, Designs>,
+    clipboard: State<'_, Clipboard>,
+    id: DesignId,
+    offset_x: f64,
+    offset_y: f64,
+    as_new_block: bool,
+    text: Option<String>,
+) -> Result<DesignUpdate<Vec<EditWarning>>, String> {
+    clipboard.paste(
+        &designs,
+        id,
+        offset_x,
+        offset_y,
+        as_new_block,
+        text.as_deref(),
+    )
+}
+
+/// Tauri command to tile an open design in a grid of copies
+#[tauri::command]
+fn array_design(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    layout: RepeatLayout,
+) -> Result<DesignUpdate<()>, String> {
+    designs.edit(id, "Array", |pattern| {
+        *pattern = pattern.repeat(&layout).map_err(|e| e.to_string())?;
+        Ok(())
+    })
+}
+
+/// Tauri command to run cleanup filters over an open design
+#[tauri::command]
+fn cleanup_design(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    options: Option<CleanupOptions>,
+) -> Result<DesignUpdate<CleanupSummary>, String> {
+    let options = options.unwrap_or_default();
+    designs.edit(id, "Cleanup", |pattern| {
+        let result = cleanup(std::mem::take(pattern), &options);
+        *pattern = result.pattern;
+        Ok(result.summary)
+    })
+}
+
+/// Tauri command to reverse color blocks where that shortens travel
+///
+/// A dry run only reports what would change.
+#[tauri::command]
+fn optimize_jumps(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    dry_run: Option<bool>,
+) -> Result<DesignUpdate<JumpReport>, String> {
+    if dry_run.unwrap_or(false) {
+        return designs.with(id, |d| DesignUpdate {
+            summary: d.summary(),
+            report: d.pattern.clone().optimize_jumps(true),
+        });
+    }
+    designs.edit(id, "Optimize jumps", |pattern| {
+        Ok(pattern.optimize_jumps(false))
+    })
+}
 
-    Ok(pattern)
+/// Tauri command to group same-color blocks where they don't overlap
+#[tauri::command]
+fn optimize_colors(
+    designs: State<'_, Designs>,
+    id: DesignId,
+) -> Result<DesignUpdate<ColorSortReport>, String> {
+    designs.edit(id, "Optimize colors", |pattern| {
+        Ok(pattern.optimize_colors())
+    })
+}
+
+/// Tauri command to delete records from an open design
+///
+/// Deleting a ColorChange merges its color block into the one before it.
+#[tauri::command]
+fn delete_stitches(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    indices: Vec<usize>,
+) -> Result<DesignUpdate<Vec<EditWarning>>, String> {
+    designs.edit_stitches(id, "Delete stitches", |pattern| {
+        pattern.delete_stitches(&indices)
+    })
+}
+
+/// Tauri command to move one record of an open design to (x, y) in `unit`,
+/// native 0.1mm by default
+#[tauri::command]
+fn move_stitch(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    index: usize,
+    x: f64,
+    y: f64,
+    unit: Option<Unit>,
+) -> Result<DesignUpdate<Vec<EditWarning>>, String> {
+    let unit = unit.unwrap_or(Unit::Native);
+    let (x, y) = (unit.to_native(x), unit.to_native(y));
+    designs.edit_stitches(id, "Move stitch", |pattern| {
+        pattern.move_stitch(index, x, y)
+    })
+}
+
+/// Tauri command to insert a record after `after_index`, or first when it is null
+///
+/// (x, y) is in `unit`, native 0.1mm by default.
+#[tauri::command]
+fn insert_stitch(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    after_index: Option<usize>,
+    x: f64,
+    y: f64,
+    command: StitchCommand,
+    unit: Option<Unit>,
+) -> Result<DesignUpdate<Vec<EditWarning>>, String> {
+    let unit = unit.unwrap_or(Unit::Native);
+    let (x, y) = (unit.to_native(x), unit.to_native(y));
+    designs.edit_stitches(id, "Insert stitch", |pattern| {
+        pattern.insert_stitch(after_index, x, y, command)
+    })
+}
+
+/// Tauri command to change the command of one record of an open design
+#[tauri::command]
+fn change_command(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    index: usize,
+    command: StitchCommand,
+) -> Result<DesignUpdate<Vec<EditWarning>>, String> {
+    designs.edit_stitches(id, "Change command", |pattern| {
+        pattern.change_command(index, command)
+    })
+}
+
+/// Tauri command to move the selected records of an open design by
+/// (`dx_mm`, `dy_mm`), as arrow keys do
+///
+/// Stitches into and out of the selection stretch to follow it, or become
+/// jumps where they would grow past the longest stitch.
+#[tauri::command]
+fn nudge_selection(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    selection: Selection,
+    dx_mm: f64,
+    dy_mm: f64,
+) -> Result<DesignUpdate<Vec<EditWarning>>, String> {
+    designs.edit_stitches(id, "Nudge", |pattern| {
+        pattern.nudge(&selection, dx_mm * UNITS_PER_MM, dy_mm * UNITS_PER_MM)
+    })
+}
+
+/// Tauri command to snap the selected records of an open design to a grid
+/// of `grid_mm`, the grid setting by default
+#[tauri::command]
+fn snap_selection_to_grid(
+    designs: State<'_, Designs>,
+    settings: State<'_, SettingsStore>,
+    id: DesignId,
+    selection: Selection,
+    grid_mm: Option<f64>,
+) -> Result<DesignUpdate<Vec<EditWarning>>, String> {
+    let grid_mm = grid_mm.unwrap_or_else(|| settings.get().grid_mm);
+    designs.edit_stitches(id, "Snap to grid", |pattern| {
+        pattern.snap_to_grid(&selection, grid_mm * UNITS_PER_MM)
+    })
+}
+
+/// Tauri command to start a new color block at `stitch_index`
+///
+/// A Trim goes before the new ColorChange when `trim` is set. The new block
+/// takes `color`, or the thread of the block it was split from.
+#[tauri::command]
+fn split_block(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    stitch_index: usize,
+    trim: Option<bool>,
+    color: Option<ThreadColor>,
+) -> Result<DesignUpdate<Vec<EditWarning>>, String> {
+    designs.edit_stitches(id, "Split block", |pattern| {
+        pattern.split_block(stitch_index, trim.unwrap_or(false), color)
+    })
+}
+
+/// Tauri command to merge color block `block_index` into the block before it
+#[tauri::command]
+fn merge_blocks(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    block_index: usize,
+) -> Result<DesignUpdate<Vec<EditWarning>>, String> {
+    designs.edit_stitches(id, "Merge blocks", |pattern| {
+        pattern.merge_blocks(block_index)
+    })
+}
+
+/// Tauri command to remove color block `block_index` from an open design
+///
+/// The block after it is reached with a jump from where the previous one ended.
+#[tauri::command]
+fn delete_block(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    block_index: usize,
+) -> Result<DesignUpdate<Vec<EditWarning>>, String> {
+    designs.edit_stitches(id, "Delete block", |pattern| {
+        pattern.delete_block(block_index)
+    })
+}
+
+/// Tauri command to set the thread of color block `block_index`
+///
+/// With `catalog`, the block takes that chart's thread: the given catalog
+/// number, or the closest match to `rgb` when none is given.
+#[tauri::command]
+fn set_block_color(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    block_index: usize,
+    rgb: [u8; 3],
+    catalog: Option<ThreadRef>,
+) -> Result<DesignUpdate<Vec<EditWarning>>, String> {
+    let color = match catalog {
+        Some(thread) => thread.resolve(rgb)?.thread_color(),
+        None => ThreadColor::new(rgb),
+    };
+    designs.edit_stitches(id, "Set block color", |pattern| {
+        pattern.set_block_color(block_index, color)
+    })
+}
+
+/// Tauri command to list a built-in thread chart, the thread brand setting's
+/// when `brand` is missing
+#[tauri::command]
+fn get_palette(
+    settings: State<'_, SettingsStore>,
+    brand: Option<ThreadBrand>,
+) -> Vec<CatalogThread> {
+    brand
+        .unwrap_or_else(|| settings.get().thread_brand)
+        .palette()
+}
+
+/// Tauri command to load each color block of an open design on a needle of
+/// a `needle_count`-needle machine, returning the needle of each block
+#[tauri::command]
+fn assign_needles(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    strategy: NeedleStrategy,
+    needle_count: u8,
+) -> Result<DesignUpdate<Vec<u8>>, String> {
+    designs.edit(id, "Assign needles", |pattern| {
+        needle_assignment::assign_needles(pattern, &strategy, needle_count)
+            .map_err(|e| e.to_string())
+    })
+}
+
+/// Tauri command to read the needle of each color block of an open design
+#[tauri::command]
+fn get_needles(designs: State<'_, Designs>, id: DesignId) -> Result<Vec<Option<u8>>, String> {
+    designs.with(id, |d| block_needles(&d.pattern))
+}
+
+/// Tauri command to match every block's thread in `from_brand` to the
+/// closest thread in `to_brand`
+///
+/// The report lists each match with its color difference. With `apply` set
+/// the blocks take the matched threads.
+#[tauri::command]
+fn convert_palette(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    from_brand: ThreadBrand,
+    to_brand: ThreadBrand,
+    apply: Option<bool>,
+) -> Result<DesignUpdate<Vec<PaletteMatch>>, String> {
+    let mut report = Vec::new();
+    let summary = if apply.unwrap_or(false) {
+        designs
+            .edit_stitches(id, "Convert palette", |pattern| {
+                report = match_palette(pattern, from_brand, to_brand);
+                let colors = report
+                    .iter()
+                    .map(|row| (row.block, row.matched.thread_color()))
+                    .collect();
+                pattern.set_block_colors(colors)
+            })?
+            .summary
+    } else {
+        designs.with(id, |d| {
+            report = match_palette(&d.pattern, from_brand, to_brand);
+            d.summary()
+        })?
+    };
+    Ok(DesignUpdate { summary, report })
+}
+
+/// Tauri command to sew a basting box `margin_mm` outside an open design
+/// before it, as a new first color block
+#[tauri::command]
+fn generate_basting(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    margin_mm: f64,
+    stitch_len_mm: f64,
+    center_cross: Option<bool>,
+) -> Result<DesignUpdate<Vec<EditWarning>>, String> {
+    designs.edit_stitches(id, "Add basting", |pattern| {
+        pattern.add_basting(margin_mm, stitch_len_mm, center_cross.unwrap_or(false))
+    })
+}
+
+/// Tauri command to sew a running outline around an open design after
+/// it, as a new last color block
+#[tauri::command]
+fn generate_outline(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    options: Option<OutlineOptions>,
+) -> Result<DesignUpdate<Vec<EditWarning>>, String> {
+    let options = options.unwrap_or_default();
+    designs.edit_stitches(id, "Add outline", |pattern| {
+        pattern.generate_outline(&options)
+    })
+}
+
+/// Tauri command to get the outline `generate_outline` would sew, without
+/// adding it to the design
+#[tauri::command]
+fn preview_outline(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    options: Option<OutlineOptions>,
+) -> Result<Vec<Stitch>, String> {
+    let options = options.unwrap_or_default();
+    designs
+        .with(id, |d| d.pattern.outline_stitches(&options))?
+        .map_err(|e| e.to_string())
+}
+
+/// Tauri command to sew a drawn shape at the end of an open design
+///
+/// The shape is shifted by `offset_mm` and joined with a trim; with
+/// `new_color` it gets a color block of its own.
+#[tauri::command]
+fn add_primitive(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    primitive: Primitive,
+    offset_mm: Option<[f64; 2]>,
+    new_color: Option<bool>,
+) -> Result<DesignUpdate<()>, String> {
+    let shape = primitive.to_pattern().map_err(|e| e.to_string())?;
+    let [dx, dy] = offset_mm.unwrap_or([0.0, 0.0]);
+    designs.edit(id, "Add shape", |pattern| {
+        pattern.append(
+            &shape,
+            dx * UNITS_PER_MM,
+            dy * UNITS_PER_MM,
+            new_color.unwrap_or(false),
+        );
+        Ok(())
+    })
+}
+
+/// Tauri command to sew the color blocks of an open design in a new order
+///
+/// `new_order` lists every block index once. Orders that would sew a block
+/// under one it used to cover are refused unless `allow_overlap` is set.
+#[tauri::command]
+fn reorder_blocks(
+    designs: State<'_, Designs>,
+    id: DesignId,
+    new_order: Vec<usize>,
+    allow_overlap: Option<bool>,
+) -> Result<DesignUpdate<()>, String> {
+    designs.edit(id, "Reorder blocks", |pattern| {
+        pattern
+            .reorder_blocks(&new_order, allow_overlap.unwrap_or(false))
+            .map_err(|e| e.to_string())
+    })
+}
+
+/// Tauri command to revert the latest edit of an open design
+///
+/// The report's label is None when there was nothing to undo.
+#[tauri::command]
+fn undo_design(
+    designs: State<'_, Designs>,
+    id: DesignId,
+) -> Result<DesignUpdate<HistoryStep>, String> {
+    designs.undo(id)
+}
+
+/// Tauri command to replay the latest undone edit of an open design
+#[tauri::command]
+fn redo_design(
+    designs: State<'_, Designs>,
+    id: DesignId,
+) -> Result<DesignUpdate<HistoryStep>, String> {
+    designs.redo(id)
+}
+
+/// Tauri command to cap the undo history memory of each open design, in MB
+#[tauri::command]
+fn set_history_limit(designs: State<'_, Designs>, limit_mb: usize) {
+    designs.set_history_limit(limit_mb.saturating_mul(1024 * 1024));
+}
+
+/// Tauri command to list earlier sessions whose edits can be recovered,
+/// newest first; the frontend calls it at startup
+#[tauri::command]
+fn recover_designs(recovery: State<'_, Recovery>) -> Result<Vec<RecoverableSession>, String> {
+    recovery.sessions().map_err(|e| e.to_string())
+}
+
+/// Tauri command to reopen the designs an earlier session autosaved
+#[tauri::command]
+fn restore_session(
+    designs: State<'_, Designs>,
+    recovery: State<'_, Recovery>,
+    session: String,
+) -> Result<Vec<ReopenedDesign>, String> {
+    recovery
+        .restore(&session, &designs)
+        .map_err(|e| e.to_string())
+}
+
+/// Tauri command to delete an earlier session's autosave without restoring it
+#[tauri::command]
+fn discard_session(recovery: State<'_, Recovery>, session: String) -> Result<(), String> {
+    recovery.discard(&session).map_err(|e| e.to_string())
+}
+
+/// Tauri command to change the autosave interval and how many sessions are kept
+///
+/// The interval is saved as the autosave setting.
+#[tauri::command]
+fn set_autosave(
+    recovery: State<'_, Recovery>,
+    settings: State<'_, SettingsStore>,
+    options: AutosaveOptions,
+) -> Result<(), String> {
+    settings
+        .update(|s| s.autosave_interval_secs = options.interval_secs)
+        .map_err(|e| e.to_string())?;
+    recovery.set_options(options);
+    Ok(())
+}
+
+/// Tauri command to get the saved settings
+#[tauri::command]
+fn get_settings(settings: State<'_, SettingsStore>) -> Settings {
+    settings.get()
+}
+
+/// Tauri command to validate and save new settings, returning them as stored
+///
+/// Settings that running features hold, like the autosave interval, take
+/// effect at once.
+#[tauri::command]
+fn set_settings(
+    store: State<'_, SettingsStore>,
+    recovery: State<'_, Recovery>,
+    settings: Settings,
+) -> Result<Settings, String> {
+    let settings = store.set(settings).map_err(|e| e.to_string())?;
+    recovery.set_options(AutosaveOptions {
+        interval_secs: settings.autosave_interval_secs,
+        ..recovery.options()
+    });
+    Ok(settings)
+}
+
+/// Autosave edited designs for as long as the app runs
+///
+/// Checks once a second so a new interval takes effect promptly. Failures
+/// are reported as "autosave-failed" events and retried at the next interval.
+fn autosave_loop(app: tauri::AppHandle) {
+    let mut last = std::time::Instant::now();
+    loop {
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        let recovery = app.state::<Recovery>();
+        let interval = recovery.options().interval_secs;
+        if interval == 0 || last.elapsed().as_secs() < interval {
+            continue;
+        }
+        last = std::time::Instant::now();
+        if let Err(e) = recovery.autosave(&app.state::<Designs>()) {
+            let _ = app.emit("autosave-failed", e.to_string());
+        }
+    }
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -24,7 +1631,143 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_dialog::init())
-        .invoke_handler(tauri::generate_handler![load_design])
+        .manage(PendingLoads::default())
+        .manage(Designs::default())
+        .manage(Clipboard::default())
+        .setup(|app| {
+            let dir = app.path().app_data_dir()?;
+            app.manage(Library::open(dir.clone()));
+            let watch_handle = app.handle().clone();
+            let watcher =
+                FolderWatcher::start(&dir, move |paths| refresh_library(&watch_handle, paths))?;
+            app.manage(watcher);
+            let settings = SettingsStore::open(&app.path().app_config_dir()?);
+            let recovery = Recovery::new(dir.join("recovery"));
+            recovery.set_options(AutosaveOptions {
+                interval_secs: settings.get().autosave_interval_secs,
+                ..AutosaveOptions::default()
+            });
+            app.manage(settings);
+            app.manage(recovery);
+            let handle = app.handle().clone();
+            std::thread::spawn(move || autosave_loop(handle));
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            load_design,
+            get_design,
+            load_design_streamed,
+            get_design_info,
+            cancel_load,
+            close_design,
+            list_open_designs,
+            save_design,
+            save_project,
+            open_project,
+            export_design,
+            generate_thumbnail,
+            index_folder,
+            search_library,
+            get_recent,
+            find_duplicates,
+            add_watch_folder,
+            remove_watch_folder,
+            list_watch_folders,
+            export_animation,
+            export_worksheet,
+            convert_batch,
+            estimate_thread,
+            estimate_time,
+            simulate_runtime,
+            time_to_stitch,
+            analyze_design,
+            analyze_risks,
+            detect_underlay,
+            check_hoop_fit,
+            suggest_hoops,
+            get_design_stats,
+            measure,
+            measure_block,
+            get_unit_preference,
+            set_unit_preference,
+            list_machines,
+            validate_for_machine,
+            get_display_stitches,
+            find_nearest_stitch,
+            find_stitches_in_rect,
+            transform_design,
+            merge_designs,
+            generate_text,
+            import_image,
+            array_design,
+            crop_design,
+            extract_design,
+            copy_selection,
+            cut_selection,
+            paste,
+            cleanup_design,
+            optimize_jumps,
+            optimize_colors,
+            delete_stitches,
+            move_stitch,
+            insert_stitch,
+            change_command,
+            nudge_selection,
+            snap_selection_to_grid,
+            split_block,
+            merge_blocks,
+            delete_block,
+            reorder_blocks,
+            generate_basting,
+            generate_outline,
+            preview_outline,
+            add_primitive,
+            set_block_color,
+            get_palette,
+            convert_palette,
+            assign_needles,
+            get_needles,
+            compare_designs,
+            undo_design,
+            redo_design,
+            set_history_limit,
+            recover_designs,
+            restore_session,
+            discard_session,
+            set_autosave,
+            get_settings,
+            set_settings
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_design_info_matches_load() {
+        let files: [(&str, &[u8]); 3] = [
+            (
+                "design.t01",
+                include_bytes!("../crates/embrocad-core/tests/fixtures/legacy/design.t01"),
+            ),
+            (
+                "design.exp",
+                include_bytes!("../crates/embrocad-core/tests/fixtures/legacy/design.exp"),
+            ),
+            (
+                "sequins.dst",
+                include_bytes!("../crates/embrocad-core/tests/fixtures/dst/sequins.dst"),
+            ),
+        ];
+        for (name, data) in files {
+            let options = ParseOptions::default();
+            let design = parse_design(name, data, &options, &mut LoadMonitor::none()).unwrap();
+            let loaded = serde_json::to_value(DesignSummary::new(design.format, &design.pattern));
+            let info = serde_json::to_value(read_design_info(name, data, &options).unwrap());
+            assert_eq!(info.unwrap(), loaded.unwrap(), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,329 @@
+// duplicates.rs - Exact and near-duplicate designs found by hash and stitch density
+
+use super::LibraryEntry;
+use crate::dst::{Pattern, StitchCommand};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+
+/// Cells along each side of a fingerprint
+const GRID: usize = 32;
+
+/// Likeness above which two designs are offered as duplicates
+pub const DEFAULT_DUPLICATE_THRESHOLD: f64 = 0.9;
+
+/// Stitch counts further apart than this ratio are never near-duplicates,
+/// which spares comparing most pairs
+const MIN_COUNT_RATIO: f64 = 0.5;
+
+/// Where a design's needle goes in, as a 32 x 32 grid of densities
+///
+/// The grid covers a square around the design's sewn extent, so it does not
+/// change when the design is moved, and the densest cell is 255, so it does
+/// not change with stitch count. Saved as base64.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint(Vec<u8>);
+
+impl Serialize for Fingerprint {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(&self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Fingerprint {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = STANDARD
+            .decode(String::deserialize(deserializer)?)
+            .map_err(D::Error::custom)?;
+        if bytes.len() != GRID * GRID {
+            return Err(D::Error::custom("fingerprint has the wrong size"));
+        }
+        Ok(Self(bytes))
+    }
+}
+
+impl Fingerprint {
+    /// Fingerprint of a pattern's penetrations; None when nothing is sewn
+    pub fn new(pattern: &Pattern) -> Option<Self> {
+        let sewn: Vec<(f64, f64)> = pattern
+            .stitches
+            .iter()
+            .filter(|s| s.command == StitchCommand::Stitch)
+            .map(|s| (s.x, s.y))
+            .collect();
+        let (min_x, min_y, max_x, max_y) = sewn.iter().fold(
+            (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+            |(x0, y0, x1, y1), &(x, y)| (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+        );
+        if sewn.is_empty() {
+            return None;
+        }
+
+        // Centre the extent in a square, so turning the design a quarter
+        // turns its grid the same way. Each penetration is shared between
+        // the four nearest cell centres, so mirroring the design mirrors
+        // the grid exactly even for points on cell edges.
+        let side = (max_x - min_x).max(max_y - min_y).max(f64::EPSILON);
+        let left = min_x - (side - (max_x - min_x)) / 2.0;
+        let top = min_y - (side - (max_y - min_y)) / 2.0;
+        let last = (GRID - 1) as f64;
+        let split = |value: f64, start: f64| {
+            let p = ((value - start) / side * GRID as f64 - 0.5).clamp(0.0, last);
+            let i = (p.floor() as usize).min(GRID - 2);
+            let f = p - i as f64;
+            [(i, 1.0 - f), (i + 1, f)]
+        };
+        let mut density = vec![0.0; GRID * GRID];
+        for &(x, y) in &sewn {
+            for (row, wy) in split(y, top) {
+                for (column, wx) in split(x, left) {
+                    density[row * GRID + column] += wy * wx;
+                }
+            }
+        }
+        let densest = density.iter().copied().fold(f64::MIN_POSITIVE, f64::max);
+        Some(Self(
+            density
+                .iter()
+                .map(|&d| (d / densest * 255.0).round() as u8)
+                .collect(),
+        ))
+    }
+
+    /// The grid turned and mirrored by `variant`, one of the eight
+    /// symmetries of a square; 0 leaves it as it is
+    fn variant(&self, variant: usize) -> Self {
+        let last = GRID - 1;
+        let mut cells = vec![0; GRID * GRID];
+        for y in 0..GRID {
+            for x in 0..GRID {
+                let (tx, ty) = match variant {
+                    0 => (x, y),
+                    1 => (last - y, x),
+                    2 => (last - x, last - y),
+                    3 => (y, last - x),
+                    4 => (last - x, y),
+                    5 => (x, last - y),
+                    6 => (y, x),
+                    _ => (last - y, last - x),
+                };
+                cells[ty * GRID + tx] = self.0[y * GRID + x];
+            }
+        }
+        Self(cells)
+    }
+
+    /// How alike two grids are, from 0 to 1: the density they share over
+    /// the density either has
+    fn overlap(&self, other: &Self) -> f64 {
+        let (shared, total) = self
+            .0
+            .iter()
+            .zip(&other.0)
+            .fold((0u32, 0u32), |(shared, total), (&a, &b)| {
+                (shared + a.min(b) as u32, total + a.max(b) as u32)
+            });
+        if total == 0 {
+            1.0
+        } else {
+            shared as f64 / total as f64
+        }
+    }
+}
+
+/// One design in a cluster of duplicates
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateMember {
+    pub entry: LibraryEntry,
+    /// Likeness to the cluster's first member, from 0 to 1
+    pub similarity: f64,
+    /// Byte for byte the same file as the first member
+    pub exact: bool,
+}
+
+/// Designs that are the same, or look the same, under different names
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateCluster {
+    /// Sorted by path; the first is the one the others are scored against
+    pub members: Vec<DuplicateMember>,
+}
+
+/// Representative of `i`'s set, flattening the path on the way
+fn root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+/// The variants of an entry's fingerprint that count as the same design:
+/// all eight with `transforms`, else just the fingerprint
+fn variants(entry: &LibraryEntry, transforms: bool) -> Vec<Fingerprint> {
+    let count = if transforms { 8 } else { 1 };
+    entry
+        .fingerprint
+        .iter()
+        .flat_map(|f| (0..count).map(|variant| f.variant(variant)))
+        .collect()
+}
+
+/// Likeness of two entries' designs, from 0 to 1, given the variants of
+/// the first; entries without fingerprints only match byte for byte
+fn similarity(a: &LibraryEntry, a_variants: &[Fingerprint], b: &LibraryEntry) -> f64 {
+    if a.hash == b.hash {
+        return 1.0;
+    }
+    let Some(fb) = &b.fingerprint else {
+        return 0.0;
+    };
+    a_variants
+        .iter()
+        .map(|fa| fa.overlap(fb))
+        .fold(0.0, f64::max)
+}
+
+/// Group entries whose designs are at least `threshold` alike
+///
+/// Entries are linked pairwise and a cluster is everything linked, so two
+/// members may be less alike than the threshold through a third. Only
+/// clusters of two or more are returned, largest first.
+pub fn find_duplicates(
+    entries: &[LibraryEntry],
+    threshold: f64,
+    transforms: bool,
+) -> Vec<DuplicateCluster> {
+    let mut order: Vec<usize> = (0..entries.len()).collect();
+    order.sort_by_key(|&i| entries[i].stitch_count);
+    let turned: Vec<Vec<Fingerprint>> = entries
+        .iter()
+        .map(|entry| variants(entry, transforms))
+        .collect();
+
+    // Union-find over entry indices
+    let mut parent: Vec<usize> = (0..entries.len()).collect();
+    for (position, &i) in order.iter().enumerate() {
+        for &j in &order[position + 1..] {
+            let (a, b) = (&entries[i], &entries[j]);
+            // Sorted by count, so every later entry is further off too
+            if (a.stitch_count as f64) < b.stitch_count as f64 * MIN_COUNT_RATIO {
+                break;
+            }
+            if similarity(a, &turned[i], b) >= threshold {
+                let (ri, rj) = (root(&mut parent, i), root(&mut parent, j));
+                parent[ri] = rj;
+            }
+        }
+    }
+
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in 0..entries.len() {
+        let r = root(&mut parent, i);
+        groups.entry(r).or_default().push(i);
+    }
+    let mut clusters: Vec<DuplicateCluster> = groups
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .map(|mut members| {
+            members.sort_by(|&a, &b| entries[a].path.cmp(&entries[b].path));
+            let (first, first_variants) = (&entries[members[0]], &turned[members[0]]);
+            DuplicateCluster {
+                members: members
+                    .iter()
+                    .map(|&i| DuplicateMember {
+                        entry: entries[i].clone(),
+                        similarity: similarity(first, first_variants, &entries[i]),
+                        exact: entries[i].hash == first.hash,
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    clusters.sort_by_key(|cluster| std::cmp::Reverse(cluster.members.len()));
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{DesignFormat, LoadedDesign};
+
+    /// An entry for `points` sewn in order, as if read from `path`
+    fn entry(path: &str, points: &[(f64, f64)]) -> LibraryEntry {
+        let mut pattern = Pattern::new();
+        for &(x, y) in points {
+            pattern.add_stitch(x, y, StitchCommand::Stitch);
+        }
+        pattern.calculate_bounds();
+        let design = LoadedDesign::new(DesignFormat::Dst, pattern);
+        let data: Vec<u8> = points
+            .iter()
+            .flat_map(|&(x, y)| [x.to_le_bytes(), y.to_le_bytes()].concat())
+            .collect();
+        LibraryEntry::new(path, &data, &design)
+    }
+
+    /// An L: a 40mm upright and a 20mm foot
+    fn letter_l() -> Vec<(f64, f64)> {
+        let upright = (0..=40).map(|i| (0.0, i as f64 * 10.0));
+        let foot = (1..=20).map(|i| (i as f64 * 10.0, 400.0));
+        upright.chain(foot).collect()
+    }
+
+    #[test]
+    fn test_clusters_moved_and_mirrored_copies() {
+        let l = letter_l();
+        let moved: Vec<_> = l.iter().map(|&(x, y)| (x + 537.0, y - 212.0)).collect();
+        let mirrored: Vec<_> = l.iter().map(|&(x, y)| (-x, y)).collect();
+        let ring: Vec<_> = (0..61)
+            .map(|i| {
+                let angle = i as f64 / 61.0 * std::f64::consts::TAU;
+                (200.0 * angle.cos(), 200.0 * angle.sin())
+            })
+            .collect();
+        let entries = vec![
+            entry("a/l.dst", &l),
+            entry("b/l copy.dst", &l),
+            entry("c/l moved.dst", &moved),
+            entry("d/l mirrored.dst", &mirrored),
+            entry("e/ring.dst", &ring),
+        ];
+
+        let clusters = find_duplicates(&entries, 0.9, true);
+        assert_eq!(clusters.len(), 1);
+        let members = &clusters[0].members;
+        let paths: Vec<_> = members.iter().map(|m| m.entry.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "a/l.dst",
+                "b/l copy.dst",
+                "c/l moved.dst",
+                "d/l mirrored.dst"
+            ]
+        );
+        assert!(members[1].exact && !members[2].exact);
+        assert_eq!(members[1].similarity, 1.0);
+        assert!(members[2].similarity > 0.99);
+        assert!(members[3].similarity > 0.99);
+
+        // Without turns and mirror images the mirrored L is a different design
+        let clusters = find_duplicates(&entries, 0.9, false);
+        assert_eq!(clusters[0].members.len(), 3);
+        let l_variants = variants(&entries[0], false);
+        assert!(similarity(&entries[0], &l_variants, &entries[3]) < 0.9);
+        let l_variants = variants(&entries[0], true);
+        assert!(similarity(&entries[0], &l_variants, &entries[4]) < 0.5);
+    }
+
+    #[test]
+    fn test_fingerprint_round_trip() {
+        let entry = entry("l.dst", &letter_l());
+        let json = serde_json::to_string(&entry).unwrap();
+        let back: LibraryEntry = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, entry);
+        assert!(Fingerprint::new(&Pattern::new()).is_none());
+    }
+}
@@ -0,0 +1,740 @@
+// mod.rs - Index of design files on disk, with cached metadata for search
+
+mod duplicates;
+mod watch;
+
+use crate::batch::{find_design_files, is_design_file};
+use crate::dst::{Bounds, UNITS_PER_MM};
+use crate::export::{render_png, thumbnail_file_name, PngOptions, RenderMode};
+use crate::format::{DesignFormat, LoadedDesign};
+use crate::hoops::{check_hoop_fit, find_hoop, DEFAULT_HOOP_MARGIN_MM};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, RwLock};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub use duplicates::{find_duplicates, DuplicateCluster, Fingerprint, DEFAULT_DUPLICATE_THRESHOLD};
+pub use watch::{FolderWatcher, WatchFolder};
+
+/// Edge of the thumbnails rendered while indexing, in pixels
+pub const LIBRARY_THUMBNAIL_SIZE: u32 = 128;
+
+/// Files indexed at once when no limit is given: one per core
+pub fn default_concurrency() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Name of the index file in the library folder
+const INDEX_FILE: &str = "library.json";
+
+/// What the library knows about one design file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LibraryEntry {
+    pub path: String,
+    pub format: DesignFormat,
+    pub stitch_count: usize,
+    pub color_count: usize,
+    pub bounds: Option<Bounds>,
+    /// Cached PNG preview, once one has been rendered
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+    /// SHA-256 of the file contents, in hex
+    pub hash: String,
+    pub size: u64,
+    /// Modification time when indexed, in milliseconds since the Unix epoch
+    pub modified: u64,
+    /// When the design was last opened in the app, in the same units
+    #[serde(default)]
+    pub last_opened: Option<u64>,
+    /// The file was deleted from a watched folder; the entry is kept in case
+    /// it comes back
+    #[serde(default)]
+    pub missing: bool,
+    /// Stitch density grid for finding near-duplicates
+    #[serde(default)]
+    pub fingerprint: Option<Fingerprint>,
+}
+
+impl LibraryEntry {
+    /// Describe a design file from its contents and the parsed design
+    pub fn new(path: &str, data: &[u8], design: &LoadedDesign) -> Self {
+        let (size, modified) = file_stamp(Path::new(path)).unwrap_or((data.len() as u64, 0));
+        Self {
+            path: path.to_string(),
+            format: design.format,
+            stitch_count: design.pattern.statistics.real_stitch_count as usize,
+            color_count: design.pattern.color_blocks.len(),
+            bounds: design.pattern.bounds.clone(),
+            thumbnail: None,
+            hash: content_hash(data),
+            size,
+            modified,
+            last_opened: None,
+            missing: false,
+            fingerprint: Fingerprint::new(&design.pattern),
+        }
+    }
+
+    /// Width and height of the design in mm
+    fn size_mm(&self) -> Option<(f64, f64)> {
+        self.bounds
+            .as_ref()
+            .map(|b| (b.width() / UNITS_PER_MM, b.height() / UNITS_PER_MM))
+    }
+}
+
+/// Filters for `search`; every filter that is set must match
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LibraryQuery {
+    /// Words that must all appear in the path, ignoring case
+    pub text: Option<String>,
+    pub format: Option<DesignFormat>,
+    pub max_stitches: Option<usize>,
+    pub max_colors: Option<usize>,
+    /// Catalog id of a hoop the design must fit
+    pub fits_hoop: Option<String>,
+    /// Clearance kept from the hoop frame; the usual margin when unset
+    pub margin_mm: Option<f64>,
+    pub max_width_mm: Option<f64>,
+    pub max_height_mm: Option<f64>,
+    /// Also list entries whose files are missing
+    pub include_missing: bool,
+    pub limit: Option<usize>,
+}
+
+/// What `index_folder` did with each file it found
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct IndexReport {
+    /// Files new to the library
+    pub added: usize,
+    /// Files whose contents changed since they were indexed
+    pub updated: usize,
+    /// Files skipped because their size, time or contents were unchanged
+    pub unchanged: usize,
+    /// Entries dropped because their file is gone
+    pub removed: usize,
+    /// Files that could not be read or parsed, with the reason
+    pub failed: Vec<(String, String)>,
+}
+
+/// What the folder watcher changed in the library
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum LibraryChange {
+    /// The design was added, changed, or came back after going missing
+    Updated {
+        entry: LibraryEntry,
+    },
+    Missing {
+        path: String,
+    },
+    Failed {
+        path: String,
+        error: String,
+    },
+}
+
+/// Outcome of looking at one file against its entry
+enum Scan {
+    /// Size and time match its entry, or the contents hash the same; holds
+    /// the restamped entry when the file had to be read
+    Unchanged(Option<LibraryEntry>),
+    Parsed(LibraryEntry),
+    Failed(String),
+}
+
+/// Every indexed design, keyed by path, saved as JSON in the app data folder
+#[derive(Debug)]
+pub struct Library {
+    dir: PathBuf,
+    entries: RwLock<BTreeMap<String, LibraryEntry>>,
+}
+
+fn content_hash(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+/// Size and modification time of a file, used to skip unchanged files
+fn file_stamp(path: &Path) -> io::Result<(u64, u64)> {
+    let metadata = fs::metadata(path)?;
+    Ok((metadata.len(), millis(metadata.modified()?)))
+}
+
+impl Library {
+    /// Open the library kept in `dir`; a missing or unreadable index starts
+    /// an empty library
+    pub fn open(dir: PathBuf) -> Self {
+        let entries = fs::read(dir.join(INDEX_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        Self {
+            dir,
+            entries: RwLock::new(entries),
+        }
+    }
+
+    /// Write the index through a temporary file, so a crash never leaves
+    /// half of it
+    fn save(&self, entries: &BTreeMap<String, LibraryEntry>) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(INDEX_FILE);
+        let temp = path.with_extension("tmp");
+        fs::write(&temp, serde_json::to_vec(entries)?)?;
+        fs::rename(&temp, &path)
+    }
+
+    /// Render a thumbnail into the shared cache, reusing one already there
+    fn thumbnail(&self, data: &[u8], design: &LoadedDesign) -> Option<String> {
+        let cache = self.dir.join("thumbnails");
+        let path = cache.join(thumbnail_file_name(
+            data,
+            LIBRARY_THUMBNAIL_SIZE,
+            RenderMode::Flat,
+        ));
+        if !path.exists() {
+            let png = render_png(
+                &design.pattern,
+                LIBRARY_THUMBNAIL_SIZE,
+                LIBRARY_THUMBNAIL_SIZE,
+                &PngOptions::default(),
+            )
+            .ok()?;
+            fs::create_dir_all(&cache).ok()?;
+            fs::write(&path, png).ok()?;
+        }
+        Some(path.to_string_lossy().into_owned())
+    }
+
+    /// Look at one design file, reading and parsing it only when it changed
+    fn scan(
+        &self,
+        file: &Path,
+        previous: Option<&LibraryEntry>,
+        parse: &impl Fn(&str, &[u8]) -> Result<LoadedDesign, String>,
+    ) -> Scan {
+        let path = file.to_string_lossy();
+        let stamp = file_stamp(file);
+        // Entries indexed before fingerprints existed are read once more
+        let previous = previous.filter(|entry| entry.fingerprint.is_some());
+        if let (Some(entry), Ok((size, modified))) = (previous, &stamp) {
+            if entry.size == *size && entry.modified == *modified && !entry.missing {
+                return Scan::Unchanged(None);
+            }
+        }
+
+        let data = match fs::read(file) {
+            Ok(data) => data,
+            Err(e) => return Scan::Failed(format!("Failed to read file: {}", e)),
+        };
+        if let Some(entry) = previous.filter(|entry| entry.hash == content_hash(&data)) {
+            let (size, modified) = stamp.unwrap_or((entry.size, entry.modified));
+            return Scan::Unchanged(Some(LibraryEntry {
+                size,
+                modified,
+                missing: false,
+                ..entry.clone()
+            }));
+        }
+        match parse(&path, &data) {
+            Ok(design) => {
+                let mut entry = LibraryEntry::new(&path, &data, &design);
+                entry.thumbnail = self.thumbnail(&data, &design);
+                entry.last_opened = previous.and_then(|entry| entry.last_opened);
+                Scan::Parsed(entry)
+            }
+            Err(e) => Scan::Failed(e),
+        }
+    }
+
+    /// Look at a file with `scan`, turning a panic while parsing or
+    /// rendering it into a failure so one bad file cannot end the run
+    fn scan_isolated(
+        &self,
+        file: &Path,
+        previous: Option<&LibraryEntry>,
+        parse: &impl Fn(&str, &[u8]) -> Result<LoadedDesign, String>,
+    ) -> Scan {
+        panic::catch_unwind(AssertUnwindSafe(|| self.scan(file, previous, parse))).unwrap_or_else(
+            |payload| {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|m| m.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown error".to_string());
+                Scan::Failed(format!("Reading the design crashed: {}", message))
+            },
+        )
+    }
+
+    /// Index every design file in `folder`, re-reading only changed files
+    ///
+    /// A file whose size and modification time match its entry is skipped
+    /// unread; one whose contents hash the same is only restamped. Changed
+    /// files are parsed, and their thumbnails rendered from the same read,
+    /// on up to `concurrency` threads. Entries under the folder whose files
+    /// are gone are dropped. `parse` reads a design from its path and
+    /// contents; `progress` is called on this thread with the number of
+    /// files done and the total.
+    pub fn index_folder(
+        &self,
+        folder: &Path,
+        recursive: bool,
+        concurrency: usize,
+        parse: impl Fn(&str, &[u8]) -> Result<LoadedDesign, String> + Sync,
+        mut progress: impl FnMut(usize, usize),
+    ) -> io::Result<IndexReport> {
+        let files = find_design_files(folder, recursive)?;
+        let known = self.entries.read().unwrap().clone();
+        let total = files.len();
+        progress(0, total);
+
+        // Workers take the next file in turn and send back its outcome
+        let next = AtomicUsize::new(0);
+        let mut scans: Vec<(usize, Scan)> = Vec::with_capacity(total);
+        thread::scope(|scope| {
+            let (sender, outcomes) = mpsc::channel();
+            for _ in 0..concurrency.clamp(1, total.max(1)) {
+                let sender = sender.clone();
+                let (next, files, known, parse) = (&next, &files, &known, &parse);
+                scope.spawn(move || loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(file) = files.get(index) else {
+                        break;
+                    };
+                    let previous = known.get(file.to_string_lossy().as_ref());
+                    if sender
+                        .send((index, self.scan_isolated(file, previous, parse)))
+                        .is_err()
+                    {
+                        break;
+                    }
+                });
+            }
+            drop(sender);
+            for outcome in outcomes {
+                scans.push(outcome);
+                progress(scans.len(), total);
+            }
+        });
+        scans.sort_by_key(|&(index, _)| index);
+
+        let mut report = IndexReport::default();
+        let mut changed = Vec::new();
+        for (index, scan) in scans {
+            let path = files[index].to_string_lossy().into_owned();
+            match scan {
+                Scan::Unchanged(entry) => {
+                    changed.extend(entry);
+                    report.unchanged += 1;
+                }
+                Scan::Parsed(entry) => {
+                    if known.contains_key(&path) {
+                        report.updated += 1;
+                    } else {
+                        report.added += 1;
+                    }
+                    changed.push(entry);
+                }
+                Scan::Failed(e) => report.failed.push((path, e)),
+            }
+        }
+
+        let mut entries = self.entries.write().unwrap();
+        let before = entries.len();
+        entries.retain(|path, _| {
+            let path = Path::new(path);
+            let inside = if recursive {
+                path.starts_with(folder)
+            } else {
+                path.parent() == Some(folder)
+            };
+            !inside || path.is_file()
+        });
+        report.removed = before - entries.len();
+        for entry in changed {
+            entries.insert(entry.path.clone(), entry);
+        }
+        self.save(&entries)?;
+        Ok(report)
+    }
+
+    /// Bring the entries for `path` up to date after the watcher saw it change
+    ///
+    /// A design file is indexed again when its contents changed. Entries for
+    /// a path that is gone, or that lie in a folder that is gone, are marked
+    /// missing and kept, so they return intact if the file comes back.
+    pub fn refresh_path(
+        &self,
+        path: &Path,
+        parse: impl Fn(&str, &[u8]) -> Result<LoadedDesign, String>,
+    ) -> io::Result<Vec<LibraryChange>> {
+        if path.is_file() {
+            if !is_design_file(path) {
+                return Ok(Vec::new());
+            }
+            let key = path.to_string_lossy().into_owned();
+            let previous = self.entries.read().unwrap().get(&key).cloned();
+            let entry = match self.scan_isolated(path, previous.as_ref(), &parse) {
+                Scan::Parsed(entry) => entry,
+                // Only a file that came back is news
+                Scan::Unchanged(Some(entry)) if previous.as_ref().is_some_and(|p| p.missing) => {
+                    entry
+                }
+                Scan::Unchanged(Some(entry)) => {
+                    let mut entries = self.entries.write().unwrap();
+                    entries.insert(key, entry);
+                    self.save(&entries)?;
+                    return Ok(Vec::new());
+                }
+                Scan::Unchanged(None) => return Ok(Vec::new()),
+                Scan::Failed(error) => {
+                    return Ok(vec![LibraryChange::Failed { path: key, error }]);
+                }
+            };
+            let mut entries = self.entries.write().unwrap();
+            entries.insert(key, entry.clone());
+            self.save(&entries)?;
+            return Ok(vec![LibraryChange::Updated { entry }]);
+        }
+        if path.exists() {
+            // A folder; the files in it report their own changes
+            return Ok(Vec::new());
+        }
+
+        let mut entries = self.entries.write().unwrap();
+        let mut changes = Vec::new();
+        for (key, entry) in entries.iter_mut() {
+            if !entry.missing && Path::new(key).starts_with(path) {
+                entry.missing = true;
+                changes.push(LibraryChange::Missing { path: key.clone() });
+            }
+        }
+        if !changes.is_empty() {
+            self.save(&entries)?;
+        }
+        Ok(changes)
+    }
+
+    /// Note that a design was opened, adding it to the library if it is new
+    pub fn record_opened(&self, mut entry: LibraryEntry) -> io::Result<()> {
+        let mut entries = self.entries.write().unwrap();
+        if let Some(previous) = entries.get(&entry.path) {
+            if previous.hash == entry.hash {
+                entry.thumbnail = previous.thumbnail.clone();
+            }
+        }
+        entry.last_opened = Some(millis(SystemTime::now()));
+        entry.missing = false;
+        entries.insert(entry.path.clone(), entry);
+        self.save(&entries)
+    }
+
+    /// Entries matching every filter of `query`, sorted by path
+    pub fn search(&self, query: &LibraryQuery) -> Result<Vec<LibraryEntry>, String> {
+        let hoop = match &query.fits_hoop {
+            Some(id) => Some(find_hoop(id).ok_or_else(|| format!("Unknown hoop: {}", id))?),
+            None => None,
+        };
+        let margin = query.margin_mm.unwrap_or(DEFAULT_HOOP_MARGIN_MM);
+        let words: Vec<String> = query
+            .text
+            .as_deref()
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect();
+
+        let entries = self.entries.read().unwrap();
+        Ok(entries
+            .values()
+            .filter(|entry| {
+                let path = entry.path.to_lowercase();
+                let size = entry.size_mm();
+                (query.include_missing || !entry.missing)
+                    && words.iter().all(|word| path.contains(word))
+                    && query.format.is_none_or(|f| entry.format == f)
+                    && query.max_stitches.is_none_or(|n| entry.stitch_count <= n)
+                    && query.max_colors.is_none_or(|n| entry.color_count <= n)
+                    && query
+                        .max_width_mm
+                        .is_none_or(|w| size.is_some_and(|(width, _)| width <= w))
+                    && query
+                        .max_height_mm
+                        .is_none_or(|h| size.is_some_and(|(_, height)| height <= h))
+                    && hoop.is_none_or(|hoop| {
+                        entry
+                            .bounds
+                            .as_ref()
+                            .is_some_and(|b| check_hoop_fit(b, hoop, margin).fits)
+                    })
+            })
+            .take(query.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect())
+    }
+
+    /// Clusters of designs in the library that are at least `threshold`
+    /// alike, missing files left out; see `find_duplicates`
+    pub fn duplicates(&self, threshold: f64, transforms: bool) -> Vec<DuplicateCluster> {
+        let entries: Vec<LibraryEntry> = self
+            .entries
+            .read()
+            .unwrap()
+            .values()
+            .filter(|entry| !entry.missing)
+            .cloned()
+            .collect();
+        find_duplicates(&entries, threshold, transforms)
+    }
+
+    /// The most recently opened designs, newest first
+    pub fn recent(&self, limit: usize) -> Vec<LibraryEntry> {
+        let entries = self.entries.read().unwrap();
+        let mut opened: Vec<_> = entries
+            .values()
+            .filter(|entry| entry.last_opened.is_some())
+            .cloned()
+            .collect();
+        opened.sort_by_key(|entry| std::cmp::Reverse(entry.last_opened));
+        opened.truncate(limit);
+        opened
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::{parse_dst, write_dst, ParseOptions, Pattern, StitchCommand};
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("embrocad-library-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("designs").join("nested")).unwrap();
+        dir
+    }
+
+    /// A one-color DST `width` stitches wide
+    fn dst_bytes(width: usize) -> Vec<u8> {
+        let mut pattern = Pattern::new();
+        for i in 0..=width {
+            pattern.add_stitch(i as f64 * 10.0, 0.0, StitchCommand::Stitch);
+        }
+        pattern.add_stitch(width as f64 * 10.0, 0.0, StitchCommand::End);
+        write_dst(&pattern)
+    }
+
+    fn parse(_: &str, data: &[u8]) -> Result<LoadedDesign, String> {
+        let mut pattern = parse_dst(data, &ParseOptions::default()).map_err(|e| e.to_string())?;
+        pattern.calculate_color_blocks();
+        Ok(LoadedDesign::new(DesignFormat::Dst, pattern))
+    }
+
+    #[test]
+    fn test_index_reindex_and_search() {
+        let dir = scratch("index");
+        let designs = dir.join("designs");
+        fs::write(designs.join("small rose.dst"), dst_bytes(10)).unwrap();
+        fs::write(designs.join("nested").join("big tulip.dst"), dst_bytes(300)).unwrap();
+        fs::write(designs.join("broken.dst"), b"not a design").unwrap();
+
+        let library = Library::open(dir.clone());
+        let parsed = AtomicUsize::new(0);
+        let report = library
+            .index_folder(
+                &designs,
+                true,
+                2,
+                |p, d| {
+                    parsed.fetch_add(1, Ordering::Relaxed);
+                    parse(p, d)
+                },
+                |_, _| {},
+            )
+            .unwrap();
+        assert_eq!((report.added, report.updated, report.unchanged), (2, 0, 0));
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(parsed.load(Ordering::Relaxed), 3);
+
+        // Unchanged files are not parsed again
+        parsed.store(0, Ordering::Relaxed);
+        let report = library
+            .index_folder(
+                &designs,
+                true,
+                2,
+                |p, d| {
+                    parsed.fetch_add(1, Ordering::Relaxed);
+                    parse(p, d)
+                },
+                |_, _| {},
+            )
+            .unwrap();
+        assert_eq!((report.added, report.unchanged), (0, 2));
+        assert_eq!(parsed.load(Ordering::Relaxed), 1);
+
+        let found = library
+            .search(&LibraryQuery {
+                text: Some("ROSE".to_string()),
+                ..LibraryQuery::default()
+            })
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert!(found[0].path.ends_with("small rose.dst"));
+        assert_eq!(found[0].color_count, 1);
+        assert!(found[0]
+            .thumbnail
+            .as_deref()
+            .is_some_and(|t| Path::new(t).is_file()));
+
+        // The 300mm tulip is too wide for a 4x4" hoop
+        let fits = library
+            .search(&LibraryQuery {
+                fits_hoop: Some("brother_4x4".to_string()),
+                ..LibraryQuery::default()
+            })
+            .unwrap();
+        assert_eq!(fits.len(), 1);
+        assert!(fits[0].path.ends_with("small rose.dst"));
+        let few = library
+            .search(&LibraryQuery {
+                max_stitches: Some(100),
+                ..LibraryQuery::default()
+            })
+            .unwrap();
+        assert_eq!(few, fits);
+
+        // The index outlives the library, and deleted files drop out of it
+        fs::remove_file(designs.join("nested").join("big tulip.dst")).unwrap();
+        let reopened = Library::open(dir.clone());
+        assert_eq!(reopened.search(&LibraryQuery::default()).unwrap().len(), 2);
+        let report = reopened
+            .index_folder(&designs, false, 1, parse, |_, _| {})
+            .unwrap();
+        assert_eq!(report.removed, 0);
+        let report = reopened
+            .index_folder(&designs, true, 1, parse, |_, _| {})
+            .unwrap();
+        assert_eq!(report.removed, 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_recent() {
+        let dir = scratch("recent");
+        let library = Library::open(dir.clone());
+        for (index, name) in ["a.dst", "b.dst", "c.dst"].iter().enumerate() {
+            let path = dir.join("designs").join(name);
+            let data = dst_bytes(index + 1);
+            fs::write(&path, &data).unwrap();
+            let path = path.to_string_lossy();
+            let design = parse(&path, &data).unwrap();
+            library
+                .record_opened(LibraryEntry::new(&path, &data, &design))
+                .unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        let recent: Vec<_> = library
+            .recent(2)
+            .iter()
+            .map(|entry| Path::new(&entry.path).file_name().unwrap().to_owned())
+            .collect();
+        assert_eq!(recent, vec!["c.dst", "b.dst"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_panicking_file_is_isolated() {
+        let dir = scratch("panic");
+        let designs = dir.join("designs");
+        for name in ["a.dst", "b.dst", "crash.dst", "c.dst"] {
+            fs::write(designs.join(name), dst_bytes(5)).unwrap();
+        }
+        let library = Library::open(dir.clone());
+        let mut steps = Vec::new();
+        let report = library
+            .index_folder(
+                &designs,
+                false,
+                3,
+                |path, data| {
+                    if path.ends_with("crash.dst") {
+                        panic!("corrupt block table");
+                    }
+                    parse(path, data)
+                },
+                |done, total| steps.push((done, total)),
+            )
+            .unwrap();
+        assert_eq!(report.added, 3);
+        assert_eq!(report.failed.len(), 1);
+        assert!(report.failed[0].0.ends_with("crash.dst"));
+        assert!(report.failed[0].1.contains("corrupt block table"));
+        assert_eq!(steps.first(), Some(&(0, 4)));
+        assert_eq!(steps.last(), Some(&(4, 4)));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    /// Indexing the same folder on every core beats a single thread
+    ///
+    /// Timing depends on the machine, so this only runs on request:
+    /// `cargo test --release bench_ -- --ignored`.
+    #[test]
+    #[ignore]
+    fn bench_parallel_indexing() {
+        let cores = default_concurrency();
+        let dir = scratch("bench");
+        let designs = dir.join("designs");
+        // 20,000 stitches back and forth in rows 0.5mm apart
+        let mut pattern = Pattern::new();
+        for i in 0..20_000 {
+            let row = i / 100;
+            let column = if row % 2 == 0 { i % 100 } else { 99 - i % 100 };
+            pattern.add_stitch(
+                column as f64 * 10.0,
+                row as f64 * 5.0,
+                StitchCommand::Stitch,
+            );
+        }
+        pattern.add_stitch(0.0, 995.0, StitchCommand::End);
+        let data = write_dst(&pattern);
+        for i in 0..48 {
+            fs::write(designs.join(format!("design {:02}.dst", i)), &data).unwrap();
+        }
+
+        // Each run starts from an empty library, so every file is parsed
+        let run = |name: &str, concurrency: usize| {
+            let _ = fs::remove_dir_all(dir.join(name));
+            let library = Library::open(dir.join(name));
+            let start = std::time::Instant::now();
+            let report = library
+                .index_folder(&designs, false, concurrency, parse, |_, _| {})
+                .unwrap();
+            assert_eq!(report.added, 48);
+            start.elapsed()
+        };
+        let serial = run("serial", 1);
+        let parallel = run("parallel", cores);
+        if cores >= 2 {
+            assert!(parallel < serial, "{:?} vs {:?}", parallel, serial);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
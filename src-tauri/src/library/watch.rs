@@ -0,0 +1,308 @@
+// watch.rs - Watched library folders, refreshed in the background as files change
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A path is refreshed once it has been quiet this long, so a file still
+/// being copied, or saved through a temporary file, is read once and whole
+pub const DEBOUNCE: Duration = Duration::from_millis(750);
+
+/// Name of the file listing the watched folders, next to the index
+const FOLDERS_FILE: &str = "watch_folders.json";
+
+/// A folder whose design files the library follows
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchFolder {
+    pub path: String,
+    pub recursive: bool,
+}
+
+impl WatchFolder {
+    fn mode(&self) -> RecursiveMode {
+        if self.recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        }
+    }
+}
+
+/// Paths waiting for their changes to settle
+#[derive(Debug)]
+pub struct Debouncer {
+    quiet: Duration,
+    /// Time of each path's latest change
+    pending: HashMap<PathBuf, Instant>,
+}
+
+impl Debouncer {
+    pub fn new(quiet: Duration) -> Self {
+        Self {
+            quiet,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Note a change to `path`, restarting its wait
+    pub fn push(&mut self, path: PathBuf, at: Instant) {
+        self.pending.insert(path, at);
+    }
+
+    /// When the next path settles
+    pub fn next_due(&self) -> Option<Instant> {
+        self.pending.values().min().map(|&at| at + self.quiet)
+    }
+
+    /// Remove and return the paths unchanged for the quiet time, sorted
+    pub fn take_due(&mut self, now: Instant) -> Vec<PathBuf> {
+        let mut due: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|&(_, &at)| now.duration_since(at) >= self.quiet)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in &due {
+            self.pending.remove(path);
+        }
+        due.sort();
+        due
+    }
+}
+
+/// Collect changed paths from `events` and hand them to `on_settled` once
+/// each has been quiet for `quiet`; returns when the sender is dropped
+fn debounce_loop(
+    events: mpsc::Receiver<PathBuf>,
+    quiet: Duration,
+    mut on_settled: impl FnMut(Vec<PathBuf>),
+) {
+    let mut debouncer = Debouncer::new(quiet);
+    loop {
+        let received = match debouncer.next_due() {
+            Some(due) => events.recv_timeout(due.saturating_duration_since(Instant::now())),
+            None => events.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(path) => debouncer.push(path, Instant::now()),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        let due = debouncer.take_due(Instant::now());
+        if !due.is_empty() {
+            on_settled(due);
+        }
+    }
+}
+
+/// Watches the registered folders, passing settled changes to a worker
+///
+/// The folder list is saved in the library folder and watched again at the
+/// next start. A registered folder that has disappeared stays listed but
+/// unwatched until it is added again.
+pub struct FolderWatcher {
+    file: PathBuf,
+    folders: Mutex<Vec<WatchFolder>>,
+    watcher: Mutex<RecommendedWatcher>,
+}
+
+impl FolderWatcher {
+    /// Watch the folders saved in `dir`, calling `on_settled` on a worker
+    /// thread with each batch of changed paths
+    pub fn start(
+        dir: &Path,
+        on_settled: impl FnMut(Vec<PathBuf>) + Send + 'static,
+    ) -> notify::Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else {
+                    return;
+                };
+                if matches!(event.kind, EventKind::Access(_)) {
+                    return;
+                }
+                for path in event.paths {
+                    let _ = sender.send(path);
+                }
+            })?;
+        std::thread::spawn(move || debounce_loop(events, DEBOUNCE, on_settled));
+
+        let file = dir.join(FOLDERS_FILE);
+        let folders: Vec<WatchFolder> = fs::read(&file)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        for folder in &folders {
+            let _ = watcher.watch(Path::new(&folder.path), folder.mode());
+        }
+        Ok(Self {
+            file,
+            folders: Mutex::new(folders),
+            watcher: Mutex::new(watcher),
+        })
+    }
+
+    fn save(&self, folders: &[WatchFolder]) -> Result<(), String> {
+        let data = serde_json::to_vec(folders).map_err(|e| e.to_string())?;
+        if let Some(dir) = self.file.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Failed to save folders: {}", e))?;
+        }
+        fs::write(&self.file, data).map_err(|e| format!("Failed to save folders: {}", e))
+    }
+
+    /// Start watching a folder, replacing its earlier registration
+    pub fn add(&self, folder: WatchFolder) -> Result<(), String> {
+        let mut folders = self.folders.lock().unwrap();
+        let mut watcher = self.watcher.lock().unwrap();
+        if folders.iter().any(|f| f.path == folder.path) {
+            let _ = watcher.unwatch(Path::new(&folder.path));
+        }
+        watcher
+            .watch(Path::new(&folder.path), folder.mode())
+            .map_err(|e| format!("Failed to watch {}: {}", folder.path, e))?;
+        folders.retain(|f| f.path != folder.path);
+        folders.push(folder);
+        self.save(&folders)
+    }
+
+    /// Stop watching a folder; returns false if it was not registered
+    pub fn remove(&self, path: &str) -> Result<bool, String> {
+        let mut folders = self.folders.lock().unwrap();
+        let before = folders.len();
+        folders.retain(|f| f.path != path);
+        if folders.len() == before {
+            return Ok(false);
+        }
+        let _ = self.watcher.lock().unwrap().unwatch(Path::new(path));
+        self.save(&folders)?;
+        Ok(true)
+    }
+
+    pub fn list(&self) -> Vec<WatchFolder> {
+        self.folders.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::{parse_dst, write_dst, ParseOptions, Pattern, StitchCommand};
+    use crate::format::{DesignFormat, LoadedDesign};
+    use crate::library::{Library, LibraryChange, LibraryQuery};
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("embrocad-watch-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("designs")).unwrap();
+        dir
+    }
+
+    fn dst_bytes(stitches: usize) -> Vec<u8> {
+        let mut pattern = Pattern::new();
+        for i in 0..stitches {
+            pattern.add_stitch(i as f64 * 10.0, 0.0, StitchCommand::Stitch);
+        }
+        let end = stitches.saturating_sub(1) as f64 * 10.0;
+        pattern.add_stitch(end, 0.0, StitchCommand::End);
+        write_dst(&pattern)
+    }
+
+    fn parse(_: &str, data: &[u8]) -> Result<LoadedDesign, String> {
+        let pattern = parse_dst(data, &ParseOptions::default()).map_err(|e| e.to_string())?;
+        Ok(LoadedDesign::new(DesignFormat::Dst, pattern))
+    }
+
+    #[test]
+    fn test_debouncer_waits_for_quiet() {
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        let mut debouncer = Debouncer::new(ms(100));
+        let (a, b) = (PathBuf::from("a.dst"), PathBuf::from("b.dst"));
+        debouncer.push(a.clone(), start);
+        debouncer.push(b.clone(), start + ms(50));
+        // A second write to `a` restarts its wait
+        debouncer.push(a.clone(), start + ms(80));
+
+        assert_eq!(debouncer.next_due(), Some(start + ms(150)));
+        assert!(debouncer.take_due(start + ms(120)).is_empty());
+        assert_eq!(debouncer.take_due(start + ms(170)), vec![b]);
+        assert_eq!(debouncer.take_due(start + ms(180)), vec![a]);
+        assert_eq!(debouncer.next_due(), None);
+    }
+
+    #[test]
+    fn test_loop_batches_rapid_changes() {
+        let (sender, events) = mpsc::channel();
+        let (settled, batches) = mpsc::channel();
+        let worker = std::thread::spawn(move || {
+            debounce_loop(events, Duration::from_millis(50), |paths| {
+                settled.send(paths).unwrap();
+            })
+        });
+        for _ in 0..10 {
+            sender.send(PathBuf::from("churn.dst")).unwrap();
+        }
+        let batch = batches.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(batch, vec![PathBuf::from("churn.dst")]);
+        drop(sender);
+        worker.join().unwrap();
+        assert!(batches.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_refresh_through_churn() {
+        let dir = scratch("churn");
+        let file = dir.join("designs").join("star.dst");
+        let library = Library::open(dir.clone());
+        let refresh = |path: &Path| library.refresh_path(path, parse).unwrap();
+
+        fs::write(&file, dst_bytes(5)).unwrap();
+        let changes = refresh(&file);
+        assert!(matches!(&changes[..], [LibraryChange::Updated { .. }]));
+        // Seeing the same file again changes nothing
+        assert!(refresh(&file).is_empty());
+
+        fs::write(&file, dst_bytes(9)).unwrap();
+        let changes = refresh(&file);
+        let [LibraryChange::Updated { entry }] = &changes[..] else {
+            panic!("expected an update, got {:?}", changes);
+        };
+        let count = entry.stitch_count;
+
+        // A deleted file is kept as missing and left out of searches
+        fs::remove_file(&file).unwrap();
+        assert!(matches!(
+            &refresh(&file)[..],
+            [LibraryChange::Missing { .. }]
+        ));
+        assert!(refresh(&file).is_empty());
+        assert!(library.search(&LibraryQuery::default()).unwrap().is_empty());
+        let all = LibraryQuery {
+            include_missing: true,
+            ..LibraryQuery::default()
+        };
+        assert_eq!(library.search(&all).unwrap().len(), 1);
+
+        // Putting it back restores the entry
+        fs::write(&file, dst_bytes(9)).unwrap();
+        let changes = refresh(&file);
+        let [LibraryChange::Updated { entry }] = &changes[..] else {
+            panic!("expected an update, got {:?}", changes);
+        };
+        assert!(!entry.missing);
+        assert_eq!(entry.stitch_count, count);
+
+        // Removing the whole folder marks what was in it
+        fs::remove_dir_all(dir.join("designs")).unwrap();
+        assert_eq!(refresh(&dir.join("designs")).len(), 1);
+        assert!(refresh(&dir.join("notes.txt")).is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,267 @@
+// project.rs - Project files that save and reopen a set of open designs
+
+use crate::dst::Pattern;
+use crate::format::{DesignFormat, LoadedDesign};
+use crate::session::{DesignHandle, DesignId, Designs, OpenDesign};
+use serde::{Deserialize, Serialize};
+
+/// Version written by this build
+///
+/// The minor version goes up when fields are added, which older builds
+/// skip and newer ones default; the major version goes up when a file
+/// could no longer be read correctly by an older build.
+pub const PROJECT_VERSION: ProjectVersion = ProjectVersion { major: 1, minor: 0 };
+
+/// Error type for reading project files
+#[derive(Debug, thiserror::Error)]
+pub enum ProjectError {
+    #[error("Invalid project file: {0}")]
+    Invalid(#[from] serde_json::Error),
+    #[error(
+        "The project was saved by a newer version of EmbroCAD (format {found}.x, this version reads {supported}.x)"
+    )]
+    NewerVersion { found: u32, supported: u32 },
+}
+
+/// Format version of a project file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectVersion {
+    pub major: u32,
+    #[serde(default)]
+    pub minor: u32,
+}
+
+/// One design as saved in a project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectDesign {
+    /// File the design was first loaded from
+    #[serde(default)]
+    pub source_path: Option<String>,
+    pub format: DesignFormat,
+    /// The edited stitches with their thread colors and needles
+    pub pattern: Pattern,
+    /// Labels of the edits made to the design, oldest first; the edits
+    /// themselves are not kept, so they cannot be undone after reopening
+    #[serde(default)]
+    pub edits: Vec<String>,
+}
+
+impl ProjectDesign {
+    pub fn from_open(design: &OpenDesign) -> Self {
+        Self {
+            source_path: design.path.clone(),
+            format: design.format,
+            pattern: design.pattern.clone(),
+            edits: design.edit_labels(),
+        }
+    }
+
+    /// The design ready to edit again, with its derived fields recomputed
+    /// and a fresh history
+    pub fn into_open(self) -> OpenDesign {
+        let mut pattern = self.pattern;
+        pattern.calculate_bounds();
+        pattern.calculate_statistics();
+        pattern.calculate_color_blocks();
+        let mut design = OpenDesign::new(self.source_path, LoadedDesign::new(self.format, pattern));
+        design.earlier_edits = self.edits;
+        design
+    }
+}
+
+/// A saved set of designs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    pub version: ProjectVersion,
+    #[serde(default)]
+    pub designs: Vec<ProjectDesign>,
+}
+
+/// Just enough of a project file to check its version before the rest
+#[derive(Deserialize)]
+struct ProjectHeader {
+    version: ProjectVersion,
+}
+
+impl Project {
+    pub fn new(designs: Vec<ProjectDesign>) -> Self {
+        Self {
+            version: PROJECT_VERSION,
+            designs,
+        }
+    }
+
+    /// Snapshot the designs in `ids`, in that order
+    pub fn save(designs: &Designs, ids: &[DesignId]) -> Result<Self, String> {
+        let saved = ids
+            .iter()
+            .map(|&id| designs.with(id, ProjectDesign::from_open))
+            .collect::<Result<_, _>>()?;
+        Ok(Self::new(saved))
+    }
+
+    /// Open every design of the project alongside those already open
+    pub fn open(self, designs: &Designs) -> Vec<ReopenedDesign> {
+        self.designs
+            .into_iter()
+            .map(|saved| ReopenedDesign::insert(saved.into_open(), designs))
+            .collect()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec_pretty(self).expect("projects always serialize")
+    }
+
+    /// Read a project, refusing one from a newer major version before its
+    /// designs are looked at
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ProjectError> {
+        let header: ProjectHeader = serde_json::from_slice(data)?;
+        if header.version.major > PROJECT_VERSION.major {
+            return Err(ProjectError::NewerVersion {
+                found: header.version.major,
+                supported: PROJECT_VERSION.major,
+            });
+        }
+        Ok(serde_json::from_slice(data)?)
+    }
+}
+
+/// A design opened from a project, with the edits it had when saved
+#[derive(Debug, Clone, Serialize)]
+pub struct ReopenedDesign {
+    #[serde(flatten)]
+    pub handle: DesignHandle,
+    pub edits: Vec<String>,
+}
+
+impl ReopenedDesign {
+    /// Hand a design from a project to `designs`
+    pub fn insert(design: OpenDesign, designs: &Designs) -> Self {
+        let summary = design.summary();
+        let edits = design.earlier_edits.clone();
+        Self {
+            handle: DesignHandle {
+                id: designs.insert(design),
+                summary,
+            },
+            edits,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::{StitchCommand, ThreadColor};
+
+    fn two_color_design(first: [u8; 3], second: [u8; 3]) -> OpenDesign {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(0.0, 0.0, StitchCommand::Stitch);
+        pattern.add_stitch(10.0, 0.0, StitchCommand::Stitch);
+        pattern.add_stitch(10.0, 0.0, StitchCommand::ColorChange);
+        pattern.add_stitch(10.0, 10.0, StitchCommand::Stitch);
+        pattern.add_stitch(10.0, 10.0, StitchCommand::End);
+        pattern.metadata.thread_colors = vec![ThreadColor::new(first), ThreadColor::new(second)];
+        pattern.calculate_bounds();
+        pattern.calculate_statistics();
+        pattern.calculate_color_blocks();
+        OpenDesign::new(
+            Some("/designs/logo.dst".to_string()),
+            LoadedDesign::new(DesignFormat::Dst, pattern),
+        )
+    }
+
+    #[test]
+    fn test_round_trip_two_designs() {
+        let designs = Designs::default();
+        let first = designs.insert(two_color_design([200, 0, 0], [0, 0, 200]));
+        let mut second = two_color_design([10, 20, 30], [40, 50, 60]);
+        second.path = None;
+        second.format = DesignFormat::Pes;
+        let second = designs.insert(second);
+        designs
+            .edit(first, "Recolor", |pattern| {
+                let thread = &mut pattern.metadata.thread_colors[1];
+                thread.rgb = [1, 2, 3];
+                thread.description = Some("Custom".to_string());
+                thread.needle = Some(4);
+                Ok(())
+            })
+            .unwrap();
+
+        let data = Project::save(&designs, &[first, second])
+            .unwrap()
+            .to_bytes();
+        let reopened = Designs::default();
+        let handles = Project::from_bytes(&data).unwrap().open(&reopened);
+        assert_eq!(handles.len(), 2);
+        assert_eq!(handles[0].edits, vec!["Recolor".to_string()]);
+        assert!(handles[1].edits.is_empty());
+
+        for (&id, handle) in [first, second].iter().zip(&handles) {
+            let (before, format, path) = designs
+                .with(id, |d| (d.pattern.clone(), d.format, d.path.clone()))
+                .unwrap();
+            reopened
+                .with(handle.handle.id, |d| {
+                    assert_eq!(d.format, format);
+                    assert_eq!(d.path, path);
+                    assert_eq!(d.pattern.stitches, before.stitches);
+                    assert_eq!(
+                        d.pattern.metadata.thread_colors,
+                        before.metadata.thread_colors
+                    );
+                    assert_eq!(d.pattern.color_blocks.len(), 2);
+                    assert_eq!(d.history.undo_depth(), 0);
+                })
+                .unwrap();
+        }
+        let thread = reopened
+            .with(handles[0].handle.id, |d| {
+                d.pattern.metadata.thread_colors[1].clone()
+            })
+            .unwrap();
+        assert_eq!(thread.rgb, [1, 2, 3]);
+        assert_eq!(thread.needle, Some(4));
+
+        // Saving again keeps the labels of edits made in earlier sessions
+        let resaved = Project::save(&reopened, &[handles[0].handle.id]).unwrap();
+        assert_eq!(resaved.designs[0].edits, vec!["Recolor".to_string()]);
+    }
+
+    #[test]
+    fn test_versions() {
+        let designs = Designs::default();
+        let id = designs.insert(two_color_design([0, 0, 0], [255, 255, 255]));
+        let mut project: serde_json::Value =
+            serde_json::to_value(Project::save(&designs, &[id]).unwrap()).unwrap();
+
+        // Fields from a later minor version are skipped, missing ones defaulted
+        project["version"]["minor"] = 7.into();
+        project["layout"] = "grid".into();
+        project["designs"][0]["zoom"] = 2.into();
+        project["designs"][0]
+            .as_object_mut()
+            .unwrap()
+            .remove("edits");
+        let data = serde_json::to_vec(&project).unwrap();
+        let read = Project::from_bytes(&data).unwrap();
+        assert_eq!(read.designs.len(), 1);
+        assert!(read.designs[0].edits.is_empty());
+
+        project["version"]["major"] = (PROJECT_VERSION.major + 1).into();
+        project["designs"] = "changed beyond recognition".into();
+        let data = serde_json::to_vec(&project).unwrap();
+        assert!(matches!(
+            Project::from_bytes(&data),
+            Err(ProjectError::NewerVersion {
+                found: 2,
+                supported: 1
+            })
+        ));
+        assert!(matches!(
+            Project::from_bytes(b"not json"),
+            Err(ProjectError::Invalid(_))
+        ));
+    }
+}
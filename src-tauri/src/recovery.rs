@@ -0,0 +1,377 @@
+// recovery.rs - Periodic autosave of edited designs and their recovery after a crash
+
+use crate::format::DesignFormat;
+use crate::project::{Project, ProjectDesign, ProjectError, ReopenedDesign};
+use crate::session::{DesignId, Designs};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Recovery files are named `session-<start millis>-<process id>.ecad`
+const SESSION_PREFIX: &str = "session-";
+const SESSION_EXTENSION: &str = "ecad";
+
+/// Error type for autosaves and recovery
+#[derive(Debug, thiserror::Error)]
+pub enum RecoveryError {
+    #[error("Recovery file error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Project(#[from] ProjectError),
+    #[error("No recoverable session named {0}")]
+    UnknownSession(String),
+}
+
+/// How often edits are autosaved and how many sessions are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutosaveOptions {
+    /// Seconds between autosaves; 0 turns autosave off
+    pub interval_secs: u64,
+    /// Recovery files of earlier sessions kept; older ones are deleted
+    pub keep_sessions: usize,
+}
+
+impl Default for AutosaveOptions {
+    fn default() -> Self {
+        Self {
+            interval_secs: 60,
+            keep_sessions: 5,
+        }
+    }
+}
+
+/// One design of an earlier session that can be restored
+#[derive(Debug, Clone, Serialize)]
+pub struct RecoverableDesign {
+    pub source_path: Option<String>,
+    pub format: DesignFormat,
+    pub stitch_count: usize,
+    pub edits: Vec<String>,
+}
+
+/// An earlier session that left edited designs behind
+#[derive(Debug, Clone, Serialize)]
+pub struct RecoverableSession {
+    pub session: String,
+    /// Time of the last autosave, in milliseconds since the Unix epoch
+    pub saved_at: u64,
+    pub designs: Vec<RecoverableDesign>,
+}
+
+/// Autosaves of this session's edited designs, one recovery file per session
+///
+/// A design is saved once it has been edited, and again whenever it changes;
+/// designs never edited can be reopened from their files. Each autosave
+/// rewrites the session's file through a temporary file and a rename, so a
+/// crash mid-write leaves the previous autosave intact.
+#[derive(Debug)]
+pub struct Recovery {
+    dir: PathBuf,
+    session: String,
+    options: Mutex<AutosaveOptions>,
+    /// Revisions of the designs in the latest autosave
+    saved: Mutex<Vec<(DesignId, u64)>>,
+}
+
+fn now_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+impl Recovery {
+    pub fn new(dir: PathBuf) -> Self {
+        let session = format!(
+            "{}{}-{}",
+            SESSION_PREFIX,
+            now_millis(SystemTime::now()),
+            std::process::id()
+        );
+        Self {
+            dir,
+            session,
+            options: Mutex::new(AutosaveOptions::default()),
+            saved: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn options(&self) -> AutosaveOptions {
+        *self.options.lock().unwrap()
+    }
+
+    pub fn set_options(&self, options: AutosaveOptions) {
+        *self.options.lock().unwrap() = options;
+    }
+
+    fn path(&self, session: &str) -> PathBuf {
+        self.dir.join(session).with_extension(SESSION_EXTENSION)
+    }
+
+    /// Path of an earlier session's file, refusing names that could point
+    /// outside the recovery directory
+    fn earlier_session(&self, session: &str) -> Result<PathBuf, RecoveryError> {
+        let valid = session.starts_with(SESSION_PREFIX)
+            && session
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-')
+            && session != self.session;
+        let path = self.path(session);
+        if !valid || !path.is_file() {
+            return Err(RecoveryError::UnknownSession(session.to_string()));
+        }
+        Ok(path)
+    }
+
+    /// Save the edited designs if any changed since the last autosave;
+    /// returns whether the recovery file was rewritten
+    pub fn autosave(&self, designs: &Designs) -> Result<bool, RecoveryError> {
+        let edited: Vec<_> = designs
+            .revisions()
+            .into_iter()
+            .filter(|&(_, revision)| revision > 0)
+            .collect();
+        let mut saved = self.saved.lock().unwrap();
+        if *saved == edited {
+            return Ok(false);
+        }
+
+        let path = self.path(&self.session);
+        if edited.is_empty() {
+            // Every edited design was closed
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+        } else {
+            // A design closed since the revisions were read is left out
+            let project = Project::new(
+                edited
+                    .iter()
+                    .filter_map(|&(id, _)| designs.with(id, ProjectDesign::from_open).ok())
+                    .collect(),
+            );
+            fs::create_dir_all(&self.dir)?;
+            let temp = path.with_extension("tmp");
+            fs::write(&temp, project.to_bytes())?;
+            fs::rename(&temp, &path)?;
+        }
+        *saved = edited;
+        drop(saved);
+
+        self.prune()?;
+        Ok(true)
+    }
+
+    /// Earlier sessions' files with their modification times, newest first
+    fn earlier_files(&self) -> Result<Vec<(String, SystemTime)>, RecoveryError> {
+        if !self.dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let Some(session) = session_name(&path) else {
+                continue;
+            };
+            if session != self.session {
+                files.push((session, fs::metadata(&path)?.modified()?));
+            }
+        }
+        files.sort_by_key(|&(_, modified)| std::cmp::Reverse(modified));
+        Ok(files)
+    }
+
+    /// Delete earlier sessions beyond the number kept
+    fn prune(&self) -> Result<(), RecoveryError> {
+        let keep = self.options().keep_sessions;
+        for (session, _) in self.earlier_files()?.into_iter().skip(keep) {
+            fs::remove_file(self.path(&session))?;
+        }
+        Ok(())
+    }
+
+    /// Earlier sessions that can be restored, newest first
+    ///
+    /// Files that cannot be read, such as those from a newer version, are
+    /// left out rather than failing the whole list.
+    pub fn sessions(&self) -> Result<Vec<RecoverableSession>, RecoveryError> {
+        let mut sessions = Vec::new();
+        for (session, modified) in self.earlier_files()? {
+            let project = fs::read(self.path(&session))
+                .ok()
+                .and_then(|data| Project::from_bytes(&data).ok());
+            let Some(project) = project else {
+                continue;
+            };
+            let designs = project
+                .designs
+                .into_iter()
+                .map(|design| RecoverableDesign {
+                    source_path: design.source_path,
+                    format: design.format,
+                    stitch_count: design.pattern.stitches.len(),
+                    edits: design.edits,
+                })
+                .collect();
+            sessions.push(RecoverableSession {
+                session,
+                saved_at: now_millis(modified),
+                designs,
+            });
+        }
+        Ok(sessions)
+    }
+
+    /// Open an earlier session's designs and delete its recovery file
+    ///
+    /// The restored designs count as edited, so they are autosaved again
+    /// under this session.
+    pub fn restore(
+        &self,
+        session: &str,
+        designs: &Designs,
+    ) -> Result<Vec<ReopenedDesign>, RecoveryError> {
+        let path = self.earlier_session(session)?;
+        let project = Project::from_bytes(&fs::read(&path)?)?;
+        let reopened = project
+            .designs
+            .into_iter()
+            .map(|saved| {
+                let mut design = saved.into_open();
+                design.revision = 1;
+                ReopenedDesign::insert(design, designs)
+            })
+            .collect();
+        fs::remove_file(&path)?;
+        Ok(reopened)
+    }
+
+    /// Delete an earlier session's recovery file without restoring it
+    pub fn discard(&self, session: &str) -> Result<(), RecoveryError> {
+        fs::remove_file(self.earlier_session(session)?)?;
+        Ok(())
+    }
+}
+
+/// The session a recovery file belongs to, if `path` is one
+fn session_name(path: &Path) -> Option<String> {
+    if path.extension()? != SESSION_EXTENSION {
+        return None;
+    }
+    let stem = path.file_stem()?.to_str()?;
+    stem.starts_with(SESSION_PREFIX).then(|| stem.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::{Pattern, Stitch, StitchCommand};
+    use crate::format::LoadedDesign;
+    use crate::session::OpenDesign;
+
+    fn recovery_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("embrocad-recovery-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn open(path: &str) -> OpenDesign {
+        let mut pattern = Pattern::new();
+        pattern.add_stitch(0.0, 0.0, StitchCommand::Stitch);
+        pattern.add_stitch(10.0, 0.0, StitchCommand::Stitch);
+        pattern.add_stitch(10.0, 0.0, StitchCommand::End);
+        OpenDesign::new(
+            Some(path.to_string()),
+            LoadedDesign::new(DesignFormat::Dst, pattern),
+        )
+    }
+
+    #[test]
+    fn test_autosave_and_restore() {
+        let dir = recovery_dir("restore");
+        let crashed = Recovery::new(dir.clone());
+        let designs = Designs::default();
+        let edited = designs.insert(open("/designs/edited.dst"));
+        designs.insert(open("/designs/untouched.dst"));
+
+        // Nothing is saved until a design is edited
+        assert!(!crashed.autosave(&designs).unwrap());
+        designs
+            .edit(edited, "Add stitch", |pattern| {
+                pattern
+                    .stitches
+                    .insert(2, Stitch::new(20.0, 0.0, StitchCommand::Stitch));
+                Ok(())
+            })
+            .unwrap();
+        assert!(crashed.autosave(&designs).unwrap());
+        assert!(!crashed.autosave(&designs).unwrap());
+        assert!(!dir.join(format!("{}.tmp", crashed.session)).exists());
+        // A session does not offer its own autosave for recovery
+        assert!(crashed.sessions().unwrap().is_empty());
+
+        let mut relaunched = Recovery::new(dir.clone());
+        relaunched.session = format!("{}later", SESSION_PREFIX);
+        let sessions = relaunched.sessions().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].session, crashed.session);
+        assert_eq!(sessions[0].designs.len(), 1);
+        assert_eq!(sessions[0].designs[0].stitch_count, 4);
+        assert_eq!(sessions[0].designs[0].edits, vec!["Add stitch".to_string()]);
+
+        let fresh = Designs::default();
+        let restored = relaunched.restore(&crashed.session, &fresh).unwrap();
+        assert_eq!(restored.len(), 1);
+        let (path, x) = fresh
+            .with(restored[0].handle.id, |d| {
+                (d.path.clone(), d.pattern.stitches[2].x)
+            })
+            .unwrap();
+        assert_eq!(path.as_deref(), Some("/designs/edited.dst"));
+        assert_eq!(x, 20.0);
+        // The restored design is autosaved again in the new session
+        assert!(relaunched.sessions().unwrap().is_empty());
+        assert!(relaunched.autosave(&fresh).unwrap());
+        assert!(relaunched.path(&relaunched.session).is_file());
+
+        assert!(matches!(
+            relaunched.restore("../outside", &fresh),
+            Err(RecoveryError::UnknownSession(_))
+        ));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_prunes_old_sessions() {
+        let dir = recovery_dir("prune");
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["session-1-1", "session-2-1", "session-3-1"] {
+            fs::write(dir.join(name).with_extension(SESSION_EXTENSION), b"{}").unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        let recovery = Recovery::new(dir.clone());
+        recovery.set_options(AutosaveOptions {
+            keep_sessions: 2,
+            ..AutosaveOptions::default()
+        });
+        let designs = Designs::default();
+        let id = designs.insert(open("/designs/a.dst"));
+        designs.edit(id, "Nothing", |_| Ok(())).unwrap();
+        recovery.autosave(&designs).unwrap();
+
+        let mut left: Vec<_> = recovery
+            .earlier_files()
+            .unwrap()
+            .into_iter()
+            .map(|(session, _)| session)
+            .collect();
+        left.sort();
+        assert_eq!(left, vec!["session-2-1", "session-3-1"]);
+        assert!(recovery.path(&recovery.session).is_file());
+        // Unreadable files are not offered
+        assert!(recovery.sessions().unwrap().is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,351 @@
+// session.rs - Open designs held in backend state and addressed by id
+
+use crate::dst::Pattern;
+use crate::edit::{EditError, EditWarning, StitchEdit};
+use crate::format::{DesignFormat, LoadedDesign};
+use crate::history::{History, HistoryStep, DEFAULT_HISTORY_LIMIT};
+use crate::stream::DesignSummary;
+use crate::transform::{TransformOperation, TransformOptions, TransformWarning};
+use crate::view::StitchIndex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// Handle the frontend uses to refer to an open design
+pub type DesignId = u64;
+
+/// A design held by the backend, with its hit-testing index and edit history
+#[derive(Debug)]
+pub struct OpenDesign {
+    pub path: Option<String>,
+    pub format: DesignFormat,
+    pub pattern: Pattern,
+    pub index: StitchIndex,
+    pub history: History,
+    /// Labels of edits made before the design was saved in a project; they
+    /// can no longer be undone
+    pub earlier_edits: Vec<String>,
+    /// Changes since the design was opened, undo and redo included
+    pub revision: u64,
+}
+
+impl OpenDesign {
+    pub fn new(path: Option<String>, design: LoadedDesign) -> Self {
+        Self {
+            path,
+            format: design.format,
+            index: StitchIndex::new(&design.pattern),
+            pattern: design.pattern,
+            history: History::default(),
+            earlier_edits: Vec::new(),
+            revision: 0,
+        }
+    }
+
+    pub fn summary(&self) -> DesignSummary {
+        DesignSummary::new(self.format, &self.pattern)
+    }
+
+    /// Labels of every edit made to the design, oldest first, including
+    /// those from before it was saved in a project
+    pub fn edit_labels(&self) -> Vec<String> {
+        let mut labels = self.earlier_edits.clone();
+        labels.extend(self.history.labels());
+        labels
+    }
+
+    /// The full pattern with its format, for commands that return stitches
+    pub fn loaded(&self) -> LoadedDesign {
+        LoadedDesign::new(self.format, self.pattern.clone())
+    }
+}
+
+/// What loading or opening a design returns instead of its stitches
+#[derive(Debug, Clone, Serialize)]
+pub struct DesignHandle {
+    pub id: DesignId,
+    #[serde(flatten)]
+    pub summary: DesignSummary,
+}
+
+/// The result of a command that edits an open design: its new summary and
+/// the operation's own report
+#[derive(Debug, Clone, Serialize)]
+pub struct DesignUpdate<T> {
+    pub summary: DesignSummary,
+    pub report: T,
+}
+
+/// One entry of `list_open_designs`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OpenDesignInfo {
+    pub id: DesignId,
+    pub path: Option<String>,
+    pub format: DesignFormat,
+    pub stitch_count: usize,
+}
+
+/// Every open design, keyed by id
+///
+/// The map lock is only held to look a design up, so a slow command on one
+/// design never blocks another. Each design has its own lock: commands on
+/// the same id read concurrently and edits run one at a time.
+#[derive(Debug)]
+pub struct Designs {
+    next_id: AtomicU64,
+    open: RwLock<HashMap<DesignId, Arc<RwLock<OpenDesign>>>>,
+    history_limit: AtomicUsize,
+}
+
+impl Default for Designs {
+    fn default() -> Self {
+        Self {
+            next_id: AtomicU64::new(0),
+            open: RwLock::new(HashMap::new()),
+            history_limit: AtomicUsize::new(DEFAULT_HISTORY_LIMIT),
+        }
+    }
+}
+
+impl Designs {
+    /// Take ownership of a design and return its new id
+    pub fn insert(&self, mut design: OpenDesign) -> DesignId {
+        design
+            .history
+            .set_limit(self.history_limit.load(Ordering::Relaxed));
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        self.open
+            .write()
+            .unwrap()
+            .insert(id, Arc::new(RwLock::new(design)));
+        id
+    }
+
+    fn get(&self, id: DesignId) -> Result<Arc<RwLock<OpenDesign>>, String> {
+        self.open
+            .read()
+            .unwrap()
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| format!("No open design with id {}", id))
+    }
+
+    /// Run `f` with shared access to a design
+    pub fn with<R>(&self, id: DesignId, f: impl FnOnce(&OpenDesign) -> R) -> Result<R, String> {
+        let design = self.get(id)?;
+        let design = design.read().unwrap();
+        Ok(f(&design))
+    }
+
+    /// Change a design with exclusive access, then rebuild its index
+    fn update<R>(
+        &self,
+        id: DesignId,
+        f: impl FnOnce(&mut Pattern, &mut History) -> Result<R, String>,
+    ) -> Result<DesignUpdate<R>, String> {
+        let design = self.get(id)?;
+        let mut design = design.write().unwrap();
+        let design = &mut *design;
+        let report = f(&mut design.pattern, &mut design.history)?;
+        design.index = StitchIndex::new(&design.pattern);
+        design.revision += 1;
+        Ok(DesignUpdate {
+            summary: design.summary(),
+            report,
+        })
+    }
+
+    /// Edit a design's pattern, recording the edit for undo
+    ///
+    /// `f` must leave the pattern untouched when it fails.
+    pub fn edit<R>(
+        &self,
+        id: DesignId,
+        label: &str,
+        f: impl FnOnce(&mut Pattern) -> Result<R, String>,
+    ) -> Result<DesignUpdate<R>, String> {
+        self.update(id, |pattern, history| history.edit(pattern, label, f))
+    }
+
+    /// Make a stitch-level edit to a design, recording it for undo
+    pub fn edit_stitches(
+        &self,
+        id: DesignId,
+        label: &str,
+        f: impl FnOnce(&mut Pattern) -> Result<StitchEdit, EditError>,
+    ) -> Result<DesignUpdate<Vec<EditWarning>>, String> {
+        self.update(id, |pattern, history| {
+            history.patch(pattern, label, |pattern| {
+                f(pattern).map_err(|e| e.to_string())
+            })
+        })
+    }
+
+    /// Apply a transform to a design, recording it for undo
+    pub fn transform(
+        &self,
+        id: DesignId,
+        operation: &TransformOperation,
+        options: &TransformOptions,
+    ) -> Result<DesignUpdate<Vec<TransformWarning>>, String> {
+        self.update(id, |pattern, history| {
+            Ok(history.transform(pattern, operation, options))
+        })
+    }
+
+    /// Revert a design's latest edit
+    pub fn undo(&self, id: DesignId) -> Result<DesignUpdate<HistoryStep>, String> {
+        self.update(id, |pattern, history| Ok(history.undo(pattern)))
+    }
+
+    /// Replay a design's latest undone edit
+    pub fn redo(&self, id: DesignId) -> Result<DesignUpdate<HistoryStep>, String> {
+        self.update(id, |pattern, history| Ok(history.redo(pattern)))
+    }
+
+    /// Cap the memory each design's history may use, open designs included
+    pub fn set_history_limit(&self, limit: usize) {
+        self.history_limit.store(limit, Ordering::Relaxed);
+        for design in self.open.read().unwrap().values() {
+            design.write().unwrap().history.set_limit(limit);
+        }
+    }
+
+    /// Drop a design; returns false if the id was not open
+    pub fn close(&self, id: DesignId) -> bool {
+        self.open.write().unwrap().remove(&id).is_some()
+    }
+
+    /// The revision of every open design, oldest first
+    pub fn revisions(&self) -> Vec<(DesignId, u64)> {
+        let open = self.open.read().unwrap();
+        let mut revisions: Vec<_> = open
+            .iter()
+            .map(|(&id, design)| (id, design.read().unwrap().revision))
+            .collect();
+        revisions.sort_unstable();
+        revisions
+    }
+
+    /// Every open design, oldest first
+    pub fn list(&self) -> Vec<OpenDesignInfo> {
+        let open = self.open.read().unwrap();
+        let mut designs: Vec<OpenDesignInfo> = open
+            .iter()
+            .map(|(&id, design)| {
+                let design = design.read().unwrap();
+                OpenDesignInfo {
+                    id,
+                    path: design.path.clone(),
+                    format: design.format,
+                    stitch_count: design.pattern.stitches.len(),
+                }
+            })
+            .collect();
+        designs.sort_by_key(|d| d.id);
+        designs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::StitchCommand;
+
+    fn open(stitches: usize) -> OpenDesign {
+        let mut pattern = Pattern::new();
+        for i in 0..stitches {
+            pattern.add_stitch(i as f64, 0.0, StitchCommand::Stitch);
+        }
+        OpenDesign::new(None, LoadedDesign::new(DesignFormat::Dst, pattern))
+    }
+
+    #[test]
+    fn test_insert_list_close() {
+        let designs = Designs::default();
+        let first = designs.insert(open(3));
+        let second = designs.insert(open(5));
+        assert_ne!(first, second);
+
+        let counts: Vec<_> = designs
+            .list()
+            .iter()
+            .map(|d| (d.id, d.stitch_count))
+            .collect();
+        assert_eq!(counts, vec![(first, 3), (second, 5)]);
+
+        assert!(designs.close(first));
+        assert!(!designs.close(first));
+        assert!(designs.with(first, |_| ()).is_err());
+        assert_eq!(designs.with(second, |d| d.pattern.stitches.len()), Ok(5));
+    }
+
+    #[test]
+    fn test_edits_rebuild_index() {
+        let designs = Designs::default();
+        let id = designs.insert(open(2));
+
+        let update = designs
+            .edit(id, "Move", |pattern| {
+                pattern.translate(100.0, 0.0);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(update.summary.bounds.unwrap().min_x, 100.0);
+        let hit = designs
+            .with(id, |d| d.index.nearest(101.0, 0.0, 0.5))
+            .unwrap();
+        assert_eq!(hit.map(|h| h.index), Some(1));
+        assert_eq!(designs.revisions(), vec![(id, 1)]);
+    }
+
+    #[test]
+    fn test_concurrent_edits_on_one_design() {
+        let designs = Arc::new(Designs::default());
+        let id = designs.insert(open(1));
+
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                let designs = Arc::clone(&designs);
+                std::thread::spawn(move || {
+                    for _ in 0..50 {
+                        designs
+                            .edit(id, "Add stitch", |pattern| {
+                                pattern.add_stitch(0.0, 0.0, StitchCommand::Stitch);
+                                Ok(())
+                            })
+                            .unwrap();
+                        designs.with(id, |d| d.pattern.stitches.len()).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        assert_eq!(designs.with(id, |d| d.pattern.stitches.len()), Ok(401));
+    }
+
+    #[test]
+    fn test_undo_rebuilds_index() {
+        let designs = Designs::default();
+        let id = designs.insert(open(2));
+        let operation = TransformOperation::Translate { dx: 100.0, dy: 0.0 };
+        designs
+            .transform(id, &operation, &TransformOptions::default())
+            .unwrap();
+
+        let update = designs.undo(id).unwrap();
+        assert_eq!(update.report.label.as_deref(), Some("Move"));
+        assert_eq!(update.report.redo_depth, 1);
+        let hit = designs
+            .with(id, |d| d.index.nearest(1.0, 0.0, 0.5))
+            .unwrap();
+        assert_eq!(hit.map(|h| h.index), Some(1));
+
+        designs.redo(id).unwrap();
+        assert_eq!(designs.with(id, |d| d.pattern.stitches[0].x), Ok(100.0));
+    }
+}
@@ -0,0 +1,303 @@
+// settings.rs - User preferences saved as versioned JSON in the app config folder
+
+use crate::analysis::RiskThresholds;
+use crate::dst::{ParseOptions, TimeEstimator};
+use crate::hoops::find_hoop;
+use crate::threads::ThreadBrand;
+use crate::units::Unit;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// Version written by this build; older files gain the fields added since
+/// with their defaults
+pub const SETTINGS_VERSION: u32 = 1;
+
+/// Name of the settings file in the app config folder
+const SETTINGS_FILE: &str = "settings.json";
+
+/// Where the preferred unit was kept before it became a setting
+const LEGACY_UNITS_FILE: &str = "units.json";
+
+/// Error type for reading and saving settings
+#[derive(Debug, thiserror::Error)]
+pub enum SettingsError {
+    #[error("Failed to save settings: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid settings file: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid setting {field}: {reason}")]
+    Invalid { field: &'static str, reason: String },
+}
+
+/// Everything the user can set once rather than per command
+///
+/// Fields missing from a saved file take their defaults, so files written
+/// before a field was added still load.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u32,
+    /// Top speed run time estimates assume, in stitches per minute
+    pub machine_speed_spm: f64,
+    /// Catalog hoop id designs are checked against when none is given
+    pub hoop: Option<String>,
+    /// Unit lengths are shown in
+    pub unit: Unit,
+    /// Refuse damaged files rather than read what can be read
+    pub strict_parsing: bool,
+    /// Runs of at least this many consecutive jumps are read as a trim;
+    /// None disables
+    pub trim_jump_threshold: Option<usize>,
+    /// Seconds between autosaves; 0 turns autosave off
+    pub autosave_interval_secs: u64,
+    /// Thread line palettes and matches use when none is given
+    pub thread_brand: ThreadBrand,
+    /// Distance an arrow key nudges the selection, in mm
+    pub nudge_step_mm: f64,
+    /// Distance a shifted arrow key nudges the selection, in mm
+    pub nudge_large_step_mm: f64,
+    /// Spacing of the grid selections snap to, in mm
+    pub grid_mm: f64,
+    /// When the thread-break report flags a place
+    pub risk_thresholds: RiskThresholds,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let parse = ParseOptions::default();
+        Self {
+            version: SETTINGS_VERSION,
+            machine_speed_spm: TimeEstimator::default().speed_spm,
+            hoop: None,
+            unit: Unit::default(),
+            strict_parsing: parse.strict,
+            trim_jump_threshold: parse.trim_jump_threshold,
+            autosave_interval_secs: 60,
+            thread_brand: ThreadBrand::MadeiraClassic40,
+            nudge_step_mm: 0.1,
+            nudge_large_step_mm: 1.0,
+            grid_mm: 1.0,
+            risk_thresholds: RiskThresholds::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Read saved settings, bringing those of older versions up to date
+    pub fn from_bytes(data: &[u8]) -> Result<Self, SettingsError> {
+        let mut settings: Settings = serde_json::from_slice(data)?;
+        settings.version = settings.version.max(SETTINGS_VERSION);
+        Ok(settings)
+    }
+
+    /// Check every value before the settings are saved or used
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        if !(self.machine_speed_spm.is_finite() && self.machine_speed_spm > 0.0) {
+            return Err(SettingsError::Invalid {
+                field: "machine_speed_spm",
+                reason: "must be positive".to_string(),
+            });
+        }
+        if let Some(hoop) = &self.hoop {
+            if find_hoop(hoop).is_none() {
+                return Err(SettingsError::Invalid {
+                    field: "hoop",
+                    reason: format!("unknown hoop {}", hoop),
+                });
+            }
+        }
+        for (field, value) in [
+            ("nudge_step_mm", self.nudge_step_mm),
+            ("nudge_large_step_mm", self.nudge_large_step_mm),
+            ("grid_mm", self.grid_mm),
+        ] {
+            if !(value.is_finite() && value > 0.0) {
+                return Err(SettingsError::Invalid {
+                    field,
+                    reason: "must be positive".to_string(),
+                });
+            }
+        }
+        if self.trim_jump_threshold == Some(0) {
+            return Err(SettingsError::Invalid {
+                field: "trim_jump_threshold",
+                reason: "must be at least 1".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Parser options for loads that do not pass their own
+    pub fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            strict: self.strict_parsing,
+            trim_jump_threshold: self.trim_jump_threshold,
+            ..ParseOptions::default()
+        }
+    }
+
+    /// Machine figures for estimates that do not pass their own
+    pub fn time_estimator(&self) -> TimeEstimator {
+        TimeEstimator {
+            speed_spm: self.machine_speed_spm,
+            ..TimeEstimator::default()
+        }
+    }
+}
+
+/// The saved settings, shared by every command
+#[derive(Debug)]
+pub struct SettingsStore {
+    file: PathBuf,
+    settings: RwLock<Settings>,
+}
+
+impl SettingsStore {
+    /// Read the settings saved in `dir`
+    ///
+    /// A missing or unreadable file gives the defaults, keeping a unit
+    /// chosen before settings existed.
+    pub fn open(dir: &Path) -> Self {
+        let file = dir.join(SETTINGS_FILE);
+        let settings = match fs::read(&file) {
+            Ok(data) => Settings::from_bytes(&data)
+                .ok()
+                .filter(|s| s.validate().is_ok())
+                .unwrap_or_default(),
+            Err(_) => Settings {
+                unit: fs::read(dir.join(LEGACY_UNITS_FILE))
+                    .ok()
+                    .and_then(|data| serde_json::from_slice(&data).ok())
+                    .unwrap_or_default(),
+                ..Settings::default()
+            },
+        };
+        Self {
+            file,
+            settings: RwLock::new(settings),
+        }
+    }
+
+    pub fn get(&self) -> Settings {
+        self.settings.read().unwrap().clone()
+    }
+
+    /// Validate and save new settings, returning them as stored
+    pub fn set(&self, settings: Settings) -> Result<Settings, SettingsError> {
+        settings.validate()?;
+        let settings = Settings {
+            version: SETTINGS_VERSION,
+            ..settings
+        };
+        let mut current = self.settings.write().unwrap();
+        if let Some(dir) = self.file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Through a temporary file, so a crash never leaves half of it
+        let temp = self.file.with_extension("tmp");
+        fs::write(&temp, serde_json::to_vec_pretty(&settings)?)?;
+        fs::rename(&temp, &self.file)?;
+        *current = settings.clone();
+        Ok(settings)
+    }
+
+    /// Change some settings, keeping the rest
+    pub fn update(&self, change: impl FnOnce(&mut Settings)) -> Result<Settings, SettingsError> {
+        let mut settings = self.get();
+        change(&mut settings);
+        self.set(settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("embrocad-settings-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_migrates_file_missing_new_fields() {
+        // Written before versioning, with only some of today's fields
+        let old = br#"{"machine_speed_spm": 650.0, "hoop": "brother_5x7", "retired": true}"#;
+        let settings = Settings::from_bytes(old).unwrap();
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert_eq!(settings.machine_speed_spm, 650.0);
+        assert_eq!(settings.hoop.as_deref(), Some("brother_5x7"));
+        assert_eq!(settings.unit, Unit::Mm);
+        assert_eq!(settings.thread_brand, ThreadBrand::MadeiraClassic40);
+        assert_eq!(settings.autosave_interval_secs, 60);
+        assert_eq!(settings.nudge_step_mm, 0.1);
+        assert_eq!(settings.grid_mm, 1.0);
+        assert_eq!(settings.risk_thresholds, RiskThresholds::default());
+        let parse = settings.parse_options();
+        assert!(!parse.strict);
+        assert_eq!(
+            parse.trim_jump_threshold,
+            ParseOptions::default().trim_jump_threshold
+        );
+        assert_eq!(settings.time_estimator().speed_spm, 650.0);
+
+        let dir = scratch("migrate");
+        fs::write(dir.join(SETTINGS_FILE), old).unwrap();
+        let store = SettingsStore::open(&dir);
+        assert_eq!(store.get(), settings);
+        store.update(|s| s.strict_parsing = true).unwrap();
+        let saved: serde_json::Value =
+            serde_json::from_slice(&fs::read(dir.join(SETTINGS_FILE)).unwrap()).unwrap();
+        assert_eq!(saved["version"], SETTINGS_VERSION);
+        assert_eq!(saved["thread_brand"], "madeira_classic_40");
+        assert!(SettingsStore::open(&dir).get().parse_options().strict);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_keeps_unit_saved_before_settings() {
+        let dir = scratch("units");
+        fs::write(dir.join(LEGACY_UNITS_FILE), br#""inch""#).unwrap();
+        assert_eq!(SettingsStore::open(&dir).get().unit, Unit::Inch);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_rejects_invalid_values() {
+        let dir = scratch("invalid");
+        let store = SettingsStore::open(&dir);
+        let invalid = [
+            Settings {
+                machine_speed_spm: 0.0,
+                ..Settings::default()
+            },
+            Settings {
+                hoop: Some("shoebox".to_string()),
+                ..Settings::default()
+            },
+            Settings {
+                trim_jump_threshold: Some(0),
+                ..Settings::default()
+            },
+            Settings {
+                grid_mm: -1.0,
+                ..Settings::default()
+            },
+        ];
+        for settings in invalid {
+            assert!(matches!(
+                store.set(settings),
+                Err(SettingsError::Invalid { .. })
+            ));
+        }
+        assert_eq!(store.get(), Settings::default());
+        assert!(!dir.join(SETTINGS_FILE).exists());
+        // Values of the wrong type never get past deserializing
+        assert!(Settings::from_bytes(br#"{"unit": "furlong"}"#).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
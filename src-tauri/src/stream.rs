@@ -0,0 +1,195 @@
+// stream.rs - Design summaries and binary stitch chunks for streamed loading
+
+use crate::dst::{
+    Bounds, ColorBlock, ParseWarning, Pattern, PatternInfo, PatternMetadata, PatternStatistics,
+    Stitch,
+};
+use crate::format::DesignFormat;
+use serde::Serialize;
+
+/// Stitches per chunk when the frontend does not ask for a size
+pub const DEFAULT_CHUNK_SIZE: usize = 10_000;
+
+/// Bytes before the coordinates: start index and stitch count as u32 LE
+pub const CHUNK_HEADER_SIZE: usize = 8;
+
+/// Everything about a loaded design except its stitches
+#[derive(Debug, Clone, Serialize)]
+pub struct DesignSummary {
+    pub format: DesignFormat,
+    pub metadata: PatternMetadata,
+    pub bounds: Option<Bounds>,
+    pub statistics: PatternStatistics,
+    pub color_changes: u32,
+    pub color_blocks: Vec<ColorBlock>,
+    pub warnings: Vec<ParseWarning>,
+    /// Length of the stitch list, e.g. the total that streamed chunks add up to
+    pub stitch_count: usize,
+}
+
+impl DesignSummary {
+    pub fn new(format: DesignFormat, pattern: &Pattern) -> Self {
+        Self {
+            format,
+            metadata: pattern.metadata.clone(),
+            bounds: pattern.bounds.clone(),
+            statistics: pattern.statistics.clone(),
+            color_changes: pattern.color_changes,
+            color_blocks: pattern.color_blocks.clone(),
+            warnings: pattern.warnings.clone(),
+            stitch_count: pattern.stitches.len(),
+        }
+    }
+
+    /// Summary of a design read without its stitches
+    pub fn from_info(format: DesignFormat, info: PatternInfo) -> Self {
+        Self {
+            format,
+            metadata: info.metadata,
+            bounds: info.bounds,
+            statistics: info.statistics,
+            color_changes: info.color_changes,
+            color_blocks: info.color_blocks,
+            warnings: info.warnings,
+            stitch_count: info.stitch_count,
+        }
+    }
+}
+
+/// Encode a run of stitches beginning at index `start` as one binary chunk
+///
+/// Layout, little-endian: `start: u32`, `count: u32`, then `count` (x, y)
+/// pairs as f32 in 0.1mm, then `count` command bytes (`StitchCommand::code`).
+/// The coordinates begin at byte 8, so the frontend can view them as
+/// `new Float32Array(buffer, 8, count * 2)` and the commands as
+/// `new Uint8Array(buffer, 8 + count * 8, count)` without copying.
+pub fn encode_chunk(start: usize, stitches: &[Stitch]) -> Vec<u8> {
+    let count = stitches.len();
+    let mut chunk = Vec::with_capacity(CHUNK_HEADER_SIZE + count * 9);
+    chunk.extend_from_slice(&(start as u32).to_le_bytes());
+    chunk.extend_from_slice(&(count as u32).to_le_bytes());
+    for stitch in stitches {
+        chunk.extend_from_slice(&(stitch.x as f32).to_le_bytes());
+        chunk.extend_from_slice(&(stitch.y as f32).to_le_bytes());
+    }
+    chunk.extend(stitches.iter().map(|s| s.command.code()));
+    chunk
+}
+
+/// Split `stitches` into encoded chunks of at most `chunk_size` stitches
+pub fn stitch_chunks(stitches: &[Stitch], chunk_size: usize) -> impl Iterator<Item = Vec<u8>> + '_ {
+    let chunk_size = chunk_size.max(1);
+    stitches
+        .chunks(chunk_size)
+        .enumerate()
+        .map(move |(index, chunk)| encode_chunk(index * chunk_size, chunk))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dst::StitchCommand;
+    use crate::format::LoadedDesign;
+
+    /// Decode a chunk back into (start, stitches)
+    fn decode_chunk(chunk: &[u8]) -> (usize, Vec<Stitch>) {
+        let word = |at: usize| u32::from_le_bytes(chunk[at..at + 4].try_into().unwrap());
+        let float = |at: usize| f32::from_le_bytes(chunk[at..at + 4].try_into().unwrap());
+        let (start, count) = (word(0) as usize, word(4) as usize);
+        let commands = CHUNK_HEADER_SIZE + count * 8;
+
+        let stitches = (0..count)
+            .map(|i| {
+                let at = CHUNK_HEADER_SIZE + i * 8;
+                Stitch::new(
+                    float(at) as f64,
+                    float(at + 4) as f64,
+                    StitchCommand::from_code(chunk[commands + i]).unwrap(),
+                )
+            })
+            .collect();
+        (start, stitches)
+    }
+
+    /// A zigzag of `count` stitches, with a color change every 1000
+    fn large_pattern(count: usize) -> Pattern {
+        let mut pattern = Pattern::new();
+        for i in 0..count {
+            let command = if i > 0 && i % 1000 == 0 {
+                StitchCommand::ColorChange
+            } else {
+                StitchCommand::Stitch
+            };
+            pattern.add_stitch((i % 500) as f64, (i % 7) as f64 * 3.0, command);
+        }
+        pattern.add_stitch(0.0, 0.0, StitchCommand::End);
+        pattern.calculate_bounds();
+        pattern.calculate_statistics();
+        pattern.calculate_color_blocks();
+        pattern
+    }
+
+    #[test]
+    fn test_chunks_round_trip() {
+        let pattern = large_pattern(25_001);
+        let chunks: Vec<_> = stitch_chunks(&pattern.stitches, DEFAULT_CHUNK_SIZE).collect();
+        assert_eq!(chunks.len(), 3);
+
+        let mut decoded = Vec::new();
+        for chunk in &chunks {
+            let (start, stitches) = decode_chunk(chunk);
+            assert_eq!(start, decoded.len());
+            decoded.extend(stitches);
+        }
+        assert_eq!(decoded, pattern.stitches);
+        assert_eq!(chunks[2].len(), CHUNK_HEADER_SIZE + 5_002 * 9);
+    }
+
+    #[test]
+    fn test_summary_leaves_out_stitches() {
+        let json =
+            serde_json::to_value(DesignSummary::new(DesignFormat::Dst, &large_pattern(2_500)))
+                .unwrap();
+
+        assert_eq!(json["stitch_count"], 2_501);
+        assert_eq!(json["color_blocks"].as_array().unwrap().len(), 3);
+        assert!(json.get("stitches").is_none());
+    }
+
+    /// Compares the one-shot JSON response with streamed chunks on 500k stitches
+    ///
+    /// Run with `cargo test --release bench_ -- --ignored --nocapture`. The
+    /// chunks take 9 bytes per stitch against roughly 45 for JSON, and the
+    /// first chunk is ready after 10k stitches instead of after the whole
+    /// design has been serialized.
+    #[test]
+    #[ignore]
+    fn bench_streamed_transfer() {
+        use std::time::Instant;
+
+        let design = LoadedDesign::new(DesignFormat::Dst, large_pattern(500_000));
+
+        let start = Instant::now();
+        let json = serde_json::to_vec(&design).unwrap();
+        let json_time = start.elapsed();
+
+        let start = Instant::now();
+        let summary =
+            serde_json::to_vec(&DesignSummary::new(design.format, &design.pattern)).unwrap();
+        let mut chunks = stitch_chunks(&design.pattern.stitches, DEFAULT_CHUNK_SIZE);
+        let first = chunks.next().unwrap();
+        let first_chunk_time = start.elapsed();
+        let streamed = summary.len() + first.len() + chunks.map(|c| c.len()).sum::<usize>();
+        let streamed_time = start.elapsed();
+
+        println!(
+            "json: {} bytes in {:?}; streamed: {} bytes in {:?}, first chunk after {:?}",
+            json.len(),
+            json_time,
+            streamed,
+            streamed_time,
+            first_chunk_time
+        );
+        assert!(streamed * 3 < json.len());
+    }
+}
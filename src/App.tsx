@@ -1,7 +1,7 @@
 // App.tsx - Main application component with embroidery viewer, tabs, and canvas rendering
 
 import React, { useState, useRef, useCallback, useEffect } from "react";
-import { invoke } from "@tauri-apps/api/core";
+import { Channel, invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import { getCurrentWindow } from "@tauri-apps/api/window";
 import { open } from "@tauri-apps/plugin-dialog";
@@ -18,6 +18,7 @@ import {
   FileImage,
   FolderOpen,
   Columns2,
+  AlertTriangle,
 } from "lucide-react";
 import { Tooltip, TooltipContent, TooltipTrigger } from "./components/ui/tooltip";
 import { Empty } from "./components/ui/empty";
@@ -70,7 +71,20 @@ interface PatternStatistics {
   estimated_time_minutes: number;
 }
 
+interface ParseWarning {
+  kind: string;
+  header?: number;
+  decoded?: number;
+  offset?: number;
+  length?: number;
+  code?: number;
+  limit?: number;
+  key?: string;
+}
+
 interface Pattern {
+  format: string;
+  warnings: ParseWarning[];
   stitches: Stitch[];
   bounds: Bounds | null;
   statistics: PatternStatistics;
@@ -86,10 +100,38 @@ interface Tab {
   id: string;
   name: string;
   filePath: string | null;
+  designId?: number;
   pattern: Pattern | null;
+  warningsDismissed?: boolean;
 }
 
-const SUPPORTED_FORMATS = [".dst"];
+const SUPPORTED_FORMATS = [".dst", ".pes", ".pec", ".exp", ".jef", ".vp3", ".xxx", ".hus", ".vip", ".sew", ".pcs", ".dsb", ".dsz", ".t01", ".t03", ".t09", ".10o", ".ksm", ".csv", ".svg"];
+
+// Human-readable text for a parse warning, matching the backend messages
+const describeWarning = (warning: ParseWarning): string => {
+  switch (warning.kind) {
+    case "stitch_count_mismatch":
+      return `Header declares ${warning.header} stitches but ${warning.decoded} were decoded`;
+    case "color_count_mismatch":
+      return `Header declares ${warning.header} color changes but ${warning.decoded} were decoded`;
+    case "truncated_record":
+      return `Stitch data ends with a partial ${warning.length}-byte record at offset ${warning.offset}`;
+    case "missing_end":
+      return `Stitch data ends at offset ${warning.offset} without an End command; one was added`;
+    case "data_after_end":
+      return `${warning.length} bytes of stitch data follow the End command at offset ${warning.offset}`;
+    case "unknown_control":
+      return `Unknown control code 0x${(warning.code ?? 0).toString(16).toUpperCase().padStart(2, "0")} at offset ${warning.offset}`;
+    case "stitch_limit_reached":
+      return `Stopped decoding after ${warning.limit} stitches`;
+    case "coordinate_out_of_range":
+      return `Stopped decoding at offset ${warning.offset}: coordinates left the physical range`;
+    case "invalid_header_record":
+      return `Header record ${warning.key} at offset ${warning.offset} could not be read`;
+    default:
+      return warning.kind;
+  }
+};
 
 // Helper for formatting numbers with commas
 const NumberDisplay = ({ value }: { value: number }) => {
@@ -220,6 +262,10 @@ function App() {
       if (tabs.length === 1) {
         return;
       }
+      const closing = tabs.find((t) => t.id === tabId);
+      if (closing?.designId !== undefined) {
+        void invoke("close_design", { id: closing.designId });
+      }
       const newTabs = tabs.filter((t) => t.id !== tabId);
       setTabs(newTabs);
       if (activeTabId === tabId) {
@@ -381,11 +427,19 @@ function App() {
       const fileName = filePath.split(/[\\/]/).pop() ?? "Untitled";
 
       try {
-        const pattern = await invoke<Pattern>("load_design", { path: filePath });
+        const { id: designId } = await invoke<{ id: number }>("load_design", {
+          path: filePath,
+          onProgress: new Channel(),
+        });
+        const pattern = await invoke<Pattern>("get_design", { id: designId });
 
+        const replaced = tabs.find((t) => t.id === activeTabId)?.designId;
+        if (replaced !== undefined) {
+          void invoke("close_design", { id: replaced });
+        }
         setTabs((prev) =>
           prev.map((t) =>
-            t.id === activeTabId ? { ...t, name: fileName, filePath: filePath, pattern } : t
+            t.id === activeTabId ? { ...t, name: fileName, filePath: filePath, designId, pattern } : t
           )
         );
       } catch (err) {
@@ -413,8 +467,12 @@ function App() {
       const fileName = filePath.split(/[\\/]/).pop() ?? "Untitled";
 
       try {
-        const pattern = await invoke<Pattern>("load_design", { path: filePath });
-        const newTab = { id: newId, name: fileName, filePath: filePath, pattern };
+        const { id: designId } = await invoke<{ id: number }>("load_design", {
+          path: filePath,
+          onProgress: new Channel(),
+        });
+        const pattern = await invoke<Pattern>("get_design", { id: designId });
+        const newTab = { id: newId, name: fileName, filePath: filePath, designId, pattern };
 
         setTabs((prev) => {
           if (insertIndex !== undefined && insertIndex >= 0 && insertIndex <= prev.length) {
@@ -440,7 +498,7 @@ function App() {
       filters: [
         {
           name: "Embroidery Files",
-          extensions: ["dst", "pes", "exp", "jef", "vp3"],
+          extensions: ["dst", "pes", "pec", "exp", "jef", "vp3", "xxx", "hus", "vip", "sew", "pcs", "dsb", "dsz", "t01", "t03", "t09", "10o", "ksm", "csv", "svg"],
         },
       ],
     });
@@ -712,9 +770,34 @@ function App() {
         </ContextMenuContent>
       </ContextMenu>
 
+      {/* Warning Banner - Non-blocking notice of irregularities found while parsing */}
+      {activeTab?.pattern && activeTab.pattern.warnings.length > 0 && !activeTab.warningsDismissed && (
+        <div className="warning-banner">
+          <AlertTriangle size={14} />
+          <span className="warning-text">
+            {activeTab.pattern.warnings.map(describeWarning).join(" · ")}
+          </span>
+          <button
+            className="warning-dismiss"
+            onClick={() =>
+              setTabs((prev) =>
+                prev.map((t) => (t.id === activeTab.id ? { ...t, warningsDismissed: true } : t))
+              )
+            }
+            title="Dismiss"
+          >
+            <X size={14} />
+          </button>
+        </div>
+      )}
+
       {/* Status Bar - Only shown when a design is loaded */}
       {activeTab?.pattern && (
         <footer className="status-bar">
+          <div className="status-item">
+            <span className="status-label">Format:</span>
+            <span className="status-value">{activeTab.pattern.format.toUpperCase()}</span>
+          </div>
           <div className="status-item">
             <span className="status-label">Stitches:</span>
             <span className="status-value">